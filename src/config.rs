@@ -21,9 +21,18 @@ pub struct Config {
     /// Security configuration
     pub security: SecurityConfig,
     
+    /// Message and room activity configuration
+    pub messages: MessagesConfig,
+    
+    /// Page sizes for endpoints that take a `limit`
+    pub pagination: PaginationConfig,
+    
     /// Push notification configuration
     pub push: PushConfig,
     
+    /// Email configuration
+    pub mail: MailConfig,
+    
     /// Metrics configuration
     pub metrics: MetricsConfig,
     
@@ -32,6 +41,12 @@ pub struct Config {
     
     /// Cache configuration
     pub cache: CacheConfig,
+    
+    /// File storage configuration
+    pub storage: StorageConfig,
+    
+    /// Initial admin to provision on an empty database (None = use /setup)
+    pub admin_seed: Option<AdminSeedConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,16 +57,116 @@ pub struct ServerConfig {
     /// Request timeout in seconds
     pub request_timeout_secs: u64,
     
+    /// Route groups with their own timeout, e.g. slow searches and exports;
+    /// the longest matching path prefix wins
+    pub request_timeout_overrides: Vec<RouteTimeout>,
+    
     /// Maximum request body size in bytes
     pub max_request_size: usize,
     
     /// Graceful shutdown timeout in seconds
     pub shutdown_timeout_secs: u64,
     
+    /// Seconds shutdown waits for queued database writes to be applied
+    /// before closing the pool; writes still queued after that are lost
+    pub shutdown_write_drain_secs: u64,
+    
+    /// Seconds WebSocket clients are told to wait before reconnecting after
+    /// a shutdown
+    pub ws_reconnect_after_secs: u64,
+    
+    /// Frames queued per WebSocket connection before the overflow policy applies
+    pub ws_send_buffer_size: usize,
+    
+    /// What to do when a WebSocket client falls a full buffer behind
+    pub ws_send_buffer_overflow: SendBufferOverflow,
+    
+    /// Users announced typing one by one per room; beyond this clients get
+    /// a single summary (0 = no limit)
+    pub ws_typing_broadcast_limit: usize,
+    
+    /// Seconds without activity before a connected user shows as offline
+    pub ws_presence_timeout_secs: u64,
+    
+    /// Most rooms described in the Welcome frame on connect, most recently
+    /// active first (0 = don't send it)
+    pub ws_welcome_max_rooms: usize,
+    
+    /// Numbered sends on one connection that may wait for an earlier
+    /// sequence number to arrive before the connection is closed
+    pub ws_max_sequence_ahead: usize,
+    
+    /// Shortest gap in seconds between last-seen writes for a user who
+    /// stays online; going offline is always recorded
+    pub last_seen_write_interval_secs: u64,
+    
     /// Number of worker threads (0 = auto)
     pub worker_threads: usize,
 }
 
+/// A timeout budget for every path under `path_prefix`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteTimeout {
+    pub path_prefix: String,
+    pub timeout_secs: u64,
+}
+
+impl std::str::FromStr for RouteTimeout {
+    type Err = anyhow::Error;
+    
+    /// Parses `/api/search=60`
+    fn from_str(s: &str) -> Result<Self> {
+        let (path_prefix, secs) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("expected PATH=SECONDS, got {:?}", s))?;
+        Ok(RouteTimeout {
+            path_prefix: path_prefix.trim().to_string(),
+            timeout_secs: secs.trim().parse().with_context(|| format!("invalid seconds in {:?}", s))?,
+        })
+    }
+}
+
+/// A problem found by [`Config::validate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigDiagnostic {
+    /// The server can't run correctly as configured
+    Error(String),
+    /// Allowed, but probably a mistake
+    Warning(String),
+}
+
+impl ConfigDiagnostic {
+    pub fn is_error(&self) -> bool {
+        matches!(self, ConfigDiagnostic::Error(_))
+    }
+}
+
+impl std::fmt::Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigDiagnostic::Error(message) | ConfigDiagnostic::Warning(message) => f.write_str(message),
+        }
+    }
+}
+
+/// What happens to a message when the moderation hook fails or times out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModerationFailurePolicy {
+    /// Post it unchanged
+    FailOpen,
+    /// Reject it; the sender can retry
+    FailClosed,
+}
+
+/// What happens when a WebSocket client can't keep up with its frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SendBufferOverflow {
+    /// Discard the oldest queued frame to make room
+    DropOldest,
+    /// Close the connection; the client reconnects and catches up
+    Disconnect,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatabaseConfig {
     /// Database file path
@@ -68,6 +183,31 @@ pub struct DatabaseConfig {
     
     /// Database backup directory
     pub backup_dir: Option<PathBuf>,
+    
+    /// Delete messages older than this many days (0 = keep forever).
+    /// Message deduplication by `client_message_id` holds for this window.
+    /// Site admins can override it per room.
+    pub message_retention_days: u64,
+    
+    /// Seconds between retention purges
+    pub retention_purge_interval_secs: u64,
+    
+    /// Compare the search index with messages at startup and warn on drift
+    pub check_search_index_on_startup: bool,
+    
+    /// Writes queued for the database writer at which write requests are
+    /// answered 503 while reads keep being served (0 = never shed)
+    pub write_shed_queue_depth: usize,
+    
+    /// Read-only database that search, message history and room lists are
+    /// served from (None = the primary serves everything)
+    pub replica_url: Option<String>,
+    
+    /// Connection pool size for the replica
+    pub replica_max_connections: u32,
+    
+    /// Seconds after a user writes during which their reads skip the replica
+    pub replica_read_your_writes_secs: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -104,6 +244,9 @@ pub struct LoggingConfig {
     
     /// Log rotation settings
     pub rotation: LogRotationConfig,
+    
+    /// Request log sampling for high-volume routes
+    pub sampling: LogSamplingConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -128,6 +271,21 @@ pub struct LogRotationConfig {
     pub check_interval_secs: u64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogSamplingConfig {
+    /// Fraction of successful high-volume requests to log (0.0 - 1.0)
+    pub sample_rate: f64,
+    
+    /// Requests slower than this are always logged, regardless of sampling
+    pub always_log_latency_ms: u64,
+    
+    /// Path prefixes considered high-volume (health checks, metrics, static assets)
+    pub high_volume_prefixes: Vec<String>,
+    
+    /// Log every 4xx response with its error code; 5xx always are
+    pub log_client_errors: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecurityConfig {
     /// CORS allowed origins (empty = allow all)
@@ -136,17 +294,291 @@ pub struct SecurityConfig {
     /// Rate limiting: requests per minute
     pub rate_limit_rpm: u32,
     
+    /// Send `X-RateLimit-*` headers on rate-limited responses
+    pub rate_limit_headers: bool,
+    
     /// Session token length in bytes
     pub session_token_length: usize,
     
     /// Session expiry in hours
     pub session_expiry_hours: u64,
     
+    /// Sign out sessions unused for this many minutes (0 = never)
+    pub session_idle_timeout_mins: u64,
+    
+    /// Lifetime of sessions admins open to impersonate a user
+    pub impersonation_session_mins: u64,
+    
     /// Enable HTTPS redirect
     pub force_https: bool,
     
     /// Trusted proxy headers
     pub trust_proxy: bool,
+    
+    /// Proxy addresses (CIDRs or bare IPs) whose forwarding headers are honored
+    pub trusted_proxies: Vec<String>,
+    
+    /// Accept WebSocket upgrades without an Origin header (native clients)
+    pub ws_allow_missing_origin: bool,
+    
+    /// Messages a single user may create per minute across all rooms (0 = unlimited)
+    pub message_rate_per_minute: u32,
+    
+    /// Exempt bot accounts from the per-user message rate
+    pub message_rate_exempt_bots: bool,
+    
+    /// Exempt admins from the per-user message rate
+    pub message_rate_exempt_admins: bool,
+    
+    /// Open rooms a single user may join per minute (0 = unlimited); site
+    /// admins adding people are exempt
+    pub room_join_rate_per_minute: u32,
+    
+    /// Bots the instance may have in total (0 = unlimited)
+    pub max_bots: u32,
+    
+    /// First-run admin creation attempts one client may make per minute
+    /// (0 = unlimited)
+    pub setup_attempts_per_minute: u32,
+    
+    /// API requests a user may make per day (0 = unlimited)
+    pub daily_request_quota: u32,
+    
+    /// API requests a bot may make per day (0 = unlimited)
+    pub bot_daily_request_quota: u32,
+    
+    /// IANA timezone whose midnight resets daily quotas
+    pub quota_timezone: String,
+    
+    /// Deepest nesting of objects and arrays accepted in a JSON body (0 = unlimited)
+    pub json_max_depth: usize,
+    
+    /// Most object fields accepted in a JSON body, counted across all
+    /// levels (0 = unlimited)
+    pub json_max_fields: usize,
+    
+    /// Rules for passwords set at setup and account creation
+    pub password_policy: PasswordPolicy,
+    
+    /// How long each kind of single-use token stays valid
+    pub token_lifetimes: TokenLifetimes,
+    
+    /// How often expired and used tokens are purged
+    pub token_cleanup_interval_secs: u64,
+    
+    /// Only send bot and room webhooks over https
+    pub webhook_require_https: bool,
+    
+    /// Let webhooks reach private, loopback and link-local addresses
+    pub webhook_allow_private_networks: bool,
+    
+    /// Hosts (domains, CIDRs or IPs) webhooks may reach; empty = any public host
+    pub webhook_allowed_hosts: Vec<String>,
+    
+    /// Hosts webhooks may never reach, even if allowed above
+    pub webhook_denied_hosts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordPolicy {
+    /// Minimum length in characters
+    pub min_length: usize,
+    
+    /// Require both upper- and lowercase letters
+    pub require_mixed_case: bool,
+    
+    /// Require at least one digit
+    pub require_digit: bool,
+    
+    /// Require at least one character that isn't a letter or digit
+    pub require_symbol: bool,
+    
+    /// Reject passwords from the built-in list of common passwords
+    pub block_common: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            require_mixed_case: false,
+            require_digit: false,
+            require_symbol: false,
+            block_common: true,
+        }
+    }
+}
+
+/// Default lifetime of each kind of single-use token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenLifetimes {
+    /// Password reset links, in minutes
+    pub password_reset_mins: u64,
+    
+    /// Email verification links, in hours
+    pub email_verification_hours: u64,
+    
+    /// Room invite links, in hours
+    pub room_invite_hours: u64,
+}
+
+impl Default for TokenLifetimes {
+    fn default() -> Self {
+        Self {
+            password_reset_mins: 60,
+            email_verification_hours: 48,
+            room_invite_hours: 168,
+        }
+    }
+}
+
+impl TokenLifetimes {
+    pub fn lifetime(&self, kind: crate::models::TokenKind) -> chrono::Duration {
+        use crate::models::TokenKind;
+        match kind {
+            TokenKind::PasswordReset => chrono::Duration::minutes(self.password_reset_mins as i64),
+            TokenKind::EmailVerification => chrono::Duration::hours(self.email_verification_hours as i64),
+            TokenKind::RoomInvite => chrono::Duration::hours(self.room_invite_hours as i64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessagesConfig {
+    /// Rooms with more members than this don't answer seen-by queries
+    pub seen_by_max_members: u32,
+    
+    /// Messages each user may have saved (0 = unlimited)
+    pub max_saved_messages: u32,
+    
+    /// Canned responses each room may have (0 = unlimited)
+    pub max_canned_responses: u32,
+    
+    /// Archive rooms with no messages for this many days (0 = never).
+    /// Direct rooms are never archived.
+    pub room_auto_archive_days: u64,
+    
+    /// Seconds between sweeps for rooms to auto-archive
+    pub room_auto_archive_interval_secs: u64,
+    
+    /// Characters of the last message previewed in the room list (0 = no previews)
+    pub room_preview_length: usize,
+    
+    /// Candidates returned by the `@` autocomplete endpoint
+    pub mention_autocomplete_limit: u32,
+    
+    /// Offer `@room` and `@here` in autocomplete (never in direct rooms)
+    pub room_mentions: bool,
+    
+    /// Distinct users one message may mention; `@room` / `@here` don't count
+    pub max_mentions: usize,
+    
+    /// Reject messages over the mention cap instead of ignoring the extras
+    pub reject_excess_mentions: bool,
+    
+    /// Seconds between sounds in a room; extra /play commands are dropped
+    /// (0 = no cooldown). Room admins can override it per room.
+    pub sound_cooldown_secs: u64,
+    
+    /// Milliseconds a message send waits on the database writer before
+    /// answering 504 (0 = wait indefinitely)
+    pub write_timeout_ms: u64,
+    
+    /// Seconds within which the same text from the same user in the same
+    /// room is treated as an accidental double send and answered with the
+    /// first message (0 = off)
+    pub duplicate_window_secs: u64,
+    
+    /// Content stages messages go through, in order; a stage left out is
+    /// disabled, except "sanitize", which must come last (see
+    /// `rich_text::Pipeline`)
+    pub content_pipeline: Vec<String>,
+    
+    /// Longest room names, topics and user names accepted
+    pub text_limits: TextLimits,
+    
+    /// One-time bot reply to a new user's first message (None = off)
+    pub welcome_reply: Option<WelcomeReplyConfig>,
+    
+    /// External service every new message is checked with (None = off)
+    pub moderation: Option<ModerationConfig>,
+}
+
+/// Moderation or DLP webhook consulted before a message is stored; see
+/// `services::moderation`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationConfig {
+    pub webhook_url: String,
+    
+    /// Milliseconds to wait for a verdict before applying `failure_policy`
+    pub timeout_ms: u64,
+    
+    pub failure_policy: ModerationFailurePolicy,
+}
+
+/// Onboarding tips a bot posts when someone's very first message lands in
+/// the welcome room
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WelcomeReplyConfig {
+    /// Open room the reply is given in, matched by name
+    pub room_name: String,
+    
+    /// User ID of the bot that posts the reply
+    pub bot_id: uuid::Uuid,
+    
+    /// Reply text; `{name}` and `{room}` are filled in
+    pub template: String,
+}
+
+/// Maximum lengths in grapheme clusters (what a reader counts as one
+/// character), so an emoji made of several code points counts once
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TextLimits {
+    pub room_name: usize,
+    pub room_topic: usize,
+    pub user_name: usize,
+}
+
+impl Default for TextLimits {
+    fn default() -> Self {
+        Self {
+            room_name: 100,
+            room_topic: 500,
+            user_name: 50,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PaginationConfig {
+    /// Page size when the client doesn't ask for one
+    pub default_limit: u32,
+    
+    /// Largest page served; bigger requests are clamped to this
+    pub max_limit: u32,
+    
+    /// Answer room message requests that don't pick a format with the
+    /// deprecated bare array instead of the paginated envelope
+    pub legacy_message_array: bool,
+    
+    /// How many of a public room's most recent messages anonymous readers
+    /// can page through (0 = all of them); members are not limited
+    pub public_history_depth: u32,
+    
+    /// Most ids one batch lookup, such as `POST /api/users/resolve`, accepts
+    pub max_batch_ids: usize,
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        Self {
+            default_limit: 50,
+            max_limit: 100,
+            legacy_message_array: false,
+            public_history_depth: 500,
+            max_batch_ids: 100,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -162,6 +594,54 @@ pub struct PushConfig {
     
     /// Enable push notifications
     pub enabled: bool,
+    
+    /// Push requests in flight at once across all subscriptions
+    pub max_concurrent_sends: usize,
+    
+    /// First backoff after a failed send to an endpoint, in seconds
+    pub backoff_base_secs: u64,
+    
+    /// Backoff ceiling for an endpoint that keeps failing, in seconds
+    pub backoff_max_secs: u64,
+    
+    /// Sound hint sent with each kind of notification
+    pub sounds: NotificationSounds,
+}
+
+/// Sound hints clients use to pick an alert; `None` leaves the choice to the
+/// client. Hints are dropped for users who turned sounds off.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationSounds {
+    pub new_message: Option<String>,
+    pub mention: Option<String>,
+    pub direct_message: Option<String>,
+    pub sound_playback: Option<String>,
+}
+
+impl Default for NotificationSounds {
+    fn default() -> Self {
+        Self {
+            new_message: None,
+            mention: Some("mention".to_string()),
+            direct_message: Some("direct_message".to_string()),
+            sound_playback: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MailConfig {
+    /// Send email; until a transport is configured emails are only logged
+    pub enabled: bool,
+    
+    /// Sender address
+    pub from_address: String,
+    
+    /// Public URL of this instance, used for links in emails
+    pub base_url: String,
+    
+    /// Email people when someone adds them to a closed room
+    pub room_added: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -177,6 +657,9 @@ pub struct MetricsConfig {
     
     /// Histogram buckets for response times
     pub response_time_buckets: Vec<f64>,
+    
+    /// Bearer token required to scrape metrics (unset = open)
+    pub auth_token: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -196,11 +679,20 @@ pub struct FeatureFlags {
     /// Enable sound system
     pub sounds: bool,
     
-    /// Enable file uploads (future feature)
+    /// Enable file uploads (`POST /api/rooms/:id/attachments`)
     pub file_uploads: bool,
     
     /// Enable offline demo mode with sample data
     pub demo_mode: bool,
+    
+    /// Let `#channel` references create missing open rooms
+    pub auto_create_rooms: bool,
+    
+    /// Serve read-only history of rooms marked public without signing in
+    pub public_rooms: bool,
+    
+    /// Let admins open short-lived sessions as other users for support
+    pub impersonation: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -236,6 +728,147 @@ pub struct CacheConfig {
     pub cleanup_interval_secs: u64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageConfig {
+    /// Storage backend (local, s3)
+    pub backend: StorageBackend,
+    
+    /// Directory for the local backend
+    pub local_path: PathBuf,
+    
+    /// S3-compatible backend settings (None = not configured)
+    pub s3: Option<S3Config>,
+    
+    /// Validity of presigned upload/download URLs in seconds
+    pub presign_expiry_secs: u64,
+    
+    /// Total bytes uploads may use (0 = unlimited)
+    pub quota_bytes: u64,
+    
+    /// Bytes each user's uploads may use (0 = unlimited)
+    pub user_quota_bytes: u64,
+    
+    /// Uploads written to the backend at once; further uploads wait
+    pub max_concurrent_uploads: usize,
+    
+    /// Attachments one message may reference (0 = unlimited)
+    pub max_attachments_per_message: usize,
+    
+    /// Combined size of one message's attachments (0 = unlimited)
+    pub max_attachment_bytes_per_message: u64,
+    
+    /// `host:port` of a clamd daemon to virus-scan uploads (None = no scanning)
+    pub clamav_address: Option<String>,
+    
+    /// How long an upload waits for its scan before it's stored quarantined
+    /// and the scan finishes in the background
+    pub scan_timeout_ms: u64,
+    
+    /// Content types attachments may have, e.g. `image/*` or
+    /// `application/pdf` (empty = any); rooms can narrow or replace this
+    pub allowed_attachment_types: Vec<String>,
+    
+    /// Attachment types browsers may display inline; everything else is
+    /// served as a download (empty = download everything)
+    pub previewable_attachment_types: Vec<String>,
+    
+    /// Separate origin attachments are served from, e.g.
+    /// `https://files.example.com` (None = the app's own origin)
+    pub attachment_origin: Option<String>,
+    
+    /// Secret the attachment origin's links are signed with; nodes sharing
+    /// an origin need the same one. Never serialized or logged.
+    #[serde(skip_serializing, default)]
+    pub attachment_signing_secret: Option<String>,
+    
+    /// Longest side of the thumbnails made for image uploads (0 = none)
+    pub thumbnail_max_dimension: u32,
+    
+    /// Images wider or taller than this aren't decoded for a thumbnail
+    pub thumbnail_max_source_dimension: u32,
+    
+    /// Memory one thumbnail's decoder may allocate
+    pub thumbnail_max_decode_bytes: u64,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            backend: StorageBackend::Local,
+            local_path: PathBuf::from("uploads"),
+            s3: None,
+            presign_expiry_secs: 900,
+            quota_bytes: 0,
+            user_quota_bytes: 0,
+            max_concurrent_uploads: 4,
+            max_attachments_per_message: 10,
+            max_attachment_bytes_per_message: 50 * 1024 * 1024,
+            clamav_address: None,
+            scan_timeout_ms: 5000,
+            allowed_attachment_types: Vec::new(),
+            previewable_attachment_types: ["image/png", "image/jpeg", "image/gif", "image/webp"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            attachment_origin: None,
+            attachment_signing_secret: None,
+            thumbnail_max_dimension: 320,
+            thumbnail_max_source_dimension: 4096,
+            thumbnail_max_decode_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum StorageBackend {
+    Local,
+    S3,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AdminSeedConfig {
+    /// Admin email (CAMPFIRE_ADMIN_EMAIL)
+    pub email: String,
+    
+    /// Admin password (CAMPFIRE_ADMIN_PASSWORD); never serialized or logged
+    #[serde(skip_serializing, default)]
+    pub password: String,
+    
+    /// Display name (CAMPFIRE_ADMIN_NAME)
+    pub name: String,
+}
+
+impl std::fmt::Debug for AdminSeedConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AdminSeedConfig")
+            .field("email", &self.email)
+            .field("password", &"[REDACTED]")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct S3Config {
+    /// Endpoint URL, e.g. https://s3.amazonaws.com or http://minio:9000
+    pub endpoint: String,
+    
+    /// Bucket name
+    pub bucket: String,
+    
+    /// Signing region
+    pub region: String,
+    
+    /// Access key ID
+    pub access_key_id: String,
+    
+    /// Secret access key
+    pub secret_access_key: String,
+    
+    /// Use path-style URLs (endpoint/bucket/key) instead of bucket subdomains
+    pub path_style: bool,
+}
+
 impl Config {
     /// Load configuration from environment variables
     pub fn from_env() -> Result<Self> {
@@ -244,53 +877,254 @@ impl Config {
             database: DatabaseConfig::from_env()?,
             logging: LoggingConfig::from_env()?,
             security: SecurityConfig::from_env()?,
+            messages: MessagesConfig::from_env()?,
+            pagination: PaginationConfig::from_env()?,
             push: PushConfig::from_env()?,
+            mail: MailConfig::from_env()?,
             metrics: MetricsConfig::from_env()?,
             features: FeatureFlags::from_env()?,
             cache: CacheConfig::from_env()?,
+            storage: StorageConfig::from_env()?,
+            admin_seed: AdminSeedConfig::from_env()?,
         };
         
-        config.validate()?;
+        let errors: Vec<String> = config
+            .validate()
+            .into_iter()
+            .filter(ConfigDiagnostic::is_error)
+            .map(|diagnostic| diagnostic.to_string())
+            .collect();
+        if !errors.is_empty() {
+            return Err(anyhow::anyhow!("Invalid configuration: {}", errors.join("; ")));
+        }
         Ok(config)
     }
     
-    /// Validate configuration values
-    fn validate(&self) -> Result<()> {
+    /// Check the configuration as a whole. Errors mean the server can't run
+    /// as configured; warnings are combinations that work but are probably
+    /// not what the operator meant.
+    pub fn validate(&self) -> Vec<ConfigDiagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut error = |message: &str| diagnostics.push(ConfigDiagnostic::Error(message.to_string()));
+        
         // Validate server config
         if self.server.request_timeout_secs == 0 {
-            return Err(anyhow::anyhow!("Request timeout must be greater than 0"));
+            error("Request timeout must be greater than 0");
+        }
+        
+        for route in &self.server.request_timeout_overrides {
+            if !route.path_prefix.starts_with('/') {
+                error(&format!("Request timeout override path {:?} must start with '/'", route.path_prefix));
+            }
+            if route.timeout_secs == 0 {
+                error(&format!("Request timeout override for {} must be greater than 0", route.path_prefix));
+            }
         }
         
         if self.server.max_request_size == 0 {
-            return Err(anyhow::anyhow!("Max request size must be greater than 0"));
+            error("Max request size must be greater than 0");
+        }
+        
+        if self.server.ws_send_buffer_size == 0 {
+            error("WebSocket send buffer size must be greater than 0");
+        }
+        
+        if self.server.ws_presence_timeout_secs == 0 {
+            error("WebSocket presence timeout must be greater than 0");
+        }
+        
+        if self.server.ws_max_sequence_ahead == 0 {
+            error("WebSocket max sequence ahead must be greater than 0");
         }
         
         // Validate database config
         if self.database.max_connections == 0 {
-            return Err(anyhow::anyhow!("Database max connections must be greater than 0"));
+            error("Database max connections must be greater than 0");
+        }
+        
+        if self.database.replica_url.is_some() && self.database.replica_max_connections == 0 {
+            error("Database replica max connections must be greater than 0");
+        }
+        
+        if self.database.retention_purge_interval_secs == 0 {
+            error("Retention purge interval must be greater than 0 seconds");
+        }
+        
+        if self.messages.room_auto_archive_interval_secs == 0 {
+            error("Room auto-archive interval must be greater than 0 seconds");
+        }
+        
+        // Validate logging config
+        if !(0.0..=1.0).contains(&self.logging.sampling.sample_rate) {
+            error("Log sample rate must be between 0.0 and 1.0");
         }
         
         // Validate security config
         if self.security.session_token_length < 16 {
-            return Err(anyhow::anyhow!("Session token length must be at least 16 bytes"));
+            error("Session token length must be at least 16 bytes");
         }
         
         if self.security.session_expiry_hours == 0 {
-            return Err(anyhow::anyhow!("Session expiry must be greater than 0 hours"));
+            error("Session expiry must be greater than 0 hours");
+        }
+        
+        if self.features.impersonation && self.security.impersonation_session_mins == 0 {
+            error("Impersonation session length must be greater than 0 minutes");
+        }
+        
+        let lifetimes = &self.security.token_lifetimes;
+        if lifetimes.password_reset_mins == 0
+            || lifetimes.email_verification_hours == 0
+            || lifetimes.room_invite_hours == 0
+        {
+            error("Token lifetimes must be greater than 0");
+        }
+        
+        if crate::timezone::parse_timezone(&self.security.quota_timezone).is_none() {
+            error(&format!("Unknown quota timezone: {}", self.security.quota_timezone));
+        }
+        
+        if self.security.token_cleanup_interval_secs == 0 {
+            error("Token cleanup interval must be greater than 0 seconds");
+        }
+        
+        if !(crate::validation::MIN_PASSWORD_LENGTH..=crate::validation::MAX_PASSWORD_LENGTH)
+            .contains(&self.security.password_policy.min_length)
+        {
+            error(&format!(
+                "Password minimum length must be between {} and {}",
+                crate::validation::MIN_PASSWORD_LENGTH,
+                crate::validation::MAX_PASSWORD_LENGTH
+            ));
+        }
+        
+        for proxy in &self.security.trusted_proxies {
+            if crate::middleware::client_ip::parse_proxy_net(proxy).is_err() {
+                error(&format!("Invalid trusted proxy address: {}", proxy));
+            }
+        }
+        
+        for host in self.security.webhook_allowed_hosts.iter().chain(&self.security.webhook_denied_hosts) {
+            if !crate::services::webhook_policy::is_valid_host_rule(host) {
+                error(&format!("Invalid webhook host rule: {}", host));
+            }
+        }
+        
+        // Listed origins are sent with credentials allowed, which browsers
+        // (and the CORS layer) refuse to combine with a wildcard
+        if self.security.cors_origins.iter().any(|origin| origin == "*") {
+            error("CAMPFIRE_CORS_ORIGINS cannot contain '*'; leave it empty to allow all origins without credentials");
+        }
+        
+        for stage in &self.messages.content_pipeline {
+            if !crate::rich_text::is_known_stage(stage) {
+                error(&format!("Unknown content pipeline stage: {}", stage));
+            }
+        }
+        if let Some(position) = self.messages.content_pipeline.iter().position(|stage| stage == "sanitize") {
+            if position + 1 != self.messages.content_pipeline.len() {
+                error("CAMPFIRE_CONTENT_PIPELINE must end with sanitize, after anything that adds markup");
+            }
+        } else {
+            error("CAMPFIRE_CONTENT_PIPELINE must include sanitize; it can't be disabled");
+        }
+        
+        let text_limits = &self.messages.text_limits;
+        if text_limits.room_name == 0 || text_limits.room_topic == 0 || text_limits.user_name == 0 {
+            error("Room name, room topic and user name length limits must be greater than 0");
+        }
+        
+        // Validate pagination config
+        if self.pagination.default_limit == 0 {
+            error("Pagination default limit must be greater than 0");
+        }
+        
+        if self.pagination.default_limit > self.pagination.max_limit {
+            error("Pagination default limit must not exceed the maximum limit");
+        }
+        
+        // Validate storage config
+        if self.storage.max_concurrent_uploads == 0 {
+            error("Max concurrent uploads must be greater than 0");
+        }
+        
+        if let Some(origin) = &self.storage.attachment_origin {
+            let host = origin
+                .strip_prefix("https://")
+                .or_else(|| origin.strip_prefix("http://"))
+                .unwrap_or("");
+            if host.is_empty() || host.contains('/') {
+                error("Attachment origin must be a scheme and host, e.g. https://files.example.com");
+            }
+            match &self.storage.attachment_signing_secret {
+                None => error("CAMPFIRE_ATTACHMENT_SIGNING_SECRET is required when an attachment origin is set"),
+                Some(secret) if secret.len() < 32 => {
+                    error("Attachment signing secret must be at least 32 characters")
+                }
+                Some(_) => {}
+            }
         }
         
         // Validate push config if enabled
+        if self.push.max_concurrent_sends == 0 {
+            error("Push max concurrent sends must be greater than 0");
+        }
+        
+        if self.push.backoff_base_secs > self.push.backoff_max_secs {
+            error("Push backoff base must not exceed the backoff maximum");
+        }
+        
         if self.push.enabled {
             if self.push.vapid_private_key.is_none() || self.push.vapid_public_key.is_none() {
-                return Err(anyhow::anyhow!("VAPID keys required when push notifications are enabled"));
+                error("VAPID keys required when push notifications are enabled");
             }
             
             if self.push.vapid_subject.is_empty() {
-                return Err(anyhow::anyhow!("VAPID subject required when push notifications are enabled"));
+                error("VAPID subject required when push notifications are enabled");
+            }
+        }
+        
+        let mut warning = |message: String| diagnostics.push(ConfigDiagnostic::Warning(message));
+        
+        for origin in &self.security.cors_origins {
+            if origin != "*" && origin.parse::<axum::http::HeaderValue>().is_err() {
+                warning(format!("Ignoring CORS origin that isn't a valid header value: {}", origin));
             }
         }
         
-        Ok(())
+        if self.security.cors_origins.is_empty() && self.security.force_https {
+            warning("HTTPS is enforced but CAMPFIRE_CORS_ORIGINS is empty, so any origin may call the API".to_string());
+        }
+        
+        if !self.push.enabled && (self.push.vapid_private_key.is_some() || self.push.vapid_public_key.is_some()) {
+            warning("VAPID keys are set but push notifications are disabled".to_string());
+        }
+        
+        if self.database.write_shed_queue_depth > crate::database::WRITE_QUEUE_CAPACITY {
+            warning(format!(
+                "Write shedding threshold ({}) is above the writer queue capacity ({}), so writes are never shed",
+                self.database.write_shed_queue_depth,
+                crate::database::WRITE_QUEUE_CAPACITY
+            ));
+        }
+        
+        if self.server.shutdown_timeout_secs < self.server.request_timeout_secs {
+            warning(format!(
+                "Shutdown timeout ({}s) is shorter than the request timeout ({}s); slow requests may be cut off on shutdown",
+                self.server.shutdown_timeout_secs, self.server.request_timeout_secs
+            ));
+        }
+        
+        // WebSockets get the first half of the shutdown budget, the write
+        // drain has to fit in what's left
+        if self.server.shutdown_write_drain_secs > self.server.shutdown_timeout_secs - self.server.shutdown_timeout_secs / 2 {
+            warning(format!(
+                "Write drain ({}s) doesn't fit in the second half of the shutdown timeout ({}s); queued writes may be lost on shutdown",
+                self.server.shutdown_write_drain_secs, self.server.shutdown_timeout_secs
+            ));
+        }
+        
+        diagnostics
     }
     
     /// Get tracing level from config
@@ -340,6 +1174,14 @@ impl ServerConfig {
                 .unwrap_or_else(|_| "30".to_string())
                 .parse()
                 .context("Invalid CAMPFIRE_REQUEST_TIMEOUT")?,
+            request_timeout_overrides: env::var("CAMPFIRE_REQUEST_TIMEOUT_OVERRIDES")
+                .unwrap_or_else(|_| "/api/search=60,/api/users/me/export=300,/api/exports=300".to_string())
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::parse)
+                .collect::<Result<_>>()
+                .context("Invalid CAMPFIRE_REQUEST_TIMEOUT_OVERRIDES")?,
             max_request_size: env::var("CAMPFIRE_MAX_REQUEST_SIZE")
                 .unwrap_or_else(|_| "16777216".to_string()) // 16MB
                 .parse()
@@ -348,6 +1190,47 @@ impl ServerConfig {
                 .unwrap_or_else(|_| "30".to_string())
                 .parse()
                 .context("Invalid CAMPFIRE_SHUTDOWN_TIMEOUT")?,
+            shutdown_write_drain_secs: env::var("CAMPFIRE_SHUTDOWN_WRITE_DRAIN")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_SHUTDOWN_WRITE_DRAIN")?,
+            ws_reconnect_after_secs: env::var("CAMPFIRE_WS_RECONNECT_AFTER")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_WS_RECONNECT_AFTER")?,
+            ws_send_buffer_size: env::var("CAMPFIRE_WS_SEND_BUFFER")
+                .unwrap_or_else(|_| "256".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_WS_SEND_BUFFER")?,
+            ws_send_buffer_overflow: match env::var("CAMPFIRE_WS_OVERFLOW_POLICY")
+                .unwrap_or_else(|_| "drop-oldest".to_string())
+                .to_lowercase()
+                .as_str()
+            {
+                "drop-oldest" => SendBufferOverflow::DropOldest,
+                "disconnect" => SendBufferOverflow::Disconnect,
+                other => return Err(anyhow::anyhow!("Invalid CAMPFIRE_WS_OVERFLOW_POLICY: {}", other)),
+            },
+            ws_typing_broadcast_limit: env::var("CAMPFIRE_WS_TYPING_LIMIT")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_WS_TYPING_LIMIT")?,
+            ws_presence_timeout_secs: env::var("CAMPFIRE_WS_PRESENCE_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_WS_PRESENCE_TIMEOUT_SECS")?,
+            ws_welcome_max_rooms: env::var("CAMPFIRE_WS_WELCOME_MAX_ROOMS")
+                .unwrap_or_else(|_| "50".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_WS_WELCOME_MAX_ROOMS")?,
+            ws_max_sequence_ahead: env::var("CAMPFIRE_WS_MAX_SEQUENCE_AHEAD")
+                .unwrap_or_else(|_| "256".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_WS_MAX_SEQUENCE_AHEAD")?,
+            last_seen_write_interval_secs: env::var("CAMPFIRE_LAST_SEEN_WRITE_INTERVAL_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_LAST_SEEN_WRITE_INTERVAL_SECS")?,
             worker_threads: env::var("CAMPFIRE_WORKER_THREADS")
                 .unwrap_or_else(|_| "0".to_string())
                 .parse()
@@ -376,6 +1259,33 @@ impl DatabaseConfig {
             backup_dir: env::var("CAMPFIRE_BACKUP_DIR")
                 .ok()
                 .map(PathBuf::from),
+            message_retention_days: env::var("CAMPFIRE_MESSAGE_RETENTION_DAYS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MESSAGE_RETENTION_DAYS")?,
+            retention_purge_interval_secs: env::var("CAMPFIRE_RETENTION_PURGE_INTERVAL_SECS")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_RETENTION_PURGE_INTERVAL_SECS")?,
+            check_search_index_on_startup: env::var("CAMPFIRE_CHECK_SEARCH_INDEX")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_CHECK_SEARCH_INDEX")?,
+            write_shed_queue_depth: env::var("CAMPFIRE_WRITE_SHED_QUEUE_DEPTH")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_WRITE_SHED_QUEUE_DEPTH")?,
+            replica_url: env::var("CAMPFIRE_DATABASE_REPLICA_URL")
+                .ok()
+                .filter(|url| !url.trim().is_empty()),
+            replica_max_connections: env::var("CAMPFIRE_DB_REPLICA_MAX_CONNECTIONS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_DB_REPLICA_MAX_CONNECTIONS")?,
+            replica_read_your_writes_secs: env::var("CAMPFIRE_DB_REPLICA_READ_YOUR_WRITES_SECS")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_DB_REPLICA_READ_YOUR_WRITES_SECS")?,
         })
     }
 }
@@ -411,6 +1321,27 @@ impl LoggingConfig {
                 .context("Invalid CAMPFIRE_LOG_ROTATION_INTERVAL")?,
         };
         
+        let sampling = LogSamplingConfig {
+            sample_rate: env::var("CAMPFIRE_LOG_SAMPLE_RATE")
+                .unwrap_or_else(|_| "0.01".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_LOG_SAMPLE_RATE")?,
+            always_log_latency_ms: env::var("CAMPFIRE_LOG_ALWAYS_LATENCY_MS")
+                .unwrap_or_else(|_| "500".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_LOG_ALWAYS_LATENCY_MS")?,
+            high_volume_prefixes: env::var("CAMPFIRE_LOG_SAMPLED_PREFIXES")
+                .unwrap_or_else(|_| "/health,/metrics,/static".to_string())
+                .split(',')
+                .filter(|s| !s.is_empty())
+                .map(|s| s.trim().to_string())
+                .collect(),
+            log_client_errors: env::var("CAMPFIRE_LOG_CLIENT_ERRORS")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_LOG_CLIENT_ERRORS")?,
+        };
+        
         Ok(LoggingConfig {
             level: env::var("CAMPFIRE_LOG_LEVEL")
                 .unwrap_or_else(|_| "info".to_string()),
@@ -446,6 +1377,7 @@ impl LoggingConfig {
                 .parse()
                 .context("Invalid CAMPFIRE_ERROR_RECOVERY_LOGGING")?,
             rotation,
+            sampling,
         })
     }
 }
@@ -465,6 +1397,10 @@ impl SecurityConfig {
                 .unwrap_or_else(|_| "60".to_string())
                 .parse()
                 .context("Invalid CAMPFIRE_RATE_LIMIT_RPM")?,
+            rate_limit_headers: env::var("CAMPFIRE_RATE_LIMIT_HEADERS")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_RATE_LIMIT_HEADERS")?,
             session_token_length: env::var("CAMPFIRE_SESSION_TOKEN_LENGTH")
                 .unwrap_or_else(|_| "32".to_string())
                 .parse()
@@ -473,6 +1409,14 @@ impl SecurityConfig {
                 .unwrap_or_else(|_| "24".to_string())
                 .parse()
                 .context("Invalid CAMPFIRE_SESSION_EXPIRY_HOURS")?,
+            session_idle_timeout_mins: env::var("CAMPFIRE_SESSION_IDLE_TIMEOUT_MINS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_SESSION_IDLE_TIMEOUT_MINS")?,
+            impersonation_session_mins: env::var("CAMPFIRE_IMPERSONATION_SESSION_MINS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_IMPERSONATION_SESSION_MINS")?,
             force_https: env::var("CAMPFIRE_FORCE_HTTPS")
                 .unwrap_or_else(|_| "false".to_string())
                 .parse()
@@ -481,6 +1425,132 @@ impl SecurityConfig {
                 .unwrap_or_else(|_| "false".to_string())
                 .parse()
                 .context("Invalid CAMPFIRE_TRUST_PROXY")?,
+            trusted_proxies: env::var("CAMPFIRE_TRUSTED_PROXIES")
+                .unwrap_or_else(|_| "127.0.0.1/32,::1/128".to_string())
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            ws_allow_missing_origin: env::var("CAMPFIRE_WS_ALLOW_MISSING_ORIGIN")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_WS_ALLOW_MISSING_ORIGIN")?,
+            message_rate_per_minute: env::var("CAMPFIRE_MESSAGE_RATE_PER_MINUTE")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MESSAGE_RATE_PER_MINUTE")?,
+            message_rate_exempt_bots: env::var("CAMPFIRE_MESSAGE_RATE_EXEMPT_BOTS")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MESSAGE_RATE_EXEMPT_BOTS")?,
+            message_rate_exempt_admins: env::var("CAMPFIRE_MESSAGE_RATE_EXEMPT_ADMINS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MESSAGE_RATE_EXEMPT_ADMINS")?,
+            room_join_rate_per_minute: env::var("CAMPFIRE_ROOM_JOIN_RATE_PER_MINUTE")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_ROOM_JOIN_RATE_PER_MINUTE")?,
+            max_bots: env::var("CAMPFIRE_MAX_BOTS")
+                .unwrap_or_else(|_| "100".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MAX_BOTS")?,
+            setup_attempts_per_minute: env::var("CAMPFIRE_SETUP_ATTEMPTS_PER_MINUTE")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_SETUP_ATTEMPTS_PER_MINUTE")?,
+            daily_request_quota: env::var("CAMPFIRE_DAILY_REQUEST_QUOTA")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_DAILY_REQUEST_QUOTA")?,
+            bot_daily_request_quota: env::var("CAMPFIRE_BOT_DAILY_REQUEST_QUOTA")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_BOT_DAILY_REQUEST_QUOTA")?,
+            quota_timezone: env::var("CAMPFIRE_QUOTA_TIMEZONE")
+                .unwrap_or_else(|_| "UTC".to_string()),
+            json_max_depth: env::var("CAMPFIRE_JSON_MAX_DEPTH")
+                .unwrap_or_else(|_| "32".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_JSON_MAX_DEPTH")?,
+            json_max_fields: env::var("CAMPFIRE_JSON_MAX_FIELDS")
+                .unwrap_or_else(|_| "10000".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_JSON_MAX_FIELDS")?,
+            password_policy: PasswordPolicy::from_env()?,
+            token_lifetimes: TokenLifetimes::from_env()?,
+            token_cleanup_interval_secs: env::var("CAMPFIRE_TOKEN_CLEANUP_INTERVAL_SECS")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_TOKEN_CLEANUP_INTERVAL_SECS")?,
+            // Deployments that force https get https-only webhooks unless told otherwise
+            webhook_require_https: env::var("CAMPFIRE_WEBHOOK_REQUIRE_HTTPS")
+                .or_else(|_| env::var("CAMPFIRE_FORCE_HTTPS"))
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_WEBHOOK_REQUIRE_HTTPS")?,
+            webhook_allow_private_networks: env::var("CAMPFIRE_WEBHOOK_ALLOW_PRIVATE_NETWORKS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_WEBHOOK_ALLOW_PRIVATE_NETWORKS")?,
+            webhook_allowed_hosts: env::var("CAMPFIRE_WEBHOOK_ALLOWED_HOSTS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            webhook_denied_hosts: env::var("CAMPFIRE_WEBHOOK_DENIED_HOSTS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+        })
+    }
+}
+
+impl PasswordPolicy {
+    fn from_env() -> Result<Self> {
+        Ok(PasswordPolicy {
+            min_length: env::var("CAMPFIRE_PASSWORD_MIN_LENGTH")
+                .unwrap_or_else(|_| "8".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_PASSWORD_MIN_LENGTH")?,
+            require_mixed_case: env::var("CAMPFIRE_PASSWORD_REQUIRE_MIXED_CASE")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_PASSWORD_REQUIRE_MIXED_CASE")?,
+            require_digit: env::var("CAMPFIRE_PASSWORD_REQUIRE_DIGIT")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_PASSWORD_REQUIRE_DIGIT")?,
+            require_symbol: env::var("CAMPFIRE_PASSWORD_REQUIRE_SYMBOL")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_PASSWORD_REQUIRE_SYMBOL")?,
+            block_common: env::var("CAMPFIRE_PASSWORD_BLOCK_COMMON")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_PASSWORD_BLOCK_COMMON")?,
+        })
+    }
+}
+
+impl TokenLifetimes {
+    fn from_env() -> Result<Self> {
+        Ok(TokenLifetimes {
+            password_reset_mins: env::var("CAMPFIRE_PASSWORD_RESET_TOKEN_MINS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_PASSWORD_RESET_TOKEN_MINS")?,
+            email_verification_hours: env::var("CAMPFIRE_EMAIL_VERIFICATION_TOKEN_HOURS")
+                .unwrap_or_else(|_| "48".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_EMAIL_VERIFICATION_TOKEN_HOURS")?,
+            room_invite_hours: env::var("CAMPFIRE_ROOM_INVITE_TOKEN_HOURS")
+                .unwrap_or_else(|_| "168".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_ROOM_INVITE_TOKEN_HOURS")?,
         })
     }
 }
@@ -496,6 +1566,66 @@ impl PushConfig {
                 .unwrap_or_else(|_| "true".to_string())
                 .parse()
                 .context("Invalid CAMPFIRE_PUSH_ENABLED")?,
+            max_concurrent_sends: env::var("CAMPFIRE_PUSH_MAX_CONCURRENT")
+                .unwrap_or_else(|_| "32".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_PUSH_MAX_CONCURRENT")?,
+            backoff_base_secs: env::var("CAMPFIRE_PUSH_BACKOFF_BASE_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_PUSH_BACKOFF_BASE_SECS")?,
+            backoff_max_secs: env::var("CAMPFIRE_PUSH_BACKOFF_MAX_SECS")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_PUSH_BACKOFF_MAX_SECS")?,
+            sounds: NotificationSounds::from_env(),
+        })
+    }
+}
+
+impl NotificationSounds {
+    /// An empty variable turns the hint off for that notification type
+    fn from_env() -> Self {
+        let defaults = Self::default();
+        let hint = |var: &str, default: Option<String>| match env::var(var) {
+            Ok(value) if value.trim().is_empty() => None,
+            Ok(value) => Some(value.trim().to_string()),
+            Err(_) => default,
+        };
+        Self {
+            new_message: hint("CAMPFIRE_PUSH_SOUND_NEW_MESSAGE", defaults.new_message),
+            mention: hint("CAMPFIRE_PUSH_SOUND_MENTION", defaults.mention),
+            direct_message: hint("CAMPFIRE_PUSH_SOUND_DIRECT_MESSAGE", defaults.direct_message),
+            sound_playback: hint("CAMPFIRE_PUSH_SOUND_PLAYBACK", defaults.sound_playback),
+        }
+    }
+    
+    pub fn for_type(&self, notification_type: &crate::models::NotificationType) -> Option<&str> {
+        use crate::models::NotificationType;
+        match notification_type {
+            NotificationType::NewMessage => self.new_message.as_deref(),
+            NotificationType::Mention => self.mention.as_deref(),
+            NotificationType::DirectMessage => self.direct_message.as_deref(),
+            NotificationType::SoundPlayback => self.sound_playback.as_deref(),
+        }
+    }
+}
+
+impl MailConfig {
+    fn from_env() -> Result<Self> {
+        Ok(MailConfig {
+            enabled: env::var("CAMPFIRE_MAIL_ENABLED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MAIL_ENABLED")?,
+            from_address: env::var("CAMPFIRE_MAIL_FROM")
+                .unwrap_or_else(|_| "campfire@localhost".to_string()),
+            base_url: env::var("CAMPFIRE_BASE_URL")
+                .unwrap_or_else(|_| "http://localhost:3000".to_string()),
+            room_added: env::var("CAMPFIRE_MAIL_ROOM_ADDED")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MAIL_ROOM_ADDED")?,
         })
     }
 }
@@ -521,6 +1651,7 @@ impl MetricsConfig {
                 .parse()
                 .context("Invalid CAMPFIRE_METRICS_DETAILED")?,
             response_time_buckets: buckets,
+            auth_token: env::var("CAMPFIRE_METRICS_TOKEN").ok().filter(|token| !token.is_empty()),
         })
     }
 }
@@ -556,6 +1687,18 @@ impl FeatureFlags {
                 .unwrap_or_else(|_| "true".to_string())
                 .parse()
                 .context("Invalid CAMPFIRE_DEMO_MODE")?,
+            auto_create_rooms: env::var("CAMPFIRE_FEATURE_AUTO_CREATE_ROOMS")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_FEATURE_AUTO_CREATE_ROOMS")?,
+            public_rooms: env::var("CAMPFIRE_FEATURE_PUBLIC_ROOMS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_FEATURE_PUBLIC_ROOMS")?,
+            impersonation: env::var("CAMPFIRE_FEATURE_IMPERSONATION")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_FEATURE_IMPERSONATION")?,
         })
     }
 }
@@ -607,6 +1750,294 @@ impl CacheConfig {
     }
 }
 
+impl MessagesConfig {
+    fn from_env() -> Result<Self> {
+        Ok(MessagesConfig {
+            seen_by_max_members: env::var("CAMPFIRE_SEEN_BY_MAX_MEMBERS")
+                .unwrap_or_else(|_| "20".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_SEEN_BY_MAX_MEMBERS")?,
+            max_saved_messages: env::var("CAMPFIRE_MAX_SAVED_MESSAGES")
+                .unwrap_or_else(|_| "1000".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MAX_SAVED_MESSAGES")?,
+            max_canned_responses: env::var("CAMPFIRE_MAX_CANNED_RESPONSES")
+                .unwrap_or_else(|_| "100".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MAX_CANNED_RESPONSES")?,
+            room_auto_archive_days: env::var("CAMPFIRE_ROOM_AUTO_ARCHIVE_DAYS")
+                .unwrap_or_else(|_| "90".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_ROOM_AUTO_ARCHIVE_DAYS")?,
+            room_auto_archive_interval_secs: env::var("CAMPFIRE_ROOM_AUTO_ARCHIVE_INTERVAL_SECS")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_ROOM_AUTO_ARCHIVE_INTERVAL_SECS")?,
+            room_preview_length: env::var("CAMPFIRE_ROOM_PREVIEW_LENGTH")
+                .unwrap_or_else(|_| "80".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_ROOM_PREVIEW_LENGTH")?,
+            mention_autocomplete_limit: env::var("CAMPFIRE_MENTION_AUTOCOMPLETE_LIMIT")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MENTION_AUTOCOMPLETE_LIMIT")?,
+            room_mentions: env::var("CAMPFIRE_ROOM_MENTIONS")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_ROOM_MENTIONS")?,
+            max_mentions: env::var("CAMPFIRE_MAX_MENTIONS")
+                .unwrap_or_else(|_| "20".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MAX_MENTIONS")?,
+            reject_excess_mentions: env::var("CAMPFIRE_REJECT_EXCESS_MENTIONS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_REJECT_EXCESS_MENTIONS")?,
+            sound_cooldown_secs: env::var("CAMPFIRE_SOUND_COOLDOWN_SECS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_SOUND_COOLDOWN_SECS")?,
+            write_timeout_ms: env::var("CAMPFIRE_MESSAGE_WRITE_TIMEOUT_MS")
+                .unwrap_or_else(|_| "5000".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MESSAGE_WRITE_TIMEOUT_MS")?,
+            duplicate_window_secs: env::var("CAMPFIRE_DUPLICATE_MESSAGE_WINDOW_SECS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_DUPLICATE_MESSAGE_WINDOW_SECS")?,
+            content_pipeline: env::var("CAMPFIRE_CONTENT_PIPELINE")
+                .unwrap_or_else(|_| crate::rich_text::DEFAULT_PIPELINE.join(","))
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            text_limits: TextLimits::from_env()?,
+            welcome_reply: WelcomeReplyConfig::from_env()?,
+            moderation: ModerationConfig::from_env()?,
+        })
+    }
+}
+
+impl WelcomeReplyConfig {
+    /// The reply is on once a bot is named to post it
+    fn from_env() -> Result<Option<Self>> {
+        let Some(bot_id) = env::var("CAMPFIRE_WELCOME_BOT_ID").ok().filter(|v| !v.trim().is_empty()) else {
+            return Ok(None);
+        };
+        
+        Ok(Some(WelcomeReplyConfig {
+            room_name: env::var("CAMPFIRE_WELCOME_ROOM")
+                .unwrap_or_else(|_| "General".to_string()),
+            bot_id: bot_id.trim().parse().context("Invalid CAMPFIRE_WELCOME_BOT_ID")?,
+            template: env::var("CAMPFIRE_WELCOME_TEMPLATE").unwrap_or_else(|_| {
+                "Welcome to {room}, {name}! @mention someone to get their attention, \
+                 browse the room list for more conversations, and try /play to share a sound."
+                    .to_string()
+            }),
+        }))
+    }
+}
+
+impl ModerationConfig {
+    /// The hook is on once a webhook URL is set
+    fn from_env() -> Result<Option<Self>> {
+        let Some(webhook_url) = env::var("CAMPFIRE_MODERATION_WEBHOOK_URL").ok().filter(|v| !v.trim().is_empty()) else {
+            return Ok(None);
+        };
+        
+        Ok(Some(ModerationConfig {
+            webhook_url: webhook_url.trim().to_string(),
+            timeout_ms: env::var("CAMPFIRE_MODERATION_TIMEOUT_MS")
+                .unwrap_or_else(|_| "2000".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MODERATION_TIMEOUT_MS")?,
+            failure_policy: match env::var("CAMPFIRE_MODERATION_FAILURE_POLICY")
+                .unwrap_or_else(|_| "open".to_string())
+                .as_str()
+            {
+                "open" => ModerationFailurePolicy::FailOpen,
+                "closed" => ModerationFailurePolicy::FailClosed,
+                other => return Err(anyhow::anyhow!("Invalid CAMPFIRE_MODERATION_FAILURE_POLICY: {}", other)),
+            },
+        }))
+    }
+}
+
+impl TextLimits {
+    fn from_env() -> Result<Self> {
+        Ok(TextLimits {
+            room_name: env::var("CAMPFIRE_MAX_ROOM_NAME_LENGTH")
+                .unwrap_or_else(|_| "100".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MAX_ROOM_NAME_LENGTH")?,
+            room_topic: env::var("CAMPFIRE_MAX_ROOM_TOPIC_LENGTH")
+                .unwrap_or_else(|_| "500".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MAX_ROOM_TOPIC_LENGTH")?,
+            user_name: env::var("CAMPFIRE_MAX_USER_NAME_LENGTH")
+                .unwrap_or_else(|_| "50".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MAX_USER_NAME_LENGTH")?,
+        })
+    }
+}
+
+impl PaginationConfig {
+    fn from_env() -> Result<Self> {
+        Ok(PaginationConfig {
+            default_limit: env::var("CAMPFIRE_PAGINATION_DEFAULT_LIMIT")
+                .unwrap_or_else(|_| "50".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_PAGINATION_DEFAULT_LIMIT")?,
+            max_limit: env::var("CAMPFIRE_PAGINATION_MAX_LIMIT")
+                .unwrap_or_else(|_| "100".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_PAGINATION_MAX_LIMIT")?,
+            legacy_message_array: env::var("CAMPFIRE_LEGACY_MESSAGE_ARRAY")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_LEGACY_MESSAGE_ARRAY")?,
+            public_history_depth: env::var("CAMPFIRE_PUBLIC_HISTORY_DEPTH")
+                .unwrap_or_else(|_| "500".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_PUBLIC_HISTORY_DEPTH")?,
+            max_batch_ids: env::var("CAMPFIRE_MAX_BATCH_IDS")
+                .unwrap_or_else(|_| "100".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MAX_BATCH_IDS")?,
+        })
+    }
+}
+
+impl StorageConfig {
+    fn from_env() -> Result<Self> {
+        let backend = match env::var("CAMPFIRE_STORAGE_BACKEND")
+            .unwrap_or_else(|_| "local".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "s3" => StorageBackend::S3,
+            _ => StorageBackend::Local,
+        };
+        
+        // S3 is only considered configured when bucket and credentials are present
+        let s3 = match (
+            env::var("CAMPFIRE_S3_BUCKET"),
+            env::var("CAMPFIRE_S3_ACCESS_KEY_ID"),
+            env::var("CAMPFIRE_S3_SECRET_ACCESS_KEY"),
+        ) {
+            (Ok(bucket), Ok(access_key_id), Ok(secret_access_key)) => Some(S3Config {
+                endpoint: env::var("CAMPFIRE_S3_ENDPOINT")
+                    .unwrap_or_else(|_| "https://s3.amazonaws.com".to_string()),
+                bucket,
+                region: env::var("CAMPFIRE_S3_REGION")
+                    .unwrap_or_else(|_| "us-east-1".to_string()),
+                access_key_id,
+                secret_access_key,
+                path_style: env::var("CAMPFIRE_S3_PATH_STYLE")
+                    .unwrap_or_else(|_| "true".to_string())
+                    .parse()
+                    .context("Invalid CAMPFIRE_S3_PATH_STYLE")?,
+            }),
+            _ => None,
+        };
+        
+        Ok(StorageConfig {
+            backend,
+            local_path: env::var("CAMPFIRE_STORAGE_PATH")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from("uploads")),
+            s3,
+            presign_expiry_secs: env::var("CAMPFIRE_STORAGE_PRESIGN_EXPIRY")
+                .unwrap_or_else(|_| "900".to_string()) // 15 minutes
+                .parse()
+                .context("Invalid CAMPFIRE_STORAGE_PRESIGN_EXPIRY")?,
+            quota_bytes: env::var("CAMPFIRE_STORAGE_QUOTA_BYTES")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_STORAGE_QUOTA_BYTES")?,
+            user_quota_bytes: env::var("CAMPFIRE_STORAGE_USER_QUOTA_BYTES")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_STORAGE_USER_QUOTA_BYTES")?,
+            max_concurrent_uploads: env::var("CAMPFIRE_MAX_CONCURRENT_UPLOADS")
+                .unwrap_or_else(|_| "4".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MAX_CONCURRENT_UPLOADS")?,
+            max_attachments_per_message: env::var("CAMPFIRE_MAX_ATTACHMENTS_PER_MESSAGE")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_MAX_ATTACHMENTS_PER_MESSAGE")?,
+            max_attachment_bytes_per_message: env::var("CAMPFIRE_MAX_ATTACHMENT_BYTES_PER_MESSAGE")
+                .unwrap_or_else(|_| "52428800".to_string()) // 50MB
+                .parse()
+                .context("Invalid CAMPFIRE_MAX_ATTACHMENT_BYTES_PER_MESSAGE")?,
+            clamav_address: env::var("CAMPFIRE_CLAMAV_ADDRESS").ok().filter(|v| !v.trim().is_empty()),
+            scan_timeout_ms: env::var("CAMPFIRE_SCAN_TIMEOUT_MS")
+                .unwrap_or_else(|_| "5000".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_SCAN_TIMEOUT_MS")?,
+            allowed_attachment_types: env::var("CAMPFIRE_ATTACHMENT_TYPES")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            previewable_attachment_types: env::var("CAMPFIRE_PREVIEWABLE_ATTACHMENT_TYPES")
+                .unwrap_or_else(|_| "image/png,image/jpeg,image/gif,image/webp".to_string())
+                .split(',')
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            attachment_origin: env::var("CAMPFIRE_ATTACHMENT_ORIGIN")
+                .ok()
+                .map(|v| v.trim().trim_end_matches('/').to_string())
+                .filter(|v| !v.is_empty()),
+            attachment_signing_secret: env::var("CAMPFIRE_ATTACHMENT_SIGNING_SECRET")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            thumbnail_max_dimension: env::var("CAMPFIRE_THUMBNAIL_MAX_DIMENSION")
+                .unwrap_or_else(|_| "320".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_THUMBNAIL_MAX_DIMENSION")?,
+            thumbnail_max_source_dimension: env::var("CAMPFIRE_THUMBNAIL_MAX_SOURCE_DIMENSION")
+                .unwrap_or_else(|_| "4096".to_string())
+                .parse()
+                .context("Invalid CAMPFIRE_THUMBNAIL_MAX_SOURCE_DIMENSION")?,
+            thumbnail_max_decode_bytes: env::var("CAMPFIRE_THUMBNAIL_MAX_DECODE_BYTES")
+                .unwrap_or_else(|_| "67108864".to_string()) // 64MB
+                .parse()
+                .context("Invalid CAMPFIRE_THUMBNAIL_MAX_DECODE_BYTES")?,
+        })
+    }
+}
+
+impl AdminSeedConfig {
+    /// Reads the seed admin; email and password must be set together
+    pub fn from_env() -> Result<Option<Self>> {
+        Self::from_lookup(|key| env::var(key).ok())
+    }
+    
+    /// `from_env` over any source of variables
+    fn from_lookup(var: impl Fn(&str) -> Option<String>) -> Result<Option<Self>> {
+        let email = var("CAMPFIRE_ADMIN_EMAIL").filter(|v| !v.trim().is_empty());
+        let password = var("CAMPFIRE_ADMIN_PASSWORD").filter(|v| !v.is_empty());
+        
+        match (email, password) {
+            (Some(email), Some(password)) => Ok(Some(AdminSeedConfig {
+                email,
+                password,
+                name: var("CAMPFIRE_ADMIN_NAME")
+                    .unwrap_or_else(|| "Administrator".to_string()),
+            })),
+            (None, None) => Ok(None),
+            _ => Err(anyhow::anyhow!(
+                "CAMPFIRE_ADMIN_EMAIL and CAMPFIRE_ADMIN_PASSWORD must be set together"
+            )),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -642,4 +2073,113 @@ mod tests {
         
         env::remove_var("CAMPFIRE_SESSION_TOKEN_LENGTH");
     }
+    
+    /// Loads each section without validating, pinning the values other tests
+    /// change through the environment
+    fn unvalidated_config() -> Config {
+        let mut config = Config {
+            server: ServerConfig::from_env().unwrap(),
+            database: DatabaseConfig::from_env().unwrap(),
+            logging: LoggingConfig::from_env().unwrap(),
+            security: SecurityConfig::from_env().unwrap(),
+            messages: MessagesConfig::from_env().unwrap(),
+            pagination: PaginationConfig::from_env().unwrap(),
+            push: PushConfig::from_env().unwrap(),
+            mail: MailConfig::from_env().unwrap(),
+            metrics: MetricsConfig::from_env().unwrap(),
+            features: FeatureFlags::from_env().unwrap(),
+            cache: CacheConfig::from_env().unwrap(),
+            storage: StorageConfig::from_env().unwrap(),
+            admin_seed: None,
+        };
+        config.security.session_token_length = 32;
+        config.push.vapid_private_key = Some("test_private_key".to_string());
+        config.push.vapid_public_key = Some("test_public_key".to_string());
+        config
+    }
+    
+    fn errors(config: &Config) -> Vec<String> {
+        config.validate().iter().filter(|d| d.is_error()).map(ToString::to_string).collect()
+    }
+    
+    #[test]
+    fn test_validate_reports_every_error() {
+        let mut config = unvalidated_config();
+        assert!(errors(&config).is_empty());
+        
+        config.database.max_connections = 0;
+        config.push.vapid_private_key = None;
+        config.security.cors_origins = vec!["https://chat.example.com".to_string(), "*".to_string()];
+        
+        let errors = errors(&config);
+        assert_eq!(errors.len(), 3);
+        assert!(errors.contains(&"Database max connections must be greater than 0".to_string()));
+        assert!(errors.contains(&"VAPID keys required when push notifications are enabled".to_string()));
+        assert!(errors.iter().any(|e| e.contains("CAMPFIRE_CORS_ORIGINS cannot contain '*'")));
+    }
+    
+    #[test]
+    fn test_validate_requires_sanitize_last() {
+        let mut config = unvalidated_config();
+        for pipeline in [vec!["sounds", "mentions"], vec!["sanitize", "mentions"]] {
+            config.messages.content_pipeline = pipeline.iter().map(ToString::to_string).collect();
+            assert_eq!(errors(&config).len(), 1, "{:?}", pipeline);
+            assert!(errors(&config)[0].contains("sanitize"));
+        }
+    }
+    
+    #[test]
+    fn test_validate_warns_without_failing() {
+        let mut config = unvalidated_config();
+        config.push.enabled = false;
+        config.security.force_https = true;
+        config.security.cors_origins = Vec::new();
+        config.server.request_timeout_secs = 60;
+        config.server.shutdown_timeout_secs = 10;
+        
+        let diagnostics = config.validate();
+        assert!(diagnostics.iter().all(|d| !d.is_error()));
+        assert!(diagnostics.contains(&ConfigDiagnostic::Warning(
+            "VAPID keys are set but push notifications are disabled".to_string()
+        )));
+        assert!(diagnostics.iter().any(|d| d.to_string().contains("any origin may call the API")));
+        assert!(diagnostics.iter().any(|d| d.to_string().contains("Shutdown timeout (10s)")));
+    }
+    
+    #[test]
+    fn test_admin_seed_password_redacted() {
+        let seed = AdminSeedConfig {
+            email: "admin@example.com".to_string(),
+            password: "hunter2hunter2".to_string(),
+            name: "Administrator".to_string(),
+        };
+        
+        assert!(!format!("{:?}", seed).contains("hunter2"));
+        assert!(!serde_json::to_string(&seed).unwrap().contains("hunter2"));
+    }
+    
+    #[test]
+    fn test_admin_seed_needs_email_and_password_together() {
+        let lookup = |vars: &'static [(&'static str, &'static str)]| {
+            move |key: &str| vars.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string())
+        };
+        
+        let seed = AdminSeedConfig::from_lookup(lookup(&[
+            ("CAMPFIRE_ADMIN_EMAIL", "Ops@Example.com"),
+            ("CAMPFIRE_ADMIN_PASSWORD", "bootstrap123"),
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(seed.email, "Ops@Example.com");
+        assert_eq!(seed.password, "bootstrap123");
+        assert_eq!(seed.name, "Administrator");
+        
+        assert!(AdminSeedConfig::from_lookup(lookup(&[])).unwrap().is_none());
+        assert!(AdminSeedConfig::from_lookup(lookup(&[("CAMPFIRE_ADMIN_EMAIL", "ops@example.com")])).is_err());
+        assert!(AdminSeedConfig::from_lookup(lookup(&[
+            ("CAMPFIRE_ADMIN_EMAIL", "  "),
+            ("CAMPFIRE_ADMIN_PASSWORD", "bootstrap123"),
+        ]))
+        .is_err());
+    }
 }
\ No newline at end of file
@@ -2,9 +2,16 @@ use sqlx::{sqlite::SqlitePool, Row, Sqlite, Transaction};
 use anyhow::Result;
 use crate::errors::DatabaseError;
 use crate::models::*;
+use crate::rich_text::{RichTextProcessor, ROOM_WIDE_MENTIONS};
+use crate::sounds::SoundPolicy;
 use tokio::sync::{mpsc, oneshot};
+use dashmap::DashMap;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 
 pub mod optimized_pool;
 pub use optimized_pool::{OptimizedConnectionPool, PoolConfig};
@@ -15,6 +22,17 @@ pub use optimized_pool::{OptimizedConnectionPool, PoolConfig};
 /// to prevent SQLite write conflicts and ensure data consistency.
 #[async_trait]
 pub trait DatabaseWriter: Send + Sync {
+    /// Writes queued behind the one in progress; a measure of write load
+    fn queue_depth(&self) -> usize {
+        0
+    }
+    
+    /// Stops accepting writes and applies those already queued, giving up
+    /// after `timeout`
+    async fn drain(&self, _timeout: Duration) -> Result<WriterDrainReport, DatabaseError> {
+        Ok(WriterDrainReport::default())
+    }
+    
     /// Create a new user
     async fn create_user(&self, user: User) -> Result<(), DatabaseError>;
     
@@ -25,7 +43,11 @@ pub trait DatabaseWriter: Send + Sync {
     async fn delete_session(&self, token: String) -> Result<(), DatabaseError>;
     
     /// Create a message with deduplication
-    async fn create_message_with_deduplication(&self, message: Message) -> Result<Message, DatabaseError>;
+    ///
+    /// A `client_message_id` is deduplicated per room for as long as the original
+    /// message is retained; once retention purges it, the key is released too.
+    /// The result says whether a row was inserted or the stored one came back.
+    async fn create_message_with_deduplication(&self, message: Message) -> Result<MessageWrite, DatabaseError>;
     
     /// Create a new room
     async fn create_room(&self, room: Room) -> Result<(), DatabaseError>;
@@ -38,6 +60,152 @@ pub trait DatabaseWriter: Send + Sync {
     
     /// Update notification preferences
     async fn update_notification_preferences(&self, preferences: NotificationPreferences) -> Result<(), DatabaseError>;
+    
+    /// Delete every session belonging to a user
+    async fn delete_user_sessions(&self, user_id: UserId) -> Result<u64, DatabaseError>;
+    
+    /// Delete messages created before the cutoff (retention)
+    async fn purge_messages_before(&self, cutoff: DateTime<Utc>) -> Result<u64, DatabaseError>;
+    
+    /// Delete messages past their room's retention: the room's own
+    /// `retention_days` when set (0 = legal hold, never purged), otherwise
+    /// `default_retention_days` (0 = keep forever)
+    async fn purge_expired_messages(&self, default_retention_days: u64, now: DateTime<Utc>) -> Result<u64, DatabaseError>;
+    
+    /// Set a room's retention override in days; None inherits the global
+    /// setting and 0 holds its messages indefinitely
+    async fn set_room_retention(&self, room_id: RoomId, retention_days: Option<u32>) -> Result<(), DatabaseError>;
+    
+    /// Store a new single-use token
+    async fn create_token(&self, token: Token) -> Result<(), DatabaseError>;
+    
+    /// Mark a token used if it is of `kind`, unused and unexpired, returning it
+    async fn consume_token(&self, token: String, kind: TokenKind) -> Result<Option<Token>, DatabaseError>;
+    
+    /// Delete tokens that have expired or been used
+    async fn purge_expired_tokens(&self) -> Result<u64, DatabaseError>;
+    
+    /// Advances the user's read marker in the message's room
+    async fn update_read_marker(&self, user_id: UserId, message_id: MessageId) -> Result<(), DatabaseError>;
+    
+    /// Changes a room's type (open/closed)
+    async fn update_room_type(&self, room_id: RoomId, room_type: RoomType) -> Result<(), DatabaseError>;
+    
+    /// Returns the open room with the same name, or creates it with the creator as admin
+    async fn resolve_or_create_open_room(&self, room: Room, creator_id: UserId) -> Result<(Room, bool), DatabaseError>;
+    
+    /// Records activity on a session, for the idle timeout
+    async fn touch_session(&self, token: String, at: DateTime<Utc>) -> Result<(), DatabaseError>;
+    
+    /// Sets a per-user or per-room feature flag override
+    async fn set_feature_override(&self, scope: FeatureScope, flag: String, enabled: bool) -> Result<(), DatabaseError>;
+    
+    /// Removes a feature flag override, falling back to the next layer
+    async fn clear_feature_override(&self, scope: FeatureScope, flag: String) -> Result<(), DatabaseError>;
+    
+    /// Archive non-direct rooms with no activity since the cutoff, returning the rooms archived
+    async fn archive_inactive_rooms(&self, cutoff: DateTime<Utc>) -> Result<Vec<RoomId>, DatabaseError>;
+    
+    /// Change who may post in a room
+    async fn update_room_post_permission(&self, room_id: RoomId, permission: PostPermission) -> Result<(), DatabaseError>;
+    
+    /// Grant or revoke a user's right to post in an admins-only room
+    async fn set_room_post_grant(&self, room_id: RoomId, user_id: UserId, granted: bool) -> Result<(), DatabaseError>;
+    
+    /// Records a stored blob, returning the entry it replaced
+    async fn record_blob(&self, key: String, user_id: Option<UserId>, room_id: Option<RoomId>, size_bytes: u64, content_type: String, quarantined: bool) -> Result<Option<BlobUsage>, DatabaseError>;
+    
+    /// Forgets a deleted blob, returning what it accounted for
+    async fn delete_blob_record(&self, key: String) -> Result<Option<BlobUsage>, DatabaseError>;
+    
+    /// Add an outbound webhook to a room
+    async fn create_room_webhook(&self, webhook: RoomWebhook) -> Result<(), DatabaseError>;
+    
+    /// Enable or disable a room webhook; false when it doesn't exist
+    async fn set_room_webhook_enabled(&self, webhook_id: RoomWebhookId, enabled: bool) -> Result<bool, DatabaseError>;
+    
+    /// Remove a room webhook; false when it doesn't exist
+    async fn delete_room_webhook(&self, webhook_id: RoomWebhookId) -> Result<bool, DatabaseError>;
+    
+    /// Set (or replace) the room's bridge to an external chat
+    async fn set_room_bridge(&self, bridge: RoomBridge) -> Result<(), DatabaseError>;
+    
+    /// Remove the room's bridge; false when it had none
+    async fn delete_room_bridge(&self, room_id: RoomId) -> Result<bool, DatabaseError>;
+    
+    /// Toggle anonymous read-only access for an open room
+    async fn set_room_public(&self, room_id: RoomId, public: bool) -> Result<(), DatabaseError>;
+    
+    /// Set or clear (None) the URL a bot's webhook is delivered to
+    async fn set_bot_webhook_url(&self, bot_id: UserId, url: Option<String>) -> Result<(), DatabaseError>;
+    
+    /// Marks a blob as held back pending a virus scan, or releases it
+    async fn set_blob_quarantined(&self, key: String, quarantined: bool) -> Result<(), DatabaseError>;
+    
+    /// Records the blobs a message was posted with; keys already linked are skipped
+    async fn link_message_attachments(&self, message_id: MessageId, keys: Vec<String>) -> Result<(), DatabaseError>;
+    
+    /// Overrides the room's sound cooldown (None = use the configured default)
+    async fn set_room_sound_cooldown(&self, room_id: RoomId, cooldown_secs: Option<u64>) -> Result<(), DatabaseError>;
+    
+    /// Sets the content types attachments in the room may have (None = use
+    /// the configured allowlist)
+    async fn set_room_sound_policy(&self, room_id: RoomId, policy: SoundPolicy) -> Result<(), DatabaseError>;
+    async fn set_room_attachment_types(&self, room_id: RoomId, types: Option<Vec<String>>) -> Result<(), DatabaseError>;
+    
+    /// Creates or replaces a room's canned response
+    async fn set_canned_response(&self, room_id: RoomId, shortcut: String, content: String) -> Result<(), DatabaseError>;
+    
+    /// Removes a room's canned response; false if there was none
+    async fn delete_canned_response(&self, room_id: RoomId, shortcut: String) -> Result<bool, DatabaseError>;
+    
+    /// Removes a user from a room; false if they weren't a member
+    async fn delete_membership(&self, room_id: RoomId, user_id: UserId) -> Result<bool, DatabaseError>;
+    
+    /// Deletes a room and everything stored under it; None if it didn't
+    /// exist, else the keys of the room's blobs, which are left for the
+    /// caller to remove from the blob store
+    async fn delete_room_permanently(&self, room_id: RoomId) -> Result<Option<Vec<String>>, DatabaseError>;
+    
+    /// Rebuild messages_fts from messages in one transaction; returns rows indexed
+    async fn rebuild_search_index(&self) -> Result<u64, DatabaseError>;
+    
+    /// VACUUM and optimize the database file. Writes queue behind it, and it
+    /// holds an exclusive lock for as long as rewriting the file takes.
+    async fn vacuum(&self) -> Result<VacuumReport, DatabaseError>;
+    
+    /// Store a user's IANA timezone; false if the user doesn't exist
+    async fn set_user_timezone(&self, user_id: UserId, timezone: String) -> Result<bool, DatabaseError>;
+    
+    /// Turns the room's mentions-only push notification mode on or off
+    async fn set_room_notify_mentions_only(&self, room_id: RoomId, mentions_only: bool) -> Result<(), DatabaseError>;
+    
+    /// Lets a bot be added to rooms it's mentioned in by an admin
+    async fn set_bot_auto_join_on_mention(&self, bot_id: UserId, enabled: bool) -> Result<(), DatabaseError>;
+    
+    /// Lets a bot post messages attributed to other users
+    async fn set_bot_post_on_behalf(&self, bot_id: UserId, enabled: bool) -> Result<(), DatabaseError>;
+    
+    /// Revokes a bot's token so it can no longer authenticate; its user and
+    /// messages are kept
+    async fn deactivate_bot(&self, bot_id: UserId) -> Result<(), DatabaseError>;
+    
+    /// Records the bot that actually posted a message attributed to a user
+    async fn record_bot_attribution(&self, message_id: MessageId, bot_id: UserId) -> Result<(), DatabaseError>;
+    
+    /// Saves a message to the user's private list; saving it again is a no-op.
+    /// Returns false, saving nothing, when the user already has `max_saved`
+    /// messages saved (0 = unlimited).
+    async fn save_message(&self, user_id: UserId, message_id: MessageId, room_id: RoomId, max_saved: u32) -> Result<bool, DatabaseError>;
+    
+    /// Removes a message from the user's saved list, returning whether it was there
+    async fn unsave_message(&self, user_id: UserId, message_id: MessageId) -> Result<bool, DatabaseError>;
+    
+    /// Create a session for `session.user_id` on behalf of an admin
+    async fn create_impersonation_session(&self, session: Session, impersonated_by: UserId) -> Result<(), DatabaseError>;
+    
+    /// Moves the user's last-seen time forward to `at`; earlier times are ignored
+    async fn set_last_seen(&self, user_id: UserId, at: DateTime<Utc>) -> Result<(), DatabaseError>;
 }
 
 /// Write operations that can be sent to the writer task
@@ -57,7 +225,7 @@ pub enum WriteOperation {
     },
     CreateMessageWithDeduplication {
         message: Message,
-        respond_to: oneshot::Sender<Result<Message, DatabaseError>>,
+        respond_to: oneshot::Sender<Result<MessageWrite, DatabaseError>>,
     },
     CreateRoom {
         room: Room,
@@ -75,63 +243,543 @@ pub enum WriteOperation {
         preferences: NotificationPreferences,
         respond_to: oneshot::Sender<Result<(), DatabaseError>>,
     },
+    DeleteUserSessions {
+        user_id: UserId,
+        respond_to: oneshot::Sender<Result<u64, DatabaseError>>,
+    },
+    PurgeExpiredMessages {
+        default_retention_days: u64,
+        now: DateTime<Utc>,
+        respond_to: oneshot::Sender<Result<u64, DatabaseError>>,
+    },
+    SetRoomRetention {
+        room_id: RoomId,
+        retention_days: Option<u32>,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    PurgeMessagesBefore {
+        cutoff: DateTime<Utc>,
+        respond_to: oneshot::Sender<Result<u64, DatabaseError>>,
+    },
+    CreateToken {
+        token: Token,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    ConsumeToken {
+        token: String,
+        kind: TokenKind,
+        respond_to: oneshot::Sender<Result<Option<Token>, DatabaseError>>,
+    },
+    PurgeExpiredTokens {
+        respond_to: oneshot::Sender<Result<u64, DatabaseError>>,
+    },
+    UpdateReadMarker {
+        user_id: UserId,
+        message_id: MessageId,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    UpdateRoomType {
+        room_id: RoomId,
+        room_type: RoomType,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    ResolveOrCreateOpenRoom {
+        room: Room,
+        creator_id: UserId,
+        respond_to: oneshot::Sender<Result<(Room, bool), DatabaseError>>,
+    },
+    TouchSession {
+        token: String,
+        at: DateTime<Utc>,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    SetFeatureOverride {
+        scope: FeatureScope,
+        flag: String,
+        enabled: bool,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    ClearFeatureOverride {
+        scope: FeatureScope,
+        flag: String,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    ArchiveInactiveRooms {
+        cutoff: DateTime<Utc>,
+        respond_to: oneshot::Sender<Result<Vec<RoomId>, DatabaseError>>,
+    },
+    UpdateRoomPostPermission {
+        room_id: RoomId,
+        permission: PostPermission,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    SetRoomPostGrant {
+        room_id: RoomId,
+        user_id: UserId,
+        granted: bool,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    RecordBlob {
+        key: String,
+        user_id: Option<UserId>,
+        room_id: Option<RoomId>,
+        size_bytes: u64,
+        content_type: String,
+        quarantined: bool,
+        respond_to: oneshot::Sender<Result<Option<BlobUsage>, DatabaseError>>,
+    },
+    DeleteBlobRecord {
+        key: String,
+        respond_to: oneshot::Sender<Result<Option<BlobUsage>, DatabaseError>>,
+    },
+    CreateRoomWebhook {
+        webhook: RoomWebhook,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    SetRoomWebhookEnabled {
+        webhook_id: RoomWebhookId,
+        enabled: bool,
+        respond_to: oneshot::Sender<Result<bool, DatabaseError>>,
+    },
+    DeleteRoomWebhook {
+        webhook_id: RoomWebhookId,
+        respond_to: oneshot::Sender<Result<bool, DatabaseError>>,
+    },
+    SetRoomBridge {
+        bridge: RoomBridge,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    DeleteRoomBridge {
+        room_id: RoomId,
+        respond_to: oneshot::Sender<Result<bool, DatabaseError>>,
+    },
+    SetRoomPublic {
+        room_id: RoomId,
+        public: bool,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    SetBotWebhookUrl {
+        bot_id: UserId,
+        url: Option<String>,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    SetBlobQuarantined {
+        key: String,
+        quarantined: bool,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    LinkMessageAttachments {
+        message_id: MessageId,
+        keys: Vec<String>,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    SetRoomSoundCooldown {
+        room_id: RoomId,
+        cooldown_secs: Option<u64>,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    SetRoomSoundPolicy {
+        room_id: RoomId,
+        policy: SoundPolicy,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    SetCannedResponse {
+        room_id: RoomId,
+        shortcut: String,
+        content: String,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    DeleteCannedResponse {
+        room_id: RoomId,
+        shortcut: String,
+        respond_to: oneshot::Sender<Result<bool, DatabaseError>>,
+    },
+    SetRoomAttachmentTypes {
+        room_id: RoomId,
+        types: Option<Vec<String>>,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    DeleteMembership {
+        room_id: RoomId,
+        user_id: UserId,
+        respond_to: oneshot::Sender<Result<bool, DatabaseError>>,
+    },
+    DeleteRoomPermanently {
+        room_id: RoomId,
+        respond_to: oneshot::Sender<Result<Option<Vec<String>>, DatabaseError>>,
+    },
+    RebuildSearchIndex {
+        respond_to: oneshot::Sender<Result<u64, DatabaseError>>,
+    },
+    Vacuum {
+        respond_to: oneshot::Sender<Result<VacuumReport, DatabaseError>>,
+    },
+    SetUserTimezone {
+        user_id: UserId,
+        timezone: String,
+        respond_to: oneshot::Sender<Result<bool, DatabaseError>>,
+    },
+    SetRoomNotifyMentionsOnly {
+        room_id: RoomId,
+        mentions_only: bool,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    SetBotAutoJoinOnMention {
+        bot_id: UserId,
+        enabled: bool,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    SetBotPostOnBehalf {
+        bot_id: UserId,
+        enabled: bool,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    DeactivateBot {
+        bot_id: UserId,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    RecordBotAttribution {
+        message_id: MessageId,
+        bot_id: UserId,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    SaveMessage {
+        user_id: UserId,
+        message_id: MessageId,
+        room_id: RoomId,
+        max_saved: u32,
+        respond_to: oneshot::Sender<Result<bool, DatabaseError>>,
+    },
+    UnsaveMessage {
+        user_id: UserId,
+        message_id: MessageId,
+        respond_to: oneshot::Sender<Result<bool, DatabaseError>>,
+    },
+    CreateImpersonationSession {
+        session: Session,
+        impersonated_by: UserId,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
+    SetLastSeen {
+        user_id: UserId,
+        at: DateTime<Utc>,
+        respond_to: oneshot::Sender<Result<(), DatabaseError>>,
+    },
 }
 
+/// Writes that can wait for the writer before senders block
+pub const WRITE_QUEUE_CAPACITY: usize = 1000;
+
 /// Database writer implementation that serializes all writes
 pub struct SerializedDatabaseWriter {
     write_sender: mpsc::Sender<WriteOperation>,
+    drain_sender: mpsc::Sender<DrainRequest>,
+}
+
+/// Asks the writer task to finish up before shutdown
+struct DrainRequest {
+    deadline: tokio::time::Instant,
+    respond_to: oneshot::Sender<WriterDrainReport>,
+}
+
+/// What happened to the writes queued when the writer was drained
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriterDrainReport {
+    /// Queued writes applied before the deadline
+    pub applied: usize,
+    /// Writes still queued at the deadline; their callers get
+    /// `WriterChannelClosed`
+    pub dropped: usize,
+}
+
+/// A deduplicated message write: the message as stored, and whether this
+/// write inserted it or found it already there under its `client_message_id`
+#[derive(Debug, Clone)]
+pub struct MessageWrite {
+    pub message: Message,
+    pub inserted: bool,
+}
+
+impl MessageWrite {
+    fn existing(message: Message) -> Self {
+        Self { message, inserted: false }
+    }
 }
 
 impl SerializedDatabaseWriter {
     /// Create a new serialized database writer with background task
     pub fn new(database: Database) -> Self {
-        let (write_sender, write_receiver) = mpsc::channel::<WriteOperation>(1000);
+        let (write_sender, write_receiver) = mpsc::channel::<WriteOperation>(WRITE_QUEUE_CAPACITY);
+        let (drain_sender, drain_receiver) = mpsc::channel::<DrainRequest>(1);
         
         // Spawn the writer task
-        tokio::spawn(Self::writer_task(database, write_receiver));
+        tokio::spawn(Self::writer_task(database, write_receiver, drain_receiver));
         
-        Self { write_sender }
+        Self { write_sender, drain_sender }
     }
     
     /// Background task that processes all write operations serially
     async fn writer_task(
         database: Database,
         mut write_receiver: mpsc::Receiver<WriteOperation>,
+        mut drain_receiver: mpsc::Receiver<DrainRequest>,
     ) {
-        while let Some(operation) = write_receiver.recv().await {
-            match operation {
-                WriteOperation::CreateUser { user, respond_to } => {
-                    let result = database.create_user_internal(&user).await;
-                    let _ = respond_to.send(result);
-                }
-                WriteOperation::CreateSession { session, respond_to } => {
-                    let result = database.create_session_internal(&session).await;
-                    let _ = respond_to.send(result);
-                }
-                WriteOperation::DeleteSession { token, respond_to } => {
-                    let result = database.delete_session_internal(&token).await;
-                    let _ = respond_to.send(result);
-                }
-                WriteOperation::CreateMessageWithDeduplication { message, respond_to } => {
-                    let result = database.create_message_with_deduplication_internal(&message).await;
-                    let _ = respond_to.send(result);
-                }
-                WriteOperation::CreateRoom { room, respond_to } => {
-                    let result = database.create_room_internal(&room).await;
-                    let _ = respond_to.send(result);
-                }
-                WriteOperation::CreateMembership { membership, respond_to } => {
-                    let result = database.create_membership_internal(&membership).await;
-                    let _ = respond_to.send(result);
-                }
-                WriteOperation::CreatePushSubscription { subscription, respond_to } => {
-                    let result = database.create_push_subscription_internal(&subscription).await;
-                    let _ = respond_to.send(result);
-                }
-                WriteOperation::UpdateNotificationPreferences { preferences, respond_to } => {
-                    let result = database.update_notification_preferences_internal(&preferences).await;
-                    let _ = respond_to.send(result);
+        loop {
+            let operation = tokio::select! {
+                operation = write_receiver.recv() => operation,
+                Some(request) = drain_receiver.recv() => {
+                    Self::drain(&database, write_receiver, request).await;
+                    return;
                 }
+            };
+            let Some(operation) = operation else { return };
+            metrics::gauge!("database_writer_queue_depth", write_receiver.len() as f64);
+            Self::apply(&database, operation).await;
+        }
+    }
+    
+    /// Stops accepting writes, then applies the ones already queued until the
+    /// deadline. Writes still queued after it are dropped.
+    async fn drain(
+        database: &Database,
+        mut write_receiver: mpsc::Receiver<WriteOperation>,
+        request: DrainRequest,
+    ) {
+        // New sends fail from here on, but queued writes can still be received
+        write_receiver.close();
+        
+        let mut report = WriterDrainReport::default();
+        let finished = tokio::time::timeout_at(request.deadline, async {
+            while let Some(operation) = write_receiver.recv().await {
+                Self::apply(database, operation).await;
+                report.applied += 1;
+            }
+        })
+        .await;
+        if finished.is_err() {
+            report.dropped = write_receiver.len();
+        }
+        
+        let _ = request.respond_to.send(report);
+    }
+    
+    async fn apply(database: &Database, operation: WriteOperation) {
+        match operation {
+            WriteOperation::CreateUser { user, respond_to } => {
+                let result = database.create_user_internal(&user).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::CreateSession { session, respond_to } => {
+                let result = database.create_session_internal(&session).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::DeleteSession { token, respond_to } => {
+                let result = database.delete_session_internal(&token).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::CreateMessageWithDeduplication { message, respond_to } => {
+                let result = database.create_message_with_deduplication_internal(&message).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::CreateRoom { room, respond_to } => {
+                let result = database.create_room_internal(&room).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::CreateMembership { membership, respond_to } => {
+                let result = database.create_membership_internal(&membership).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::CreatePushSubscription { subscription, respond_to } => {
+                let result = database.create_push_subscription_internal(&subscription).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::UpdateNotificationPreferences { preferences, respond_to } => {
+                let result = database.update_notification_preferences_internal(&preferences).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::DeleteUserSessions { user_id, respond_to } => {
+                let result = database.delete_user_sessions_internal(user_id).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::PurgeExpiredMessages { default_retention_days, now, respond_to } => {
+                let result = database.purge_expired_messages_internal(default_retention_days, now).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SetRoomRetention { room_id, retention_days, respond_to } => {
+                let result = database.set_room_retention_internal(room_id, retention_days).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::PurgeMessagesBefore { cutoff, respond_to } => {
+                let result = database.purge_messages_before_internal(cutoff).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::CreateToken { token, respond_to } => {
+                let result = database.create_token_internal(&token).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::ConsumeToken { token, kind, respond_to } => {
+                let result = database.consume_token_internal(&token, kind).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::PurgeExpiredTokens { respond_to } => {
+                let result = database.purge_expired_tokens_internal().await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::UpdateReadMarker { user_id, message_id, respond_to } => {
+                let result = database.update_read_marker_internal(user_id, message_id).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::UpdateRoomType { room_id, room_type, respond_to } => {
+                let result = database.update_room_type_internal(room_id, &room_type).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::ResolveOrCreateOpenRoom { room, creator_id, respond_to } => {
+                let result = database.resolve_or_create_open_room_internal(&room, creator_id).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::TouchSession { token, at, respond_to } => {
+                let result = database.touch_session_internal(&token, at).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SetFeatureOverride { scope, flag, enabled, respond_to } => {
+                let result = database.set_feature_override_internal(&scope, &flag, enabled).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::ClearFeatureOverride { scope, flag, respond_to } => {
+                let result = database.clear_feature_override_internal(&scope, &flag).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::ArchiveInactiveRooms { cutoff, respond_to } => {
+                let result = database.archive_inactive_rooms_internal(cutoff).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::UpdateRoomPostPermission { room_id, permission, respond_to } => {
+                let result = database.update_room_post_permission_internal(room_id, permission).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SetRoomPostGrant { room_id, user_id, granted, respond_to } => {
+                let result = database.set_room_post_grant_internal(room_id, user_id, granted).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::RecordBlob { key, user_id, room_id, size_bytes, content_type, quarantined, respond_to } => {
+                let result = database.record_blob_internal(&key, user_id, room_id, size_bytes, &content_type, quarantined).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::DeleteBlobRecord { key, respond_to } => {
+                let result = database.delete_blob_record_internal(&key).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::CreateRoomWebhook { webhook, respond_to } => {
+                let result = database.create_room_webhook_internal(&webhook).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SetRoomWebhookEnabled { webhook_id, enabled, respond_to } => {
+                let result = database.set_room_webhook_enabled_internal(webhook_id, enabled).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::DeleteRoomWebhook { webhook_id, respond_to } => {
+                let result = database.delete_room_webhook_internal(webhook_id).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SetRoomBridge { bridge, respond_to } => {
+                let result = database.set_room_bridge_internal(&bridge).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::DeleteRoomBridge { room_id, respond_to } => {
+                let result = database.delete_room_bridge_internal(room_id).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SetRoomPublic { room_id, public, respond_to } => {
+                let result = database.set_room_public_internal(room_id, public).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SetBotWebhookUrl { bot_id, url, respond_to } => {
+                let result = database.set_bot_webhook_url_internal(bot_id, url).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SetBlobQuarantined { key, quarantined, respond_to } => {
+                let result = database.set_blob_quarantined_internal(&key, quarantined).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::LinkMessageAttachments { message_id, keys, respond_to } => {
+                let result = database.link_message_attachments_internal(message_id, &keys).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SetRoomSoundCooldown { room_id, cooldown_secs, respond_to } => {
+                let result = database.set_room_sound_cooldown_internal(room_id, cooldown_secs).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SetRoomSoundPolicy { room_id, policy, respond_to } => {
+                let result = database.set_room_sound_policy_internal(room_id, policy).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SetCannedResponse { room_id, shortcut, content, respond_to } => {
+                let result = database.set_canned_response_internal(room_id, &shortcut, &content).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::DeleteCannedResponse { room_id, shortcut, respond_to } => {
+                let result = database.delete_canned_response_internal(room_id, &shortcut).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SetRoomAttachmentTypes { room_id, types, respond_to } => {
+                let result = database.set_room_attachment_types_internal(room_id, types).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::DeleteMembership { room_id, user_id, respond_to } => {
+                let result = database.delete_membership_internal(room_id, user_id).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::DeleteRoomPermanently { room_id, respond_to } => {
+                let result = database.delete_room_permanently_internal(room_id).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::RebuildSearchIndex { respond_to } => {
+                let result = database.rebuild_search_index_internal().await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::Vacuum { respond_to } => {
+                let result = database.vacuum_internal().await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SetUserTimezone { user_id, timezone, respond_to } => {
+                let result = database.set_user_timezone_internal(user_id, &timezone).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SetRoomNotifyMentionsOnly { room_id, mentions_only, respond_to } => {
+                let result = database.set_room_notify_mentions_only_internal(room_id, mentions_only).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SetBotAutoJoinOnMention { bot_id, enabled, respond_to } => {
+                let result = database.set_bot_auto_join_on_mention_internal(bot_id, enabled).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SetBotPostOnBehalf { bot_id, enabled, respond_to } => {
+                let result = database.set_bot_post_on_behalf_internal(bot_id, enabled).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::DeactivateBot { bot_id, respond_to } => {
+                let result = database.deactivate_bot_internal(bot_id).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::RecordBotAttribution { message_id, bot_id, respond_to } => {
+                let result = database.record_bot_attribution_internal(message_id, bot_id).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SaveMessage { user_id, message_id, room_id, max_saved, respond_to } => {
+                let result = database.save_message_internal(user_id, message_id, room_id, max_saved).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::UnsaveMessage { user_id, message_id, respond_to } => {
+                let result = database.unsave_message_internal(user_id, message_id).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::CreateImpersonationSession { session, impersonated_by, respond_to } => {
+                let result = database.create_impersonation_session_internal(&session, impersonated_by).await;
+                let _ = respond_to.send(result);
+            }
+            WriteOperation::SetLastSeen { user_id, at, respond_to } => {
+                let result = database.set_last_seen_internal(user_id, at).await;
+                let _ = respond_to.send(result);
             }
         }
     }
@@ -139,6 +787,25 @@ impl SerializedDatabaseWriter {
 
 #[async_trait]
 impl DatabaseWriter for SerializedDatabaseWriter {
+    fn queue_depth(&self) -> usize {
+        self.write_sender.max_capacity() - self.write_sender.capacity()
+    }
+    
+    async fn drain(&self, timeout: Duration) -> Result<WriterDrainReport, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.drain_sender
+            .send(DrainRequest {
+                deadline: tokio::time::Instant::now() + timeout,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)
+    }
+    
     async fn create_user(&self, user: User) -> Result<(), DatabaseError> {
         let (tx, rx) = oneshot::channel();
         
@@ -184,7 +851,7 @@ impl DatabaseWriter for SerializedDatabaseWriter {
             .map_err(|_| DatabaseError::WriterChannelClosed)?
     }
     
-    async fn create_message_with_deduplication(&self, message: Message) -> Result<Message, DatabaseError> {
+    async fn create_message_with_deduplication(&self, message: Message) -> Result<MessageWrite, DatabaseError> {
         let (tx, rx) = oneshot::channel();
         
         self.write_sender
@@ -258,636 +925,3621 @@ impl DatabaseWriter for SerializedDatabaseWriter {
         rx.await
             .map_err(|_| DatabaseError::WriterChannelClosed)?
     }
-}
-
-#[derive(Clone)]
-pub struct Database {
-    pool: SqlitePool,
-}
-
-impl Database {
-    pub async fn new(database_url: &str) -> Result<Self> {
-        // Create SQLite connection pool
-        let pool = SqlitePool::connect(database_url).await?;
+    
+    async fn delete_user_sessions(&self, user_id: UserId) -> Result<u64, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::DeleteUserSessions {
+                user_id,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn purge_expired_messages(&self, default_retention_days: u64, now: DateTime<Utc>) -> Result<u64, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::PurgeExpiredMessages {
+                default_retention_days,
+                now,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn set_room_retention(&self, room_id: RoomId, retention_days: Option<u32>) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SetRoomRetention {
+                room_id,
+                retention_days,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn purge_messages_before(&self, cutoff: DateTime<Utc>) -> Result<u64, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::PurgeMessagesBefore {
+                cutoff,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn create_token(&self, token: Token) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::CreateToken {
+                token,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn consume_token(&self, token: String, kind: TokenKind) -> Result<Option<Token>, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::ConsumeToken {
+                token,
+                kind,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn purge_expired_tokens(&self) -> Result<u64, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::PurgeExpiredTokens { respond_to: tx })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn update_read_marker(&self, user_id: UserId, message_id: MessageId) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::UpdateReadMarker {
+                user_id,
+                message_id,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn update_room_type(&self, room_id: RoomId, room_type: RoomType) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::UpdateRoomType {
+                room_id,
+                room_type,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn resolve_or_create_open_room(&self, room: Room, creator_id: UserId) -> Result<(Room, bool), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::ResolveOrCreateOpenRoom {
+                room,
+                creator_id,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn touch_session(&self, token: String, at: DateTime<Utc>) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::TouchSession {
+                token,
+                at,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn set_feature_override(&self, scope: FeatureScope, flag: String, enabled: bool) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SetFeatureOverride {
+                scope,
+                flag,
+                enabled,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn clear_feature_override(&self, scope: FeatureScope, flag: String) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::ClearFeatureOverride {
+                scope,
+                flag,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn archive_inactive_rooms(&self, cutoff: DateTime<Utc>) -> Result<Vec<RoomId>, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::ArchiveInactiveRooms {
+                cutoff,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn update_room_post_permission(&self, room_id: RoomId, permission: PostPermission) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::UpdateRoomPostPermission {
+                room_id,
+                permission,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn set_room_post_grant(&self, room_id: RoomId, user_id: UserId, granted: bool) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SetRoomPostGrant {
+                room_id,
+                user_id,
+                granted,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn record_blob(&self, key: String, user_id: Option<UserId>, room_id: Option<RoomId>, size_bytes: u64, content_type: String, quarantined: bool) -> Result<Option<BlobUsage>, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::RecordBlob {
+                key,
+                user_id,
+                room_id,
+                size_bytes,
+                content_type,
+                quarantined,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn delete_blob_record(&self, key: String) -> Result<Option<BlobUsage>, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::DeleteBlobRecord {
+                key,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn create_room_webhook(&self, webhook: RoomWebhook) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::CreateRoomWebhook {
+                webhook,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn set_room_webhook_enabled(&self, webhook_id: RoomWebhookId, enabled: bool) -> Result<bool, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SetRoomWebhookEnabled {
+                webhook_id,
+                enabled,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn delete_room_webhook(&self, webhook_id: RoomWebhookId) -> Result<bool, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::DeleteRoomWebhook {
+                webhook_id,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn set_room_bridge(&self, bridge: RoomBridge) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SetRoomBridge {
+                bridge,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn delete_room_bridge(&self, room_id: RoomId) -> Result<bool, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::DeleteRoomBridge {
+                room_id,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn set_room_public(&self, room_id: RoomId, public: bool) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SetRoomPublic {
+                room_id,
+                public,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn set_bot_webhook_url(&self, bot_id: UserId, url: Option<String>) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SetBotWebhookUrl {
+                bot_id,
+                url,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn set_blob_quarantined(&self, key: String, quarantined: bool) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SetBlobQuarantined {
+                key,
+                quarantined,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn link_message_attachments(&self, message_id: MessageId, keys: Vec<String>) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::LinkMessageAttachments {
+                message_id,
+                keys,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn set_room_sound_cooldown(&self, room_id: RoomId, cooldown_secs: Option<u64>) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SetRoomSoundCooldown {
+                room_id,
+                cooldown_secs,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn set_canned_response(&self, room_id: RoomId, shortcut: String, content: String) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SetCannedResponse {
+                room_id,
+                shortcut,
+                content,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn delete_canned_response(&self, room_id: RoomId, shortcut: String) -> Result<bool, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::DeleteCannedResponse {
+                room_id,
+                shortcut,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn set_room_sound_policy(&self, room_id: RoomId, policy: SoundPolicy) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SetRoomSoundPolicy {
+                room_id,
+                policy,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn set_room_attachment_types(&self, room_id: RoomId, types: Option<Vec<String>>) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SetRoomAttachmentTypes {
+                room_id,
+                types,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn delete_membership(&self, room_id: RoomId, user_id: UserId) -> Result<bool, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::DeleteMembership {
+                room_id,
+                user_id,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn delete_room_permanently(&self, room_id: RoomId) -> Result<Option<Vec<String>>, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::DeleteRoomPermanently {
+                room_id,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn rebuild_search_index(&self) -> Result<u64, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::RebuildSearchIndex {
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn vacuum(&self) -> Result<VacuumReport, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::Vacuum {
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn set_user_timezone(&self, user_id: UserId, timezone: String) -> Result<bool, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SetUserTimezone {
+                user_id,
+                timezone,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn set_room_notify_mentions_only(&self, room_id: RoomId, mentions_only: bool) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SetRoomNotifyMentionsOnly {
+                room_id,
+                mentions_only,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn set_bot_auto_join_on_mention(&self, bot_id: UserId, enabled: bool) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SetBotAutoJoinOnMention {
+                bot_id,
+                enabled,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn set_bot_post_on_behalf(&self, bot_id: UserId, enabled: bool) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SetBotPostOnBehalf {
+                bot_id,
+                enabled,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn deactivate_bot(&self, bot_id: UserId) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::DeactivateBot {
+                bot_id,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn record_bot_attribution(&self, message_id: MessageId, bot_id: UserId) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::RecordBotAttribution {
+                message_id,
+                bot_id,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn save_message(&self, user_id: UserId, message_id: MessageId, room_id: RoomId, max_saved: u32) -> Result<bool, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SaveMessage {
+                user_id,
+                message_id,
+                room_id,
+                max_saved,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn unsave_message(&self, user_id: UserId, message_id: MessageId) -> Result<bool, DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::UnsaveMessage {
+                user_id,
+                message_id,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn create_impersonation_session(&self, session: Session, impersonated_by: UserId) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::CreateImpersonationSession {
+                session,
+                impersonated_by,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+    
+    async fn set_last_seen(&self, user_id: UserId, at: DateTime<Utc>) -> Result<(), DatabaseError> {
+        let (tx, rx) = oneshot::channel();
+        
+        self.write_sender
+            .send(WriteOperation::SetLastSeen {
+                user_id,
+                at,
+                respond_to: tx,
+            })
+            .await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?;
+        
+        rx.await
+            .map_err(|_| DatabaseError::WriterChannelClosed)?
+    }
+}
+
+/// Connections the read pool opens at most
+pub const POOL_MAX_CONNECTIONS: u32 = 10;
+
+/// Longest @mention handle handed out
+const MAX_HANDLE_LENGTH: usize = 64;
+
+/// Recorded in `data_migrations` once existing HTML has been re-sanitized
+const RESANITIZE_HTML_MIGRATION: &str = "resanitize_html_content";
+
+/// Messages re-sanitized per page during that migration
+const RESANITIZE_HTML_PAGE_SIZE: i64 = 500;
+
+#[derive(Clone)]
+pub struct Database {
+    pool: SqlitePool,
+}
+
+impl Database {
+    pub async fn new(database_url: &str) -> Result<Self> {
+        // Create SQLite connection pool
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(POOL_MAX_CONNECTIONS)
+            .connect(database_url)
+            .await?;
         
         let db = Self { pool };
         
-        // Run migrations
-        db.migrate().await?;
+        // Run migrations
+        db.migrate().await?;
+        
+        Ok(db)
+    }
+    
+    /// Opens a read-only pool on a database someone else migrates, such as
+    /// a replica of the primary
+    pub async fn open_read_only(database_url: &str, max_connections: u32) -> Result<Self> {
+        let options = sqlx::sqlite::SqliteConnectOptions::from_str(database_url)?.read_only(true);
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .connect_with(options)
+            .await?;
+        
+        Ok(Self { pool })
+    }
+    
+    pub async fn migrate(&self) -> Result<()> {
+        // Create users table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS users (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                email TEXT UNIQUE NOT NULL,
+                password_hash TEXT NOT NULL,
+                bio TEXT,
+                admin BOOLEAN NOT NULL DEFAULT FALSE,
+                bot_token TEXT UNIQUE,
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create rooms table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS rooms (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                topic TEXT,
+                room_type TEXT NOT NULL CHECK (room_type IN ('open', 'closed', 'direct')),
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                last_message_at DATETIME,
+                archived_at DATETIME,
+                post_permission TEXT NOT NULL DEFAULT 'everyone',
+                public INTEGER NOT NULL DEFAULT 0
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        
+        let _ = sqlx::query("ALTER TABLE rooms ADD COLUMN archived_at DATETIME")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        
+        let _ = sqlx::query("ALTER TABLE rooms ADD COLUMN post_permission TEXT NOT NULL DEFAULT 'everyone'")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        
+        let _ = sqlx::query("ALTER TABLE rooms ADD COLUMN public INTEGER NOT NULL DEFAULT 0")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        
+        let _ = sqlx::query("ALTER TABLE rooms ADD COLUMN sound_cooldown_secs INTEGER")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        
+        // Per-room retention override in days; NULL inherits the global
+        // setting, 0 is a legal hold
+        let _ = sqlx::query("ALTER TABLE rooms ADD COLUMN retention_days INTEGER")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        
+        // JSON SoundPolicy; NULL lets every sound play
+        let _ = sqlx::query("ALTER TABLE rooms ADD COLUMN sound_policy TEXT")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        
+        // JSON array of content types; NULL inherits the configured allowlist
+        let _ = sqlx::query("ALTER TABLE rooms ADD COLUMN allowed_attachment_types TEXT")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        
+        // Bots that join a room when an admin mentions them there
+        let _ = sqlx::query("ALTER TABLE users ADD COLUMN bot_auto_join_on_mention INTEGER NOT NULL DEFAULT 0")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        
+        // Bots that may post messages attributed to other users
+        let _ = sqlx::query("ALTER TABLE users ADD COLUMN bot_post_on_behalf INTEGER NOT NULL DEFAULT 0")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        
+        // Large rooms can limit push notifications to messages with a mention
+        let _ = sqlx::query("ALTER TABLE rooms ADD COLUMN notify_mentions_only INTEGER NOT NULL DEFAULT 0")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        
+        // IANA zone name; NULL means UTC
+        let _ = sqlx::query("ALTER TABLE users ADD COLUMN timezone TEXT")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        
+        // When the user was last connected; NULL means never seen
+        let _ = sqlx::query("ALTER TABLE users ADD COLUMN last_seen_at DATETIME")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        
+        // Public name users are @mentioned by. Users from before the column
+        // existed get one, oldest first, so the oldest keeps the plain handle.
+        let _ = sqlx::query("ALTER TABLE users ADD COLUMN handle TEXT")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        let unhandled = sqlx::query("SELECT id, name, email, bot_token FROM users WHERE handle IS NULL ORDER BY created_at, rowid")
+            .fetch_all(&self.pool)
+            .await?;
+        for row in unhandled {
+            let bot_token: Option<String> = row.get("bot_token");
+            let handle = Self::free_handle(&self.pool, row.get("name"), row.get("email"), bot_token.is_some()).await?;
+            sqlx::query("UPDATE users SET handle = ? WHERE id = ?")
+                .bind(handle)
+                .bind(row.get::<String, _>("id"))
+                .execute(&self.pool)
+                .await?;
+        }
+        sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_users_handle ON users(handle)")
+            .execute(&self.pool)
+            .await?;
+
+        // Create messages table with UNIQUE constraint for Critical Gap #1
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS messages (
+                id TEXT PRIMARY KEY,
+                room_id TEXT NOT NULL REFERENCES rooms(id),
+                creator_id TEXT NOT NULL REFERENCES users(id),
+                content TEXT NOT NULL,
+                client_message_id TEXT NOT NULL,
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                html_content TEXT,
+                mentions TEXT,
+                sound_commands TEXT,
+                UNIQUE(client_message_id, room_id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        
+        // Room history and last-message lookups walk a room's messages by time
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_messages_room_created ON messages(room_id, created_at)")
+            .execute(&self.pool)
+            .await?;
+        
+        // Add rich text columns to existing messages table if they don't exist
+        // This handles the case where the table already exists without rich text fields
+        let _ = sqlx::query("ALTER TABLE messages ADD COLUMN html_content TEXT")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+            
+        let _ = sqlx::query("ALTER TABLE messages ADD COLUMN mentions TEXT")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+            
+        let _ = sqlx::query("ALTER TABLE messages ADD COLUMN sound_commands TEXT")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        
+        // Per-room position assigned by the writer; breaks created_at ties so
+        // a room's history has one total order. Messages stored before the
+        // column existed are numbered by time, then insertion order.
+        let _ = sqlx::query("ALTER TABLE messages ADD COLUMN seq INTEGER")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        sqlx::query(
+            r#"
+            UPDATE messages SET seq = numbered.seq
+            FROM (
+                SELECT id, ROW_NUMBER() OVER (PARTITION BY room_id ORDER BY created_at, rowid) AS seq
+                FROM messages
+            ) AS numbered
+            WHERE messages.id = numbered.id AND messages.seq IS NULL
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_messages_room_seq ON messages(room_id, seq)")
+            .execute(&self.pool)
+            .await?;
+        
+        // Last seq handed out in each room. Kept on the room rather than
+        // derived from MAX(seq) so a deleted message's number is never reused.
+        let _ = sqlx::query("ALTER TABLE rooms ADD COLUMN last_seq INTEGER NOT NULL DEFAULT 0")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        sqlx::query(
+            r#"
+            UPDATE rooms SET last_seq = numbered.max_seq
+            FROM (SELECT room_id, MAX(seq) AS max_seq FROM messages GROUP BY room_id) AS numbered
+            WHERE rooms.id = numbered.room_id AND rooms.last_seq < numbered.max_seq
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Open rooms are addressed by name (`#general`), so their names must
+        // be unique; closed and direct rooms may share names. Fails on a
+        // database that already holds duplicate open names, in which case
+        // lookups fall back to the oldest match until they're renamed.
+        if let Err(e) = sqlx::query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_rooms_open_name ON rooms(name COLLATE NOCASE) WHERE room_type = 'open'"
+        )
+        .execute(&self.pool)
+        .await
+        {
+            tracing::warn!("Could not create unique index on open room names: {}", e);
+        }
+
+        // Create room memberships table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS room_memberships (
+                room_id TEXT NOT NULL REFERENCES rooms(id),
+                user_id TEXT NOT NULL REFERENCES users(id),
+                involvement_level TEXT NOT NULL CHECK (involvement_level IN ('member', 'admin')),
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (room_id, user_id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create sessions table for authentication (Critical Gap #4)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS sessions (
+                token TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL REFERENCES users(id),
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                expires_at DATETIME NOT NULL,
+                last_active_at DATETIME
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        
+        let _ = sqlx::query("ALTER TABLE sessions ADD COLUMN last_active_at DATETIME")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        
+        // Single-use tokens of every kind (password reset, email
+        // verification, room invites), purged once expired or used
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS tokens (
+                token TEXT PRIMARY KEY,
+                kind TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                created_at DATETIME NOT NULL,
+                expires_at DATETIME NOT NULL,
+                used_at DATETIME
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_tokens_expires_at ON tokens(expires_at)")
+            .execute(&self.pool)
+            .await?;
+        
+        // Admin who opened the session while impersonating its user
+        let _ = sqlx::query("ALTER TABLE sessions ADD COLUMN impersonated_by TEXT")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+
+        // Create blobs table (size and owner of every stored upload, for quotas)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS blobs (
+                key TEXT PRIMARY KEY,
+                user_id TEXT REFERENCES users(id) ON DELETE SET NULL,
+                size_bytes INTEGER NOT NULL,
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        
+        let _ = sqlx::query("ALTER TABLE blobs ADD COLUMN quarantined INTEGER NOT NULL DEFAULT 0")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        
+        let _ = sqlx::query("ALTER TABLE blobs ADD COLUMN content_type TEXT")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        
+        let _ = sqlx::query("ALTER TABLE blobs ADD COLUMN room_id TEXT")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+
+        // Create room webhooks table (outbound URLs notified of new messages)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS room_webhooks (
+                id TEXT PRIMARY KEY,
+                room_id TEXT NOT NULL REFERENCES rooms(id) ON DELETE CASCADE,
+                url TEXT NOT NULL,
+                secret TEXT NOT NULL,
+                enabled BOOLEAN NOT NULL DEFAULT TRUE,
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create room bridges table (external chats a room is mirrored to)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS room_bridges (
+                room_id TEXT PRIMARY KEY REFERENCES rooms(id) ON DELETE CASCADE,
+                url TEXT NOT NULL,
+                format TEXT NOT NULL,
+                inbound_bot_id TEXT REFERENCES users(id) ON DELETE SET NULL,
+                enabled BOOLEAN NOT NULL DEFAULT TRUE,
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create bot webhooks table (where a bot's mentions are delivered)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS bot_webhooks (
+                bot_id TEXT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+                url TEXT NOT NULL,
+                updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create saved messages table (each user's private bookmarks). No
+        // foreign key on the message, so a deleted one leaves a tombstone
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS saved_messages (
+                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                message_id TEXT NOT NULL,
+                room_id TEXT NOT NULL,
+                saved_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (user_id, message_id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_saved_messages_user ON saved_messages(user_id, saved_at)")
+            .execute(&self.pool)
+            .await?;
+        
+        // Create bot attributions table (the bot behind each message it
+        // posted on a user's behalf). No foreign key on the bot, so the
+        // record outlives it.
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS bot_attributions (
+                message_id TEXT PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+                bot_id TEXT NOT NULL,
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        
+        // Create message attachments table (the uploads a message was posted with)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS message_attachments (
+                message_id TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+                blob_key TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                PRIMARY KEY (message_id, blob_key)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        
+        // Create post grants table (who may post in admins-only rooms)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS room_post_grants (
+                room_id TEXT NOT NULL REFERENCES rooms(id) ON DELETE CASCADE,
+                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (room_id, user_id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create canned responses table (saved replies posted via /canned)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS room_canned_responses (
+                room_id TEXT NOT NULL REFERENCES rooms(id) ON DELETE CASCADE,
+                shortcut TEXT NOT NULL,
+                content TEXT NOT NULL,
+                updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (room_id, shortcut)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create read markers table (last message each user has read per room)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS read_markers (
+                user_id TEXT NOT NULL REFERENCES users(id),
+                room_id TEXT NOT NULL REFERENCES rooms(id),
+                last_read_message_id TEXT NOT NULL,
+                updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (user_id, room_id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create feature overrides table (per-user / per-room rollout flags)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS feature_overrides (
+                scope TEXT NOT NULL CHECK (scope IN ('user', 'room')),
+                scope_id TEXT NOT NULL,
+                flag TEXT NOT NULL,
+                enabled BOOLEAN NOT NULL,
+                updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (scope, scope_id, flag)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create FTS5 virtual table for message search
+        // We'll create a standalone FTS5 table since we can't use UUID as content_rowid
+        sqlx::query(
+            r#"
+            CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+                message_id,
+                content
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create triggers to keep FTS5 in sync
+        sqlx::query(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+                INSERT INTO messages_fts(message_id, content) VALUES (new.id, new.content);
+            END
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
+                DELETE FROM messages_fts WHERE message_id = old.id;
+            END
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS messages_fts_update AFTER UPDATE ON messages BEGIN
+                DELETE FROM messages_fts WHERE message_id = old.id;
+                INSERT INTO messages_fts(message_id, content) VALUES (new.id, new.content);
+            END
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create push subscriptions table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS push_subscriptions (
+                id TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL REFERENCES users(id),
+                endpoint TEXT NOT NULL,
+                p256dh_key TEXT NOT NULL,
+                auth_key TEXT NOT NULL,
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                last_used_at DATETIME,
+                UNIQUE(user_id, endpoint)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create notification preferences table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS notification_preferences (
+                user_id TEXT PRIMARY KEY REFERENCES users(id),
+                mentions_enabled BOOLEAN NOT NULL DEFAULT TRUE,
+                direct_messages_enabled BOOLEAN NOT NULL DEFAULT TRUE,
+                all_messages_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+                sounds_enabled BOOLEAN NOT NULL DEFAULT TRUE,
+                updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        
+        let _ = sqlx::query("ALTER TABLE notification_preferences ADD COLUMN room_added_email_enabled BOOLEAN NOT NULL DEFAULT TRUE")
+            .execute(&self.pool)
+            .await; // Ignore error if column already exists
+        
+        // Data migrations that rewrite rows run once; each is recorded here
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS data_migrations (
+                name TEXT PRIMARY KEY,
+                applied_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        
+        // HTML stored before every write went through the sanitizer is
+        // cleaned in place
+        let resanitized: Option<String> = sqlx::query_scalar("SELECT name FROM data_migrations WHERE name = ?")
+            .bind(RESANITIZE_HTML_MIGRATION)
+            .fetch_optional(&self.pool)
+            .await?;
+        if resanitized.is_none() {
+            self.resanitize_html_content().await?;
+            sqlx::query("INSERT INTO data_migrations (name) VALUES (?)")
+                .bind(RESANITIZE_HTML_MIGRATION)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+    
+    /// Runs every stored `html_content` through the sanitizer again, a page
+    /// at a time, rewriting the rows it changes. Safe to repeat.
+    async fn resanitize_html_content(&self) -> Result<u64> {
+        let mut after = 0i64;
+        let mut rewritten = 0;
+        loop {
+            let rows = sqlx::query(
+                "SELECT rowid, html_content FROM messages WHERE html_content IS NOT NULL AND rowid > ? ORDER BY rowid LIMIT ?"
+            )
+            .bind(after)
+            .bind(RESANITIZE_HTML_PAGE_SIZE)
+            .fetch_all(&self.pool)
+            .await?;
+            
+            for row in &rows {
+                let rowid: i64 = row.get("rowid");
+                let html: String = row.get("html_content");
+                let sanitized = crate::rich_text::sanitize_stored_html(&html);
+                if sanitized != html {
+                    sqlx::query("UPDATE messages SET html_content = ? WHERE rowid = ?")
+                        .bind(&sanitized)
+                        .bind(rowid)
+                        .execute(&self.pool)
+                        .await?;
+                    rewritten += 1;
+                }
+                after = rowid;
+            }
+            
+            if rows.len() < RESANITIZE_HTML_PAGE_SIZE as usize {
+                break;
+            }
+        }
+        
+        if rewritten > 0 {
+            tracing::info!("Re-sanitized stored HTML of {} messages", rewritten);
+        }
+        Ok(rewritten)
+    }
+    
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+    
+    pub async fn begin(&self) -> Result<Transaction<'_, Sqlite>, sqlx::Error> {
+        self.pool.begin().await
+    }
+    
+    /// Health check method for database connectivity
+    pub async fn health_check(&self) -> Result<DatabaseStats, DatabaseError> {
+        let start = std::time::Instant::now();
+        
+        // Simple query to test connectivity
+        let row = sqlx::query("SELECT COUNT(*) as count FROM users")
+            .fetch_one(&self.pool)
+            .await?;
+        
+        let user_count: i64 = row.get("count");
+        let query_time = start.elapsed();
+        
+        // Get connection pool stats
+        let connection_count = self.pool.size();
+        
+        Ok(DatabaseStats {
+            connection_count: connection_count as u32,
+            total_queries: user_count as u64, // Simplified - in production you'd track this properly
+            avg_query_time_ms: query_time.as_millis() as u64,
+        })
+    }
+    
+    /// Compares the number of rows in messages_fts and messages; cheap
+    /// enough for health checks
+    pub async fn check_search_index(&self) -> Result<SearchIndexStatus, DatabaseError> {
+        let row = sqlx::query(
+            "SELECT (SELECT COUNT(*) FROM messages) AS messages, (SELECT COUNT(*) FROM messages_fts) AS indexed"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        
+        Ok(SearchIndexStatus {
+            messages: row.get::<i64, _>("messages") as u64,
+            indexed: row.get::<i64, _>("indexed") as u64,
+        })
+    }
+    
+    /// Finds messages missing from messages_fts and index rows left behind
+    /// by deleted messages. Scans both tables, so it's run on request rather
+    /// than from health checks.
+    pub async fn find_search_index_drift(&self) -> Result<SearchIndexDrift, DatabaseError> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM messages m
+                    WHERE NOT EXISTS (SELECT 1 FROM messages_fts f WHERE f.message_id = m.id)) AS missing,
+                (SELECT COUNT(*) FROM messages_fts f
+                    WHERE NOT EXISTS (SELECT 1 FROM messages m WHERE m.id = f.message_id)) AS orphaned
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        
+        Ok(SearchIndexDrift {
+            missing: row.get::<i64, _>("missing") as u64,
+            orphaned: row.get::<i64, _>("orphaned") as u64,
+        })
+    }
+    
+    /// Simple ping method for readiness checks
+    pub async fn ping(&self) -> Result<(), DatabaseError> {
+        sqlx::query("SELECT 1")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Row counts of the messages and the search index over them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchIndexStatus {
+    pub messages: u64,
+    pub indexed: u64,
+}
+
+impl SearchIndexStatus {
+    /// Matching counts can still hide a missing row offset by an orphaned
+    /// one; [`SearchIndexDrift`] tells them apart
+    pub fn is_consistent(&self) -> bool {
+        self.messages == self.indexed
+    }
+}
+
+/// How far the search index has drifted from the messages it indexes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchIndexDrift {
+    /// Messages with no index row; search can't find them
+    pub missing: u64,
+    /// Index rows whose message is gone; search returns dead hits
+    pub orphaned: u64,
+}
+
+impl SearchIndexDrift {
+    pub fn is_consistent(&self) -> bool {
+        self.missing == 0 && self.orphaned == 0
+    }
+}
+
+/// Size of the database file around a VACUUM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VacuumReport {
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+impl VacuumReport {
+    pub fn reclaimed_bytes(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+/// Database statistics for health checks
+#[derive(Debug, Clone)]
+pub struct DatabaseStats {
+    pub connection_count: u32,
+    pub total_queries: u64,
+    pub avg_query_time_ms: u64,
+}
+
+// Internal database operations (used by the writer task)
+impl Database {
+    pub(crate) async fn create_user_internal(&self, user: &User) -> Result<(), DatabaseError> {
+        Self::insert_user(&self.pool, user).await
+    }
+    
+    /// Inserts a user with a free handle; for paths that write before the
+    /// writer runs, such as first-run setup
+    pub(crate) async fn insert_user(pool: &SqlitePool, user: &User) -> Result<(), DatabaseError> {
+        let handle = Self::free_handle(pool, &user.name, &user.email, user.bot_token.is_some()).await?;
+        sqlx::query(
+            r#"
+            INSERT INTO users (id, name, email, password_hash, bio, admin, bot_token, created_at, handle)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(user.id.0.to_string())
+        .bind(&user.name)
+        .bind(&user.email)
+        .bind(&user.password_hash)
+        .bind(&user.bio)
+        .bind(user.admin)
+        .bind(&user.bot_token)
+        .bind(user.created_at)
+        .bind(handle)
+        .execute(pool)
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// A handle no one has yet, with a numeric suffix if the natural one
+    /// is taken
+    ///
+    /// People go by their email's local part. A bot's email carries its
+    /// token, so bots go by their name instead.
+    async fn free_handle(pool: &SqlitePool, name: &str, email: &str, is_bot: bool) -> Result<String, DatabaseError> {
+        let source = if is_bot { name } else { email.split('@').next().unwrap_or("") };
+        let base: String = source
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '-' })
+            .take(MAX_HANDLE_LENGTH)
+            .collect();
+        let base = match base.trim_matches('-') {
+            "" if is_bot => "bot".to_string(),
+            "" => "user".to_string(),
+            trimmed => trimmed.to_string(),
+        };
+        
+        let mut candidate = base.clone();
+        for suffix in 2.. {
+            let taken: Option<i64> = sqlx::query_scalar("SELECT 1 FROM users WHERE handle = ?")
+                .bind(&candidate)
+                .fetch_optional(pool)
+                .await?;
+            if taken.is_none() {
+                break;
+            }
+            candidate = format!("{}-{}", base, suffix);
+        }
+        Ok(candidate)
+    }
+    
+    pub async fn get_user_by_id(&self, user_id: UserId) -> Result<Option<User>, DatabaseError> {
+        let row = sqlx::query(
+            "SELECT id, name, email, password_hash, bio, admin, bot_token, created_at FROM users WHERE id = ?"
+        )
+        .bind(user_id.0.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+        
+        if let Some(row) = row {
+            let id_str: &str = row.get("id");
+            Ok(Some(User {
+                id: UserId(uuid::Uuid::parse_str(id_str)?),
+                name: row.get("name"),
+                email: row.get("email"),
+                password_hash: row.get("password_hash"),
+                bio: row.get("bio"),
+                admin: row.get("admin"),
+                bot_token: row.get("bot_token"),
+                created_at: row.get("created_at"),
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+    
+    pub async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, DatabaseError> {
+        let row = sqlx::query(
+            "SELECT id, name, email, password_hash, bio, admin, bot_token, created_at FROM users WHERE email = ?"
+        )
+        .bind(email)
+        .fetch_optional(&self.pool)
+        .await?;
+        
+        Self::optional_user_from_row(row)
+    }
+    
+    /// User whose public handle is `handle`, compared case-insensitively,
+    /// as used by @mentions
+    pub async fn get_user_by_handle(&self, handle: &str) -> Result<Option<User>, DatabaseError> {
+        let row = sqlx::query(
+            "SELECT id, name, email, password_hash, bio, admin, bot_token, created_at FROM users WHERE handle = ?"
+        )
+        .bind(handle.to_lowercase())
+        .fetch_optional(&self.pool)
+        .await?;
+        
+        Self::optional_user_from_row(row)
+    }
+    
+    fn optional_user_from_row(row: Option<sqlx::sqlite::SqliteRow>) -> Result<Option<User>, DatabaseError> {
+        if let Some(row) = row {
+            let id_str: &str = row.get("id");
+            Ok(Some(User {
+                id: UserId(uuid::Uuid::parse_str(id_str)?),
+                name: row.get("name"),
+                email: row.get("email"),
+                password_hash: row.get("password_hash"),
+                bio: row.get("bio"),
+                admin: row.get("admin"),
+                bot_token: row.get("bot_token"),
+                created_at: row.get("created_at"),
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+// Database operations for sessions (Critical Gap #4)
+impl Database {
+    pub(crate) async fn create_session_internal(&self, session: &Session) -> Result<(), DatabaseError> {
+        sqlx::query(
+            "INSERT INTO sessions (token, user_id, created_at, expires_at, last_active_at) VALUES (?, ?, ?, ?, ?)"
+        )
+        .bind(&session.token)
+        .bind(session.user_id.0.to_string())
+        .bind(session.created_at)
+        .bind(session.expires_at)
+        .bind(session.created_at)
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn create_impersonation_session_internal(
+        &self,
+        session: &Session,
+        impersonated_by: UserId,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query(
+            "INSERT INTO sessions (token, user_id, created_at, expires_at, last_active_at, impersonated_by) VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&session.token)
+        .bind(session.user_id.0.to_string())
+        .bind(session.created_at)
+        .bind(session.expires_at)
+        .bind(session.created_at)
+        .bind(impersonated_by.0.to_string())
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn touch_session_internal(&self, token: &str, at: DateTime<Utc>) -> Result<(), DatabaseError> {
+        sqlx::query("UPDATE sessions SET last_active_at = ? WHERE token = ?")
+            .bind(at)
+            .bind(token)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    /// Last time the session was used; sessions that predate activity
+    /// tracking count from their creation
+    pub async fn get_session_last_active(&self, token: &str) -> Result<Option<DateTime<Utc>>, DatabaseError> {
+        let row = sqlx::query(
+            "SELECT COALESCE(last_active_at, created_at) as last_active_at FROM sessions WHERE token = ?"
+        )
+        .bind(token)
+        .fetch_optional(&self.pool)
+        .await?;
+        
+        Ok(row.map(|row| row.get("last_active_at")))
+    }
+    
+    /// The session, with the admin impersonating its user if it's an
+    /// impersonation session
+    pub async fn get_session_with_impersonator(
+        &self,
+        token: &str,
+    ) -> Result<Option<(Session, Option<UserId>)>, DatabaseError> {
+        let row = sqlx::query(
+            "SELECT token, user_id, created_at, expires_at, impersonated_by FROM sessions WHERE token = ? AND expires_at > CURRENT_TIMESTAMP"
+        )
+        .bind(token)
+        .fetch_optional(&self.pool)
+        .await?;
+        
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let user_id_str: &str = row.get("user_id");
+        let impersonated_by = match row.get::<Option<String>, _>("impersonated_by") {
+            Some(id) => Some(UserId(uuid::Uuid::parse_str(&id)?)),
+            None => None,
+        };
+        Ok(Some((
+            Session {
+                token: row.get("token"),
+                user_id: UserId(uuid::Uuid::parse_str(user_id_str)?),
+                created_at: row.get("created_at"),
+                expires_at: row.get("expires_at"),
+            },
+            impersonated_by,
+        )))
+    }
+    
+    pub async fn get_session(&self, token: &str) -> Result<Option<Session>, DatabaseError> {
+        let row = sqlx::query(
+            "SELECT token, user_id, created_at, expires_at FROM sessions WHERE token = ? AND expires_at > CURRENT_TIMESTAMP"
+        )
+        .bind(token)
+        .fetch_optional(&self.pool)
+        .await?;
+        
+        if let Some(row) = row {
+            let user_id_str: &str = row.get("user_id");
+            Ok(Some(Session {
+                token: row.get("token"),
+                user_id: UserId(uuid::Uuid::parse_str(user_id_str)?),
+                created_at: row.get("created_at"),
+                expires_at: row.get("expires_at"),
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+    
+    pub(crate) async fn delete_session_internal(&self, token: &str) -> Result<(), DatabaseError> {
+        sqlx::query("DELETE FROM sessions WHERE token = ?")
+            .bind(token)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn delete_user_sessions_internal(&self, user_id: UserId) -> Result<u64, DatabaseError> {
+        let result = sqlx::query("DELETE FROM sessions WHERE user_id = ?")
+            .bind(user_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(result.rows_affected())
+    }
+    
+    pub async fn get_user_session_tokens(&self, user_id: UserId) -> Result<Vec<String>, DatabaseError> {
+        let rows = sqlx::query("SELECT token FROM sessions WHERE user_id = ?")
+            .bind(user_id.0.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+        
+        Ok(rows.iter().map(|row| row.get("token")).collect())
+    }
+}
+
+// Database operations for messages (Critical Gap #1 - Deduplication)
+impl Database {
+    pub(crate) async fn create_message_with_deduplication_internal(
+        &self,
+        message: &Message,
+    ) -> Result<MessageWrite, DatabaseError> {
+        // First, try to get existing message with same client_message_id and room_id
+        if let Some(existing) = self.get_message_by_client_id(
+            message.client_message_id,
+            message.room_id,
+        ).await? {
+            return Ok(MessageWrite::existing(existing));
+        }
+        
+        // Whatever produced it, HTML is only stored once it's been cleaned
+        let mut message = message.clone();
+        message.html_content = message
+            .html_content
+            .as_deref()
+            .map(crate::rich_text::sanitize_stored_html);
+        
+        // Insert new message with rich text fields
+        let mentions_json = if message.mentions.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&message.mentions).unwrap_or_default())
+        };
+        
+        let sound_commands_json = if message.sound_commands.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&message.sound_commands).unwrap_or_default())
+        };
+        
+        // Taking the room's next seq and bumping its activity share the
+        // insert's transaction, so a refused insert gives the number back.
+        // New activity also un-archives the room.
+        let mut tx = self.pool.begin().await?;
+        let seq: i64 = sqlx::query_scalar(
+            "UPDATE rooms SET last_seq = last_seq + 1, last_message_at = ?, archived_at = NULL WHERE id = ? RETURNING last_seq"
+        )
+        .bind(message.created_at)
+        .bind(message.room_id.0.to_string())
+        .fetch_one(&mut tx)
+        .await?;
+        
+        let insert = sqlx::query(
+            r#"
+            INSERT INTO messages (id, room_id, creator_id, content, client_message_id, created_at, html_content, mentions, sound_commands, seq)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(message.id.0.to_string())
+        .bind(message.room_id.0.to_string())
+        .bind(message.creator_id.0.to_string())
+        .bind(&message.content)
+        .bind(message.client_message_id.to_string())
+        .bind(message.created_at)
+        .bind(&message.html_content)
+        .bind(mentions_json)
+        .bind(sound_commands_json)
+        .bind(seq)
+        .execute(&mut tx)
+        .await;
+        
+        // The writer serializes submissions, but if another connection won the race
+        // the UNIQUE(client_message_id, room_id) constraint fires; return the winner
+        match insert {
+            Err(sqlx::Error::Database(db_err)) if db_err.message().contains("UNIQUE constraint failed") => {
+                tx.rollback().await?;
+                return match self.get_message_by_client_id(message.client_message_id, message.room_id).await? {
+                    Some(existing) => Ok(MessageWrite::existing(existing)),
+                    None => Err(sqlx::Error::Database(db_err).into()),
+                };
+            }
+            insert => {
+                insert?;
+            }
+        }
+        tx.commit().await?;
+        
+        Ok(MessageWrite { message, inserted: true })
+    }
+    
+    pub(crate) async fn purge_messages_before_internal(
+        &self,
+        cutoff: DateTime<Utc>,
+    ) -> Result<u64, DatabaseError> {
+        // Deleting the row also drops its client_message_id from the unique
+        // index, so the dedup index shrinks along with retention
+        let result = sqlx::query("DELETE FROM messages WHERE created_at < ?")
+            .bind(cutoff)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(result.rows_affected())
+    }
+    
+    pub(crate) async fn purge_expired_messages_internal(
+        &self,
+        default_retention_days: u64,
+        now: DateTime<Utc>,
+    ) -> Result<u64, DatabaseError> {
+        let mut purged = 0;
+        
+        if default_retention_days > 0 {
+            let cutoff = now - chrono::Duration::days(default_retention_days as i64);
+            purged += sqlx::query(
+                "DELETE FROM messages WHERE created_at < ? AND room_id IN (SELECT id FROM rooms WHERE retention_days IS NULL)"
+            )
+            .bind(cutoff)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        }
+        
+        let overrides = sqlx::query("SELECT id, retention_days FROM rooms WHERE retention_days > 0")
+            .fetch_all(&self.pool)
+            .await?;
+        for row in overrides {
+            let cutoff = now - chrono::Duration::days(row.get::<i64, _>("retention_days"));
+            purged += sqlx::query("DELETE FROM messages WHERE room_id = ? AND created_at < ?")
+                .bind(row.get::<String, _>("id"))
+                .bind(cutoff)
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+        }
+        
+        Ok(purged)
+    }
+    
+    pub(crate) async fn set_room_retention_internal(
+        &self,
+        room_id: RoomId,
+        retention_days: Option<u32>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query("UPDATE rooms SET retention_days = ? WHERE id = ?")
+            .bind(retention_days.map(i64::from))
+            .bind(room_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn create_token_internal(&self, token: &Token) -> Result<(), DatabaseError> {
+        sqlx::query(
+            "INSERT INTO tokens (token, kind, subject, created_at, expires_at, used_at) VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&token.token)
+        .bind(token.kind.as_str())
+        .bind(&token.subject)
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .bind(token.used_at)
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn consume_token_internal(
+        &self,
+        token: &str,
+        kind: TokenKind,
+    ) -> Result<Option<Token>, DatabaseError> {
+        // Marking it used in the same statement that checks it means a token
+        // can only be redeemed once, however many requests race for it
+        let now = Utc::now();
+        let result = sqlx::query(
+            "UPDATE tokens SET used_at = ? WHERE token = ? AND kind = ? AND used_at IS NULL AND expires_at > ?"
+        )
+        .bind(now)
+        .bind(token)
+        .bind(kind.as_str())
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        
+        let row = sqlx::query(
+            "SELECT token, subject, created_at, expires_at, used_at FROM tokens WHERE token = ?"
+        )
+        .bind(token)
+        .fetch_one(&self.pool)
+        .await?;
+        
+        Ok(Some(Token {
+            token: row.get("token"),
+            kind,
+            subject: row.get("subject"),
+            created_at: row.get("created_at"),
+            expires_at: row.get("expires_at"),
+            used_at: row.get("used_at"),
+        }))
+    }
+    
+    pub(crate) async fn purge_expired_tokens_internal(&self) -> Result<u64, DatabaseError> {
+        let result = sqlx::query("DELETE FROM tokens WHERE expires_at <= ? OR used_at IS NOT NULL")
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(result.rows_affected())
+    }
+    
+    pub(crate) async fn archive_inactive_rooms_internal(
+        &self,
+        cutoff: DateTime<Utc>,
+    ) -> Result<Vec<RoomId>, DatabaseError> {
+        // Rooms that never had a message count from when they were created
+        let rows = sqlx::query(
+            r#"
+            UPDATE rooms SET archived_at = ?
+            WHERE archived_at IS NULL
+              AND room_type != 'direct'
+              AND COALESCE(last_message_at, created_at) < ?
+            RETURNING id
+            "#
+        )
+        .bind(Utc::now())
+        .bind(cutoff)
+        .fetch_all(&self.pool)
+        .await?;
+        
+        let mut room_ids = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id_str: &str = row.get("id");
+            room_ids.push(RoomId(uuid::Uuid::parse_str(id_str)?));
+        }
+        
+        Ok(room_ids)
+    }
+    
+    pub(crate) async fn update_read_marker_internal(
+        &self,
+        user_id: UserId,
+        message_id: MessageId,
+    ) -> Result<(), DatabaseError> {
+        // The room comes from the message itself, and the marker only moves
+        // forward (by room sequence, which timestamps can tie on) so a stale
+        // update from another tab can't rewind it
+        sqlx::query(
+            r#"
+            INSERT INTO read_markers (user_id, room_id, last_read_message_id, updated_at)
+            SELECT ?, room_id, id, ? FROM messages WHERE id = ?
+            ON CONFLICT(user_id, room_id) DO UPDATE SET
+                last_read_message_id = excluded.last_read_message_id,
+                updated_at = excluded.updated_at
+            WHERE (SELECT seq FROM messages WHERE id = excluded.last_read_message_id)
+                >= IFNULL((SELECT seq FROM messages WHERE id = read_markers.last_read_message_id), 0)
+            "#
+        )
+        .bind(user_id.0.to_string())
+        .bind(Utc::now())
+        .bind(message_id.0.to_string())
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Oldest message in the room posted by someone else after the user's
+    /// read marker. None when the user is caught up or has no marker yet.
+    pub async fn get_first_unread_message_id(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+    ) -> Result<Option<MessageId>, DatabaseError> {
+        let row = sqlx::query(
+            r#"
+            SELECT m.id
+            FROM messages m
+            INNER JOIN read_markers r ON r.room_id = m.room_id AND r.user_id = ?
+            WHERE m.room_id = ?
+              AND m.creator_id != ?
+              AND m.seq > (
+                  SELECT seq FROM messages WHERE id = r.last_read_message_id
+              )
+            ORDER BY m.seq ASC
+            LIMIT 1
+            "#
+        )
+        .bind(user_id.0.to_string())
+        .bind(room_id.0.to_string())
+        .bind(user_id.0.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+        
+        match row {
+            Some(row) => {
+                let id_str: &str = row.get("id");
+                Ok(Some(MessageId(uuid::Uuid::parse_str(id_str)?)))
+            }
+            None => Ok(None),
+        }
+    }
+    
+    /// Room a message was posted in, or None if it doesn't exist
+    pub async fn get_message_room_id(&self, message_id: MessageId) -> Result<Option<RoomId>, DatabaseError> {
+        let row = sqlx::query("SELECT room_id FROM messages WHERE id = ?")
+            .bind(message_id.0.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+        
+        match row {
+            Some(row) => {
+                let room_id_str: &str = row.get("room_id");
+                Ok(Some(RoomId(uuid::Uuid::parse_str(room_id_str)?)))
+            }
+            None => Ok(None),
+        }
+    }
+    
+    /// Members (other than the author) whose read marker is at or past the
+    /// message, earliest reader first
+    pub async fn get_seen_by(&self, message_id: MessageId) -> Result<Vec<SeenReceipt>, DatabaseError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT r.user_id, r.updated_at
+            FROM messages m
+            INNER JOIN read_markers r ON r.room_id = m.room_id
+            INNER JOIN messages lr ON lr.id = r.last_read_message_id
+            INNER JOIN room_memberships rm ON rm.room_id = r.room_id AND rm.user_id = r.user_id
+            WHERE m.id = ?
+              AND r.user_id != m.creator_id
+              AND lr.seq >= m.seq
+            ORDER BY r.updated_at ASC
+            "#
+        )
+        .bind(message_id.0.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+        
+        let mut receipts = Vec::with_capacity(rows.len());
+        for row in rows {
+            let user_id_str: &str = row.get("user_id");
+            receipts.push(SeenReceipt {
+                user_id: UserId(uuid::Uuid::parse_str(user_id_str)?),
+                seen_at: row.get("updated_at"),
+            });
+        }
+        
+        Ok(receipts)
+    }
+    
+    pub async fn count_bots(&self) -> Result<u32, DatabaseError> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM users WHERE bot_token IS NOT NULL")
+            .fetch_one(&self.pool)
+            .await?;
+        
+        let count: i64 = row.get("count");
+        Ok(count as u32)
+    }
+    
+    pub async fn count_room_members(&self, room_id: RoomId) -> Result<u32, DatabaseError> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM room_memberships WHERE room_id = ?")
+            .bind(room_id.0.to_string())
+            .fetch_one(&self.pool)
+            .await?;
+        
+        let count: i64 = row.get("count");
+        Ok(count as u32)
+    }
+    
+    pub async fn get_message_by_client_id(
+        &self,
+        client_message_id: uuid::Uuid,
+        room_id: RoomId,
+    ) -> Result<Option<Message>, DatabaseError> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, room_id, creator_id, content, client_message_id, created_at, html_content, mentions, sound_commands
+            FROM messages 
+            WHERE client_message_id = ? AND room_id = ?
+            "#
+        )
+        .bind(client_message_id.to_string())
+        .bind(room_id.0.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+        
+        if let Some(row) = row {
+            let id_str: &str = row.get("id");
+            let room_id_str: &str = row.get("room_id");
+            let creator_id_str: &str = row.get("creator_id");
+            let client_message_id_str: &str = row.get("client_message_id");
+            
+            // Parse JSON fields
+            let mentions: Vec<String> = if let Some(mentions_json) = row.get::<Option<String>, _>("mentions") {
+                serde_json::from_str(&mentions_json).unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            
+            let sound_commands: Vec<String> = if let Some(commands_json) = row.get::<Option<String>, _>("sound_commands") {
+                serde_json::from_str(&commands_json).unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            
+            Ok(Some(Message {
+                id: MessageId(uuid::Uuid::parse_str(id_str)?),
+                room_id: RoomId(uuid::Uuid::parse_str(room_id_str)?),
+                creator_id: UserId(uuid::Uuid::parse_str(creator_id_str)?),
+                content: row.get("content"),
+                client_message_id: uuid::Uuid::parse_str(client_message_id_str)?,
+                created_at: row.get("created_at"),
+                html_content: row.get("html_content"),
+                mentions,
+                sound_commands,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+    
+    pub async fn get_room_messages(
+        &self,
+        room_id: RoomId,
+        limit: u32,
+        before: Option<MessageId>,
+    ) -> Result<Vec<Message>, DatabaseError> {
+        self.get_room_messages_from(room_id, limit, before, None).await
+    }
+    
+    /// Like `get_room_messages`, leaving out messages sequenced before `min_seq`
+    pub async fn get_room_messages_from(
+        &self,
+        room_id: RoomId,
+        limit: u32,
+        before: Option<MessageId>,
+        min_seq: Option<i64>,
+    ) -> Result<Vec<Message>, DatabaseError> {
+        let query = if let Some(before_id) = before {
+            sqlx::query(
+                r#"
+                SELECT id, room_id, creator_id, content, client_message_id, created_at, html_content, mentions, sound_commands
+                FROM messages 
+                WHERE room_id = ? AND (created_at, seq) < (
+                    SELECT created_at, seq FROM messages WHERE id = ?
+                )
+                AND (? IS NULL OR seq >= ?)
+                ORDER BY created_at DESC, seq DESC
+                LIMIT ?
+                "#
+            )
+            .bind(room_id.0.to_string())
+            .bind(before_id.0.to_string())
+            .bind(min_seq)
+            .bind(min_seq)
+            .bind(limit as i64)
+        } else {
+            sqlx::query(
+                r#"
+                SELECT id, room_id, creator_id, content, client_message_id, created_at, html_content, mentions, sound_commands
+                FROM messages 
+                WHERE room_id = ? AND (? IS NULL OR seq >= ?)
+                ORDER BY created_at DESC, seq DESC
+                LIMIT ?
+                "#
+            )
+            .bind(room_id.0.to_string())
+            .bind(min_seq)
+            .bind(min_seq)
+            .bind(limit as i64)
+        };
+        
+        let rows = query.fetch_all(&self.pool).await?;
         
-        Ok(db)
+        let mut messages = Vec::new();
+        for row in rows {
+            let id_str: &str = row.get("id");
+            let room_id_str: &str = row.get("room_id");
+            let creator_id_str: &str = row.get("creator_id");
+            let client_message_id_str: &str = row.get("client_message_id");
+            
+            // Parse JSON fields
+            let mentions: Vec<String> = if let Some(mentions_json) = row.get::<Option<String>, _>("mentions") {
+                serde_json::from_str(&mentions_json).unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            
+            let sound_commands: Vec<String> = if let Some(commands_json) = row.get::<Option<String>, _>("sound_commands") {
+                serde_json::from_str(&commands_json).unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            
+            messages.push(Message {
+                id: MessageId(uuid::Uuid::parse_str(id_str)?),
+                room_id: RoomId(uuid::Uuid::parse_str(room_id_str)?),
+                creator_id: UserId(uuid::Uuid::parse_str(creator_id_str)?),
+                content: row.get("content"),
+                client_message_id: uuid::Uuid::parse_str(client_message_id_str)?,
+                created_at: row.get("created_at"),
+                html_content: row.get("html_content"),
+                mentions,
+                sound_commands,
+            });
+        }
+        
+        Ok(messages)
     }
     
-    pub async fn migrate(&self) -> Result<()> {
-        // Create users table
-        sqlx::query(
+    /// One page of the messages a user wrote, oldest first, starting after
+    /// the `(created_at, id)` of the previous page's last message. Used to
+    /// walk a user's whole history without loading it at once; the cursor
+    /// holds its own values so it still works if that message is deleted.
+    pub async fn get_messages_by_creator(
+        &self,
+        creator_id: UserId,
+        after: Option<(DateTime<Utc>, MessageId)>,
+        limit: u32,
+    ) -> Result<Vec<Message>, DatabaseError> {
+        let rows = sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS users (
-                id TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                email TEXT UNIQUE NOT NULL,
-                password_hash TEXT NOT NULL,
-                bio TEXT,
-                admin BOOLEAN NOT NULL DEFAULT FALSE,
-                bot_token TEXT UNIQUE,
-                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
-            )
+            SELECT id, room_id, creator_id, content, client_message_id, created_at, html_content, mentions, sound_commands
+            FROM messages
+            WHERE creator_id = ?1
+              AND (?3 IS NULL OR (created_at, id) > (?2, ?3))
+            ORDER BY created_at, id
+            LIMIT ?4
             "#
         )
-        .execute(&self.pool)
+        .bind(creator_id.0.to_string())
+        .bind(after.map(|(created_at, _)| created_at))
+        .bind(after.map(|(_, id)| id.0.to_string()))
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
         .await?;
-
-        // Create rooms table
-        sqlx::query(
+        
+        let mut messages = Vec::new();
+        for row in rows {
+            let id_str: &str = row.get("id");
+            let room_id_str: &str = row.get("room_id");
+            let client_message_id_str: &str = row.get("client_message_id");
+            
+            messages.push(Message {
+                id: MessageId(uuid::Uuid::parse_str(id_str)?),
+                room_id: RoomId(uuid::Uuid::parse_str(room_id_str)?),
+                creator_id,
+                content: row.get("content"),
+                client_message_id: uuid::Uuid::parse_str(client_message_id_str)?,
+                created_at: row.get("created_at"),
+                html_content: row.get("html_content"),
+                mentions: row
+                    .get::<Option<String>, _>("mentions")
+                    .and_then(|json| serde_json::from_str(&json).ok())
+                    .unwrap_or_default(),
+                sound_commands: row
+                    .get::<Option<String>, _>("sound_commands")
+                    .and_then(|json| serde_json::from_str(&json).ok())
+                    .unwrap_or_default(),
+            });
+        }
+        
+        Ok(messages)
+    }
+    
+    /// The user's latest message in the room if it has exactly this content
+    /// and was posted after `since`
+    pub async fn get_recent_identical_message(
+        &self,
+        room_id: RoomId,
+        creator_id: UserId,
+        content: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<Message>, DatabaseError> {
+        let row = sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS rooms (
-                id TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                topic TEXT,
-                room_type TEXT NOT NULL CHECK (room_type IN ('open', 'closed', 'direct')),
-                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-                last_message_at DATETIME
-            )
+            SELECT id, client_message_id, content, created_at, html_content, mentions, sound_commands
+            FROM messages
+            WHERE room_id = ? AND creator_id = ? AND created_at > ?
+            ORDER BY created_at DESC
+            LIMIT 1
             "#
         )
-        .execute(&self.pool)
+        .bind(room_id.0.to_string())
+        .bind(creator_id.0.to_string())
+        .bind(since)
+        .fetch_optional(&self.pool)
         .await?;
-
-        // Create messages table with UNIQUE constraint for Critical Gap #1
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS messages (
-                id TEXT PRIMARY KEY,
-                room_id TEXT NOT NULL REFERENCES rooms(id),
-                creator_id TEXT NOT NULL REFERENCES users(id),
-                content TEXT NOT NULL,
-                client_message_id TEXT NOT NULL,
-                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-                html_content TEXT,
-                mentions TEXT,
-                sound_commands TEXT,
-                UNIQUE(client_message_id, room_id)
+        
+        let Some(row) = row.filter(|row| row.get::<&str, _>("content") == content) else {
+            return Ok(None);
+        };
+        
+        let id_str: &str = row.get("id");
+        let client_message_id_str: &str = row.get("client_message_id");
+        Ok(Some(Message {
+            id: MessageId(uuid::Uuid::parse_str(id_str)?),
+            room_id,
+            creator_id,
+            content: row.get("content"),
+            client_message_id: uuid::Uuid::parse_str(client_message_id_str)?,
+            created_at: row.get("created_at"),
+            html_content: row.get("html_content"),
+            mentions: row
+                .get::<Option<String>, _>("mentions")
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+            sound_commands: row
+                .get::<Option<String>, _>("sound_commands")
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+        }))
+    }
+    
+    /// Get messages since a specific message ID for missed message delivery (Critical Gap #2)
+    pub async fn get_messages_since(
+        &self,
+        user_id: UserId,
+        last_seen_message_id: Option<MessageId>,
+        limit: u32,
+    ) -> Result<Vec<Message>, DatabaseError> {
+        let query = if let Some(last_seen_id) = last_seen_message_id {
+            // Get messages newer than the last seen message in rooms where user is a member
+            sqlx::query(
+                r#"
+                SELECT m.id, m.room_id, m.creator_id, m.content, m.client_message_id, m.created_at, m.html_content, m.mentions, m.sound_commands
+                FROM messages m
+                INNER JOIN room_memberships rm ON m.room_id = rm.room_id
+                WHERE rm.user_id = ? 
+                  AND m.created_at > (
+                      SELECT created_at FROM messages WHERE id = ?
+                  )
+                ORDER BY m.created_at ASC 
+                LIMIT ?
+                "#
             )
-            "#
-        )
-        .execute(&self.pool)
-        .await?;
+            .bind(user_id.0.to_string())
+            .bind(last_seen_id.0.to_string())
+            .bind(limit as i64)
+        } else {
+            // If no last seen message, get recent messages from all user's rooms
+            sqlx::query(
+                r#"
+                SELECT m.id, m.room_id, m.creator_id, m.content, m.client_message_id, m.created_at, m.html_content, m.mentions, m.sound_commands
+                FROM messages m
+                INNER JOIN room_memberships rm ON m.room_id = rm.room_id
+                WHERE rm.user_id = ?
+                ORDER BY m.created_at DESC 
+                LIMIT ?
+                "#
+            )
+            .bind(user_id.0.to_string())
+            .bind(limit as i64)
+        };
         
-        // Add rich text columns to existing messages table if they don't exist
-        // This handles the case where the table already exists without rich text fields
-        let _ = sqlx::query("ALTER TABLE messages ADD COLUMN html_content TEXT")
-            .execute(&self.pool)
-            .await; // Ignore error if column already exists
+        let rows = query.fetch_all(&self.pool).await?;
+        
+        let mut messages = Vec::new();
+        for row in rows {
+            let id_str: &str = row.get("id");
+            let room_id_str: &str = row.get("room_id");
+            let creator_id_str: &str = row.get("creator_id");
+            let client_message_id_str: &str = row.get("client_message_id");
             
-        let _ = sqlx::query("ALTER TABLE messages ADD COLUMN mentions TEXT")
-            .execute(&self.pool)
-            .await; // Ignore error if column already exists
+            // Parse JSON fields
+            let mentions: Vec<String> = if let Some(mentions_json) = row.get::<Option<String>, _>("mentions") {
+                serde_json::from_str(&mentions_json).unwrap_or_default()
+            } else {
+                Vec::new()
+            };
             
-        let _ = sqlx::query("ALTER TABLE messages ADD COLUMN sound_commands TEXT")
-            .execute(&self.pool)
-            .await; // Ignore error if column already exists
-
-        // Create room memberships table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS room_memberships (
-                room_id TEXT NOT NULL REFERENCES rooms(id),
-                user_id TEXT NOT NULL REFERENCES users(id),
-                involvement_level TEXT NOT NULL CHECK (involvement_level IN ('member', 'admin')),
-                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-                PRIMARY KEY (room_id, user_id)
-            )
-            "#
-        )
-        .execute(&self.pool)
-        .await?;
+            let sound_commands: Vec<String> = if let Some(commands_json) = row.get::<Option<String>, _>("sound_commands") {
+                serde_json::from_str(&commands_json).unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            
+            messages.push(Message {
+                id: MessageId(uuid::Uuid::parse_str(id_str)?),
+                room_id: RoomId(uuid::Uuid::parse_str(room_id_str)?),
+                creator_id: UserId(uuid::Uuid::parse_str(creator_id_str)?),
+                content: row.get("content"),
+                client_message_id: uuid::Uuid::parse_str(client_message_id_str)?,
+                created_at: row.get("created_at"),
+                html_content: row.get("html_content"),
+                mentions,
+                sound_commands,
+            });
+        }
+        
+        Ok(messages)
+    }
+}
 
-        // Create sessions table for authentication (Critical Gap #4)
+// Database operations for feature flag overrides
+impl Database {
+    fn feature_scope_key(scope: &FeatureScope) -> (&'static str, String) {
+        match scope {
+            FeatureScope::User(user_id) => ("user", user_id.0.to_string()),
+            FeatureScope::Room(room_id) => ("room", room_id.0.to_string()),
+        }
+    }
+    
+    pub(crate) async fn set_feature_override_internal(
+        &self,
+        scope: &FeatureScope,
+        flag: &str,
+        enabled: bool,
+    ) -> Result<(), DatabaseError> {
+        let (scope, scope_id) = Self::feature_scope_key(scope);
         sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS sessions (
-                token TEXT PRIMARY KEY,
-                user_id TEXT NOT NULL REFERENCES users(id),
-                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-                expires_at DATETIME NOT NULL
-            )
+            INSERT INTO feature_overrides (scope, scope_id, flag, enabled, updated_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (scope, scope_id, flag) DO UPDATE SET
+                enabled = excluded.enabled,
+                updated_at = excluded.updated_at
             "#
         )
+        .bind(scope)
+        .bind(scope_id)
+        .bind(flag)
+        .bind(enabled)
+        .bind(Utc::now())
         .execute(&self.pool)
         .await?;
-
-        // Create FTS5 virtual table for message search
-        // We'll create a standalone FTS5 table since we can't use UUID as content_rowid
-        sqlx::query(
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn clear_feature_override_internal(
+        &self,
+        scope: &FeatureScope,
+        flag: &str,
+    ) -> Result<(), DatabaseError> {
+        let (scope, scope_id) = Self::feature_scope_key(scope);
+        sqlx::query("DELETE FROM feature_overrides WHERE scope = ? AND scope_id = ? AND flag = ?")
+            .bind(scope)
+            .bind(scope_id)
+            .bind(flag)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    /// Overrides that apply to the user, plus the room's when one is given
+    pub async fn get_feature_overrides(
+        &self,
+        user_id: UserId,
+        room_id: Option<RoomId>,
+    ) -> Result<Vec<FeatureOverride>, DatabaseError> {
+        let rows = sqlx::query(
             r#"
-            CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
-                message_id,
-                content
-            )
+            SELECT scope, scope_id, flag, enabled FROM feature_overrides
+            WHERE (scope = 'user' AND scope_id = ?) OR (scope = 'room' AND scope_id = ?)
             "#
         )
-        .execute(&self.pool)
+        .bind(user_id.0.to_string())
+        .bind(room_id.map(|room_id| room_id.0.to_string()))
+        .fetch_all(&self.pool)
         .await?;
+        
+        let mut overrides = Vec::with_capacity(rows.len());
+        for row in rows {
+            let scope_str: &str = row.get("scope");
+            let scope_id = uuid::Uuid::parse_str(row.get("scope_id"))?;
+            let scope = match scope_str {
+                "user" => FeatureScope::User(UserId(scope_id)),
+                "room" => FeatureScope::Room(RoomId(scope_id)),
+                _ => return Err(DatabaseError::DataIntegrity {
+                    reason: format!("Invalid feature override scope: {}", scope_str)
+                }),
+            };
+            overrides.push(FeatureOverride {
+                scope,
+                flag: row.get("flag"),
+                enabled: row.get("enabled"),
+            });
+        }
+        
+        Ok(overrides)
+    }
+}
 
-        // Create triggers to keep FTS5 in sync
+// Database operations for rooms and memberships
+impl Database {
+    pub(crate) async fn update_room_type_internal(
+        &self,
+        room_id: RoomId,
+        room_type: &RoomType,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query("UPDATE rooms SET room_type = ? WHERE id = ?")
+            .bind(match room_type {
+                RoomType::Open => "open",
+                RoomType::Closed => "closed",
+                RoomType::Direct => "direct",
+            })
+            .bind(room_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn save_message_internal(
+        &self,
+        user_id: UserId,
+        message_id: MessageId,
+        room_id: RoomId,
+        max_saved: u32,
+    ) -> Result<bool, DatabaseError> {
+        // Runs on the writer task, so the count can't change before the insert
+        if max_saved > 0 {
+            let (already_saved, saved_count): (bool, i64) = sqlx::query_as(
+                r#"
+                SELECT EXISTS(SELECT 1 FROM saved_messages WHERE user_id = ?1 AND message_id = ?2),
+                       (SELECT COUNT(*) FROM saved_messages WHERE user_id = ?1)
+                "#
+            )
+            .bind(user_id.0.to_string())
+            .bind(message_id.0.to_string())
+            .fetch_one(&self.pool)
+            .await?;
+            
+            if already_saved {
+                return Ok(true);
+            }
+            if saved_count >= max_saved as i64 {
+                return Ok(false);
+            }
+        }
+        
         sqlx::query(
             r#"
-            CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
-                INSERT INTO messages_fts(message_id, content) VALUES (new.id, new.content);
-            END
+            INSERT INTO saved_messages (user_id, message_id, room_id, saved_at) VALUES (?, ?, ?, ?)
+            ON CONFLICT(user_id, message_id) DO NOTHING
             "#
         )
+        .bind(user_id.0.to_string())
+        .bind(message_id.0.to_string())
+        .bind(room_id.0.to_string())
+        .bind(Utc::now())
         .execute(&self.pool)
         .await?;
-
+        
+        Ok(true)
+    }
+    
+    pub(crate) async fn unsave_message_internal(
+        &self,
+        user_id: UserId,
+        message_id: MessageId,
+    ) -> Result<bool, DatabaseError> {
+        let result = sqlx::query("DELETE FROM saved_messages WHERE user_id = ? AND message_id = ?")
+            .bind(user_id.0.to_string())
+            .bind(message_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+    
+    pub(crate) async fn set_bot_auto_join_on_mention_internal(
+        &self,
+        bot_id: UserId,
+        enabled: bool,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query("UPDATE users SET bot_auto_join_on_mention = ? WHERE id = ? AND bot_token IS NOT NULL")
+            .bind(enabled)
+            .bind(bot_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn set_bot_post_on_behalf_internal(
+        &self,
+        bot_id: UserId,
+        enabled: bool,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query("UPDATE users SET bot_post_on_behalf = ? WHERE id = ? AND bot_token IS NOT NULL")
+            .bind(enabled)
+            .bind(bot_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn deactivate_bot_internal(&self, bot_id: UserId) -> Result<(), DatabaseError> {
+        sqlx::query("UPDATE users SET bot_token = NULL WHERE id = ? AND bot_token IS NOT NULL")
+            .bind(bot_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn set_last_seen_internal(
+        &self,
+        user_id: UserId,
+        at: DateTime<Utc>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query("UPDATE users SET last_seen_at = ?1 WHERE id = ?2 AND (last_seen_at IS NULL OR last_seen_at < ?1)")
+            .bind(at)
+            .bind(user_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn record_bot_attribution_internal(
+        &self,
+        message_id: MessageId,
+        bot_id: UserId,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query("INSERT OR REPLACE INTO bot_attributions (message_id, bot_id, created_at) VALUES (?, ?, ?)")
+            .bind(message_id.0.to_string())
+            .bind(bot_id.0.to_string())
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn set_bot_webhook_url_internal(
+        &self,
+        bot_id: UserId,
+        url: Option<String>,
+    ) -> Result<(), DatabaseError> {
+        match url {
+            Some(url) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO bot_webhooks (bot_id, url, updated_at) VALUES (?, ?, ?)
+                    ON CONFLICT(bot_id) DO UPDATE SET url = excluded.url, updated_at = excluded.updated_at
+                    "#
+                )
+                .bind(bot_id.0.to_string())
+                .bind(url)
+                .bind(Utc::now())
+                .execute(&self.pool)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM bot_webhooks WHERE bot_id = ?")
+                    .bind(bot_id.0.to_string())
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn set_room_public_internal(
+        &self,
+        room_id: RoomId,
+        public: bool,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query("UPDATE rooms SET public = ? WHERE id = ?")
+            .bind(public)
+            .bind(room_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn set_user_timezone_internal(&self, user_id: UserId, timezone: &str) -> Result<bool, DatabaseError> {
+        let updated = sqlx::query("UPDATE users SET timezone = ? WHERE id = ?")
+            .bind(timezone)
+            .bind(user_id.0.to_string())
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        
+        Ok(updated > 0)
+    }
+    
+    pub(crate) async fn set_room_sound_cooldown_internal(
+        &self,
+        room_id: RoomId,
+        cooldown_secs: Option<u64>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query("UPDATE rooms SET sound_cooldown_secs = ? WHERE id = ?")
+            .bind(cooldown_secs.map(|secs| secs as i64))
+            .bind(room_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn set_canned_response_internal(
+        &self,
+        room_id: RoomId,
+        shortcut: &str,
+        content: &str,
+    ) -> Result<(), DatabaseError> {
         sqlx::query(
             r#"
-            CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
-                DELETE FROM messages_fts WHERE message_id = old.id;
-            END
+            INSERT INTO room_canned_responses (room_id, shortcut, content, updated_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(room_id, shortcut) DO UPDATE SET
+                content = excluded.content,
+                updated_at = excluded.updated_at
             "#
         )
+        .bind(room_id.0.to_string())
+        .bind(shortcut)
+        .bind(content)
+        .bind(Utc::now())
         .execute(&self.pool)
         .await?;
-
-        sqlx::query(
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn delete_canned_response_internal(
+        &self,
+        room_id: RoomId,
+        shortcut: &str,
+    ) -> Result<bool, DatabaseError> {
+        let result = sqlx::query("DELETE FROM room_canned_responses WHERE room_id = ? AND shortcut = ?")
+            .bind(room_id.0.to_string())
+            .bind(shortcut)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+    
+    pub(crate) async fn set_room_sound_policy_internal(
+        &self,
+        room_id: RoomId,
+        policy: SoundPolicy,
+    ) -> Result<(), DatabaseError> {
+        let policy = match policy {
+            SoundPolicy::Any => None,
+            policy => Some(
+                serde_json::to_string(&policy)
+                    .map_err(|e| DatabaseError::DataIntegrity { reason: e.to_string() })?,
+            ),
+        };
+        sqlx::query("UPDATE rooms SET sound_policy = ? WHERE id = ?")
+            .bind(policy)
+            .bind(room_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn set_room_attachment_types_internal(
+        &self,
+        room_id: RoomId,
+        types: Option<Vec<String>>,
+    ) -> Result<(), DatabaseError> {
+        let types = types
+            .map(|types| serde_json::to_string(&types))
+            .transpose()
+            .map_err(|e| DatabaseError::DataIntegrity { reason: e.to_string() })?;
+        sqlx::query("UPDATE rooms SET allowed_attachment_types = ? WHERE id = ?")
+            .bind(types)
+            .bind(room_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn set_room_notify_mentions_only_internal(
+        &self,
+        room_id: RoomId,
+        mentions_only: bool,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query("UPDATE rooms SET notify_mentions_only = ? WHERE id = ?")
+            .bind(mentions_only)
+            .bind(room_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn update_room_post_permission_internal(
+        &self,
+        room_id: RoomId,
+        permission: PostPermission,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query("UPDATE rooms SET post_permission = ? WHERE id = ?")
+            .bind(permission.as_str())
+            .bind(room_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn set_room_post_grant_internal(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+        granted: bool,
+    ) -> Result<(), DatabaseError> {
+        if granted {
+            sqlx::query("INSERT OR IGNORE INTO room_post_grants (room_id, user_id, created_at) VALUES (?, ?, ?)")
+                .bind(room_id.0.to_string())
+                .bind(user_id.0.to_string())
+                .bind(Utc::now())
+                .execute(&self.pool)
+                .await?;
+        } else {
+            sqlx::query("DELETE FROM room_post_grants WHERE room_id = ? AND user_id = ?")
+                .bind(room_id.0.to_string())
+                .bind(user_id.0.to_string())
+                .execute(&self.pool)
+                .await?;
+        }
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn record_blob_internal(
+        &self,
+        key: &str,
+        user_id: Option<UserId>,
+        room_id: Option<RoomId>,
+        size_bytes: u64,
+        content_type: &str,
+        quarantined: bool,
+    ) -> Result<Option<BlobUsage>, DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+        
+        let previous = sqlx::query("SELECT user_id, size_bytes FROM blobs WHERE key = ?")
+            .bind(key)
+            .fetch_optional(&mut tx)
+            .await?
+            .map(|row| Self::blob_usage_from_row(&row))
+            .transpose()?;
+        
+        sqlx::query("INSERT OR REPLACE INTO blobs (key, user_id, room_id, size_bytes, content_type, quarantined, created_at) VALUES (?, ?, ?, ?, ?, ?, ?)")
+            .bind(key)
+            .bind(user_id.map(|id| id.0.to_string()))
+            .bind(room_id.map(|id| id.0.to_string()))
+            .bind(size_bytes as i64)
+            .bind(content_type)
+            .bind(quarantined)
+            .bind(Utc::now())
+            .execute(&mut tx)
+            .await?;
+        
+        tx.commit().await?;
+        Ok(previous)
+    }
+    
+    pub(crate) async fn link_message_attachments_internal(&self, message_id: MessageId, keys: &[String]) -> Result<(), DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+        
+        for (position, key) in keys.iter().enumerate() {
+            sqlx::query("INSERT OR IGNORE INTO message_attachments (message_id, blob_key, position) VALUES (?, ?, ?)")
+                .bind(message_id.0.to_string())
+                .bind(key)
+                .bind(position as i64)
+                .execute(&mut tx)
+                .await?;
+        }
+        
+        tx.commit().await?;
+        Ok(())
+    }
+    
+    pub(crate) async fn set_blob_quarantined_internal(&self, key: &str, quarantined: bool) -> Result<(), DatabaseError> {
+        sqlx::query("UPDATE blobs SET quarantined = ? WHERE key = ?")
+            .bind(quarantined)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    pub(crate) async fn delete_blob_record_internal(&self, key: &str) -> Result<Option<BlobUsage>, DatabaseError> {
+        let row = sqlx::query("DELETE FROM blobs WHERE key = ? RETURNING user_id, size_bytes")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?;
+        
+        row.map(|row| Self::blob_usage_from_row(&row)).transpose()
+    }
+    
+    /// Recent messages in the user's rooms that @mention them, newest first
+    /// 
+    /// A mention names a user by their handle, compared case-insensitively.
+    /// Only rooms the user is a member of are searched,
+    /// and their own messages are skipped.
+    pub async fn get_mentions(
+        &self,
+        user_id: UserId,
+        limit: u32,
+        before: Option<MessageId>,
+    ) -> Result<Vec<Mention>, DatabaseError> {
+        let rows = sqlx::query(
             r#"
-            CREATE TRIGGER IF NOT EXISTS messages_fts_update AFTER UPDATE ON messages BEGIN
-                DELETE FROM messages_fts WHERE message_id = old.id;
-                INSERT INTO messages_fts(message_id, content) VALUES (new.id, new.content);
-            END
+            SELECT m.id, m.room_id, m.creator_id, m.content, m.client_message_id, m.created_at,
+                   m.html_content, m.mentions, m.sound_commands, r.name AS room_name
+            FROM messages m
+            INNER JOIN room_memberships rm ON rm.room_id = m.room_id AND rm.user_id = ?1
+            INNER JOIN rooms r ON r.id = m.room_id
+            INNER JOIN users u ON u.id = ?1
+            WHERE m.mentions IS NOT NULL
+              AND m.creator_id != ?1
+              AND EXISTS (
+                  SELECT 1 FROM json_each(m.mentions)
+                  WHERE lower(json_each.value) = u.handle
+              )
+              AND (?2 IS NULL OR m.created_at < (SELECT created_at FROM messages WHERE id = ?2))
+            ORDER BY m.created_at DESC
+            LIMIT ?3
             "#
         )
-        .execute(&self.pool)
+        .bind(user_id.0.to_string())
+        .bind(before.map(|id| id.0.to_string()))
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
         .await?;
-
-        // Create push subscriptions table
-        sqlx::query(
+        
+        let mut mentions = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id_str: &str = row.get("id");
+            let room_id_str: &str = row.get("room_id");
+            let creator_id_str: &str = row.get("creator_id");
+            let client_message_id_str: &str = row.get("client_message_id");
+            
+            let mentioned: Vec<String> = row.get::<Option<String>, _>("mentions")
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default();
+            let sound_commands: Vec<String> = row.get::<Option<String>, _>("sound_commands")
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default();
+            
+            mentions.push(Mention {
+                message: Message {
+                    id: MessageId(uuid::Uuid::parse_str(id_str)?),
+                    room_id: RoomId(uuid::Uuid::parse_str(room_id_str)?),
+                    creator_id: UserId(uuid::Uuid::parse_str(creator_id_str)?),
+                    content: row.get("content"),
+                    client_message_id: uuid::Uuid::parse_str(client_message_id_str)?,
+                    created_at: row.get("created_at"),
+                    html_content: row.get("html_content"),
+                    mentions: mentioned,
+                    sound_commands,
+                },
+                room_name: row.get("room_name"),
+            });
+        }
+        
+        Ok(mentions)
+    }
+    
+    /// The user's saved messages in rooms they can still see (open rooms,
+    /// or ones they're a member of), most recently saved first. Messages deleted since are returned without content.
+    pub async fn get_saved_messages(
+        &self,
+        user_id: UserId,
+        limit: u32,
+        before: Option<MessageId>,
+    ) -> Result<Vec<SavedMessage>, DatabaseError> {
+        let rows = sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS push_subscriptions (
-                id TEXT PRIMARY KEY,
-                user_id TEXT NOT NULL REFERENCES users(id),
-                endpoint TEXT NOT NULL,
-                p256dh_key TEXT NOT NULL,
-                auth_key TEXT NOT NULL,
-                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-                last_used_at DATETIME,
-                UNIQUE(user_id, endpoint)
-            )
+            SELECT s.message_id, s.room_id, s.saved_at, r.name AS room_name,
+                   m.id, m.creator_id, m.content, m.client_message_id, m.created_at,
+                   m.html_content, m.mentions, m.sound_commands
+            FROM saved_messages s
+            INNER JOIN rooms r ON r.id = s.room_id
+            LEFT JOIN messages m ON m.id = s.message_id
+            WHERE s.user_id = ?1
+              AND (r.room_type = 'open' OR EXISTS (
+                  SELECT 1 FROM room_memberships rm WHERE rm.room_id = s.room_id AND rm.user_id = s.user_id
+              ))
+              AND (?2 IS NULL OR s.saved_at < (
+                  SELECT saved_at FROM saved_messages WHERE user_id = ?1 AND message_id = ?2
+              ))
+            ORDER BY s.saved_at DESC
+            LIMIT ?3
             "#
         )
-        .execute(&self.pool)
+        .bind(user_id.0.to_string())
+        .bind(before.map(|id| id.0.to_string()))
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
         .await?;
-
-        // Create notification preferences table
-        sqlx::query(
+        
+        let mut saved = Vec::with_capacity(rows.len());
+        for row in rows {
+            let message_id = MessageId(uuid::Uuid::parse_str(row.get("message_id"))?);
+            let room_id = RoomId(uuid::Uuid::parse_str(row.get("room_id"))?);
+            
+            let message = match row.get::<Option<&str>, _>("id") {
+                Some(_) => {
+                    let creator_id_str: &str = row.get("creator_id");
+                    let client_message_id_str: &str = row.get("client_message_id");
+                    Some(Message {
+                        id: message_id,
+                        room_id,
+                        creator_id: UserId(uuid::Uuid::parse_str(creator_id_str)?),
+                        content: row.get("content"),
+                        client_message_id: uuid::Uuid::parse_str(client_message_id_str)?,
+                        created_at: row.get("created_at"),
+                        html_content: row.get("html_content"),
+                        mentions: row
+                            .get::<Option<String>, _>("mentions")
+                            .and_then(|json| serde_json::from_str(&json).ok())
+                            .unwrap_or_default(),
+                        sound_commands: row
+                            .get::<Option<String>, _>("sound_commands")
+                            .and_then(|json| serde_json::from_str(&json).ok())
+                            .unwrap_or_default(),
+                    })
+                }
+                None => None,
+            };
+            
+            saved.push(SavedMessage {
+                message_id,
+                room_id,
+                room_name: row.get("room_name"),
+                saved_at: row.get("saved_at"),
+                deleted: message.is_none(),
+                message,
+            });
+        }
+        
+        Ok(saved)
+    }
+    
+    /// Preview of the newest message in each of the user's rooms
+    /// 
+    /// Read from `messages` on every call rather than kept on the room, so
+    /// the preview follows the last message if it is edited or removed.
+    /// Rooms without messages are absent from the map.
+    pub async fn get_last_message_previews(
+        &self,
+        user_id: UserId,
+        max_chars: usize,
+    ) -> Result<HashMap<RoomId, MessagePreview>, DatabaseError> {
+        let rows = sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS notification_preferences (
-                user_id TEXT PRIMARY KEY REFERENCES users(id),
-                mentions_enabled BOOLEAN NOT NULL DEFAULT TRUE,
-                direct_messages_enabled BOOLEAN NOT NULL DEFAULT TRUE,
-                all_messages_enabled BOOLEAN NOT NULL DEFAULT FALSE,
-                sounds_enabled BOOLEAN NOT NULL DEFAULT TRUE,
-                updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+            SELECT m.room_id, m.content, m.created_at, u.name AS sender_name
+            FROM room_memberships rm
+            INNER JOIN messages m ON m.id = (
+                SELECT id FROM messages
+                WHERE room_id = rm.room_id
+                ORDER BY created_at DESC, seq DESC
+                LIMIT 1
             )
+            INNER JOIN users u ON u.id = m.creator_id
+            WHERE rm.user_id = ?
             "#
         )
-        .execute(&self.pool)
+        .bind(user_id.0.to_string())
+        .fetch_all(&self.pool)
         .await?;
-
-        Ok(())
+        
+        let mut previews = HashMap::with_capacity(rows.len());
+        for row in rows {
+            let room_id_str: &str = row.get("room_id");
+            let content: String = row.get("content");
+            previews.insert(
+                RoomId(uuid::Uuid::parse_str(room_id_str)?),
+                MessagePreview::new(row.get("sender_name"), &content, row.get("created_at"), max_chars),
+            );
+        }
+        
+        Ok(previews)
     }
     
-    pub fn pool(&self) -> &SqlitePool {
-        &self.pool
+    /// Stored bytes per owner, for seeding the quota counters at startup
+    pub async fn get_blob_usage(&self) -> Result<Vec<BlobUsage>, DatabaseError> {
+        let rows = sqlx::query("SELECT user_id, SUM(size_bytes) AS size_bytes FROM blobs GROUP BY user_id")
+            .fetch_all(&self.pool)
+            .await?;
+        
+        rows.iter().map(Self::blob_usage_from_row).collect()
     }
     
-    pub async fn begin(&self) -> Result<Transaction<'_, Sqlite>, sqlx::Error> {
-        self.pool.begin().await
+    /// Keys of every blob with a record
+    pub async fn get_recorded_blob_keys(&self) -> Result<HashSet<String>, DatabaseError> {
+        let keys: Vec<String> = sqlx::query_scalar("SELECT key FROM blobs")
+            .fetch_all(&self.pool)
+            .await?;
+        
+        Ok(keys.into_iter().collect())
     }
     
-    /// Health check method for database connectivity
-    pub async fn health_check(&self) -> Result<DatabaseStats, DatabaseError> {
-        let start = std::time::Instant::now();
+    /// Whether a blob is held back until its virus scan finishes
+    pub async fn is_blob_quarantined(&self, key: &str) -> Result<bool, DatabaseError> {
+        let row = sqlx::query("SELECT 1 FROM blobs WHERE key = ? AND quarantined = 1")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?;
         
-        // Simple query to test connectivity
-        let row = sqlx::query("SELECT COUNT(*) as count FROM users")
-            .fetch_one(&self.pool)
+        Ok(row.is_some())
+    }
+    
+    /// Size of a blob stored by `user_id` (None = no such blob of theirs)
+    pub async fn get_blob_size(&self, key: &str, user_id: UserId) -> Result<Option<u64>, DatabaseError> {
+        let row = sqlx::query("SELECT size_bytes FROM blobs WHERE key = ? AND user_id = ?")
+            .bind(key)
+            .bind(user_id.0.to_string())
+            .fetch_optional(&self.pool)
             .await?;
         
-        let user_count: i64 = row.get("count");
-        let query_time = start.elapsed();
+        Ok(row.map(|row| row.get::<i64, _>("size_bytes") as u64))
+    }
+    
+    /// Keys of the blobs a message was posted with, in the order given
+    pub async fn get_message_attachments(&self, message_id: MessageId) -> Result<Vec<String>, DatabaseError> {
+        let keys = sqlx::query_scalar("SELECT blob_key FROM message_attachments WHERE message_id = ? ORDER BY position")
+            .bind(message_id.0.to_string())
+            .fetch_all(&self.pool)
+            .await?;
         
-        // Get connection pool stats
-        let connection_count = self.pool.size();
+        Ok(keys)
+    }
+    
+    /// Who uploaded a blob and the room it was posted to (None = unknown blob)
+    pub async fn get_blob_owner(&self, key: &str) -> Result<Option<BlobOwner>, DatabaseError> {
+        let row = sqlx::query("SELECT user_id, room_id FROM blobs WHERE key = ?")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?;
         
-        Ok(DatabaseStats {
-            connection_count: connection_count as u32,
-            total_queries: user_count as u64, // Simplified - in production you'd track this properly
-            avg_query_time_ms: query_time.as_millis() as u64,
+        row.map(|row| {
+            let user_id: Option<&str> = row.get("user_id");
+            let room_id: Option<&str> = row.get("room_id");
+            Ok(BlobOwner {
+                user_id: user_id.map(uuid::Uuid::parse_str).transpose()?.map(UserId),
+                room_id: room_id.map(uuid::Uuid::parse_str).transpose()?.map(RoomId),
+            })
         })
+        .transpose()
     }
     
-    /// Simple ping method for readiness checks
-    pub async fn ping(&self) -> Result<(), DatabaseError> {
-        sqlx::query("SELECT 1")
-            .fetch_one(&self.pool)
+    /// Content type a blob was uploaded with (None = unknown blob, or one
+    /// recorded before types were kept)
+    pub async fn get_blob_content_type(&self, key: &str) -> Result<Option<String>, DatabaseError> {
+        let row = sqlx::query("SELECT content_type FROM blobs WHERE key = ?")
+            .bind(key)
+            .fetch_optional(&self.pool)
             .await?;
-        Ok(())
+        
+        Ok(row.and_then(|row| row.get::<Option<String>, _>("content_type")))
     }
-}
-
-/// Database statistics for health checks
-#[derive(Debug, Clone)]
-pub struct DatabaseStats {
-    pub connection_count: u32,
-    pub total_queries: u64,
-    pub avg_query_time_ms: u64,
-}
-
-// Internal database operations (used by the writer task)
-impl Database {
-    pub(crate) async fn create_user_internal(&self, user: &User) -> Result<(), DatabaseError> {
+    
+    fn blob_usage_from_row(row: &sqlx::sqlite::SqliteRow) -> Result<BlobUsage, DatabaseError> {
+        let user_id: Option<&str> = row.get("user_id");
+        let size_bytes: i64 = row.get("size_bytes");
+        
+        Ok(BlobUsage {
+            user_id: user_id.map(uuid::Uuid::parse_str).transpose()?.map(UserId),
+            size_bytes: size_bytes as u64,
+        })
+    }
+    
+    pub(crate) async fn create_room_webhook_internal(&self, webhook: &RoomWebhook) -> Result<(), DatabaseError> {
         sqlx::query(
             r#"
-            INSERT INTO users (id, name, email, password_hash, bio, admin, bot_token, created_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO room_webhooks (id, room_id, url, secret, enabled, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
             "#
         )
-        .bind(user.id.0.to_string())
-        .bind(&user.name)
-        .bind(&user.email)
-        .bind(&user.password_hash)
-        .bind(&user.bio)
-        .bind(user.admin)
-        .bind(&user.bot_token)
-        .bind(user.created_at)
+        .bind(webhook.id.0.to_string())
+        .bind(webhook.room_id.0.to_string())
+        .bind(&webhook.url)
+        .bind(&webhook.secret)
+        .bind(webhook.enabled)
+        .bind(webhook.created_at)
         .execute(&self.pool)
         .await?;
         
         Ok(())
     }
     
-    pub async fn get_user_by_id(&self, user_id: UserId) -> Result<Option<User>, DatabaseError> {
-        let row = sqlx::query(
-            "SELECT id, name, email, password_hash, bio, admin, bot_token, created_at FROM users WHERE id = ?"
-        )
-        .bind(user_id.0.to_string())
-        .fetch_optional(&self.pool)
-        .await?;
+    pub(crate) async fn set_room_webhook_enabled_internal(
+        &self,
+        webhook_id: RoomWebhookId,
+        enabled: bool,
+    ) -> Result<bool, DatabaseError> {
+        let result = sqlx::query("UPDATE room_webhooks SET enabled = ? WHERE id = ?")
+            .bind(enabled)
+            .bind(webhook_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
         
-        if let Some(row) = row {
-            let id_str: &str = row.get("id");
-            Ok(Some(User {
-                id: UserId(uuid::Uuid::parse_str(id_str)?),
-                name: row.get("name"),
-                email: row.get("email"),
-                password_hash: row.get("password_hash"),
-                bio: row.get("bio"),
-                admin: row.get("admin"),
-                bot_token: row.get("bot_token"),
-                created_at: row.get("created_at"),
-            }))
-        } else {
-            Ok(None)
-        }
+        Ok(result.rows_affected() > 0)
     }
     
-    pub async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, DatabaseError> {
-        let row = sqlx::query(
-            "SELECT id, name, email, password_hash, bio, admin, bot_token, created_at FROM users WHERE email = ?"
-        )
-        .bind(email)
-        .fetch_optional(&self.pool)
-        .await?;
+    pub(crate) async fn delete_room_webhook_internal(&self, webhook_id: RoomWebhookId) -> Result<bool, DatabaseError> {
+        let result = sqlx::query("DELETE FROM room_webhooks WHERE id = ?")
+            .bind(webhook_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
         
-        if let Some(row) = row {
-            let id_str: &str = row.get("id");
-            Ok(Some(User {
-                id: UserId(uuid::Uuid::parse_str(id_str)?),
-                name: row.get("name"),
-                email: row.get("email"),
-                password_hash: row.get("password_hash"),
-                bio: row.get("bio"),
-                admin: row.get("admin"),
-                bot_token: row.get("bot_token"),
-                created_at: row.get("created_at"),
-            }))
-        } else {
-            Ok(None)
-        }
+        Ok(result.rows_affected() > 0)
     }
-}
-
-// Database operations for sessions (Critical Gap #4)
-impl Database {
-    pub(crate) async fn create_session_internal(&self, session: &Session) -> Result<(), DatabaseError> {
+    
+    pub(crate) async fn set_room_bridge_internal(&self, bridge: &RoomBridge) -> Result<(), DatabaseError> {
         sqlx::query(
-            "INSERT INTO sessions (token, user_id, created_at, expires_at) VALUES (?, ?, ?, ?)"
+            r#"
+            INSERT INTO room_bridges (room_id, url, format, inbound_bot_id, enabled, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(room_id) DO UPDATE SET
+                url = excluded.url,
+                format = excluded.format,
+                inbound_bot_id = excluded.inbound_bot_id,
+                enabled = excluded.enabled
+            "#
         )
-        .bind(&session.token)
-        .bind(session.user_id.0.to_string())
-        .bind(session.created_at)
-        .bind(session.expires_at)
+        .bind(bridge.room_id.0.to_string())
+        .bind(&bridge.url)
+        .bind(bridge.format.as_str())
+        .bind(bridge.inbound_bot_id.map(|id| id.0.to_string()))
+        .bind(bridge.enabled)
+        .bind(bridge.created_at)
         .execute(&self.pool)
         .await?;
         
         Ok(())
     }
     
-    pub async fn get_session(&self, token: &str) -> Result<Option<Session>, DatabaseError> {
-        let row = sqlx::query(
-            "SELECT token, user_id, created_at, expires_at FROM sessions WHERE token = ? AND expires_at > CURRENT_TIMESTAMP"
-        )
-        .bind(token)
-        .fetch_optional(&self.pool)
-        .await?;
+    pub(crate) async fn delete_room_bridge_internal(&self, room_id: RoomId) -> Result<bool, DatabaseError> {
+        let result = sqlx::query("DELETE FROM room_bridges WHERE room_id = ?")
+            .bind(room_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
         
-        if let Some(row) = row {
-            let user_id_str: &str = row.get("user_id");
-            Ok(Some(Session {
-                token: row.get("token"),
-                user_id: UserId(uuid::Uuid::parse_str(user_id_str)?),
-                created_at: row.get("created_at"),
-                expires_at: row.get("expires_at"),
-            }))
-        } else {
-            Ok(None)
-        }
+        Ok(result.rows_affected() > 0)
     }
     
-    pub(crate) async fn delete_session_internal(&self, token: &str) -> Result<(), DatabaseError> {
-        sqlx::query("DELETE FROM sessions WHERE token = ?")
-            .bind(token)
-            .execute(&self.pool)
+    /// All of the room's webhooks, enabled or not, oldest first
+    pub async fn get_bot_webhook_url(&self, bot_id: UserId) -> Result<Option<String>, DatabaseError> {
+        let row = sqlx::query("SELECT url FROM bot_webhooks WHERE bot_id = ?")
+            .bind(bot_id.0.to_string())
+            .fetch_optional(&self.pool)
             .await?;
         
-        Ok(())
+        Ok(row.map(|row| row.get("url")))
     }
-}
-
-// Database operations for messages (Critical Gap #1 - Deduplication)
-impl Database {
-    pub(crate) async fn create_message_with_deduplication_internal(
-        &self,
-        message: &Message,
-    ) -> Result<Message, DatabaseError> {
-        // First, try to get existing message with same client_message_id and room_id
-        if let Some(existing) = self.get_message_by_client_id(
-            message.client_message_id,
-            message.room_id,
-        ).await? {
-            return Ok(existing);
-        }
-        
-        // Insert new message with rich text fields
-        let mentions_json = if message.mentions.is_empty() {
-            None
-        } else {
-            Some(serde_json::to_string(&message.mentions).unwrap_or_default())
-        };
+    
+    pub async fn get_bot_auto_join_on_mention(&self, bot_id: UserId) -> Result<bool, DatabaseError> {
+        let row = sqlx::query("SELECT bot_auto_join_on_mention FROM users WHERE id = ?")
+            .bind(bot_id.0.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
         
-        let sound_commands_json = if message.sound_commands.is_empty() {
-            None
-        } else {
-            Some(serde_json::to_string(&message.sound_commands).unwrap_or_default())
-        };
+        Ok(row.is_some_and(|row| row.get("bot_auto_join_on_mention")))
+    }
+    
+    pub async fn get_bot_post_on_behalf(&self, bot_id: UserId) -> Result<bool, DatabaseError> {
+        let row = sqlx::query("SELECT bot_post_on_behalf FROM users WHERE id = ?")
+            .bind(bot_id.0.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
         
-        sqlx::query(
+        Ok(row.is_some_and(|row| row.get("bot_post_on_behalf")))
+    }
+    
+    pub async fn get_last_seen(&self, user_id: UserId) -> Result<Option<DateTime<Utc>>, DatabaseError> {
+        let last_seen: Option<Option<DateTime<Utc>>> = sqlx::query_scalar("SELECT last_seen_at FROM users WHERE id = ?")
+            .bind(user_id.0.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+        
+        Ok(last_seen.flatten())
+    }
+    
+    /// Last-seen times of a room's members, leaving out those never seen
+    pub async fn get_room_last_seen(&self, room_id: RoomId) -> Result<HashMap<UserId, DateTime<Utc>>, DatabaseError> {
+        let rows = sqlx::query(
             r#"
-            INSERT INTO messages (id, room_id, creator_id, content, client_message_id, created_at, html_content, mentions, sound_commands)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            SELECT u.id, u.last_seen_at FROM room_memberships m
+            JOIN users u ON u.id = m.user_id
+            WHERE m.room_id = ? AND u.last_seen_at IS NOT NULL
             "#
         )
-        .bind(message.id.0.to_string())
-        .bind(message.room_id.0.to_string())
-        .bind(message.creator_id.0.to_string())
-        .bind(&message.content)
-        .bind(message.client_message_id.to_string())
-        .bind(message.created_at)
-        .bind(&message.html_content)
-        .bind(mentions_json)
-        .bind(sound_commands_json)
-        .execute(&self.pool)
+        .bind(room_id.0.to_string())
+        .fetch_all(&self.pool)
         .await?;
         
-        // Update room's last_message_at
-        sqlx::query("UPDATE rooms SET last_message_at = ? WHERE id = ?")
-            .bind(message.created_at)
-            .bind(message.room_id.0.to_string())
-            .execute(&self.pool)
+        rows.iter()
+            .map(|row| {
+                let user_id: String = row.get("id");
+                Ok((UserId(uuid::Uuid::parse_str(&user_id)?), row.get("last_seen_at")))
+            })
+            .collect()
+    }
+    
+    /// The bot that posted a message on a user's behalf (None = posted by
+    /// its author)
+    pub async fn get_message_bot_author(&self, message_id: MessageId) -> Result<Option<UserId>, DatabaseError> {
+        let bot_id: Option<String> = sqlx::query_scalar("SELECT bot_id FROM bot_attributions WHERE message_id = ?")
+            .bind(message_id.0.to_string())
+            .fetch_optional(&self.pool)
             .await?;
         
-        Ok(message.clone())
+        Ok(bot_id.map(|id| uuid::Uuid::parse_str(&id)).transpose()?.map(UserId))
     }
     
-    pub async fn get_message_by_client_id(
-        &self,
-        client_message_id: uuid::Uuid,
-        room_id: RoomId,
-    ) -> Result<Option<Message>, DatabaseError> {
-        let row = sqlx::query(
+    pub async fn get_room_webhooks(&self, room_id: RoomId) -> Result<Vec<RoomWebhook>, DatabaseError> {
+        let rows = sqlx::query(
             r#"
-            SELECT id, room_id, creator_id, content, client_message_id, created_at, html_content, mentions, sound_commands
-            FROM messages 
-            WHERE client_message_id = ? AND room_id = ?
+            SELECT id, room_id, url, secret, enabled, created_at
+            FROM room_webhooks
+            WHERE room_id = ?
+            ORDER BY created_at ASC
             "#
         )
-        .bind(client_message_id.to_string())
         .bind(room_id.0.to_string())
-        .fetch_optional(&self.pool)
+        .fetch_all(&self.pool)
         .await?;
         
-        if let Some(row) = row {
+        let mut webhooks = Vec::with_capacity(rows.len());
+        for row in rows {
             let id_str: &str = row.get("id");
             let room_id_str: &str = row.get("room_id");
-            let creator_id_str: &str = row.get("creator_id");
-            let client_message_id_str: &str = row.get("client_message_id");
-            
-            // Parse JSON fields
-            let mentions: Vec<String> = if let Some(mentions_json) = row.get::<Option<String>, _>("mentions") {
-                serde_json::from_str(&mentions_json).unwrap_or_default()
-            } else {
-                Vec::new()
-            };
             
-            let sound_commands: Vec<String> = if let Some(commands_json) = row.get::<Option<String>, _>("sound_commands") {
-                serde_json::from_str(&commands_json).unwrap_or_default()
-            } else {
-                Vec::new()
-            };
-            
-            Ok(Some(Message {
-                id: MessageId(uuid::Uuid::parse_str(id_str)?),
+            webhooks.push(RoomWebhook {
+                id: RoomWebhookId(uuid::Uuid::parse_str(id_str)?),
                 room_id: RoomId(uuid::Uuid::parse_str(room_id_str)?),
-                creator_id: UserId(uuid::Uuid::parse_str(creator_id_str)?),
-                content: row.get("content"),
-                client_message_id: uuid::Uuid::parse_str(client_message_id_str)?,
+                url: row.get("url"),
+                secret: row.get("secret"),
+                enabled: row.get("enabled"),
                 created_at: row.get("created_at"),
-                html_content: row.get("html_content"),
-                mentions,
-                sound_commands,
-            }))
-        } else {
-            Ok(None)
+            });
         }
+        
+        Ok(webhooks)
     }
     
-    pub async fn get_room_messages(
+    pub async fn get_room_bridge(&self, room_id: RoomId) -> Result<Option<RoomBridge>, DatabaseError> {
+        let row = sqlx::query(
+            r#"
+            SELECT room_id, url, format, inbound_bot_id, enabled, created_at
+            FROM room_bridges
+            WHERE room_id = ?
+            "#
+        )
+        .bind(room_id.0.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+        
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let format: &str = row.get("format");
+        let inbound_bot_id: Option<String> = row.get("inbound_bot_id");
+        
+        Ok(Some(RoomBridge {
+            room_id,
+            url: row.get("url"),
+            format: format.parse().map_err(|reason| DatabaseError::DataIntegrity { reason })?,
+            inbound_bot_id: inbound_bot_id.map(|id| uuid::Uuid::parse_str(&id)).transpose()?.map(UserId),
+            enabled: row.get("enabled"),
+            created_at: row.get("created_at"),
+        }))
+    }
+    
+    /// Only open rooms count; the flag is ignored once a room becomes closed or direct
+    /// The room's own sound cooldown, if an admin set one
+    pub async fn get_user_timezone(&self, user_id: UserId) -> Result<Option<String>, DatabaseError> {
+        let row = sqlx::query("SELECT timezone FROM users WHERE id = ?")
+            .bind(user_id.0.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+        
+        Ok(row.and_then(|row| row.get::<Option<String>, _>("timezone")))
+    }
+    
+    pub async fn get_room_sound_cooldown(&self, room_id: RoomId) -> Result<Option<u64>, DatabaseError> {
+        let row = sqlx::query("SELECT sound_cooldown_secs FROM rooms WHERE id = ?")
+            .bind(room_id.0.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+        
+        Ok(row
+            .and_then(|row| row.get::<Option<i64>, _>("sound_cooldown_secs"))
+            .map(|secs| secs as u64))
+    }
+    
+    pub async fn get_room_retention(&self, room_id: RoomId) -> Result<Option<u32>, DatabaseError> {
+        let row = sqlx::query("SELECT retention_days FROM rooms WHERE id = ?")
+            .bind(room_id.0.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+        
+        Ok(row
+            .and_then(|row| row.get::<Option<i64>, _>("retention_days"))
+            .map(|days| days as u32))
+    }
+    
+    /// The room's canned responses whose shortcut starts with `prefix`, in
+    /// shortcut order
+    pub async fn get_canned_responses(
         &self,
         room_id: RoomId,
+        prefix: &str,
         limit: u32,
-        before: Option<MessageId>,
-    ) -> Result<Vec<Message>, DatabaseError> {
-        let query = if let Some(before_id) = before {
-            sqlx::query(
-                r#"
-                SELECT id, room_id, creator_id, content, client_message_id, created_at, html_content, mentions, sound_commands
-                FROM messages 
-                WHERE room_id = ? AND created_at < (
-                    SELECT created_at FROM messages WHERE id = ?
-                )
-                ORDER BY created_at DESC 
-                LIMIT ?
-                "#
-            )
+    ) -> Result<Vec<CannedResponse>, DatabaseError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT shortcut, content, updated_at
+            FROM room_canned_responses
+            WHERE room_id = ? AND substr(shortcut, 1, length(?)) = ?
+            ORDER BY shortcut
+            LIMIT ?
+            "#
+        )
+        .bind(room_id.0.to_string())
+        .bind(prefix)
+        .bind(prefix)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(rows
+            .into_iter()
+            .map(|row| CannedResponse {
+                shortcut: row.get("shortcut"),
+                content: row.get("content"),
+                updated_at: row.get("updated_at"),
+            })
+            .collect())
+    }
+    
+    pub async fn get_canned_response(&self, room_id: RoomId, shortcut: &str) -> Result<Option<String>, DatabaseError> {
+        let row = sqlx::query("SELECT content FROM room_canned_responses WHERE room_id = ? AND shortcut = ?")
             .bind(room_id.0.to_string())
-            .bind(before_id.0.to_string())
-            .bind(limit as i64)
-        } else {
-            sqlx::query(
-                r#"
-                SELECT id, room_id, creator_id, content, client_message_id, created_at, html_content, mentions, sound_commands
-                FROM messages 
-                WHERE room_id = ?
-                ORDER BY created_at DESC 
-                LIMIT ?
-                "#
-            )
+            .bind(shortcut)
+            .fetch_optional(&self.pool)
+            .await?;
+        
+        Ok(row.map(|row| row.get("content")))
+    }
+    
+    pub async fn count_canned_responses(&self, room_id: RoomId) -> Result<u32, DatabaseError> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM room_canned_responses WHERE room_id = ?")
             .bind(room_id.0.to_string())
-            .bind(limit as i64)
-        };
+            .fetch_one(&self.pool)
+            .await?;
         
-        let rows = query.fetch_all(&self.pool).await?;
+        Ok(count as u32)
+    }
+    
+    pub async fn get_room_sound_policy(&self, room_id: RoomId) -> Result<SoundPolicy, DatabaseError> {
+        let row = sqlx::query("SELECT sound_policy FROM rooms WHERE id = ?")
+            .bind(room_id.0.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
         
-        let mut messages = Vec::new();
-        for row in rows {
-            let id_str: &str = row.get("id");
-            let room_id_str: &str = row.get("room_id");
-            let creator_id_str: &str = row.get("creator_id");
-            let client_message_id_str: &str = row.get("client_message_id");
-            
-            // Parse JSON fields
-            let mentions: Vec<String> = if let Some(mentions_json) = row.get::<Option<String>, _>("mentions") {
-                serde_json::from_str(&mentions_json).unwrap_or_default()
-            } else {
-                Vec::new()
-            };
-            
-            let sound_commands: Vec<String> = if let Some(commands_json) = row.get::<Option<String>, _>("sound_commands") {
-                serde_json::from_str(&commands_json).unwrap_or_default()
-            } else {
-                Vec::new()
-            };
-            
-            messages.push(Message {
-                id: MessageId(uuid::Uuid::parse_str(id_str)?),
-                room_id: RoomId(uuid::Uuid::parse_str(room_id_str)?),
-                creator_id: UserId(uuid::Uuid::parse_str(creator_id_str)?),
-                content: row.get("content"),
-                client_message_id: uuid::Uuid::parse_str(client_message_id_str)?,
-                created_at: row.get("created_at"),
-                html_content: row.get("html_content"),
-                mentions,
-                sound_commands,
-            });
+        match row.and_then(|row| row.get::<Option<String>, _>("sound_policy")) {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| DatabaseError::DataIntegrity { reason: format!("Invalid sound policy: {}", e) }),
+            None => Ok(SoundPolicy::Any),
         }
+    }
+    
+    pub async fn get_room_attachment_types(&self, room_id: RoomId) -> Result<Option<Vec<String>>, DatabaseError> {
+        let row = sqlx::query("SELECT allowed_attachment_types FROM rooms WHERE id = ?")
+            .bind(room_id.0.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
         
-        Ok(messages)
+        Ok(row
+            .and_then(|row| row.get::<Option<String>, _>("allowed_attachment_types"))
+            .and_then(|json| serde_json::from_str(&json).ok()))
     }
     
-    /// Get messages since a specific message ID for missed message delivery (Critical Gap #2)
-    pub async fn get_messages_since(
+    pub async fn get_room_notify_mentions_only(&self, room_id: RoomId) -> Result<bool, DatabaseError> {
+        let row = sqlx::query("SELECT notify_mentions_only FROM rooms WHERE id = ?")
+            .bind(room_id.0.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+        
+        Ok(row.map(|row| row.get::<bool, _>("notify_mentions_only")).unwrap_or(false))
+    }
+    
+    pub async fn is_room_public(&self, room_id: RoomId) -> Result<bool, DatabaseError> {
+        let row = sqlx::query("SELECT 1 FROM rooms WHERE id = ? AND public = 1 AND room_type = 'open'")
+            .bind(room_id.0.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+        
+        Ok(row.is_some())
+    }
+    
+    /// Sequence of the `depth`-th newest message in a room; None if the room
+    /// has fewer messages than that
+    pub async fn get_room_history_horizon(
         &self,
-        user_id: UserId,
-        last_seen_message_id: Option<MessageId>,
-        limit: u32,
-    ) -> Result<Vec<Message>, DatabaseError> {
-        let query = if let Some(last_seen_id) = last_seen_message_id {
-            // Get messages newer than the last seen message in rooms where user is a member
-            sqlx::query(
-                r#"
-                SELECT m.id, m.room_id, m.creator_id, m.content, m.client_message_id, m.created_at, m.html_content, m.mentions, m.sound_commands
-                FROM messages m
-                INNER JOIN room_memberships rm ON m.room_id = rm.room_id
-                WHERE rm.user_id = ? 
-                  AND m.created_at > (
-                      SELECT created_at FROM messages WHERE id = ?
-                  )
-                ORDER BY m.created_at ASC 
-                LIMIT ?
-                "#
-            )
-            .bind(user_id.0.to_string())
-            .bind(last_seen_id.0.to_string())
-            .bind(limit as i64)
-        } else {
-            // If no last seen message, get recent messages from all user's rooms
-            sqlx::query(
-                r#"
-                SELECT m.id, m.room_id, m.creator_id, m.content, m.client_message_id, m.created_at, m.html_content, m.mentions, m.sound_commands
-                FROM messages m
-                INNER JOIN room_memberships rm ON m.room_id = rm.room_id
-                WHERE rm.user_id = ?
-                ORDER BY m.created_at DESC 
-                LIMIT ?
-                "#
-            )
-            .bind(user_id.0.to_string())
-            .bind(limit as i64)
-        };
+        room_id: RoomId,
+        depth: u32,
+    ) -> Result<Option<i64>, DatabaseError> {
+        let seq = sqlx::query_scalar(
+            "SELECT seq FROM messages WHERE room_id = ? ORDER BY seq DESC LIMIT 1 OFFSET ?"
+        )
+        .bind(room_id.0.to_string())
+        .bind(depth.saturating_sub(1) as i64)
+        .fetch_optional(&self.pool)
+        .await?;
         
-        let rows = query.fetch_all(&self.pool).await?;
+        Ok(seq)
+    }
+    
+    /// Everyone for unknown rooms, so callers check existence separately
+    pub async fn get_room_post_permission(&self, room_id: RoomId) -> Result<PostPermission, DatabaseError> {
+        let row = sqlx::query("SELECT post_permission FROM rooms WHERE id = ?")
+            .bind(room_id.0.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
         
-        let mut messages = Vec::new();
-        for row in rows {
-            let id_str: &str = row.get("id");
-            let room_id_str: &str = row.get("room_id");
-            let creator_id_str: &str = row.get("creator_id");
-            let client_message_id_str: &str = row.get("client_message_id");
-            
-            // Parse JSON fields
-            let mentions: Vec<String> = if let Some(mentions_json) = row.get::<Option<String>, _>("mentions") {
-                serde_json::from_str(&mentions_json).unwrap_or_default()
-            } else {
-                Vec::new()
-            };
-            
-            let sound_commands: Vec<String> = if let Some(commands_json) = row.get::<Option<String>, _>("sound_commands") {
-                serde_json::from_str(&commands_json).unwrap_or_default()
-            } else {
-                Vec::new()
-            };
-            
-            messages.push(Message {
-                id: MessageId(uuid::Uuid::parse_str(id_str)?),
-                room_id: RoomId(uuid::Uuid::parse_str(room_id_str)?),
-                creator_id: UserId(uuid::Uuid::parse_str(creator_id_str)?),
-                content: row.get("content"),
-                client_message_id: uuid::Uuid::parse_str(client_message_id_str)?,
-                created_at: row.get("created_at"),
-                html_content: row.get("html_content"),
-                mentions,
-                sound_commands,
-            });
+        match row {
+            Some(row) => {
+                let permission: &str = row.get("post_permission");
+                permission.parse().map_err(|reason| DatabaseError::DataIntegrity { reason })
+            }
+            None => Ok(PostPermission::Everyone),
         }
+    }
+    
+    pub async fn has_room_post_grant(&self, room_id: RoomId, user_id: UserId) -> Result<bool, DatabaseError> {
+        let row = sqlx::query("SELECT 1 FROM room_post_grants WHERE room_id = ? AND user_id = ?")
+            .bind(room_id.0.to_string())
+            .bind(user_id.0.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
         
-        Ok(messages)
+        Ok(row.is_some())
     }
-}
-
-// Database operations for rooms and memberships
-impl Database {
+    
     pub(crate) async fn create_room_internal(&self, room: &Room) -> Result<(), DatabaseError> {
         sqlx::query(
             r#"
@@ -945,6 +4597,162 @@ impl Database {
         }
     }
     
+    pub async fn get_open_room_by_name(&self, name: &str) -> Result<Option<Room>, DatabaseError> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, name, topic, room_type, created_at, last_message_at FROM rooms
+            WHERE room_type = 'open' AND name = ? COLLATE NOCASE
+            ORDER BY created_at ASC
+            LIMIT 1
+            "#
+        )
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+        
+        if let Some(row) = row {
+            let id_str: &str = row.get("id");
+            Ok(Some(Room {
+                id: RoomId(uuid::Uuid::parse_str(id_str)?),
+                name: row.get("name"),
+                topic: row.get("topic"),
+                room_type: RoomType::Open,
+                created_at: row.get("created_at"),
+                last_message_at: row.get("last_message_at"),
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+    
+    pub(crate) async fn rebuild_search_index_internal(&self) -> Result<u64, DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM messages_fts").execute(&mut tx).await?;
+        let indexed = sqlx::query("INSERT INTO messages_fts (message_id, content) SELECT id, content FROM messages")
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+        tx.commit().await?;
+        Ok(indexed)
+    }
+    
+    /// Only ever run from the writer task, so no write is mid-transaction
+    pub(crate) async fn vacuum_internal(&self) -> Result<VacuumReport, DatabaseError> {
+        let bytes_before = self.database_size().await?;
+        sqlx::query("VACUUM").execute(&self.pool).await?;
+        sqlx::query("PRAGMA optimize").execute(&self.pool).await?;
+        let bytes_after = self.database_size().await?;
+        Ok(VacuumReport { bytes_before, bytes_after })
+    }
+    
+    async fn database_size(&self) -> Result<u64, DatabaseError> {
+        let row = sqlx::query(
+            "SELECT page_count * page_size AS size FROM pragma_page_count(), pragma_page_size()"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.get::<i64, _>("size") as u64)
+    }
+    
+    pub(crate) async fn delete_room_permanently_internal(&self, room_id: RoomId) -> Result<Option<Vec<String>>, DatabaseError> {
+        let room_id = room_id.0.to_string();
+        let mut tx = self.pool.begin().await?;
+        
+        // Uploads made to the room and any posted in it; their rows stay so
+        // the blob store can release the uploaders' quota when it deletes them
+        let blob_keys: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT key FROM blobs WHERE room_id = ?1
+            UNION
+            SELECT a.blob_key FROM message_attachments a
+            JOIN messages m ON m.id = a.message_id
+            WHERE m.room_id = ?1
+            "#
+        )
+        .bind(&room_id)
+        .fetch_all(&mut tx)
+        .await?;
+        
+        // Children first; deleting messages clears their FTS rows by trigger
+        for statement in [
+            "DELETE FROM read_markers WHERE room_id = ?",
+            "DELETE FROM saved_messages WHERE room_id = ?",
+            "DELETE FROM room_post_grants WHERE room_id = ?",
+            "DELETE FROM room_webhooks WHERE room_id = ?",
+            "DELETE FROM room_bridges WHERE room_id = ?",
+            "DELETE FROM feature_overrides WHERE scope = 'room' AND scope_id = ?",
+            "DELETE FROM room_memberships WHERE room_id = ?",
+            "DELETE FROM messages WHERE room_id = ?",
+        ] {
+            sqlx::query(statement).bind(&room_id).execute(&mut tx).await?;
+        }
+        
+        let deleted = sqlx::query("DELETE FROM rooms WHERE id = ?")
+            .bind(&room_id)
+            .execute(&mut tx)
+            .await?
+            .rows_affected() > 0;
+        
+        tx.commit().await?;
+        Ok(deleted.then_some(blob_keys))
+    }
+    
+    pub(crate) async fn resolve_or_create_open_room_internal(
+        &self,
+        room: &Room,
+        creator_id: UserId,
+    ) -> Result<(Room, bool), DatabaseError> {
+        if let Some(existing) = self.get_open_room_by_name(&room.name).await? {
+            return Ok((existing, false));
+        }
+        
+        let mut tx = self.pool.begin().await?;
+        let insert = sqlx::query(
+            r#"
+            INSERT INTO rooms (id, name, topic, room_type, created_at, last_message_at)
+            VALUES (?, ?, ?, 'open', ?, ?)
+            "#
+        )
+        .bind(room.id.0.to_string())
+        .bind(&room.name)
+        .bind(&room.topic)
+        .bind(room.created_at)
+        .bind(room.last_message_at)
+        .execute(&mut tx)
+        .await;
+        
+        // Another connection created it between the lookup and the insert;
+        // the unique index on open room names makes that visible here
+        if let Err(sqlx::Error::Database(db_err)) = &insert {
+            if db_err.message().contains("UNIQUE constraint failed") {
+                tx.rollback().await?;
+                return self
+                    .get_open_room_by_name(&room.name)
+                    .await?
+                    .map(|existing| (existing, false))
+                    .ok_or(DatabaseError::ConstraintViolation {
+                        constraint: "idx_rooms_open_name".to_string(),
+                    });
+            }
+        }
+        insert?;
+        
+        sqlx::query(
+            r#"
+            INSERT INTO room_memberships (room_id, user_id, involvement_level, created_at)
+            VALUES (?, ?, 'admin', ?)
+            "#
+        )
+        .bind(room.id.0.to_string())
+        .bind(creator_id.0.to_string())
+        .bind(room.created_at)
+        .execute(&mut tx)
+        .await?;
+        
+        tx.commit().await?;
+        Ok((room.clone(), true))
+    }
+    
     pub(crate) async fn create_membership_internal(
         &self,
         membership: &Membership,
@@ -968,6 +4776,20 @@ impl Database {
         Ok(())
     }
     
+    pub(crate) async fn delete_membership_internal(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+    ) -> Result<bool, DatabaseError> {
+        let result = sqlx::query("DELETE FROM room_memberships WHERE room_id = ? AND user_id = ?")
+            .bind(room_id.0.to_string())
+            .bind(user_id.0.to_string())
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+    
     pub async fn get_membership(
         &self,
         room_id: RoomId,
@@ -1009,6 +4831,37 @@ impl Database {
         }
     }
     
+    /// Every room membership the user holds, oldest first
+    pub async fn get_user_memberships(&self, user_id: UserId) -> Result<Vec<Membership>, DatabaseError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT room_id, user_id, involvement_level, created_at
+            FROM room_memberships
+            WHERE user_id = ?
+            ORDER BY created_at
+            "#
+        )
+        .bind(user_id.0.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+        
+        let mut memberships = Vec::new();
+        for row in rows {
+            let room_id_str: &str = row.get("room_id");
+            let involvement_level_str: &str = row.get("involvement_level");
+            let involvement_level = involvement_level_str.parse().map_err(|reason| DatabaseError::DataIntegrity { reason })?;
+            
+            memberships.push(Membership {
+                room_id: RoomId(uuid::Uuid::parse_str(room_id_str)?),
+                user_id,
+                involvement_level,
+                created_at: row.get("created_at"),
+            });
+        }
+        
+        Ok(memberships)
+    }
+    
     pub async fn get_user_rooms(&self, user_id: UserId) -> Result<Vec<Room>, DatabaseError> {
         let rows = sqlx::query(
             r#"
@@ -1050,6 +4903,217 @@ impl Database {
         Ok(rooms)
     }
     
+    /// Unread counts for the user's `limit` most recently active rooms
+    pub async fn get_room_unread_counts(&self, user_id: UserId, limit: u32) -> Result<Vec<(RoomId, u32)>, DatabaseError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT r.id,
+                   (
+                       SELECT COUNT(*)
+                       FROM messages m
+                       LEFT JOIN read_markers rk ON rk.room_id = m.room_id AND rk.user_id = rm.user_id
+                       WHERE m.room_id = r.id
+                         AND m.creator_id != rm.user_id
+                         AND (
+                             rk.last_read_message_id IS NULL
+                             OR m.seq > (SELECT seq FROM messages WHERE id = rk.last_read_message_id)
+                         )
+                   ) AS unread_count
+            FROM rooms r
+            INNER JOIN room_memberships rm ON r.id = rm.room_id AND rm.user_id = ?
+            ORDER BY COALESCE(r.last_message_at, r.created_at) DESC
+            LIMIT ?
+            "#
+        )
+        .bind(user_id.0.to_string())
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        
+        let mut counts = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id_str: &str = row.get("id");
+            let unread_count: i64 = row.get("unread_count");
+            counts.push((RoomId(uuid::Uuid::parse_str(id_str)?), unread_count as u32));
+        }
+        
+        Ok(counts)
+    }
+    
+    /// Public profiles of those of `user_ids` who share a room with
+    /// `viewer`; everyone else is left out
+    pub async fn get_public_users_sharing_room(
+        &self,
+        viewer: UserId,
+        user_ids: &[UserId],
+    ) -> Result<Vec<PublicUser>, DatabaseError> {
+        let ids: Vec<String> = user_ids.iter().map(|id| id.0.to_string()).collect();
+        let rows = sqlx::query(
+            r#"
+            SELECT u.id, u.name, u.bio, u.bot_token IS NOT NULL AS bot
+            FROM users u
+            WHERE u.id IN (SELECT value FROM json_each(?))
+              AND EXISTS (
+                  SELECT 1
+                  FROM room_memberships theirs
+                  INNER JOIN room_memberships mine ON mine.room_id = theirs.room_id AND mine.user_id = ?
+                  WHERE theirs.user_id = u.id
+              )
+            ORDER BY u.name
+            "#
+        )
+        .bind(serde_json::to_string(&ids).unwrap_or_default())
+        .bind(viewer.0.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+        
+        let mut users = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id_str: &str = row.get("id");
+            users.push(PublicUser {
+                id: UserId(uuid::Uuid::parse_str(id_str)?),
+                name: row.get("name"),
+                bio: row.get("bio"),
+                bot: row.get("bot"),
+            });
+        }
+        
+        Ok(users)
+    }
+    
+    /// The user's direct rooms, most recently active first, each with the
+    /// other participant and how many messages the user hasn't read
+    pub async fn get_direct_conversations(&self, user_id: UserId) -> Result<Vec<DirectConversation>, DatabaseError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT r.id, r.name, r.topic, r.created_at, r.last_message_at,
+                   u.id AS participant_id, u.name AS participant_name, u.bio AS participant_bio,
+                   u.bot_token IS NOT NULL AS participant_bot,
+                   (
+                       SELECT COUNT(*)
+                       FROM messages m
+                       LEFT JOIN read_markers rk ON rk.room_id = m.room_id AND rk.user_id = rm.user_id
+                       WHERE m.room_id = r.id
+                         AND m.creator_id != rm.user_id
+                         AND (
+                             rk.last_read_message_id IS NULL
+                             OR m.seq > (SELECT seq FROM messages WHERE id = rk.last_read_message_id)
+                         )
+                   ) AS unread_count
+            FROM rooms r
+            INNER JOIN room_memberships rm ON r.id = rm.room_id AND rm.user_id = ?
+            LEFT JOIN users u ON u.id = (
+                SELECT other.user_id FROM room_memberships other
+                WHERE other.room_id = r.id AND other.user_id != rm.user_id
+                ORDER BY other.created_at ASC
+                LIMIT 1
+            )
+            WHERE r.room_type = 'direct'
+            ORDER BY COALESCE(r.last_message_at, r.created_at) DESC
+            "#
+        )
+        .bind(user_id.0.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+        
+        let mut conversations = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id_str: &str = row.get("id");
+            let participant_id: Option<&str> = row.get("participant_id");
+            let participant = match participant_id {
+                Some(participant_id) => Some(PublicUser {
+                    id: UserId(uuid::Uuid::parse_str(participant_id)?),
+                    name: row.get("participant_name"),
+                    bio: row.get("participant_bio"),
+                    bot: row.get("participant_bot"),
+                }),
+                None => None,
+            };
+            let unread_count: i64 = row.get("unread_count");
+            
+            conversations.push(DirectConversation {
+                room: Room {
+                    id: RoomId(uuid::Uuid::parse_str(id_str)?),
+                    name: row.get("name"),
+                    topic: row.get("topic"),
+                    room_type: RoomType::Direct,
+                    created_at: row.get("created_at"),
+                    last_message_at: row.get("last_message_at"),
+                },
+                participant,
+                unread_count: unread_count as u32,
+            });
+        }
+        
+        Ok(conversations)
+    }
+    
+    /// Room members whose name or username starts with `prefix`
+    /// (case-insensitive), sorted by name
+    pub async fn find_room_members_by_prefix(
+        &self,
+        room_id: RoomId,
+        prefix: &str,
+        limit: u32,
+    ) -> Result<Vec<MentionCandidate>, DatabaseError> {
+        let pattern = format!(
+            "{}%",
+            prefix.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+        );
+        let rows = sqlx::query(
+            r#"
+            SELECT u.id, u.name, u.handle
+            FROM room_memberships rm
+            INNER JOIN users u ON u.id = rm.user_id
+            WHERE rm.room_id = ?
+              AND (u.name LIKE ? ESCAPE '\' OR u.handle LIKE ? ESCAPE '\')
+            ORDER BY u.name COLLATE NOCASE
+            LIMIT ?
+            "#
+        )
+        .bind(room_id.0.to_string())
+        .bind(&pattern)
+        .bind(&pattern)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        
+        let mut candidates = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id_str: &str = row.get("id");
+            candidates.push(MentionCandidate {
+                handle: row.get("handle"),
+                name: row.get("name"),
+                user_id: Some(UserId(uuid::Uuid::parse_str(id_str)?)),
+            });
+        }
+        
+        Ok(candidates)
+    }
+    
+    /// The user's rooms that are currently archived
+    pub async fn get_archived_room_ids(&self, user_id: UserId) -> Result<Vec<RoomId>, DatabaseError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT r.id
+            FROM rooms r
+            INNER JOIN room_memberships rm ON r.id = rm.room_id
+            WHERE rm.user_id = ? AND r.archived_at IS NOT NULL
+            "#
+        )
+        .bind(user_id.0.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+        
+        let mut room_ids = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id_str: &str = row.get("id");
+            room_ids.push(RoomId(uuid::Uuid::parse_str(id_str)?));
+        }
+        
+        Ok(room_ids)
+    }
+    
     pub async fn check_user_can_add_member(
         &self,
         room_id: RoomId,
@@ -1086,6 +5150,40 @@ impl Database {
     }
 }
 
+/// Who and where has written recently enough that a replica may not have
+/// caught up yet
+#[derive(Default)]
+struct RecentWrites {
+    users: DashMap<UserId, Instant>,
+    rooms: DashMap<RoomId, Instant>,
+}
+
+/// A read-only pool that heavy reads (search, history, room lists) go to
+struct ReadReplica {
+    db: Database,
+    /// How long after writing a user's (or room's) reads stay on the primary
+    read_your_writes: Duration,
+    recent: RecentWrites,
+}
+
+impl ReadReplica {
+    fn user_is_fresh(&self, user_id: UserId) -> bool {
+        Self::is_fresh(&self.recent.users, &user_id, self.read_your_writes)
+    }
+    
+    fn room_is_fresh(&self, room_id: RoomId) -> bool {
+        Self::is_fresh(&self.recent.rooms, &room_id, self.read_your_writes)
+    }
+    
+    fn is_fresh<K: std::hash::Hash + Eq>(writes: &DashMap<K, Instant>, key: &K, window: Duration) -> bool {
+        let fresh = writes.get(key).is_some_and(|written| written.elapsed() < window);
+        if !fresh {
+            writes.remove_if(key, |_, written| written.elapsed() >= window);
+        }
+        fresh
+    }
+}
+
 /// Combined database interface that uses the writer pattern for writes
 /// and direct access for reads (Critical Gap #3 implementation)
 #[derive(Clone)]
@@ -1094,6 +5192,8 @@ pub struct CampfireDatabase {
     read_db: Database,
     /// Serialized writer for all write operations
     writer: Arc<dyn DatabaseWriter>,
+    /// Optional replica for read-heavy queries
+    replica: Option<Arc<ReadReplica>>,
 }
 
 impl CampfireDatabase {
@@ -1106,14 +5206,86 @@ impl CampfireDatabase {
         Ok(Self {
             read_db,
             writer,
+            replica: None,
         })
     }
     
+    /// Sends search, message history and room list reads to a read-only
+    /// pool on `replica_url`, which may be the primary's own file (more
+    /// connections that can never take the write lock) or a replicated copy
+    ///
+    /// A replica can lag the primary, so for `read_your_writes` after a user
+    /// posts or changes membership, that user's reads and the room's history
+    /// are served by the primary.
+    pub async fn with_read_replica(
+        mut self,
+        replica_url: &str,
+        max_connections: u32,
+        read_your_writes: Duration,
+    ) -> Result<Self> {
+        let db = Database::open_read_only(replica_url, max_connections).await?;
+        self.replica = Some(Arc::new(ReadReplica {
+            db,
+            read_your_writes,
+            recent: RecentWrites::default(),
+        }));
+        Ok(self)
+    }
+    
+    /// Where a heavy read on behalf of `user_id` should go
+    fn heavy_reader_for_user(&self, user_id: UserId) -> &Database {
+        match &self.replica {
+            Some(replica) if !replica.user_is_fresh(user_id) => &replica.db,
+            _ => &self.read_db,
+        }
+    }
+    
+    /// Where a heavy read of `room_id`'s history should go
+    fn heavy_reader_for_room(&self, room_id: RoomId) -> &Database {
+        match &self.replica {
+            Some(replica) if !replica.room_is_fresh(room_id) => &replica.db,
+            _ => &self.read_db,
+        }
+    }
+    
+    /// Notes a write so the writer's next reads skip the replica; called
+    /// before the write so a read racing it can't see the replica either
+    fn note_write(&self, user_id: Option<UserId>, room_id: Option<RoomId>) {
+        if let Some(replica) = &self.replica {
+            let now = Instant::now();
+            if let Some(user_id) = user_id {
+                replica.recent.users.insert(user_id, now);
+            }
+            if let Some(room_id) = room_id {
+                replica.recent.rooms.insert(room_id, now);
+            }
+        }
+    }
+    
+    /// Pool for a read-heavy query made for `user_id`: the replica, unless
+    /// the user wrote too recently for it to be trusted
+    pub fn read_pool_for(&self, user_id: UserId) -> &SqlitePool {
+        self.heavy_reader_for_user(user_id).pool()
+    }
+    
     /// Get the writer interface for write operations
     pub fn writer(&self) -> Arc<dyn DatabaseWriter> {
         Arc::clone(&self.writer)
     }
     
+    /// Shuts the database down in order: stop accepting writes, apply the
+    /// ones already queued (up to `drain_timeout`), then close the pools
+    pub async fn shutdown(&self, drain_timeout: Duration) -> Result<WriterDrainReport, DatabaseError> {
+        let report = self.writer.drain(drain_timeout).await?;
+        
+        self.read_db.pool().close().await;
+        if let Some(replica) = &self.replica {
+            replica.db.pool().close().await;
+        }
+        
+        Ok(report)
+    }
+    
     /// Get the database pool for direct read operations
     pub fn pool(&self) -> &SqlitePool {
         self.read_db.pool()
@@ -1134,10 +5306,29 @@ impl CampfireDatabase {
         self.read_db.get_user_by_email(email).await
     }
     
+    pub async fn get_user_by_handle(&self, handle: &str) -> Result<Option<User>, DatabaseError> {
+        self.read_db.get_user_by_handle(handle).await
+    }
+    
     pub async fn get_session(&self, token: &str) -> Result<Option<Session>, DatabaseError> {
         self.read_db.get_session(token).await
     }
     
+    pub async fn get_user_session_tokens(&self, user_id: UserId) -> Result<Vec<String>, DatabaseError> {
+        self.read_db.get_user_session_tokens(user_id).await
+    }
+    
+    pub async fn get_session_last_active(&self, token: &str) -> Result<Option<DateTime<Utc>>, DatabaseError> {
+        self.read_db.get_session_last_active(token).await
+    }
+    
+    pub async fn get_session_with_impersonator(
+        &self,
+        token: &str,
+    ) -> Result<Option<(Session, Option<UserId>)>, DatabaseError> {
+        self.read_db.get_session_with_impersonator(token).await
+    }
+    
     pub async fn get_message_by_client_id(
         &self,
         client_message_id: uuid::Uuid,
@@ -1156,13 +5347,63 @@ impl CampfireDatabase {
         self.read_db.ping().await
     }
     
+    pub async fn check_search_index(&self) -> Result<SearchIndexStatus, DatabaseError> {
+        self.read_db.check_search_index().await
+    }
+    
+    pub async fn find_search_index_drift(&self) -> Result<SearchIndexDrift, DatabaseError> {
+        self.read_db.find_search_index_drift().await
+    }
+    
+    pub async fn rebuild_search_index(&self) -> Result<u64, DatabaseError> {
+        self.writer.rebuild_search_index().await
+    }
+    
+    pub async fn vacuum(&self) -> Result<VacuumReport, DatabaseError> {
+        self.writer.vacuum().await
+    }
+    
     pub async fn get_room_messages(
         &self,
         room_id: RoomId,
         limit: u32,
         before: Option<MessageId>,
     ) -> Result<Vec<Message>, DatabaseError> {
-        self.read_db.get_room_messages(room_id, limit, before).await
+        self.heavy_reader_for_room(room_id).get_room_messages(room_id, limit, before).await
+    }
+    
+    pub async fn get_room_messages_from(
+        &self,
+        room_id: RoomId,
+        limit: u32,
+        before: Option<MessageId>,
+        min_seq: Option<i64>,
+    ) -> Result<Vec<Message>, DatabaseError> {
+        self.heavy_reader_for_room(room_id).get_room_messages_from(room_id, limit, before, min_seq).await
+    }
+    
+    pub async fn get_first_unread_message_id(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+    ) -> Result<Option<MessageId>, DatabaseError> {
+        self.read_db.get_first_unread_message_id(room_id, user_id).await
+    }
+    
+    pub async fn get_message_room_id(&self, message_id: MessageId) -> Result<Option<RoomId>, DatabaseError> {
+        self.read_db.get_message_room_id(message_id).await
+    }
+    
+    pub async fn get_seen_by(&self, message_id: MessageId) -> Result<Vec<SeenReceipt>, DatabaseError> {
+        self.read_db.get_seen_by(message_id).await
+    }
+    
+    pub async fn count_room_members(&self, room_id: RoomId) -> Result<u32, DatabaseError> {
+        self.read_db.count_room_members(room_id).await
+    }
+    
+    pub async fn count_bots(&self) -> Result<u32, DatabaseError> {
+        self.read_db.count_bots().await
     }
     
     pub async fn get_messages_since(
@@ -1178,6 +5419,130 @@ impl CampfireDatabase {
         self.read_db.get_room_by_id(room_id).await
     }
     
+    pub async fn get_room_post_permission(&self, room_id: RoomId) -> Result<PostPermission, DatabaseError> {
+        self.read_db.get_room_post_permission(room_id).await
+    }
+    
+    pub async fn is_room_public(&self, room_id: RoomId) -> Result<bool, DatabaseError> {
+        self.read_db.is_room_public(room_id).await
+    }
+    
+    pub async fn get_room_history_horizon(&self, room_id: RoomId, depth: u32) -> Result<Option<i64>, DatabaseError> {
+        self.read_db.get_room_history_horizon(room_id, depth).await
+    }
+    
+    pub async fn has_room_post_grant(&self, room_id: RoomId, user_id: UserId) -> Result<bool, DatabaseError> {
+        self.read_db.has_room_post_grant(room_id, user_id).await
+    }
+    
+    pub async fn get_mentions(&self, user_id: UserId, limit: u32, before: Option<MessageId>) -> Result<Vec<Mention>, DatabaseError> {
+        self.read_db.get_mentions(user_id, limit, before).await
+    }
+    
+    pub async fn get_saved_messages(&self, user_id: UserId, limit: u32, before: Option<MessageId>) -> Result<Vec<SavedMessage>, DatabaseError> {
+        self.read_db.get_saved_messages(user_id, limit, before).await
+    }
+    
+    pub async fn save_message(&self, user_id: UserId, message_id: MessageId, room_id: RoomId, max_saved: u32) -> Result<bool, DatabaseError> {
+        self.writer.save_message(user_id, message_id, room_id, max_saved).await
+    }
+    
+    pub async fn unsave_message(&self, user_id: UserId, message_id: MessageId) -> Result<bool, DatabaseError> {
+        self.writer.unsave_message(user_id, message_id).await
+    }
+    
+    pub async fn get_last_message_previews(&self, user_id: UserId, max_chars: usize) -> Result<HashMap<RoomId, MessagePreview>, DatabaseError> {
+        self.read_db.get_last_message_previews(user_id, max_chars).await
+    }
+    
+    pub async fn get_room_webhooks(&self, room_id: RoomId) -> Result<Vec<RoomWebhook>, DatabaseError> {
+        self.read_db.get_room_webhooks(room_id).await
+    }
+    
+    pub async fn get_bot_webhook_url(&self, bot_id: UserId) -> Result<Option<String>, DatabaseError> {
+        self.read_db.get_bot_webhook_url(bot_id).await
+    }
+    
+    pub async fn set_bot_webhook_url(&self, bot_id: UserId, url: Option<String>) -> Result<(), DatabaseError> {
+        self.writer.set_bot_webhook_url(bot_id, url).await
+    }
+    
+    pub async fn get_bot_auto_join_on_mention(&self, bot_id: UserId) -> Result<bool, DatabaseError> {
+        self.read_db.get_bot_auto_join_on_mention(bot_id).await
+    }
+    
+    pub async fn set_bot_auto_join_on_mention(&self, bot_id: UserId, enabled: bool) -> Result<(), DatabaseError> {
+        self.writer.set_bot_auto_join_on_mention(bot_id, enabled).await
+    }
+    
+    pub async fn get_bot_post_on_behalf(&self, bot_id: UserId) -> Result<bool, DatabaseError> {
+        self.read_db.get_bot_post_on_behalf(bot_id).await
+    }
+    
+    pub async fn set_bot_post_on_behalf(&self, bot_id: UserId, enabled: bool) -> Result<(), DatabaseError> {
+        self.writer.set_bot_post_on_behalf(bot_id, enabled).await
+    }
+    
+    pub async fn deactivate_bot(&self, bot_id: UserId) -> Result<(), DatabaseError> {
+        self.writer.deactivate_bot(bot_id).await
+    }
+    
+    pub async fn record_bot_attribution(&self, message_id: MessageId, bot_id: UserId) -> Result<(), DatabaseError> {
+        self.writer.record_bot_attribution(message_id, bot_id).await
+    }
+    
+    pub async fn get_message_bot_author(&self, message_id: MessageId) -> Result<Option<UserId>, DatabaseError> {
+        self.read_db.get_message_bot_author(message_id).await
+    }
+    
+    pub async fn set_last_seen(&self, user_id: UserId, at: DateTime<Utc>) -> Result<(), DatabaseError> {
+        self.writer.set_last_seen(user_id, at).await
+    }
+    
+    pub async fn get_last_seen(&self, user_id: UserId) -> Result<Option<DateTime<Utc>>, DatabaseError> {
+        self.read_db.get_last_seen(user_id).await
+    }
+    
+    pub async fn get_room_last_seen(&self, room_id: RoomId) -> Result<HashMap<UserId, DateTime<Utc>>, DatabaseError> {
+        self.read_db.get_room_last_seen(room_id).await
+    }
+    
+    pub async fn create_room_webhook(&self, webhook: RoomWebhook) -> Result<(), DatabaseError> {
+        self.writer.create_room_webhook(webhook).await
+    }
+    
+    pub async fn set_room_webhook_enabled(&self, webhook_id: RoomWebhookId, enabled: bool) -> Result<bool, DatabaseError> {
+        self.writer.set_room_webhook_enabled(webhook_id, enabled).await
+    }
+    
+    pub async fn delete_room_webhook(&self, webhook_id: RoomWebhookId) -> Result<bool, DatabaseError> {
+        self.writer.delete_room_webhook(webhook_id).await
+    }
+    
+    pub async fn get_room_bridge(&self, room_id: RoomId) -> Result<Option<RoomBridge>, DatabaseError> {
+        self.read_db.get_room_bridge(room_id).await
+    }
+    
+    pub async fn set_room_bridge(&self, bridge: RoomBridge) -> Result<(), DatabaseError> {
+        self.writer.set_room_bridge(bridge).await
+    }
+    
+    pub async fn delete_room_bridge(&self, room_id: RoomId) -> Result<bool, DatabaseError> {
+        self.writer.delete_room_bridge(room_id).await
+    }
+    
+    pub async fn get_blob_usage(&self) -> Result<Vec<BlobUsage>, DatabaseError> {
+        self.read_db.get_blob_usage().await
+    }
+    
+    pub async fn get_recorded_blob_keys(&self) -> Result<HashSet<String>, DatabaseError> {
+        self.read_db.get_recorded_blob_keys().await
+    }
+    
+    pub async fn get_archived_room_ids(&self, user_id: UserId) -> Result<Vec<RoomId>, DatabaseError> {
+        self.read_db.get_archived_room_ids(user_id).await
+    }
+    
     pub async fn get_membership(
         &self,
         room_id: RoomId,
@@ -1186,8 +5551,56 @@ impl CampfireDatabase {
         self.read_db.get_membership(room_id, user_id).await
     }
     
+    pub async fn get_user_memberships(&self, user_id: UserId) -> Result<Vec<Membership>, DatabaseError> {
+        self.read_db.get_user_memberships(user_id).await
+    }
+    
+    pub async fn get_messages_by_creator(
+        &self,
+        creator_id: UserId,
+        after: Option<(DateTime<Utc>, MessageId)>,
+        limit: u32,
+    ) -> Result<Vec<Message>, DatabaseError> {
+        self.read_db.get_messages_by_creator(creator_id, after, limit).await
+    }
+    
+    pub async fn get_recent_identical_message(
+        &self,
+        room_id: RoomId,
+        creator_id: UserId,
+        content: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<Message>, DatabaseError> {
+        self.read_db.get_recent_identical_message(room_id, creator_id, content, since).await
+    }
+    
     pub async fn get_user_rooms(&self, user_id: UserId) -> Result<Vec<Room>, DatabaseError> {
-        self.read_db.get_user_rooms(user_id).await
+        self.heavy_reader_for_user(user_id).get_user_rooms(user_id).await
+    }
+    
+    pub async fn get_direct_conversations(&self, user_id: UserId) -> Result<Vec<DirectConversation>, DatabaseError> {
+        self.read_db.get_direct_conversations(user_id).await
+    }
+    
+    pub async fn get_room_unread_counts(&self, user_id: UserId, limit: u32) -> Result<Vec<(RoomId, u32)>, DatabaseError> {
+        self.read_db.get_room_unread_counts(user_id, limit).await
+    }
+    
+    pub async fn get_public_users_sharing_room(
+        &self,
+        viewer: UserId,
+        user_ids: &[UserId],
+    ) -> Result<Vec<PublicUser>, DatabaseError> {
+        self.read_db.get_public_users_sharing_room(viewer, user_ids).await
+    }
+    
+    pub async fn find_room_members_by_prefix(
+        &self,
+        room_id: RoomId,
+        prefix: &str,
+        limit: u32,
+    ) -> Result<Vec<MentionCandidate>, DatabaseError> {
+        self.read_db.find_room_members_by_prefix(room_id, prefix, limit).await
     }
     
     pub async fn check_user_can_add_member(
@@ -1212,11 +5625,202 @@ impl CampfireDatabase {
         self.writer.create_session(session).await
     }
     
+    pub async fn create_impersonation_session(&self, session: Session, impersonated_by: UserId) -> Result<(), DatabaseError> {
+        self.writer.create_impersonation_session(session, impersonated_by).await
+    }
+    
     pub async fn delete_session(&self, token: String) -> Result<(), DatabaseError> {
         self.writer.delete_session(token).await
     }
     
+    pub async fn touch_session(&self, token: String, at: DateTime<Utc>) -> Result<(), DatabaseError> {
+        self.writer.touch_session(token, at).await
+    }
+    
+    // Feature flag override operations
+    
+    pub async fn get_feature_overrides(
+        &self,
+        user_id: UserId,
+        room_id: Option<RoomId>,
+    ) -> Result<Vec<FeatureOverride>, DatabaseError> {
+        self.read_db.get_feature_overrides(user_id, room_id).await
+    }
+    
+    pub async fn set_feature_override(
+        &self,
+        scope: FeatureScope,
+        flag: String,
+        enabled: bool,
+    ) -> Result<(), DatabaseError> {
+        self.writer.set_feature_override(scope, flag, enabled).await
+    }
+    
+    pub async fn clear_feature_override(&self, scope: FeatureScope, flag: String) -> Result<(), DatabaseError> {
+        self.writer.clear_feature_override(scope, flag).await
+    }
+    
+    pub async fn delete_user_sessions(&self, user_id: UserId) -> Result<u64, DatabaseError> {
+        self.writer.delete_user_sessions(user_id).await
+    }
+    
+    pub async fn purge_messages_before(&self, cutoff: DateTime<Utc>) -> Result<u64, DatabaseError> {
+        self.writer.purge_messages_before(cutoff).await
+    }
+    
+    pub async fn purge_expired_messages(&self, default_retention_days: u64, now: DateTime<Utc>) -> Result<u64, DatabaseError> {
+        self.writer.purge_expired_messages(default_retention_days, now).await
+    }
+    
+    pub async fn set_room_retention(&self, room_id: RoomId, retention_days: Option<u32>) -> Result<(), DatabaseError> {
+        self.writer.set_room_retention(room_id, retention_days).await
+    }
+    
+    pub async fn get_room_retention(&self, room_id: RoomId) -> Result<Option<u32>, DatabaseError> {
+        self.read_db.get_room_retention(room_id).await
+    }
+    
+    pub async fn create_token(&self, token: Token) -> Result<(), DatabaseError> {
+        self.writer.create_token(token).await
+    }
+    
+    pub async fn consume_token(&self, token: &str, kind: TokenKind) -> Result<Option<Token>, DatabaseError> {
+        self.writer.consume_token(token.to_string(), kind).await
+    }
+    
+    pub async fn purge_expired_tokens(&self) -> Result<u64, DatabaseError> {
+        self.writer.purge_expired_tokens().await
+    }
+    
+    pub async fn archive_inactive_rooms(&self, cutoff: DateTime<Utc>) -> Result<Vec<RoomId>, DatabaseError> {
+        self.writer.archive_inactive_rooms(cutoff).await
+    }
+    
+    pub async fn update_room_post_permission(&self, room_id: RoomId, permission: PostPermission) -> Result<(), DatabaseError> {
+        self.writer.update_room_post_permission(room_id, permission).await
+    }
+    
+    pub async fn set_room_sound_cooldown(&self, room_id: RoomId, cooldown_secs: Option<u64>) -> Result<(), DatabaseError> {
+        self.writer.set_room_sound_cooldown(room_id, cooldown_secs).await
+    }
+    
+    pub async fn get_room_sound_cooldown(&self, room_id: RoomId) -> Result<Option<u64>, DatabaseError> {
+        self.read_db.get_room_sound_cooldown(room_id).await
+    }
+    
+    pub async fn set_room_sound_policy(&self, room_id: RoomId, policy: SoundPolicy) -> Result<(), DatabaseError> {
+        self.writer.set_room_sound_policy(room_id, policy).await
+    }
+    
+    pub async fn get_room_sound_policy(&self, room_id: RoomId) -> Result<SoundPolicy, DatabaseError> {
+        self.read_db.get_room_sound_policy(room_id).await
+    }
+    
+    pub async fn set_canned_response(&self, room_id: RoomId, shortcut: String, content: String) -> Result<(), DatabaseError> {
+        self.writer.set_canned_response(room_id, shortcut, content).await
+    }
+    
+    pub async fn delete_canned_response(&self, room_id: RoomId, shortcut: String) -> Result<bool, DatabaseError> {
+        self.writer.delete_canned_response(room_id, shortcut).await
+    }
+    
+    pub async fn get_canned_responses(
+        &self,
+        room_id: RoomId,
+        prefix: &str,
+        limit: u32,
+    ) -> Result<Vec<CannedResponse>, DatabaseError> {
+        self.read_db.get_canned_responses(room_id, prefix, limit).await
+    }
+    
+    pub async fn get_canned_response(&self, room_id: RoomId, shortcut: &str) -> Result<Option<String>, DatabaseError> {
+        self.read_db.get_canned_response(room_id, shortcut).await
+    }
+    
+    pub async fn count_canned_responses(&self, room_id: RoomId) -> Result<u32, DatabaseError> {
+        self.read_db.count_canned_responses(room_id).await
+    }
+    
+    pub async fn set_room_attachment_types(&self, room_id: RoomId, types: Option<Vec<String>>) -> Result<(), DatabaseError> {
+        self.writer.set_room_attachment_types(room_id, types).await
+    }
+    
+    pub async fn get_room_attachment_types(&self, room_id: RoomId) -> Result<Option<Vec<String>>, DatabaseError> {
+        self.read_db.get_room_attachment_types(room_id).await
+    }
+    
+    pub async fn set_room_notify_mentions_only(&self, room_id: RoomId, mentions_only: bool) -> Result<(), DatabaseError> {
+        self.writer.set_room_notify_mentions_only(room_id, mentions_only).await
+    }
+    
+    pub async fn get_room_notify_mentions_only(&self, room_id: RoomId) -> Result<bool, DatabaseError> {
+        self.read_db.get_room_notify_mentions_only(room_id).await
+    }
+    
+    pub async fn get_user_timezone(&self, user_id: UserId) -> Result<Option<String>, DatabaseError> {
+        self.read_db.get_user_timezone(user_id).await
+    }
+    
+    pub async fn set_user_timezone(&self, user_id: UserId, timezone: String) -> Result<bool, DatabaseError> {
+        self.writer.set_user_timezone(user_id, timezone).await
+    }
+    
+    pub async fn set_room_public(&self, room_id: RoomId, public: bool) -> Result<(), DatabaseError> {
+        self.writer.set_room_public(room_id, public).await
+    }
+    
+    pub async fn set_room_post_grant(&self, room_id: RoomId, user_id: UserId, granted: bool) -> Result<(), DatabaseError> {
+        self.writer.set_room_post_grant(room_id, user_id, granted).await
+    }
+    
+    pub async fn record_blob(&self, key: String, user_id: Option<UserId>, room_id: Option<RoomId>, size_bytes: u64, content_type: String, quarantined: bool) -> Result<Option<BlobUsage>, DatabaseError> {
+        self.writer.record_blob(key, user_id, room_id, size_bytes, content_type, quarantined).await
+    }
+    
+    pub async fn set_blob_quarantined(&self, key: String, quarantined: bool) -> Result<(), DatabaseError> {
+        self.writer.set_blob_quarantined(key, quarantined).await
+    }
+    
+    pub async fn link_message_attachments(&self, message_id: MessageId, keys: Vec<String>) -> Result<(), DatabaseError> {
+        self.writer.link_message_attachments(message_id, keys).await
+    }
+    
+    pub async fn get_message_attachments(&self, message_id: MessageId) -> Result<Vec<String>, DatabaseError> {
+        self.read_db.get_message_attachments(message_id).await
+    }
+    
+    pub async fn is_blob_quarantined(&self, key: &str) -> Result<bool, DatabaseError> {
+        self.read_db.is_blob_quarantined(key).await
+    }
+    
+    pub async fn get_blob_size(&self, key: &str, user_id: UserId) -> Result<Option<u64>, DatabaseError> {
+        self.read_db.get_blob_size(key, user_id).await
+    }
+    
+    pub async fn get_blob_content_type(&self, key: &str) -> Result<Option<String>, DatabaseError> {
+        self.read_db.get_blob_content_type(key).await
+    }
+    
+    pub async fn get_blob_owner(&self, key: &str) -> Result<Option<BlobOwner>, DatabaseError> {
+        self.read_db.get_blob_owner(key).await
+    }
+    
+    pub async fn delete_blob_record(&self, key: String) -> Result<Option<BlobUsage>, DatabaseError> {
+        self.writer.delete_blob_record(key).await
+    }
+    
+    pub async fn update_read_marker(&self, user_id: UserId, message_id: MessageId) -> Result<(), DatabaseError> {
+        self.writer.update_read_marker(user_id, message_id).await
+    }
+    
     pub async fn create_message_with_deduplication(&self, message: Message) -> Result<Message, DatabaseError> {
+        Ok(self.write_message_with_deduplication(message).await?.message)
+    }
+    
+    /// `create_message_with_deduplication`, also saying whether the message
+    /// is new or was already stored under its `client_message_id`
+    pub async fn write_message_with_deduplication(&self, message: Message) -> Result<MessageWrite, DatabaseError> {
+        self.note_write(Some(message.creator_id), Some(message.room_id));
         self.writer.create_message_with_deduplication(message).await
     }
     
@@ -1225,9 +5829,35 @@ impl CampfireDatabase {
     }
     
     pub async fn create_membership(&self, membership: Membership) -> Result<(), DatabaseError> {
+        self.note_write(Some(membership.user_id), None);
         self.writer.create_membership(membership).await
     }
     
+    pub async fn delete_membership(&self, room_id: RoomId, user_id: UserId) -> Result<bool, DatabaseError> {
+        self.note_write(Some(user_id), None);
+        self.writer.delete_membership(room_id, user_id).await
+    }
+    
+    pub async fn delete_room_permanently(&self, room_id: RoomId) -> Result<Option<Vec<String>>, DatabaseError> {
+        self.writer.delete_room_permanently(room_id).await
+    }
+    
+    pub async fn update_room_type(&self, room_id: RoomId, room_type: RoomType) -> Result<(), DatabaseError> {
+        self.writer.update_room_type(room_id, room_type).await
+    }
+    
+    pub async fn get_open_room_by_name(&self, name: &str) -> Result<Option<Room>, DatabaseError> {
+        self.read_db.get_open_room_by_name(name).await
+    }
+    
+    pub async fn resolve_or_create_open_room(
+        &self,
+        room: Room,
+        creator_id: UserId,
+    ) -> Result<(Room, bool), DatabaseError> {
+        self.writer.resolve_or_create_open_room(room, creator_id).await
+    }
+    
     // Push notification operations
     
     pub async fn get_push_subscriptions_for_user(
@@ -1255,8 +5885,9 @@ impl CampfireDatabase {
         &self,
         message: &Message,
         room: &Room,
+        max_mentions: usize,
     ) -> Result<Vec<(UserId, NotificationPreferences)>, DatabaseError> {
-        self.read_db.get_notification_recipients(message, room).await
+        self.read_db.get_notification_recipients(message, room, max_mentions).await
     }
     
     pub async fn create_push_subscription(&self, subscription: PushSubscription) -> Result<(), DatabaseError> {
@@ -1347,8 +5978,8 @@ impl Database {
         sqlx::query(
             r#"
             INSERT OR REPLACE INTO notification_preferences 
-            (user_id, mentions_enabled, direct_messages_enabled, all_messages_enabled, sounds_enabled, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?)
+            (user_id, mentions_enabled, direct_messages_enabled, all_messages_enabled, sounds_enabled, room_added_email_enabled, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
             "#
         )
         .bind(preferences.user_id.0.to_string())
@@ -1356,6 +5987,7 @@ impl Database {
         .bind(preferences.direct_messages_enabled)
         .bind(preferences.all_messages_enabled)
         .bind(preferences.sounds_enabled)
+        .bind(preferences.room_added_email_enabled)
         .bind(preferences.updated_at)
         .execute(&self.pool)
         .await?;
@@ -1369,7 +6001,8 @@ impl Database {
     ) -> Result<NotificationPreferences, DatabaseError> {
         let row = sqlx::query(
             r#"
-            SELECT user_id, mentions_enabled, direct_messages_enabled, all_messages_enabled, sounds_enabled, updated_at
+            SELECT user_id, mentions_enabled, direct_messages_enabled, all_messages_enabled, sounds_enabled,
+                   room_added_email_enabled, updated_at
             FROM notification_preferences 
             WHERE user_id = ?
             "#
@@ -1386,6 +6019,7 @@ impl Database {
                 direct_messages_enabled: row.get("direct_messages_enabled"),
                 all_messages_enabled: row.get("all_messages_enabled"),
                 sounds_enabled: row.get("sounds_enabled"),
+                room_added_email_enabled: row.get("room_added_email_enabled"),
                 updated_at: row.get("updated_at"),
             })
         } else {
@@ -1398,10 +6032,15 @@ impl Database {
     }
     
     /// Get users who should receive push notifications for a message
+    /// 
+    /// At most `max_mentions` distinct mentioned users are notified; further
+    /// mentions are ignored. Rooms in mentions-only mode skip members'
+    /// all-messages preference.
     pub async fn get_notification_recipients(
         &self,
         message: &Message,
         room: &Room,
+        max_mentions: usize,
     ) -> Result<Vec<(UserId, NotificationPreferences)>, DatabaseError> {
         let mut recipients = Vec::new();
         
@@ -1414,6 +6053,7 @@ impl Database {
                        COALESCE(np.direct_messages_enabled, 1) as direct_messages_enabled,
                        COALESCE(np.all_messages_enabled, 0) as all_messages_enabled,
                        COALESCE(np.sounds_enabled, 1) as sounds_enabled,
+                       COALESCE(np.room_added_email_enabled, 1) as room_added_email_enabled,
                        COALESCE(np.updated_at, CURRENT_TIMESTAMP) as updated_at
                 FROM room_memberships rm
                 LEFT JOIN notification_preferences np ON rm.user_id = np.user_id
@@ -1435,6 +6075,7 @@ impl Database {
                         direct_messages_enabled: row.get("direct_messages_enabled"),
                         all_messages_enabled: row.get("all_messages_enabled"),
                         sounds_enabled: row.get("sounds_enabled"),
+                        room_added_email_enabled: row.get("room_added_email_enabled"),
                         updated_at: row.get("updated_at"),
                     },
                 ));
@@ -1442,8 +6083,9 @@ impl Database {
         } else {
             // For mentions, notify mentioned users
             if !message.mentions.is_empty() {
-                for mention in &message.mentions {
-                    if let Some(user) = self.get_user_by_email(mention).await? {
+                let (mentions, _) = RichTextProcessor::limit_mentions(&message.mentions, max_mentions);
+                for mention in mentions.iter().filter(|m| !ROOM_WIDE_MENTIONS.contains(&m.to_lowercase().as_str())) {
+                    if let Some(user) = self.get_user_by_handle(mention).await? {
                         let preferences = self.get_notification_preferences(user.id).await?;
                         if preferences.mentions_enabled {
                             recipients.push((user.id, preferences));
@@ -1452,20 +6094,39 @@ impl Database {
                 }
             }
             
+            // In mentions-only rooms a plain message notifies nobody, whatever
+            // members chose for all messages; @room/@here reaches everyone
+            // who accepts mentions
+            let mentions_only = self.get_room_notify_mentions_only(message.room_id).await?;
+            let member_filter = if mentions_only {
+                let addresses_room = message
+                    .mentions
+                    .iter()
+                    .any(|m| ROOM_WIDE_MENTIONS.contains(&m.to_lowercase().as_str()));
+                if !addresses_room {
+                    return Ok(recipients);
+                }
+                "COALESCE(np.mentions_enabled, 1) = 1"
+            } else {
+                "np.all_messages_enabled = 1"
+            };
+            
             // For all messages (if enabled), notify all room members except sender
-            let rows = sqlx::query(
+            let rows = sqlx::query(&format!(
                 r#"
                 SELECT rm.user_id,
                        COALESCE(np.mentions_enabled, 1) as mentions_enabled,
                        COALESCE(np.direct_messages_enabled, 1) as direct_messages_enabled,
                        COALESCE(np.all_messages_enabled, 0) as all_messages_enabled,
                        COALESCE(np.sounds_enabled, 1) as sounds_enabled,
+                       COALESCE(np.room_added_email_enabled, 1) as room_added_email_enabled,
                        COALESCE(np.updated_at, CURRENT_TIMESTAMP) as updated_at
                 FROM room_memberships rm
                 LEFT JOIN notification_preferences np ON rm.user_id = np.user_id
-                WHERE rm.room_id = ? AND rm.user_id != ? AND np.all_messages_enabled = 1
-                "#
-            )
+                WHERE rm.room_id = ? AND rm.user_id != ? AND {}
+                "#,
+                member_filter
+            ))
             .bind(message.room_id.0.to_string())
             .bind(message.creator_id.0.to_string())
             .fetch_all(&self.pool)
@@ -1485,6 +6146,7 @@ impl Database {
                             direct_messages_enabled: row.get("direct_messages_enabled"),
                             all_messages_enabled: row.get("all_messages_enabled"),
                             sounds_enabled: row.get("sounds_enabled"),
+                            room_added_email_enabled: row.get("room_added_email_enabled"),
                             updated_at: row.get("updated_at"),
                         },
                     ));
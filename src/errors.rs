@@ -1,5 +1,5 @@
 use thiserror::Error;
-use crate::models::{UserId, RoomId, MessageId, ConnectionId, PushSubscriptionId};
+use crate::models::{UserId, RoomId, MessageId, ConnectionId, PushSubscriptionId, RoomWebhookId};
 
 // Library-level errors using thiserror for structured, matchable errors
 #[derive(Error, Debug)]
@@ -23,10 +23,37 @@ pub enum MessageError {
     Broadcast(#[from] BroadcastError),
     
     #[error("Rate limit exceeded: {limit} messages per {window}")]
-    RateLimit { limit: u32, window: String },
+    RateLimit {
+        limit: u32,
+        window: String,
+        retry_after_secs: u64,
+        /// Sent as `X-RateLimit-*` headers when they're enabled
+        status: Option<crate::middleware::rate_limiting::RateLimitStatus>,
+    },
     
     #[error("Message not found: {message_id}")]
     NotFound { message_id: MessageId },
+    
+    #[error("User {user_id} may not post in room {room_id}")]
+    PostingRestricted { user_id: UserId, room_id: RoomId },
+    
+    #[error("Seen-by is only available in rooms with up to {limit} members")]
+    SeenByUnavailable { limit: u32 },
+    
+    #[error("Timed out saving message {client_message_id}; retry with the same client_message_id")]
+    WriteTimeout { client_message_id: uuid::Uuid },
+    
+    #[error("Saved message limit of {limit} reached")]
+    SavedLimitReached { limit: u32 },
+    
+    #[error("Message blocked by moderation")]
+    Blocked { reason: Option<String> },
+    
+    #[error("Moderation service unavailable")]
+    ModerationUnavailable,
+    
+    #[error("No canned response '{shortcut}' in this room")]
+    UnknownCannedResponse { shortcut: String },
 }
 
 // From implementations for error conversion
@@ -53,6 +80,37 @@ pub enum RoomError {
     #[error("Invalid room name: {reason}")]
     InvalidName { reason: String },
     
+    #[error("Cannot change type of room {room_id}: {reason}")]
+    InvalidTypeChange { room_id: RoomId, reason: String },
+    
+    #[error("Room {room_id} is not an open room")]
+    NotOpen { room_id: RoomId },
+    
+    #[error("Direct room {room_id} already has {max_members} members")]
+    DirectRoomFull { room_id: RoomId, max_members: u32 },
+    
+    #[error("Joining rooms too quickly: {limit} joins per minute")]
+    JoinRateLimit { limit: u32, retry_after_secs: u64 },
+    
+    #[error("Invalid attachment type: {value}")]
+    InvalidAttachmentType { value: String },
+    
+    #[error("Unknown sound: {name}")]
+    UnknownSound { name: String },
+    
+    #[error("Invalid canned response: {reason}")]
+    InvalidCannedResponse { reason: String },
+    
+    #[error("No canned response '{shortcut}' in this room")]
+    CannedResponseNotFound { shortcut: String },
+    
+    #[error("Canned response limit of {limit} reached")]
+    CannedResponseLimitReached { limit: u32 },
+    
+    /// Irreversible operations are repeated with `token` to go through
+    #[error("Deleting room {room_id} cannot be undone and must be confirmed")]
+    ConfirmationRequired { room_id: RoomId, token: String },
+    
     #[error("Database operation failed: {0}")]
     Database(#[from] sqlx::Error),
 }
@@ -84,8 +142,14 @@ pub enum AuthError {
     #[error("Invalid email format: {email}")]
     InvalidEmail { email: String },
     
-    #[error("Password too weak")]
-    WeakPassword,
+    #[error("Invalid name: {reason}")]
+    InvalidName { reason: String },
+    
+    #[error("Unknown timezone: {timezone}")]
+    InvalidTimezone { timezone: String },
+    
+    #[error("Password too weak: {}", reasons.join("; "))]
+    WeakPassword { reasons: Vec<String> },
     
     #[error("Database operation failed: {0}")]
     Database(#[from] sqlx::Error),
@@ -95,6 +159,9 @@ pub enum AuthError {
     
     #[error("Token generation failed")]
     TokenGeneration,
+    
+    #[error("User {user_id} can't be impersonated")]
+    ImpersonationForbidden { user_id: UserId },
 }
 
 // From implementations for error conversion
@@ -186,6 +253,9 @@ pub enum ValidationError {
     
     #[error("Required field missing: {field}")]
     RequiredField { field: String },
+    
+    #[error("Too many mentions: at most {limit} people can be mentioned in one message")]
+    TooManyMentions { limit: usize },
 }
 
 #[derive(Error, Debug)]
@@ -218,6 +288,18 @@ pub enum PushNotificationError {
     UuidParse(#[from] uuid::Error),
 }
 
+#[derive(Error, Debug)]
+pub enum MailerError {
+    #[error("Invalid recipient address: {address}")]
+    InvalidAddress { address: String },
+    
+    #[error("Failed to send email: {0}")]
+    SendFailed(String),
+    
+    #[error("Database operation failed: {0}")]
+    Database(#[from] DatabaseError),
+}
+
 #[derive(Error, Debug)]
 pub enum BotError {
     #[error("Invalid bot token")]
@@ -232,9 +314,15 @@ pub enum BotError {
     #[error("Bot token already exists")]
     TokenExists,
     
+    #[error("Bot limit of {limit} reached")]
+    QuotaExceeded { limit: u32 },
+    
     #[error("Invalid webhook URL: {url}")]
     InvalidWebhookUrl { url: String },
     
+    #[error("Webhook URL not allowed: {url} ({reason})")]
+    WebhookUrlBlocked { url: String, reason: String },
+    
     #[error("Webhook delivery failed: {reason}")]
     WebhookDeliveryFailed { reason: String },
     
@@ -244,6 +332,24 @@ pub enum BotError {
     #[error("Invalid bot name: {reason}")]
     InvalidName { reason: String },
     
+    #[error("Bot {bot_id} has no grant to post in room {room_id}")]
+    PostingRestricted { bot_id: UserId, room_id: RoomId },
+    
+    #[error("Room webhook not found: {webhook_id}")]
+    WebhookNotFound { webhook_id: RoomWebhookId },
+    
+    #[error("Room {room_id} has no bridge")]
+    BridgeNotFound { room_id: RoomId },
+    
+    #[error("Bot {bot_id} has no webhook configured")]
+    NoWebhook { bot_id: UserId },
+    
+    #[error("Bot {bot_id} may not post on behalf of users")]
+    OnBehalfNotGranted { bot_id: UserId },
+    
+    #[error("Can't post on behalf of user {user_id}")]
+    InvalidOnBehalf { user_id: UserId },
+    
     #[error("Database operation failed: {0}")]
     Database(#[from] DatabaseError),
     
@@ -254,6 +360,66 @@ pub enum BotError {
     JsonSerialization(#[from] serde_json::Error),
 }
 
+#[derive(Error, Debug)]
+pub enum ModerationError {
+    #[error("Moderation request failed: {0}")]
+    Request(String),
+    
+    #[error("Moderation service answered {status}")]
+    Status { status: u16 },
+    
+    #[error("Invalid moderation verdict: {0}")]
+    InvalidVerdict(String),
+}
+
+#[derive(Error, Debug)]
+pub enum StorageError {
+    #[error("Blob not found: {key}")]
+    NotFound { key: String },
+    
+    #[error("Invalid blob key: {key}")]
+    InvalidKey { key: String },
+    
+    #[error("Storage I/O failed: {0}")]
+    Io(#[from] std::io::Error),
+    
+    #[error("Storage backend error: {reason}")]
+    Backend { reason: String },
+    
+    #[error("Upload quota of {limit} bytes exceeded")]
+    QuotaExceeded { limit: u64 },
+    
+    #[error("Storage is full")]
+    StorageFull,
+    
+    #[error("Upload {key} was flagged by the virus scanner: {signature}")]
+    Infected { key: String, signature: String },
+    
+    #[error("Blob {key} is quarantined until its virus scan finishes")]
+    Quarantined { key: String },
+    
+    #[error("Attachments of type {content_type} aren't allowed here")]
+    DisallowedType { content_type: String },
+}
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("Export not found: {export_id}")]
+    NotFound { export_id: uuid::Uuid },
+    
+    #[error("Export {export_id} is not ready yet")]
+    NotReady { export_id: uuid::Uuid },
+    
+    #[error("Download link is invalid or has expired")]
+    InvalidLink,
+    
+    #[error("Export I/O failed: {0}")]
+    Io(#[from] std::io::Error),
+    
+    #[error("Database error: {0}")]
+    Database(#[from] DatabaseError),
+}
+
 // From implementations for web-push errors
 impl From<web_push::WebPushError> for PushNotificationError {
     fn from(err: web_push::WebPushError) -> Self {
@@ -277,16 +443,137 @@ impl From<sqlx::Error> for PushNotificationError {
 // Application-level result type
 pub type Result<T> = std::result::Result<T, anyhow::Error>;
 
+/// Stable, machine-readable identifier for an error, logged as `error_code`
+///
+/// Client errors use the same code as the API response; internal errors,
+/// which clients only see as INTERNAL_ERROR, get a specific one so logs
+/// can tell them apart. Codes never change once shipped.
+pub trait ErrorCode {
+    fn code(&self) -> &'static str;
+}
+
+impl ErrorCode for DatabaseError {
+    fn code(&self) -> &'static str {
+        match self {
+            DatabaseError::Connection(_) => "DATABASE_CONNECTION",
+            DatabaseError::Migration { .. } => "DATABASE_MIGRATION",
+            DatabaseError::Transaction { .. } => "DATABASE_TRANSACTION",
+            DatabaseError::ConstraintViolation { .. } => "DATABASE_CONSTRAINT",
+            DatabaseError::DataIntegrity { .. } => "DATABASE_DATA_INTEGRITY",
+            DatabaseError::UuidParse(_) => "DATABASE_UUID_PARSE",
+            DatabaseError::WriterChannelClosed => "DATABASE_WRITER_CLOSED",
+        }
+    }
+}
+
+impl ErrorCode for MessageError {
+    fn code(&self) -> &'static str {
+        match self {
+            MessageError::Authorization { .. } => "ROOM_ACCESS_DENIED",
+            MessageError::InvalidContent { .. } => "INVALID_CONTENT",
+            MessageError::ContentTooLong { .. } => "MESSAGE_TOO_LONG",
+            MessageError::ContentTooShort => "MESSAGE_EMPTY",
+            MessageError::Database(_) => "MESSAGE_DATABASE",
+            MessageError::Broadcast(_) => "MESSAGE_BROADCAST",
+            MessageError::RateLimit { .. } => "RATE_LIMIT_EXCEEDED",
+            MessageError::NotFound { .. } => "MESSAGE_NOT_FOUND",
+            MessageError::PostingRestricted { .. } => "ROOM_POSTING_RESTRICTED",
+            MessageError::SeenByUnavailable { .. } => "SEEN_BY_UNAVAILABLE",
+            MessageError::WriteTimeout { .. } => "MESSAGE_WRITE_TIMEOUT",
+            MessageError::SavedLimitReached { .. } => "SAVED_MESSAGE_LIMIT_REACHED",
+            MessageError::Blocked { .. } => "MESSAGE_BLOCKED",
+            MessageError::UnknownCannedResponse { .. } => "UNKNOWN_CANNED_RESPONSE",
+            MessageError::ModerationUnavailable => "MODERATION_UNAVAILABLE",
+        }
+    }
+}
+
+impl ErrorCode for RoomError {
+    fn code(&self) -> &'static str {
+        match self {
+            RoomError::NotFound { .. } => "ROOM_NOT_FOUND",
+            RoomError::AlreadyMember { .. } => "ALREADY_MEMBER",
+            RoomError::NotAuthorized { .. } => "ROOM_PERMISSION_DENIED",
+            RoomError::InvalidName { .. } => "INVALID_ROOM_NAME",
+            RoomError::InvalidTypeChange { .. } => "INVALID_ROOM_TYPE_CHANGE",
+            RoomError::NotOpen { .. } => "ROOM_NOT_OPEN",
+            RoomError::DirectRoomFull { .. } => "DIRECT_ROOM_FULL",
+            RoomError::JoinRateLimit { .. } => "JOIN_RATE_LIMIT_EXCEEDED",
+            RoomError::InvalidAttachmentType { .. } => "INVALID_ATTACHMENT_TYPE",
+            RoomError::UnknownSound { .. } => "UNKNOWN_SOUND",
+            RoomError::InvalidCannedResponse { .. } => "INVALID_CANNED_RESPONSE",
+            RoomError::CannedResponseNotFound { .. } => "CANNED_RESPONSE_NOT_FOUND",
+            RoomError::CannedResponseLimitReached { .. } => "CANNED_RESPONSE_LIMIT_REACHED",
+            RoomError::ConfirmationRequired { .. } => "CONFIRMATION_REQUIRED",
+            RoomError::Database(_) => "ROOM_DATABASE",
+        }
+    }
+}
+
+impl ErrorCode for AuthError {
+    fn code(&self) -> &'static str {
+        match self {
+            AuthError::InvalidCredentials => "INVALID_CREDENTIALS",
+            AuthError::SessionExpired => "SESSION_EXPIRED",
+            // Reported to clients as invalid credentials, so accounts
+            // can't be enumerated
+            AuthError::UserNotFound { .. } => "USER_NOT_FOUND",
+            AuthError::EmailExists { .. } => "EMAIL_EXISTS",
+            AuthError::InvalidEmail { .. } => "INVALID_EMAIL",
+            AuthError::InvalidName { .. } => "INVALID_NAME",
+            AuthError::InvalidTimezone { .. } => "INVALID_TIMEZONE",
+            AuthError::WeakPassword { .. } => "WEAK_PASSWORD",
+            AuthError::Database(_) => "AUTH_DATABASE",
+            AuthError::PasswordHash(_) => "AUTH_PASSWORD_HASH",
+            AuthError::TokenGeneration => "AUTH_TOKEN_GENERATION",
+            AuthError::ImpersonationForbidden { .. } => "IMPERSONATION_FORBIDDEN",
+        }
+    }
+}
+
+impl ErrorCode for BotError {
+    fn code(&self) -> &'static str {
+        match self {
+            BotError::InvalidToken => "INVALID_BOT_TOKEN",
+            BotError::NotFound { .. } => "BOT_NOT_FOUND",
+            BotError::NotABot { .. } => "NOT_A_BOT",
+            BotError::TokenExists => "TOKEN_EXISTS",
+            BotError::QuotaExceeded { .. } => "BOT_QUOTA_EXCEEDED",
+            BotError::InvalidWebhookUrl { .. } => "INVALID_WEBHOOK_URL",
+            BotError::WebhookUrlBlocked { .. } => "WEBHOOK_URL_BLOCKED",
+            BotError::WebhookDeliveryFailed { .. } => "WEBHOOK_DELIVERY_FAILED",
+            BotError::WebhookTimeout { .. } => "WEBHOOK_TIMEOUT",
+            BotError::InvalidName { .. } => "INVALID_BOT_NAME",
+            BotError::PostingRestricted { .. } => "ROOM_POSTING_RESTRICTED",
+            BotError::WebhookNotFound { .. } => "WEBHOOK_NOT_FOUND",
+            BotError::BridgeNotFound { .. } => "BRIDGE_NOT_FOUND",
+            BotError::NoWebhook { .. } => "NO_WEBHOOK",
+            BotError::OnBehalfNotGranted { .. } => "ON_BEHALF_NOT_GRANTED",
+            BotError::InvalidOnBehalf { .. } => "INVALID_ON_BEHALF",
+            BotError::Database(e) => e.code(),
+            BotError::HttpRequest(_) => "BOT_HTTP_REQUEST",
+            BotError::JsonSerialization(_) => "BOT_JSON_SERIALIZATION",
+        }
+    }
+}
+
 // Conversion implementations for HTTP responses
 impl From<MessageError> for axum::http::StatusCode {
     fn from(err: MessageError) -> Self {
         match err {
-            MessageError::Authorization { .. } => axum::http::StatusCode::FORBIDDEN,
+            MessageError::Authorization { .. }
+            | MessageError::PostingRestricted { .. } => axum::http::StatusCode::FORBIDDEN,
             MessageError::InvalidContent { .. } 
             | MessageError::ContentTooLong { .. }
-            | MessageError::ContentTooShort => axum::http::StatusCode::BAD_REQUEST,
+            | MessageError::ContentTooShort
+            | MessageError::SeenByUnavailable { .. }
+            | MessageError::UnknownCannedResponse { .. } => axum::http::StatusCode::BAD_REQUEST,
             MessageError::NotFound { .. } => axum::http::StatusCode::NOT_FOUND,
             MessageError::RateLimit { .. } => axum::http::StatusCode::TOO_MANY_REQUESTS,
+            MessageError::WriteTimeout { .. } => axum::http::StatusCode::GATEWAY_TIMEOUT,
+            MessageError::SavedLimitReached { .. } => axum::http::StatusCode::CONFLICT,
+            MessageError::Blocked { .. } => axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+            MessageError::ModerationUnavailable => axum::http::StatusCode::SERVICE_UNAVAILABLE,
             MessageError::Database(_) | MessageError::Broadcast(_) => {
                 axum::http::StatusCode::INTERNAL_SERVER_ERROR
             }
@@ -297,10 +584,20 @@ impl From<MessageError> for axum::http::StatusCode {
 impl From<RoomError> for axum::http::StatusCode {
     fn from(err: RoomError) -> Self {
         match err {
-            RoomError::NotFound { .. } => axum::http::StatusCode::NOT_FOUND,
+            RoomError::NotFound { .. }
+            | RoomError::CannedResponseNotFound { .. } => axum::http::StatusCode::NOT_FOUND,
             RoomError::NotAuthorized { .. } => axum::http::StatusCode::FORBIDDEN,
-            RoomError::AlreadyMember { .. } => axum::http::StatusCode::CONFLICT,
-            RoomError::InvalidName { .. } => axum::http::StatusCode::BAD_REQUEST,
+            RoomError::AlreadyMember { .. }
+            | RoomError::DirectRoomFull { .. }
+            | RoomError::CannedResponseLimitReached { .. } => axum::http::StatusCode::CONFLICT,
+            RoomError::InvalidName { .. }
+            | RoomError::InvalidTypeChange { .. }
+            | RoomError::NotOpen { .. }
+            | RoomError::InvalidAttachmentType { .. }
+            | RoomError::UnknownSound { .. }
+            | RoomError::InvalidCannedResponse { .. } => axum::http::StatusCode::BAD_REQUEST,
+            RoomError::JoinRateLimit { .. } => axum::http::StatusCode::TOO_MANY_REQUESTS,
+            RoomError::ConfirmationRequired { .. } => axum::http::StatusCode::PRECONDITION_REQUIRED,
             RoomError::Database(_) => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -313,8 +610,11 @@ impl From<AuthError> for axum::http::StatusCode {
             | AuthError::SessionExpired => axum::http::StatusCode::UNAUTHORIZED,
             AuthError::UserNotFound { .. } => axum::http::StatusCode::NOT_FOUND,
             AuthError::EmailExists { .. } => axum::http::StatusCode::CONFLICT,
+            AuthError::ImpersonationForbidden { .. } => axum::http::StatusCode::FORBIDDEN,
             AuthError::InvalidEmail { .. } 
-            | AuthError::WeakPassword => axum::http::StatusCode::BAD_REQUEST,
+            | AuthError::InvalidName { .. }
+            | AuthError::InvalidTimezone { .. }
+            | AuthError::WeakPassword { .. } => axum::http::StatusCode::BAD_REQUEST,
             AuthError::Database(_) 
             | AuthError::PasswordHash(_) 
             | AuthError::TokenGeneration => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
@@ -347,8 +647,8 @@ pub enum SetupError {
     #[error("Invalid email format: {email}")]
     InvalidEmail { email: String },
     
-    #[error("Password too weak: {reason}")]
-    WeakPassword { reason: String },
+    #[error("Password too weak: {}", reasons.join("; "))]
+    WeakPassword { reasons: Vec<String> },
     
     #[error("Admin account creation failed: {0}")]
     AdminCreationFailed(String),
@@ -367,17 +667,28 @@ pub enum SetupError {
     
     #[error("Password hashing failed: {0}")]
     PasswordHash(#[from] bcrypt::BcryptError),
+    
+    #[error("Too many setup attempts, retry after {retry_after_secs} seconds")]
+    TooManyAttempts { retry_after_secs: u64 },
 }
 
 impl From<BotError> for axum::http::StatusCode {
     fn from(err: BotError) -> Self {
         match err {
             BotError::InvalidToken => axum::http::StatusCode::UNAUTHORIZED,
-            BotError::NotFound { .. } => axum::http::StatusCode::NOT_FOUND,
-            BotError::NotABot { .. } => axum::http::StatusCode::FORBIDDEN,
-            BotError::TokenExists => axum::http::StatusCode::CONFLICT,
+            BotError::NotFound { .. }
+            | BotError::WebhookNotFound { .. }
+            | BotError::BridgeNotFound { .. } => axum::http::StatusCode::NOT_FOUND,
+            BotError::NotABot { .. }
+            | BotError::PostingRestricted { .. }
+            | BotError::OnBehalfNotGranted { .. }
+            | BotError::InvalidOnBehalf { .. } => axum::http::StatusCode::FORBIDDEN,
+            BotError::TokenExists
+            | BotError::QuotaExceeded { .. } => axum::http::StatusCode::CONFLICT,
             BotError::InvalidWebhookUrl { .. } 
-            | BotError::InvalidName { .. } => axum::http::StatusCode::BAD_REQUEST,
+            | BotError::WebhookUrlBlocked { .. }
+            | BotError::InvalidName { .. }
+            | BotError::NoWebhook { .. } => axum::http::StatusCode::BAD_REQUEST,
             BotError::WebhookDeliveryFailed { .. }
             | BotError::WebhookTimeout { .. }
             | BotError::Database(_)
@@ -387,10 +698,27 @@ impl From<BotError> for axum::http::StatusCode {
     }
 }
 
+impl From<StorageError> for axum::http::StatusCode {
+    fn from(err: StorageError) -> Self {
+        match err {
+            StorageError::NotFound { .. } => axum::http::StatusCode::NOT_FOUND,
+            StorageError::InvalidKey { .. } => axum::http::StatusCode::BAD_REQUEST,
+            StorageError::QuotaExceeded { .. } => axum::http::StatusCode::PAYLOAD_TOO_LARGE,
+            StorageError::StorageFull => axum::http::StatusCode::INSUFFICIENT_STORAGE,
+            StorageError::Infected { .. } => axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+            StorageError::Quarantined { .. } => axum::http::StatusCode::LOCKED,
+            StorageError::DisallowedType { .. } => axum::http::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            StorageError::Io(_)
+            | StorageError::Backend { .. } => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
 impl From<SetupError> for axum::http::StatusCode {
     fn from(err: SetupError) -> Self {
         match err {
             SetupError::NotFirstRun => axum::http::StatusCode::CONFLICT,
+            SetupError::TooManyAttempts { .. } => axum::http::StatusCode::TOO_MANY_REQUESTS,
             SetupError::InvalidEmail { .. } 
             | SetupError::WeakPassword { .. }
             | SetupError::InvalidConfiguration { .. } => axum::http::StatusCode::BAD_REQUEST,
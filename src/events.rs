@@ -0,0 +1,208 @@
+//! In-process domain events
+//!
+//! Services emit a [`DomainEvent`] once a change is persisted, and side
+//! effects (WebSocket fan-out, push notifications, ...) subscribe to the
+//! [`EventBus`] instead of being called from the service directly. Subscribers
+//! run in registration order and are awaited by `emit`, so a caller that gets
+//! its result back knows the event has been handled. Subscriber failures are
+//! logged by the subscriber and never fail the operation that emitted.
+
+use async_trait::async_trait;
+use chrono::Utc;
+use std::sync::{Arc, RwLock};
+
+use crate::database::CampfireDatabase;
+use crate::models::{Message, RoomId, UserId, WebSocketMessage};
+use crate::services::connection::ConnectionManager;
+use crate::services::push::PushNotificationService;
+use crate::sounds::SoundManager;
+
+#[derive(Debug, Clone)]
+pub enum DomainEvent {
+    MessageCreated { message: Message },
+    /// `added_by` is `user_id` itself when they joined on their own
+    UserJoined { room_id: RoomId, user_id: UserId, added_by: UserId },
+    UserRemoved { room_id: RoomId, user_id: UserId },
+    RoomArchived { room_id: RoomId },
+    /// `blob_keys` are the room's uploads, for the blob store to delete
+    RoomDeleted { room_id: RoomId, blob_keys: Vec<String> },
+}
+
+#[async_trait]
+pub trait EventSubscriber: Send + Sync {
+    async fn handle(&self, event: &DomainEvent);
+}
+
+/// Fans events out to every registered subscriber
+///
+/// Clones share the subscriber list, so a subscriber registered through any
+/// clone sees events emitted through all of them.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<RwLock<Vec<Arc<dyn EventSubscriber>>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(&self, subscriber: Arc<dyn EventSubscriber>) {
+        self.subscribers.write().unwrap().push(subscriber);
+    }
+
+    pub async fn emit(&self, event: DomainEvent) {
+        // Don't hold the lock across awaits
+        let subscribers = self.subscribers.read().unwrap().clone();
+        for subscriber in subscribers {
+            subscriber.handle(&event).await;
+        }
+    }
+}
+
+/// Pushes events to the WebSocket clients in the affected room
+pub struct BroadcastSubscriber {
+    connection_manager: Arc<dyn ConnectionManager>,
+}
+
+impl BroadcastSubscriber {
+    pub fn new(connection_manager: Arc<dyn ConnectionManager>) -> Self {
+        Self { connection_manager }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for BroadcastSubscriber {
+    async fn handle(&self, event: &DomainEvent) {
+        match event {
+            DomainEvent::MessageCreated { message } => {
+                let ws_message = WebSocketMessage::NewMessage { message: message.clone() };
+                if let Err(e) = self.connection_manager.broadcast_to_room(message.room_id, ws_message).await {
+                    tracing::warn!("Failed to broadcast message {}: {}", message.id.0, e);
+                }
+
+                for sound_name in message.sound_commands.iter().filter(|name| SoundManager::sound_exists(name)) {
+                    let sound_message = WebSocketMessage::SoundPlayback {
+                        sound_name: sound_name.clone(),
+                        triggered_by: message.creator_id,
+                        room_id: message.room_id,
+                        timestamp: Utc::now(),
+                    };
+                    if let Err(e) = self.connection_manager.broadcast_to_room(message.room_id, sound_message).await {
+                        tracing::warn!("Failed to broadcast sound playback {}: {}", sound_name, e);
+                    }
+                }
+            }
+            DomainEvent::RoomArchived { room_id } => {
+                let update = WebSocketMessage::RoomArchived { room_id: *room_id };
+                if let Err(e) = self.connection_manager.broadcast_to_room(*room_id, update).await {
+                    tracing::debug!("RoomArchived for room {} not delivered: {}", room_id, e);
+                }
+            }
+            DomainEvent::RoomDeleted { room_id, .. } => {
+                let closed = self.connection_manager.close_room(*room_id).await;
+                tracing::info!("Detached {} connection(s) from deleted room {}", closed, room_id);
+            }
+            // Presence covers what the rest of the room sees; the member's
+            // own clients need to know to refresh their room list
+            DomainEvent::UserJoined { room_id, user_id, .. } => {
+                self.connection_manager.join_room(*room_id, *user_id).await;
+                let update = WebSocketMessage::RoomMembershipChanged { room_id: *room_id, added: true };
+                if let Err(e) = self.connection_manager.send_to_user(*user_id, update).await {
+                    tracing::debug!("RoomMembershipChanged for user {} not delivered: {}", user_id, e);
+                }
+            }
+            DomainEvent::UserRemoved { room_id, user_id } => {
+                // Tell them before they stop receiving the room's frames
+                let update = WebSocketMessage::RoomMembershipChanged { room_id: *room_id, added: false };
+                if let Err(e) = self.connection_manager.send_to_user(*user_id, update).await {
+                    tracing::debug!("RoomMembershipChanged for user {} not delivered: {}", user_id, e);
+                }
+                self.connection_manager.leave_room(*room_id, *user_id).await;
+            }
+        }
+    }
+}
+
+/// Sends message, mention and sound push notifications for new messages
+pub struct PushSubscriber {
+    db: Arc<CampfireDatabase>,
+    push_service: Arc<dyn PushNotificationService>,
+}
+
+impl PushSubscriber {
+    pub fn new(db: Arc<CampfireDatabase>, push_service: Arc<dyn PushNotificationService>) -> Self {
+        Self { db, push_service }
+    }
+
+    async fn message_created(&self, message: &Message) {
+        let (room, sender) = match (
+            self.db.get_room_by_id(message.room_id).await,
+            self.db.get_user_by_id(message.creator_id).await,
+        ) {
+            (Ok(Some(room)), Ok(Some(sender))) => (room, sender),
+            _ => return,
+        };
+
+        if let Err(e) = self.push_service.send_message_notification(message, &room, &sender.name).await {
+            tracing::warn!("Failed to send push notification for message {}: {}", message.id.0, e);
+        }
+
+        for mention in &message.mentions {
+            if let Ok(Some(mentioned_user)) = self.db.get_user_by_email(mention).await {
+                if let Err(e) = self.push_service.send_mention_notification(message, &room, &sender.name, mentioned_user.id).await {
+                    tracing::warn!("Failed to send mention notification to {}: {}", mention, e);
+                }
+            }
+        }
+
+        for sound_name in message.sound_commands.iter().filter(|name| SoundManager::sound_exists(name)) {
+            if let Err(e) = self.push_service.send_sound_notification(sound_name, &room, &sender.name).await {
+                tracing::warn!("Failed to send sound notification for {}: {}", sound_name, e);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for PushSubscriber {
+    async fn handle(&self, event: &DomainEvent) {
+        if let DomainEvent::MessageCreated { message } = event {
+            self.message_created(message).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use uuid::Uuid;
+
+    #[derive(Default)]
+    struct RecordingSubscriber {
+        seen: Mutex<Vec<DomainEvent>>,
+    }
+
+    #[async_trait]
+    impl EventSubscriber for RecordingSubscriber {
+        async fn handle(&self, event: &DomainEvent) {
+            self.seen.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_message_created_reaches_subscriber() {
+        let bus = EventBus::new();
+        let subscriber = Arc::new(RecordingSubscriber::default());
+        // Registering through a clone still hears events from the original
+        bus.clone().subscribe(subscriber.clone());
+
+        let message = Message::new(RoomId::new(), UserId::new(), "hello".to_string(), Uuid::new_v4());
+        bus.emit(DomainEvent::MessageCreated { message: message.clone() }).await;
+
+        let seen = subscriber.seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert!(matches!(&seen[0], DomainEvent::MessageCreated { message: m } if m.id == message.id));
+    }
+}
@@ -0,0 +1,463 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Redirect, Response},
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::errors::RoomError;
+use crate::middleware::{session::AuthenticatedUser, PathId};
+use crate::models::{RoomId, UserId};
+use crate::services::room::RoomServiceTrait;
+use crate::storage::thumbnails::{is_thumbnailable, make_thumbnail, thumbnail_key};
+use crate::storage::{validate_key, QuotaBlobStore, StoredBlob};
+use crate::AppState;
+
+/// No scripts, styles, frames or fetches, and a sandbox, so an HTML or SVG
+/// upload opened directly can't act as the app
+pub const ATTACHMENT_CSP: &str = "default-src 'none'; sandbox";
+
+/// Name offered for the download: the key's last segment, with anything
+/// that could break out of the quoted header value replaced
+fn download_filename(key: &str) -> String {
+    key.rsplit('/')
+        .next()
+        .unwrap_or(key)
+        .chars()
+        .map(|c| if c.is_ascii_graphic() && c != '"' && c != '\\' || c == ' ' { c } else { '_' })
+        .collect()
+}
+
+/// Builds the response for a stored attachment
+///
+/// Browsers are told not to sniff the type, and anything that isn't
+/// previewable is sent as a download rather than rendered.
+pub fn attachment_response(blob: StoredBlob, filename: &str, previewable: bool) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(&blob.content_type)
+            .unwrap_or_else(|_| HeaderValue::from_static("application/octet-stream")),
+    );
+    headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    headers.insert(header::CONTENT_SECURITY_POLICY, HeaderValue::from_static(ATTACHMENT_CSP));
+
+    let disposition = if previewable { "inline" } else { "attachment" };
+    let disposition = format!("{}; filename=\"{}\"", disposition, filename);
+    if let Ok(value) = HeaderValue::from_str(&disposition) {
+        headers.insert(header::CONTENT_DISPOSITION, value);
+    }
+
+    (headers, blob.data).into_response()
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UploadAttachmentQuery {
+    pub filename: Option<String>,
+}
+
+/// What a client needs to attach an upload to a message
+#[derive(Debug, Serialize)]
+pub struct UploadedAttachment {
+    pub key: String,
+    pub size_bytes: u64,
+    pub content_type: String,
+    pub url: String,
+    /// Downscaled copy of an image upload; None for other types and images
+    /// too large to decode
+    pub thumbnail_url: Option<String>,
+}
+
+/// Last segment of the client's filename, reduced to characters that are
+/// safe in a key; `file` when nothing is left
+fn upload_filename(filename: Option<&str>) -> String {
+    let name: String = filename
+        .and_then(|name| name.rsplit(['/', '\\']).next())
+        .unwrap_or("")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .take(128)
+        .collect();
+    if name.trim_matches('.').is_empty() {
+        "file".to_string()
+    } else {
+        name
+    }
+}
+
+/// Stores an upload to `room_id` under a fresh key, through the store's
+/// quotas, scanner and the room's content-type allowlist
+///
+/// Image uploads also get a thumbnail stored next to them; failing to make
+/// or store one leaves the upload without it.
+pub(crate) async fn store_upload(
+    rooms: &dyn RoomServiceTrait,
+    blobs: &QuotaBlobStore,
+    user_id: UserId,
+    room_id: RoomId,
+    filename: Option<&str>,
+    content_type: &str,
+    data: Vec<u8>,
+) -> Result<UploadedAttachment, StatusCode> {
+    match rooms.check_room_access(room_id, user_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(StatusCode::FORBIDDEN),
+        Err(RoomError::NotFound { .. }) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            warn!("Room access check for upload to {} failed: {}", room_id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+    if data.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let key = format!("attachments/{}/{}", Uuid::new_v4(), upload_filename(filename));
+    validate_key(&key)?;
+
+    let size_bytes = data.len() as u64;
+    let limits = blobs.thumbnail_limits();
+    let source = (limits.enabled() && is_thumbnailable(content_type)).then(|| data.clone());
+    blobs
+        .put_attachment(user_id, room_id, &key, data, content_type)
+        .await
+        .map_err(|e| {
+            warn!("Upload of {} to room {} failed: {}", key, room_id, e);
+            StatusCode::from(e)
+        })?;
+
+    let mut thumbnail_url = None;
+    if let Some(source) = source {
+        let source_type = content_type.to_string();
+        let thumbnail = tokio::task::spawn_blocking(move || make_thumbnail(&source, &source_type, &limits))
+            .await
+            .ok()
+            .flatten();
+        if let Some(thumbnail) = thumbnail {
+            let thumb_key = thumbnail_key(&key);
+            match blobs.put_attachment(user_id, room_id, &thumb_key, thumbnail, content_type).await {
+                Ok(()) => thumbnail_url = Some(format!("/api/attachments/{}", thumb_key)),
+                Err(e) => warn!("Storing thumbnail {} failed: {}", thumb_key, e),
+            }
+        }
+    }
+
+    Ok(UploadedAttachment {
+        url: format!("/api/attachments/{}", key),
+        key,
+        size_bytes,
+        content_type: content_type.to_string(),
+        thumbnail_url,
+    })
+}
+
+/// POST /api/rooms/:id/attachments?filename=photo.png
+///
+/// Uploads a file to attach to messages in the room. The body is the raw
+/// file and `Content-Type` its type. The returned `key` goes in a message's
+/// `attachments`.
+///
+/// # Response
+/// - 201 Created: `{key, size_bytes, content_type, url, thumbnail_url}`
+/// - 400 Bad Request: Empty body
+/// - 401 Unauthorized: Invalid or missing session token
+/// - 403 Forbidden: User does not have access to this room
+/// - 404 Not Found: Room not found
+/// - 413 Payload Too Large: Over the user's storage quota
+/// - 415 Unsupported Media Type: Type not allowed in this room
+/// - 422 Unprocessable Entity: Flagged by the virus scanner
+/// - 507 Insufficient Storage: Instance storage is full
+pub async fn upload_attachment(
+    State(state): State<AppState>,
+    auth_user: AuthenticatedUser,
+    PathId(room_id): PathId<RoomId>,
+    Query(query): Query<UploadAttachmentQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<UploadedAttachment>), StatusCode> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/octet-stream");
+
+    let uploaded = store_upload(
+        state.room_service.as_ref(),
+        &state.blob_store,
+        auth_user.user.id,
+        room_id,
+        query.filename.as_deref(),
+        content_type,
+        body.to_vec(),
+    )
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(uploaded)))
+}
+
+/// Whether `user_id` may read the blob: attachments follow their room's
+/// access, and a blob not posted to a room is only its uploader's. Blobs
+/// they can't read look like they don't exist.
+pub(crate) async fn authorize_download(
+    rooms: &dyn RoomServiceTrait,
+    blobs: &QuotaBlobStore,
+    user_id: UserId,
+    key: &str,
+) -> Result<(), StatusCode> {
+    let owner = blobs.blob_owner(key).await.map_err(|e| {
+        warn!("Failed to look up owner of attachment {}: {}", key, e);
+        StatusCode::from(e)
+    })?;
+    let owner = owner.ok_or(StatusCode::NOT_FOUND)?;
+
+    match owner.room_id {
+        Some(room_id) => match rooms.check_room_access(room_id, user_id).await {
+            Ok(Some(_)) => Ok(()),
+            Ok(None) | Err(RoomError::NotFound { .. }) => Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                warn!("Room access check for attachment {} failed: {}", key, e);
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        },
+        None if owner.user_id == Some(user_id) => Ok(()),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AttachmentLinkQuery {
+    pub expires: Option<i64>,
+    pub signature: Option<String>,
+}
+
+/// GET /api/attachments/*key
+///
+/// Serves an uploaded file with `X-Content-Type-Options: nosniff` and a
+/// sandboxed CSP; types that aren't previewable are served as downloads.
+/// Attachments posted to a room can be read by those with access to the
+/// room; others only by their uploader.
+///
+/// When a separate attachment origin is configured, the app checks access
+/// and redirects to a short-lived signed link there. The origin doesn't see
+/// the session cookie and serves only signed links.
+///
+/// # Response
+/// - 200 OK: The file
+/// - 307 Temporary Redirect: Served from the attachment origin
+/// - 400 Bad Request: Invalid key
+/// - 401 Unauthorized: Invalid or missing session token
+/// - 403 Forbidden: Missing, invalid or expired link on the attachment origin
+/// - 404 Not Found: No such attachment, or no access to it
+/// - 423 Locked: Still being virus-scanned
+pub async fn serve_attachment(
+    State(state): State<AppState>,
+    auth_user: Option<AuthenticatedUser>,
+    Path(key): Path<String>,
+    Query(link): Query<AttachmentLinkQuery>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let key = key.trim_start_matches('/');
+    validate_key(key)?;
+
+    let on_origin = state.blob_store.attachment_origin().is_some_and(|origin| {
+        let origin_host = origin.split_once("://").map_or(origin, |(_, host)| host).trim_end_matches('/');
+        let host = headers.get(header::HOST).and_then(|h| h.to_str().ok()).unwrap_or("");
+        host.eq_ignore_ascii_case(origin_host)
+    });
+
+    if on_origin {
+        let signed = match (link.expires, link.signature.as_deref()) {
+            (Some(expires), Some(signature)) => state.blob_store.verify_origin_link(key, expires, signature),
+            _ => false,
+        };
+        if !signed {
+            return Err(StatusCode::FORBIDDEN);
+        }
+    } else {
+        let auth_user = auth_user.ok_or(StatusCode::UNAUTHORIZED)?;
+        authorize_download(state.room_service.as_ref(), &state.blob_store, auth_user.user.id, key).await?;
+        if let Some(url) = state.blob_store.signed_origin_url(key) {
+            return Ok(Redirect::temporary(&url).into_response());
+        }
+    }
+
+    let blob = state.blob_store.get_with_type(key).await.map_err(|e| {
+        warn!("Failed to serve attachment {}: {}", key, e);
+        StatusCode::from(e)
+    })?;
+    let previewable = state.blob_store.is_previewable(&blob.content_type);
+    Ok(attachment_response(blob, &download_filename(key), previewable))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header<'a>(response: &'a Response, name: header::HeaderName) -> &'a str {
+        response.headers().get(name).unwrap().to_str().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_upload_goes_through_room_access_and_user_quota() {
+        use crate::config::StorageConfig;
+        use crate::database::CampfireDatabase;
+        use crate::models::{InvolvementLevel, Membership, Room, RoomType, User};
+        use crate::services::RoomService;
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let config = StorageConfig {
+            local_path: dir.path().to_path_buf(),
+            user_quota_bytes: 10,
+            ..Default::default()
+        };
+        let blobs = QuotaBlobStore::new(
+            Arc::new(crate::storage::LocalBlobStore::new(dir.path().to_path_buf())),
+            db.clone(),
+            &config,
+        );
+        let rooms = RoomService::new(db.clone());
+
+        let mut user_ids = Vec::new();
+        for name in ["member", "outsider"] {
+            let user_id = UserId::new();
+            db.create_user(User {
+                id: user_id,
+                ..User::for_tests(name)
+            }).await.unwrap();
+            user_ids.push(user_id);
+        }
+        let (member, outsider) = (user_ids[0], user_ids[1]);
+        let room_id = RoomId::new();
+        db.create_room(Room {
+            id: room_id,
+            ..Room::for_tests("Uploads", RoomType::Closed)
+        }).await.unwrap();
+        db.create_membership(Membership {
+            room_id,
+            user_id: member,
+            involvement_level: InvolvementLevel::Member,
+            created_at: chrono::Utc::now(),
+        }).await.unwrap();
+
+        let uploaded = store_upload(&rooms, &blobs, member, room_id, Some("../notes.txt"), "text/plain", vec![1; 8])
+            .await
+            .unwrap();
+        assert!(uploaded.key.starts_with("attachments/") && uploaded.key.ends_with("/notes.txt"));
+        assert_eq!(blobs.owned_blob_size(member, &uploaded.key).await.unwrap(), Some(8));
+
+        // The per-user quota applies to real uploads
+        let over_quota = store_upload(&rooms, &blobs, member, room_id, Some("more.txt"), "text/plain", vec![1; 4]).await;
+        assert_eq!(over_quota.unwrap_err(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let outside = store_upload(&rooms, &blobs, outsider, room_id, Some("x.txt"), "text/plain", vec![1; 1]).await;
+        assert_eq!(outside.unwrap_err(), StatusCode::FORBIDDEN);
+
+        // Reading follows the room the attachment was posted to
+        assert_eq!(authorize_download(&rooms, &blobs, member, &uploaded.key).await, Ok(()));
+        assert_eq!(
+            authorize_download(&rooms, &blobs, outsider, &uploaded.key).await,
+            Err(StatusCode::NOT_FOUND)
+        );
+        assert_eq!(
+            authorize_download(&rooms, &blobs, member, "attachments/missing/file").await,
+            Err(StatusCode::NOT_FOUND)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_image_uploads_get_a_bounded_thumbnail() {
+        use crate::config::StorageConfig;
+        use crate::database::CampfireDatabase;
+        use crate::models::{Room, RoomType, User};
+        use crate::services::RoomService;
+        use image::{DynamicImage, ImageFormat, RgbImage};
+        use std::io::Cursor;
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let config = StorageConfig {
+            local_path: dir.path().to_path_buf(),
+            thumbnail_max_dimension: 160,
+            thumbnail_max_source_dimension: 4000,
+            ..Default::default()
+        };
+        let blobs = QuotaBlobStore::new(
+            Arc::new(crate::storage::LocalBlobStore::new(dir.path().to_path_buf())),
+            db.clone(),
+            &config,
+        );
+        let rooms = RoomService::new(db.clone());
+
+        let user_id = UserId::new();
+        db.create_user(User {
+            id: user_id,
+            ..User::for_tests("uploader")
+        }).await.unwrap();
+        let room_id = RoomId::new();
+        db.create_room(Room {
+            id: room_id,
+            ..Room::for_tests("Photos", RoomType::Open)
+        }).await.unwrap();
+
+        let image = |width, height, format| {
+            let mut encoded = Cursor::new(Vec::new());
+            DynamicImage::ImageRgb8(RgbImage::new(width, height)).write_to(&mut encoded, format).unwrap();
+            encoded.into_inner()
+        };
+
+        for (content_type, format) in [("image/png", ImageFormat::Png), ("image/jpeg", ImageFormat::Jpeg)] {
+            let uploaded = store_upload(&rooms, &blobs, user_id, room_id, Some("photo"), content_type, image(3000, 1500, format))
+                .await
+                .unwrap();
+            let thumb_key = uploaded.thumbnail_url.unwrap().trim_start_matches("/api/attachments/").to_string();
+            assert_eq!(thumb_key, thumbnail_key(&uploaded.key));
+
+            let thumbnail = blobs.get_with_type(&thumb_key).await.unwrap();
+            assert_eq!(thumbnail.content_type, content_type);
+            let decoded = image::load_from_memory(&thumbnail.data).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (160, 80));
+            // Thumbnails follow the room like the upload itself
+            assert_eq!(authorize_download(&rooms, &blobs, user_id, &thumb_key).await, Ok(()));
+        }
+
+        // Over the decode limit, and not an image: stored without one
+        let huge = store_upload(&rooms, &blobs, user_id, room_id, Some("huge.png"), "image/png", image(4001, 10, ImageFormat::Png))
+            .await
+            .unwrap();
+        assert!(huge.thumbnail_url.is_none());
+        let text = store_upload(&rooms, &blobs, user_id, room_id, Some("notes.txt"), "text/plain", vec![1; 8])
+            .await
+            .unwrap();
+        assert!(text.thumbnail_url.is_none());
+    }
+
+    #[test]
+    fn test_attachment_responses_carry_security_headers() {
+        let html = StoredBlob {
+            data: b"<script>alert(1)</script>".to_vec(),
+            content_type: "text/html".to_string(),
+        };
+        let response = attachment_response(html, &download_filename("attachments/abc/\"evil\".html"), false);
+        assert_eq!(header(&response, header::CONTENT_TYPE), "text/html");
+        assert_eq!(header(&response, header::X_CONTENT_TYPE_OPTIONS), "nosniff");
+        assert_eq!(header(&response, header::CONTENT_SECURITY_POLICY), ATTACHMENT_CSP);
+        assert_eq!(
+            header(&response, header::CONTENT_DISPOSITION),
+            "attachment; filename=\"_evil_.html\""
+        );
+
+        let image = StoredBlob {
+            data: vec![0x89, b'P', b'N', b'G'],
+            content_type: "image/png".to_string(),
+        };
+        let response = attachment_response(image, "photo.png", true);
+        assert_eq!(header(&response, header::X_CONTENT_TYPE_OPTIONS), "nosniff");
+        assert_eq!(header(&response, header::CONTENT_SECURITY_POLICY), ATTACHMENT_CSP);
+        assert_eq!(header(&response, header::CONTENT_DISPOSITION), "inline; filename=\"photo.png\"");
+    }
+}
@@ -1,15 +1,14 @@
 use axum::{
-    extract::{State, ConnectInfo},
+    extract::State,
     http::{header::SET_COOKIE, StatusCode, HeaderMap},
     response::{IntoResponse, Json, Response},
 };
 use serde_json::json;
-use std::net::SocketAddr;
 use std::collections::HashMap;
 use tracing::{error, info, warn};
 
 use crate::errors::AuthError;
-use crate::middleware::session::SessionToken;
+use crate::middleware::{session::{AuthenticatedUser, SessionToken}, ClientIp};
 use crate::models::LoginResponse;
 use crate::validation::{LoginRequest, sanitization, validate_request};
 use crate::logging::{audit::{AuditAction, AuditLogger}, error_handling::handle_auth_error};
@@ -34,12 +33,12 @@ use crate::AppState;
 /// - 500 Internal Server Error: Server error
 pub async fn login(
     State(state): State<AppState>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIp(client_ip): ClientIp,
     headers: HeaderMap,
     Json(request): Json<LoginRequest>,
 ) -> Response {
     // Extract client information for audit logging
-    let ip_address = addr.ip().to_string();
+    let ip_address = client_ip.to_string();
     let user_agent = headers
         .get("user-agent")
         .and_then(|h| h.to_str().ok())
@@ -174,10 +173,10 @@ pub async fn login(
 /// - 500 Internal Server Error: Server error
 pub async fn logout(
     State(state): State<AppState>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIp(client_ip): ClientIp,
     session_token: SessionToken,
 ) -> Response {
-    let ip_address = addr.ip().to_string();
+    let ip_address = client_ip.to_string();
     let audit_logger = AuditLogger::new(true); // TODO: Get from config
     
     info!("Logout attempt for session token from IP: {}", ip_address);
@@ -265,5 +264,83 @@ pub async fn logout(
     }
 }
 
+/// POST /api/auth/logout-all
+/// 
+/// Revokes every session for the authenticated user and closes their
+/// active WebSocket connections ("log out everywhere")
+/// 
+/// # Authentication
+/// Requires valid session token in Authorization header or cookie
+/// 
+/// # Response
+/// - 200 OK: All sessions revoked, returns number of sessions revoked
+/// - 401 Unauthorized: Invalid or missing session token
+/// - 403 Forbidden: The session was opened by impersonation
+/// - 500 Internal Server Error: Server error
+pub async fn logout_all(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    auth_user: AuthenticatedUser,
+) -> Response {
+    let ip_address = client_ip.to_string();
+    let audit_logger = AuditLogger::new(true); // TODO: Get from config
+    if let Err(refused) = auth_user.forbid_impersonation("log out everywhere") {
+        return refused.into_response();
+    }
+    let user = auth_user.user;
+    
+    info!("Logout-all requested by user {} from IP: {}", user.id, ip_address);
+    
+    let revoked = match state.auth_service.delete_all_sessions(user.id).await {
+        Ok(revoked) => revoked,
+        Err(auth_error) => {
+            error!("Failed to revoke sessions for user {}: {}", user.id, auth_error);
+            return handle_auth_error(auth_error, Some("logout_all")).into_response();
+        }
+    };
+    
+    // Sessions are gone, so live sockets must not keep streaming events
+    let disconnected = match state
+        .message_service
+        .connection_manager()
+        .disconnect_user(user.id)
+        .await
+    {
+        Ok(count) => count,
+        Err(e) => {
+            warn!("Failed to disconnect WebSockets for user {}: {}", user.id, e);
+            0
+        }
+    };
+    
+    let mut details = HashMap::new();
+    details.insert("email".to_string(), user.email.clone());
+    details.insert("scope".to_string(), "all_sessions".to_string());
+    details.insert("sessions_revoked".to_string(), revoked.to_string());
+    details.insert("connections_closed".to_string(), disconnected.to_string());
+    
+    audit_logger.log_user_action(
+        AuditAction::Logout,
+        user.id,
+        "session",
+        None::<String>,
+        details,
+    );
+    
+    let clear_cookie = "session_token=; HttpOnly; SameSite=Lax; Path=/; Max-Age=0";
+    
+    let mut response = (
+        StatusCode::OK,
+        Json(json!({
+            "message": "Logged out of all sessions",
+            "success": true,
+            "sessions_revoked": revoked
+        }))
+    ).into_response();
+    
+    response.headers_mut().insert(SET_COOKIE, clear_cookie.parse().unwrap());
+    response
+}
+
 // Note: auth_error_to_response and create_error_response functions removed
 // Now using the enhanced error handling from logging::error_handling module
\ No newline at end of file
@@ -5,10 +5,9 @@ use axum::{
 };
 use serde_json::json;
 use tracing::{error, info, warn};
-use uuid::Uuid;
 
 use crate::errors::BotError;
-use crate::middleware::session::AuthenticatedUser;
+use crate::middleware::{session::AuthenticatedUser, parse_path_id, PathId};
 use crate::models::*;
 use crate::validation::{CreateBotRequest, CreateBotMessageRequest, sanitization, validate_request};
 use crate::AppState;
@@ -133,11 +132,11 @@ pub async fn create_bot(
 pub async fn get_bot(
     State(state): State<AppState>,
     auth_user: AuthenticatedUser,
-    Path(bot_id): Path<Uuid>,
+    PathId(bot_user_id): PathId<UserId>,
 ) -> Response {
     // Check admin privileges
     if !auth_user.user.admin {
-        warn!("Non-admin user {} attempted to get bot {}", auth_user.user.id, bot_id);
+        warn!("Non-admin user {} attempted to get bot {}", auth_user.user.id, bot_user_id);
         return create_error_response(
             StatusCode::FORBIDDEN,
             "Admin privileges required",
@@ -145,8 +144,6 @@ pub async fn get_bot(
         );
     }
     
-    let bot_user_id = UserId(bot_id);
-    
     match state.bot_service.get_bot(bot_user_id).await {
         Ok(Some(bot)) => {
             (StatusCode::OK, Json(json!({
@@ -162,7 +159,7 @@ pub async fn get_bot(
             )
         }
         Err(bot_error) => {
-            error!("Failed to get bot {}: {}", bot_id, bot_error);
+            error!("Failed to get bot {}: {}", bot_user_id, bot_error);
             bot_error_to_response(bot_error)
         }
     }
@@ -176,7 +173,9 @@ pub async fn get_bot(
 /// ```json
 /// {
 ///   "name": "Updated Bot Name", // optional
-///   "webhook_url": "https://example.com/new-webhook" // optional, empty string to remove
+///   "webhook_url": "https://example.com/new-webhook", // optional, empty string to remove
+///   "auto_join_on_mention": true, // optional, join rooms whose admins mention the bot
+///   "post_on_behalf": true // optional, may post messages attributed to users
 /// }
 /// ```
 /// 
@@ -193,12 +192,12 @@ pub async fn get_bot(
 pub async fn update_bot(
     State(state): State<AppState>,
     auth_user: AuthenticatedUser,
-    Path(bot_id): Path<Uuid>,
+    PathId(bot_user_id): PathId<UserId>,
     Json(request): Json<UpdateBotRequest>,
 ) -> Response {
     // Check admin privileges
     if !auth_user.user.admin {
-        warn!("Non-admin user {} attempted to update bot {}", auth_user.user.id, bot_id);
+        warn!("Non-admin user {} attempted to update bot {}", auth_user.user.id, bot_user_id);
         return create_error_response(
             StatusCode::FORBIDDEN,
             "Admin privileges required",
@@ -206,15 +205,25 @@ pub async fn update_bot(
         );
     }
     
-    let bot_user_id = UserId(bot_id);
-    
-    info!("Updating bot {} for admin {}", bot_id, auth_user.user.id);
+    info!("Updating bot {} for admin {}", bot_user_id, auth_user.user.id);
     
-    match state.bot_service.update_bot(
+    let result = match state.bot_service.update_bot(
         bot_user_id,
         request.name,
         request.webhook_url,
     ).await {
+        Ok(bot) => match request.auto_join_on_mention {
+            Some(enabled) => state.bot_service.set_auto_join_on_mention(bot.id, enabled).await,
+            None => Ok(bot),
+        },
+        Err(e) => Err(e),
+    };
+    let result = match (result, request.post_on_behalf) {
+        (Ok(bot), Some(enabled)) => state.bot_service.set_post_on_behalf(bot.id, enabled).await,
+        (result, _) => result,
+    };
+    
+    match result {
         Ok(bot) => {
             info!("Updated bot: {} ({})", bot.name, bot.id);
             (StatusCode::OK, Json(json!({
@@ -223,7 +232,7 @@ pub async fn update_bot(
             }))).into_response()
         }
         Err(bot_error) => {
-            error!("Failed to update bot {}: {}", bot_id, bot_error);
+            error!("Failed to update bot {}: {}", bot_user_id, bot_error);
             bot_error_to_response(bot_error)
         }
     }
@@ -245,11 +254,11 @@ pub async fn update_bot(
 pub async fn delete_bot(
     State(state): State<AppState>,
     auth_user: AuthenticatedUser,
-    Path(bot_id): Path<Uuid>,
+    PathId(bot_user_id): PathId<UserId>,
 ) -> Response {
     // Check admin privileges
     if !auth_user.user.admin {
-        warn!("Non-admin user {} attempted to delete bot {}", auth_user.user.id, bot_id);
+        warn!("Non-admin user {} attempted to delete bot {}", auth_user.user.id, bot_user_id);
         return create_error_response(
             StatusCode::FORBIDDEN,
             "Admin privileges required",
@@ -257,25 +266,297 @@ pub async fn delete_bot(
         );
     }
     
-    let bot_user_id = UserId(bot_id);
-    
-    info!("Deleting bot {} for admin {}", bot_id, auth_user.user.id);
+    info!("Deleting bot {} for admin {}", bot_user_id, auth_user.user.id);
     
     match state.bot_service.delete_bot(bot_user_id).await {
         Ok(()) => {
-            info!("Deleted bot: {}", bot_id);
+            info!("Deleted bot: {}", bot_user_id);
             (StatusCode::OK, Json(json!({
                 "message": "Bot deleted successfully",
                 "success": true
             }))).into_response()
         }
         Err(bot_error) => {
-            error!("Failed to delete bot {}: {}", bot_id, bot_error);
+            error!("Failed to delete bot {}: {}", bot_user_id, bot_error);
+            bot_error_to_response(bot_error)
+        }
+    }
+}
+
+/// GET /api/rooms/:id/webhooks
+/// 
+/// List a room's outbound webhooks, without their secrets (admin only)
+/// 
+/// # Response
+/// - 200 OK: Returns the room's webhooks
+/// - 403 Forbidden: User is not an admin
+/// - 404 Not Found: Room not found
+pub async fn list_room_webhooks(
+    State(state): State<AppState>,
+    auth_user: AuthenticatedUser,
+    PathId(room_id): PathId<RoomId>,
+) -> Response {
+    if let Err(response) = require_admin_and_room(&state, &auth_user, room_id).await {
+        return response;
+    }
+    
+    match state.room_webhooks.list_webhooks(room_id).await {
+        Ok(webhooks) => (StatusCode::OK, Json(json!({
+            "webhooks": webhooks,
+            "success": true
+        }))).into_response(),
+        Err(bot_error) => {
+            error!("Failed to list webhooks for room {}: {}", room_id, bot_error);
+            bot_error_to_response(bot_error)
+        }
+    }
+}
+
+/// POST /api/rooms/:id/webhooks
+/// 
+/// Add an outbound webhook to a room (admin only)
+/// 
+/// # Request Body
+/// ```json
+/// {
+///   "url": "https://example.com/hooks/campfire"
+/// }
+/// ```
+/// 
+/// # Response
+/// - 201 Created: Returns the webhook and its signing secret, which is not
+///   shown again
+/// - 400 Bad Request: Invalid webhook URL
+/// - 403 Forbidden: User is not an admin
+/// - 404 Not Found: Room not found
+pub async fn create_room_webhook(
+    State(state): State<AppState>,
+    auth_user: AuthenticatedUser,
+    PathId(room_id): PathId<RoomId>,
+    Json(request): Json<CreateRoomWebhookRequest>,
+) -> Response {
+    if let Err(response) = require_admin_and_room(&state, &auth_user, room_id).await {
+        return response;
+    }
+    
+    let url = sanitization::sanitize_user_input(&request.url);
+    
+    match state.room_webhooks.create_webhook(room_id, url).await {
+        Ok(webhook) => {
+            info!("Admin {} added webhook {} to room {}", auth_user.user.id, webhook.id, room_id);
+            (StatusCode::CREATED, Json(json!({
+                "secret": webhook.secret,
+                "webhook": webhook,
+                "success": true
+            }))).into_response()
+        }
+        Err(bot_error) => {
+            error!("Failed to add webhook to room {}: {}", room_id, bot_error);
+            bot_error_to_response(bot_error)
+        }
+    }
+}
+
+/// PUT /api/rooms/:id/webhooks/:webhook_id
+/// 
+/// Enable or disable a room webhook (admin only)
+/// 
+/// # Request Body
+/// ```json
+/// {
+///   "enabled": false
+/// }
+/// ```
+/// 
+/// # Response
+/// - 204 No Content: Updated
+/// - 403 Forbidden: User is not an admin
+/// - 404 Not Found: Room or webhook not found
+pub async fn update_room_webhook(
+    State(state): State<AppState>,
+    auth_user: AuthenticatedUser,
+    Path((room_id, webhook_id)): Path<(String, String)>,
+    Json(request): Json<UpdateRoomWebhookRequest>,
+) -> Response {
+    let room_id: RoomId = match parse_path_id(&room_id) {
+        Ok(room_id) => room_id,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let webhook_id: RoomWebhookId = match parse_path_id(&webhook_id) {
+        Ok(webhook_id) => webhook_id,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if let Err(response) = require_admin_and_room(&state, &auth_user, room_id).await {
+        return response;
+    }
+    
+    match state.room_webhooks.set_enabled(room_id, webhook_id, request.enabled).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(bot_error) => bot_error_to_response(bot_error),
+    }
+}
+
+/// DELETE /api/rooms/:id/webhooks/:webhook_id
+/// 
+/// Remove a room webhook (admin only)
+/// 
+/// # Response
+/// - 204 No Content: Removed
+/// - 403 Forbidden: User is not an admin
+/// - 404 Not Found: Room or webhook not found
+pub async fn delete_room_webhook(
+    State(state): State<AppState>,
+    auth_user: AuthenticatedUser,
+    Path((room_id, webhook_id)): Path<(String, String)>,
+) -> Response {
+    let room_id: RoomId = match parse_path_id(&room_id) {
+        Ok(room_id) => room_id,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let webhook_id: RoomWebhookId = match parse_path_id(&webhook_id) {
+        Ok(webhook_id) => webhook_id,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if let Err(response) = require_admin_and_room(&state, &auth_user, room_id).await {
+        return response;
+    }
+    
+    match state.room_webhooks.delete_webhook(room_id, webhook_id).await {
+        Ok(()) => {
+            info!("Admin {} removed webhook {} from room {}", auth_user.user.id, webhook_id, room_id);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(bot_error) => bot_error_to_response(bot_error),
+    }
+}
+
+/// GET /api/rooms/:id/bridge
+/// 
+/// Show the room's bridge to an external chat (admin only)
+/// 
+/// # Response
+/// - 200 OK: Returns the bridge
+/// - 403 Forbidden: User is not an admin
+/// - 404 Not Found: Room not found, or it has no bridge
+pub async fn get_room_bridge(
+    State(state): State<AppState>,
+    auth_user: AuthenticatedUser,
+    PathId(room_id): PathId<RoomId>,
+) -> Response {
+    if let Err(response) = require_admin_and_room(&state, &auth_user, room_id).await {
+        return response;
+    }
+    
+    match state.room_bridges.get_bridge(room_id).await {
+        Ok(bridge) => (StatusCode::OK, Json(json!({
+            "bridge": bridge,
+            "success": true
+        }))).into_response(),
+        Err(bot_error) => bot_error_to_response(bot_error),
+    }
+}
+
+/// PUT /api/rooms/:id/bridge
+/// 
+/// Mirror the room's new messages to a Slack or Discord incoming webhook,
+/// replacing any bridge it already had (admin only). Messages from
+/// `inbound_bot_id`, the bot posting the other side's replies here, are
+/// not relayed back out.
+/// 
+/// # Request Body
+/// ```json
+/// {
+///   "url": "https://hooks.slack.com/services/T000/B000/XXXX",
+///   "format": "slack",
+///   "inbound_bot_id": "uuid-of-relay-bot",
+///   "enabled": true
+/// }
+/// ```
+/// 
+/// # Response
+/// - 200 OK: Returns the bridge
+/// - 400 Bad Request: Invalid or disallowed URL
+/// - 403 Forbidden: User is not an admin, or `inbound_bot_id` is not a bot
+/// - 404 Not Found: Room not found
+pub async fn set_room_bridge(
+    State(state): State<AppState>,
+    auth_user: AuthenticatedUser,
+    PathId(room_id): PathId<RoomId>,
+    Json(mut request): Json<SetRoomBridgeRequest>,
+) -> Response {
+    if let Err(response) = require_admin_and_room(&state, &auth_user, room_id).await {
+        return response;
+    }
+    
+    request.url = sanitization::sanitize_user_input(&request.url);
+    
+    match state.room_bridges.set_bridge(room_id, request).await {
+        Ok(bridge) => {
+            info!("Admin {} bridged room {} to {}", auth_user.user.id, room_id, bridge.format.as_str());
+            (StatusCode::OK, Json(json!({
+                "bridge": bridge,
+                "success": true
+            }))).into_response()
+        }
+        Err(bot_error) => {
+            error!("Failed to bridge room {}: {}", room_id, bot_error);
             bot_error_to_response(bot_error)
         }
     }
 }
 
+/// DELETE /api/rooms/:id/bridge
+/// 
+/// Stop mirroring the room (admin only)
+/// 
+/// # Response
+/// - 204 No Content: Removed
+/// - 403 Forbidden: User is not an admin
+/// - 404 Not Found: Room not found, or it has no bridge
+pub async fn delete_room_bridge(
+    State(state): State<AppState>,
+    auth_user: AuthenticatedUser,
+    PathId(room_id): PathId<RoomId>,
+) -> Response {
+    if let Err(response) = require_admin_and_room(&state, &auth_user, room_id).await {
+        return response;
+    }
+    
+    match state.room_bridges.delete_bridge(room_id).await {
+        Ok(()) => {
+            info!("Admin {} removed the bridge from room {}", auth_user.user.id, room_id);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(bot_error) => bot_error_to_response(bot_error),
+    }
+}
+
+/// Room webhooks are managed by site admins, like bots
+async fn require_admin_and_room(
+    state: &AppState,
+    auth_user: &AuthenticatedUser,
+    room_id: RoomId,
+) -> Result<(), Response> {
+    if !auth_user.user.admin {
+        warn!("Non-admin user {} attempted to manage webhooks for room {}", auth_user.user.id, room_id);
+        return Err(create_error_response(
+            StatusCode::FORBIDDEN,
+            "Admin privileges required",
+            "INSUFFICIENT_PRIVILEGES"
+        ));
+    }
+    
+    match state.db.get_room_by_id(room_id).await {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(create_error_response(
+            StatusCode::NOT_FOUND,
+            "Room not found",
+            "ROOM_NOT_FOUND"
+        )),
+        Err(e) => Err(bot_error_to_response(BotError::Database(e))),
+    }
+}
+
 /// POST /api/bots/:id/reset-token
 /// 
 /// Reset bot API token (admin only)
@@ -286,32 +567,34 @@ pub async fn delete_bot(
 /// # Response
 /// - 200 OK: Token reset successfully, returns new bot key
 /// - 401 Unauthorized: Invalid or missing session token
-/// - 403 Forbidden: User is not an admin
+/// - 403 Forbidden: User is not an admin, or the session was opened by
+///   impersonation
 /// - 404 Not Found: Bot not found
 /// - 500 Internal Server Error: Server error
 pub async fn reset_bot_token(
     State(state): State<AppState>,
     auth_user: AuthenticatedUser,
-    Path(bot_id): Path<Uuid>,
+    PathId(bot_user_id): PathId<UserId>,
 ) -> Response {
     // Check admin privileges
     if !auth_user.user.admin {
-        warn!("Non-admin user {} attempted to reset bot token {}", auth_user.user.id, bot_id);
+        warn!("Non-admin user {} attempted to reset bot token {}", auth_user.user.id, bot_user_id);
         return create_error_response(
             StatusCode::FORBIDDEN,
             "Admin privileges required",
             "INSUFFICIENT_PRIVILEGES"
         );
     }
+    if let Err(refused) = auth_user.forbid_impersonation("reset a bot token") {
+        return refused.into_response();
+    }
     
-    let bot_user_id = UserId(bot_id);
-    
-    info!("Resetting bot token {} for admin {}", bot_id, auth_user.user.id);
+    info!("Resetting bot token {} for admin {}", bot_user_id, auth_user.user.id);
     
     match state.bot_service.reset_bot_token(bot_user_id).await {
         Ok(new_token) => {
-            let new_bot_key = format!("{}-{}", bot_id, new_token);
-            info!("Reset bot token: {}", bot_id);
+            let new_bot_key = format!("{}-{}", bot_user_id, new_token);
+            info!("Reset bot token: {}", bot_user_id);
             (StatusCode::OK, Json(json!({
                 "bot_key": new_bot_key,
                 "message": "Bot token reset successfully",
@@ -319,7 +602,49 @@ pub async fn reset_bot_token(
             }))).into_response()
         }
         Err(bot_error) => {
-            error!("Failed to reset bot token {}: {}", bot_id, bot_error);
+            error!("Failed to reset bot token {}: {}", bot_user_id, bot_error);
+            bot_error_to_response(bot_error)
+        }
+    }
+}
+
+/// POST /api/bots/:id/simulate
+/// 
+/// Dry-run a bot's webhook (admin only): sends a sample payload, as if the
+/// caller had mentioned the bot, and reports the response. No message is
+/// created and the bot's reply is not posted.
+/// 
+/// # Response
+/// - 200 OK: `{ url, payload, status, body, latency_ms }` for any HTTP answer
+/// - 400 Bad Request: Bot has no webhook, or its URL is not allowed
+/// - 403 Forbidden: User is not an admin
+/// - 404 Not Found: Bot not found
+/// - 502 Bad Gateway: Webhook unreachable or timed out
+pub async fn simulate_bot_webhook(
+    State(state): State<AppState>,
+    auth_user: AuthenticatedUser,
+    PathId(bot_user_id): PathId<UserId>,
+) -> Response {
+    if !auth_user.user.admin {
+        warn!("Non-admin user {} attempted to simulate bot webhook {}", auth_user.user.id, bot_user_id);
+        return create_error_response(
+            StatusCode::FORBIDDEN,
+            "Admin privileges required",
+            "INSUFFICIENT_PRIVILEGES"
+        );
+    }
+    
+    match state.bot_service.simulate_webhook(bot_user_id, &auth_user.user).await {
+        Ok(simulation) => (StatusCode::OK, Json(json!({
+            "simulation": simulation,
+            "success": true
+        }))).into_response(),
+        Err(bot_error @ (BotError::WebhookDeliveryFailed { .. } | BotError::WebhookTimeout { .. })) => {
+            // The whole point is debugging, so say what went wrong
+            create_error_response(StatusCode::BAD_GATEWAY, &bot_error.to_string(), "WEBHOOK_UNREACHABLE")
+        }
+        Err(bot_error) => {
+            error!("Failed to simulate webhook for bot {}: {}", bot_user_id, bot_error);
             bot_error_to_response(bot_error)
         }
     }
@@ -333,10 +658,14 @@ pub async fn reset_bot_token(
 /// Plain text body or JSON:
 /// ```json
 /// {
-///   "body": "Hello from bot!"
+///   "content": "Hello from bot!",
+///   "on_behalf_of": "user-uuid" // optional, needs the post_on_behalf grant
 /// }
 /// ```
 /// 
+/// A message on a user's behalf is attributed to them, needs their access
+/// to the room, and records the bot as its real author.
+/// 
 /// # Authentication
 /// Uses bot_key in URL path for authentication
 /// 
@@ -349,10 +678,13 @@ pub async fn reset_bot_token(
 /// - 500 Internal Server Error: Server error
 pub async fn create_bot_message(
     State(state): State<AppState>,
-    Path((room_id, bot_key)): Path<(Uuid, String)>,
+    Path((room_id, bot_key)): Path<(String, String)>,
     Json(message_request): Json<CreateBotMessageRequest>,
 ) -> Response {
-    let room_id = RoomId(room_id);
+    let room_id: RoomId = match parse_path_id(&room_id) {
+        Ok(room_id) => room_id,
+        Err(rejection) => return rejection.into_response(),
+    };
     
     info!("Bot message creation attempt for room {} with key {}", room_id, bot_key);
     
@@ -408,7 +740,7 @@ pub async fn create_bot_message(
     };
     
     // Create message
-    match state.bot_service.create_bot_message(bot_user.id, room_id, content).await {
+    match state.bot_service.create_bot_message(bot_user.id, room_id, content, message_request.on_behalf_of).await {
         Ok(message) => {
             info!("Bot {} created message {} in room {}", bot_user.id, message.id, room_id);
             
@@ -444,26 +776,66 @@ fn bot_error_to_response(error: BotError) -> Response {
             "Bot not found",
             "BOT_NOT_FOUND"
         ),
+        BotError::WebhookNotFound { .. } => (
+            StatusCode::NOT_FOUND,
+            "Webhook not found",
+            "WEBHOOK_NOT_FOUND"
+        ),
+        BotError::BridgeNotFound { .. } => (
+            StatusCode::NOT_FOUND,
+            "Room has no bridge",
+            "BRIDGE_NOT_FOUND"
+        ),
         BotError::NotABot { .. } => (
             StatusCode::FORBIDDEN,
             "User is not a bot",
             "NOT_A_BOT"
         ),
+        BotError::PostingRestricted { .. } => (
+            StatusCode::FORBIDDEN,
+            "Bot is not allowed to post in this room",
+            "ROOM_POSTING_RESTRICTED"
+        ),
+        BotError::OnBehalfNotGranted { .. } => (
+            StatusCode::FORBIDDEN,
+            "Bot is not allowed to post on behalf of users",
+            "ON_BEHALF_NOT_GRANTED"
+        ),
+        BotError::InvalidOnBehalf { .. } => (
+            StatusCode::FORBIDDEN,
+            "Bot can't post on behalf of that user in this room",
+            "INVALID_ON_BEHALF"
+        ),
         BotError::TokenExists => (
             StatusCode::CONFLICT,
             "Bot token already exists",
             "TOKEN_EXISTS"
         ),
+        BotError::QuotaExceeded { .. } => (
+            StatusCode::CONFLICT,
+            "This instance already has as many bots as it allows",
+            "BOT_QUOTA_EXCEEDED"
+        ),
         BotError::InvalidWebhookUrl { .. } => (
             StatusCode::BAD_REQUEST,
             "Invalid webhook URL",
             "INVALID_WEBHOOK_URL"
         ),
+        BotError::WebhookUrlBlocked { .. } => (
+            StatusCode::BAD_REQUEST,
+            "Webhook URL points somewhere webhooks may not be sent",
+            "WEBHOOK_URL_BLOCKED"
+        ),
         BotError::InvalidName { .. } => (
             StatusCode::BAD_REQUEST,
             "Invalid bot name",
             "INVALID_BOT_NAME"
         ),
+        BotError::NoWebhook { .. } => (
+            StatusCode::BAD_REQUEST,
+            "Bot has no webhook configured",
+            "NO_WEBHOOK"
+        ),
         BotError::WebhookDeliveryFailed { .. } 
         | BotError::WebhookTimeout { .. }
         | BotError::Database(_)
@@ -0,0 +1,281 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use tracing::error;
+
+use crate::logging::audit::{AuditAction, AuditLogger};
+use crate::middleware::session::AuthenticatedUser;
+use crate::models::FeatureScope;
+use crate::AppState;
+
+/// Longest flag name an override may use
+const MAX_FLAG_NAME_LENGTH: usize = 64;
+
+/// GET /api/features
+/// 
+/// Returns the feature flags as resolved for the current user: global
+/// configuration with any per-user overrides applied
+/// 
+/// # Authentication
+/// Requires valid session token in Authorization header or cookie
+/// 
+/// # Response
+/// - 200 OK: JSON object mapping flag names to booleans
+/// - 401 Unauthorized: Invalid or missing session token
+/// - 500 Internal Server Error: Overrides could not be loaded
+/// 
+/// # Response Body
+/// ```json
+/// {
+///   "search": true,
+///   "sounds": false
+/// }
+/// ```
+pub async fn get_features(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+) -> Result<Json<BTreeMap<String, bool>>, StatusCode> {
+    let flags = state
+        .features
+        .resolve_for_user(auth_user.user.id)
+        .await
+        .map_err(|e| {
+            error!("Failed to resolve feature flags for {}: {}", auth_user.user.id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(flags))
+}
+
+/// Body of `PUT /api/admin/features/:flag`
+#[derive(Debug, Deserialize)]
+pub struct SetFeatureOverrideRequest {
+    #[serde(flatten)]
+    pub scope: FeatureScope,
+    pub enabled: bool,
+}
+
+/// PUT /api/admin/features/:flag
+/// 
+/// Turns a flag on or off for one user or room, on top of the global
+/// configuration (site admins only). Takes effect on the next request; no
+/// redeploy needed.
+/// 
+/// # Request Body
+/// ```json
+/// {
+///   "scope": "user",
+///   "id": "uuid-of-user",
+///   "enabled": true
+/// }
+/// ```
+/// 
+/// # Response
+/// - 204 No Content: Override stored
+/// - 400 Bad Request: Invalid flag name or scope
+/// - 401 Unauthorized: Invalid or missing session token
+/// - 403 Forbidden: User is not an admin
+/// - 404 Not Found: The user or room doesn't exist
+pub async fn set_feature_override(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    Path(flag): Path<String>,
+    Json(request): Json<SetFeatureOverrideRequest>,
+) -> Result<StatusCode, StatusCode> {
+    check_override_target(&auth_user, &state, &flag, request.scope).await?;
+
+    state
+        .features
+        .set_override(request.scope, &flag, request.enabled)
+        .await
+        .map_err(|e| {
+            error!("Failed to set feature override {} for {:?}: {}", flag, request.scope, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    audit_override(&auth_user, &flag, request.scope, Some(request.enabled));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// DELETE /api/admin/features/:flag?scope=user&id=uuid
+/// 
+/// Removes a user or room override, so the flag falls back to the next
+/// layer (site admins only)
+/// 
+/// # Response
+/// - 204 No Content: Override removed (or there was none)
+/// - 400 Bad Request: Invalid flag name or scope
+/// - 401 Unauthorized: Invalid or missing session token
+/// - 403 Forbidden: User is not an admin
+/// - 404 Not Found: The user or room doesn't exist
+pub async fn clear_feature_override(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    Path(flag): Path<String>,
+    Query(scope): Query<FeatureScope>,
+) -> Result<StatusCode, StatusCode> {
+    check_override_target(&auth_user, &state, &flag, scope).await?;
+
+    state
+        .features
+        .clear_override(scope, &flag)
+        .await
+        .map_err(|e| {
+            error!("Failed to clear feature override {} for {:?}: {}", flag, scope, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    audit_override(&auth_user, &flag, scope, None);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Admins only, a sane flag name, and a user or room that exists
+async fn check_override_target(
+    auth_user: &AuthenticatedUser,
+    state: &AppState,
+    flag: &str,
+    scope: FeatureScope,
+) -> Result<(), StatusCode> {
+    if !auth_user.user.admin {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let valid_name = !flag.is_empty()
+        && flag.len() <= MAX_FLAG_NAME_LENGTH
+        && flag.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_');
+    if !valid_name {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let exists = match scope {
+        FeatureScope::User(user_id) => state.db.get_user_by_id(user_id).await.map(|user| user.is_some()),
+        FeatureScope::Room(room_id) => state.db.get_room_by_id(room_id).await.map(|room| room.is_some()),
+    };
+    match exists {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to look up feature override target {:?}: {}", scope, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+fn audit_override(auth_user: &AuthenticatedUser, flag: &str, scope: FeatureScope, enabled: Option<bool>) {
+    let (scope_name, target) = match scope {
+        FeatureScope::User(user_id) => ("user", user_id.to_string()),
+        FeatureScope::Room(room_id) => ("room", room_id.to_string()),
+    };
+    let change = enabled.map_or("cleared".to_string(), |enabled| enabled.to_string());
+
+    AuditLogger::new(true).log_user_action(
+        AuditAction::SystemConfigChanged,
+        auth_user.user.id,
+        "feature_flag",
+        Some(flag.to_string()),
+        HashMap::from([
+            ("scope".to_string(), scope_name.to_string()),
+            ("target_id".to_string(), target),
+            ("enabled".to_string(), change),
+        ]),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::features::FeatureFlags;
+    use axum::{body::Body, http::Request, routing::{get, put}, Router};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn app(state: AppState) -> Router {
+        Router::new()
+            .route("/api/search", get(crate::handlers::search::search_messages))
+            .route("/api/admin/features/:flag", put(set_feature_override).delete(clear_feature_override))
+            .with_state(state)
+    }
+
+    async fn create_admin(state: &AppState) -> crate::models::User {
+        let admin = crate::models::User {
+            admin: true,
+            ..crate::models::User::for_tests("Admin")
+        };
+        state.db.create_user(admin.clone()).await.unwrap();
+        admin
+    }
+
+    fn request(method: &str, uri: &str, token: &str, body: Option<serde_json::Value>) -> Request<Body> {
+        let builder = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Authorization", format!("Bearer {}", token));
+        match body {
+            Some(body) => builder
+                .header("Content-Type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+            None => builder.body(Body::empty()).unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_user_override_turns_search_on_for_that_user_only() {
+        let mut state = crate::handlers::websocket::tests::create_test_state().await;
+        let global = [("search".to_string(), false)].into_iter().collect();
+        state.features = Arc::new(FeatureFlags::new(Arc::new(state.db.clone()), global));
+
+        let admin = create_admin(&state).await;
+        let tester = state
+            .auth_service
+            .create_user("Tester".to_string(), "tester@example.com".to_string(), "password123".to_string())
+            .await
+            .unwrap();
+        let admin_token = state.auth_service.create_session(admin.id).await.unwrap().token;
+        let tester_token = state.auth_service.create_session(tester.id).await.unwrap().token;
+        let app = app(state);
+
+        let search = |token: &str| request("GET", "/api/search?q=deploy", token, None);
+        let response = app.clone().oneshot(search(&tester_token)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Only admins may roll a flag out
+        let enable = serde_json::json!({ "scope": "user", "id": tester.id, "enabled": true });
+        let uri = "/api/admin/features/search";
+        let response = app.clone().oneshot(request("PUT", uri, &tester_token, Some(enable.clone()))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app.clone().oneshot(request("PUT", uri, &admin_token, Some(enable))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = app.clone().oneshot(search(&tester_token)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.clone().oneshot(search(&admin_token)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Clearing the override falls back to the global flag
+        let clear = format!("{}?scope=user&id={}", uri, tester.id);
+        let response = app.clone().oneshot(request("DELETE", &clear, &admin_token, None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = app.oneshot(search(&tester_token)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_override_rejects_bad_flags_and_unknown_targets() {
+        let state = crate::handlers::websocket::tests::create_test_state().await;
+        let admin = create_admin(&state).await;
+        let token = state.auth_service.create_session(admin.id).await.unwrap().token;
+        let app = app(state);
+
+        let body = serde_json::json!({ "scope": "user", "id": admin.id, "enabled": true });
+        let response = app.clone().oneshot(request("PUT", "/api/admin/features/New-Composer", &token, Some(body))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = serde_json::json!({ "scope": "room", "id": crate::models::RoomId::new(), "enabled": true });
+        let response = app.oneshot(request("PUT", "/api/admin/features/new_composer", &token, Some(body))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,141 @@
+use axum::{extract::{Query, State}, http::StatusCode, response::Json};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{error, info, warn};
+
+use crate::logging::audit::{AuditAction, AuditLogger};
+use crate::middleware::session::AuthenticatedUser;
+use crate::validation::{resolve_limit, LimitQuery};
+use crate::AppState;
+
+/// Set while a vacuum started from the API is running
+static VACUUM_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Set while a search index check started from the API is running
+static SEARCH_INDEX_CHECK_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// POST /api/admin/maintenance/vacuum
+///
+/// Compacts the database file in the background (site admins only), the
+/// same as `campfire-on-rust vacuum`. Writes wait until it finishes, which
+/// can take minutes on a large database; the space reclaimed is logged.
+///
+/// # Response
+/// - 202 Accepted: Vacuum started
+/// - 401 Unauthorized: Invalid or missing session token
+/// - 403 Forbidden: User is not an admin
+/// - 409 Conflict: A vacuum is already running
+pub async fn start_vacuum(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+) -> Result<(StatusCode, Json<serde_json::Value>), StatusCode> {
+    if !auth_user.user.admin {
+        warn!("Non-admin user {} attempted to vacuum the database", auth_user.user.id);
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    if VACUUM_RUNNING.swap(true, Ordering::SeqCst) {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    AuditLogger::new(true).log_user_action(
+        AuditAction::DatabaseMaintenance,
+        auth_user.user.id,
+        "database",
+        None::<String>,
+        HashMap::from([("operation".to_string(), "vacuum".to_string())]),
+    );
+
+    let db = state.db.clone();
+    tokio::spawn(async move {
+        match db.vacuum().await {
+            Ok(report) => info!(
+                "Database vacuumed: {} -> {} bytes ({} reclaimed)",
+                report.bytes_before, report.bytes_after, report.reclaimed_bytes()
+            ),
+            Err(e) => error!("Database vacuum failed: {}", e),
+        }
+        VACUUM_RUNNING.store(false, Ordering::SeqCst);
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(json!({ "status": "started" }))))
+}
+
+/// POST /api/admin/maintenance/search-index/check
+///
+/// Compares the search index with the messages row by row in the background
+/// (site admins only). Health checks only compare counts, which can't see a
+/// missing row offset by an orphaned one. The messages missing from the
+/// index and the index rows without a message are logged; either means
+/// `campfire-on-rust reindex` should be run.
+///
+/// # Response
+/// - 202 Accepted: Check started
+/// - 401 Unauthorized: Invalid or missing session token
+/// - 403 Forbidden: User is not an admin
+/// - 409 Conflict: A check is already running
+pub async fn start_search_index_check(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+) -> Result<(StatusCode, Json<serde_json::Value>), StatusCode> {
+    if !auth_user.user.admin {
+        warn!("Non-admin user {} attempted to check the search index", auth_user.user.id);
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    if SEARCH_INDEX_CHECK_RUNNING.swap(true, Ordering::SeqCst) {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    AuditLogger::new(true).log_user_action(
+        AuditAction::DatabaseMaintenance,
+        auth_user.user.id,
+        "database",
+        None::<String>,
+        HashMap::from([("operation".to_string(), "search_index_check".to_string())]),
+    );
+
+    let db = state.db.clone();
+    tokio::spawn(async move {
+        match db.find_search_index_drift().await {
+            Ok(drift) if drift.is_consistent() => info!("Search index check: in sync"),
+            Ok(drift) => warn!(
+                "Search index check: {} messages missing, {} orphaned rows; run `campfire-on-rust reindex`",
+                drift.missing, drift.orphaned
+            ),
+            Err(e) => error!("Search index check failed: {}", e),
+        }
+        SEARCH_INDEX_CHECK_RUNNING.store(false, Ordering::SeqCst);
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(json!({ "status": "started" }))))
+}
+
+/// GET /api/admin/jobs
+///
+/// Background jobs with their interval, run and failure counts, last and
+/// next run, and the last run's error (site admins only)
+///
+/// # Query Parameters
+/// - limit: Most jobs returned (default and cap from `pagination`)
+///
+/// # Response
+/// - 200 OK: Jobs, by name
+/// - 400 Bad Request: Negative limit
+/// - 401 Unauthorized: Invalid or missing session token
+/// - 403 Forbidden: User is not an admin
+pub async fn list_jobs(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    Query(query): Query<LimitQuery>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    if !auth_user.user.admin {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let limit = resolve_limit(query.limit, &state.pagination).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let mut jobs = state.scheduler.statuses();
+    jobs.truncate(limit as usize);
+    Ok(Json(json!({ "jobs": jobs })))
+}
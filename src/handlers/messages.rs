@@ -1,26 +1,100 @@
 use axum::{
-    extract::{Path, Query, State, ConnectInfo},
-    http::{StatusCode, HeaderMap},
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderName, StatusCode},
     response::{IntoResponse, Json, Response},
 };
 use serde::{Deserialize, Serialize};
-use std::net::SocketAddr;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::time::Instant;
 use tracing::{info, warn};
 use uuid::Uuid;
 
 use crate::errors::MessageError;
-use crate::middleware::AuthenticatedUser;
-use crate::models::{Message, MessageId, RoomId};
-use crate::validation::{CreateMessageRequest, sanitization, validate_request};
+use crate::handlers::users::local_time;
+use crate::middleware::{parse_path_id, AuthenticatedUser, ClientIp, PathId};
+use crate::models::{Mention, Message, MessageId, RoomId, SavedMessage, SeenReceipt, UserId};
+use crate::storage::QuotaBlobStore;
+use crate::timezone::LocalTime;
+use crate::validation::{
+    attachment_from_other_room, CreateMessageRequest, resolve_limit, sanitization, unknown_attachment,
+    validate_attachment_bytes, validate_attachment_count, validate_request, ValidationErrorResponse,
+};
 use crate::logging::{audit::{AuditAction, AuditLogger}, error_handling::handle_message_error};
 use crate::{AppState, log_performance_warning, log_business_event};
 
 #[derive(Deserialize)]
 pub struct GetMessagesQuery {
-    limit: Option<u32>,
+    limit: Option<i64>,
     before: Option<String>, // MessageId as string
+    /// `envelope` or `array`; only room message history honours it
+    format: Option<String>,
+}
+
+/// Media type that asks for the bare message array
+pub const LEGACY_MESSAGES_MEDIA_TYPE: &str = "application/vnd.campfire.v1+json";
+/// Media type that asks for the paginated envelope
+pub const MESSAGES_MEDIA_TYPE: &str = "application/vnd.campfire.v2+json";
+
+/// Shape of a room message history response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessagesFormat {
+    /// `MessagesResponse`, with paging and read marker details
+    Envelope,
+    /// The messages alone, as before pagination; deprecated
+    LegacyArray,
+}
+
+impl MessagesFormat {
+    /// Picks the format from `?format=`, then the `Accept` header, then the
+    /// configured default
+    pub fn negotiate(
+        query_format: Option<&str>,
+        headers: &HeaderMap,
+        legacy_default: bool,
+    ) -> Result<Self, MessageError> {
+        match query_format {
+            Some("envelope") => return Ok(Self::Envelope),
+            Some("array") => return Ok(Self::LegacyArray),
+            Some(_) => {
+                return Err(MessageError::InvalidContent {
+                    reason: "format must be 'envelope' or 'array'".to_string(),
+                })
+            }
+            None => {}
+        }
+        
+        let accept = headers
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        if accept.contains(MESSAGES_MEDIA_TYPE) {
+            Ok(Self::Envelope)
+        } else if accept.contains(LEGACY_MESSAGES_MEDIA_TYPE) || legacy_default {
+            Ok(Self::LegacyArray)
+        } else {
+            Ok(Self::Envelope)
+        }
+    }
+    
+    /// Renders the page; the legacy array is marked deprecated and points
+    /// at the envelope
+    pub fn respond(self, room_id: RoomId, page: MessagesResponse) -> Response {
+        match self {
+            Self::Envelope => (StatusCode::OK, Json(page)).into_response(),
+            Self::LegacyArray => (
+                StatusCode::OK,
+                [
+                    (HeaderName::from_static("deprecation"), "true".to_string()),
+                    (
+                        header::LINK,
+                        format!("</api/rooms/{}/messages?format=envelope>; rel=\"successor-version\"", room_id),
+                    ),
+                ],
+                Json(page.messages),
+            )
+                .into_response(),
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -32,6 +106,31 @@ pub struct MessageResponse {
 pub struct MessagesResponse {
     pub messages: Vec<Message>,
     pub has_more: bool,
+    /// First message after the user's read marker; null when caught up
+    pub first_unread_message_id: Option<MessageId>,
+    /// The reader's timezone, for localizing the UTC timestamps
+    #[serde(flatten)]
+    pub local_time: LocalTime,
+}
+
+#[derive(Serialize)]
+pub struct SeenByResponse {
+    pub message_id: MessageId,
+    pub seen_by: Vec<SeenReceipt>,
+}
+
+#[derive(Serialize)]
+pub struct MentionsResponse {
+    pub mentions: Vec<Mention>,
+    pub has_more: bool,
+    #[serde(flatten)]
+    pub local_time: LocalTime,
+}
+
+#[derive(Serialize)]
+pub struct SavedMessagesResponse {
+    pub saved: Vec<SavedMessage>,
+    pub has_more: bool,
 }
 
 #[derive(Serialize)]
@@ -51,26 +150,32 @@ pub struct ErrorResponse {
 /// ```json
 /// {
 ///   "content": "Message content (1-10000 chars)",
-///   "client_message_id": "uuid-v4-string"
+///   "client_message_id": "uuid-v4-string",
+///   "attachments": ["attachments/..."]
 /// }
 /// ```
 /// 
+/// `attachments` is optional: keys of the sender's own uploads to this room
+/// (from `POST /api/rooms/:id/attachments`), capped in number and combined
+/// size per message. Repeated keys count once.
+/// 
 /// # Response
 /// - 201: Message created successfully
-/// - 400: Invalid request (bad content, invalid UUID)
+/// - 400: Invalid request (bad content, invalid UUID, unknown attachment or
+///   one uploaded to another room, too many or too large attachments)
 /// - 401: Authentication required
 /// - 403: User not authorized for room
 /// - 500: Internal server error
 pub async fn create_message(
     State(state): State<AppState>,
-    Path(room_id_str): Path<String>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    PathId(room_id): PathId<RoomId>,
+    ClientIp(client_ip): ClientIp,
     headers: HeaderMap,
     auth_user: AuthenticatedUser,
     Json(request): Json<CreateMessageRequest>,
 ) -> Result<Response, Response> {
     let start_time = Instant::now();
-    let ip_address = addr.ip().to_string();
+    let ip_address = client_ip.to_string();
     let user_agent = headers
         .get("user-agent")
         .and_then(|h| h.to_str().ok())
@@ -79,14 +184,14 @@ pub async fn create_message(
 
     info!(
         "Creating message in room {} for user {} from IP: {}",
-        room_id_str, auth_user.user.id, ip_address
+        room_id, auth_user.user.id, ip_address
     );
 
     // Validate request
     if let Err(validation_error) = validate_request(&request) {
         // Log validation failure
         let mut details = HashMap::new();
-        details.insert("room_id".to_string(), room_id_str.clone());
+        details.insert("room_id".to_string(), room_id.to_string());
         details.insert("user_id".to_string(), auth_user.user.id.to_string());
         details.insert("error".to_string(), "validation_failed".to_string());
         
@@ -99,9 +204,10 @@ pub async fn create_message(
         
         return Err(validation_error.into_response());
     }
-    
-    // Parse room_id from path parameter
-    let room_id = parse_room_id(&room_id_str)?;
+
+    let attachments = resolve_attachments(&state.blob_store, auth_user.user.id, room_id, &request.attachments)
+        .await
+        .map_err(IntoResponse::into_response)?;
 
     // Sanitize message content
     let content = sanitization::sanitize_message_content(&request.content);
@@ -118,6 +224,14 @@ pub async fn create_message(
         .await
     {
         Ok(message) => {
+            // A retry with the same client_message_id links them again, harmlessly
+            if !attachments.is_empty() {
+                if let Err(e) = state.db.link_message_attachments(message.id, attachments).await {
+                    warn!("Failed to link attachments to message {}: {}", message.id, e);
+                    return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+                }
+            }
+
             let duration = start_time.elapsed();
             
             // Check for performance issues
@@ -192,6 +306,70 @@ pub async fn create_message(
     }
 }
 
+/// Why a message's attachments were refused
+#[derive(Debug)]
+pub(crate) enum AttachmentRejection {
+    Invalid(ValidationErrorResponse),
+    Lookup(StatusCode),
+}
+
+impl AttachmentRejection {
+    /// What to tell a WebSocket client
+    pub(crate) fn reason(&self) -> String {
+        match self {
+            Self::Invalid(error) => error.details.values().flatten().cloned().collect::<Vec<_>>().join("; "),
+            Self::Lookup(_) => "Attachments could not be checked".to_string(),
+        }
+    }
+}
+
+impl IntoResponse for AttachmentRejection {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Invalid(error) => error.into_response(),
+            Self::Lookup(status) => status.into_response(),
+        }
+    }
+}
+
+/// Checks the attachments of a message to `room_id`, returning the keys to
+/// link to it with repeats dropped. Every key must be one of the sender's
+/// uploads, made to this room or to no room, and the set must be within the
+/// per-message caps.
+pub(crate) async fn resolve_attachments(
+    blobs: &QuotaBlobStore,
+    user_id: UserId,
+    room_id: RoomId,
+    keys: &[String],
+) -> Result<Vec<String>, AttachmentRejection> {
+    let mut seen = HashSet::new();
+    let keys: Vec<String> = keys.iter().filter(|key| seen.insert(key.as_str())).cloned().collect();
+
+    let limits = blobs.attachment_limits();
+    validate_attachment_count(keys.len(), &limits).map_err(AttachmentRejection::Invalid)?;
+
+    let lookup_failed = |key: &str, e| {
+        warn!("Failed to look up attachment {}: {}", key, e);
+        AttachmentRejection::Lookup(StatusCode::from(e))
+    };
+    let mut total_bytes: u64 = 0;
+    for key in &keys {
+        let size = blobs
+            .owned_blob_size(user_id, key)
+            .await
+            .map_err(|e| lookup_failed(key, e))?
+            .ok_or_else(|| AttachmentRejection::Invalid(unknown_attachment(key)))?;
+        let owner = blobs.blob_owner(key).await.map_err(|e| lookup_failed(key, e))?;
+        if owner.and_then(|owner| owner.room_id).is_some_and(|uploaded_to| uploaded_to != room_id) {
+            return Err(AttachmentRejection::Invalid(attachment_from_other_room(key)));
+        }
+        total_bytes = total_bytes.saturating_add(size);
+    }
+    validate_attachment_bytes(total_bytes, &limits).map_err(AttachmentRejection::Invalid)?;
+
+    Ok(keys)
+}
+
 /// GET /api/rooms/:room_id/messages
 /// 
 /// Retrieves message history for the specified room with pagination
@@ -200,43 +378,46 @@ pub async fn create_message(
 /// Requires valid session token via Authorization header or cookie
 /// 
 /// # Query Parameters
-/// - `limit`: Number of messages to retrieve (default: 50, max: 100)
+/// - `limit`: Number of messages to retrieve (configured default and cap; larger values are clamped)
 /// - `before`: MessageId to paginate before (optional)
+/// - `format`: `envelope` or `array` (optional, see below)
+/// 
+/// # Format
+/// The paginated envelope is asked for with `?format=envelope` or
+/// `Accept: application/vnd.campfire.v2+json`; the bare array that predates
+/// it with `?format=array` or `Accept: application/vnd.campfire.v1+json`.
+/// Without either, `CAMPFIRE_LEGACY_MESSAGE_ARRAY` decides. Array responses
+/// carry `Deprecation: true` and a `Link` to the envelope.
 /// 
 /// # Response
 /// - 200: Messages retrieved successfully
-/// - 400: Invalid request (bad UUID, invalid limit)
+/// - 400: Invalid request (bad UUID, negative limit, unknown format)
 /// - 401: Authentication required
 /// - 403: User not authorized for room
 /// - 500: Internal server error
 pub async fn get_messages(
     State(state): State<AppState>,
-    Path(room_id_str): Path<String>,
+    PathId(room_id): PathId<RoomId>,
     Query(query): Query<GetMessagesQuery>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    ClientIp(client_ip): ClientIp,
     auth_user: AuthenticatedUser,
 ) -> Result<Response, Response> {
     let start_time = Instant::now();
-    let ip_address = addr.ip().to_string();
+    let ip_address = client_ip.to_string();
     
     info!(
         "Getting messages for room {} for user {} from IP: {}",
-        room_id_str, auth_user.user.id, ip_address
+        room_id, auth_user.user.id, ip_address
     );
 
-    // Parse room_id from path parameter
-    let room_id = parse_room_id(&room_id_str)?;
-
-    // Parse and validate limit
-    let limit = query.limit.unwrap_or(50);
-    if limit > 100 {
-        return Err(handle_message_error(
-            MessageError::InvalidContent { 
-                reason: "Limit cannot exceed 100 messages".to_string() 
-            },
-            Some("get_messages")
-        ).into_response());
-    }
+    let limit = resolve_limit(query.limit, &state.pagination).map_err(IntoResponse::into_response)?;
+    let format = MessagesFormat::negotiate(
+        query.format.as_deref(),
+        &headers,
+        state.pagination.legacy_message_array,
+    )
+    .map_err(|e| handle_message_error(e, Some("get_messages")).into_response())?;
 
     // Parse before parameter if provided
     let before = if let Some(before_str) = query.before {
@@ -283,10 +464,22 @@ pub async fn get_messages(
             // This is a simple heuristic - if we got the full limit, there might be more
             let has_more = messages.len() as u32 == limit;
             
-            Ok((
-                StatusCode::OK,
-                Json(MessagesResponse { messages, has_more }),
-            ).into_response())
+            // A missing divider is better than a failed history load
+            let first_unread_message_id = state
+                .message_service
+                .get_first_unread_message_id(room_id, auth_user.user.id)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Failed to compute first unread message for room {}: {}", room_id, e);
+                    None
+                });
+            
+            Ok(format.respond(room_id, MessagesResponse {
+                messages,
+                has_more,
+                first_unread_message_id,
+                local_time: local_time(&state, auth_user.user.id).await,
+            }))
         }
         Err(message_error) => {
             let duration = start_time.elapsed();
@@ -314,21 +507,169 @@ pub async fn get_messages(
     }
 }
 
-/// Parse room ID from string parameter
-fn parse_room_id(room_id_str: &str) -> Result<RoomId, Response> {
-    match Uuid::parse_str(room_id_str) {
-        Ok(uuid) => Ok(RoomId(uuid)),
-        Err(_) => Err(handle_message_error(
-            MessageError::InvalidContent { 
-                reason: "Invalid room ID format".to_string() 
-            },
-            Some("parse_room_id")
-        ).into_response()),
+/// GET /api/rooms/:room_id/messages/:message_id/seen
+/// 
+/// Lists the members whose read marker has reached the message. Only
+/// available in rooms up to the configured member count.
+/// 
+/// # Response
+/// - 200: Seen-by list, earliest reader first
+/// - 400: Invalid ID, or room too large for seen-by
+/// - 401: Authentication required
+/// - 403: User not authorized for room
+/// - 404: Message not found in this room
+pub async fn get_seen_by(
+    State(state): State<AppState>,
+    Path((room_id, message_id)): Path<(String, String)>,
+    auth_user: AuthenticatedUser,
+) -> Result<Response, Response> {
+    let room_id: RoomId = parse_path_id(&room_id).map_err(IntoResponse::into_response)?;
+    let message_id: MessageId = parse_path_id(&message_id).map_err(IntoResponse::into_response)?;
+    
+    match state
+        .message_service
+        .get_seen_by(room_id, message_id, auth_user.user.id)
+        .await
+    {
+        Ok(seen_by) => Ok(Json(SeenByResponse { message_id, seen_by }).into_response()),
+        Err(message_error) => {
+            Err(handle_message_error(message_error, Some("get_seen_by")).into_response())
+        }
+    }
+}
+
+/// GET /api/users/me/mentions
+/// 
+/// Recent messages in the user's rooms that @mention them, newest first,
+/// each with the name of the room it was posted in
+/// 
+/// # Query Parameters
+/// - `limit`: Number of mentions to retrieve (configured default and cap; larger values are clamped)
+/// - `before`: MessageId to paginate before (optional)
+/// 
+/// # Response
+/// - 200: Mentions retrieved successfully
+/// - 400: Invalid request (bad UUID, negative limit)
+/// - 401: Authentication required
+pub async fn get_my_mentions(
+    State(state): State<AppState>,
+    Query(query): Query<GetMessagesQuery>,
+    auth_user: AuthenticatedUser,
+) -> Result<Response, Response> {
+    let limit = resolve_limit(query.limit, &state.pagination).map_err(IntoResponse::into_response)?;
+    
+    let before = if let Some(before_str) = query.before {
+        Some(parse_message_id(&before_str)?)
+    } else {
+        None
+    };
+    
+    match state
+        .message_service
+        .get_mentions(auth_user.user.id, limit, before)
+        .await
+    {
+        Ok(mentions) => {
+            let has_more = mentions.len() as u32 == limit;
+            let local_time = local_time(&state, auth_user.user.id).await;
+            Ok(Json(MentionsResponse { mentions, has_more, local_time }).into_response())
+        }
+        Err(message_error) => {
+            Err(handle_message_error(message_error, Some("get_my_mentions")).into_response())
+        }
+    }
+}
+
+/// POST /api/messages/:id/save
+/// 
+/// Saves a message to the user's private list; saving twice is harmless
+/// 
+/// # Response
+/// - 204: Saved
+/// - 400: Invalid message ID
+/// - 401: Authentication required
+/// - 403: User not authorized for the message's room
+/// - 404: Message not found
+/// - 409: The user's saved message limit is reached
+pub async fn save_message(
+    State(state): State<AppState>,
+    PathId(message_id): PathId<MessageId>,
+    auth_user: AuthenticatedUser,
+) -> Result<StatusCode, Response> {
+    state
+        .message_service
+        .save_message(auth_user.user.id, message_id)
+        .await
+        .map(|()| StatusCode::NO_CONTENT)
+        .map_err(|e| handle_message_error(e, Some("save_message")).into_response())
+}
+
+/// DELETE /api/messages/:id/save
+/// 
+/// Removes a message from the user's saved list, including tombstones of
+/// deleted messages
+/// 
+/// # Response
+/// - 204: No longer saved
+/// - 400: Invalid message ID
+/// - 401: Authentication required
+pub async fn unsave_message(
+    State(state): State<AppState>,
+    PathId(message_id): PathId<MessageId>,
+    auth_user: AuthenticatedUser,
+) -> Result<StatusCode, Response> {
+    state
+        .message_service
+        .unsave_message(auth_user.user.id, message_id)
+        .await
+        .map(|()| StatusCode::NO_CONTENT)
+        .map_err(|e| handle_message_error(e, Some("unsave_message")).into_response())
+}
+
+/// GET /api/users/me/saved
+/// 
+/// The user's saved messages, most recently saved first. Messages in rooms
+/// the user has since left are left out; deleted messages are listed with
+/// `deleted: true` and no `message`.
+/// 
+/// # Query Parameters
+/// - `limit`: Number of saved messages to retrieve (configured default and cap)
+/// - `before`: Message ID of a saved message to paginate before (optional)
+/// 
+/// # Response
+/// - 200: Saved messages retrieved successfully
+/// - 400: Invalid request (bad UUID, negative limit)
+/// - 401: Authentication required
+pub async fn get_saved_messages(
+    State(state): State<AppState>,
+    Query(query): Query<GetMessagesQuery>,
+    auth_user: AuthenticatedUser,
+) -> Result<Response, Response> {
+    let limit = resolve_limit(query.limit, &state.pagination).map_err(IntoResponse::into_response)?;
+    
+    let before = if let Some(before_str) = query.before {
+        Some(parse_message_id(&before_str)?)
+    } else {
+        None
+    };
+    
+    match state
+        .message_service
+        .get_saved_messages(auth_user.user.id, limit, before)
+        .await
+    {
+        Ok(saved) => {
+            let has_more = saved.len() as u32 == limit;
+            Ok(Json(SavedMessagesResponse { saved, has_more }).into_response())
+        }
+        Err(message_error) => {
+            Err(handle_message_error(message_error, Some("get_saved_messages")).into_response())
+        }
     }
 }
 
 /// Parse message ID from string parameter
-fn parse_message_id(message_id_str: &str) -> Result<MessageId, Response> {
+pub(crate) fn parse_message_id(message_id_str: &str) -> Result<MessageId, Response> {
     match Uuid::parse_str(message_id_str) {
         Ok(uuid) => Ok(MessageId(uuid)),
         Err(_) => Err(handle_message_error(
@@ -343,18 +684,19 @@ fn parse_message_id(message_id_str: &str) -> Result<MessageId, Response> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::middleware::parse_path_id;
     use uuid::Uuid;
 
     #[test]
     fn test_parse_room_id_valid() {
         let uuid = Uuid::new_v4();
-        let room_id = parse_room_id(&uuid.to_string()).unwrap();
+        let room_id: RoomId = parse_path_id(&uuid.to_string()).unwrap();
         assert_eq!(room_id.0, uuid);
     }
 
     #[test]
     fn test_parse_room_id_invalid() {
-        let result = parse_room_id("invalid-uuid");
+        let result = parse_path_id::<RoomId>("invalid-uuid");
         assert!(result.is_err());
     }
 
@@ -371,6 +713,70 @@ mod tests {
         assert!(result.is_err());
     }
 
+    fn accept(media_type: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, media_type.parse().unwrap());
+        headers
+    }
+
+    fn page() -> MessagesResponse {
+        MessagesResponse {
+            messages: vec![Message::new(RoomId::new(), crate::models::UserId::new(), "Hi".to_string(), Uuid::new_v4())],
+            has_more: false,
+            first_unread_message_id: None,
+            local_time: LocalTime::now(crate::timezone::DEFAULT_TIMEZONE),
+        }
+    }
+
+    #[test]
+    fn test_messages_format_negotiation() {
+        let none = HeaderMap::new();
+        assert_eq!(MessagesFormat::negotiate(None, &none, false).unwrap(), MessagesFormat::Envelope);
+        assert_eq!(MessagesFormat::negotiate(None, &none, true).unwrap(), MessagesFormat::LegacyArray);
+
+        // Clients that ask get what they asked for, whatever the default
+        assert_eq!(
+            MessagesFormat::negotiate(None, &accept(MESSAGES_MEDIA_TYPE), true).unwrap(),
+            MessagesFormat::Envelope
+        );
+        assert_eq!(
+            MessagesFormat::negotiate(None, &accept(LEGACY_MESSAGES_MEDIA_TYPE), false).unwrap(),
+            MessagesFormat::LegacyArray
+        );
+        assert_eq!(
+            MessagesFormat::negotiate(Some("envelope"), &accept(LEGACY_MESSAGES_MEDIA_TYPE), true).unwrap(),
+            MessagesFormat::Envelope
+        );
+        assert_eq!(MessagesFormat::negotiate(Some("array"), &none, false).unwrap(), MessagesFormat::LegacyArray);
+
+        assert!(matches!(
+            MessagesFormat::negotiate(Some("xml"), &none, false),
+            Err(MessageError::InvalidContent { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_legacy_array_is_marked_deprecated() {
+        let room_id = RoomId::new();
+
+        let response = MessagesFormat::Envelope.respond(room_id, page());
+        assert!(response.headers().get("deprecation").is_none());
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+        assert_eq!(body["has_more"], false);
+
+        let response = MessagesFormat::LegacyArray.respond(room_id, page());
+        assert_eq!(response.headers()["deprecation"], "true");
+        assert!(response.headers()[header::LINK]
+            .to_str()
+            .unwrap()
+            .contains(&format!("/api/rooms/{}/messages?format=envelope", room_id)));
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.as_array().unwrap()[0]["content"], "Hi");
+    }
+
     #[test]
     fn test_error_handling() {
         // Test that error handling functions work correctly
@@ -381,4 +787,59 @@ mod tests {
         assert_eq!(user_friendly.status, StatusCode::BAD_REQUEST);
         assert_eq!(user_friendly.code, "INVALID_CONTENT");
     }
+
+    #[tokio::test]
+    async fn test_attachments_are_deduped_checked_per_room_and_linked() {
+        use crate::config::StorageConfig;
+        use crate::database::CampfireDatabase;
+        use crate::models::{Room, RoomType, User};
+        use crate::storage::LocalBlobStore;
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let config = StorageConfig {
+            local_path: dir.path().to_path_buf(),
+            max_attachments_per_message: 2,
+            max_attachment_bytes_per_message: 0,
+            ..Default::default()
+        };
+        let blobs = QuotaBlobStore::new(Arc::new(LocalBlobStore::new(dir.path().to_path_buf())), db.clone(), &config);
+
+        let user_id = UserId::new();
+        db.create_user(User {
+            id: user_id,
+            ..User::for_tests("Poster")
+        }).await.unwrap();
+        let (room_id, other_room) = (RoomId::new(), RoomId::new());
+        for (id, name) in [(room_id, "Design"), (other_room, "Ops")] {
+            db.create_room(Room {
+                id,
+                ..Room::for_tests(name, RoomType::Open)
+            }).await.unwrap();
+        }
+        blobs.put_attachment(user_id, room_id, "attachments/1/a.txt", vec![1; 4], "text/plain").await.unwrap();
+        blobs.put_attachment(user_id, other_room, "attachments/2/b.txt", vec![1; 4], "text/plain").await.unwrap();
+
+        // Repeats count once, so this fits the cap of two
+        let keys = vec!["attachments/1/a.txt".to_string(); 3];
+        let resolved = resolve_attachments(&blobs, user_id, room_id, &keys).await.unwrap();
+        assert_eq!(resolved, vec!["attachments/1/a.txt".to_string()]);
+
+        let elsewhere = vec!["attachments/2/b.txt".to_string()];
+        let rejection = resolve_attachments(&blobs, user_id, room_id, &elsewhere).await.unwrap_err();
+        assert!(rejection.reason().contains("another room"));
+        let unknown = vec!["attachments/3/c.txt".to_string()];
+        assert!(matches!(
+            resolve_attachments(&blobs, user_id, room_id, &unknown).await,
+            Err(AttachmentRejection::Invalid(_))
+        ));
+
+        let message = Message::new(room_id, user_id, "see attached".to_string(), Uuid::new_v4());
+        db.create_message_with_deduplication(message.clone()).await.unwrap();
+        db.link_message_attachments(message.id, resolved.clone()).await.unwrap();
+        // Linking again, as a deduplicated retry does, changes nothing
+        db.link_message_attachments(message.id, resolved.clone()).await.unwrap();
+        assert_eq!(db.get_message_attachments(message.id).await.unwrap(), resolved);
+    }
 }
\ No newline at end of file
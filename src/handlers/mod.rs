@@ -11,4 +11,8 @@ pub mod pages;
 pub mod setup;
 pub mod demo;
 pub mod security;
-pub mod analytics;
\ No newline at end of file
+pub mod analytics;
+pub mod features;
+pub mod public;
+pub mod maintenance;
+pub mod attachments;
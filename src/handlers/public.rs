@@ -0,0 +1,214 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use serde::Deserialize;
+use tracing::error;
+
+use crate::database::CampfireDatabase;
+use crate::handlers::messages::parse_message_id;
+use crate::middleware::PathId;
+use crate::models::{Message, MessageId, RoomId};
+use crate::validation::resolve_limit;
+use crate::AppState;
+
+/// Public history is the same for every reader, so shared caches may keep it
+const PUBLIC_CACHE_CONTROL: &str = "public, max-age=60, stale-while-revalidate=300";
+
+#[derive(Debug, Deserialize)]
+pub struct PublicMessagesQuery {
+    limit: Option<i64>,
+    before: Option<String>,
+}
+
+/// GET /api/public/rooms/:id/messages
+///
+/// Read-only message history for rooms an admin has marked public. No
+/// session is needed, and there are no public write paths. Only the most
+/// recent `public_history_depth` messages can be paged through; older pages
+/// come back empty.
+///
+/// # Query Parameters
+/// - `limit`: Number of messages to retrieve (configured default and cap; larger values are clamped)
+/// - `before`: MessageId to paginate before (optional)
+///
+/// # Response
+/// - 200: Messages, with a Cache-Control header allowing shared caching
+/// - 400: Negative limit or invalid message ID
+/// - 401: Room is not public (unknown and private rooms look the same)
+/// - 500: Internal server error
+pub async fn get_public_messages(
+    State(state): State<AppState>,
+    PathId(room_id): PathId<RoomId>,
+    Query(query): Query<PublicMessagesQuery>,
+) -> Result<Response, Response> {
+    let limit = resolve_limit(query.limit, &state.pagination).map_err(IntoResponse::into_response)?;
+
+    let before = match query.before {
+        Some(before) => Some(parse_message_id(&before)?),
+        None => None,
+    };
+
+    let depth = state.pagination.public_history_depth;
+    let messages = load_public_history(&state.db, room_id, limit, before, depth)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    Ok((
+        [(header::CACHE_CONTROL, PUBLIC_CACHE_CONTROL)],
+        Json(messages),
+    ).into_response())
+}
+
+async fn load_public_history(
+    db: &CampfireDatabase,
+    room_id: RoomId,
+    limit: u32,
+    before: Option<MessageId>,
+    depth: u32,
+) -> Result<Vec<Message>, StatusCode> {
+    let public = db.is_room_public(room_id).await.map_err(|e| {
+        error!("Failed to check whether room {} is public: {}", room_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !public {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // Only messages from the `depth`-th newest on, by room sequence
+    let horizon = if depth > 0 {
+        db.get_room_history_horizon(room_id, depth).await.map_err(|e| {
+            error!("Failed to find public history horizon for room {}: {}", room_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    } else {
+        None
+    };
+
+    let messages = db.get_room_messages_from(room_id, limit, before, horizon).await.map_err(|e| {
+        error!("Failed to load public history for room {}: {}", room_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Room, RoomType, User};
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    async fn create_room(db: &CampfireDatabase, room_type: RoomType) -> RoomId {
+        let room = Room::for_tests(&format!("Room {}", Uuid::new_v4()), room_type);
+        db.create_room(room.clone()).await.unwrap();
+        room.id
+    }
+
+    async fn post(db: &CampfireDatabase, room_id: RoomId, content: &str) {
+        post_at(db, room_id, content, Utc::now()).await;
+    }
+
+    async fn post_at(db: &CampfireDatabase, room_id: RoomId, content: &str, created_at: chrono::DateTime<Utc>) {
+        let user = User::for_tests("Poster");
+        db.create_user(user.clone()).await.unwrap();
+        let mut message = Message::new(room_id, user.id, content.to_string(), Uuid::new_v4());
+        message.created_at = created_at;
+        db.create_message_with_deduplication(message).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_public_room_is_readable_anonymously() {
+        let db = CampfireDatabase::new(":memory:").await.unwrap();
+        let room_id = create_room(&db, RoomType::Open).await;
+        post(&db, room_id, "hello outside world").await;
+        db.set_room_public(room_id, true).await.unwrap();
+
+        let messages = load_public_history(&db, room_id, 50, None, 0).await.unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].content, "hello outside world");
+    }
+
+    #[tokio::test]
+    async fn test_non_public_rooms_return_unauthorized() {
+        let db = CampfireDatabase::new(":memory:").await.unwrap();
+
+        let open_room = create_room(&db, RoomType::Open).await;
+        post(&db, open_room, "members only").await;
+        assert_eq!(
+            load_public_history(&db, open_room, 50, None, 0).await.unwrap_err(),
+            StatusCode::UNAUTHORIZED
+        );
+
+        // A stale flag on a closed room must not leak its history
+        let closed_room = create_room(&db, RoomType::Closed).await;
+        post(&db, closed_room, "secret").await;
+        db.set_room_public(closed_room, true).await.unwrap();
+        assert_eq!(
+            load_public_history(&db, closed_room, 50, None, 0).await.unwrap_err(),
+            StatusCode::UNAUTHORIZED
+        );
+
+        assert_eq!(
+            load_public_history(&db, RoomId::new(), 50, None, 0).await.unwrap_err(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_paging_stops_at_history_depth() {
+        let db = CampfireDatabase::new(":memory:").await.unwrap();
+        let room_id = create_room(&db, RoomType::Open).await;
+        for i in 0..5 {
+            post(&db, room_id, &format!("message {}", i)).await;
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        db.set_room_public(room_id, true).await.unwrap();
+
+        // Pages of two over a depth of three: the second is cut short
+        let first = load_public_history(&db, room_id, 2, None, 3).await.unwrap();
+        assert_eq!(first.len(), 2);
+        let second = load_public_history(&db, room_id, 2, Some(first[1].id), 3).await.unwrap();
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].content, "message 2");
+        let third = load_public_history(&db, room_id, 2, Some(second[0].id), 3).await.unwrap();
+        assert!(third.is_empty());
+
+        // Members page through the room's full history
+        let member_page = db.get_room_messages(room_id, 2, Some(second[0].id)).await.unwrap();
+        assert_eq!(member_page.len(), 2);
+        assert_eq!(member_page[1].content, "message 0");
+    }
+
+    #[tokio::test]
+    async fn test_history_depth_holds_when_timestamps_tie() {
+        let db = CampfireDatabase::new(":memory:").await.unwrap();
+        let room_id = create_room(&db, RoomType::Open).await;
+        let now = Utc::now();
+        for i in 0..5 {
+            post_at(&db, room_id, &format!("message {}", i), now).await;
+        }
+        db.set_room_public(room_id, true).await.unwrap();
+
+        let messages = load_public_history(&db, room_id, 50, None, 3).await.unwrap();
+        let contents: Vec<_> = messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["message 4", "message 3", "message 2"]);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_before_gets_the_json_error_body() {
+        let state = AppState::for_tests(CampfireDatabase::new(":memory:").await.unwrap());
+        let room_id = create_room(&state.db, RoomType::Open).await;
+        state.db.set_room_public(room_id, true).await.unwrap();
+
+        let query = PublicMessagesQuery { limit: None, before: Some("not-a-message-id".to_string()) };
+        let response = get_public_messages(State(state), PathId(room_id), Query(query)).await.unwrap_err();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["code"], "INVALID_CONTENT");
+    }
+}
@@ -1,9 +1,10 @@
 use axum::{
-    extract::{Path, State},
+    extract::State,
     http::StatusCode,
     response::Json,
     Extension,
 };
+use crate::middleware::PathId;
 use crate::models::*;
 use crate::validation::{CreatePushSubscriptionRequest, validate_request};
 
@@ -44,20 +45,9 @@ pub async fn create_push_subscription(
 pub async fn delete_push_subscription(
     State(app_state): State<crate::AppState>,
     Extension(_user): Extension<User>,
-    Path(subscription_id): Path<String>,
+    PathId(subscription_id): PathId<PushSubscriptionId>,
 ) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
     let push_service = &app_state.push_service;
-    let subscription_id = match uuid::Uuid::parse_str(&subscription_id) {
-        Ok(id) => PushSubscriptionId(id),
-        Err(_) => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({
-                    "error": "Invalid subscription ID format"
-                })),
-            ));
-        }
-    };
     
     match push_service.delete_subscription(subscription_id).await {
         Ok(_) => Ok(StatusCode::NO_CONTENT),
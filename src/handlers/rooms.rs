@@ -1,15 +1,21 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
     response::{IntoResponse, Json, Response},
 };
+use serde::Deserialize;
 use serde_json::json;
-use uuid::Uuid;
+use std::collections::HashMap;
 
 use crate::errors::RoomError;
-use crate::middleware::session::AuthenticatedUser;
-use crate::models::{Room, RoomId};
-use crate::validation::{CreateRoomRequest, AddRoomMemberRequest, sanitization, validate_request};
+use crate::logging::audit::{AuditAction, AuditLogger};
+use crate::middleware::{session::AuthenticatedUser, parse_path_id, PathId};
+use crate::models::{CannedResponse, DirectConversation, MentionCandidate, Room, RoomId, RoomListEntry, RoomPermissions, UserId};
+use crate::sounds::SoundPolicy;
+use crate::validation::{
+    CreateRoomRequest, AddRoomMemberRequest, ChangeRoomTypeRequest, ResolveRoomRequest,
+    LimitQuery, UpdatePostPermissionRequest, resolve_limit, sanitization, validate_request,
+};
 use crate::AppState;
 
 /// GET /api/rooms
@@ -20,23 +26,74 @@ use crate::AppState;
 /// Requires valid session token via Authorization header or cookie
 /// 
 /// # Response
-/// - 200: JSON array of Room objects the user has access to
+/// - 200: JSON array of Room objects the user has access to, each with a
+///   `last_message_preview` (`sender_name`, `text`, `created_at`) or null
 /// - 401: Invalid or missing authentication token
 /// - 500: Internal server error
+/// 
+/// Archived rooms are left out unless `?include_archived=true`.
 pub async fn get_rooms(
     auth_user: AuthenticatedUser,
     State(state): State<AppState>,
-) -> Result<Json<Vec<Room>>, RoomApiError> {
+    Query(query): Query<GetRoomsQuery>,
+) -> Result<Json<Vec<RoomListEntry>>, RoomApiError> {
     // Get rooms for the authenticated user
-    let rooms = state
+    let mut rooms = state
         .room_service
-        .get_user_rooms(auth_user.user.id)
+        .get_room_list(auth_user.user.id)
         .await
         .map_err(RoomApiError::from)?;
 
+    if !query.include_archived {
+        let archived = state
+            .db
+            .get_archived_room_ids(auth_user.user.id)
+            .await
+            .map_err(|e| RoomApiError::from(RoomError::from(e)))?;
+        rooms.retain(|entry| !archived.contains(&entry.room.id));
+    }
+
     Ok(Json(rooms))
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct GetRoomsQuery {
+    #[serde(default)]
+    pub include_archived: bool,
+}
+
+/// GET /api/direct
+/// 
+/// Returns the current user's direct-message rooms, most recently active first
+/// 
+/// # Authentication
+/// Requires valid session token via Authorization header or cookie
+/// 
+/// # Query Parameters
+/// - limit: Most conversations returned (default and cap from `pagination`)
+/// 
+/// # Response
+/// - 200: JSON array of Room objects, each with a `participant` (`id`,
+///   `name`, `bio`, `bot`) or null, and an `unread_count`
+/// - 400: Negative limit
+/// - 401: Invalid or missing authentication token
+/// - 500: Internal server error
+pub async fn get_direct_conversations(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    Query(query): Query<LimitQuery>,
+) -> Result<Json<Vec<DirectConversation>>, RoomApiError> {
+    let limit = resolve_limit(query.limit, &state.pagination).map_err(RoomApiError::ValidationError)?;
+    let mut conversations = state
+        .room_service
+        .get_direct_conversations(auth_user.user.id)
+        .await
+        .map_err(RoomApiError::from)?;
+    conversations.truncate(limit as usize);
+
+    Ok(Json(conversations))
+}
+
 /// POST /api/rooms
 /// 
 /// Creates a new room with the authenticated user as admin
@@ -91,6 +148,47 @@ pub async fn create_room(
     Ok((StatusCode::CREATED, Json(room)))
 }
 
+/// POST /api/rooms/resolve-or-create
+/// 
+/// Resolves a `#channel` reference to an open room, creating the room with the
+/// authenticated user as admin if no open room has that name
+/// 
+/// # Authentication
+/// Requires valid session token via Authorization header or cookie
+/// 
+/// # Request Body
+/// ```json
+/// {
+///   "name": "#channel-name"
+/// }
+/// ```
+/// 
+/// # Response
+/// - 200: `{"room": Room, "created": false}` for an existing room
+/// - 201: `{"room": Room, "created": true}` for a newly created room
+/// - 400: Invalid channel name
+/// - 401: Invalid or missing authentication token
+/// - 500: Internal server error
+pub async fn resolve_or_create_room(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    Json(request): Json<ResolveRoomRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), RoomApiError> {
+    if let Err(validation_error) = validate_request(&request) {
+        return Err(RoomApiError::ValidationError(validation_error));
+    }
+    
+    let name = sanitization::sanitize_room_name(&request.name);
+    let (room, created) = state
+        .room_service
+        .resolve_or_create_room(name, auth_user.user.id)
+        .await
+        .map_err(RoomApiError::from)?;
+
+    let status = if created { StatusCode::CREATED } else { StatusCode::OK };
+    Ok((status, Json(json!({ "room": room, "created": created }))))
+}
+
 /// GET /api/rooms/:id
 /// 
 /// Gets details for a specific room
@@ -111,11 +209,8 @@ pub async fn create_room(
 pub async fn get_room(
     auth_user: AuthenticatedUser,
     State(state): State<AppState>,
-    Path(room_id_str): Path<String>,
+    PathId(room_id): PathId<RoomId>,
 ) -> Result<Json<Room>, RoomApiError> {
-    // Parse room ID
-    let room_id = parse_room_id(&room_id_str)?;
-
     // Check if user has access to the room
     let access_level = state
         .room_service
@@ -140,6 +235,85 @@ pub async fn get_room(
     }
 }
 
+/// GET /api/rooms/:id/permissions
+/// 
+/// Returns what the authenticated user may do in the room
+/// 
+/// # Authentication
+/// Requires valid session token via Authorization header or cookie
+/// 
+/// # Path Parameters
+/// - id: UUID of the room
+/// 
+/// # Response
+/// - 200: JSON RoomPermissions object
+/// - 400: Invalid room ID format
+/// - 401: Invalid or missing authentication token
+/// - 403: User does not have access to this room
+/// - 404: Room not found
+/// - 500: Internal server error
+pub async fn get_room_permissions(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    PathId(room_id): PathId<RoomId>,
+) -> Result<Json<RoomPermissions>, RoomApiError> {
+    let permissions = state
+        .room_service
+        .get_permissions(room_id, auth_user.user.id)
+        .await
+        .map_err(RoomApiError::from)?;
+
+    // Same visibility as GET /api/rooms/:id
+    if !permissions.can_read {
+        return Err(RoomApiError::AccessDenied { room_id });
+    }
+
+    Ok(Json(permissions))
+}
+
+/// GET /api/rooms/:id/mentionable
+/// 
+/// Returns `@mention` autocomplete candidates for the room
+/// 
+/// # Authentication
+/// Requires valid session token via Authorization header or cookie
+/// 
+/// # Query Parameters
+/// - q: Prefix of a member's name or username (a leading `@` is ignored)
+/// - limit: Most candidates returned (default and cap from `pagination`)
+/// 
+/// # Response
+/// - 200: JSON array of `{handle, name, user_id}`; `@room` and `@here`
+///   come first with a null `user_id` when room-wide mentions are enabled
+/// - 400: Invalid room ID format or negative limit
+/// - 401: Invalid or missing authentication token
+/// - 403: User does not have access to this room
+/// - 404: Room not found
+/// - 500: Internal server error
+pub async fn get_mentionable(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    PathId(room_id): PathId<RoomId>,
+    Query(query): Query<MentionableQuery>,
+) -> Result<Json<Vec<MentionCandidate>>, RoomApiError> {
+    let limit = resolve_limit(query.limit, &state.pagination).map_err(RoomApiError::ValidationError)?;
+    let mut candidates = state
+        .room_service
+        .get_mentionable(room_id, auth_user.user.id, &query.q)
+        .await
+        .map_err(RoomApiError::from)?;
+    candidates.truncate(limit as usize);
+
+    Ok(Json(candidates))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct MentionableQuery {
+    #[serde(default)]
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
 /// POST /api/rooms/:id/members
 /// 
 /// Adds a member to a room
@@ -170,16 +344,13 @@ pub async fn get_room(
 pub async fn add_room_member(
     auth_user: AuthenticatedUser,
     State(state): State<AppState>,
-    Path(room_id_str): Path<String>,
+    PathId(room_id): PathId<RoomId>,
     Json(request): Json<AddRoomMemberRequest>,
 ) -> Result<StatusCode, RoomApiError> {
     // Validate request
     if let Err(validation_error) = validate_request(&request) {
         return Err(RoomApiError::ValidationError(validation_error));
     }
-    
-    // Parse room ID
-    let room_id = parse_room_id(&room_id_str)?;
 
     // Parse user ID and involvement level
     let user_id = request.user_id.into();
@@ -196,23 +367,548 @@ pub async fn add_room_member(
     Ok(StatusCode::CREATED)
 }
 
-/// Helper function to parse room ID from string
-fn parse_room_id(room_id_str: &str) -> Result<RoomId, RoomApiError> {
-    Uuid::parse_str(room_id_str)
-        .map(RoomId::from)
-        .map_err(|_| RoomApiError::InvalidRoomId {
-            room_id: room_id_str.to_string(),
-        })
+/// DELETE /api/rooms/:id/members/:user_id
+/// 
+/// Removes a member from a room. Room admins only. The removed user's open
+/// connections are told and stop receiving the room's events.
+/// 
+/// # Response
+/// - 204: Removed, or wasn't a member
+/// - 400: Invalid room or user ID
+/// - 403: User is not an admin of the room
+/// - 404: Room not found
+pub async fn remove_room_member(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    Path((room_id, user_id)): Path<(String, String)>,
+) -> Result<StatusCode, Response> {
+    let room_id: RoomId = parse_path_id(&room_id).map_err(IntoResponse::into_response)?;
+    let user_id: UserId = parse_path_id(&user_id).map_err(IntoResponse::into_response)?;
+    
+    state
+        .room_service
+        .remove_member(room_id, user_id, auth_user.user.id)
+        .await
+        .map_err(|e| RoomApiError::from(e).into_response())?;
+    
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// PUT /api/rooms/:id/type
+/// 
+/// Converts a room between open and closed
+/// 
+/// # Authentication
+/// Requires valid session token via Authorization header or cookie
+/// User must be an admin of the room (or a site admin)
+/// 
+/// # Request Body
+/// ```json
+/// {
+///   "room_type": "open" | "closed"
+/// }
+/// ```
+/// 
+/// # Response
+/// - 200: JSON Room object with the new type
+/// - 400: Invalid room type, or the room is a direct room
+/// - 401: Invalid or missing authentication token
+/// - 403: User is not an admin of the room
+/// - 404: Room not found
+/// - 500: Internal server error
+pub async fn change_room_type(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    PathId(room_id): PathId<RoomId>,
+    Json(request): Json<ChangeRoomTypeRequest>,
+) -> Result<Json<Room>, RoomApiError> {
+    if let Err(validation_error) = validate_request(&request) {
+        return Err(RoomApiError::ValidationError(validation_error));
+    }
+    
+    let room_type = request.room_type.parse()
+        .map_err(|_| RoomApiError::InvalidRoomType { room_type: request.room_type })?;
+    
+    let room = state
+        .room_service
+        .change_room_type(room_id, auth_user.user.id, room_type)
+        .await
+        .map_err(RoomApiError::from)?;
+
+    Ok(Json(room))
+}
+
+/// PUT /api/rooms/:id/post-permission
+/// 
+/// Sets who may post in the room; members are notified so clients can
+/// disable the composer
+/// 
+/// # Request Body
+/// ```json
+/// {
+///   "post_permission": "everyone" | "admins_only"
+/// }
+/// ```
+/// 
+/// # Response
+/// - 200: The caller's permissions in the room after the change
+/// - 400: Invalid post permission
+/// - 401: Invalid or missing authentication token
+/// - 403: User is not an admin of the room
+/// - 404: Room not found
+pub async fn update_post_permission(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    PathId(room_id): PathId<RoomId>,
+    Json(request): Json<UpdatePostPermissionRequest>,
+) -> Result<Json<RoomPermissions>, RoomApiError> {
+    if let Err(validation_error) = validate_request(&request) {
+        return Err(RoomApiError::ValidationError(validation_error));
+    }
+    
+    let post_permission = request.post_permission.parse()
+        .map_err(|_| RoomApiError::InvalidPostPermission { post_permission: request.post_permission })?;
+    
+    state
+        .room_service
+        .set_post_permission(room_id, auth_user.user.id, post_permission)
+        .await
+        .map_err(RoomApiError::from)?;
+    
+    let permissions = state
+        .room_service
+        .get_permissions(room_id, auth_user.user.id)
+        .await
+        .map_err(RoomApiError::from)?;
+
+    Ok(Json(permissions))
+}
+
+/// PUT /api/rooms/:id/post-grants/:user_id
+/// 
+/// Lets a user (typically a bot) post in an admins-only room. Room admins only.
+/// 
+/// # Response
+/// - 204: Grant recorded
+/// - 400: Invalid room or user ID
+/// - 403: User is not an admin of the room
+/// - 404: Room not found
+pub async fn grant_post_permission(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    Path((room_id, user_id)): Path<(String, String)>,
+) -> Result<StatusCode, Response> {
+    set_post_grant(auth_user, state, room_id, user_id, true).await
+}
+
+/// DELETE /api/rooms/:id/post-grants/:user_id
+/// 
+/// Revokes a grant made with PUT. Room admins only.
+pub async fn revoke_post_permission(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    Path((room_id, user_id)): Path<(String, String)>,
+) -> Result<StatusCode, Response> {
+    set_post_grant(auth_user, state, room_id, user_id, false).await
+}
+
+async fn set_post_grant(
+    auth_user: AuthenticatedUser,
+    state: AppState,
+    room_id: String,
+    user_id: String,
+    granted: bool,
+) -> Result<StatusCode, Response> {
+    let room_id: RoomId = parse_path_id(&room_id).map_err(IntoResponse::into_response)?;
+    let user_id: UserId = parse_path_id(&user_id).map_err(IntoResponse::into_response)?;
+    
+    state
+        .room_service
+        .set_post_grant(room_id, auth_user.user.id, user_id, granted)
+        .await
+        .map_err(|e| RoomApiError::from(e).into_response())?;
+    
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRoomPublicRequest {
+    pub public: bool,
+}
+
+/// PUT /api/rooms/:id/public
+/// 
+/// Makes an open room's history readable without signing in, via
+/// `GET /api/public/rooms/:id/messages`, or turns that off again
+/// 
+/// # Request Body
+/// ```json
+/// {
+///   "public": true
+/// }
+/// ```
+/// 
+/// # Response
+/// - 204: Flag updated
+/// - 400: Room is not an open room
+/// - 401: Invalid or missing authentication token
+/// - 403: User is not an admin of the room
+/// - 404: Room not found
+pub async fn update_room_public(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    PathId(room_id): PathId<RoomId>,
+    Json(request): Json<UpdateRoomPublicRequest>,
+) -> Result<StatusCode, RoomApiError> {
+    state
+        .room_service
+        .set_public(room_id, auth_user.user.id, request.public)
+        .await
+        .map_err(RoomApiError::from)?;
+    
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAttachmentTypesRequest {
+    pub allowed_attachment_types: Option<Vec<String>>,
+}
+
+/// PUT /api/rooms/:id/attachment-types
+/// 
+/// Sets the content types attachments in the room may have, replacing the
+/// server's allowlist; `null` goes back to the server's allowlist and `[]`
+/// allows any type
+/// 
+/// # Request Body
+/// ```json
+/// {
+///   "allowed_attachment_types": ["application/pdf"]
+/// }
+/// ```
+/// 
+/// # Response
+/// - 204: Allowlist updated
+/// - 400: An entry isn't a content type or `type/*` wildcard
+/// - 401: Invalid or missing authentication token
+/// - 403: User is not an admin of the room
+/// - 404: Room not found
+pub async fn update_attachment_types(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    PathId(room_id): PathId<RoomId>,
+    Json(request): Json<UpdateAttachmentTypesRequest>,
+) -> Result<StatusCode, RoomApiError> {
+    state
+        .room_service
+        .set_attachment_types(room_id, auth_user.user.id, request.allowed_attachment_types)
+        .await
+        .map_err(RoomApiError::from)?;
+    
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSoundCooldownRequest {
+    pub cooldown_secs: Option<u64>,
+}
+
+/// PUT /api/rooms/:id/sound-cooldown
+/// 
+/// Sets how many seconds must pass between sounds in the room; `null` goes
+/// back to the server default and `0` turns the cooldown off
+/// 
+/// # Request Body
+/// ```json
+/// {
+///   "cooldown_secs": 30
+/// }
+/// ```
+/// 
+/// # Response
+/// - 204: Cooldown updated
+/// - 401: Invalid or missing authentication token
+/// - 403: User is not an admin of the room
+/// - 404: Room not found
+pub async fn update_sound_cooldown(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    PathId(room_id): PathId<RoomId>,
+    Json(request): Json<UpdateSoundCooldownRequest>,
+) -> Result<StatusCode, RoomApiError> {
+    state
+        .room_service
+        .set_sound_cooldown(room_id, auth_user.user.id, request.cooldown_secs)
+        .await
+        .map_err(RoomApiError::from)?;
+    
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// PUT /api/rooms/:id/sound-policy
+/// 
+/// Sets which sounds `/play` may trigger in the room. Disallowed commands
+/// are dropped; the message itself still posts.
+/// 
+/// # Request Body
+/// ```json
+/// { "mode": "any" }
+/// { "mode": "disabled" }
+/// { "mode": "allowlist", "sounds": ["tada", "rimshot"] }
+/// ```
+/// 
+/// # Response
+/// - 204: Policy updated
+/// - 400: Unknown sound in the allowlist
+/// - 401: Invalid or missing authentication token
+/// - 403: User is not an admin of the room
+/// - 404: Room not found
+pub async fn update_sound_policy(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    PathId(room_id): PathId<RoomId>,
+    Json(policy): Json<SoundPolicy>,
+) -> Result<StatusCode, RoomApiError> {
+    state
+        .room_service
+        .set_sound_policy(room_id, auth_user.user.id, policy)
+        .await
+        .map_err(RoomApiError::from)?;
+    
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /api/rooms/:id/canned
+/// 
+/// Lists the room's canned responses for autocomplete after `/canned `
+/// 
+/// # Query Parameters
+/// - q: Prefix of the shortcut
+/// 
+/// # Response
+/// - 200: JSON array of `{shortcut, content, updated_at}`, by shortcut
+/// - 400: Invalid room ID format
+/// - 401: Invalid or missing authentication token
+/// - 403: User does not have access to this room
+/// - 404: Room not found
+pub async fn get_canned_responses(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    PathId(room_id): PathId<RoomId>,
+    Query(query): Query<MentionableQuery>,
+) -> Result<Json<Vec<CannedResponse>>, RoomApiError> {
+    let responses = state
+        .room_service
+        .get_canned_responses(room_id, auth_user.user.id, &query.q)
+        .await
+        .map_err(RoomApiError::from)?;
+
+    Ok(Json(responses))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetCannedResponseRequest {
+    pub content: String,
+}
+
+/// PUT /api/rooms/:id/canned/:shortcut
+/// 
+/// Saves a canned response; `/canned <shortcut>` in the room then posts
+/// `content` in its place. Room admins only.
+/// 
+/// # Request Body
+/// ```json
+/// {
+///   "content": "Thanks for reaching out! We'll get back to you shortly."
+/// }
+/// ```
+/// 
+/// # Response
+/// - 204: Response saved
+/// - 400: Invalid shortcut or content
+/// - 401: Invalid or missing authentication token
+/// - 403: User is not an admin of the room
+/// - 404: Room not found
+/// - 409: The room already has as many canned responses as allowed
+pub async fn set_canned_response(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    Path((room_id, shortcut)): Path<(String, String)>,
+    Json(request): Json<SetCannedResponseRequest>,
+) -> Result<StatusCode, Response> {
+    let room_id: RoomId = parse_path_id(&room_id).map_err(IntoResponse::into_response)?;
+    
+    state
+        .room_service
+        .set_canned_response(room_id, auth_user.user.id, &shortcut, request.content)
+        .await
+        .map_err(|e| RoomApiError::from(e).into_response())?;
+    
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// DELETE /api/rooms/:id/canned/:shortcut
+/// 
+/// Removes a canned response. Room admins only.
+/// 
+/// # Response
+/// - 204: Response removed
+/// - 403: User is not an admin of the room
+/// - 404: Room or shortcut not found
+pub async fn delete_canned_response(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    Path((room_id, shortcut)): Path<(String, String)>,
+) -> Result<StatusCode, Response> {
+    let room_id: RoomId = parse_path_id(&room_id).map_err(IntoResponse::into_response)?;
+    
+    state
+        .room_service
+        .delete_canned_response(room_id, auth_user.user.id, &shortcut)
+        .await
+        .map_err(|e| RoomApiError::from(e).into_response())?;
+    
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateNotifyModeRequest {
+    pub mentions_only: bool,
 }
 
+/// PUT /api/rooms/:id/notify-mode
+/// 
+/// With `mentions_only` set, only messages that mention someone (or
+/// `@room`/`@here`) send push notifications in the room, even to members
+/// who asked to hear about every message. Useful for large rooms.
+/// 
+/// # Request Body
+/// ```json
+/// {
+///   "mentions_only": true
+/// }
+/// ```
+/// 
+/// # Response
+/// - 204: Mode updated
+/// - 401: Invalid or missing authentication token
+/// - 403: User is not an admin of the room
+/// - 404: Room not found
+pub async fn update_notify_mode(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    PathId(room_id): PathId<RoomId>,
+    Json(request): Json<UpdateNotifyModeRequest>,
+) -> Result<StatusCode, RoomApiError> {
+    state
+        .room_service
+        .set_notify_mentions_only(room_id, auth_user.user.id, request.mentions_only)
+        .await
+        .map_err(RoomApiError::from)?;
+    
+    Ok(StatusCode::NO_CONTENT)
+}
 
+#[derive(Debug, Deserialize)]
+pub struct DeleteRoomQuery {
+    pub confirm: Option<String>,
+}
+
+/// DELETE /api/admin/rooms/:id
+/// 
+/// Permanently deletes a room with its messages, memberships, webhooks and
+/// read state (site admins only). Unlike archiving this can't be undone, so
+/// the first call is refused with a `confirmation_token`; repeating it with
+/// `?confirm=<token>` goes ahead. Connected members are told the room is gone.
+/// 
+/// # Response
+/// - 204: Room deleted
+/// - 400: Invalid room ID
+/// - 401: Invalid or missing authentication token
+/// - 403: User is not a site admin
+/// - 404: Room not found
+/// - 428: Confirmation required; the body carries `confirmation_token`
+pub async fn delete_room_permanently(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    PathId(room_id): PathId<RoomId>,
+    Query(query): Query<DeleteRoomQuery>,
+) -> Result<StatusCode, RoomApiError> {
+    state
+        .room_service
+        .delete_room_permanently(room_id, auth_user.user.id, query.confirm)
+        .await
+        .map_err(RoomApiError::from)?;
+    
+    AuditLogger::new(true).log_user_action(
+        AuditAction::RoomDeleted,
+        auth_user.user.id,
+        "room",
+        Some(room_id.to_string()),
+        HashMap::from([("permanent".to_string(), "true".to_string())]),
+    );
+    
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRetentionRequest {
+    pub retention_days: Option<u32>,
+}
+
+/// PUT /api/admin/rooms/:id/retention
+/// 
+/// Overrides how long the room's messages are kept (site admins only).
+/// `null` goes back to the global retention and `0` places the room on
+/// legal hold, so its messages are never purged.
+/// 
+/// # Request Body
+/// ```json
+/// {
+///   "retention_days": 365
+/// }
+/// ```
+/// 
+/// # Response
+/// - 204: Retention updated
+/// - 400: Invalid room ID
+/// - 401: Invalid or missing authentication token
+/// - 403: User is not a site admin
+/// - 404: Room not found
+pub async fn update_room_retention(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    PathId(room_id): PathId<RoomId>,
+    Json(request): Json<UpdateRetentionRequest>,
+) -> Result<StatusCode, RoomApiError> {
+    state
+        .room_service
+        .set_retention(room_id, auth_user.user.id, request.retention_days)
+        .await
+        .map_err(RoomApiError::from)?;
+    
+    let retention = match request.retention_days {
+        None => "inherit".to_string(),
+        Some(0) => "legal_hold".to_string(),
+        Some(days) => days.to_string(),
+    };
+    AuditLogger::new(true).log_user_action(
+        AuditAction::RoomRetentionChanged,
+        auth_user.user.id,
+        "room",
+        Some(room_id.to_string()),
+        HashMap::from([("retention_days".to_string(), retention)]),
+    );
+    
+    Ok(StatusCode::NO_CONTENT)
+}
 
 /// Room API specific errors with proper HTTP status codes
 #[derive(Debug)]
 pub enum RoomApiError {
-    InvalidRoomId { room_id: String },
     InvalidUserId { user_id: String },
     InvalidRoomType { room_type: String },
+    InvalidPostPermission { post_permission: String },
     InvalidInvolvementLevel { level: String },
     NotFound { room_id: RoomId },
     AccessDenied { room_id: RoomId },
@@ -239,11 +935,6 @@ impl From<crate::errors::DatabaseError> for RoomApiError {
 impl IntoResponse for RoomApiError {
     fn into_response(self) -> Response {
         let (status, error_message, error_code) = match self {
-            RoomApiError::InvalidRoomId { room_id } => (
-                StatusCode::BAD_REQUEST,
-                format!("Invalid room ID format: {}", room_id),
-                "INVALID_ROOM_ID",
-            ),
             RoomApiError::InvalidUserId { user_id } => (
                 StatusCode::BAD_REQUEST,
                 format!("Invalid user ID format: {}", user_id),
@@ -254,6 +945,11 @@ impl IntoResponse for RoomApiError {
                 format!("Invalid room type: {}", room_type),
                 "INVALID_ROOM_TYPE",
             ),
+            RoomApiError::InvalidPostPermission { post_permission } => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid post permission: {}", post_permission),
+                "INVALID_POST_PERMISSION",
+            ),
             RoomApiError::InvalidInvolvementLevel { level } => (
                 StatusCode::BAD_REQUEST,
                 format!("Invalid involvement level: {}", level),
@@ -293,11 +989,70 @@ impl IntoResponse for RoomApiError {
                     format!("User {} is already a member of room {}", user_id, room_id),
                     "ALREADY_MEMBER",
                 ),
+                RoomError::DirectRoomFull { room_id, max_members } => (
+                    StatusCode::CONFLICT,
+                    format!("Direct room {} can't have more than {} members", room_id, max_members),
+                    "DIRECT_ROOM_FULL",
+                ),
                 RoomError::InvalidName { reason } => (
                     StatusCode::BAD_REQUEST,
                     format!("Invalid room name: {}", reason),
                     "INVALID_ROOM_NAME",
                 ),
+                RoomError::InvalidTypeChange { room_id, reason } => (
+                    StatusCode::BAD_REQUEST,
+                    format!("Cannot change type of room {}: {}", room_id, reason),
+                    "INVALID_ROOM_TYPE_CHANGE",
+                ),
+                RoomError::NotOpen { room_id } => (
+                    StatusCode::BAD_REQUEST,
+                    format!("Room {} is not an open room", room_id),
+                    "ROOM_NOT_OPEN",
+                ),
+                RoomError::InvalidAttachmentType { value } => (
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid attachment type: {}", value),
+                    "INVALID_ATTACHMENT_TYPE",
+                ),
+                RoomError::UnknownSound { name } => (
+                    StatusCode::BAD_REQUEST,
+                    format!("Unknown sound: {}", name),
+                    "UNKNOWN_SOUND",
+                ),
+                RoomError::InvalidCannedResponse { reason } => (
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid canned response: {}", reason),
+                    "INVALID_CANNED_RESPONSE",
+                ),
+                RoomError::CannedResponseNotFound { shortcut } => (
+                    StatusCode::NOT_FOUND,
+                    format!("No canned response '{}' in this room", shortcut),
+                    "CANNED_RESPONSE_NOT_FOUND",
+                ),
+                RoomError::CannedResponseLimitReached { limit } => (
+                    StatusCode::CONFLICT,
+                    format!("Canned response limit of {} reached", limit),
+                    "CANNED_RESPONSE_LIMIT_REACHED",
+                ),
+                RoomError::JoinRateLimit { limit, retry_after_secs } => {
+                    let status = StatusCode::TOO_MANY_REQUESTS;
+                    let body = Json(json!({
+                        "error": format!("Joining rooms too quickly; at most {} per minute", limit),
+                        "code": "JOIN_RATE_LIMIT_EXCEEDED",
+                        "status": status.as_u16(),
+                    }));
+                    return (status, [(header::RETRY_AFTER, retry_after_secs.to_string())], body).into_response();
+                }
+                RoomError::ConfirmationRequired { room_id, token } => {
+                    let status = StatusCode::PRECONDITION_REQUIRED;
+                    let body = Json(json!({
+                        "error": format!("Deleting room {} cannot be undone; repeat the request with ?confirm=<confirmation_token>", room_id),
+                        "code": "CONFIRMATION_REQUIRED",
+                        "status": status.as_u16(),
+                        "confirmation_token": token,
+                    }));
+                    return (status, body).into_response();
+                }
                 RoomError::Database(db_error) => (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     format!("Database error: {}", db_error),
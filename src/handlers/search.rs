@@ -9,17 +9,36 @@ use crate::{
     AppState,
     services::search::{SearchResponse, SearchError},
     middleware::session::AuthenticatedUser,
-    validation::{SearchRequest, sanitization},
+    validation::{SearchRequest, resolve_limit, sanitization},
 };
 
-/// GET /api/search?q=query&limit=20&offset=0&room_id=uuid
+/// GET /api/search?q=query&limit=20&offset=0&room_id=uuid&prefix=true
 /// 
-/// Search messages with full-text search across user's accessible rooms
+/// Search messages with full-text search across user's accessible rooms.
+/// `prefix=true` (with a room_id) matches word prefixes for search-as-you-type.
+/// Answers 404 when the `search` feature flag is off for the user (or room).
 pub async fn search_messages(
     State(state): State<AppState>,
     auth_user: AuthenticatedUser,
     Query(params): Query<SearchRequest>,
 ) -> Result<Json<SearchResponse>, (StatusCode, Json<Value>)> {
+    let room_id = params.room_id.map(crate::models::RoomId);
+    match state.features.is_enabled("search", auth_user.user.id, room_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(json!({ "error": "Search is not enabled", "type": "feature_disabled" }))
+            ));
+        }
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": e.to_string(), "type": "database_error" }))
+            ));
+        }
+    }
+    
     // Validate the search request
     if let Err(validation_errors) = params.validate() {
         return Err((
@@ -31,15 +50,29 @@ pub async fn search_messages(
         ));
     }
     
+    let limit = match resolve_limit(params.limit, &state.pagination) {
+        Ok(limit) => limit,
+        Err(validation_error) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "error": validation_error.error,
+                    "details": validation_error.details
+                }))
+            ));
+        }
+    };
+    
     // Sanitize search query
     let sanitized_query = sanitization::sanitize_user_input(&params.q);
     
     // Create sanitized search request
     let search_request = crate::services::search::SearchRequest {
         query: sanitized_query,
-        limit: params.limit,
+        limit: Some(limit),
         offset: None,
-        room_id: params.room_id.map(|id| crate::models::RoomId(id)),
+        room_id,
+        prefix: params.prefix,
     };
     
     match state.search_service.search_messages(auth_user.user.id, search_request).await {
@@ -50,6 +83,7 @@ pub async fn search_messages(
                 SearchError::InvalidQuery { .. } => "invalid_query",
                 SearchError::QueryTooShort => "query_too_short",
                 SearchError::QueryTooLong => "query_too_long",
+                SearchError::PrefixTooShort { .. } => "prefix_too_short",
                 SearchError::Database(_) => "database_error",
                 SearchError::RoomAccess(_) => "access_denied",
             };
@@ -7,6 +7,8 @@ use serde_json::json;
 
 use crate::{
     AppState,
+    errors::SetupError,
+    middleware::ClientIp,
     models::CreateAdminRequest,
 };
 
@@ -196,8 +198,11 @@ pub async fn get_setup_status(State(state): State<AppState>) -> impl IntoRespons
 /// - Returns created user and session token
 /// - Enables subsequent normal login flow
 /// - Provides detailed error information for troubleshooting
+/// - Returns 409 once setup is complete, and 429 when one client makes too
+///   many attempts
 pub async fn create_admin_account(
     State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
     Json(request): Json<CreateAdminRequest>,
 ) -> impl IntoResponse {
     // Pre-validation: Check if setup is still needed
@@ -239,6 +244,21 @@ pub async fn create_admin_account(
         }
     }
     
+    if let Err(SetupError::TooManyAttempts { retry_after_secs }) = state.setup_service.record_attempt(client_ip).await {
+        let error_response = json!({
+            "success": false,
+            "error": "TOO_MANY_SETUP_ATTEMPTS",
+            "message": format!("Too many setup attempts. Try again in {} seconds.", retry_after_secs),
+            "retry_after_seconds": retry_after_secs,
+        });
+        
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, retry_after_secs.to_string())],
+            Json(error_response),
+        ).into_response();
+    }
+    
     // Attempt to create admin account
     match state.setup_service.create_admin_account(request).await {
         Ok(response) => {
@@ -293,7 +313,7 @@ pub async fn create_admin_account(
         Err(e) => {
             // Error creating admin account - provide detailed error information
             let (error_code, recovery_actions) = match &e {
-                crate::errors::SetupError::NotFirstRun => (
+                SetupError::NotFirstRun => (
                     "NOT_FIRST_RUN",
                     vec![
                         "Refresh the page and check if setup is already complete".to_string(),
@@ -301,7 +321,7 @@ pub async fn create_admin_account(
                         "Try accessing /login to see if an admin account already exists".to_string(),
                     ]
                 ),
-                crate::errors::SetupError::InvalidEmail { email } => (
+                SetupError::InvalidEmail { email } => (
                     "INVALID_EMAIL",
                     vec![
                         format!("Provide a valid email address (current: '{}')", email),
@@ -309,15 +329,17 @@ pub async fn create_admin_account(
                         "Avoid special characters that might cause issues".to_string(),
                     ]
                 ),
-                crate::errors::SetupError::WeakPassword { reason } => (
+                SetupError::WeakPassword { reasons } => (
                     "WEAK_PASSWORD",
-                    vec![
-                        format!("Password requirement: {}", reason),
-                        "Use at least 8 characters with letters and numbers".to_string(),
-                        "Consider using a password manager for strong passwords".to_string(),
-                    ]
+                    reasons
+                        .iter()
+                        .map(|reason| format!("Password requirement: {}", reason))
+                        .chain(std::iter::once(
+                            "Consider using a password manager for strong passwords".to_string(),
+                        ))
+                        .collect(),
                 ),
-                crate::errors::SetupError::AdminCreationFailed(msg) => (
+                SetupError::AdminCreationFailed(msg) => (
                     "ADMIN_CREATION_FAILED",
                     vec![
                         "Check database connectivity and permissions".to_string(),
@@ -326,7 +348,7 @@ pub async fn create_admin_account(
                         "Check application logs for more information".to_string(),
                     ]
                 ),
-                crate::errors::SetupError::Database(db_err) => (
+                SetupError::Database(db_err) => (
                     "DATABASE_ERROR",
                     vec![
                         "Check database connectivity".to_string(),
@@ -346,7 +368,7 @@ pub async fn create_admin_account(
                 ),
             };
             
-            let error_response = json!({
+            let mut error_response = json!({
                 "success": false,
                 "error": error_code,
                 "message": e.to_string(),
@@ -358,12 +380,17 @@ pub async fn create_admin_account(
                 }
             });
             
+            // Same field-level shape as validation errors elsewhere
+            if let SetupError::WeakPassword { reasons } = &e {
+                error_response["details"] = json!({ "password": reasons });
+            }
+            
             let status_code = match &e {
-                crate::errors::SetupError::NotFirstRun => StatusCode::CONFLICT,
-                crate::errors::SetupError::InvalidEmail { .. } => StatusCode::BAD_REQUEST,
-                crate::errors::SetupError::WeakPassword { .. } => StatusCode::BAD_REQUEST,
-                crate::errors::SetupError::AdminCreationFailed(_) => StatusCode::UNPROCESSABLE_ENTITY,
-                crate::errors::SetupError::Database(_) => StatusCode::SERVICE_UNAVAILABLE,
+                SetupError::NotFirstRun => StatusCode::CONFLICT,
+                SetupError::InvalidEmail { .. } => StatusCode::BAD_REQUEST,
+                SetupError::WeakPassword { .. } => StatusCode::BAD_REQUEST,
+                SetupError::AdminCreationFailed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+                SetupError::Database(_) => StatusCode::SERVICE_UNAVAILABLE,
                 _ => StatusCode::INTERNAL_SERVER_ERROR,
             };
             
@@ -484,7 +511,7 @@ mod tests {
         };
         
         let result = service.create_admin_account(request).await;
-        assert!(matches!(result, Err(crate::errors::SetupError::InvalidEmail { .. })));
+        assert!(matches!(result, Err(SetupError::InvalidEmail { .. })));
     }
     
     #[tokio::test]
@@ -498,7 +525,7 @@ mod tests {
         };
         
         let result = service.create_admin_account(request).await;
-        assert!(matches!(result, Err(crate::errors::SetupError::WeakPassword { .. })));
+        assert!(matches!(result, Err(SetupError::WeakPassword { .. })));
     }
     
     #[tokio::test]
@@ -1,11 +1,59 @@
 use axum::{
-    http::StatusCode,
+    body::StreamBody,
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Json, Response},
 };
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
 use serde_json::json;
-use tracing::info;
+use std::net::IpAddr;
+use tracing::{info, warn};
 
-use crate::middleware::session::AuthenticatedUser;
+use crate::errors::{AuthError, ExportError};
+use crate::logging::audit::{AuditAction, AuditEvent};
+use crate::logging::error_handling::handle_auth_error;
+use crate::middleware::{session::AuthenticatedUser, ClientIp, PathId, QuotaUsage};
+use crate::config::PaginationConfig;
+use crate::database::CampfireDatabase;
+use crate::models::{ExportId, PublicUser, User, UserId, WebSocketMessage};
+use crate::services::export::{ExportJob, ExportStatus};
+use crate::timezone::{parse_timezone, LocalTime, DEFAULT_TIMEZONE};
+use crate::validation::validate_batch_size;
+use crate::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateProfileRequest {
+    /// IANA timezone name, e.g. "Europe/Paris"
+    pub timezone: Option<String>,
+}
+
+/// The user's timezone annotation for responses carrying timestamps; a
+/// failed lookup falls back to UTC rather than failing the request
+pub(crate) async fn local_time(state: &AppState, user_id: UserId) -> LocalTime {
+    let timezone = state.auth_service.get_timezone(user_id).await.unwrap_or_else(|e| {
+        warn!("Failed to load timezone for user {}: {}", user_id, e);
+        DEFAULT_TIMEZONE
+    });
+    LocalTime::now(timezone)
+}
+
+fn profile_response(user: &User, local_time: LocalTime) -> Response {
+    // Create response with user data (excluding sensitive fields)
+    let user_response = json!({
+        "id": user.id,
+        "name": user.name,
+        "email": user.email,
+        "bio": user.bio,
+        "admin": user.admin,
+        "created_at": user.created_at,
+        "timezone": local_time.timezone,
+        "utc_offset": local_time.utc_offset,
+        // Exclude password_hash and bot_token for security
+    });
+    
+    (StatusCode::OK, Json(user_response)).into_response()
+}
 
 /// GET /api/users/me
 /// 
@@ -26,24 +74,439 @@ use crate::middleware::session::AuthenticatedUser;
 ///   "email": "user@example.com",
 ///   "bio": "Optional bio",
 ///   "admin": false,
-///   "created_at": "2023-01-01T00:00:00Z"
+///   "created_at": "2023-01-01T00:00:00Z",
+///   "timezone": "Europe/Paris",
+///   "utc_offset": "+02:00"
 /// }
 /// ```
 pub async fn get_current_user(
+    State(state): State<AppState>,
     auth_user: AuthenticatedUser,
 ) -> Response {
     info!("Fetching current user info for user: {}", auth_user.user.email);
     
-    // Create response with user data (excluding sensitive fields)
-    let user_response = json!({
-        "id": auth_user.user.id,
-        "name": auth_user.user.name,
-        "email": auth_user.user.email,
-        "bio": auth_user.user.bio,
-        "admin": auth_user.user.admin,
-        "created_at": auth_user.user.created_at,
-        // Exclude password_hash and bot_token for security
-    });
+    let local_time = local_time(&state, auth_user.user.id).await;
+    profile_response(&auth_user.user, local_time)
+}
+
+/// PATCH /api/users/me
+/// 
+/// Updates the current user's profile settings
+/// 
+/// # Request Body
+/// ```json
+/// {
+///   "timezone": "Europe/Paris"
+/// }
+/// ```
+/// 
+/// # Response
+/// - 200 OK: The updated profile, as returned by `GET /api/users/me`
+/// - 400 Bad Request: Unknown timezone
+/// - 401 Unauthorized: Invalid or missing session token
+pub async fn update_current_user(
+    State(state): State<AppState>,
+    auth_user: AuthenticatedUser,
+    Json(request): Json<UpdateProfileRequest>,
+) -> Response {
+    let timezone = match request.timezone {
+        Some(timezone) => match state.auth_service.set_timezone(auth_user.user.id, timezone).await {
+            Ok(timezone) => timezone,
+            Err(e) => return handle_auth_error(e, Some("update_profile")).into_response(),
+        },
+        None => match state.auth_service.get_timezone(auth_user.user.id).await {
+            Ok(timezone) => timezone,
+            Err(e) => return handle_auth_error(e, Some("update_profile")).into_response(),
+        },
+    };
     
-    (StatusCode::OK, Json(user_response)).into_response()
-}
\ No newline at end of file
+    info!("Updated profile for user {}", auth_user.user.id);
+    profile_response(&auth_user.user, LocalTime::now(timezone))
+}
+
+/// What any signed-in user may see about another
+pub(crate) fn public_profile(user: &User, last_seen_at: Option<DateTime<Utc>>) -> serde_json::Value {
+    json!({
+        "id": user.id,
+        "name": user.name,
+        "bio": user.bio,
+        "bot": user.bot_token.is_some(),
+        "created_at": user.created_at,
+        "last_seen_at": last_seen_at,
+    })
+}
+
+/// GET /api/users/:id
+/// 
+/// Returns another user's public profile, including when they were last
+/// seen (null if they have never connected)
+/// 
+/// # Response
+/// - 200 OK: The public profile
+/// - 400 Bad Request: Invalid user ID
+/// - 401 Unauthorized: Invalid or missing session token
+/// - 404 Not Found: No such user
+/// 
+/// # Response Body
+/// ```json
+/// {
+///   "id": "uuid",
+///   "name": "User Name",
+///   "bio": "Optional bio",
+///   "bot": false,
+///   "created_at": "2023-01-01T00:00:00Z",
+///   "last_seen_at": "2023-06-01T12:00:00Z"
+/// }
+/// ```
+pub async fn get_user_profile(
+    State(state): State<AppState>,
+    _auth_user: AuthenticatedUser,
+    PathId(user_id): PathId<UserId>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let user = state.db.get_user_by_id(user_id).await
+        .map_err(|e| {
+            warn!("Failed to load user {}: {}", user_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let last_seen_at = state.db.get_last_seen(user_id).await.map_err(|e| {
+        warn!("Failed to load last seen for user {}: {}", user_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    
+    Ok(Json(public_profile(&user, last_seen_at)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResolveUsersRequest {
+    pub user_ids: Vec<UserId>,
+}
+
+/// Public profiles of the requested users who share a room with `viewer`
+async fn resolve_public_users(
+    db: &CampfireDatabase,
+    pagination: &PaginationConfig,
+    viewer: UserId,
+    user_ids: &[UserId],
+) -> Result<Vec<PublicUser>, Response> {
+    validate_batch_size("user_ids", user_ids.len(), pagination).map_err(IntoResponse::into_response)?;
+    
+    db.get_public_users_sharing_room(viewer, user_ids).await.map_err(|e| {
+        warn!("Failed to resolve users for {}: {}", viewer, e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })
+}
+
+/// POST /api/users/resolve
+/// 
+/// Looks up many users at once, e.g. the authors of a page of messages.
+/// Only users who share a room with the requester are returned; other ids,
+/// and ids that don't exist, are left out.
+/// 
+/// # Request Body
+/// ```json
+/// { "user_ids": ["uuid", "uuid"] }
+/// ```
+/// 
+/// # Response
+/// - 200 OK: The users found
+/// - 400 Bad Request: More ids than the configured batch cap
+/// - 401 Unauthorized: Invalid or missing session token
+/// 
+/// # Response Body
+/// ```json
+/// {
+///   "users": [
+///     { "id": "uuid", "name": "User Name", "bio": null, "bot": false }
+///   ]
+/// }
+/// ```
+pub async fn resolve_users(
+    State(state): State<AppState>,
+    auth_user: AuthenticatedUser,
+    Json(request): Json<ResolveUsersRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let users = resolve_public_users(&state.db, &state.pagination, auth_user.user.id, &request.user_ids).await?;
+    Ok(Json(json!({ "users": users })))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ExportQuery {
+    /// IANA timezone for the archive's local timestamps; UTC by default
+    pub tz: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadExportQuery {
+    pub expires: i64,
+    pub signature: String,
+}
+
+fn export_job_response(job: ExportJob) -> Response {
+    let status = match job.status {
+        ExportStatus::Pending => StatusCode::ACCEPTED,
+        ExportStatus::Ready | ExportStatus::Failed => StatusCode::OK,
+    };
+    (status, Json(job)).into_response()
+}
+
+/// GET /api/users/me/export?tz=America/New_York
+/// 
+/// Starts an export of the current user's profile, memberships and authored
+/// messages, or returns the one already in progress. Timestamps are kept in
+/// UTC and also rendered in `tz`.
+/// 
+/// # Response
+/// - 202 Accepted: The export is being generated; poll its status
+/// - 200 OK: The export is ready, with a signed `download_url`
+/// - 400 Bad Request: Unknown timezone
+/// - 401 Unauthorized: Invalid or missing session token
+pub async fn export_current_user(
+    State(state): State<AppState>,
+    auth_user: AuthenticatedUser,
+    Query(query): Query<ExportQuery>,
+) -> Response {
+    let timezone = match query.tz {
+        Some(tz) => match parse_timezone(&tz) {
+            Some(timezone) => timezone,
+            None => {
+                return handle_auth_error(AuthError::InvalidTimezone { timezone: tz }, Some("export")).into_response()
+            }
+        },
+        None => DEFAULT_TIMEZONE,
+    };
+    export_job_response(state.exports.request(auth_user.user.id, timezone))
+}
+
+/// GET /api/users/me/quota
+/// 
+/// The current user's daily request quota: the limit, how much of it
+/// today's requests used, and when it resets. Reading it doesn't count.
+/// 
+/// # Response
+/// - 200 OK: `{ "limit", "used", "remaining", "resets_at" }`; a `limit` of 0
+///   (and `remaining` of null) means unlimited
+/// - 401 Unauthorized: Invalid or missing session token
+pub async fn get_my_quota(
+    State(state): State<AppState>,
+    auth_user: AuthenticatedUser,
+) -> Json<QuotaUsage> {
+    Json(state.daily_quota.usage(&auth_user.user, Utc::now()))
+}
+
+/// GET /api/users/me/export/:id
+/// 
+/// Reports the status of one of the current user's exports
+/// 
+/// # Response
+/// - 202 Accepted: Still being generated
+/// - 200 OK: Ready (with a signed `download_url`) or failed
+/// - 400 Bad Request: Malformed export id
+/// - 404 Not Found: No such export for this user
+pub async fn get_export_status(
+    State(state): State<AppState>,
+    auth_user: AuthenticatedUser,
+    PathId(ExportId(export_id)): PathId<ExportId>,
+) -> Response {
+    match state.exports.status(auth_user.user.id, export_id) {
+        Some(job) => export_job_response(job),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Export not found" })),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /api/exports/:id/download?expires=..&signature=..
+/// 
+/// Streams a finished export archive as newline-delimited JSON. The signed
+/// link is the credential, so no session is needed.
+/// 
+/// # Response
+/// - 200 OK: The archive
+/// - 400 Bad Request: Malformed export id
+/// - 403 Forbidden: Bad or expired link
+/// - 404 Not Found: Unknown export
+/// - 409 Conflict: Export not finished yet
+pub async fn download_export(
+    State(state): State<AppState>,
+    PathId(ExportId(export_id)): PathId<ExportId>,
+    Query(query): Query<DownloadExportQuery>,
+) -> Response {
+    match state.exports.open(export_id, query.expires, &query.signature).await {
+        Ok(stream) => (
+            [
+                (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"campfire-export-{}.ndjson\"", export_id),
+                ),
+            ],
+            StreamBody::new(stream),
+        )
+            .into_response(),
+        Err(e) => {
+            let status = match &e {
+                ExportError::InvalidLink => StatusCode::FORBIDDEN,
+                ExportError::NotFound { .. } => StatusCode::NOT_FOUND,
+                ExportError::NotReady { .. } => StatusCode::CONFLICT,
+                ExportError::Io(_) | ExportError::Database(_) => {
+                    warn!("Failed to open export {}: {}", export_id, e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+            };
+            (status, Json(json!({ "error": e.to_string() }))).into_response()
+        }
+    }
+}
+
+/// Audit record for an admin opening (or being refused) an impersonation
+/// session
+fn impersonation_audit_event(
+    admin_id: UserId,
+    target_id: UserId,
+    client_ip: IpAddr,
+    user_agent: &str,
+    outcome: Result<DateTime<Utc>, &AuthError>,
+) -> AuditEvent {
+    let event = AuditEvent::new(AuditAction::UserImpersonated, "user")
+        .with_user(admin_id)
+        .with_resource_id(target_id.to_string())
+        .with_ip_address(client_ip.to_string())
+        .with_user_agent(user_agent)
+        .with_detail("impersonated_user_id", target_id.to_string());
+
+    match outcome {
+        Ok(expires_at) => event.with_detail("expires_at", expires_at.to_rfc3339()),
+        Err(e) => event.with_error(e.to_string()),
+    }
+}
+
+/// POST /api/admin/users/:id/impersonate
+/// 
+/// Opens a short-lived session acting as the user, so support can see what
+/// they see. Only available when the impersonation feature is enabled.
+/// Every attempt is audit-logged, as is every mutating request made with the
+/// session. The user's open connections, and any connecting with the
+/// session, get an `Impersonation` frame so they can show a banner. The
+/// session can't log the user out everywhere or change their credentials.
+/// 
+/// # Response
+/// - 200 OK: `session_token`, `expires_at` and the `impersonation` banner
+/// - 403 Forbidden: Caller isn't an admin, or the target is an admin
+/// - 404 Not Found: No such user
+pub async fn impersonate_user(
+    State(state): State<AppState>,
+    auth_user: AuthenticatedUser,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
+    PathId(target_id): PathId<UserId>,
+) -> Response {
+    let admin_id = auth_user.user.id;
+    if !auth_user.user.admin {
+        warn!("Non-admin user {} attempted to impersonate user {}", admin_id, target_id);
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("unknown");
+
+    match state.auth_service.impersonate(admin_id, target_id).await {
+        Ok(session) => {
+            impersonation_audit_event(admin_id, target_id, client_ip, user_agent, Ok(session.expires_at)).log();
+            warn!("Admin {} is impersonating user {} until {}", admin_id, target_id, session.expires_at);
+            
+            // Clients already connected as the user won't see a connect-time
+            // frame, so tell them now
+            let notice = WebSocketMessage::Impersonation {
+                impersonated_by: admin_id,
+                expires_at: session.expires_at,
+            };
+            if let Err(e) = state.message_service.connection_manager().send_to_user(target_id, notice).await {
+                warn!("Failed to notify user {} of impersonation: {}", target_id, e);
+            }
+
+            Json(json!({
+                "session_token": session.token,
+                "user_id": session.user_id,
+                "expires_at": session.expires_at,
+                "impersonation": {
+                    "impersonated_by": admin_id,
+                    "expires_at": session.expires_at,
+                },
+            }))
+            .into_response()
+        }
+        Err(e) => {
+            impersonation_audit_event(admin_id, target_id, client_ip, user_agent, Err(&e)).log();
+            handle_auth_error(e, Some("impersonate")).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_impersonation_audit_event_records_admin_target_and_expiry() {
+        let admin_id = UserId::new();
+        let target_id = UserId::new();
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        let expires_at = Utc::now() + chrono::Duration::minutes(30);
+
+        let event = impersonation_audit_event(admin_id, target_id, ip, "support-console", Ok(expires_at));
+        assert!(matches!(event.action, AuditAction::UserImpersonated));
+        assert!(event.success);
+        assert_eq!(event.user_id, Some(admin_id));
+        assert_eq!(event.resource_id, Some(target_id.to_string()));
+        assert_eq!(event.ip_address.as_deref(), Some("203.0.113.7"));
+        assert_eq!(event.user_agent.as_deref(), Some("support-console"));
+        assert_eq!(event.details["impersonated_user_id"], target_id.to_string());
+        assert_eq!(event.details["expires_at"], expires_at.to_rfc3339());
+
+        let refused = AuthError::ImpersonationForbidden { user_id: target_id };
+        let event = impersonation_audit_event(admin_id, target_id, ip, "support-console", Err(&refused));
+        assert!(!event.success);
+        assert!(!event.details.contains_key("expires_at"));
+        assert_eq!(event.error_message, Some(refused.to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_users_returns_only_shared_room_users_within_cap() {
+        let db = CampfireDatabase::new(":memory:").await.unwrap();
+        let (viewer, colleague, stranger) =
+            (User::for_tests("Viewer"), User::for_tests("Colleague"), User::for_tests("Stranger"));
+        for user in [&viewer, &colleague, &stranger] {
+            db.create_user(user.clone()).await.unwrap();
+        }
+        
+        let room_id = crate::models::RoomId::new();
+        db.create_room(crate::models::Room {
+            id: room_id,
+            ..crate::models::Room::for_tests("Shared", crate::models::RoomType::Closed)
+        }).await.unwrap();
+        for user_id in [viewer.id, colleague.id] {
+            db.create_membership(crate::models::Membership {
+                room_id,
+                user_id,
+                involvement_level: crate::models::InvolvementLevel::Member,
+                created_at: Utc::now(),
+            }).await.unwrap();
+        }
+        
+        let pagination = PaginationConfig { max_batch_ids: 3, ..Default::default() };
+        let requested = [colleague.id, stranger.id, UserId::new()];
+        let users = resolve_public_users(&db, &pagination, viewer.id, &requested).await.unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].id, colleague.id);
+        assert_eq!(users[0].name, "Colleague");
+        
+        let too_many = [colleague.id, stranger.id, viewer.id, UserId::new()];
+        let response = resolve_public_users(&db, &pagination, viewer.id, &too_many).await.unwrap_err();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -4,23 +4,37 @@ use axum::{
         Query, State,
     },
     http::{HeaderMap, StatusCode},
-    response::IntoResponse,
+    response::{IntoResponse, Json},
 };
 use futures_util::{sink::SinkExt, stream::StreamExt};
 use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+use std::net::IpAddr;
+use std::sync::Arc;
 use tokio::sync::mpsc;
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
 use crate::{
-    errors::AuthError,
-    models::{ConnectionId, MessageId, UserId, WebSocketMessage},
+    errors::{AuthError, ConnectionError, DatabaseError},
+    handlers::messages::resolve_attachments,
+    logging::audit::{AuditAction, AuditEvent, AuditLogger},
+    middleware::{load_shedding::SHED_RETRY_AFTER_SECS, session::AuthenticatedUser, ClientIp, PathId},
+    models::{
+        ConnectionId, MessageId, UserId, WebSocketMessage, WelcomeRoom, WS_LEGACY_PROTOCOL_VERSION,
+        WS_PROTOCOL_VERSION,
+    },
     AppState,
 };
 
 #[derive(Debug, Deserialize)]
 pub struct WebSocketQuery {
     token: Option<String>,
+    /// Newest protocol version the client understands; absent for legacy clients
+    protocol: Option<u32>,
+    /// `acked_through` from the client's previous connection; numbered sends
+    /// on this one carry on after it
+    seq_base: Option<u64>,
 }
 
 /// Extract session token from headers (simplified version for WebSocket)
@@ -60,10 +74,16 @@ fn extract_session_token_from_headers(headers: &HeaderMap) -> Result<String, Aut
 /// 1. Query parameter: ?token=<session_token>
 /// 2. Authorization header: "Bearer <token>"
 /// 3. Cookie: "session_token=<token>"
+/// 
+/// Clients pass `?protocol=N` to advertise the protocol they speak; the
+/// connection uses the lower of that and [`WS_PROTOCOL_VERSION`]. A client
+/// reconnecting with unacknowledged sends passes `?seq_base=N`, the last
+/// `acked_through` it saw; see [`SendSequence`].
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
     Query(params): Query<WebSocketQuery>,
     State(state): State<AppState>,
+    client_ip: Option<ClientIp>,
     headers: HeaderMap,
 ) -> impl IntoResponse {
     // Extract session token from query params, headers, or cookies
@@ -80,8 +100,8 @@ pub async fn websocket_handler(
     };
 
     // Validate session and get user
-    let user = match state.auth_service.validate_session(token).await {
-        Ok(user) => user,
+    let (user, impersonated_by) = match state.auth_service.validate_session_with_impersonator(token.clone()).await {
+        Ok(resolved) => resolved,
         Err(_) => {
             return (StatusCode::UNAUTHORIZED, "Invalid session").into_response();
         }
@@ -89,12 +109,120 @@ pub async fn websocket_handler(
 
     info!("WebSocket connection authenticated for user: {}", user.id.0);
 
+    let protocol_version = params
+        .protocol
+        .unwrap_or(WS_LEGACY_PROTOCOL_VERSION)
+        .clamp(WS_LEGACY_PROTOCOL_VERSION, WS_PROTOCOL_VERSION);
+
+    let impersonation = match impersonated_by {
+        Some(admin_id) => impersonation_frame(&state, &token, admin_id).await,
+        None => None,
+    };
+
     // Upgrade the connection
-    ws.on_upgrade(move |socket| handle_websocket(socket, user.id, state))
+    let client_ip = client_ip.map(|ClientIp(ip)| ip);
+    let sequences = SendSequence::starting_after(params.seq_base.unwrap_or(0), state.ws_max_sequence_ahead);
+    let context = ConnectionContext {
+        user_id: user.id,
+        impersonated_by,
+        protocol_version,
+        client_ip,
+        impersonation,
+        sequences,
+    };
+    ws.on_upgrade(move |socket| handle_websocket(socket, context, state))
+}
+
+/// What the handshake settled about a connection before it was upgraded
+struct ConnectionContext {
+    user_id: UserId,
+    /// The admin, if this is an impersonation session
+    impersonated_by: Option<UserId>,
+    protocol_version: u32,
+    client_ip: Option<IpAddr>,
+    /// Banner frame sent first on impersonation sessions
+    impersonation: Option<WebSocketMessage>,
+    sequences: SendSequence,
+}
+
+/// Builds the banner frame for sessions an admin opened by impersonation
+async fn impersonation_frame(state: &AppState, token: &str, impersonated_by: UserId) -> Option<WebSocketMessage> {
+    let session = state.db.get_session(token).await.ok().flatten()?;
+
+    Some(WebSocketMessage::Impersonation {
+        impersonated_by,
+        expires_at: session.expires_at,
+    })
+}
+
+/// Audit record for a message an admin sends over an impersonation session
+fn impersonated_send_audit_event(
+    admin_id: UserId,
+    user_id: UserId,
+    room_id: crate::models::RoomId,
+    client_message_id: Uuid,
+) -> AuditEvent {
+    AuditEvent::new(AuditAction::ImpersonatedRequest, "websocket_frame")
+        .with_user(admin_id)
+        .with_resource_id(room_id.to_string())
+        .with_detail("impersonated_user_id", user_id.to_string())
+        .with_detail("frame", "CreateMessage")
+        .with_detail("client_message_id", client_message_id.to_string())
+}
+
+/// Builds the Capabilities frame sent when a connection opens
+async fn capabilities_frame(
+    state: &AppState,
+    user_id: UserId,
+    protocol_version: u32,
+) -> Result<WebSocketMessage, DatabaseError> {
+    Ok(WebSocketMessage::Capabilities {
+        protocol_version,
+        features: state.features.resolve_for_user(user_id).await?,
+    })
+}
+
+/// Builds the Welcome frame: capabilities plus unread counts and who's
+/// online for the user's most recently active rooms
+async fn welcome_frame(
+    state: &AppState,
+    user_id: UserId,
+    protocol_version: u32,
+) -> Result<WebSocketMessage, DatabaseError> {
+    let max_rooms = state.ws_welcome_max_rooms;
+    // One extra row tells us whether the list was cut short
+    let mut unread = state.db.get_room_unread_counts(user_id, max_rooms.saturating_add(1) as u32).await?;
+    let truncated = unread.len() > max_rooms;
+    unread.truncate(max_rooms);
+    
+    let connection_manager = state.message_service.connection_manager();
+    let mut rooms = Vec::with_capacity(unread.len());
+    for (room_id, unread_count) in unread {
+        let online_users = connection_manager.get_room_presence(room_id).await.unwrap_or_else(|e| {
+            warn!("Failed to get presence for room {}: {}", room_id.0, e);
+            Vec::new()
+        });
+        rooms.push(WelcomeRoom { room_id, unread_count, online_users });
+    }
+    
+    Ok(WebSocketMessage::Welcome {
+        protocol_version,
+        features: state.features.resolve_for_user(user_id).await?,
+        rooms,
+        truncated,
+    })
 }
 
 /// Handle individual WebSocket connection
-async fn handle_websocket(socket: WebSocket, user_id: UserId, state: AppState) {
+async fn handle_websocket(socket: WebSocket, context: ConnectionContext, state: AppState) {
+    let ConnectionContext {
+        user_id,
+        impersonated_by,
+        protocol_version,
+        client_ip,
+        impersonation,
+        mut sequences,
+    } = context;
     let connection_id = ConnectionId::new();
     
     info!("WebSocket connection established: {} for user: {}", 
@@ -116,19 +244,87 @@ async fn handle_websocket(socket: WebSocket, user_id: UserId, state: AppState) {
         error!("Failed to register WebSocket connection: {}", e);
         return;
     }
+    
+    if let Err(e) = state
+        .message_service
+        .connection_manager()
+        .set_protocol_version(connection_id, protocol_version)
+        .await
+    {
+        warn!("Failed to record protocol version for {}: {}", connection_id.0, e);
+    }
+    
+    if let Some(ip) = client_ip {
+        if let Err(e) = state
+            .message_service
+            .connection_manager()
+            .set_client_ip(connection_id, ip)
+            .await
+        {
+            warn!("Failed to record client address for {}: {}", connection_id.0, e);
+        }
+    }
+    
+    // Legacy clients don't know the Capabilities frame
+    if protocol_version >= WS_PROTOCOL_VERSION {
+        match capabilities_frame(&state, user_id, protocol_version).await {
+            Ok(frame) => {
+                if let Ok(serialized) = serde_json::to_string(&frame) {
+                    let _ = tx.send(serialized);
+                }
+            }
+            Err(e) => warn!("Failed to resolve capabilities for user {}: {}", user_id.0, e),
+        }
+        
+        if state.ws_welcome_max_rooms > 0 {
+            match welcome_frame(&state, user_id, protocol_version).await {
+                Ok(frame) => {
+                    if let Ok(serialized) = serde_json::to_string(&frame) {
+                        let _ = tx.send(serialized);
+                    }
+                }
+                Err(e) => warn!("Failed to build welcome frame for user {}: {}", user_id.0, e),
+            }
+        }
+        
+        if let Some(serialized) = impersonation.and_then(|frame| serde_json::to_string(&frame).ok()) {
+            let _ = tx.send(serialized);
+        }
+    }
+
+    // Move frames off the unbounded channel into a bounded buffer as they
+    // arrive, so a client that stops reading can't grow it without limit
+    let buffer = Arc::new(state.message_service.connection_manager().send_buffer());
+    let pump_buffer = buffer.clone();
+    let pump_task = tokio::spawn(async move {
+        while let Some(frame) = rx.recv().await {
+            if !pump_buffer.push(frame) {
+                return true;
+            }
+        }
+        pump_buffer.close();
+        false
+    });
 
     // Spawn task to handle outgoing messages
     let outgoing_task = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
+        while let Some(msg) = buffer.pop().await {
             if let Err(e) = sender.send(Message::Text(msg)).await {
                 warn!("Failed to send WebSocket message: {}", e);
-                break;
+                return;
             }
         }
+        // The ConnectionManager let go of the connection (logout-all,
+        // shutdown), so close it properly once everything queued is sent
+        let _ = sender.send(Message::Close(None)).await;
     });
 
-    // Handle incoming messages
+    // Handle incoming messages. Only a weak handle is kept here so that when the
+    // ConnectionManager drops the connection (e.g. logout-all), the outgoing
+    // channel closes and the socket shuts down.
     let state_clone = state.clone();
+    let weak_tx = tx.downgrade();
+    drop(tx);
     let incoming_task = tokio::spawn(async move {
         while let Some(msg) = receiver.next().await {
             match msg {
@@ -136,9 +332,15 @@ async fn handle_websocket(socket: WebSocket, user_id: UserId, state: AppState) {
                     if let Err(e) = handle_incoming_message(
                         &text, 
                         user_id, 
+                        impersonated_by,
                         connection_id, 
+                        &mut sequences,
                         &state_clone
                     ).await {
+                        if e.is::<SequenceOverflow>() {
+                            warn!("Closing WebSocket connection {}: {}", connection_id.0, e);
+                            break;
+                        }
                         error!("Error handling incoming WebSocket message: {}", e);
                     }
                 }
@@ -147,6 +349,7 @@ async fn handle_websocket(socket: WebSocket, user_id: UserId, state: AppState) {
                 }
                 Ok(Message::Ping(data)) => {
                     // Respond to ping with pong
+                    let Some(tx) = weak_tx.upgrade() else { break };
                     if let Err(e) = tx.send(format!("{{\"type\":\"pong\",\"data\":\"{}\"}}", 
                                                    base64::encode(&data))) {
                         warn!("Failed to send pong response: {}", e);
@@ -167,8 +370,15 @@ async fn handle_websocket(socket: WebSocket, user_id: UserId, state: AppState) {
         }
     });
 
-    // Wait for either task to complete
+    // Wait for either task to complete, or for the send buffer to overflow
+    // under the disconnect policy
+    let outgoing_abort = outgoing_task.abort_handle();
     tokio::select! {
+        Ok(true) = pump_task => {
+            // The writer may be stuck on a socket that isn't draining
+            outgoing_abort.abort();
+            warn!("Disconnected slow WebSocket client: {}", connection_id.0);
+        }
         _ = outgoing_task => {
             info!("Outgoing message task completed for connection: {}", connection_id.0);
         }
@@ -191,11 +401,195 @@ async fn handle_websocket(socket: WebSocket, user_id: UserId, state: AppState) {
           connection_id.0, user_id.0);
 }
 
+/// GET /api/admin/connections
+/// 
+/// Lists open WebSocket connections with their user, rooms, start time and
+/// client address (site admins only)
+/// 
+/// # Response
+/// - 200 OK: `{ "connections": [...] }`
+/// - 401 Unauthorized: Invalid or missing session token
+/// - 403 Forbidden: User is not an admin
+pub async fn list_connections(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    if !auth_user.user.admin {
+        warn!("Non-admin user {} attempted to list connections", auth_user.user.id);
+        return Err(StatusCode::FORBIDDEN);
+    }
+    
+    let connections = state.message_service.connection_manager().list_connections().await;
+    Ok(Json(serde_json::json!({ "connections": connections })))
+}
+
+/// DELETE /api/admin/connections/:id
+/// 
+/// Closes one WebSocket connection, e.g. a misbehaving client (site admins
+/// only). The client may reconnect unless its sessions are revoked too.
+/// 
+/// # Response
+/// - 204 No Content: Connection closed
+/// - 401 Unauthorized: Invalid or missing session token
+/// - 403 Forbidden: User is not an admin
+/// - 404 Not Found: No such connection
+pub async fn force_disconnect(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    PathId(connection_id): PathId<ConnectionId>,
+) -> Result<StatusCode, StatusCode> {
+    if !auth_user.user.admin {
+        warn!("Non-admin user {} attempted to close connection {}", auth_user.user.id, connection_id.0);
+        return Err(StatusCode::FORBIDDEN);
+    }
+    
+    let user_id = state
+        .message_service
+        .connection_manager()
+        .force_disconnect(connection_id)
+        .await
+        .map_err(|e| match e {
+            ConnectionError::NotFound { .. } => StatusCode::NOT_FOUND,
+            e => {
+                error!("Failed to close connection {}: {}", connection_id.0, e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+    
+    AuditLogger::new(true).log_user_action(
+        AuditAction::ConnectionTerminated,
+        auth_user.user.id,
+        "connection",
+        Some(connection_id.0.to_string()),
+        HashMap::from([("connection_user_id".to_string(), user_id.to_string())]),
+    );
+    
+    Ok(StatusCode::NO_CONTENT)
+}
+
+
+/// Sequence numbers of the sends settled on one connection
+///
+/// Clients may number their `CreateMessage` frames 1, 2, 3, ... Each stored
+/// send is acknowledged with the highest sequence below which nothing is
+/// missing, so after a reconnect the client replays everything past it;
+/// `client_message_id` dedup makes replaying a send that did arrive
+/// harmless. The client passes that `acked_through` as `?seq_base=` when it
+/// reconnects and numbering carries on from there; without it a connection
+/// starts again from 1.
+///
+/// A send that fails is answered with an Error frame carrying its `seq` and
+/// counts as settled too, so it doesn't hold back the acks after it; it's
+/// up to the client to send it again under a new number. Sends that arrive
+/// past a gap wait for it to fill, and a connection with more than
+/// `max_ahead` of them waiting is closed.
+#[derive(Debug)]
+struct SendSequence {
+    contiguous: u64,
+    /// Settled sequences past a gap, waiting for it to fill
+    ahead: BTreeSet<u64>,
+    /// Most sends that can wait on a gap before the connection is closed
+    max_ahead: usize,
+}
+
+impl Default for SendSequence {
+    fn default() -> Self {
+        Self::starting_after(0, 256)
+    }
+}
+
+/// Too many sends were waiting on a gap in the sequence
+#[derive(Debug)]
+struct SequenceOverflow {
+    max_ahead: usize,
+}
+
+impl std::fmt::Display for SequenceOverflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "more than {} sends waiting on a gap in the sequence", self.max_ahead)
+    }
+}
+
+impl std::error::Error for SequenceOverflow {}
+
+impl SendSequence {
+    /// Numbering that carries on after `base`, as acknowledged earlier
+    fn starting_after(base: u64, max_ahead: usize) -> Self {
+        Self { contiguous: base, ahead: BTreeSet::new(), max_ahead }
+    }
+
+    /// Records a settled send and returns the highest contiguous sequence
+    fn record(&mut self, seq: u64) -> Result<u64, SequenceOverflow> {
+        if seq > self.contiguous && !self.ahead.contains(&seq) {
+            // The send that fills the gap is always taken
+            if seq > self.contiguous + 1 && self.ahead.len() >= self.max_ahead {
+                return Err(SequenceOverflow { max_ahead: self.max_ahead });
+            }
+            self.ahead.insert(seq);
+        }
+        // The base comes from the client, so the numbering may already be
+        // at its end
+        while let Some(next) = self.contiguous.checked_add(1) {
+            if !self.ahead.remove(&next) {
+                break;
+            }
+            self.contiguous = next;
+        }
+        Ok(self.contiguous)
+    }
+}
+
+/// Queues a frame for the connection; best effort
+async fn send_frame(state: &AppState, connection_id: ConnectionId, frame: &OutgoingWebSocketMessage) {
+    if let Ok(serialized) = serde_json::to_string(frame) {
+        let _ = state
+            .message_service
+            .connection_manager()
+            .send_to_connection(connection_id, serialized)
+            .await;
+    }
+}
+
+/// Stores a message sent over the socket, with its attachments; errors are
+/// the code and reason to send back
+async fn create_from_socket(
+    state: &AppState,
+    user_id: UserId,
+    room_id: crate::models::RoomId,
+    content: String,
+    client_message_id: Uuid,
+    attachments: &[String],
+) -> Result<crate::models::Message, (&'static str, String)> {
+    let attachments = resolve_attachments(&state.blob_store, user_id, room_id, attachments)
+        .await
+        .map_err(|rejection| ("INVALID_ATTACHMENTS", rejection.reason()))?;
+
+    let message = state
+        .message_service
+        .create_message_with_deduplication(content, room_id, user_id, client_message_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to create message via WebSocket: {}", e);
+            ("MESSAGE_CREATION_FAILED", e.to_string())
+        })?;
+    info!("Message created via WebSocket: {}", message.id.0);
+
+    if !attachments.is_empty() {
+        state.db.link_message_attachments(message.id, attachments).await.map_err(|e| {
+            error!("Failed to link attachments to message {}: {}", message.id, e);
+            ("MESSAGE_CREATION_FAILED", e.to_string())
+        })?;
+    }
+    Ok(message)
+}
+
 /// Handle incoming WebSocket messages
 async fn handle_incoming_message(
     text: &str,
     user_id: UserId,
+    impersonated_by: Option<UserId>,
     connection_id: ConnectionId,
+    sequences: &mut SendSequence,
     state: &AppState,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Parse incoming message
@@ -205,39 +599,68 @@ async fn handle_incoming_message(
         IncomingWebSocketMessage::CreateMessage { 
             room_id, 
             content, 
-            client_message_id 
+            client_message_id,
+            seq,
+            attachments,
         } => {
-            // Create message through MessageService
-            match state
-                .message_service
-                .create_message_with_deduplication(
-                    content,
-                    room_id,
-                    user_id,
-                    client_message_id,
-                )
-                .await
-            {
+            // Stored or not, the send is settled; check the cap before storing
+            let acked_through = match seq.map(|seq| sequences.record(seq)).transpose() {
+                Ok(acked_through) => acked_through,
+                Err(overflow) => {
+                    let error_msg = OutgoingWebSocketMessage::Error {
+                        message: format!("Closing connection: {}", overflow),
+                        code: "SEQUENCE_GAP_TOO_LARGE".to_string(),
+                        seq,
+                        acked_through: Some(sequences.contiguous),
+                        retry_after_secs: None,
+                    };
+                    send_frame(state, connection_id, &error_msg).await;
+                    return Err(Box::new(overflow));
+                }
+            };
+
+            // Sends are writes, shed like HTTP ones while the writer is backed up
+            if state.write_shedder.as_ref().is_some_and(|shedder| shedder.is_overloaded()) {
+                metrics::counter!("writes_shed_total", 1);
+                let error_msg = OutgoingWebSocketMessage::Error {
+                    message: "The server is busy. Please try again in a few seconds.".to_string(),
+                    code: "OVERLOADED".to_string(),
+                    seq,
+                    acked_through,
+                    retry_after_secs: Some(SHED_RETRY_AFTER_SECS),
+                };
+                send_frame(state, connection_id, &error_msg).await;
+                return Ok(());
+            }
+
+            // Writes are recorded against the admin, as for HTTP requests
+            if let Some(admin_id) = impersonated_by {
+                impersonated_send_audit_event(admin_id, user_id, room_id, client_message_id).log();
+            }
+
+            let created = create_from_socket(state, user_id, room_id, content, client_message_id, &attachments).await;
+            match created {
+                // Unnumbered sends (older clients) aren't acknowledged
                 Ok(message) => {
-                    info!("Message created via WebSocket: {}", message.id.0);
+                    if let (Some(seq), Some(acked_through)) = (seq, acked_through) {
+                        let ack = OutgoingWebSocketMessage::Ack {
+                            seq,
+                            acked_through,
+                            client_message_id,
+                            message_id: message.id,
+                        };
+                        send_frame(state, connection_id, &ack).await;
+                    }
                 }
-                Err(e) => {
-                    error!("Failed to create message via WebSocket: {}", e);
-                    
-                    // Send error back to client
+                Err((code, reason)) => {
                     let error_msg = OutgoingWebSocketMessage::Error {
-                        message: format!("Failed to create message: {}", e),
-                        code: "MESSAGE_CREATION_FAILED".to_string(),
+                        message: format!("Failed to create message: {}", reason),
+                        code: code.to_string(),
+                        seq,
+                        acked_through,
+                        retry_after_secs: None,
                     };
-                    
-                    if let Ok(serialized) = serde_json::to_string(&error_msg) {
-                        // Try to send error back (best effort)
-                        let _ = state
-                            .message_service
-                            .connection_manager()
-                            .send_to_connection(connection_id, serialized)
-                            .await;
-                    }
+                    send_frame(state, connection_id, &error_msg).await;
                 }
             }
         }
@@ -251,6 +674,11 @@ async fn handle_incoming_message(
             {
                 warn!("Failed to update last seen message: {}", e);
             }
+            
+            // Persist it as the read marker so history can show the unread divider
+            if let Err(e) = state.message_service.mark_read(user_id, message_id).await {
+                warn!("Failed to update read marker: {}", e);
+            }
         }
         IncomingWebSocketMessage::JoinRoom { room_id } => {
             // Verify user has access to room
@@ -321,57 +749,51 @@ async fn handle_incoming_message(
             }
         }
         IncomingWebSocketMessage::StartTyping { room_id } => {
-            // Start typing indicator in connection manager
-            if let Err(e) = state
-                .message_service
-                .connection_manager()
-                .start_typing(user_id, room_id)
-                .await
-            {
-                warn!("Failed to start typing indicator: {}", e);
-            }
-            
-            // Send typing indicator to room
-            let typing_msg = WebSocketMessage::TypingStart {
-                user_id,
-                room_id,
-            };
-            
+            // Busy rooms get a TypingSummary rather than one frame per typer
             if let Err(e) = state
                 .message_service
                 .connection_manager()
-                .broadcast_to_room(room_id, typing_msg)
+                .broadcast_typing(user_id, room_id, true)
                 .await
             {
                 warn!("Failed to broadcast typing start: {}", e);
             }
         }
         IncomingWebSocketMessage::StopTyping { room_id } => {
-            // Stop typing indicator in connection manager
-            if let Err(e) = state
-                .message_service
-                .connection_manager()
-                .stop_typing(user_id, room_id)
-                .await
-            {
-                warn!("Failed to stop typing indicator: {}", e);
-            }
-            
-            // Send typing indicator to room
-            let typing_msg = WebSocketMessage::TypingStop {
-                user_id,
-                room_id,
-            };
-            
             if let Err(e) = state
                 .message_service
                 .connection_manager()
-                .broadcast_to_room(room_id, typing_msg)
+                .broadcast_typing(user_id, room_id, false)
                 .await
             {
                 warn!("Failed to broadcast typing stop: {}", e);
             }
         }
+        IncomingWebSocketMessage::Subscribe { room_ids } => {
+            let mut allowed = Vec::new();
+            let mut denied = Vec::new();
+            for room_id in room_ids {
+                match state.room_service.check_room_access(room_id, user_id).await {
+                    Ok(Some(_)) => allowed.push(room_id),
+                    _ => denied.push(room_id),
+                }
+            }
+            
+            let connection_manager = state.message_service.connection_manager();
+            connection_manager.subscribe_rooms(connection_id, &allowed).await?;
+            let reply = OutgoingWebSocketMessage::Subscribed { room_ids: allowed, denied };
+            connection_manager
+                .send_to_connection(connection_id, serde_json::to_string(&reply)?)
+                .await?;
+        }
+        IncomingWebSocketMessage::Unsubscribe { room_ids } => {
+            let connection_manager = state.message_service.connection_manager();
+            connection_manager.unsubscribe_rooms(connection_id, &room_ids).await?;
+            let reply = OutgoingWebSocketMessage::Unsubscribed { room_ids };
+            connection_manager
+                .send_to_connection(connection_id, serde_json::to_string(&reply)?)
+                .await?;
+        }
     }
 
     Ok(())
@@ -385,6 +807,12 @@ enum IncomingWebSocketMessage {
         room_id: crate::models::RoomId,
         content: String,
         client_message_id: Uuid,
+        /// Per-connection send number, starting at 1; see `SendSequence`
+        #[serde(default)]
+        seq: Option<u64>,
+        /// Keys of the sender's uploads, checked as for HTTP posts
+        #[serde(default)]
+        attachments: Vec<String>,
     },
     UpdateLastSeen {
         message_id: MessageId,
@@ -401,6 +829,14 @@ enum IncomingWebSocketMessage {
     StopTyping {
         room_id: crate::models::RoomId,
     },
+    /// Narrows this connection to the listed rooms (adding to earlier
+    /// subscriptions), so one connection can follow several rooms
+    Subscribe {
+        room_ids: Vec<crate::models::RoomId>,
+    },
+    Unsubscribe {
+        room_ids: Vec<crate::models::RoomId>,
+    },
 }
 
 /// Outgoing WebSocket message types (to client)
@@ -408,73 +844,59 @@ enum IncomingWebSocketMessage {
 #[serde(tag = "type")]
 #[allow(dead_code)]
 enum OutgoingWebSocketMessage {
+    /// `seq` and `acked_through` are set when a numbered send failed
     Error {
         message: String,
         code: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        seq: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        acked_through: Option<u64>,
+        /// Set when the same send can be retried after this many seconds
+        #[serde(skip_serializing_if = "Option::is_none")]
+        retry_after_secs: Option<u64>,
     },
     Pong {
         data: String,
     },
+    /// A numbered send was stored; every send up to `acked_through` has been
+    Ack {
+        seq: u64,
+        acked_through: u64,
+        client_message_id: Uuid,
+        message_id: MessageId,
+    },
+    /// Answers Subscribe: the rooms now followed, and any the user can't see
+    Subscribed {
+        room_ids: Vec<crate::models::RoomId>,
+        denied: Vec<crate::models::RoomId>,
+    },
+    Unsubscribed {
+        room_ids: Vec<crate::models::RoomId>,
+    },
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
-    use crate::{
-        database::CampfireDatabase,
-        models::RoomId,
-        services::{AuthService, RoomService, MessageService},
-        ConnectionManagerImpl,
-    };
+    use crate::{database::CampfireDatabase, models::RoomId};
     use std::sync::Arc;
     use tokio::time::{timeout, Duration};
 
-    async fn create_test_state() -> AppState {
-        let db = CampfireDatabase::new(":memory:").await.unwrap();
-        let db_arc = Arc::new(db.clone());
-        
-        let connection_manager = Arc::new(ConnectionManagerImpl::new(db_arc.clone()));
-        let auth_service = Arc::new(AuthService::new(db_arc.clone()));
-        let room_service = Arc::new(RoomService::new(db_arc.clone()));
-        let message_service = Arc::new(MessageService::new(
-            db_arc.clone(),
-            connection_manager,
-            room_service.clone(),
-        ));
-        let search_service = Arc::new(crate::SearchService::new(
-            db_arc.clone(),
-            room_service.clone(),
-        ));
-        
-        // Create push notification service for testing
-        let vapid_config = crate::VapidConfig::default();
-        let push_service = Arc::new(crate::PushNotificationServiceImpl::new(
-            db.clone(),
-            db.writer(),
-            vapid_config,
-        ));
-        
-        let bot_service = Arc::new(crate::BotServiceImpl::new(
-            db_arc.clone(),
-            db.writer(),
-            message_service.clone(),
-        ));
-        
-        let setup_service = Arc::new(crate::services::setup::SetupServiceImpl::new(db.clone()));
-        let demo_service = Arc::new(crate::services::demo::DemoServiceImpl::new(db_arc.clone()));
-        
-        AppState {
-            db,
-            auth_service,
-            room_service,
-            message_service,
-            search_service,
-            push_service,
-            bot_service,
-            setup_service,
-            demo_service,
-            analytics_store: Arc::new(crate::analytics::AnalyticsStore::new(100)),
-        }
+    pub(crate) async fn create_test_state() -> AppState {
+        AppState::for_tests(CampfireDatabase::new(":memory:").await.unwrap())
+    }
+
+    #[test]
+    fn test_impersonated_sends_are_audited_against_the_admin() {
+        let (admin_id, user_id, room_id) = (UserId::new(), UserId::new(), RoomId::new());
+        let client_message_id = Uuid::new_v4();
+
+        let event = impersonated_send_audit_event(admin_id, user_id, room_id, client_message_id);
+        assert_eq!(event.user_id, Some(admin_id));
+        assert_eq!(event.resource_id, Some(room_id.to_string()));
+        assert_eq!(event.details.get("impersonated_user_id"), Some(&user_id.to_string()));
+        assert_eq!(event.details.get("client_message_id"), Some(&client_message_id.to_string()));
     }
 
     #[tokio::test]
@@ -497,11 +919,16 @@ mod tests {
         let error_msg = OutgoingWebSocketMessage::Error {
             message: "Test error".to_string(),
             code: "TEST_ERROR".to_string(),
+            seq: None,
+            acked_through: None,
+            retry_after_secs: None,
         };
         
         let serialized = serde_json::to_string(&error_msg).unwrap();
         assert!(serialized.contains("Test error"));
         assert!(serialized.contains("TEST_ERROR"));
+        assert!(!serialized.contains("seq"));
+        assert!(!serialized.contains("retry_after_secs"));
     }
 
     #[tokio::test]
@@ -516,7 +943,7 @@ mod tests {
         // This should not panic or error
         let result = timeout(
             Duration::from_secs(1),
-            handle_incoming_message(msg, user_id, connection_id, &state)
+            handle_incoming_message(msg, user_id, None, connection_id, &mut SendSequence::default(), &state)
         ).await;
         
         assert!(result.is_ok());
@@ -537,7 +964,7 @@ mod tests {
         
         let result = timeout(
             Duration::from_secs(1),
-            handle_incoming_message(&start_msg, user_id, connection_id, &state)
+            handle_incoming_message(&start_msg, user_id, None, connection_id, &mut SendSequence::default(), &state)
         ).await;
         
         assert!(result.is_ok());
@@ -550,7 +977,7 @@ mod tests {
         
         let result = timeout(
             Duration::from_secs(1),
-            handle_incoming_message(&stop_msg, user_id, connection_id, &state)
+            handle_incoming_message(&stop_msg, user_id, None, connection_id, &mut SendSequence::default(), &state)
         ).await;
         
         assert!(result.is_ok());
@@ -571,7 +998,7 @@ mod tests {
         
         let result = timeout(
             Duration::from_secs(1),
-            handle_incoming_message(&join_msg, user_id, connection_id, &state)
+            handle_incoming_message(&join_msg, user_id, None, connection_id, &mut SendSequence::default(), &state)
         ).await;
         
         assert!(result.is_ok());
@@ -584,9 +1011,261 @@ mod tests {
         
         let result = timeout(
             Duration::from_secs(1),
-            handle_incoming_message(&leave_msg, user_id, connection_id, &state)
+            handle_incoming_message(&leave_msg, user_id, None, connection_id, &mut SendSequence::default(), &state)
         ).await;
         
         assert!(result.is_ok());
     }
-}
\ No newline at end of file
+    
+    #[tokio::test]
+    async fn test_capabilities_frame_reflects_feature_flags() {
+        let mut state = create_test_state().await;
+        let global = [("search".to_string(), true), ("sounds".to_string(), false)].into_iter().collect();
+        state.features = Arc::new(crate::services::features::FeatureFlags::new(Arc::new(state.db.clone()), global));
+        let user_id = UserId::new();
+        
+        let frame = capabilities_frame(&state, user_id, WS_PROTOCOL_VERSION).await.unwrap();
+        let json = serde_json::to_value(&frame).unwrap();
+        assert_eq!(json["type"], "Capabilities");
+        assert_eq!(json["protocol_version"], WS_PROTOCOL_VERSION);
+        assert_eq!(json["features"]["search"], true);
+        assert_eq!(json["features"]["sounds"], false);
+        
+        // Older clients never see it
+        assert!(frame.min_protocol_version() > WS_LEGACY_PROTOCOL_VERSION);
+    }
+    
+    #[tokio::test]
+    async fn test_welcome_frame_bundles_capabilities_unread_and_presence() {
+        let mut state = create_test_state().await;
+        state.ws_welcome_max_rooms = 1;
+        let reader = UserId::new();
+        let writer = UserId::new();
+        for (user_id, name) in [(reader, "Reader"), (writer, "Writer")] {
+            state.db.create_user(crate::models::User {
+                id: user_id,
+                ..crate::models::User::for_tests(name)
+            }).await.unwrap();
+        }
+        
+        let quiet_room = crate::models::RoomId::new();
+        let busy_room = crate::models::RoomId::new();
+        for (room_id, name) in [(quiet_room, "Quiet"), (busy_room, "Busy")] {
+            state.db.create_room(crate::models::Room {
+                id: room_id,
+                ..crate::models::Room::for_tests(name, crate::models::RoomType::Open)
+            }).await.unwrap();
+            for user_id in [reader, writer] {
+                state.db.create_membership(crate::models::Membership {
+                    room_id,
+                    user_id,
+                    involvement_level: crate::models::InvolvementLevel::Member,
+                    created_at: chrono::Utc::now(),
+                }).await.unwrap();
+            }
+        }
+        
+        for n in 0..2 {
+            let send = serde_json::json!({
+                "type": "CreateMessage",
+                "room_id": busy_room,
+                "content": format!("unread {}", n),
+                "client_message_id": Uuid::new_v4(),
+            })
+            .to_string();
+            handle_incoming_message(&send, writer, None, ConnectionId::new(), &mut SendSequence::default(), &state)
+                .await
+                .unwrap();
+        }
+        
+        let connection_manager = state.message_service.connection_manager();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        connection_manager.add_connection(writer, ConnectionId::new(), tx).await.unwrap();
+        connection_manager.join_room(busy_room, writer).await;
+        
+        let frame = welcome_frame(&state, reader, WS_PROTOCOL_VERSION).await.unwrap();
+        let json = serde_json::to_value(&frame).unwrap();
+        assert_eq!(json["type"], "Welcome");
+        assert_eq!(json["protocol_version"], WS_PROTOCOL_VERSION);
+        assert!(json["features"].is_object());
+        
+        // Only the most recently active room fits under the limit
+        assert_eq!(json["truncated"], true);
+        let rooms = json["rooms"].as_array().unwrap();
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(rooms[0]["room_id"], serde_json::json!(busy_room));
+        assert_eq!(rooms[0]["unread_count"], 2);
+        assert_eq!(rooms[0]["online_users"], serde_json::json!([writer]));
+        
+        assert!(frame.min_protocol_version() > WS_LEGACY_PROTOCOL_VERSION);
+    }
+    
+    #[test]
+    fn test_send_sequence_acks_highest_contiguous() {
+        let mut sequences = SendSequence::default();
+        assert_eq!(sequences.record(1).unwrap(), 1);
+        assert_eq!(sequences.record(3).unwrap(), 1);
+        assert_eq!(sequences.record(4).unwrap(), 1);
+        assert_eq!(sequences.record(2).unwrap(), 4);
+        // A replay of an already acknowledged send changes nothing
+        assert_eq!(sequences.record(2).unwrap(), 4);
+        
+        // Numbering can carry on from an earlier connection's ack
+        let mut resumed = SendSequence::starting_after(4, 256);
+        assert_eq!(resumed.record(5).unwrap(), 5);
+        
+        // A base at the end of the numbering doesn't overflow
+        let mut exhausted = SendSequence::starting_after(u64::MAX, 256);
+        assert_eq!(exhausted.record(5).unwrap(), u64::MAX);
+        let mut last = SendSequence::starting_after(u64::MAX - 1, 256);
+        assert_eq!(last.record(u64::MAX).unwrap(), u64::MAX);
+    }
+    
+    #[test]
+    fn test_send_sequence_caps_sends_waiting_on_a_gap() {
+        let max_ahead = 8;
+        let mut sequences = SendSequence::starting_after(0, max_ahead);
+        for seq in 2..2 + max_ahead as u64 {
+            assert_eq!(sequences.record(seq).unwrap(), 0);
+        }
+        // Repeats of waiting sends don't count again
+        assert!(sequences.record(2).is_ok());
+        assert!(sequences.record(2 + max_ahead as u64).is_err());
+        // Filling the gap drains them all
+        assert_eq!(sequences.record(1).unwrap(), 1 + max_ahead as u64);
+    }
+    
+    #[tokio::test]
+    async fn test_replaying_sequence_gap_after_reconnect_stores_no_duplicates() {
+        use sqlx::Row;
+        
+        let state = create_test_state().await;
+        let user_id = UserId::new();
+        let room_id = crate::models::RoomId::new();
+        state.db.create_user(crate::models::User {
+            id: user_id,
+            ..crate::models::User::for_tests("Sequencer")
+        }).await.unwrap();
+        state.db.create_room(crate::models::Room {
+            id: room_id,
+            ..crate::models::Room::for_tests("Sequenced", crate::models::RoomType::Open)
+        }).await.unwrap();
+        state.db.create_membership(crate::models::Membership {
+            room_id,
+            user_id,
+            involvement_level: crate::models::InvolvementLevel::Member,
+            created_at: chrono::Utc::now(),
+        }).await.unwrap();
+        
+        let client_ids: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+        let send = |seq: u64, content: &str| {
+            serde_json::json!({
+                "type": "CreateMessage",
+                "room_id": room_id,
+                "content": content,
+                "client_message_id": client_ids[seq as usize - 1],
+                "seq": seq,
+            })
+            .to_string()
+        };
+        // Each connection's frames, as the client would read them
+        let connect = || async {
+            let connection_id = ConnectionId::new();
+            let (tx, rx) = mpsc::unbounded_channel();
+            state
+                .message_service
+                .connection_manager()
+                .add_connection(user_id, connection_id, tx)
+                .await
+                .unwrap();
+            (connection_id, rx)
+        };
+        let next_frame = |rx: &mut mpsc::UnboundedReceiver<String>| {
+            let frame = rx.try_recv().expect("a frame was sent");
+            serde_json::from_str::<serde_json::Value>(&frame).unwrap()
+        };
+        
+        // Send 2 is lost in transit, so the acknowledgement stops at 1
+        let (connection_id, mut frames) = connect().await;
+        let mut first = SendSequence::default();
+        for seq in [1, 3] {
+            handle_incoming_message(&send(seq, &format!("message {}", seq)), user_id, None, connection_id, &mut first, &state)
+                .await
+                .unwrap();
+        }
+        let ack = next_frame(&mut frames);
+        assert_eq!((ack["type"].as_str(), ack["seq"].as_u64(), ack["acked_through"].as_u64()), (Some("Ack"), Some(1), Some(1)));
+        let ack = next_frame(&mut frames);
+        assert_eq!((ack["seq"].as_u64(), ack["acked_through"].as_u64()), (Some(3), Some(1)));
+        
+        // After reconnecting with that ack the client replays everything past it
+        let (connection_id, mut frames) = connect().await;
+        let mut second = SendSequence::starting_after(1, 256);
+        for seq in [2, 3] {
+            handle_incoming_message(&send(seq, &format!("message {}", seq)), user_id, None, connection_id, &mut second, &state)
+                .await
+                .unwrap();
+        }
+        let ack = next_frame(&mut frames);
+        assert_eq!((ack["seq"].as_u64(), ack["acked_through"].as_u64()), (Some(2), Some(2)));
+        let ack = next_frame(&mut frames);
+        assert_eq!((ack["seq"].as_u64(), ack["acked_through"].as_u64()), (Some(3), Some(3)));
+        
+        // A send that fails is answered with its seq and doesn't stall the acks
+        handle_incoming_message(&send(4, ""), user_id, None, connection_id, &mut second, &state)
+            .await
+            .unwrap();
+        let failed = next_frame(&mut frames);
+        assert_eq!(failed["type"], "Error");
+        assert_eq!((failed["seq"].as_u64(), failed["acked_through"].as_u64()), (Some(4), Some(4)));
+        
+        let stored: i64 = sqlx::query("SELECT COUNT(*) as count FROM messages WHERE room_id = ?")
+            .bind(room_id.0.to_string())
+            .fetch_one(state.db.pool())
+            .await
+            .unwrap()
+            .get("count");
+        assert_eq!(stored, 3);
+    }
+    
+    #[tokio::test]
+    async fn test_sends_are_shed_while_the_writer_is_overloaded() {
+        let mut state = create_test_state().await;
+        // No queue is too short for a threshold of 0
+        state.write_shedder = Some(Arc::new(crate::middleware::WriteLoadShedder::new(state.db.writer(), 0)));
+        let user_id = UserId::new();
+        let room_id = RoomId::new();
+        let connection_id = ConnectionId::new();
+        let (tx, mut frames) = mpsc::unbounded_channel();
+        state
+            .message_service
+            .connection_manager()
+            .add_connection(user_id, connection_id, tx)
+            .await
+            .unwrap();
+        
+        let send = serde_json::json!({
+            "type": "CreateMessage",
+            "room_id": room_id,
+            "content": "Busy?",
+            "client_message_id": Uuid::new_v4(),
+            "seq": 1,
+        })
+        .to_string();
+        handle_incoming_message(&send, user_id, None, connection_id, &mut SendSequence::default(), &state)
+            .await
+            .unwrap();
+        
+        let frame: serde_json::Value = serde_json::from_str(&frames.try_recv().unwrap()).unwrap();
+        assert_eq!(frame["type"], "Error");
+        assert_eq!(frame["code"], "OVERLOADED");
+        assert_eq!(frame["retry_after_secs"].as_u64(), Some(SHED_RETRY_AFTER_SECS));
+        assert_eq!((frame["seq"].as_u64(), frame["acked_through"].as_u64()), (Some(1), Some(1)));
+        
+        let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages")
+            .fetch_one(state.db.pool())
+            .await
+            .unwrap();
+        assert_eq!(stored, 0);
+    }
+}
@@ -8,6 +8,10 @@ use serde::{Deserialize, Serialize};
 use std::time::{Duration, Instant};
 use tracing::{error, warn};
 
+use crate::database::CampfireDatabase;
+use crate::middleware::{session::AuthenticatedUser, ClientIp};
+use crate::services::connection::ConnectionManager;
+use crate::services::push::PushNotificationService;
 use crate::AppState;
 
 /// Health check response structure
@@ -35,6 +39,7 @@ pub struct HealthChecks {
     pub database: CheckResult,
     pub memory: CheckResult,
     pub disk_space: CheckResult,
+    pub search_index: CheckResult,
 }
 
 /// Individual check result
@@ -71,6 +76,56 @@ pub struct ReadinessChecks {
     pub services: bool,
 }
 
+/// Operational snapshot of how close the server is to saturation
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HealthDetail {
+    pub timestamp: DateTime<Utc>,
+    pub uptime_seconds: u64,
+    /// Writes queued behind the one the database writer is running
+    pub writer_queue_depth: usize,
+    pub websocket_connections: usize,
+    pub db_pool: PoolUtilization,
+    pub push_in_flight: usize,
+}
+
+/// Read pool connections in use against the pool's ceiling
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PoolUtilization {
+    pub open: u32,
+    pub idle: usize,
+    pub max: u32,
+    /// Share of `max` currently checked out, from 0.0 to 1.0
+    pub utilization: f64,
+}
+
+impl HealthDetail {
+    pub async fn collect(
+        db: &CampfireDatabase,
+        connections: &dyn ConnectionManager,
+        push: &dyn PushNotificationService,
+    ) -> Self {
+        let pool = db.pool();
+        let open = pool.size();
+        let idle = pool.num_idle();
+        let max = crate::database::POOL_MAX_CONNECTIONS;
+        let in_use = (open as usize).saturating_sub(idle);
+        
+        Self {
+            timestamp: Utc::now(),
+            uptime_seconds: get_uptime_seconds(),
+            writer_queue_depth: db.writer().queue_depth(),
+            websocket_connections: connections.list_connections().await.len(),
+            db_pool: PoolUtilization {
+                open,
+                idle,
+                max,
+                utilization: in_use as f64 / max as f64,
+            },
+            push_in_flight: push.in_flight(),
+        }
+    }
+}
+
 /// Application startup time for uptime calculation
 static mut START_TIME: Option<Instant> = None;
 
@@ -98,12 +153,14 @@ pub async fn health_check(State(state): State<AppState>) -> Result<Json<HealthRe
     let database_check = check_database_health(&state).await;
     let memory_check = check_memory_health().await;
     let disk_check = check_disk_space_health().await;
+    let search_index_check = check_search_index_health(&state).await;
     
     // Determine overall status
     let overall_status = determine_overall_status(&[
         &database_check,
         &memory_check,
         &disk_check,
+        &search_index_check,
     ]);
     
     let response = HealthResponse {
@@ -115,6 +172,7 @@ pub async fn health_check(State(state): State<AppState>) -> Result<Json<HealthRe
             database: database_check,
             memory: memory_check,
             disk_space: disk_check,
+            search_index: search_index_check,
         },
     };
     
@@ -158,6 +216,32 @@ pub async fn readiness_check(State(state): State<AppState>) -> Result<Json<Readi
     Ok(Json(response))
 }
 
+/// Detailed operational snapshot for site admins and local tooling
+///
+/// Requests from a loopback address need no session; anyone else must be a
+/// site admin.
+pub async fn health_detail(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    auth_user: Option<AuthenticatedUser>,
+) -> Result<Json<HealthDetail>, StatusCode> {
+    let is_admin = auth_user.as_ref().is_some_and(|auth_user| auth_user.user.admin);
+    if !client_ip.is_loopback() && !is_admin {
+        return Err(match auth_user {
+            Some(_) => StatusCode::FORBIDDEN,
+            None => StatusCode::UNAUTHORIZED,
+        });
+    }
+    
+    let detail = HealthDetail::collect(
+        &state.db,
+        state.message_service.connection_manager().as_ref(),
+        state.push_service.as_ref(),
+    )
+    .await;
+    Ok(Json(detail))
+}
+
 /// Simple liveness check endpoint
 pub async fn liveness_check() -> StatusCode {
     // Basic liveness - if we can respond, we're alive
@@ -202,6 +286,44 @@ async fn check_database_health(state: &AppState) -> CheckResult {
     }
 }
 
+/// Check that the search index has a row for each message, by count; the
+/// full comparison is the admin search index check
+async fn check_search_index_health(state: &AppState) -> CheckResult {
+    let start = Instant::now();
+    
+    match state.db.check_search_index().await {
+        Ok(index) => {
+            let (status, message) = if index.is_consistent() {
+                (CheckStatus::Pass, "Search index in sync".to_string())
+            } else {
+                (
+                    CheckStatus::Warn,
+                    format!(
+                        "Search index out of sync ({} messages, {} indexed); run `campfire-on-rust reindex`",
+                        index.messages, index.indexed
+                    ),
+                )
+            };
+            
+            CheckResult {
+                status,
+                message,
+                duration_ms: start.elapsed().as_millis() as u64,
+                details: Some(serde_json::json!({
+                    "messages": index.messages,
+                    "indexed": index.indexed,
+                })),
+            }
+        }
+        Err(e) => CheckResult {
+            status: CheckStatus::Warn,
+            message: format!("Search index check failed: {}", e),
+            duration_ms: start.elapsed().as_millis() as u64,
+            details: None,
+        },
+    }
+}
+
 /// Check memory usage
 async fn check_memory_health() -> CheckResult {
     let start = Instant::now();
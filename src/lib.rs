@@ -11,10 +11,13 @@ pub mod validation;
 pub mod health;
 pub mod metrics;
 pub mod shutdown;
+pub mod events;
 pub mod config;
 pub mod logging;
 pub mod demo;
 pub mod analytics;
+pub mod storage;
+pub mod timezone;
 
 // L1 Core Testing Framework - Professional CI/CD Testing
 #[cfg(any(test, feature = "testing"))]
@@ -23,10 +26,10 @@ pub mod testing;
 pub use database::CampfireDatabase;
 pub use services::auth::{AuthService, AuthServiceTrait};
 pub use services::room::{RoomService, RoomServiceTrait};
-pub use services::message::{MessageService, MessageServiceTrait};
+pub use services::message::{MessageService, MessageServiceTrait, MessageRateLimiter};
 pub use services::connection::{ConnectionManager, ConnectionManagerImpl};
 pub use services::search::{SearchService, SearchServiceTrait};
-pub use services::push::{PushNotificationService, PushNotificationServiceImpl, VapidConfig};
+pub use services::push::{PushDispatcher, PushNotificationService, PushNotificationServiceImpl, VapidConfig};
 pub use services::bot::{BotService, BotServiceImpl};
 pub use services::setup::{SetupService, SetupServiceImpl};
 pub use services::demo::{DemoServiceTrait, DemoServiceImpl};
@@ -45,4 +48,74 @@ pub struct AppState {
     pub setup_service: Arc<dyn SetupService>,
     pub demo_service: Arc<dyn DemoServiceTrait>,
     pub analytics_store: Arc<analytics::AnalyticsStore>,
-}
\ No newline at end of file
+    pub blob_store: Arc<storage::QuotaBlobStore>,
+    pub features: Arc<services::features::FeatureFlags>,
+    pub room_webhooks: Arc<services::webhooks::RoomWebhookService>,
+    pub room_bridges: Arc<services::bridge::RoomBridgeService>,
+    pub exports: Arc<services::export::ExportService>,
+    pub scheduler: services::scheduler::Scheduler,
+    pub daily_quota: Arc<middleware::DailyQuota>,
+    /// Turns writes away while the database writer is backed up, if enabled
+    pub write_shedder: Option<Arc<middleware::WriteLoadShedder>>,
+    pub pagination: config::PaginationConfig,
+    /// Most rooms described in the Welcome frame (0 = don't send it)
+    pub ws_welcome_max_rooms: usize,
+    /// Numbered WebSocket sends that may wait on a gap before the
+    /// connection is closed
+    pub ws_max_sequence_ahead: usize,
+}
+impl AppState {
+    /// State over `db` with default services and every optional subsystem
+    /// at its default, for tests. Set what a test cares about with struct
+    /// update syntax, so tests keep compiling as fields are added.
+    #[doc(hidden)]
+    pub fn for_tests(db: CampfireDatabase) -> Self {
+        let db_arc = Arc::new(db.clone());
+        let connection_manager = Arc::new(ConnectionManagerImpl::new(db_arc.clone()));
+        let room_service = Arc::new(RoomService::with_connection_manager(db_arc.clone(), connection_manager.clone()));
+        let message_service = Arc::new(MessageService::new(
+            db_arc.clone(),
+            connection_manager,
+            room_service.clone(),
+        ));
+        let storage_dir = std::env::temp_dir().join("campfire-test-blobs");
+
+        Self {
+            auth_service: Arc::new(AuthService::new(db_arc.clone())),
+            search_service: Arc::new(SearchService::new(db_arc.clone(), room_service.clone())),
+            push_service: Arc::new(PushNotificationServiceImpl::new(
+                db.clone(),
+                db.writer(),
+                VapidConfig::default(),
+            )),
+            bot_service: Arc::new(BotServiceImpl::new(db_arc.clone(), db.writer(), message_service.clone())),
+            setup_service: Arc::new(SetupServiceImpl::new(db.clone())),
+            demo_service: Arc::new(DemoServiceImpl::new(db_arc.clone())),
+            analytics_store: Arc::new(analytics::AnalyticsStore::new(100)),
+            blob_store: Arc::new(storage::QuotaBlobStore::new(
+                Arc::new(storage::LocalBlobStore::new(storage_dir.clone())),
+                db_arc.clone(),
+                &config::StorageConfig {
+                    local_path: storage_dir,
+                    ..Default::default()
+                },
+            )),
+            features: Arc::new(services::features::FeatureFlags::new(db_arc.clone(), Default::default())),
+            room_webhooks: Arc::new(services::webhooks::RoomWebhookService::new(db_arc.clone())),
+            room_bridges: Arc::new(services::bridge::RoomBridgeService::new(db_arc.clone())),
+            exports: Arc::new(services::export::ExportService::new(
+                db_arc.clone(),
+                std::env::temp_dir().join("campfire-test-exports"),
+            )),
+            scheduler: Default::default(),
+            daily_quota: Arc::new(middleware::DailyQuota::new(0, 0, chrono_tz::Tz::UTC)),
+            write_shedder: None,
+            pagination: Default::default(),
+            ws_welcome_max_rooms: 50,
+            ws_max_sequence_ahead: 256,
+            db,
+            room_service,
+            message_service,
+        }
+    }
+}
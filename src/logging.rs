@@ -336,17 +336,116 @@ macro_rules! log_business_event {
 /// Request tracing middleware for structured HTTP logging
 pub mod middleware {
     use axum::{
-        extract::MatchedPath,
-        http::Request,
+        extract::{MatchedPath, State},
+        http::{HeaderValue, Request, StatusCode},
         middleware::Next,
         response::IntoResponse,
     };
-    use std::time::Instant;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
     use tracing::{info_span, Instrument};
     
-    /// Trace HTTP requests with structured logging
+    use super::error_handling::ResponseErrorCode;
+    use crate::config::LogSamplingConfig;
+    
+    pub const REQUEST_ID_HEADER: &str = "x-request-id";
+    
+    /// Identifies one request in logs and in the `X-Request-Id` response
+    /// header; available to handlers as a request extension
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct RequestId(pub String);
+    
+    impl RequestId {
+        /// Reuses an id set by a proxy in front of us when it looks like
+        /// one, so a request can be followed across services; anything else
+        /// gets a fresh id rather than putting client input into logs
+        pub fn from_header(value: Option<&HeaderValue>) -> Self {
+            value
+                .and_then(|value| value.to_str().ok())
+                .filter(|id| {
+                    !id.is_empty()
+                        && id.len() <= 64
+                        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+                })
+                .map(|id| Self(id.to_string()))
+                .unwrap_or_else(|| Self(uuid::Uuid::new_v4().to_string()))
+        }
+    }
+    
+    /// Code logged for an error response that didn't carry one, e.g.
+    /// NOT_FOUND for a bare 404
+    fn status_error_code(status: StatusCode) -> String {
+        status
+            .canonical_reason()
+            .unwrap_or("UNKNOWN")
+            .to_ascii_uppercase()
+            .replace([' ', '-'], "_")
+    }
+    
+    /// Decides which completed requests get a log line
+    ///
+    /// Successful requests on high-volume routes are logged at the configured
+    /// sample rate; errors (5xx) and slow requests are always logged.
+    #[derive(Debug)]
+    pub struct RequestLogSampler {
+        config: LogSamplingConfig,
+        sampled_seen: AtomicU64,
+    }
+    
+    impl RequestLogSampler {
+        pub fn new(config: LogSamplingConfig) -> Self {
+            Self {
+                config,
+                sampled_seen: AtomicU64::new(0),
+            }
+        }
+        
+        /// A prefix covers its own path and the ones below it, so `/static`
+        /// matches `/static/app.js` but not `/statistics`
+        fn is_high_volume(&self, path: &str) -> bool {
+            self.config.high_volume_prefixes.iter().any(|prefix| {
+                matches!(
+                    path.strip_prefix(prefix.trim_end_matches('/')),
+                    Some(rest) if rest.is_empty() || rest.starts_with('/')
+                )
+            })
+        }
+        
+        /// Returns true if a request with this outcome should be logged
+        pub fn should_log(&self, path: &str, status: StatusCode, duration: Duration) -> bool {
+            if status.is_server_error() {
+                return true;
+            }
+            if duration.as_millis() >= self.config.always_log_latency_ms as u128 {
+                return true;
+            }
+            if !self.is_high_volume(path) {
+                return true;
+            }
+            
+            // Deterministic sampling: log whenever the running count crosses
+            // the next multiple of 1/sample_rate
+            let rate = self.config.sample_rate.clamp(0.0, 1.0);
+            let n = self.sampled_seen.fetch_add(1, Ordering::Relaxed);
+            ((n + 1) as f64 * rate).floor() > (n as f64 * rate).floor()
+        }
+        
+        /// Returns true if a response with this status is logged as a
+        /// failure, with its error code
+        pub fn logs_as_error(&self, status: StatusCode) -> bool {
+            status.is_server_error() || (status.is_client_error() && self.config.log_client_errors)
+        }
+    }
+    
+    /// Trace HTTP requests with structured logging, sampling high-volume routes
+    ///
+    /// Every request gets a request id, recorded on its span so that
+    /// anything logged while handling it carries the id. Failed requests are
+    /// logged with their `error_code`.
     pub async fn trace_requests<B>(
-        request: Request<B>,
+        State(sampler): State<Arc<RequestLogSampler>>,
+        mut request: Request<B>,
         next: Next<B>,
     ) -> impl IntoResponse {
         let start = Instant::now();
@@ -357,27 +456,64 @@ pub mod middleware {
         let path = request
             .extensions()
             .get::<MatchedPath>()
-            .map(|p| p.as_str())
-            .unwrap_or(uri.path());
+            .map(|p| p.as_str().to_string())
+            .unwrap_or_else(|| uri.path().to_string());
+        
+        let client_ip = request
+            .extensions()
+            .get::<crate::middleware::ClientIp>()
+            .map(|ip| ip.0.to_string())
+            .unwrap_or_default();
+        
+        let request_id = RequestId::from_header(request.headers().get(REQUEST_ID_HEADER));
+        request.extensions_mut().insert(request_id.clone());
         
         let span = info_span!(
             "http_request",
+            request_id = %request_id.0,
             method = %method,
             path = %path,
             uri = %uri,
+            client_ip = %client_ip,
         );
         
         async move {
-            let response = next.run(request).await;
+            let mut response = next.run(request).await;
             let duration = start.elapsed();
             let status = response.status();
             
-            tracing::info!(
-                status = %status,
-                duration_ms = %duration.as_millis(),
-                "Request completed"
-            );
+            if sampler.logs_as_error(status) {
+                let error_code = response
+                    .extensions()
+                    .get::<ResponseErrorCode>()
+                    .map(|code| code.0.clone())
+                    .unwrap_or_else(|| status_error_code(status));
+                if status.is_server_error() {
+                    tracing::error!(
+                        status = %status,
+                        error_code = %error_code,
+                        duration_ms = %duration.as_millis(),
+                        "Request failed"
+                    );
+                } else {
+                    tracing::warn!(
+                        status = %status,
+                        error_code = %error_code,
+                        duration_ms = %duration.as_millis(),
+                        "Request failed"
+                    );
+                }
+            } else if sampler.should_log(&path, status, duration) {
+                tracing::info!(
+                    status = %status,
+                    duration_ms = %duration.as_millis(),
+                    "Request completed"
+                );
+            }
             
+            if let Ok(value) = HeaderValue::from_str(&request_id.0) {
+                response.headers_mut().insert(REQUEST_ID_HEADER, value);
+            }
             response
         }
         .instrument(span)
@@ -388,6 +524,7 @@ pub mod middleware {
 /// Error recovery and user-friendly error handling
 pub mod error_handling {
     use crate::errors::*;
+    use crate::middleware::rate_limiting::RateLimitStatus;
     use axum::{
         http::StatusCode,
         response::{IntoResponse, Json, Response},
@@ -395,6 +532,10 @@ pub mod error_handling {
     use serde_json::json;
     use tracing::{error, warn};
     
+    /// Response extension carrying the error code of a failed request, so
+    /// request tracing can log it
+    #[derive(Debug, Clone)]
+    pub struct ResponseErrorCode(pub String);
 
     /// User-friendly error messages with actionable guidance
     pub struct UserFriendlyError {
@@ -403,6 +544,10 @@ pub mod error_handling {
         pub status: StatusCode,
         pub recovery_suggestions: Vec<String>,
         pub support_info: Option<String>,
+        pub retry_after_secs: Option<u64>,
+        pub client_message_id: Option<uuid::Uuid>,
+        /// Sent as `X-RateLimit-*` headers
+        pub rate_limit: Option<RateLimitStatus>,
     }
 
     impl UserFriendlyError {
@@ -417,6 +562,9 @@ pub mod error_handling {
                 status,
                 recovery_suggestions: Vec::new(),
                 support_info: None,
+                retry_after_secs: None,
+                client_message_id: None,
+                rate_limit: None,
             }
         }
 
@@ -429,10 +577,39 @@ pub mod error_handling {
             self.support_info = Some(info.into());
             self
         }
+
+        pub fn with_retry_after(mut self, secs: u64) -> Self {
+            self.retry_after_secs = Some(secs);
+            self
+        }
+
+        pub fn with_rate_limit(mut self, status: Option<RateLimitStatus>) -> Self {
+            self.rate_limit = status;
+            self
+        }
+
+        /// Echoes the client's id so it can retry the same send safely
+        pub fn with_client_message_id(mut self, client_message_id: uuid::Uuid) -> Self {
+            self.client_message_id = Some(client_message_id);
+            self
+        }
     }
 
     impl IntoResponse for UserFriendlyError {
         fn into_response(self) -> Response {
+            let error_code = ResponseErrorCode(self.code.clone());
+            let rate_limit = self.rate_limit;
+            let mut response = self.into_response_body();
+            response.extensions_mut().insert(error_code);
+            if let Some(status) = rate_limit {
+                status.apply(response.headers_mut());
+            }
+            response
+        }
+    }
+    
+    impl UserFriendlyError {
+        fn into_response_body(self) -> Response {
             let mut response_body = json!({
                 "error": {
                     "message": self.message,
@@ -450,6 +627,19 @@ pub mod error_handling {
                 response_body["error"]["support_info"] = json!(support_info);
             }
 
+            if let Some(client_message_id) = self.client_message_id {
+                response_body["error"]["client_message_id"] = json!(client_message_id);
+            }
+
+            if let Some(secs) = self.retry_after_secs {
+                response_body["error"]["retry_after_secs"] = json!(secs);
+                return (
+                    self.status,
+                    [(axum::http::header::RETRY_AFTER, secs.to_string())],
+                    Json(response_body),
+                ).into_response();
+            }
+
             (self.status, Json(response_body)).into_response()
         }
     }
@@ -458,7 +648,7 @@ pub mod error_handling {
     pub fn handle_message_error(error: MessageError, _user_context: Option<&str>) -> UserFriendlyError {
         match error {
             MessageError::Authorization { user_id, room_id } => {
-                warn!("Authorization error: user {} attempted to access room {}", user_id, room_id);
+                warn!(error_code = error.code(), %user_id, %room_id, "User attempted to access a room they aren't in");
                 UserFriendlyError::new(
                     "You don't have permission to access this room",
                     "ROOM_ACCESS_DENIED",
@@ -490,7 +680,7 @@ pub mod error_handling {
                     "Make sure your message contains visible text".to_string(),
                 ])
             }
-            MessageError::RateLimit { limit, window } => {
+            MessageError::RateLimit { limit, window, retry_after_secs, status } => {
                 UserFriendlyError::new(
                     format!("You're sending messages too quickly. Limit: {} messages per {}", limit, window),
                     "RATE_LIMIT_EXCEEDED",
@@ -498,7 +688,7 @@ pub mod error_handling {
                 ).with_suggestions(vec![
                     "Wait a moment before sending another message".to_string(),
                     "Combine multiple thoughts into a single message".to_string(),
-                ])
+                ]).with_retry_after(retry_after_secs).with_rate_limit(status)
             }
             MessageError::NotFound { message_id: _ } => {
                 UserFriendlyError::new(
@@ -510,6 +700,67 @@ pub mod error_handling {
                     "Refresh the page to see the latest messages".to_string(),
                 ])
             }
+            MessageError::PostingRestricted { user_id, room_id } => {
+                warn!(error_code = error.code(), %user_id, %room_id, "User attempted to post in a restricted room");
+                UserFriendlyError::new(
+                    "Only admins can post in this room",
+                    "ROOM_POSTING_RESTRICTED",
+                    StatusCode::FORBIDDEN,
+                )
+            }
+            MessageError::SeenByUnavailable { limit } => {
+                UserFriendlyError::new(
+                    format!("Seen-by is only shown in rooms with up to {} members", limit),
+                    "SEEN_BY_UNAVAILABLE",
+                    StatusCode::BAD_REQUEST,
+                )
+            }
+            MessageError::SavedLimitReached { limit } => {
+                UserFriendlyError::new(
+                    format!("You can save up to {} messages", limit),
+                    "SAVED_MESSAGE_LIMIT_REACHED",
+                    StatusCode::CONFLICT,
+                ).with_suggestions(vec![
+                    "Remove a saved message you no longer need, then try again".to_string(),
+                ])
+            }
+            MessageError::Blocked { reason } => {
+                UserFriendlyError::new(
+                    reason.unwrap_or_else(|| "This message can't be posted".to_string()),
+                    "MESSAGE_BLOCKED",
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                ).with_suggestions(vec![
+                    "Remove anything that breaks the workspace's content policy and try again".to_string(),
+                ])
+            }
+            MessageError::UnknownCannedResponse { shortcut } => {
+                UserFriendlyError::new(
+                    format!("There's no canned response '{}' in this room", shortcut),
+                    "UNKNOWN_CANNED_RESPONSE",
+                    StatusCode::BAD_REQUEST,
+                ).with_suggestions(vec![
+                    "Check the room's canned responses for the right shortcut".to_string(),
+                ])
+            }
+            MessageError::ModerationUnavailable => {
+                UserFriendlyError::new(
+                    "Messages can't be checked right now, so they can't be posted",
+                    "MODERATION_UNAVAILABLE",
+                    StatusCode::SERVICE_UNAVAILABLE,
+                ).with_suggestions(vec![
+                    "Wait a moment and send it again".to_string(),
+                ])
+            }
+            MessageError::WriteTimeout { client_message_id } => {
+                warn!(error_code = error.code(), %client_message_id, "Timed out saving message");
+                UserFriendlyError::new(
+                    "Your message is taking longer than usual to save",
+                    "MESSAGE_WRITE_TIMEOUT",
+                    StatusCode::GATEWAY_TIMEOUT,
+                ).with_suggestions(vec![
+                    "Send it again; it won't be posted twice".to_string(),
+                ]).with_client_message_id(client_message_id)
+            }
             MessageError::InvalidContent { reason } => {
                 UserFriendlyError::new(
                     format!("Message content is invalid: {}", reason),
@@ -521,7 +772,7 @@ pub mod error_handling {
                 ])
             }
             MessageError::Database(_) | MessageError::Broadcast(_) => {
-                error!("Internal message error: {}", error);
+                error!(error_code = error.code(), error = %error, "Internal message error");
                 UserFriendlyError::new(
                     "We're experiencing technical difficulties. Please try again in a moment.",
                     "INTERNAL_ERROR",
@@ -591,19 +842,38 @@ pub mod error_handling {
                     "Check for typos in your email address".to_string(),
                 ])
             }
-            AuthError::WeakPassword => {
+            AuthError::InvalidName { reason } => {
                 UserFriendlyError::new(
-                    "Password must be at least 8 characters long",
-                    "WEAK_PASSWORD",
+                    format!("Please choose a different name: {}", reason),
+                    "INVALID_NAME",
+                    StatusCode::BAD_REQUEST,
+                )
+            }
+            AuthError::InvalidTimezone { timezone } => {
+                UserFriendlyError::new(
+                    format!("'{}' isn't a timezone we recognise", timezone),
+                    "INVALID_TIMEZONE",
                     StatusCode::BAD_REQUEST,
                 ).with_suggestions(vec![
-                    "Use at least 8 characters".to_string(),
-                    "Include a mix of letters, numbers, and symbols".to_string(),
-                    "Avoid common passwords or personal information".to_string(),
+                    "Use an IANA timezone name such as Europe/London or America/New_York".to_string(),
                 ])
             }
+            AuthError::WeakPassword { reasons } => {
+                UserFriendlyError::new(
+                    "Password doesn't meet the password policy",
+                    "WEAK_PASSWORD",
+                    StatusCode::BAD_REQUEST,
+                ).with_suggestions(reasons)
+            }
+            AuthError::ImpersonationForbidden { .. } => {
+                UserFriendlyError::new(
+                    "Admins can't be impersonated",
+                    "IMPERSONATION_FORBIDDEN",
+                    StatusCode::FORBIDDEN,
+                )
+            }
             AuthError::Database(_) | AuthError::PasswordHash(_) | AuthError::TokenGeneration => {
-                error!("Internal auth error: {}", error);
+                error!(error_code = error.code(), error = %error, "Internal auth error");
                 UserFriendlyError::new(
                     "We're experiencing technical difficulties. Please try again in a moment.",
                     "INTERNAL_ERROR",
@@ -631,7 +901,7 @@ pub mod error_handling {
                 ])
             }
             RoomError::NotAuthorized { user_id, room_id } => {
-                warn!("Room authorization error: user {} attempted to modify room {}", user_id, room_id);
+                warn!(error_code = error.code(), %user_id, %room_id, "User attempted to modify a room without permission");
                 UserFriendlyError::new(
                     "You don't have permission to perform this action in this room",
                     "ROOM_PERMISSION_DENIED",
@@ -651,6 +921,15 @@ pub mod error_handling {
                     "Navigate to the room to start chatting".to_string(),
                 ])
             }
+            RoomError::DirectRoomFull { room_id: _, max_members: _ } => {
+                UserFriendlyError::new(
+                    "Direct messages are between two people; no one else can be added",
+                    "DIRECT_ROOM_FULL",
+                    StatusCode::CONFLICT,
+                ).with_suggestions(vec![
+                    "Create a closed room to talk with more than one person".to_string(),
+                ])
+            }
             RoomError::InvalidName { reason } => {
                 UserFriendlyError::new(
                     format!("Invalid room name: {}", reason),
@@ -662,8 +941,87 @@ pub mod error_handling {
                     "Avoid special characters or emojis in room names".to_string(),
                 ])
             }
+            RoomError::InvalidTypeChange { room_id: _, reason } => {
+                UserFriendlyError::new(
+                    format!("This room's type can't be changed: {}", reason),
+                    "INVALID_ROOM_TYPE_CHANGE",
+                    StatusCode::BAD_REQUEST,
+                ).with_suggestions(vec![
+                    "Only open and closed rooms can be converted".to_string(),
+                ])
+            }
+            RoomError::NotOpen { room_id: _ } => {
+                UserFriendlyError::new(
+                    "Only open rooms can be made public",
+                    "ROOM_NOT_OPEN",
+                    StatusCode::BAD_REQUEST,
+                ).with_suggestions(vec![
+                    "Convert the room to an open room first".to_string(),
+                ])
+            }
+            RoomError::InvalidAttachmentType { value } => {
+                UserFriendlyError::new(
+                    format!("'{}' isn't a content type", value),
+                    "INVALID_ATTACHMENT_TYPE",
+                    StatusCode::BAD_REQUEST,
+                ).with_suggestions(vec![
+                    "Use MIME types like application/pdf, or image/* for any image".to_string(),
+                ])
+            }
+            RoomError::UnknownSound { name } => {
+                UserFriendlyError::new(
+                    format!("There's no sound called '{}'", name),
+                    "UNKNOWN_SOUND",
+                    StatusCode::BAD_REQUEST,
+                ).with_suggestions(vec![
+                    "See /api/sounds for the available sounds".to_string(),
+                ])
+            }
+            RoomError::InvalidCannedResponse { reason } => {
+                UserFriendlyError::new(
+                    format!("Invalid canned response: {}", reason),
+                    "INVALID_CANNED_RESPONSE",
+                    StatusCode::BAD_REQUEST,
+                ).with_suggestions(vec![
+                    "Shortcuts use lowercase letters, digits, '-' and '_'".to_string(),
+                ])
+            }
+            RoomError::CannedResponseNotFound { shortcut } => {
+                UserFriendlyError::new(
+                    format!("There's no canned response '{}' in this room", shortcut),
+                    "CANNED_RESPONSE_NOT_FOUND",
+                    StatusCode::NOT_FOUND,
+                )
+            }
+            RoomError::CannedResponseLimitReached { limit } => {
+                UserFriendlyError::new(
+                    format!("A room can have up to {} canned responses", limit),
+                    "CANNED_RESPONSE_LIMIT_REACHED",
+                    StatusCode::CONFLICT,
+                ).with_suggestions(vec![
+                    "Remove a canned response the room no longer uses, then try again".to_string(),
+                ])
+            }
+            RoomError::JoinRateLimit { limit, retry_after_secs } => {
+                UserFriendlyError::new(
+                    format!("You're joining rooms too quickly. Limit: {} rooms per minute", limit),
+                    "JOIN_RATE_LIMIT_EXCEEDED",
+                    StatusCode::TOO_MANY_REQUESTS,
+                ).with_suggestions(vec![
+                    "Wait a moment before joining another room".to_string(),
+                ]).with_retry_after(retry_after_secs)
+            }
+            RoomError::ConfirmationRequired { room_id: _, token: _ } => {
+                UserFriendlyError::new(
+                    "Deleting a room permanently removes all of its messages and can't be undone",
+                    "CONFIRMATION_REQUIRED",
+                    StatusCode::PRECONDITION_REQUIRED,
+                ).with_suggestions(vec![
+                    "Repeat the request with the confirmation token to go ahead".to_string(),
+                ])
+            }
             RoomError::Database(_) => {
-                error!("Internal room error: {}", error);
+                error!(error_code = error.code(), error = %error, "Internal room error");
                 UserFriendlyError::new(
                     "We're experiencing technical difficulties. Please try again in a moment.",
                     "INTERNAL_ERROR",
@@ -886,6 +1244,7 @@ pub mod audit {
         Logout,
         PasswordChange,
         SessionExpired,
+        ConnectionTerminated,
         
         // User management
         UserCreated,
@@ -893,6 +1252,8 @@ pub mod audit {
         UserDeleted,
         UserPromoted,
         UserDemoted,
+        UserImpersonated,
+        ImpersonatedRequest,
         
         // Room management
         RoomCreated,
@@ -901,6 +1262,7 @@ pub mod audit {
         RoomMemberAdded,
         RoomMemberRemoved,
         RoomPermissionChanged,
+        RoomRetentionChanged,
         
         // Message actions
         MessageCreated,
@@ -913,6 +1275,7 @@ pub mod audit {
         BotUpdated,
         BotDeleted,
         BotTokenReset,
+        BotPostedOnBehalf,
         
         // System administration
         SystemConfigChanged,
@@ -1316,6 +1679,143 @@ mod tests {
         assert!(filter_str.contains("debug") || filter_str.contains("DEBUG") || filter_str.len() > 0);
     }
     
+    fn sampler(rate: f64) -> middleware::RequestLogSampler {
+        middleware::RequestLogSampler::new(crate::config::LogSamplingConfig {
+            sample_rate: rate,
+            always_log_latency_ms: 500,
+            high_volume_prefixes: vec!["/health".to_string(), "/static".to_string()],
+            log_client_errors: true,
+        })
+    }
+    
+    #[test]
+    fn test_health_requests_are_sampled() {
+        use axum::http::StatusCode;
+        use std::time::Duration;
+        
+        let sampler = sampler(0.1);
+        let logged = (0..100)
+            .filter(|_| sampler.should_log("/health", StatusCode::OK, Duration::from_millis(1)))
+            .count();
+        assert_eq!(logged, 10);
+        
+        // Regular API routes are never sampled
+        assert!(sampler.should_log("/api/rooms", StatusCode::OK, Duration::from_millis(1)));
+    }
+    
+    #[test]
+    fn test_errors_and_slow_requests_always_logged() {
+        use axum::http::StatusCode;
+        use std::time::Duration;
+        
+        let sampler = sampler(0.0);
+        assert!(!sampler.should_log("/health", StatusCode::OK, Duration::from_millis(1)));
+        assert!(sampler.should_log("/health", StatusCode::INTERNAL_SERVER_ERROR, Duration::from_millis(1)));
+        assert!(sampler.should_log("/static/app.js", StatusCode::OK, Duration::from_millis(750)));
+    }
+    
+    #[test]
+    fn test_prefixes_match_whole_path_segments() {
+        use axum::http::StatusCode;
+        use std::time::Duration;
+        
+        let sampler = sampler(0.0);
+        assert!(!sampler.should_log("/static", StatusCode::OK, Duration::from_millis(1)));
+        assert!(!sampler.should_log("/static/css/app.css", StatusCode::OK, Duration::from_millis(1)));
+        
+        // Routes that merely share the prefix's characters are not sampled
+        assert!(sampler.should_log("/statistics", StatusCode::OK, Duration::from_millis(1)));
+        assert!(sampler.should_log("/healthz-admin", StatusCode::OK, Duration::from_millis(1)));
+    }
+    
+    /// Collects formatted log output for assertions
+    #[derive(Clone, Default)]
+    struct CapturedLogs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+    
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_failed_requests_log_error_code_and_request_id() {
+        use axum::{body::Body, http::Request, routing::get, Router};
+        use std::sync::Arc;
+        use tower::ServiceExt;
+        use crate::errors::RoomError;
+        use crate::models::RoomId;
+        
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+        
+        let app = Router::new()
+            .route(
+                "/api/rooms/missing",
+                get(|| async {
+                    error_handling::handle_room_error(RoomError::NotFound { room_id: RoomId::new() }, None)
+                }),
+            )
+            .route(
+                "/api/rooms/broken",
+                get(|| async {
+                    let error = RoomError::Database(sqlx::Error::PoolTimedOut);
+                    error_handling::handle_room_error(error, None)
+                }),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                Arc::new(sampler(1.0)),
+                middleware::trace_requests,
+            ));
+        
+        let response = app.clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/rooms/missing")
+                    .header(middleware::REQUEST_ID_HEADER, "req-42")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[middleware::REQUEST_ID_HEADER], "req-42");
+        
+        // Ids that don't look like ids are replaced
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/rooms/broken")
+                    .header(middleware::REQUEST_ID_HEADER, "not/an id;")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let generated = response.headers()[middleware::REQUEST_ID_HEADER].to_str().unwrap().to_string();
+        assert!(uuid::Uuid::parse_str(&generated).is_ok());
+        
+        let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let missing = output.lines().find(|line| line.contains("ROOM_NOT_FOUND")).unwrap();
+        assert!(missing.contains("request_id=req-42"));
+        assert!(missing.contains("WARN"));
+        
+        // Internal errors log their specific code inside the request's span,
+        // while the client only sees INTERNAL_ERROR
+        let broken = output.lines().find(|line| line.contains("error_code=\"ROOM_DATABASE\"")).unwrap();
+        assert!(broken.contains(&format!("request_id={}", generated)));
+        assert!(output.lines().any(|line| line.contains("error_code=INTERNAL_ERROR") && line.contains("ERROR")));
+    }
+    
     #[test]
     fn test_log_rotation() {
         use std::fs::File;
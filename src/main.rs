@@ -9,11 +9,16 @@ use std::time::Duration;
 use tracing::{error, info, warn};
 
 use campfire_on_rust::{
-    AppState, CampfireDatabase, AuthService, RoomService, MessageService, 
-    ConnectionManagerImpl, SearchService, PushNotificationServiceImpl, 
-    VapidConfig, BotServiceImpl, SetupServiceImpl, health, metrics, shutdown, config, logging, demo
+    AppState, CampfireDatabase, AuthService, RoomService, MessageService, MessageRateLimiter,
+    ConnectionManagerImpl, SearchService, PushDispatcher, PushNotificationServiceImpl, 
+    VapidConfig, BotServiceImpl, SetupService, SetupServiceImpl, health, metrics, shutdown, config, logging, demo
 };
-use campfire_on_rust::middleware::{security, RateLimitConfig};
+use campfire_on_rust::middleware::{security, client_ip_middleware, daily_quota_middleware, json_limits_middleware, rate_limiting_middleware, request_timeout_middleware, write_load_shedding_middleware, ws_origin_middleware, DailyQuota, JsonLimits, RateLimitConfig, RateLimitingMiddleware, RequestTimeouts, TrustedProxies, WriteLoadShedder, WsOriginPolicy};
+use campfire_on_rust::services::features::FeatureFlags;
+use campfire_on_rust::errors::{DatabaseError, RoomError};
+use campfire_on_rust::services::{ModerationGate, RoomBridgeService, RoomWebhookService, Scheduler, TokenService, WebhookUrlPolicy};
+use campfire_on_rust::services::mailer::{LogMailer, RoomAddedEmailSubscriber};
+use campfire_on_rust::rich_text::Pipeline;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -31,6 +36,12 @@ async fn main() -> Result<()> {
     // Initialize structured logging
     logging::init_logging(&config)?;
 
+    // from_env already refused any configuration errors; surface the warnings
+    // now that there's somewhere to log them
+    for diagnostic in config.validate() {
+        warn!("Configuration: {}", diagnostic);
+    }
+
     info!(
         version = env!("CARGO_PKG_VERSION"),
         host = %config.server.bind_address.ip(),
@@ -43,7 +54,7 @@ async fn main() -> Result<()> {
 
     // Initialize metrics system if enabled
     if config.metrics.enabled {
-        if let Err(e) = metrics::init_metrics() {
+        if let Err(e) = metrics::init_metrics_with_buckets(&config.metrics.response_time_buckets) {
             error!("Failed to initialize metrics: {}", e);
             // Continue without metrics rather than failing
         }
@@ -74,9 +85,48 @@ async fn main() -> Result<()> {
     }
 
     // Initialize database with configuration
-    let db = CampfireDatabase::new(&config.database.database_url).await?;
+    let mut db = CampfireDatabase::new(&config.database.database_url).await?;
+    if let Some(replica_url) = &config.database.replica_url {
+        db = db
+            .with_read_replica(
+                replica_url,
+                config.database.replica_max_connections,
+                Duration::from_secs(config.database.replica_read_your_writes_secs),
+            )
+            .await?;
+        info!("Serving read-heavy queries from replica {}", replica_url);
+    }
     let db_arc = Arc::new(db.clone());
     
+    // `campfire-on-rust reindex` rebuilds the search index from messages and exits
+    if std::env::args().nth(1).as_deref() == Some("reindex") {
+        let indexed = db.rebuild_search_index().await?;
+        info!("Search index rebuilt: {} messages indexed", indexed);
+        return Ok(());
+    }
+    
+    // `campfire-on-rust vacuum` compacts the database file and exits
+    if std::env::args().nth(1).as_deref() == Some("vacuum") {
+        warn!("Vacuuming locks the database until the file has been rewritten; large databases can take minutes");
+        let report = db.vacuum().await?;
+        info!(
+            "Database vacuumed: {} -> {} bytes ({} reclaimed)",
+            report.bytes_before, report.bytes_after, report.reclaimed_bytes()
+        );
+        return Ok(());
+    }
+    
+    if config.database.check_search_index_on_startup {
+        match db.check_search_index().await {
+            Ok(index) if !index.is_consistent() => warn!(
+                "Search index out of sync: {} messages, {} indexed; run `campfire-on-rust reindex`",
+                index.messages, index.indexed
+            ),
+            Ok(_) => {}
+            Err(e) => warn!("Search index check failed: {}", e),
+        }
+    }
+    
     // Initialize demo data if demo mode is enabled
     if config.features.demo_mode {
         let demo_initializer = demo::DemoDataInitializer::new(db_arc.clone());
@@ -86,12 +136,103 @@ async fn main() -> Result<()> {
         }
     }
     
+    // Recurring background jobs, listed at /api/admin/jobs
+    let scheduler = Scheduler::new();
+    
+    // Purge messages past the retention window (this also releases their dedup keys).
+    // Runs even with global retention off, since rooms can set their own
+    let retention_db = db_arc.clone();
+    let default_retention_days = config.database.message_retention_days;
+    scheduler.every(
+        "message_retention_purge",
+        Duration::from_secs(config.database.retention_purge_interval_secs),
+        move || {
+            let retention_db = retention_db.clone();
+            async move {
+                let purged = retention_db
+                    .purge_expired_messages(default_retention_days, chrono::Utc::now())
+                    .await?;
+                if purged > 0 {
+                    info!("Retention purged {} messages", purged);
+                }
+                Ok::<_, DatabaseError>(())
+            }
+        },
+    );
+    
+    // Drop reset, verification and invite tokens once they've expired or been used
+    let token_service = TokenService::new(db_arc.clone(), config.security.token_lifetimes.clone());
+    scheduler.every(
+        "expired_token_cleanup",
+        Duration::from_secs(config.security.token_cleanup_interval_secs),
+        move || {
+            let token_service = token_service.clone();
+            async move {
+                let purged = token_service.purge_expired().await?;
+                if purged > 0 {
+                    info!("Purged {} expired or used tokens", purged);
+                }
+                Ok::<_, DatabaseError>(())
+            }
+        },
+    );
+    
     // Initialize connection manager
-    let connection_manager = Arc::new(ConnectionManagerImpl::new(db_arc.clone()));
+    let connection_manager = Arc::new(
+        ConnectionManagerImpl::new(db_arc.clone())
+            .with_send_buffer(config.server.ws_send_buffer_size, config.server.ws_send_buffer_overflow)
+            .with_typing_broadcast_limit(config.server.ws_typing_broadcast_limit)
+            .with_presence_timeout(Duration::from_secs(config.server.ws_presence_timeout_secs))
+            .with_last_seen_interval(Duration::from_secs(config.server.last_seen_write_interval_secs)),
+    );
+    let connection_manager_for_shutdown = connection_manager.clone();
     
     // Initialize services
-    let auth_service = Arc::new(AuthService::new(db_arc.clone()));
-    let room_service = Arc::new(RoomService::new(db_arc.clone()));
+    let mut auth_service = AuthService::new(db_arc.clone())
+        .with_password_policy(config.security.password_policy.clone())
+        .with_text_limits(config.messages.text_limits)
+        .with_impersonation_ttl(Duration::from_secs(config.security.impersonation_session_mins * 60));
+    if config.security.session_idle_timeout_mins > 0 {
+        auth_service = auth_service.with_idle_timeout(
+            Duration::from_secs(config.security.session_idle_timeout_mins * 60),
+        );
+    }
+    let auth_service = Arc::new(auth_service);
+    let room_service = Arc::new(
+        RoomService::with_connection_manager(db_arc.clone(), connection_manager.clone())
+            .with_preview_length(config.messages.room_preview_length)
+            .with_mention_autocomplete(config.messages.mention_autocomplete_limit, config.messages.room_mentions)
+            .with_text_limits(config.messages.text_limits)
+            .with_join_rate_limit(config.security.room_join_rate_per_minute)
+            .with_canned_response_limit(config.messages.max_canned_responses),
+    );
+    // Tell people added to closed rooms, unless it's the demo's sample data
+    if config.mail.enabled && config.mail.room_added && !config.features.demo_mode {
+        let mailer = Arc::new(LogMailer::new(config.mail.from_address.clone()));
+        room_service.event_bus().subscribe(Arc::new(
+            RoomAddedEmailSubscriber::new(db_arc.clone(), mailer, config.mail.base_url.clone()),
+        ));
+    }
+    
+    // Archive rooms that have gone quiet
+    if config.messages.room_auto_archive_days > 0 {
+        let archive_room_service = room_service.clone();
+        let idle_for = chrono::Duration::days(config.messages.room_auto_archive_days as i64);
+        scheduler.every(
+            "room_auto_archive",
+            Duration::from_secs(config.messages.room_auto_archive_interval_secs),
+            move || {
+                let archive_room_service = archive_room_service.clone();
+                async move {
+                    let archived = archive_room_service.archive_inactive_rooms(idle_for).await?;
+                    if !archived.is_empty() {
+                        info!("Auto-archived {} inactive rooms", archived.len());
+                    }
+                    Ok::<_, RoomError>(())
+                }
+            },
+        );
+    }
     
     // Initialize push notification service with configuration
     let vapid_config = if config.push.enabled {
@@ -108,30 +249,100 @@ async fn main() -> Result<()> {
         db.clone(),
         db.writer(),
         vapid_config,
-    ));
+    )
+    .with_dispatcher(PushDispatcher::from_config(&config.push))
+    .with_max_mentions(config.messages.max_mentions)
+    .with_sounds(config.push.sounds.clone()));
     
     // Initialize message service with push notifications
-    let message_service = Arc::new(MessageService::with_push_service(
+    let mut message_service = MessageService::with_push_service(
         db_arc.clone(), 
-        connection_manager,
+        connection_manager.clone(),
         room_service.clone(),
         push_service.clone(),
+    )
+    .with_max_page_size(config.pagination.max_limit)
+    .with_seen_by_max_members(config.messages.seen_by_max_members)
+    .with_saved_message_limit(config.messages.max_saved_messages)
+    .with_mention_limit(config.messages.max_mentions, config.messages.reject_excess_mentions)
+    .with_sound_cooldown(Duration::from_secs(config.messages.sound_cooldown_secs))
+    .with_write_timeout(Duration::from_millis(config.messages.write_timeout_ms))
+    .with_duplicate_window(Duration::from_secs(config.messages.duplicate_window_secs))
+    .with_pipeline(Pipeline::from_names(&config.messages.content_pipeline)?);
+    if let Some(moderation) = &config.messages.moderation {
+        message_service = message_service.with_moderation(ModerationGate::from_config(moderation));
+    }
+    if config.security.message_rate_per_minute > 0 {
+        message_service = message_service.with_rate_limiter(
+            MessageRateLimiter::new(config.security.message_rate_per_minute, Duration::from_secs(60))
+                .with_exemptions(
+                    config.security.message_rate_exempt_bots,
+                    config.security.message_rate_exempt_admins,
+                )
+                .with_headers(config.security.rate_limit_headers),
+        );
+    }
+    
+    // Room webhooks hear about every new message
+    let webhook_url_policy = WebhookUrlPolicy::from_config(&config.security);
+    let room_webhooks = Arc::new(
+        RoomWebhookService::new(db_arc.clone()).with_url_policy(webhook_url_policy.clone()),
+    );
+    message_service.event_bus().subscribe(room_webhooks.clone());
+    // Bridged rooms are mirrored to their external chat
+    let room_bridges = Arc::new(
+        RoomBridgeService::new(db_arc.clone()).with_url_policy(webhook_url_policy.clone()),
+    );
+    message_service.event_bus().subscribe(room_bridges.clone());
+    // Bots that allow it join rooms their admins mention them in
+    message_service.event_bus().subscribe(Arc::new(
+        campfire_on_rust::services::bot::BotAutoJoinSubscriber::new(
+            db_arc.clone(),
+            room_service.clone(),
+            connection_manager.clone(),
+        ),
     ));
+    // New users' first message in the welcome room gets onboarding tips
+    if let Some(welcome_reply) = config.messages.welcome_reply.clone() {
+        message_service.event_bus().subscribe(Arc::new(
+            campfire_on_rust::services::bot::WelcomeReplySubscriber::new(
+                db_arc.clone(),
+                message_service.event_bus().clone(),
+                welcome_reply,
+            ),
+        ));
+    }
+    let message_service = Arc::new(message_service);
     
     let search_service = Arc::new(SearchService::new(
         db_arc.clone(),
         room_service.clone()
-    ));
+    ).with_max_page_size(config.pagination.max_limit));
     
     // Initialize bot service
-    let bot_service = Arc::new(BotServiceImpl::new(
-        db_arc.clone(),
-        db.writer(),
-        message_service.clone(),
-    ));
+    let bot_service = Arc::new(
+        BotServiceImpl::new(db_arc.clone(), db.writer(), message_service.clone())
+            .with_room_service(room_service.clone())
+            .with_text_limits(config.messages.text_limits)
+            .with_url_policy(webhook_url_policy)
+            .with_max_bots(config.security.max_bots),
+    );
     
     // Initialize setup service
-    let setup_service = Arc::new(SetupServiceImpl::new(db.clone()));
+    let setup_service = Arc::new(
+        SetupServiceImpl::new(db.clone())
+            .with_password_policy(config.security.password_policy.clone())
+            .with_text_limits(config.messages.text_limits)
+            .with_attempt_limit(config.security.setup_attempts_per_minute),
+    );
+    
+    // Provision the initial admin for non-interactive deployments
+    if let Some(seed) = &config.admin_seed {
+        match setup_service.seed_admin(seed).await? {
+            Some(admin) => info!(email = %admin.email, "Provisioned initial admin from environment"),
+            None => info!("Users already exist, skipping admin provisioning from environment"),
+        }
+    }
     
     // Initialize demo service
     let demo_service = Arc::new(campfire_on_rust::DemoServiceImpl::new(db_arc.clone()));
@@ -140,6 +351,50 @@ async fn main() -> Result<()> {
     let analytics_store = Arc::new(campfire_on_rust::analytics::AnalyticsStore::new(1000));
     let analytics_store_for_tracking = analytics_store.clone();
     
+    // Initialize blob storage (local disk unless S3 is configured), counting
+    // what's already stored against the quotas
+    let blob_store = Arc::new(
+        campfire_on_rust::storage::QuotaBlobStore::new(
+            campfire_on_rust::storage::create_blob_store(&config.storage),
+            db_arc.clone(),
+            &config.storage,
+        )
+        .with_scanner(campfire_on_rust::storage::create_scanner(&config.storage)),
+    );
+    let storage_used = blob_store.load_usage().await?;
+    info!("Storage in use: {} bytes", storage_used);
+    // Uploads go with the rooms they were posted to
+    room_service.event_bus().subscribe(blob_store.clone());
+    let features = Arc::new(FeatureFlags::from_config(db_arc.clone(), &config.features));
+    
+    // Data exports are written next to stored files and fetched through
+    // links that expire like presigned blob URLs
+    let exports = Arc::new(
+        campfire_on_rust::services::export::ExportService::new(
+            db_arc.clone(),
+            config.storage.local_path.join("exports"),
+        )
+        .with_link_ttl(Duration::from_secs(config.storage.presign_expiry_secs)),
+    );
+    
+    // Archives outlive neither their links nor the process that knew about
+    // them: sweep once at startup, then every link lifetime
+    let export_sweeper = exports.clone();
+    scheduler.every(
+        "export_archive_cleanup",
+        Duration::from_secs(config.storage.presign_expiry_secs.max(60)),
+        move || {
+            let export_sweeper = export_sweeper.clone();
+            async move {
+                let removed = export_sweeper.sweep_expired().await?;
+                if removed > 0 {
+                    info!("Removed {} expired data export archives", removed);
+                }
+                Ok::<_, campfire_on_rust::errors::ExportError>(())
+            }
+        },
+    );
+    
     let app_state = AppState { 
         db,
         auth_service,
@@ -151,12 +406,37 @@ async fn main() -> Result<()> {
         setup_service,
         demo_service,
         analytics_store,
+        blob_store,
+        features,
+        room_webhooks,
+        room_bridges,
+        exports,
+        scheduler,
+        daily_quota: Arc::new(DailyQuota::from_config(&config.security)),
+        write_shedder: (config.database.write_shed_queue_depth > 0).then(|| {
+            Arc::new(WriteLoadShedder::new(db_arc.writer(), config.database.write_shed_queue_depth))
+        }),
+        pagination: config.pagination,
+        ws_welcome_max_rooms: config.server.ws_welcome_max_rooms,
+        ws_max_sequence_ahead: config.server.ws_max_sequence_ahead,
     };
 
     // Setup resource manager for cleanup
+    // WebSockets drain first so clients hear about the shutdown while the
+    // database is still up; they get half the shutdown timeout. The database
+    // goes last, applying writes already queued before closing.
     let mut resource_manager = shutdown::ResourceManager::new();
-    resource_manager.add_resource(shutdown::DatabaseResource::new("campfire_db".to_string()));
-    resource_manager.add_resource(shutdown::WebSocketResource::new("websocket_connections".to_string(), 0));
+    resource_manager.add_resource(shutdown::WebSocketDrainResource::new(
+        "websocket_connections".to_string(),
+        connection_manager_for_shutdown,
+        Duration::from_secs(config.server.ws_reconnect_after_secs),
+        config.shutdown_timeout() / 2,
+    ));
+    resource_manager.add_resource(shutdown::DatabaseDrainResource::new(
+        "campfire_db".to_string(),
+        app_state.db.clone(),
+        Duration::from_secs(config.server.shutdown_write_drain_secs),
+    ));
 
     // Add shutdown tasks
     let resource_manager_arc = Arc::new(resource_manager);
@@ -164,7 +444,7 @@ async fn main() -> Result<()> {
     
     shutdown_coordinator.add_task(
         "resource_cleanup".to_string(),
-        Duration::from_secs(10),
+        config.shutdown_timeout(),
         move || {
             let rm = resource_manager_for_shutdown.clone();
             tokio::spawn(async move {
@@ -174,12 +454,19 @@ async fn main() -> Result<()> {
     );
 
     // Initialize security middleware
-    let _rate_limit_config = RateLimitConfig {
+    let rate_limiter = RateLimitingMiddleware::new(RateLimitConfig {
         general_rpm: config.security.rate_limit_rpm,
         auth_rpm: config.security.rate_limit_rpm / 6, // Stricter for auth
+        search_rpm: config.security.rate_limit_rpm / 2, // Searches are expensive
         bot_rpm: config.security.rate_limit_rpm * 2, // More lenient for bots
         burst_size: 10,
-    };
+        headers: config.security.rate_limit_headers,
+    });
+    let cleanup_rate_limiter = rate_limiter.clone();
+    app_state.scheduler.every("rate_limiter_cleanup", Duration::from_secs(300), move || {
+        cleanup_rate_limiter.cleanup_old_limiters();
+        async { Ok::<_, std::convert::Infallible>(()) }
+    });
     
     let (_csrf_protection, _csrf_layer) = security::create_csrf_protection_layer();
     let (_bot_abuse_protection, _bot_abuse_layer) = security::create_bot_abuse_protection_layer();
@@ -229,26 +516,45 @@ async fn main() -> Result<()> {
         .route("/api/setup/environment", get(campfire_on_rust::handlers::setup::validate_environment))
         
         // Static assets
-        .route("/static/*path", get(campfire_on_rust::assets::serve_static_asset))
-        
-        // Health and monitoring endpoints
+        .route("/static/*path", get(campfire_on_rust::assets::serve_static_asset));
+    
+    // Health and monitoring endpoints are merged in after the CORS, security
+    // header and setup layers so probes and scrapers get plain responses
+    let mut ops_routes = Router::new()
         .route("/health", get(health::health_check))
         .route("/health/ready", get(health::readiness_check))
-        .route("/health/live", get(health::liveness_check));
+        .route("/health/live", get(health::liveness_check))
+        .route(
+            "/health/detail",
+            get(health::health_detail).route_layer(middleware::from_fn_with_state(
+                Arc::new(TrustedProxies::from_config(&config.security)),
+                client_ip_middleware,
+            )),
+        );
     
     // Add metrics endpoints if enabled
     if config.metrics.enabled {
-        app = app
+        let metrics_auth = Arc::new(metrics::MetricsAuth::new(config.metrics.auth_token.clone()));
+        let metrics_routes = Router::new()
             .route(&config.metrics.endpoint, get(metrics::metrics_endpoint))
             .route("/metrics/summary", get(metrics::metrics_summary))
+            .route_layer(middleware::from_fn_with_state(metrics_auth, metrics::require_metrics_token));
+        ops_routes = ops_routes.merge(metrics_routes);
+        
+        app = app
             .route("/api/performance/summary", get(metrics::performance_summary))
             .route("/api/performance/optimize", post(metrics::optimize_performance));
     }
     
     // Add WebSocket endpoint if enabled (with setup completion validation)
     if config.features.websockets {
+        let ws_origin_policy = Arc::new(WsOriginPolicy::from_config(&config.security));
         let websocket_routes = Router::new()
             .route("/ws", get(campfire_on_rust::handlers::websocket::websocket_handler))
+            .layer(middleware::from_fn_with_state(
+                ws_origin_policy,
+                ws_origin_middleware,
+            ))
             .layer(middleware::from_fn_with_state(
                 app_state.clone(),
                 campfire_on_rust::middleware::setup::setup_completion_middleware
@@ -260,13 +566,68 @@ async fn main() -> Result<()> {
     let protected_api_routes = Router::new()
         .route("/api/auth/login", post(campfire_on_rust::handlers::auth::login))
         .route("/api/auth/logout", post(campfire_on_rust::handlers::auth::logout))
-        .route("/api/users/me", get(campfire_on_rust::handlers::users::get_current_user))
+        .route("/api/auth/logout-all", post(campfire_on_rust::handlers::auth::logout_all))
+        .route(
+            "/api/users/me",
+            get(campfire_on_rust::handlers::users::get_current_user)
+                .patch(campfire_on_rust::handlers::users::update_current_user),
+        )
+        .route("/api/users/resolve", post(campfire_on_rust::handlers::users::resolve_users))
+        .route("/api/users/:id", get(campfire_on_rust::handlers::users::get_user_profile))
+        .route("/api/users/me/mentions", get(campfire_on_rust::handlers::messages::get_my_mentions))
+        .route("/api/users/me/saved", get(campfire_on_rust::handlers::messages::get_saved_messages))
+        .route("/api/users/me/quota", get(campfire_on_rust::handlers::users::get_my_quota))
+        .route("/api/users/me/export", get(campfire_on_rust::handlers::users::export_current_user))
+        .route("/api/users/me/export/:id", get(campfire_on_rust::handlers::users::get_export_status))
+        .route("/api/exports/:id/download", get(campfire_on_rust::handlers::users::download_export))
+        .route("/api/attachments/*key", get(campfire_on_rust::handlers::attachments::serve_attachment))
+        .route("/api/features", get(campfire_on_rust::handlers::features::get_features))
+        .route(
+            "/api/admin/features/:flag",
+            axum::routing::put(campfire_on_rust::handlers::features::set_feature_override)
+                .delete(campfire_on_rust::handlers::features::clear_feature_override),
+        )
         .route("/api/rooms", get(campfire_on_rust::handlers::rooms::get_rooms))
+        .route("/api/direct", get(campfire_on_rust::handlers::rooms::get_direct_conversations))
         .route("/api/rooms", post(campfire_on_rust::handlers::rooms::create_room))
         .route("/api/rooms/:id", get(campfire_on_rust::handlers::rooms::get_room))
         .route("/api/rooms/:id/members", post(campfire_on_rust::handlers::rooms::add_room_member))
+        .route("/api/rooms/:id/members/:user_id", axum::routing::delete(campfire_on_rust::handlers::rooms::remove_room_member))
+        .route("/api/rooms/:id/type", axum::routing::put(campfire_on_rust::handlers::rooms::change_room_type))
+        .route("/api/rooms/:id/permissions", get(campfire_on_rust::handlers::rooms::get_room_permissions))
+        .route("/api/rooms/:id/mentionable", get(campfire_on_rust::handlers::rooms::get_mentionable))
+        .route("/api/rooms/:id/post-permission", axum::routing::put(campfire_on_rust::handlers::rooms::update_post_permission))
+        .route("/api/rooms/:id/public", axum::routing::put(campfire_on_rust::handlers::rooms::update_room_public))
+        .route("/api/rooms/:id/sound-cooldown", axum::routing::put(campfire_on_rust::handlers::rooms::update_sound_cooldown))
+        .route("/api/rooms/:id/sound-policy", axum::routing::put(campfire_on_rust::handlers::rooms::update_sound_policy))
+        .route("/api/rooms/:id/canned", get(campfire_on_rust::handlers::rooms::get_canned_responses))
+        .route(
+            "/api/rooms/:id/canned/:shortcut",
+            axum::routing::put(campfire_on_rust::handlers::rooms::set_canned_response)
+                .delete(campfire_on_rust::handlers::rooms::delete_canned_response),
+        )
+        .route("/api/rooms/:id/attachment-types", axum::routing::put(campfire_on_rust::handlers::rooms::update_attachment_types))
+        .route("/api/rooms/:id/notify-mode", axum::routing::put(campfire_on_rust::handlers::rooms::update_notify_mode))
+        .route(
+            "/api/rooms/:id/post-grants/:user_id",
+            axum::routing::put(campfire_on_rust::handlers::rooms::grant_post_permission)
+                .delete(campfire_on_rust::handlers::rooms::revoke_post_permission),
+        )
         .route("/api/rooms/:id/messages", get(campfire_on_rust::handlers::messages::get_messages))
         .route("/api/rooms/:id/messages", post(campfire_on_rust::handlers::messages::create_message))
+        .route("/api/rooms/:id/messages/:message_id/seen", get(campfire_on_rust::handlers::messages::get_seen_by))
+        .route(
+            "/api/messages/:id/save",
+            post(campfire_on_rust::handlers::messages::save_message)
+                .delete(campfire_on_rust::handlers::messages::unsave_message),
+        )
+        .route("/api/admin/rooms/:id", axum::routing::delete(campfire_on_rust::handlers::rooms::delete_room_permanently))
+        .route("/api/admin/rooms/:id/retention", axum::routing::put(campfire_on_rust::handlers::rooms::update_room_retention))
+        .route("/api/admin/connections", get(campfire_on_rust::handlers::websocket::list_connections))
+        .route("/api/admin/connections/:id", axum::routing::delete(campfire_on_rust::handlers::websocket::force_disconnect))
+        .route("/api/admin/maintenance/vacuum", post(campfire_on_rust::handlers::maintenance::start_vacuum))
+        .route("/api/admin/maintenance/search-index/check", post(campfire_on_rust::handlers::maintenance::start_search_index_check))
+        .route("/api/admin/jobs", get(campfire_on_rust::handlers::maintenance::list_jobs))
         .layer(middleware::from_fn_with_state(
             app_state.clone(),
             campfire_on_rust::middleware::setup::setup_completion_middleware
@@ -274,17 +635,67 @@ async fn main() -> Result<()> {
     
     app = app.merge(protected_api_routes);
     
-    // Add search endpoints if enabled (with setup completion validation)
-    if config.features.search {
-        let search_routes = Router::new()
-            .route("/api/search", get(campfire_on_rust::handlers::search::search_messages))
+    // Add #channel resolution if enabled (with setup completion validation)
+    if config.features.auto_create_rooms {
+        let resolve_routes = Router::new()
+            .route("/api/rooms/resolve-or-create", post(campfire_on_rust::handlers::rooms::resolve_or_create_room))
+            .layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                campfire_on_rust::middleware::setup::setup_completion_middleware
+            ));
+        app = app.merge(resolve_routes);
+    }
+    
+    // Admin impersonation for support, only when explicitly enabled
+    if config.features.impersonation {
+        let impersonation_routes = Router::new()
+            .route("/api/admin/users/:id/impersonate", post(campfire_on_rust::handlers::users::impersonate_user))
+            .layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                campfire_on_rust::middleware::setup::setup_completion_middleware
+            ));
+        app = app.merge(impersonation_routes);
+    }
+    
+    // Anonymous read-only history for public rooms, if enabled
+    if config.features.public_rooms {
+        let public_routes = Router::new()
+            .route("/api/public/rooms/:id/messages", get(campfire_on_rust::handlers::public::get_public_messages))
             .layer(middleware::from_fn_with_state(
                 app_state.clone(),
                 campfire_on_rust::middleware::setup::setup_completion_middleware
             ));
-        app = app.merge(search_routes);
+        app = app.merge(public_routes);
     }
     
+    // Add upload endpoints if enabled (with setup completion validation)
+    if config.features.file_uploads {
+        let mut upload_routes = Router::new()
+            .route("/api/rooms/:id/attachments", post(campfire_on_rust::handlers::attachments::upload_attachment));
+        // A single file can't be bigger than everything a message may attach
+        if config.storage.max_attachment_bytes_per_message > 0 {
+            upload_routes = upload_routes.layer(axum::extract::DefaultBodyLimit::max(
+                config.storage.max_attachment_bytes_per_message as usize,
+            ));
+        }
+        let upload_routes = upload_routes.layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            campfire_on_rust::middleware::setup::setup_completion_middleware
+        ));
+        app = app.merge(upload_routes);
+    }
+    
+    // Search endpoints (with setup completion validation); the handler
+    // checks the `search` flag per user and room, so overrides can turn it
+    // on where it's globally off
+    let search_routes = Router::new()
+        .route("/api/search", get(campfire_on_rust::handlers::search::search_messages))
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            campfire_on_rust::middleware::setup::setup_completion_middleware
+        ));
+    app = app.merge(search_routes);
+    
     // Add sound endpoints if enabled (with setup completion validation)
     if config.features.sounds {
         let sound_routes = Router::new()
@@ -328,6 +739,17 @@ async fn main() -> Result<()> {
             .route("/api/bots/:id", axum::routing::put(campfire_on_rust::handlers::bot::update_bot))
             .route("/api/bots/:id", axum::routing::delete(campfire_on_rust::handlers::bot::delete_bot))
             .route("/api/bots/:id/reset-token", post(campfire_on_rust::handlers::bot::reset_bot_token))
+            .route("/api/bots/:id/simulate", post(campfire_on_rust::handlers::bot::simulate_bot_webhook))
+            .route("/api/rooms/:id/webhooks", get(campfire_on_rust::handlers::bot::list_room_webhooks))
+            .route("/api/rooms/:id/webhooks", post(campfire_on_rust::handlers::bot::create_room_webhook))
+            .route("/api/rooms/:id/webhooks/:webhook_id", axum::routing::put(campfire_on_rust::handlers::bot::update_room_webhook))
+            .route("/api/rooms/:id/webhooks/:webhook_id", axum::routing::delete(campfire_on_rust::handlers::bot::delete_room_webhook))
+            .route(
+                "/api/rooms/:id/bridge",
+                get(campfire_on_rust::handlers::bot::get_room_bridge)
+                    .put(campfire_on_rust::handlers::bot::set_room_bridge)
+                    .delete(campfire_on_rust::handlers::bot::delete_room_bridge),
+            )
             .route("/rooms/:room_id/bot/:bot_key/messages", post(campfire_on_rust::handlers::bot::create_bot_message))
             .layer(middleware::from_fn_with_state(
                 app_state.clone(),
@@ -342,9 +764,28 @@ async fn main() -> Result<()> {
     }
     
     if config.logging.trace_requests {
-        app = app.layer(middleware::from_fn(logging::middleware::trace_requests));
+        let sampler = Arc::new(logging::middleware::RequestLogSampler::new(
+            config.logging.sampling.clone(),
+        ));
+        app = app.layer(middleware::from_fn_with_state(sampler, logging::middleware::trace_requests));
+    }
+    
+    // Turn writes away while the database writer is backed up; reads stay up
+    if let Some(shedder) = app_state.write_shedder.clone() {
+        app = app.layer(middleware::from_fn_with_state(shedder, write_load_shedding_middleware));
     }
     
+    // Cap each account's requests per day, if configured
+    if app_state.daily_quota.is_enabled() {
+        app = app.layer(middleware::from_fn_with_state(app_state.clone(), daily_quota_middleware));
+    }
+    
+    // Refuse oversized, pathologically nested or field-stuffed JSON before handlers parse it
+    app = app.layer(middleware::from_fn_with_state(
+        Arc::new(JsonLimits::from_config(&config.security, &config.server)),
+        json_limits_middleware,
+    ));
+    
     // Add setup detection middleware for automatic redirection to setup when needed
     // This middleware runs early to catch first-run scenarios before other processing
     app = app.layer(middleware::from_fn_with_state(
@@ -352,13 +793,27 @@ async fn main() -> Result<()> {
         campfire_on_rust::middleware::setup::setup_detection_middleware
     ));
     
+    // Per-client API rate limits, keyed on the resolved client IP
+    app = app
+        .layer(middleware::from_fn(rate_limiting_middleware))
+        .layer(axum::Extension(rate_limiter));
+    
     let app = app
         // Basic security middleware layers
         .layer(security::create_cors_layer(&config.security.cors_origins, config.security.force_https))
         .layer(security::create_security_headers_layer(config.security.force_https))
-        .layer(security::create_timeout_layer_with_duration(config.request_timeout()))
-        // TODO: Re-enable request size limit layer after fixing compatibility issue
-        // .layer(security::create_request_size_limit_layer_with_size(config.server.max_request_size))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(RequestTimeouts::from_config(&config.server)),
+            request_timeout_middleware,
+        ))
+        // Resolve the client IP outermost so every other layer sees it
+        .layer(middleware::from_fn_with_state(
+            Arc::new(TrustedProxies::from_config(&config.security)),
+            client_ip_middleware,
+        ))
+        // Cap the bodies handlers read; upload routes set their own cap
+        .layer(axum::extract::DefaultBodyLimit::max(config.server.max_request_size))
+        .merge(ops_routes)
         .with_state(app_state);
 
     // Start server with graceful shutdown
@@ -374,7 +829,7 @@ async fn main() -> Result<()> {
     let demo_mode = config.features.demo_mode;
     
     let server = axum::Server::bind(&addr)
-        .serve(app.into_make_service())
+        .serve(app.into_make_service_with_connect_info::<std::net::SocketAddr>())
         .with_graceful_shutdown(async {
             let mut shutdown_receiver = shutdown_receiver;
             if let Ok(signal) = shutdown_receiver.recv().await {
@@ -1,10 +1,10 @@
 use axum::{
-    extract::State,
+    extract::{MatchedPath, State},
     http::StatusCode,
     response::{IntoResponse, Response},
 };
 use metrics::{counter, gauge, histogram, describe_counter, describe_gauge, describe_histogram};
-use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
 use serde::{Deserialize, Serialize};
 use std::time::{Duration, Instant};
 use std::sync::Arc;
@@ -21,6 +21,10 @@ use sysinfo::{System, SystemExt, CpuExt, ProcessExt};
 use crate::AppState;
 use std::sync::OnceLock;
 
+/// Route label for requests that matched no route (404s, probes for
+/// random paths), so they can't grow the label set
+const UNMATCHED_ROUTE: &str = "unmatched";
+
 /// Metrics recorder handle for Prometheus export
 static PROMETHEUS_HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
 
@@ -611,8 +615,20 @@ pub fn get_performance_monitor() -> Arc<PerformanceMonitor> {
 
 /// Initialize metrics system
 pub fn init_metrics() -> Result<(), Box<dyn std::error::Error>> {
+    init_metrics_with_buckets(&[])
+}
+
+/// Initialize metrics system, exporting request latency as a histogram
+/// with the given buckets (in seconds) instead of a summary
+pub fn init_metrics_with_buckets(buckets: &[f64]) -> Result<(), Box<dyn std::error::Error>> {
     // Build Prometheus recorder
-    let builder = PrometheusBuilder::new();
+    let mut builder = PrometheusBuilder::new();
+    if !buckets.is_empty() {
+        builder = builder.set_buckets_for_metric(
+            Matcher::Full("http_request_duration_seconds".to_string()),
+            buckets,
+        )?;
+    }
     let handle = builder.install_recorder()?;
     
     PROMETHEUS_HANDLE.set(handle).map_err(|_| "Prometheus handle already initialized")?;
@@ -631,14 +647,15 @@ pub fn init_metrics() -> Result<(), Box<dyn std::error::Error>> {
 /// Describe all metrics for Prometheus
 fn describe_metrics() {
     // HTTP metrics
-    describe_counter!("http_requests_total", "Total number of HTTP requests");
-    describe_histogram!("http_request_duration_seconds", "HTTP request duration in seconds");
-    describe_counter!("http_requests_errors_total", "Total number of HTTP request errors");
+    describe_counter!("http_requests_total", "HTTP requests by method, route template and status class");
+    describe_histogram!("http_request_duration_seconds", "HTTP request duration in seconds by method, route template and status class");
+    describe_counter!("http_requests_errors_total", "HTTP 4xx/5xx responses by method, route template and status class");
     
     // WebSocket metrics
     describe_gauge!("websocket_connections_active", "Number of active WebSocket connections");
     describe_counter!("websocket_messages_sent_total", "Total WebSocket messages sent");
     describe_counter!("websocket_messages_received_total", "Total WebSocket messages received");
+    describe_counter!("websocket_send_buffer_overflows_total", "Frames that found a WebSocket client's send buffer full");
     
     // Database metrics
     describe_histogram!("database_query_duration_seconds", "Database query duration in seconds");
@@ -649,15 +666,23 @@ fn describe_metrics() {
     // Message metrics
     describe_counter!("messages_created_total", "Total messages created");
     describe_counter!("messages_deduplicated_total", "Total messages deduplicated");
+    describe_counter!("messages_rate_limited_total", "Total messages rejected by the per-user rate limit");
+    describe_counter!("sounds_suppressed_total", "Sound commands dropped by a room's sound cooldown");
     describe_histogram!("message_processing_duration_seconds", "Message processing duration");
     
     // Room metrics
     describe_gauge!("rooms_total", "Total number of rooms");
     describe_gauge!("users_online", "Number of users currently online");
     
+    // Storage metrics
+    describe_gauge!("storage_used_bytes", "Bytes of uploads currently stored");
+    describe_counter!("storage_scan_flagged_total", "Uploads rejected by the virus scanner");
+    
     // Push notification metrics
     describe_counter!("push_notifications_sent_total", "Total push notifications sent");
     describe_counter!("push_notifications_failed_total", "Total push notification failures");
+    describe_gauge!("push_notifications_in_flight", "Push requests currently being sent");
+    describe_counter!("push_notifications_backoff_skipped_total", "Push sends skipped because the endpoint is backing off");
     
     // System metrics
     describe_gauge!("memory_usage_bytes", "Memory usage in bytes");
@@ -840,41 +865,109 @@ async fn collect_metrics_summary(state: &AppState) -> MetricsSummary {
     }
 }
 
-/// Middleware to record HTTP request metrics
+/// Bearer token guarding the metrics endpoints; `None` leaves them open
+#[derive(Debug, Clone, Default)]
+pub struct MetricsAuth {
+    token: Option<String>,
+}
+
+impl MetricsAuth {
+    pub fn new(token: Option<String>) -> Self {
+        Self { token }
+    }
+    
+    fn is_authorized(&self, authorization: Option<&str>) -> bool {
+        let Some(expected) = &self.token else {
+            return true;
+        };
+        let Some(provided) = authorization.and_then(|value| value.strip_prefix("Bearer ")) else {
+            return false;
+        };
+        
+        // Compare without short-circuiting so timing doesn't leak the prefix
+        provided.len() == expected.len()
+            && provided
+                .bytes()
+                .zip(expected.bytes())
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+/// Middleware rejecting metrics scrapes that lack the configured bearer token
+pub async fn require_metrics_token<B>(
+    State(auth): State<Arc<MetricsAuth>>,
+    req: axum::http::Request<B>,
+    next: axum::middleware::Next<B>,
+) -> Response {
+    let authorization = req
+        .headers()
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    
+    if !auth.is_authorized(authorization) {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(axum::http::header::WWW_AUTHENTICATE, "Bearer")],
+        )
+            .into_response();
+    }
+    
+    next.run(req).await
+}
+
+/// Route template a request matched, e.g. `/api/rooms/:id/messages`, so
+/// ids in the path don't each become a label value
+fn route_label<B>(req: &axum::http::Request<B>) -> String {
+    req.extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_string())
+}
+
+fn status_class(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        _ => "5xx",
+    }
+}
+
+/// Middleware to record HTTP request metrics, labelled by route template
+/// and status class
 pub async fn record_http_request<B>(
     req: axum::http::Request<B>,
     next: axum::middleware::Next<B>,
 ) -> Response {
     let start = Instant::now();
-    let method = req.method().clone();
-    let path = req.uri().path().to_string();
-    let endpoint = format!("{} {}", method, path);
-    
-    // Increment request counter
-    counter!("http_requests_total", 1, "method" => method.to_string(), "path" => path.clone());
+    let method = req.method().to_string();
+    let route = route_label(&req);
+    let endpoint = format!("{} {}", method, route);
     
     let response = next.run(req).await;
     
-    // Record request duration
     let duration = start.elapsed();
-    let is_error = response.status().is_client_error() || response.status().is_server_error();
+    let status = response.status();
+    let is_error = status.is_client_error() || status.is_server_error();
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status_class", status_class(status).to_string()),
+    ];
     
     // Record in Prometheus
-    histogram!("http_request_duration_seconds", duration.as_secs_f64(), "method" => method.to_string(), "path" => path.clone());
+    counter!("http_requests_total", 1, &labels);
+    histogram!("http_request_duration_seconds", duration.as_secs_f64(), &labels);
+    if is_error {
+        counter!("http_requests_errors_total", 1, &labels);
+    }
     
     // Record in performance monitor
     let monitor = get_performance_monitor();
     monitor.record_http_request(&endpoint, duration, is_error);
     
-    // Record errors
-    if is_error {
-        counter!("http_requests_errors_total", 1, 
-            "method" => method.to_string(), 
-            "path" => path, 
-            "status" => response.status().as_u16().to_string()
-        );
-    }
-    
     response
 }
 
@@ -981,4 +1074,77 @@ mod tests {
         assert_eq!(summary.uptime_seconds, deserialized.uptime_seconds);
         assert_eq!(summary.http.requests_total, deserialized.http.requests_total);
     }
+    
+    #[tokio::test]
+    async fn test_metrics_token_required_but_not_for_health() {
+        use axum::{body::Body, http::Request, middleware, routing::get, Router};
+        use tower::ServiceExt;
+        
+        let auth = Arc::new(MetricsAuth::new(Some("scrape-secret".to_string())));
+        let app = Router::new()
+            .route("/metrics", get(|| async { "metrics" }))
+            .route_layer(middleware::from_fn_with_state(auth, require_metrics_token))
+            .route("/health/live", get(crate::health::liveness_check));
+        
+        let request = |uri: &str, token: Option<&str>| {
+            let mut builder = Request::builder().uri(uri);
+            if let Some(token) = token {
+                builder = builder.header("authorization", format!("Bearer {}", token));
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+        
+        let response = app.clone().oneshot(request("/metrics", None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        
+        let response = app.clone().oneshot(request("/metrics", Some("wrong-secret"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        
+        let response = app.clone().oneshot(request("/metrics", Some("scrape-secret"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        
+        let response = app.oneshot(request("/health/live", None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    
+    #[tokio::test]
+    async fn test_request_metrics_labelled_by_route_template() {
+        use axum::{body::Body, http::Request, middleware, routing::get, Router};
+        use tower::ServiceExt;
+        
+        let app = Router::new()
+            .route("/label-test/rooms/:id/messages", get(|| async { "messages" }))
+            .route("/label-test/rooms/:id/files", get(|| async { StatusCode::NOT_FOUND }))
+            .layer(middleware::from_fn(record_http_request));
+        
+        for uri in [
+            "/label-test/rooms/first/messages",
+            "/label-test/rooms/second/messages",
+            "/label-test/rooms/first/files",
+        ] {
+            let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            app.clone().oneshot(request).await.unwrap();
+        }
+        
+        let monitor = get_performance_monitor();
+        let count = |endpoint: &str| {
+            monitor.request_timings
+                .get(endpoint)
+                .map(|stats| stats.total_requests.load(Ordering::Relaxed))
+        };
+        
+        // Both rooms share the templated label; the other route has its own
+        assert_eq!(count("GET /label-test/rooms/:id/messages"), Some(2));
+        assert_eq!(count("GET /label-test/rooms/:id/files"), Some(1));
+        assert!(!monitor.request_timings.iter().any(|entry| entry.key().contains("/label-test/rooms/first")));
+        
+        assert_eq!(status_class(StatusCode::NOT_FOUND), "4xx");
+        assert_eq!(status_class(StatusCode::SERVICE_UNAVAILABLE), "5xx");
+    }
+    
+    #[test]
+    fn test_metrics_open_without_token() {
+        assert!(MetricsAuth::new(None).is_authorized(None));
+        assert!(!MetricsAuth::new(Some("secret".to_string())).is_authorized(Some("secret")));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,256 @@
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, State},
+    http::{request::Parts, HeaderMap, Request, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use crate::config::SecurityConfig;
+
+/// Resolved address of the client that made the request
+///
+/// Set by [`client_ip_middleware`]; when the middleware isn't installed the
+/// extractor falls back to the socket peer, so handlers never see a value
+/// taken from an untrusted forwarding header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// Proxies whose `X-Forwarded-For` / `Forwarded` headers are believed
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    networks: Vec<IpNet>,
+}
+
+/// Parses a CIDR, or a bare IP as a single-host network
+pub fn parse_proxy_net(value: &str) -> Result<IpNet, ipnet::AddrParseError> {
+    value.parse::<IpNet>().or_else(|err| {
+        value.parse::<IpAddr>().map(IpNet::from).map_err(|_| err)
+    })
+}
+
+impl TrustedProxies {
+    pub fn new(networks: Vec<IpNet>) -> Self {
+        Self { networks }
+    }
+
+    /// Trusts nothing unless `trust_proxy` is on; invalid entries are rejected
+    /// by `Config::validate` before we get here
+    pub fn from_config(config: &SecurityConfig) -> Self {
+        if !config.trust_proxy {
+            return Self::default();
+        }
+
+        Self::new(
+            config
+                .trusted_proxies
+                .iter()
+                .filter_map(|value| parse_proxy_net(value).ok())
+                .collect(),
+        )
+    }
+
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.networks.iter().any(|net| net.contains(&ip))
+    }
+
+    /// Resolves the client address for a request arriving from `peer`
+    ///
+    /// Forwarding headers are only read when the peer itself is trusted. The
+    /// chain is walked from the nearest hop backwards and the first address
+    /// that isn't one of our proxies is the client; anything further left
+    /// could have been written by the client and is ignored.
+    pub fn resolve(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.is_trusted(peer) {
+            return peer;
+        }
+
+        let chain = forwarded_for(headers).unwrap_or_else(|| x_forwarded_for(headers));
+        let mut client = peer;
+        for hop in chain.into_iter().rev() {
+            client = hop;
+            if !self.is_trusted(hop) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+/// Addresses from the RFC 7239 `Forwarded` header, or None when absent
+fn forwarded_for(headers: &HeaderMap) -> Option<Vec<IpAddr>> {
+    let values: Vec<&str> = headers
+        .get_all("forwarded")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+
+    let addresses = values
+        .iter()
+        .flat_map(|value| value.split(','))
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (key, value) = pair.trim().split_once('=')?;
+                if key.trim().eq_ignore_ascii_case("for") {
+                    parse_node(value.trim().trim_matches('"'))
+                } else {
+                    None
+                }
+            })
+        })
+        .collect();
+    Some(addresses)
+}
+
+fn x_forwarded_for(headers: &HeaderMap) -> Vec<IpAddr> {
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|node| parse_node(node.trim()))
+        .collect()
+}
+
+/// Parses `1.2.3.4`, `1.2.3.4:80`, `::1` or `[::1]:80`
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+    node.strip_prefix('[')
+        .and_then(|rest| rest.split(']').next())
+        .and_then(|ip| ip.parse().ok())
+}
+
+/// Resolves the client IP once per request and stores it in the extensions
+pub async fn client_ip_middleware<B>(
+    State(proxies): State<Arc<TrustedProxies>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let client_ip = proxies.resolve(addr.ip(), request.headers());
+    request.extensions_mut().insert(ClientIp(client_ip));
+    next.run(request).await
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(client_ip) = parts.extensions.get::<ClientIp>() {
+            return Ok(*client_ip);
+        }
+
+        let ConnectInfo(addr) = ConnectInfo::<SocketAddr>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(ClientIp(addr.ip()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxies() -> TrustedProxies {
+        TrustedProxies::new(vec![
+            parse_proxy_net("10.0.0.0/8").unwrap(),
+            parse_proxy_net("::1").unwrap(),
+        ])
+    }
+
+    fn headers(name: &'static str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_spoofed_header_from_untrusted_peer_is_ignored() {
+        let peer: IpAddr = "203.0.113.7".parse().unwrap();
+        let spoofed = headers("x-forwarded-for", "1.1.1.1");
+
+        assert_eq!(proxies().resolve(peer, &spoofed), peer);
+        assert_eq!(proxies().resolve(peer, &headers("forwarded", "for=1.1.1.1")), peer);
+    }
+
+    #[test]
+    fn test_forwarded_header_from_trusted_proxy_is_honored() {
+        let proxy: IpAddr = "10.0.0.2".parse().unwrap();
+
+        let xff = headers("x-forwarded-for", "198.51.100.4, 10.0.0.9");
+        assert_eq!(proxies().resolve(proxy, &xff), "198.51.100.4".parse::<IpAddr>().unwrap());
+
+        let forwarded = headers("forwarded", "for=\"[2001:db8::1]:4711\";proto=https");
+        assert_eq!(proxies().resolve(proxy, &forwarded), "2001:db8::1".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_client_cannot_prepend_addresses_through_trusted_proxy() {
+        // The client sent "X-Forwarded-For: 1.1.1.1" and our proxy appended
+        // the real address; only the proxy-written hop is believed
+        let proxy: IpAddr = "10.0.0.2".parse().unwrap();
+        let xff = headers("x-forwarded-for", "1.1.1.1, 198.51.100.4");
+
+        assert_eq!(proxies().resolve(proxy, &xff), "198.51.100.4".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_trust_proxy_disabled_trusts_nothing() {
+        let config = SecurityConfig {
+            cors_origins: vec![],
+            rate_limit_rpm: 60,
+            rate_limit_headers: true,
+            session_token_length: 32,
+            session_expiry_hours: 24,
+            session_idle_timeout_mins: 0,
+            impersonation_session_mins: 30,
+            force_https: false,
+            trust_proxy: false,
+            trusted_proxies: vec!["127.0.0.1/32".to_string()],
+            ws_allow_missing_origin: true,
+            message_rate_per_minute: 0,
+            message_rate_exempt_bots: true,
+            message_rate_exempt_admins: false,
+            room_join_rate_per_minute: 0,
+            max_bots: 0,
+            setup_attempts_per_minute: 0,
+            json_max_depth: 0,
+            json_max_fields: 0,
+            password_policy: Default::default(),
+            token_lifetimes: Default::default(),
+            token_cleanup_interval_secs: 3600,
+            daily_request_quota: 0,
+            bot_daily_request_quota: 0,
+            quota_timezone: "UTC".to_string(),
+            webhook_require_https: false,
+            webhook_allow_private_networks: false,
+            webhook_allowed_hosts: vec![],
+            webhook_denied_hosts: vec![],
+        };
+        let loopback: IpAddr = "127.0.0.1".parse().unwrap();
+        let xff = headers("x-forwarded-for", "1.1.1.1");
+
+        assert_eq!(TrustedProxies::from_config(&config).resolve(loopback, &xff), loopback);
+
+        let config = SecurityConfig { trust_proxy: true, ..config };
+        assert_eq!(
+            TrustedProxies::from_config(&config).resolve(loopback, &xff),
+            "1.1.1.1".parse::<IpAddr>().unwrap()
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use axum::{
+    body::Body,
+    extract::{FromRequestParts, State},
+    http::{request::Parts, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Datelike, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use dashmap::DashMap;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::config::SecurityConfig;
+use crate::logging::error_handling::UserFriendlyError;
+use crate::middleware::session::AuthenticatedUser;
+use crate::models::{User, UserId};
+use crate::timezone::resolve_timezone;
+use crate::AppState;
+
+/// Usage is always readable, even with the day's quota spent
+pub const QUOTA_PATH: &str = "/api/users/me/quota";
+
+/// Requests a user has made on one day; the whole per-user footprint
+#[derive(Debug, Clone, Copy)]
+struct DayCount {
+    /// Days since the common era, in the quota's timezone
+    day: i32,
+    count: u32,
+}
+
+/// How much of today's quota a user has used
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QuotaUsage {
+    /// Requests allowed per day (0 = unlimited)
+    pub limit: u32,
+    pub used: u32,
+    /// None when unlimited
+    pub remaining: Option<u32>,
+    /// Next midnight in the quota's timezone
+    pub resets_at: DateTime<Utc>,
+}
+
+/// Per-user daily API request caps
+///
+/// Short-window rate limits smooth out bursts; this caps what one account
+/// can do in a day, which matters most for bots. Counts reset at midnight
+/// in the configured timezone. Bots and people have separate limits.
+#[derive(Debug)]
+pub struct DailyQuota {
+    user_limit: u32,
+    bot_limit: u32,
+    timezone: Tz,
+    counts: DashMap<UserId, DayCount>,
+}
+
+impl DailyQuota {
+    pub fn new(user_limit: u32, bot_limit: u32, timezone: Tz) -> Self {
+        Self {
+            user_limit,
+            bot_limit,
+            timezone,
+            counts: DashMap::new(),
+        }
+    }
+
+    pub fn from_config(config: &SecurityConfig) -> Self {
+        Self::new(
+            config.daily_request_quota,
+            config.bot_daily_request_quota,
+            resolve_timezone(Some(&config.quota_timezone)),
+        )
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.user_limit > 0 || self.bot_limit > 0
+    }
+
+    fn limit_for(&self, user: &User) -> u32 {
+        if user.bot_token.is_some() {
+            self.bot_limit
+        } else {
+            self.user_limit
+        }
+    }
+
+    fn day(&self, now: DateTime<Utc>) -> i32 {
+        now.with_timezone(&self.timezone).date_naive().num_days_from_ce()
+    }
+
+    fn resets_at(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let today = now.with_timezone(&self.timezone).date_naive();
+        let midnight = today.succ_opt().unwrap_or(today).and_time(NaiveTime::MIN);
+        // Zones that skip midnight for daylight saving reset at the first
+        // instant after it
+        self.timezone
+            .from_local_datetime(&midnight)
+            .earliest()
+            .or_else(|| self.timezone.from_local_datetime(&(midnight + chrono::Duration::hours(1))).earliest())
+            .map(|reset| reset.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc.from_utc_datetime(&midnight))
+    }
+
+    fn used_today(&self, user_id: UserId, day: i32) -> u32 {
+        self.counts
+            .get(&user_id)
+            .filter(|count| count.day == day)
+            .map_or(0, |count| count.count)
+    }
+
+    fn usage_with(&self, limit: u32, used: u32, now: DateTime<Utc>) -> QuotaUsage {
+        QuotaUsage {
+            limit,
+            used,
+            remaining: (limit > 0).then(|| limit.saturating_sub(used)),
+            resets_at: self.resets_at(now),
+        }
+    }
+
+    /// Today's usage, without counting a request
+    pub fn usage(&self, user: &User, now: DateTime<Utc>) -> QuotaUsage {
+        let used = self.used_today(user.id, self.day(now));
+        self.usage_with(self.limit_for(user), used, now)
+    }
+
+    /// Counts a request, or refuses it once the day's quota is spent
+    pub fn record(&self, user: &User, now: DateTime<Utc>) -> Result<QuotaUsage, QuotaUsage> {
+        let limit = self.limit_for(user);
+        let day = self.day(now);
+        let mut count = self.counts.entry(user.id).or_insert(DayCount { day, count: 0 });
+        if count.day != day {
+            *count = DayCount { day, count: 0 };
+        }
+
+        if limit > 0 && count.count >= limit {
+            let used = count.count;
+            drop(count);
+            return Err(self.usage_with(limit, used, now));
+        }
+        count.count += 1;
+        let used = count.count;
+        drop(count);
+        Ok(self.usage_with(limit, used, now))
+    }
+}
+
+/// The account a request is made as: the session's user, or the bot whose
+/// key is in a bot message path
+///
+/// A session user is left in the request's extensions, so the handler's
+/// `AuthenticatedUser` doesn't look the session up again.
+async fn requesting_user(parts: &mut Parts, state: &AppState) -> Option<User> {
+    let segments: Vec<&str> = parts.uri.path().trim_matches('/').split('/').collect();
+    if let ["rooms", _, "bot", bot_key, "messages"] = segments.as_slice() {
+        let bot_key = bot_key.to_string();
+        return state.bot_service.authenticate_bot(&bot_key).await.ok();
+    }
+
+    AuthenticatedUser::from_request_parts(parts, state)
+        .await
+        .ok()
+        .map(|auth_user| auth_user.user)
+}
+
+/// Answers 429 once a user has spent the day's request quota
+///
+/// Requests that don't identify an account are passed on for the handler
+/// to reject.
+pub async fn daily_quota_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let path = request.uri().path();
+    let counted = path.starts_with("/api/") || path.starts_with("/rooms/");
+    if !state.daily_quota.is_enabled() || !counted || path == QUOTA_PATH {
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let user = requesting_user(&mut parts, &state).await;
+    let request = Request::from_parts(parts, body);
+    let Some(user) = user else {
+        return next.run(request).await;
+    };
+
+    let now = Utc::now();
+    match state.daily_quota.record(&user, now) {
+        Ok(_) => next.run(request).await,
+        Err(usage) => {
+            warn!("User {} has spent their daily quota of {} requests", user.id, usage.limit);
+            let retry_after = (usage.resets_at - now).num_seconds().max(1) as u64;
+            UserFriendlyError::new(
+                format!(
+                    "Daily limit of {} requests reached; it resets at {}",
+                    usage.limit,
+                    usage.resets_at.to_rfc3339()
+                ),
+                "DAILY_QUOTA_EXCEEDED",
+                StatusCode::TOO_MANY_REQUESTS,
+            )
+            .with_retry_after(retry_after)
+            .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(bot: bool) -> User {
+        User {
+            bot_token: bot.then(|| "bot-token".to_string()),
+            ..User::for_tests("Quota")
+        }
+    }
+
+    #[test]
+    fn test_spent_quota_blocks_until_midnight_in_the_quota_timezone() {
+        let paris: Tz = "Europe/Paris".parse().unwrap();
+        let quota = DailyQuota::new(0, 3, paris);
+        let bot = user(true);
+        // 22:30 in Paris (UTC+1 in winter)
+        let evening = Utc.with_ymd_and_hms(2024, 1, 15, 21, 30, 0).unwrap();
+
+        for used in 1..=3 {
+            assert_eq!(quota.record(&bot, evening).unwrap().used, used);
+        }
+        let refused = quota.record(&bot, evening).unwrap_err();
+        assert_eq!(refused.remaining, Some(0));
+        assert_eq!(refused.resets_at, Utc.with_ymd_and_hms(2024, 1, 15, 23, 0, 0).unwrap());
+        assert_eq!(quota.usage(&bot, evening).used, 3);
+
+        // Still refused a minute before the reset, allowed right after
+        assert!(quota.record(&bot, Utc.with_ymd_and_hms(2024, 1, 15, 22, 59, 0).unwrap()).is_err());
+        let after_reset = quota.record(&bot, Utc.with_ymd_and_hms(2024, 1, 15, 23, 0, 0).unwrap()).unwrap();
+        assert_eq!((after_reset.used, after_reset.remaining), (1, Some(2)));
+
+        // People have their own (here unlimited) allowance
+        let person = user(false);
+        for _ in 0..10 {
+            assert!(quota.record(&person, evening).is_ok());
+        }
+        assert_eq!(quota.usage(&person, evening).remaining, None);
+    }
+
+    #[tokio::test]
+    async fn test_middleware_hands_the_resolved_user_to_the_handler() {
+        use axum::{middleware::from_fn_with_state, routing::get, Extension, Router};
+        use std::sync::Arc;
+        use tower::ServiceExt;
+
+        let mut state = crate::handlers::websocket::tests::create_test_state().await;
+        state.daily_quota = Arc::new(DailyQuota::new(2, 0, Tz::UTC));
+        let person = state
+            .auth_service
+            .create_user("Quota".to_string(), "quota@example.com".to_string(), "password123".to_string())
+            .await
+            .unwrap();
+        let session = state.auth_service.create_session(person.id).await.unwrap();
+
+        // `Extension` only finds the user if the middleware left it there
+        let app = Router::new()
+            .route(
+                "/api/users/me",
+                get(|Extension(auth_user): Extension<AuthenticatedUser>| async move {
+                    auth_user.user.id.to_string()
+                }),
+            )
+            .layer(from_fn_with_state(state.clone(), daily_quota_middleware))
+            .with_state(state);
+        let request = || {
+            Request::get("/api/users/me")
+                .header("Authorization", format!("Bearer {}", session.token))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        for _ in 0..2 {
+            let response = app.clone().oneshot(request()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            assert_eq!(body, person.id.to_string());
+        }
+        let response = app.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
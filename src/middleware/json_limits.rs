@@ -0,0 +1,263 @@
+use axum::{
+    body::{Body, HttpBody},
+    extract::State,
+    http::{header, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::config::{SecurityConfig, ServerConfig};
+use crate::logging::error_handling::UserFriendlyError;
+
+/// Structural limits on JSON request bodies
+///
+/// The byte size limit doesn't stop a small body nested thousands of levels
+/// deep, or one packed with tiny fields, from tying up the parser. Bodies
+/// are scanned as they arrive and refused as soon as they cross a limit, so
+/// an abusive body is never handed to a handler's deserializer. Scanned
+/// bodies are buffered for the handler, up to the request size limit.
+#[derive(Debug, Clone, Copy)]
+pub struct JsonLimits {
+    /// Deepest object/array nesting (0 = unlimited)
+    pub max_depth: usize,
+    /// Most object fields across the whole body (0 = unlimited)
+    pub max_fields: usize,
+    /// Largest body in bytes (0 = unlimited)
+    pub max_bytes: usize,
+}
+
+impl JsonLimits {
+    pub fn from_config(security: &SecurityConfig, server: &ServerConfig) -> Self {
+        Self {
+            max_depth: security.json_max_depth,
+            max_fields: security.json_max_fields,
+            max_bytes: server.max_request_size,
+        }
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.max_depth == 0 && self.max_fields == 0 && self.max_bytes == 0
+    }
+
+    fn exceeds_size(&self, bytes: usize) -> bool {
+        self.max_bytes > 0 && bytes > self.max_bytes
+    }
+}
+
+/// Which limit a body broke
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonLimitViolation {
+    TooDeep { limit: usize },
+    TooManyFields { limit: usize },
+}
+
+/// Tracks nesting and field count across a body fed in chunks, without
+/// parsing it. Malformed JSON is left for the deserializer to reject.
+#[derive(Debug, Default)]
+struct JsonScanner {
+    depth: usize,
+    fields: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl JsonScanner {
+    fn feed(&mut self, chunk: &[u8], limits: &JsonLimits) -> Result<(), JsonLimitViolation> {
+        for &byte in chunk {
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match byte {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => {
+                    self.depth += 1;
+                    if limits.max_depth > 0 && self.depth > limits.max_depth {
+                        return Err(JsonLimitViolation::TooDeep { limit: limits.max_depth });
+                    }
+                }
+                b'}' | b']' => self.depth = self.depth.saturating_sub(1),
+                // Every object field has exactly one colon outside a string
+                b':' => {
+                    self.fields += 1;
+                    if limits.max_fields > 0 && self.fields > limits.max_fields {
+                        return Err(JsonLimitViolation::TooManyFields { limit: limits.max_fields });
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_json(request: &Request<Body>) -> bool {
+    request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| {
+            let mime = content_type.split(';').next().unwrap_or("").trim();
+            mime.eq_ignore_ascii_case("application/json") || mime.ends_with("+json")
+        })
+}
+
+fn too_large(limits: &JsonLimits) -> Response {
+    UserFriendlyError::new(
+        format!("The request body is larger than {} bytes", limits.max_bytes),
+        "PAYLOAD_TOO_LARGE",
+        StatusCode::PAYLOAD_TOO_LARGE,
+    )
+    .into_response()
+}
+
+/// Answers 400 for JSON bodies nested too deeply or with too many fields,
+/// and 413 for ones over the size limit
+pub async fn json_limits_middleware(
+    State(limits): State<Arc<JsonLimits>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    if limits.is_unlimited() || !is_json(&request) {
+        return next.run(request).await;
+    }
+
+    let declared = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    if declared.is_some_and(|length| limits.exceeds_size(length)) {
+        return too_large(&limits);
+    }
+
+    let (parts, mut body) = request.into_parts();
+    let mut scanner = JsonScanner::default();
+    let mut buffered = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let Ok(chunk) = chunk else {
+            return StatusCode::BAD_REQUEST.into_response();
+        };
+        if let Err(violation) = scanner.feed(&chunk, &limits) {
+            warn!(path = %parts.uri.path(), ?violation, "Refused JSON body past structural limits");
+            let message = match violation {
+                JsonLimitViolation::TooDeep { limit } => {
+                    format!("The request body is nested more than {} levels deep", limit)
+                }
+                JsonLimitViolation::TooManyFields { limit } => {
+                    format!("The request body has more than {} fields", limit)
+                }
+            };
+            return UserFriendlyError::new(message, "PAYLOAD_TOO_COMPLEX", StatusCode::BAD_REQUEST)
+                .into_response();
+        }
+        if limits.exceeds_size(buffered.len() + chunk.len()) {
+            warn!(path = %parts.uri.path(), "Refused JSON body past the size limit");
+            return too_large(&limits);
+        }
+        buffered.extend_from_slice(&chunk);
+    }
+
+    next.run(Request::from_parts(parts, Body::from(buffered))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::post, Json, Router};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tower::ServiceExt;
+
+    const LIMITS: JsonLimits = JsonLimits { max_depth: 4, max_fields: 3, max_bytes: 64 };
+
+    fn scan(body: &str) -> Result<(), JsonLimitViolation> {
+        JsonScanner::default().feed(body.as_bytes(), &LIMITS)
+    }
+
+    #[test]
+    fn test_scanner_counts_structure_outside_strings() {
+        assert!(scan(r#"{"a": [[1, 2]], "b": {"c": 1}}"#).is_ok());
+        // Brackets, colons and escaped quotes inside strings don't count
+        assert!(scan(r#"{"text": "[[[[[{a:b}:c:d]]]]] \" :::"}"#).is_ok());
+
+        assert_eq!(scan("[[[[[1]]]]]"), Err(JsonLimitViolation::TooDeep { limit: 4 }));
+        assert_eq!(
+            scan(r#"{"a": 1, "b": 2, "c": 3, "d": 4}"#),
+            Err(JsonLimitViolation::TooManyFields { limit: 3 })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deeply_nested_body_is_rejected_before_parsing() {
+        static PARSED: AtomicBool = AtomicBool::new(false);
+        let app = Router::new()
+            .route(
+                "/api/rooms",
+                post(|Json(_): Json<serde_json::Value>| async {
+                    PARSED.store(true, Ordering::SeqCst);
+                    StatusCode::CREATED
+                }),
+            )
+            .layer(middleware::from_fn_with_state(Arc::new(LIMITS), json_limits_middleware));
+
+        let request = |body: String| {
+            Request::post("/api/rooms")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let nested = format!("{}{}", "[".repeat(10_000), "]".repeat(10_000));
+        let response = app.clone().oneshot(request(nested)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(!PARSED.load(Ordering::SeqCst));
+
+        // Flat but long: refused once it passes the size limit, declared or not
+        let long = format!(r#"{{"name": "{}"}}"#, "x".repeat(100));
+        let response = app.clone().oneshot(request(long.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let chunks: Vec<Result<_, std::io::Error>> = long.into_bytes().chunks(16).map(|c| Ok(c.to_vec())).collect();
+        let streamed = Request::post("/api/rooms")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::wrap_stream(futures_util::stream::iter(chunks)))
+            .unwrap();
+        let response = app.clone().oneshot(streamed).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(!PARSED.load(Ordering::SeqCst));
+
+        let response = app.oneshot(request(r#"{"name": "ok"}"#.to_string())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(PARSED.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_size_limit_applies_without_structural_limits() {
+        let size_only = JsonLimits { max_depth: 0, max_fields: 0, max_bytes: 64 };
+        let app = Router::new()
+            .route("/api/rooms", post(|Json(_): Json<serde_json::Value>| async { StatusCode::CREATED }))
+            .layer(middleware::from_fn_with_state(Arc::new(size_only), json_limits_middleware));
+
+        let long = format!(r#"{{"name": "{}"}}"#, "x".repeat(100));
+        let chunks: Vec<Result<_, std::io::Error>> = long.into_bytes().chunks(16).map(|c| Ok(c.to_vec())).collect();
+        let streamed = Request::post("/api/rooms")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::wrap_stream(futures_util::stream::iter(chunks)))
+            .unwrap();
+        let response = app.clone().oneshot(streamed).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let nested = Request::post("/api/rooms")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("[[[[[[[[1]]]]]]]]"))
+            .unwrap();
+        let response = app.oneshot(nested).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+}
@@ -0,0 +1,145 @@
+use axum::{
+    extract::State,
+    http::{Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::database::DatabaseWriter;
+use crate::logging::error_handling::UserFriendlyError;
+
+/// Seconds clients are told to wait before retrying a shed write
+pub const SHED_RETRY_AFTER_SECS: u64 = 5;
+
+/// Turns write requests away while the database writer is backed up
+///
+/// Every write goes through one serialized writer, so a deep queue means
+/// writes are already waiting long enough to time out. Refusing new ones
+/// early lets the queue drain, while reads, health checks and WebSocket
+/// upgrades (all GETs) keep working; sends over an open socket are shed by
+/// the socket handler. Shedding stops as soon as the queue is back under the
+/// threshold.
+pub struct WriteLoadShedder {
+    writer: Arc<dyn DatabaseWriter>,
+    threshold: usize,
+    shedding: AtomicBool,
+}
+
+impl WriteLoadShedder {
+    /// Sheds writes once `threshold` of them are queued for `writer`
+    pub fn new(writer: Arc<dyn DatabaseWriter>, threshold: usize) -> Self {
+        Self {
+            writer,
+            threshold,
+            shedding: AtomicBool::new(false),
+        }
+    }
+
+    /// Whether writes should be shed right now
+    pub fn is_overloaded(&self) -> bool {
+        let depth = self.writer.queue_depth();
+        let overloaded = depth >= self.threshold;
+        if self.shedding.swap(overloaded, Ordering::Relaxed) != overloaded {
+            if overloaded {
+                warn!(queue_depth = depth, threshold = self.threshold, "Database writer overloaded, shedding writes");
+            } else {
+                info!(queue_depth = depth, "Database writer recovered, accepting writes");
+            }
+        }
+        overloaded
+    }
+}
+
+fn is_write(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Answers write requests with 503 while the writer is overloaded
+pub async fn write_load_shedding_middleware<B>(
+    State(shedder): State<Arc<WriteLoadShedder>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if is_write(request.method()) && shedder.is_overloaded() {
+        metrics::counter!("writes_shed_total", 1);
+        return UserFriendlyError::new(
+            "The server is busy. Please try again in a few seconds.",
+            "OVERLOADED",
+            StatusCode::SERVICE_UNAVAILABLE,
+        )
+        .with_retry_after(SHED_RETRY_AFTER_SECS)
+        .into_response();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::CampfireDatabase;
+    use crate::models::{Session, UserId};
+    use axum::{body::Body, middleware, routing::get, Router};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn request(method: Method, uri: &str) -> Request<Body> {
+        Request::builder().method(method).uri(uri).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_overload_sheds_writes_but_serves_reads() {
+        let db = CampfireDatabase::new(":memory:").await.unwrap();
+        let shedder = Arc::new(WriteLoadShedder::new(db.writer(), 3));
+        let app = Router::new()
+            .route("/api/rooms", get(|| async { "rooms" }).post(|| async { "created" }))
+            .route("/health", get(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(shedder.clone(), write_load_shedding_middleware));
+
+        // Another connection's open write transaction stalls the writer, so
+        // writes pile up in its queue
+        let mut blocker = db.pool().begin().await.unwrap();
+        sqlx::query("DELETE FROM sessions WHERE 0")
+            .execute(&mut blocker)
+            .await
+            .unwrap();
+        let writes: Vec<_> = (0..5)
+            .map(|i| {
+                let writer = db.writer();
+                tokio::spawn(async move {
+                    writer
+                        .create_session(Session {
+                            token: format!("token-{}", i),
+                            user_id: UserId::new(),
+                            created_at: chrono::Utc::now(),
+                            expires_at: chrono::Utc::now() + chrono::Duration::hours(1),
+                        })
+                        .await
+                })
+            })
+            .collect();
+        while db.writer().queue_depth() < 3 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let response = app.clone().oneshot(request(Method::POST, "/api/rooms")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()["retry-after"], "5");
+        let response = app.clone().oneshot(request(Method::GET, "/api/rooms")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.clone().oneshot(request(Method::GET, "/health")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Writes are accepted again once the queue drains
+        blocker.rollback().await.unwrap();
+        for write in writes {
+            let _ = write.await.unwrap();
+        }
+        let response = app.oneshot(request(Method::POST, "/api/rooms")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!shedder.is_overloaded());
+    }
+}
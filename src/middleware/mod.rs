@@ -3,8 +3,22 @@ pub mod security;
 pub mod setup;
 pub mod error_handling;
 pub mod rate_limiting;
+pub mod path_id;
+pub mod client_ip;
+pub mod ws_origin;
+pub mod load_shedding;
+pub mod timeout;
+pub mod json_limits;
+pub mod daily_quota;
 
-pub use session::{AuthenticatedUser, OptionalAuthenticatedUser, SessionToken};
+pub use session::{AuthenticatedUser, ImpersonationRefused, OptionalAuthenticatedUser, SessionToken};
+pub use path_id::{PathId, parse_path_id};
+pub use client_ip::{ClientIp, TrustedProxies, client_ip_middleware};
+pub use ws_origin::{WsOriginPolicy, ws_origin_middleware};
+pub use load_shedding::{WriteLoadShedder, write_load_shedding_middleware};
+pub use timeout::{RequestTimeouts, request_timeout_middleware};
+pub use json_limits::{JsonLimits, json_limits_middleware};
+pub use daily_quota::{DailyQuota, QuotaUsage, daily_quota_middleware};
 pub use setup::{setup_detection_middleware, setup_completion_middleware};
 pub use error_handling::{
     global_error_handler, 
@@ -13,7 +27,7 @@ pub use error_handling::{
     panic_recovery_middleware,
     timeout_middleware,
 };
-pub use rate_limiting::{RateLimitingMiddleware, RateLimitConfig, create_rate_limiting_layer};
+pub use rate_limiting::{RateLimitingMiddleware, RateLimitConfig, create_rate_limiting_layer, rate_limiting_middleware};
 pub use security::{
     CsrfProtection, BotAbuseProtection, 
     create_csrf_protection_layer, create_bot_abuse_protection_layer,
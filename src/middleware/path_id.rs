@@ -0,0 +1,162 @@
+use axum::{
+    async_trait,
+    extract::{rejection::PathRejection, FromRequestParts, Path},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::models::{ConnectionId, ExportId, MessageId, PushSubscriptionId, RoomId, RoomWebhookId, UserId};
+
+/// Typed id that can be parsed from a `:id` path segment
+pub trait PathIdKind: From<Uuid> {
+    /// Used in error messages and codes, e.g. "room" -> INVALID_ROOM_ID
+    const KIND: &'static str;
+}
+
+impl PathIdKind for RoomId {
+    const KIND: &'static str = "room";
+}
+
+impl PathIdKind for MessageId {
+    const KIND: &'static str = "message";
+}
+
+impl PathIdKind for UserId {
+    const KIND: &'static str = "user";
+}
+
+impl PathIdKind for PushSubscriptionId {
+    const KIND: &'static str = "subscription";
+}
+
+impl PathIdKind for RoomWebhookId {
+    const KIND: &'static str = "webhook";
+}
+
+impl PathIdKind for ConnectionId {
+    const KIND: &'static str = "connection";
+}
+
+impl PathIdKind for ExportId {
+    const KIND: &'static str = "export";
+}
+
+/// Path id extractor that rejects malformed UUIDs with 400 Bad Request
+///
+/// # Usage
+/// ```rust
+/// use axum::{response::IntoResponse, Json};
+/// use campfire_on_rust::middleware::PathId;
+/// use campfire_on_rust::models::RoomId;
+///
+/// async fn get_room(PathId(room_id): PathId<RoomId>) -> impl IntoResponse {
+///     // room_id is a valid RoomId
+///     Json(room_id)
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct PathId<T>(pub T);
+
+#[derive(Debug)]
+pub enum PathIdRejection {
+    InvalidId { kind: &'static str, value: String },
+    Path(PathRejection),
+}
+
+/// Parses a single id, for routes whose path carries more than one parameter
+pub fn parse_path_id<T: PathIdKind>(value: &str) -> Result<T, PathIdRejection> {
+    Uuid::parse_str(value)
+        .map(T::from)
+        .map_err(|_| PathIdRejection::InvalidId {
+            kind: T::KIND,
+            value: value.to_string(),
+        })
+}
+
+#[async_trait]
+impl<S, T> FromRequestParts<S> for PathId<T>
+where
+    S: Send + Sync,
+    T: PathIdKind + Send,
+{
+    type Rejection = PathIdRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(value) = Path::<String>::from_request_parts(parts, state)
+            .await
+            .map_err(PathIdRejection::Path)?;
+
+        parse_path_id(&value).map(PathId)
+    }
+}
+
+impl IntoResponse for PathIdRejection {
+    fn into_response(self) -> Response {
+        match self {
+            PathIdRejection::InvalidId { kind, value } => {
+                let status = StatusCode::BAD_REQUEST;
+                let body = Json(json!({
+                    "error": format!("Invalid {} ID format: {}", kind, value),
+                    "code": format!("INVALID_{}_ID", kind.to_uppercase()),
+                    "status": status.as_u16()
+                }));
+
+                (status, body).into_response()
+            }
+            PathIdRejection::Path(rejection) => rejection.into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    async fn get_room(PathId(room_id): PathId<RoomId>) -> String {
+        room_id.to_string()
+    }
+
+    #[test]
+    fn test_parse_path_id() {
+        let uuid = Uuid::new_v4();
+        let room_id: RoomId = parse_path_id(&uuid.to_string()).unwrap();
+        assert_eq!(room_id.0, uuid);
+
+        assert!(matches!(
+            parse_path_id::<MessageId>("invalid-uuid"),
+            Err(PathIdRejection::InvalidId { kind: "message", .. })
+        ));
+        assert!(matches!(
+            parse_path_id::<ExportId>("../other-export"),
+            Err(PathIdRejection::InvalidId { kind: "export", .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_room_id_is_bad_request() {
+        let app = Router::new().route("/api/rooms/:id", get(get_room));
+
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri("/api/rooms/not-a-uuid").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "INVALID_ROOM_ID");
+
+        let uuid = Uuid::new_v4();
+        let response = app
+            .oneshot(Request::builder().uri(format!("/api/rooms/{}", uuid)).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -1,11 +1,12 @@
 use axum::{
     extract::ConnectInfo,
-    http::{Request, StatusCode},
+    http::{HeaderMap, HeaderValue, Request, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
 };
 use governor::{
-    clock::DefaultClock,
+    clock::{Clock, DefaultClock},
+    middleware::StateInformationMiddleware,
     state::{InMemoryState, NotKeyed},
     Quota, RateLimiter,
 };
@@ -19,16 +20,23 @@ use std::{
 use tracing::{debug, warn};
 
 use crate::logging::audit::{AuditAction, AuditLogger};
+use crate::middleware::client_ip::ClientIp;
+
+/// A single client's limiter; checks report the state behind each decision
+/// so it can be sent back in headers
+type Limiter = RateLimiter<NotKeyed, InMemoryState, DefaultClock, StateInformationMiddleware>;
 
 /// Rate limiter for different endpoint types
 #[derive(Clone)]
 pub struct RateLimitingMiddleware {
     /// General API rate limiter (per IP)
-    general_limiter: Arc<RwLock<HashMap<IpAddr, Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>>>>,
+    general_limiter: Arc<RwLock<HashMap<IpAddr, Arc<Limiter>>>>,
     /// Authentication endpoint rate limiter (stricter)
-    auth_limiter: Arc<RwLock<HashMap<IpAddr, Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>>>>,
+    auth_limiter: Arc<RwLock<HashMap<IpAddr, Arc<Limiter>>>>,
+    /// Search rate limiter (per IP)
+    search_limiter: Arc<RwLock<HashMap<IpAddr, Arc<Limiter>>>>,
     /// Bot API rate limiter (per bot token)
-    bot_limiter: Arc<RwLock<HashMap<String, Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>>>>,
+    bot_limiter: Arc<RwLock<HashMap<String, Arc<Limiter>>>>,
     /// Configuration
     config: RateLimitConfig,
     /// Audit logger for rate limit violations
@@ -41,10 +49,14 @@ pub struct RateLimitConfig {
     pub general_rpm: u32,
     /// Authentication requests per minute per IP
     pub auth_rpm: u32,
+    /// Search requests per minute per IP
+    pub search_rpm: u32,
     /// Bot API requests per minute per token
     pub bot_rpm: u32,
     /// Burst allowance (requests that can be made immediately)
     pub burst_size: u32,
+    /// Send `X-RateLimit-*` headers on every rate-limited response
+    pub headers: bool,
 }
 
 impl Default for RateLimitConfig {
@@ -52,10 +64,66 @@ impl Default for RateLimitConfig {
         Self {
             general_rpm: 60,
             auth_rpm: 10,
+            search_rpm: 30,
             bot_rpm: 100,
             burst_size: 10,
+            headers: true,
+        }
+    }
+}
+
+/// Per-IP limiters, chosen by path
+#[derive(Debug, Clone, Copy)]
+enum IpLimit {
+    General,
+    Auth,
+    Search,
+}
+
+/// Where a client stands against the limiter that handled its request
+///
+/// `limit` is the burst capacity, the most requests that can be made back
+/// to back; `remaining` counts down from it and refills at the configured
+/// rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the full `limit` is available again
+    pub reset_secs: u64,
+}
+
+impl RateLimitStatus {
+    /// After an allowed request, with `remaining` requests still available
+    fn allowed(quota: Quota, remaining: u32) -> Self {
+        let limit = quota.burst_size().get();
+        Self {
+            limit,
+            remaining,
+            reset_secs: ceil_secs(quota.replenish_interval() * (limit - remaining)),
+        }
+    }
+
+    /// After a refused request, when the next one is allowed in `wait`
+    fn refused(quota: Quota, wait: Duration) -> Self {
+        let limit = quota.burst_size().get();
+        Self {
+            limit,
+            remaining: 0,
+            reset_secs: ceil_secs(wait + quota.replenish_interval() * (limit - 1)),
         }
     }
+
+    /// Adds `X-RateLimit-Limit`, `X-RateLimit-Remaining` and `X-RateLimit-Reset`
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        headers.insert("X-RateLimit-Limit", HeaderValue::from(self.limit));
+        headers.insert("X-RateLimit-Remaining", HeaderValue::from(self.remaining));
+        headers.insert("X-RateLimit-Reset", HeaderValue::from(self.reset_secs));
+    }
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
 }
 
 impl RateLimitingMiddleware {
@@ -63,43 +131,51 @@ impl RateLimitingMiddleware {
         Self {
             general_limiter: Arc::new(RwLock::new(HashMap::new())),
             auth_limiter: Arc::new(RwLock::new(HashMap::new())),
+            search_limiter: Arc::new(RwLock::new(HashMap::new())),
             bot_limiter: Arc::new(RwLock::new(HashMap::new())),
             config,
             audit_logger: AuditLogger::new(true),
         }
     }
 
+    fn limiter(quota: Quota) -> Arc<Limiter> {
+        Arc::new(RateLimiter::direct(quota).with_middleware::<StateInformationMiddleware>())
+    }
+
     /// Create rate limiter for general API endpoints
-    fn create_general_limiter(&self) -> Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>> {
+    fn create_general_limiter(&self) -> Arc<Limiter> {
         let quota = Quota::per_minute(NonZeroU32::new(self.config.general_rpm).unwrap())
             .allow_burst(NonZeroU32::new(self.config.burst_size).unwrap());
-        Arc::new(RateLimiter::direct(quota))
+        Self::limiter(quota)
     }
 
     /// Create rate limiter for authentication endpoints
-    fn create_auth_limiter(&self) -> Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>> {
+    fn create_auth_limiter(&self) -> Arc<Limiter> {
         let quota = Quota::per_minute(NonZeroU32::new(self.config.auth_rpm).unwrap())
             .allow_burst(NonZeroU32::new(5).unwrap()); // Lower burst for auth
-        Arc::new(RateLimiter::direct(quota))
+        Self::limiter(quota)
+    }
+
+    /// Create rate limiter for search endpoints
+    fn create_search_limiter(&self) -> Arc<Limiter> {
+        let quota = Quota::per_minute(NonZeroU32::new(self.config.search_rpm).unwrap())
+            .allow_burst(NonZeroU32::new(self.config.burst_size).unwrap());
+        Self::limiter(quota)
     }
 
     /// Create rate limiter for bot API endpoints
-    fn create_bot_limiter(&self) -> Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>> {
+    fn create_bot_limiter(&self) -> Arc<Limiter> {
         let quota = Quota::per_minute(NonZeroU32::new(self.config.bot_rpm).unwrap())
             .allow_burst(NonZeroU32::new(self.config.burst_size * 2).unwrap()); // Higher burst for bots
-        Arc::new(RateLimiter::direct(quota))
+        Self::limiter(quota)
     }
 
     /// Get or create rate limiter for IP address
-    fn get_ip_limiter(
-        &self,
-        ip: IpAddr,
-        is_auth: bool,
-    ) -> Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>> {
-        let limiter_map = if is_auth {
-            &self.auth_limiter
-        } else {
-            &self.general_limiter
+    fn get_ip_limiter(&self, ip: IpAddr, kind: IpLimit) -> Arc<Limiter> {
+        let limiter_map = match kind {
+            IpLimit::General => &self.general_limiter,
+            IpLimit::Auth => &self.auth_limiter,
+            IpLimit::Search => &self.search_limiter,
         };
 
         // Try to get existing limiter
@@ -111,10 +187,10 @@ impl RateLimitingMiddleware {
         }
 
         // Create new limiter
-        let new_limiter = if is_auth {
-            self.create_auth_limiter()
-        } else {
-            self.create_general_limiter()
+        let new_limiter = match kind {
+            IpLimit::General => self.create_general_limiter(),
+            IpLimit::Auth => self.create_auth_limiter(),
+            IpLimit::Search => self.create_search_limiter(),
         };
 
         // Store new limiter
@@ -127,7 +203,7 @@ impl RateLimitingMiddleware {
     }
 
     /// Get or create rate limiter for bot token
-    fn get_bot_limiter(&self, bot_token: &str) -> Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>> {
+    fn get_bot_limiter(&self, bot_token: &str) -> Arc<Limiter> {
         // Try to get existing limiter
         {
             let read_guard = self.bot_limiter.read().unwrap();
@@ -149,14 +225,21 @@ impl RateLimitingMiddleware {
     }
 
     /// Check if request should be rate limited
-    pub async fn check_rate_limit(&self, request: &Request<axum::body::Body>, addr: SocketAddr) -> Result<(), RateLimitError> {
+    ///
+    /// Returns where the client stands against the limiter that applied, or
+    /// `None` for paths that aren't rate limited.
+    pub async fn check_rate_limit(
+        &self,
+        request: &Request<axum::body::Body>,
+        addr: SocketAddr,
+    ) -> Result<Option<RateLimitStatus>, RateLimitError> {
         let path = request.uri().path();
         let ip = addr.ip();
 
         // Determine rate limit type based on path
         let (limiter, limit_type) = if path.starts_with("/api/auth/") {
             // Authentication endpoints - stricter limits
-            (self.get_ip_limiter(ip, true), "auth")
+            (self.get_ip_limiter(ip, IpLimit::Auth), "auth")
         } else if path.starts_with("/rooms/") && path.contains("/bot/") {
             // Bot API endpoints - check for bot token
             if let Some(bot_token) = extract_bot_token_from_path(path) {
@@ -164,21 +247,27 @@ impl RateLimitingMiddleware {
             } else {
                 return Err(RateLimitError::InvalidBotToken);
             }
+        } else if path == "/api/search" || path.starts_with("/api/search/") {
+            // Search endpoints - full-text queries are expensive
+            (self.get_ip_limiter(ip, IpLimit::Search), "search")
         } else if path.starts_with("/api/") {
             // General API endpoints
-            (self.get_ip_limiter(ip, false), "general")
+            (self.get_ip_limiter(ip, IpLimit::General), "general")
         } else {
             // Static content and pages - no rate limiting
-            return Ok(());
+            return Ok(None);
         };
 
         // Check rate limit
         match limiter.check() {
-            Ok(_) => {
+            Ok(snapshot) => {
                 debug!("Rate limit check passed for {} from {}", limit_type, ip);
-                Ok(())
+                Ok(Some(RateLimitStatus::allowed(
+                    snapshot.quota(),
+                    snapshot.remaining_burst_capacity(),
+                )))
             }
-            Err(_) => {
+            Err(not_until) => {
                 warn!("Rate limit exceeded for {} from {} on path {}", limit_type, ip, path);
                 
                 // Log rate limit violation
@@ -194,9 +283,14 @@ impl RateLimitingMiddleware {
                     details,
                 );
                 
+                let wait = not_until.wait_time_from(DefaultClock::default().now());
                 Err(RateLimitError::RateLimitExceeded {
                     limit_type: limit_type.to_string(),
-                    retry_after: Duration::from_secs(60),
+                    retry_after: Duration::from_secs(ceil_secs(wait).max(1)),
+                    status: self
+                        .config
+                        .headers
+                        .then(|| RateLimitStatus::refused(not_until.quota(), wait)),
                 })
             }
         }
@@ -222,6 +316,13 @@ impl RateLimitingMiddleware {
             }
         }
         
+        {
+            let mut search = self.search_limiter.write().unwrap();
+            if search.len() > max_limiters {
+                search.clear();
+            }
+        }
+        
         {
             let mut bot = self.bot_limiter.write().unwrap();
             if bot.len() > max_limiters {
@@ -237,6 +338,8 @@ pub enum RateLimitError {
     RateLimitExceeded {
         limit_type: String,
         retry_after: Duration,
+        /// Sent as `X-RateLimit-*` headers when they're enabled
+        status: Option<RateLimitStatus>,
     },
     
     #[error("Invalid or missing bot token")]
@@ -246,7 +349,7 @@ pub enum RateLimitError {
 impl IntoResponse for RateLimitError {
     fn into_response(self) -> Response {
         match self {
-            RateLimitError::RateLimitExceeded { retry_after, .. } => {
+            RateLimitError::RateLimitExceeded { retry_after, status, .. } => {
                 let mut response = (
                     StatusCode::TOO_MANY_REQUESTS,
                     axum::Json(serde_json::json!({
@@ -261,6 +364,9 @@ impl IntoResponse for RateLimitError {
                     "Retry-After",
                     retry_after.as_secs().to_string().parse().unwrap(),
                 );
+                if let Some(status) = status {
+                    status.apply(response.headers_mut());
+                }
                 
                 response
             }
@@ -291,22 +397,38 @@ fn extract_bot_token_from_path(path: &str) -> Option<String> {
 /// Middleware function for rate limiting
 pub async fn rate_limiting_middleware(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIp(client_ip): ClientIp,
     request: Request<axum::body::Body>,
     next: Next<axum::body::Body>,
 ) -> Response {
+    // Key on the resolved client, not the proxy we're connected to
+    let addr = SocketAddr::new(client_ip, addr.port());
+
     // Get rate limiter from request extensions (set during app setup)
     let rate_limiter = request
         .extensions()
         .get::<RateLimitingMiddleware>()
         .cloned();
 
-    if let Some(limiter) = rate_limiter {
-        if let Err(rate_limit_error) = limiter.check_rate_limit(&request, addr).await {
-            return rate_limit_error.into_response();
-        }
-    }
+    let Some(limiter) = rate_limiter else {
+        return next.run(request).await;
+    };
+
+    let status = match limiter.check_rate_limit(&request, addr).await {
+        Ok(status) => status,
+        Err(rate_limit_error) => return rate_limit_error.into_response(),
+    };
 
-    next.run(request).await
+    let mut response = next.run(request).await;
+    // A tighter limit further in (like the per-user message rate) that
+    // refused the request reports its own state
+    if response.headers().contains_key("X-RateLimit-Limit") {
+        return response;
+    }
+    if let Some(status) = status.filter(|_| limiter.config.headers) {
+        status.apply(response.headers_mut());
+    }
+    response
 }
 
 /// Create rate limiting layer with configuration
@@ -326,8 +448,10 @@ mod tests {
         let config = RateLimitConfig {
             general_rpm: 2, // Very low for testing
             auth_rpm: 1,
+            search_rpm: 2,
             bot_rpm: 3,
             burst_size: 1,
+            headers: true,
         };
         
         let middleware = RateLimitingMiddleware::new(config);
@@ -355,8 +479,10 @@ mod tests {
         let config = RateLimitConfig {
             general_rpm: 10,
             auth_rpm: 1, // Very strict for testing
+            search_rpm: 10,
             bot_rpm: 10,
             burst_size: 1,
+            headers: true,
         };
         
         let middleware = RateLimitingMiddleware::new(config);
@@ -399,8 +525,10 @@ mod tests {
         let config = RateLimitConfig {
             general_rpm: 10,
             auth_rpm: 5,
+            search_rpm: 10,
             bot_rpm: 2, // Low for testing
             burst_size: 1,
+            headers: true,
         };
         
         let middleware = RateLimitingMiddleware::new(config);
@@ -422,4 +550,90 @@ mod tests {
         // Third request should be rate limited
         assert!(middleware.check_rate_limit(&request, addr).await.is_err());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_rate_limit_headers_count_down_on_every_limiter() {
+        use axum::{routing::any, Extension, Router};
+        use tower::ServiceExt;
+
+        let limiter = RateLimitingMiddleware::new(RateLimitConfig {
+            general_rpm: 60,
+            auth_rpm: 10,
+            search_rpm: 30,
+            bot_rpm: 60,
+            burst_size: 3,
+            headers: true,
+        });
+        let app = Router::new()
+            .fallback(any(|| async { StatusCode::OK }))
+            .layer(axum::middleware::from_fn(rate_limiting_middleware))
+            .layer(Extension(limiter));
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080);
+
+        let header = |response: &Response, name: &str| -> u64 {
+            response.headers()[name].to_str().unwrap().parse().unwrap()
+        };
+
+        for (path, burst) in [
+            ("/api/rooms", 3),
+            ("/api/search?q=hello", 3),
+            ("/rooms/123/bot/test-token/messages", 6),
+        ] {
+            for expected_remaining in (0..burst).rev() {
+                let mut request = Request::get(path).body(axum::body::Body::empty()).unwrap();
+                request.extensions_mut().insert(ConnectInfo(addr));
+                let response = app.clone().oneshot(request).await.unwrap();
+
+                assert_eq!(response.status(), StatusCode::OK, "{}", path);
+                assert_eq!(header(&response, "X-RateLimit-Limit"), burst, "{}", path);
+                assert_eq!(header(&response, "X-RateLimit-Remaining"), expected_remaining, "{}", path);
+                assert!(header(&response, "X-RateLimit-Reset") >= 1, "{}", path);
+            }
+
+            let mut request = Request::get(path).body(axum::body::Body::empty()).unwrap();
+            request.extensions_mut().insert(ConnectInfo(addr));
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS, "{}", path);
+            assert_eq!(header(&response, "X-RateLimit-Limit"), burst, "{}", path);
+            assert_eq!(header(&response, "X-RateLimit-Remaining"), 0, "{}", path);
+            assert!(header(&response, "X-RateLimit-Reset") >= header(&response, "Retry-After"));
+        }
+
+        // Pages aren't limited, so they carry no headers
+        let mut request = Request::get("/").body(axum::body::Body::empty()).unwrap();
+        request.extensions_mut().insert(ConnectInfo(addr));
+        let response = app.oneshot(request).await.unwrap();
+        assert!(response.headers().get("X-RateLimit-Limit").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_inner_limit_headers_are_kept() {
+        use axum::{routing::post, Extension, Router};
+        use tower::ServiceExt;
+
+        // The per-user message limit refuses with its own numbers
+        let message_limit = RateLimitStatus { limit: 5, remaining: 0, reset_secs: 42 };
+        let app = Router::new()
+            .route(
+                "/api/rooms/123/messages",
+                post(move || async move {
+                    let mut response = StatusCode::TOO_MANY_REQUESTS.into_response();
+                    message_limit.apply(response.headers_mut());
+                    response
+                }),
+            )
+            .layer(axum::middleware::from_fn(rate_limiting_middleware))
+            .layer(Extension(RateLimitingMiddleware::new(RateLimitConfig::default())));
+
+        let mut request = Request::post("/api/rooms/123/messages").body(axum::body::Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080)));
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["X-RateLimit-Limit"], "5");
+        assert_eq!(response.headers()["X-RateLimit-Remaining"], "0");
+        assert_eq!(response.headers()["X-RateLimit-Reset"], "42");
+    }
+}
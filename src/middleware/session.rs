@@ -8,7 +8,8 @@ use axum::{
 use serde_json::json;
 
 use crate::errors::AuthError;
-use crate::models::User;
+use crate::logging::audit::{AuditAction, AuditEvent};
+use crate::models::{User, UserId};
 use crate::AppState;
 
 /// Authenticated user extractor that validates session tokens
@@ -27,6 +28,49 @@ use crate::AppState;
 #[derive(Debug, Clone)]
 pub struct AuthenticatedUser {
     pub user: User,
+    /// The admin acting as this user, when the session was opened by
+    /// impersonation
+    pub impersonated_by: Option<UserId>,
+}
+
+impl AuthenticatedUser {
+    /// Refuses actions an impersonating admin must not take on the user's
+    /// behalf, like changing credentials or signing them out everywhere
+    pub fn forbid_impersonation(&self, action: &str) -> Result<(), ImpersonationRefused> {
+        match self.impersonated_by {
+            Some(admin_id) => {
+                tracing::warn!(
+                    "Admin {} tried to {} while impersonating user {}",
+                    admin_id, action, self.user.id
+                );
+                Err(ImpersonationRefused)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// An action refused to an impersonation session; answers 403
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImpersonationRefused;
+
+impl IntoResponse for ImpersonationRefused {
+    fn into_response(self) -> Response {
+        let body = Json(json!({
+            "error": "Not allowed while impersonating",
+            "code": StatusCode::FORBIDDEN.as_u16()
+        }));
+        (StatusCode::FORBIDDEN, body).into_response()
+    }
+}
+
+/// Audit record for a mutating request made with an impersonation session
+fn impersonated_request_audit_event(admin_id: UserId, user_id: UserId, parts: &Parts) -> AuditEvent {
+    AuditEvent::new(AuditAction::ImpersonatedRequest, "request")
+        .with_user(admin_id)
+        .with_resource_id(parts.uri.path().to_string())
+        .with_detail("impersonated_user_id", user_id.to_string())
+        .with_detail("method", parts.method.to_string())
 }
 
 /// Session extraction middleware implementation
@@ -41,17 +85,32 @@ impl FromRequestParts<AppState> for AuthenticatedUser {
     type Rejection = SessionExtractionError;
 
     async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        // Middleware that needed the user first leaves it for the handler
+        if let Some(auth_user) = parts.extensions.get::<AuthenticatedUser>() {
+            return Ok(auth_user.clone());
+        }
+
         // Extract session token from headers or cookies
         let token = extract_session_token(parts)?;
 
         // Validate session and get user using auth service from AppState
-        let user = state
+        let (user, impersonated_by) = state
             .auth_service
-            .validate_session(token)
+            .validate_session_with_impersonator(token)
             .await
             .map_err(SessionExtractionError::from)?;
 
-        Ok(AuthenticatedUser { user })
+        // Reads are covered by the audit entry for opening the session;
+        // anything that changes state is recorded against the admin
+        if let Some(admin_id) = impersonated_by {
+            if !parts.method.is_safe() {
+                impersonated_request_audit_event(admin_id, user.id, parts).log();
+            }
+        }
+
+        let auth_user = AuthenticatedUser { user, impersonated_by };
+        parts.extensions.insert(auth_user.clone());
+        Ok(auth_user)
     }
 }
 
@@ -348,4 +407,28 @@ mod tests {
         let token = extract_session_token(&parts).unwrap();
         assert_eq!(token, "my_token");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_impersonated_sessions_are_refused_and_audited_against_the_admin() {
+        let user = User::for_tests("Member");
+        let own = AuthenticatedUser { user: user.clone(), impersonated_by: None };
+        assert!(own.forbid_impersonation("log out everywhere").is_ok());
+
+        let admin_id = UserId::new();
+        let impersonated = AuthenticatedUser { user: user.clone(), impersonated_by: Some(admin_id) };
+        let refused = impersonated.forbid_impersonation("log out everywhere").unwrap_err();
+        assert_eq!(refused.into_response().status(), StatusCode::FORBIDDEN);
+
+        let (parts, _) = Request::builder()
+            .method("DELETE")
+            .uri("/api/messages/123")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let event = impersonated_request_audit_event(admin_id, user.id, &parts);
+        assert_eq!(event.user_id, Some(admin_id));
+        assert_eq!(event.resource_id.as_deref(), Some("/api/messages/123"));
+        assert_eq!(event.details.get("method").map(String::as_str), Some("DELETE"));
+        assert_eq!(event.details.get("impersonated_user_id"), Some(&user.id.to_string()));
+    }
+}
@@ -0,0 +1,124 @@
+use axum::{
+    extract::State,
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::config::{RouteTimeout, ServerConfig};
+use crate::logging::error_handling::UserFriendlyError;
+
+/// Request time budgets: one default plus overrides for route groups
+///
+/// Searches and exports legitimately take longer than posting a message, so
+/// they get their own budget instead of stretching the one every endpoint
+/// shares. Overrides match by path prefix; the longest match wins.
+pub struct RequestTimeouts {
+    default: Duration,
+    overrides: Vec<(String, Duration)>,
+}
+
+impl RequestTimeouts {
+    pub fn new(default: Duration, overrides: &[RouteTimeout]) -> Self {
+        let mut overrides: Vec<_> = overrides
+            .iter()
+            .map(|route| (route.path_prefix.clone(), Duration::from_secs(route.timeout_secs)))
+            .collect();
+        overrides.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        Self { default, overrides }
+    }
+
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self::new(
+            Duration::from_secs(config.request_timeout_secs),
+            &config.request_timeout_overrides,
+        )
+    }
+
+    /// The budget for a request to `path`
+    pub fn for_path(&self, path: &str) -> Duration {
+        self.overrides
+            .iter()
+            .find(|(prefix, _)| {
+                path.strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/') || prefix.ends_with('/'))
+            })
+            .map_or(self.default, |(_, timeout)| *timeout)
+    }
+}
+
+/// Answers 504 once a request runs past the budget for its route
+pub async fn request_timeout_middleware<B>(
+    State(timeouts): State<Arc<RequestTimeouts>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let budget = timeouts.for_path(request.uri().path());
+    let path = request.uri().path().to_string();
+
+    match tokio::time::timeout(budget, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => {
+            warn!(path = %path, timeout_seconds = budget.as_secs(), "Request timed out");
+            UserFriendlyError::new(
+                format!("The request took longer than {} seconds to complete", budget.as_secs()),
+                "REQUEST_TIMEOUT",
+                StatusCode::GATEWAY_TIMEOUT,
+            )
+            .with_suggestions(vec!["Try again in a moment".to_string()])
+            .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn route(path_prefix: &str, timeout_secs: u64) -> RouteTimeout {
+        RouteTimeout { path_prefix: path_prefix.to_string(), timeout_secs }
+    }
+
+    #[test]
+    fn test_longest_prefix_wins() {
+        let timeouts = RequestTimeouts::new(
+            Duration::from_secs(30),
+            &[route("/api/users/me/export", 300), route("/api", 10)],
+        );
+
+        assert_eq!(timeouts.for_path("/api/users/me/export"), Duration::from_secs(300));
+        assert_eq!(timeouts.for_path("/api/users/me/export/abc"), Duration::from_secs(300));
+        assert_eq!(timeouts.for_path("/api/users/me/exports"), Duration::from_secs(10));
+        assert_eq!(timeouts.for_path("/api/rooms"), Duration::from_secs(10));
+        assert_eq!(timeouts.for_path("/health"), Duration::from_secs(30));
+    }
+
+    #[tokio::test]
+    async fn test_export_within_its_override_outlives_default_timeout() {
+        let slow = || async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            "done"
+        };
+        let timeouts = Arc::new(RequestTimeouts {
+            default: Duration::from_millis(20),
+            overrides: vec![("/api/users/me/export".to_string(), Duration::from_secs(5))],
+        });
+        let app = Router::new()
+            .route("/api/users/me/export", get(slow))
+            .route("/api/rooms", get(slow))
+            .layer(middleware::from_fn_with_state(timeouts, request_timeout_middleware));
+
+        let request = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        let response = app.clone().oneshot(request("/api/users/me/export")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app.oneshot(request("/api/rooms")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+}
@@ -0,0 +1,132 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::config::SecurityConfig;
+
+/// Which origins may open a WebSocket
+///
+/// Browsers don't apply CORS to WebSocket upgrades, so without this check any
+/// site could open `/ws` with the victim's session cookie (cross-site
+/// WebSocket hijacking). Browsers always send `Origin` on upgrades; native
+/// clients usually don't, which is what the missing-origin exemption is for.
+#[derive(Debug, Clone)]
+pub struct WsOriginPolicy {
+    /// Allowed origins; empty means same-origin only
+    allowed_origins: Vec<String>,
+    allow_missing_origin: bool,
+}
+
+impl WsOriginPolicy {
+    pub fn new(allowed_origins: Vec<String>, allow_missing_origin: bool) -> Self {
+        Self {
+            allowed_origins: allowed_origins
+                .into_iter()
+                .map(|origin| origin.trim_end_matches('/').to_ascii_lowercase())
+                .collect(),
+            allow_missing_origin,
+        }
+    }
+
+    /// Uses the CORS origins; an empty CORS list (allow all) still only
+    /// allows same-origin upgrades
+    pub fn from_config(config: &SecurityConfig) -> Self {
+        Self::new(config.cors_origins.clone(), config.ws_allow_missing_origin)
+    }
+
+    pub fn is_allowed(&self, headers: &HeaderMap) -> bool {
+        let origin = match headers.get(header::ORIGIN).map(|value| value.to_str()) {
+            None => return self.allow_missing_origin,
+            Some(Ok(origin)) => origin.trim_end_matches('/').to_ascii_lowercase(),
+            Some(Err(_)) => return false,
+        };
+
+        if self.allowed_origins.contains(&origin) {
+            return true;
+        }
+
+        // Same-origin: the Origin's host[:port] matches the Host we were reached on
+        let origin_authority = origin.split_once("://").map(|(_, authority)| authority);
+        let host = headers
+            .get(header::HOST)
+            .and_then(|value| value.to_str().ok())
+            .map(|host| host.to_ascii_lowercase());
+        matches!((origin_authority, host), (Some(authority), Some(host)) if authority == host)
+    }
+}
+
+/// Rejects WebSocket upgrades from origins the policy doesn't allow with 403
+pub async fn ws_origin_middleware<B>(
+    State(policy): State<Arc<WsOriginPolicy>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if !policy.is_allowed(request.headers()) {
+        warn!(
+            origin = ?request.headers().get(header::ORIGIN),
+            "Rejected WebSocket upgrade from disallowed origin"
+        );
+        return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn upgrade(origin: Option<&str>) -> Request<Body> {
+        let mut builder = Request::builder()
+            .uri("/ws")
+            .header("host", "chat.example.com")
+            .header("connection", "upgrade")
+            .header("upgrade", "websocket");
+        if let Some(origin) = origin {
+            builder = builder.header("origin", origin);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_from_disallowed_origin_is_rejected() {
+        let policy = Arc::new(WsOriginPolicy::new(vec!["https://app.example.com".to_string()], true));
+        let app = Router::new()
+            .route("/ws", get(|| async { "upgraded" }))
+            .layer(middleware::from_fn_with_state(policy, ws_origin_middleware));
+
+        let response = app.clone().oneshot(upgrade(Some("https://evil.example.net"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = app.clone().oneshot(upgrade(Some("https://app.example.com"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Same origin as the Host header
+        let response = app.clone().oneshot(upgrade(Some("https://chat.example.com"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Native clients without an Origin header, when exempted
+        let response = app.oneshot(upgrade(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_missing_origin_exemption_is_configurable() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, "chat.example.com".parse().unwrap());
+
+        assert!(WsOriginPolicy::new(vec![], true).is_allowed(&headers));
+        assert!(!WsOriginPolicy::new(vec![], false).is_allowed(&headers));
+
+        // An empty allow-list still refuses cross-site upgrades
+        headers.insert(header::ORIGIN, "https://evil.example.net".parse().unwrap());
+        assert!(!WsOriginPolicy::new(vec![], true).is_allowed(&headers));
+    }
+}
@@ -1,5 +1,6 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
 
 // Type-safe ID wrappers using newtype pattern
@@ -18,6 +19,12 @@ pub struct ConnectionId(pub Uuid);
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct PushSubscriptionId(pub Uuid);
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RoomWebhookId(pub Uuid);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ExportId(pub Uuid);
+
 // ID implementations
 impl UserId {
     pub fn new() -> Self {
@@ -49,6 +56,18 @@ impl PushSubscriptionId {
     }
 }
 
+impl RoomWebhookId {
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl Default for RoomWebhookId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // From/Into implementations for ergonomic conversions
 impl From<Uuid> for UserId {
     fn from(uuid: Uuid) -> Self {
@@ -110,6 +129,30 @@ impl From<PushSubscriptionId> for Uuid {
     }
 }
 
+impl From<Uuid> for RoomWebhookId {
+    fn from(uuid: Uuid) -> Self {
+        Self(uuid)
+    }
+}
+
+impl From<RoomWebhookId> for Uuid {
+    fn from(webhook_id: RoomWebhookId) -> Self {
+        webhook_id.0
+    }
+}
+
+impl From<Uuid> for ExportId {
+    fn from(uuid: Uuid) -> Self {
+        Self(uuid)
+    }
+}
+
+impl From<ExportId> for Uuid {
+    fn from(export_id: ExportId) -> Self {
+        export_id.0
+    }
+}
+
 // Display implementations for error messages
 impl std::fmt::Display for UserId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -141,6 +184,18 @@ impl std::fmt::Display for PushSubscriptionId {
     }
 }
 
+impl std::fmt::Display for RoomWebhookId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::fmt::Display for ExportId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 // Core domain models
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
@@ -172,11 +227,30 @@ impl User {
             name: self.name.clone(),
             bot_token: token.clone(),
             webhook_url: None, // Will be populated from webhook table
+            auto_join_on_mention: false,
+            post_on_behalf: false,
             created_at: self.created_at,
         })
     }
 }
 
+#[cfg(test)]
+impl User {
+    /// Regular user with a unique email, for test fixtures
+    pub(crate) fn for_tests(name: &str) -> Self {
+        Self {
+            id: UserId::new(),
+            name: name.to_string(),
+            email: format!("{}@example.com", Uuid::new_v4()),
+            password_hash: "hash".to_string(),
+            bio: None,
+            admin: false,
+            bot_token: None,
+            created_at: Utc::now(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Room {
     pub id: RoomId,
@@ -187,6 +261,21 @@ pub struct Room {
     pub last_message_at: Option<DateTime<Utc>>,
 }
 
+#[cfg(test)]
+impl Room {
+    /// Room with no messages yet, for test fixtures
+    pub(crate) fn for_tests(name: &str, room_type: RoomType) -> Self {
+        Self {
+            id: RoomId::new(),
+            name: name.to_string(),
+            topic: None,
+            room_type,
+            created_at: Utc::now(),
+            last_message_at: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RoomType {
     Open,    // Anyone can join
@@ -194,6 +283,9 @@ pub enum RoomType {
     Direct,  // Two-person direct message
 }
 
+/// Members a direct room can hold: the two people in the conversation
+pub const DIRECT_ROOM_MAX_MEMBERS: u32 = 2;
+
 impl std::str::FromStr for RoomType {
     type Err = String;
     
@@ -207,6 +299,37 @@ impl std::str::FromStr for RoomType {
     }
 }
 
+/// Who may post messages in a room
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PostPermission {
+    #[default]
+    Everyone,
+    /// Announcement rooms: room admins, site admins and explicitly granted
+    /// users (bots always need a grant)
+    AdminsOnly,
+}
+
+impl PostPermission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PostPermission::Everyone => "everyone",
+            PostPermission::AdminsOnly => "admins_only",
+        }
+    }
+}
+
+impl std::str::FromStr for PostPermission {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "everyone" => Ok(PostPermission::Everyone),
+            "admins_only" | "adminsonly" => Ok(PostPermission::AdminsOnly),
+            _ => Err(format!("Invalid post permission: {}", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub id: MessageId,
@@ -223,6 +346,130 @@ pub struct Message {
     pub sound_commands: Vec<String>,
 }
 
+/// Short plain-text form of a room's latest message, for the room list
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessagePreview {
+    pub sender_name: String,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl MessagePreview {
+    /// Collapses whitespace and cuts the content to `max_chars` characters,
+    /// ending a cut preview with an ellipsis
+    pub fn new(sender_name: String, content: &str, created_at: DateTime<Utc>, max_chars: usize) -> Self {
+        let collapsed = content.split_whitespace().collect::<Vec<_>>().join(" ");
+        let text = if collapsed.chars().count() > max_chars {
+            let mut cut: String = collapsed.chars().take(max_chars.saturating_sub(1)).collect();
+            cut.truncate(cut.trim_end().len());
+            cut.push('…');
+            cut
+        } else {
+            collapsed
+        };
+
+        Self { sender_name, text, created_at }
+    }
+}
+
+/// A room as listed by `GET /api/rooms`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomListEntry {
+    #[serde(flatten)]
+    pub room: Room,
+    pub last_message_preview: Option<MessagePreview>,
+}
+
+/// Profile fields any member may see about another user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicUser {
+    pub id: UserId,
+    pub name: String,
+    pub bio: Option<String>,
+    pub bot: bool,
+}
+
+/// A direct-message room as listed by `GET /api/direct`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectConversation {
+    #[serde(flatten)]
+    pub room: Room,
+    /// The other person in the room; None when nobody else is in it
+    pub participant: Option<PublicUser>,
+    /// Messages from others since the user's read marker (all of them if
+    /// the user has never read the room)
+    pub unread_count: u32,
+}
+
+/// One room's section of the Welcome frame sent when a connection opens
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WelcomeRoom {
+    pub room_id: RoomId,
+    /// Messages from others since the user's read marker
+    pub unread_count: u32,
+    pub online_users: Vec<UserId>,
+}
+
+/// A room's saved reply, posted by sending `/canned <shortcut>`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CannedResponse {
+    pub shortcut: String,
+    pub content: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A suggestion for `@` autocomplete in a room
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MentionCandidate {
+    /// What goes after the `@`: the member's handle, or `room` / `here`
+    pub handle: String,
+    pub name: String,
+    /// None for the synthetic `@room` and `@here` entries
+    pub user_id: Option<UserId>,
+}
+
+/// A message that mentions someone, with the room it was posted in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mention {
+    pub message: Message,
+    pub room_name: String,
+}
+
+/// A message a user saved for later. If the message has since been deleted
+/// only the tombstone is left: `deleted` is set and `message` is empty.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedMessage {
+    pub message_id: MessageId,
+    pub room_id: RoomId,
+    pub room_name: String,
+    pub saved_at: DateTime<Utc>,
+    pub deleted: bool,
+    pub message: Option<Message>,
+}
+
+/// Bytes of stored blobs attributed to a user (None = not tied to anyone)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobUsage {
+    pub user_id: Option<UserId>,
+    pub size_bytes: u64,
+}
+
+/// Who a stored blob belongs to: its uploader and, for attachments, the
+/// room it was posted to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobOwner {
+    pub user_id: Option<UserId>,
+    pub room_id: Option<RoomId>,
+}
+
+/// A member whose read marker has reached a message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeenReceipt {
+    pub user_id: UserId,
+    /// When the read marker last moved, i.e. no later than when they saw it
+    pub seen_at: DateTime<Utc>,
+}
+
 impl Message {
     /// Create a new message with basic content
     pub fn new(
@@ -306,6 +553,37 @@ impl std::str::FromStr for InvolvementLevel {
     }
 }
 
+/// What a user may do in a room, so clients don't have to guess
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomPermissions {
+    pub room_id: RoomId,
+    /// Explicit membership level; None for implicit access to open rooms
+    pub involvement_level: Option<InvolvementLevel>,
+    /// Room admin or site admin
+    pub is_admin: bool,
+    pub can_read: bool,
+    pub post_permission: PostPermission,
+    pub can_post: bool,
+    pub can_invite: bool,
+    pub can_change_type: bool,
+}
+
+/// Who a feature flag override applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "scope", content = "id", rename_all = "lowercase")]
+pub enum FeatureScope {
+    User(UserId),
+    Room(RoomId),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeatureOverride {
+    #[serde(flatten)]
+    pub scope: FeatureScope,
+    pub flag: String,
+    pub enabled: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
     pub token: String,
@@ -314,6 +592,50 @@ pub struct Session {
     pub expires_at: DateTime<Utc>,
 }
 
+/// What a single-use token lets its holder do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenKind {
+    PasswordReset,
+    EmailVerification,
+    RoomInvite,
+}
+
+impl TokenKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenKind::PasswordReset => "password_reset",
+            TokenKind::EmailVerification => "email_verification",
+            TokenKind::RoomInvite => "room_invite",
+        }
+    }
+}
+
+impl std::str::FromStr for TokenKind {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "password_reset" => Ok(TokenKind::PasswordReset),
+            "email_verification" => Ok(TokenKind::EmailVerification),
+            "room_invite" => Ok(TokenKind::RoomInvite),
+            _ => Err(format!("Invalid token kind: {}", s)),
+        }
+    }
+}
+
+/// A single-use token of any kind; `subject` is what it applies to (the
+/// user being reset or verified, the room being joined)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub token: String,
+    pub kind: TokenKind,
+    pub subject: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
 // Response DTOs
 #[derive(Debug, Serialize)]
 pub struct LoginResponse {
@@ -321,6 +643,13 @@ pub struct LoginResponse {
     pub session_token: String,
 }
 
+/// WebSocket protocol spoken by clients that don't advertise a version
+pub const WS_LEGACY_PROTOCOL_VERSION: u32 = 1;
+
+/// Newest WebSocket protocol the server speaks; clients ask for theirs with
+/// `?protocol=N` on connect
+pub const WS_PROTOCOL_VERSION: u32 = 2;
+
 // WebSocket message types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -349,9 +678,21 @@ pub enum WebSocketMessage {
         room_id: RoomId,
         is_typing: bool,
     },
+    /// Sent instead of TypingStart/TypingStop while more users are typing
+    /// than the server announces one by one: the earliest typers, and how
+    /// many more there are. Once the room drops back under the limit, one
+    /// last summary with `others: 0` lists everyone still typing.
+    TypingSummary {
+        room_id: RoomId,
+        user_ids: Vec<UserId>,
+        others: usize,
+    },
     PresenceUpdate {
         room_id: RoomId,
         online_users: Vec<UserId>,
+        /// When offline members were last connected, for "last seen" labels
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        last_seen: HashMap<UserId, DateTime<Utc>>,
     },
     SoundPlayback {
         sound_name: String,
@@ -359,6 +700,74 @@ pub enum WebSocketMessage {
         room_id: RoomId,
         timestamp: DateTime<Utc>,
     },
+    RoomUpdated {
+        room: Room,
+    },
+    /// Room went quiet long enough to be archived; a new message revives it
+    RoomArchived {
+        room_id: RoomId,
+    },
+    /// An admin deleted the room and its history; clients drop it from the
+    /// room list. No further frames arrive for it.
+    RoomDeleted {
+        room_id: RoomId,
+    },
+    /// Sent only to the affected user's connections when they're added to or
+    /// removed from a room, so their room list can refresh
+    RoomMembershipChanged {
+        room_id: RoomId,
+        added: bool,
+    },
+    /// Clients disable the composer when they can no longer post
+    PostPermissionChanged {
+        room_id: RoomId,
+        post_permission: PostPermission,
+    },
+    /// First frame on a connection: the negotiated protocol and the features
+    /// enabled for this user
+    Capabilities {
+        protocol_version: u32,
+        features: std::collections::BTreeMap<String, bool>,
+    },
+    /// Last frame before the server closes the connection for a restart;
+    /// clients should wait `reconnect_after` seconds before reconnecting
+    ServerShutdown {
+        reconnect_after: u64,
+    },
+    /// Sent right after Capabilities so clients can render unread badges and
+    /// presence without a burst of HTTP calls. Only the most recently active
+    /// rooms are included; `truncated` tells the client to fetch the rest.
+    Welcome {
+        protocol_version: u32,
+        features: std::collections::BTreeMap<String, bool>,
+        rooms: Vec<WelcomeRoom>,
+        truncated: bool,
+    },
+    /// Sent when an admin starts acting as this user, and on connect with
+    /// the impersonation session; clients show a banner until `expires_at`,
+    /// when the session stops working
+    Impersonation {
+        impersonated_by: UserId,
+        expires_at: DateTime<Utc>,
+    },
+}
+
+impl WebSocketMessage {
+    /// Oldest protocol version whose clients can parse this frame
+    pub fn min_protocol_version(&self) -> u32 {
+        match self {
+            WebSocketMessage::RoomArchived { .. }
+            | WebSocketMessage::PostPermissionChanged { .. }
+            | WebSocketMessage::RoomMembershipChanged { .. }
+            | WebSocketMessage::RoomDeleted { .. }
+            | WebSocketMessage::TypingSummary { .. }
+            | WebSocketMessage::Capabilities { .. }
+            | WebSocketMessage::ServerShutdown { .. }
+            | WebSocketMessage::Welcome { .. }
+            | WebSocketMessage::Impersonation { .. } => 2,
+            _ => WS_LEGACY_PROTOCOL_VERSION,
+        }
+    }
 }
 
 // Push notification models
@@ -380,6 +789,8 @@ pub struct NotificationPreferences {
     pub direct_messages_enabled: bool,
     pub all_messages_enabled: bool,
     pub sounds_enabled: bool,
+    /// Email when someone adds them to a closed room
+    pub room_added_email_enabled: bool,
     pub updated_at: DateTime<Utc>,
 }
 
@@ -391,6 +802,7 @@ impl Default for NotificationPreferences {
             direct_messages_enabled: true,
             all_messages_enabled: false,
             sounds_enabled: true,
+            room_added_email_enabled: true,
             updated_at: Utc::now(),
         }
     }
@@ -409,6 +821,7 @@ pub struct UpdateNotificationPreferencesRequest {
     pub direct_messages_enabled: Option<bool>,
     pub all_messages_enabled: Option<bool>,
     pub sounds_enabled: Option<bool>,
+    pub room_added_email_enabled: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -436,6 +849,12 @@ pub struct Bot {
     pub name: String,
     pub bot_token: String,
     pub webhook_url: Option<String>,
+    /// Joins a room when an admin of it mentions the bot there
+    #[serde(default)]
+    pub auto_join_on_mention: bool,
+    /// May post messages attributed to other users
+    #[serde(default)]
+    pub post_on_behalf: bool,
     pub created_at: DateTime<Utc>,
 }
 
@@ -506,9 +925,92 @@ pub struct AdminCreationResponse {
 pub struct UpdateBotRequest {
     pub name: Option<String>,
     pub webhook_url: Option<String>,
+    pub auto_join_on_mention: Option<bool>,
+    pub post_on_behalf: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRoomWebhookRequest {
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRoomWebhookRequest {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetRoomBridgeRequest {
+    pub url: String,
+    pub format: BridgeFormat,
+    pub inbound_bot_id: Option<UserId>,
+    /// Defaults to enabled
+    pub enabled: Option<bool>,
+}
+
+
+
+/// Outbound URL notified of every message in a room
+/// 
+/// Each webhook signs its deliveries with its own secret, which is only
+/// shown when the webhook is created.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomWebhook {
+    pub id: RoomWebhookId,
+    pub room_id: RoomId,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
 }
 
+/// Chat app a room bridge posts to, which decides the payload shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BridgeFormat {
+    /// Slack incoming webhook: `{"text": ...}`
+    Slack,
+    /// Discord webhook: `{"content": ...}`
+    Discord,
+}
 
+impl BridgeFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BridgeFormat::Slack => "slack",
+            BridgeFormat::Discord => "discord",
+        }
+    }
+}
+
+impl std::str::FromStr for BridgeFormat {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "slack" => Ok(BridgeFormat::Slack),
+            "discord" => Ok(BridgeFormat::Discord),
+            _ => Err(format!("Invalid bridge format: {}", s)),
+        }
+    }
+}
+
+/// Mirrors a room's new messages into an external chat through its
+/// incoming-webhook URL, one bridge per room
+/// 
+/// `inbound_bot_id` is the bot that relays the other side back in; its
+/// messages are never sent out again, so the two chats don't echo each
+/// other forever.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomBridge {
+    pub room_id: RoomId,
+    pub url: String,
+    pub format: BridgeFormat,
+    pub inbound_bot_id: Option<UserId>,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WebhookPayload {
@@ -541,4 +1043,15 @@ pub struct WebhookMessage {
 pub struct WebhookMessageBody {
     pub html: String,
     pub plain: String,
+}
+
+/// What a bot's webhook did with a sample payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookSimulation {
+    pub url: String,
+    pub payload: WebhookPayload,
+    pub status: u16,
+    /// Response body, truncated to a few KB
+    pub body: String,
+    pub latency_ms: u64,
 }
\ No newline at end of file
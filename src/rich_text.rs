@@ -1,6 +1,6 @@
 use regex::Regex;
 use std::collections::HashSet;
-use std::sync::OnceLock;
+use std::sync::{Arc, LazyLock, OnceLock};
 
 use crate::models::UserId;
 
@@ -19,6 +19,12 @@ pub struct RichTextProcessor;
 /// Regex for detecting @mentions
 static MENTION_REGEX: OnceLock<Regex> = OnceLock::new();
 
+/// Mentions that address the whole room rather than one user
+pub const ROOM_WIDE_MENTIONS: &[&str] = &["room", "here"];
+
+/// User mentions processed per message when not configured
+pub const DEFAULT_MAX_MENTIONS: usize = 20;
+
 /// Regex for detecting /play commands
 static PLAY_COMMAND_REGEX: OnceLock<Regex> = OnceLock::new();
 
@@ -40,8 +46,10 @@ static AVAILABLE_SOUNDS: &[&str] = &[
 pub struct ProcessedContent {
     /// The sanitized HTML content with rich text features
     pub html: String,
-    /// Extracted @mentions (username -> user_id mapping)
+    /// Extracted @mentions (username -> user_id mapping), distinct and capped
     pub mentions: Vec<String>,
+    /// Distinct user mentions dropped for exceeding the cap
+    pub ignored_mentions: usize,
     /// Extracted /play commands
     pub play_commands: Vec<String>,
     /// Whether the message contains any rich text features
@@ -53,19 +61,21 @@ impl RichTextProcessor {
     /// 
     /// # Arguments
     /// * `content` - Raw message content
+    /// * `max_mentions` - Distinct user mentions to keep (see [`limit_mentions`](Self::limit_mentions))
     /// * `user_lookup` - Function to resolve usernames to user IDs
     /// 
     /// # Returns
     /// ProcessedContent with sanitized HTML and extracted features
     pub async fn process_content<F>(
         content: &str,
+        max_mentions: usize,
         user_lookup: F,
     ) -> Result<ProcessedContent, RichTextError>
     where
         F: Fn(&str) -> Option<UserId>,
     {
         // Step 1: Extract @mentions before HTML processing
-        let mentions = Self::extract_mentions(content);
+        let (mentions, ignored_mentions) = Self::limit_mentions(&Self::extract_mentions(content), max_mentions);
         
         // Step 2: Extract /play commands
         let play_commands = Self::extract_play_commands(content);
@@ -82,6 +92,7 @@ impl RichTextProcessor {
         Ok(ProcessedContent {
             html: processed_html,
             mentions,
+            ignored_mentions,
             play_commands,
             has_rich_features,
         })
@@ -90,7 +101,7 @@ impl RichTextProcessor {
     /// Extract @mentions from content
     /// 
     /// Returns list of mentioned usernames (without @ prefix)
-    fn extract_mentions(content: &str) -> Vec<String> {
+    pub fn extract_mentions(content: &str) -> Vec<String> {
         let regex = MENTION_REGEX.get_or_init(|| {
             Regex::new(r"@([a-zA-Z0-9_-]+)").expect("Invalid mention regex")
         });
@@ -101,6 +112,35 @@ impl RichTextProcessor {
             .collect()
     }
     
+    /// Deduplicates mentions (case-insensitively) and keeps the first
+    /// `max_users` user mentions
+    /// 
+    /// `@room` and `@here` have a cap of their own: each is kept once and
+    /// neither uses up a user slot. Returns the kept mentions, in order, and
+    /// how many distinct user mentions were dropped.
+    pub fn limit_mentions(mentions: &[String], max_users: usize) -> (Vec<String>, usize) {
+        let mut seen = HashSet::new();
+        let mut kept = Vec::new();
+        let mut users = 0;
+        let mut ignored = 0;
+        
+        for mention in mentions {
+            if !seen.insert(mention.to_lowercase()) {
+                continue;
+            }
+            if ROOM_WIDE_MENTIONS.contains(&mention.to_lowercase().as_str()) {
+                kept.push(mention.clone());
+            } else if users < max_users {
+                users += 1;
+                kept.push(mention.clone());
+            } else {
+                ignored += 1;
+            }
+        }
+        
+        (kept, ignored)
+    }
+    
     /// Extract /play commands from content
     /// 
     /// Returns list of valid sound names
@@ -128,31 +168,18 @@ impl RichTextProcessor {
     where
         F: Fn(&str) -> Option<UserId>,
     {
-        // Create rich text sanitizer configuration
-        let mut schemes = HashSet::new();
-        schemes.insert("http");
-        schemes.insert("https");
-        schemes.insert("mailto");
-        
-        let mut builder = ammonia::Builder::default();
-        builder
-            // Allow basic formatting tags
-            .add_tags(&["b", "strong", "i", "em", "u", "s", "strike", "del"])
-            // Allow links with specific attributes
-            .add_tags(&["a"])
-            .add_tag_attributes("a", &["href", "data-mention-id", "class"])
-            // Allow line breaks
-            .add_tags(&["br"])
-            // Allow code formatting
-            .add_tags(&["code", "pre"])
-            // Allow lists
-            .add_tags(&["ul", "ol", "li"])
-            // Allow blockquotes
-            .add_tags(&["blockquote"])
-            // Set URL schemes for links
-            .url_schemes(schemes);
-        
         // First pass: convert @mentions to proper HTML links
+        let processed_content = Self::link_mentions(content, mentions, user_lookup);
+        
+        // Second pass: sanitize HTML while preserving our rich text features
+        Self::sanitize_html(&processed_content)
+    }
+    
+    /// Replaces each `@name` that resolves to a user with a mention link
+    fn link_mentions<F>(content: &str, mentions: &[String], user_lookup: &F) -> String
+    where
+        F: Fn(&str) -> Option<UserId> + ?Sized,
+    {
         let mut processed_content = content.to_string();
         
         for mention in mentions {
@@ -166,8 +193,12 @@ impl RichTextProcessor {
             }
         }
         
-        // Second pass: sanitize HTML while preserving our rich text features
-        let sanitized = builder.clean(&processed_content).to_string();
+        processed_content
+    }
+    
+    /// Strips HTML outside the rich text allowlist
+    fn sanitize_html(content: &str) -> Result<String, RichTextError> {
+        let sanitized = sanitize_stored_html(content);
         
         // Validate that sanitization didn't remove everything important
         if sanitized.trim().is_empty() && !content.trim().is_empty() {
@@ -237,6 +268,55 @@ impl RichTextProcessor {
     }
 }
 
+/// The rich text allowlist, built once
+static STORED_HTML_SANITIZER: LazyLock<ammonia::Builder<'static>> = LazyLock::new(|| {
+    let mut schemes = HashSet::new();
+    schemes.insert("http");
+    schemes.insert("https");
+    schemes.insert("mailto");
+    
+    let mut builder = ammonia::Builder::default();
+    builder
+        // Allow basic formatting tags
+        .add_tags(&["b", "strong", "i", "em", "u", "s", "strike", "del"])
+        // Allow links with specific attributes
+        .add_tags(&["a"])
+        .add_tag_attributes("a", &["href", "data-mention-id", "class"])
+        // Allow mention and sound highlights
+        .add_tags(&["span"])
+        .add_tag_attributes("span", &["class"])
+        // Allow line breaks
+        .add_tags(&["br"])
+        // Allow code formatting
+        .add_tags(&["code", "pre"])
+        // Allow lists
+        .add_tags(&["ul", "ol", "li"])
+        // Allow blockquotes
+        .add_tags(&["blockquote"])
+        // Set URL schemes for links
+        .url_schemes(schemes);
+    builder
+});
+
+/// Cleans HTML against the rich text allowlist
+///
+/// This is the one place `html_content` is made safe: the database runs every
+/// message's HTML through it before storing it, whichever path produced it
+/// (the pipeline, the demo seeder, a bot), so script tags, event handler
+/// attributes and `javascript:` links never reach a reader. HTML stored
+/// before that was cleaned once by a startup migration.
+pub fn sanitize_stored_html(html: &str) -> String {
+    STORED_HTML_SANITIZER.clean(html).to_string()
+}
+
+/// Text of stored message HTML, for places that can't render it
+///
+/// Tags are dropped but what they wrap is kept, so a mention reads `@name`,
+/// and entities are decoded back into the characters they stand for.
+pub fn plain_text(html: &str) -> String {
+    html_escape::decode_html_entities(&crate::validation::sanitization::sanitize_plain_text(html)).into_owned()
+}
+
 /// Errors that can occur during rich text processing
 #[derive(Debug, thiserror::Error)]
 pub enum RichTextError {
@@ -251,6 +331,203 @@ pub enum RichTextError {
     
     #[error("Content processing failed: {reason}")]
     ProcessingFailed { reason: String },
+    
+    #[error("Unknown content stage: {name}")]
+    UnknownStage { name: String },
+    
+    #[error("The content pipeline must end with the sanitize stage")]
+    SanitizeNotLast,
+}
+
+/// Stages run, in order, when the pipeline isn't configured
+pub const DEFAULT_PIPELINE: &[&str] = &["sounds", "mentions", "sanitize"];
+
+/// Message content part-way through a [`Pipeline`]
+#[derive(Debug, Clone, Default)]
+pub struct StageContent {
+    /// Content as left by the previous stage
+    pub text: String,
+    pub mentions: Vec<String>,
+    pub ignored_mentions: usize,
+    pub play_commands: Vec<String>,
+}
+
+/// What stages may consult besides the content itself
+pub struct StageContext<'a> {
+    /// Distinct user mentions to keep (see [`RichTextProcessor::limit_mentions`])
+    pub max_mentions: usize,
+    /// Resolves a username to a user, for mention links
+    pub user_lookup: &'a dyn Fn(&str) -> Option<UserId>,
+}
+
+/// One transform applied to message content before it's stored
+/// 
+/// A stage sees the text as the previous stage left it, may rewrite it,
+/// and records what it extracted in [`StageContent`]; it shouldn't discard
+/// what earlier stages recorded. Returning an error rejects the message.
+pub trait ContentStage: Send + Sync {
+    /// Name operators use to enable and order the stage
+    fn name(&self) -> &'static str;
+    
+    fn apply(&self, content: &mut StageContent, context: &StageContext<'_>) -> Result<(), RichTextError>;
+}
+
+/// Pulls valid `/play` commands out of the text. A message that was only
+/// commands becomes "🎵 Played: ...".
+pub struct SoundsStage;
+
+impl ContentStage for SoundsStage {
+    fn name(&self) -> &'static str {
+        "sounds"
+    }
+    
+    fn apply(&self, content: &mut StageContent, _context: &StageContext<'_>) -> Result<(), RichTextError> {
+        let (cleaned, play_commands) = RichTextProcessor::extract_and_clean_play_commands(&content.text);
+        
+        if cleaned.trim().is_empty() && !play_commands.is_empty() {
+            content.text = format!("🎵 Played: {}", play_commands.join(", "));
+        } else {
+            content.text = cleaned;
+        }
+        content.play_commands.extend(play_commands);
+        Ok(())
+    }
+}
+
+/// Records `@mentions`, capped, and links the ones that resolve to users
+pub struct MentionsStage;
+
+impl ContentStage for MentionsStage {
+    fn name(&self) -> &'static str {
+        "mentions"
+    }
+    
+    fn apply(&self, content: &mut StageContent, context: &StageContext<'_>) -> Result<(), RichTextError> {
+        let (mentions, ignored) = RichTextProcessor::limit_mentions(
+            &RichTextProcessor::extract_mentions(&content.text),
+            context.max_mentions,
+        );
+        content.text = RichTextProcessor::link_mentions(&content.text, &mentions, context.user_lookup);
+        content.mentions = mentions;
+        content.ignored_mentions = ignored;
+        Ok(())
+    }
+}
+
+/// Strips HTML outside the rich text allowlist. Anything that adds markup
+/// belongs before this stage.
+pub struct SanitizeStage;
+
+impl ContentStage for SanitizeStage {
+    fn name(&self) -> &'static str {
+        "sanitize"
+    }
+    
+    fn apply(&self, content: &mut StageContent, _context: &StageContext<'_>) -> Result<(), RichTextError> {
+        content.text = RichTextProcessor::sanitize_html(&content.text)?;
+        Ok(())
+    }
+}
+
+/// Built-in stage by name
+fn builtin_stage(name: &str) -> Option<Arc<dyn ContentStage>> {
+    match name {
+        "sounds" => Some(Arc::new(SoundsStage)),
+        "mentions" => Some(Arc::new(MentionsStage)),
+        "sanitize" => Some(Arc::new(SanitizeStage)),
+        _ => None,
+    }
+}
+
+/// Whether `name` is a built-in stage; used by `Config::validate`
+pub fn is_known_stage(name: &str) -> bool {
+    builtin_stage(name).is_some()
+}
+
+/// Ordered transforms every message's content goes through once
+/// 
+/// Sanitizing always runs last, after anything that adds markup, and can't
+/// be removed; stages added to the pipeline go before it. Leaving any other
+/// stage out of the list disables it.
+#[derive(Clone)]
+pub struct Pipeline {
+    /// Stages before sanitizing, in order
+    stages: Vec<Arc<dyn ContentStage>>,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::from_names(DEFAULT_PIPELINE).expect("default pipeline stages are built in")
+    }
+}
+
+impl std::fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.stage_names()).finish()
+    }
+}
+
+impl Pipeline {
+    /// Built-in stages in the given order; errors on an unknown name and
+    /// unless the list ends with "sanitize" (and names it only there)
+    pub fn from_names<S: AsRef<str>>(names: &[S]) -> Result<Self, RichTextError> {
+        let names: Vec<&str> = names.iter().map(|name| name.as_ref().trim()).collect();
+        let (last, rest) = names.split_last().ok_or(RichTextError::SanitizeNotLast)?;
+        if *last != "sanitize" || rest.contains(&"sanitize") {
+            return Err(RichTextError::SanitizeNotLast);
+        }
+        
+        let stages = rest
+            .iter()
+            .map(|name| builtin_stage(name).ok_or_else(|| RichTextError::UnknownStage { name: name.to_string() }))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { stages })
+    }
+    
+    /// Adds a stage, built in or not, after the others but before sanitizing
+    pub fn with_stage(mut self, stage: Arc<dyn ContentStage>) -> Self {
+        self.stages.push(stage);
+        self
+    }
+    
+    /// Drops the named stage; sanitizing can't be dropped
+    pub fn without(mut self, name: &str) -> Self {
+        self.stages.retain(|stage| stage.name() != name);
+        self
+    }
+    
+    pub fn stage_names(&self) -> Vec<&'static str> {
+        self.stages
+            .iter()
+            .map(|stage| stage.name())
+            .chain(std::iter::once(SanitizeStage.name()))
+            .collect()
+    }
+    
+    /// Runs `content` through every stage in order
+    pub fn run(&self, content: &str, context: &StageContext<'_>) -> Result<ProcessedContent, RichTextError> {
+        let mut state = StageContent {
+            text: content.to_string(),
+            ..Default::default()
+        };
+        for stage in &self.stages {
+            stage.apply(&mut state, context)?;
+        }
+        SanitizeStage.apply(&mut state, context)?;
+        
+        let has_rich_features = !state.mentions.is_empty()
+            || !state.play_commands.is_empty()
+            || RichTextProcessor::has_html_formatting(&state.text)
+            || state.text != content;
+        
+        Ok(ProcessedContent {
+            html: state.text,
+            mentions: state.mentions,
+            ignored_mentions: state.ignored_mentions,
+            play_commands: state.play_commands,
+            has_rich_features,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -275,6 +552,18 @@ mod tests {
         assert_eq!(mentions, vec!["alice", "bob"]);
     }
     
+    #[test]
+    fn test_limit_mentions() {
+        let mentions: Vec<String> = ["alice", "Alice", "bob", "room", "carol", "here", "room", "dave"]
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        
+        let (kept, ignored) = RichTextProcessor::limit_mentions(&mentions, 2);
+        assert_eq!(kept, vec!["alice", "bob", "room", "here"]);
+        assert_eq!(ignored, 2);
+    }
+    
     #[tokio::test]
     async fn test_extract_play_commands() {
         let content = "Check this out! /play tada and /play bell";
@@ -295,7 +584,7 @@ mod tests {
     #[tokio::test]
     async fn test_process_content_with_mentions() {
         let content = "Hello @alice, this is <b>bold</b> text!";
-        let result = RichTextProcessor::process_content(content, mock_user_lookup).await.unwrap();
+        let result = RichTextProcessor::process_content(content, DEFAULT_MAX_MENTIONS, mock_user_lookup).await.unwrap();
         
         assert_eq!(result.mentions, vec!["alice"]);
         assert!(result.html.contains("data-mention-id"));
@@ -331,10 +620,92 @@ mod tests {
     #[tokio::test]
     async fn test_html_sanitization() {
         let content = r#"<script>alert('xss')</script><b>Safe content</b>"#;
-        let result = RichTextProcessor::process_content(content, mock_user_lookup).await.unwrap();
+        let result = RichTextProcessor::process_content(content, DEFAULT_MAX_MENTIONS, mock_user_lookup).await.unwrap();
         
         // Script should be removed, but bold should remain
         assert!(!result.html.contains("<script>"));
         assert!(result.html.contains("<b>Safe content</b>"));
+    }    
+    /// Appends its own name, so the final text shows which stages ran in what order
+    struct Tag(&'static str);
+    
+    impl ContentStage for Tag {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+        
+        fn apply(&self, content: &mut StageContent, _context: &StageContext<'_>) -> Result<(), RichTextError> {
+            content.text.push_str(&format!(" [{}]", self.0));
+            Ok(())
+        }
+    }
+    
+    /// Wraps the text in markup the sanitizer doesn't allow
+    struct Marquee;
+    
+    impl ContentStage for Marquee {
+        fn name(&self) -> &'static str {
+            "marquee"
+        }
+        
+        fn apply(&self, content: &mut StageContent, _context: &StageContext<'_>) -> Result<(), RichTextError> {
+            content.text = format!("<marquee>{}</marquee>", content.text);
+            Ok(())
+        }
+    }
+    
+    fn context() -> StageContext<'static> {
+        StageContext { max_mentions: DEFAULT_MAX_MENTIONS, user_lookup: &mock_user_lookup }
+    }
+    
+    #[test]
+    fn test_pipeline_applies_stages_in_order() {
+        let pipeline = Pipeline::from_names(&["sanitize"])
+            .unwrap()
+            .with_stage(Arc::new(Tag("first")))
+            .with_stage(Arc::new(Tag("second")))
+            .with_stage(Arc::new(Tag("third")));
+        assert_eq!(pipeline.stage_names(), vec!["first", "second", "third", "sanitize"]);
+        assert_eq!(pipeline.run("hi", &context()).unwrap().html, "hi [first] [second] [third]");
+        
+        // Stages added later still run before sanitizing, so their markup is stripped
+        let added = Pipeline::from_names(&["sanitize"]).unwrap().with_stage(Arc::new(Marquee));
+        assert_eq!(added.run("hi", &context()).unwrap().html, "hi");
+        
+        let standard = Pipeline::default().run("@alice <script>x</script>/play tada", &context()).unwrap();
+        assert_eq!(standard.mentions, vec!["alice"]);
+        assert_eq!(standard.play_commands, vec!["tada"]);
+        assert!(standard.html.contains("data-mention-id"));
+        assert!(!standard.html.contains("script"));
+    }
+    
+    #[test]
+    fn test_pipeline_skips_disabled_stage() {
+        let pipeline = Pipeline::default()
+            .with_stage(Arc::new(Tag("extra")))
+            .without("extra")
+            .without("sounds")
+            .without("sanitize");
+        assert_eq!(pipeline.stage_names(), vec!["mentions", "sanitize"]);
+        assert!(!pipeline.run("hi <script>x</script>", &context()).unwrap().html.contains("script"));
+        
+        let result = pipeline.run("/play tada for @alice", &context()).unwrap();
+        assert!(result.play_commands.is_empty());
+        assert!(result.html.starts_with("/play tada"));
+        assert!(!result.html.contains("[extra]"));
+        assert_eq!(result.mentions, vec!["alice"]);
+        
+        let configured = Pipeline::from_names(&["sanitize"]).unwrap().run("@alice", &context()).unwrap();
+        assert!(configured.mentions.is_empty());
+        
+        assert!(matches!(
+            Pipeline::from_names(&["sounds", "unfurl", "sanitize"]),
+            Err(RichTextError::UnknownStage { name }) if name == "unfurl"
+        ));
+        
+        // Sanitizing can't be left out or moved off the end
+        for names in [&[][..], &["sounds", "mentions"], &["sanitize", "mentions"], &["sanitize", "mentions", "sanitize"]] {
+            assert!(matches!(Pipeline::from_names(names), Err(RichTextError::SanitizeNotLast)), "{:?}", names);
+        }
     }
 }
\ No newline at end of file
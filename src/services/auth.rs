@@ -4,9 +4,13 @@ use chrono::{Duration, Utc};
 use rand::{thread_rng, Rng};
 use std::sync::Arc;
 
+use crate::config::{PasswordPolicy, TextLimits};
 use crate::database::CampfireDatabase;
 use crate::errors::AuthError;
 use crate::models::{Session, User, UserId};
+use crate::timezone::{parse_timezone, resolve_timezone};
+use crate::validation::normalize_text;
+use chrono_tz::Tz;
 
 #[async_trait]
 pub trait AuthServiceTrait: Send + Sync {
@@ -22,6 +26,9 @@ pub trait AuthServiceTrait: Send + Sync {
     /// Revokes session
     async fn revoke_session(&self, token: String) -> Result<(), AuthError>;
     
+    /// Revokes every session belonging to the user (logout everywhere)
+    async fn delete_all_sessions(&self, user_id: UserId) -> Result<u64, AuthError>;
+    
     /// Creates a new user
     async fn create_user(
         &self,
@@ -29,16 +36,94 @@ pub trait AuthServiceTrait: Send + Sync {
         email: String,
         password: String,
     ) -> Result<User, AuthError>;
+    
+    /// The user's timezone; UTC until they pick one
+    async fn get_timezone(&self, user_id: UserId) -> Result<Tz, AuthError>;
+    
+    /// Sets the user's timezone from an IANA name like `Europe/Paris`
+    async fn set_timezone(&self, user_id: UserId, timezone: String) -> Result<Tz, AuthError>;
+    
+    /// Opens a short-lived session as `target_id` for the admin `admin_id`.
+    /// Admins can't be impersonated.
+    async fn impersonate(&self, admin_id: UserId, target_id: UserId) -> Result<Session, AuthError>;
+    
+    /// Validates like `validate_session`, also returning the admin behind
+    /// the session if it was opened by impersonation
+    async fn validate_session_with_impersonator(&self, token: String) -> Result<(User, Option<UserId>), AuthError>;
 }
 
 #[derive(Clone)]
 pub struct AuthService {
     db: Arc<CampfireDatabase>,
+    idle_timeout: Option<Duration>,
+    impersonation_ttl: Duration,
+    password_policy: PasswordPolicy,
+    text_limits: TextLimits,
 }
 
 impl AuthService {
+    /// Activity is written back at most this often, so idle tracking doesn't
+    /// cost a database write on every request
+    const ACTIVITY_TOUCH_INTERVAL_SECS: i64 = 60;
+    
     pub fn new(db: Arc<CampfireDatabase>) -> Self {
-        Self { db }
+        Self {
+            db,
+            idle_timeout: None,
+            impersonation_ttl: Duration::minutes(30),
+            password_policy: PasswordPolicy::default(),
+            text_limits: TextLimits::default(),
+        }
+    }
+    
+    pub fn with_password_policy(mut self, password_policy: PasswordPolicy) -> Self {
+        self.password_policy = password_policy;
+        self
+    }
+    
+    /// Longest user names accepted at sign-up
+    pub fn with_text_limits(mut self, text_limits: TextLimits) -> Self {
+        self.text_limits = text_limits;
+        self
+    }
+    
+    /// Expires sessions unused for longer than `idle_timeout`, regardless of
+    /// their `expires_at`
+    pub fn with_idle_timeout(mut self, idle_timeout: std::time::Duration) -> Self {
+        self.idle_timeout = Duration::from_std(idle_timeout).ok();
+        self
+    }
+    
+    /// How long impersonation sessions last before the admin has to start
+    /// a new one
+    pub fn with_impersonation_ttl(mut self, ttl: std::time::Duration) -> Self {
+        self.impersonation_ttl = Duration::from_std(ttl).unwrap_or(self.impersonation_ttl);
+        self
+    }
+    
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+    }
+    
+    /// Rejects (and deletes) an idle session, otherwise records the activity
+    async fn check_idle(&self, token: &str, idle_timeout: Duration) -> Result<(), AuthError> {
+        let now = Utc::now();
+        let last_active = self.db.get_session_last_active(token)
+            .await?
+            .ok_or(AuthError::SessionExpired)?;
+        
+        if now - last_active > idle_timeout {
+            let _ = self.db.delete_session(token.to_string()).await;
+            return Err(AuthError::SessionExpired);
+        }
+        
+        if now - last_active > Duration::seconds(Self::ACTIVITY_TOUCH_INTERVAL_SECS) {
+            if let Err(e) = self.db.touch_session(token.to_string(), now).await {
+                tracing::warn!("Failed to record session activity: {}", e);
+            }
+        }
+        
+        Ok(())
     }
     
     /// Generates cryptographically secure session token (Critical Gap #4)
@@ -62,11 +147,12 @@ impl AuthService {
     }
     
     /// Validates password strength
-    fn validate_password(password: &str) -> Result<(), AuthError> {
-        if password.len() < 8 {
-            return Err(AuthError::WeakPassword);
-        }
-        Ok(())
+    fn validate_password(&self, password: &str) -> Result<(), AuthError> {
+        crate::validation::validate_password(password, &self.password_policy).map_err(|e| {
+            AuthError::WeakPassword {
+                reasons: e.details.into_values().flatten().collect(),
+            }
+        })
     }
     
     /// Validates email format
@@ -116,8 +202,13 @@ impl AuthServiceTrait for AuthService {
     }
     
     async fn validate_session(&self, token: String) -> Result<User, AuthError> {
+        let (user, _) = self.validate_session_with_impersonator(token).await?;
+        Ok(user)
+    }
+    
+    async fn validate_session_with_impersonator(&self, token: String) -> Result<(User, Option<UserId>), AuthError> {
         // Get session from database
-        let session = self.db.get_session(&token)
+        let (session, impersonated_by) = self.db.get_session_with_impersonator(&token)
             .await?
             .ok_or(AuthError::SessionExpired)?;
         
@@ -128,6 +219,10 @@ impl AuthServiceTrait for AuthService {
             return Err(AuthError::SessionExpired);
         }
         
+        if let Some(idle_timeout) = self.idle_timeout {
+            self.check_idle(&token, idle_timeout).await?;
+        }
+        
         // Get user
         let user = self.db.get_user_by_id(session.user_id)
             .await?
@@ -135,7 +230,7 @@ impl AuthServiceTrait for AuthService {
                 email: "unknown".to_string() 
             })?;
         
-        Ok(user)
+        Ok((user, impersonated_by))
     }
     
     async fn revoke_session(&self, token: String) -> Result<(), AuthError> {
@@ -145,6 +240,13 @@ impl AuthServiceTrait for AuthService {
         Ok(())
     }
     
+    async fn delete_all_sessions(&self, user_id: UserId) -> Result<u64, AuthError> {
+        let revoked = self.db.delete_user_sessions(user_id)
+            .await?;
+        
+        Ok(revoked)
+    }
+    
     async fn create_user(
         &self,
         name: String,
@@ -153,13 +255,10 @@ impl AuthServiceTrait for AuthService {
     ) -> Result<User, AuthError> {
         // Validate inputs
         Self::validate_email(&email)?;
-        Self::validate_password(&password)?;
+        self.validate_password(&password)?;
         
-        if name.trim().is_empty() || name.len() > 50 {
-            return Err(AuthError::InvalidEmail { 
-                email: "Invalid name length".to_string() 
-            });
-        }
+        let name = normalize_text("Name", &name, self.text_limits.user_name)
+            .map_err(|e| AuthError::InvalidName { reason: e.to_string() })?;
         
         // Check if email already exists
         if self.db.get_user_by_email(&email).await?.is_some() {
@@ -186,6 +285,51 @@ impl AuthServiceTrait for AuthService {
         
         Ok(user)
     }
+    
+    async fn get_timezone(&self, user_id: UserId) -> Result<Tz, AuthError> {
+        let stored = self.db.get_user_timezone(user_id).await?;
+        Ok(resolve_timezone(stored.as_deref()))
+    }
+    
+    async fn set_timezone(&self, user_id: UserId, timezone: String) -> Result<Tz, AuthError> {
+        let tz = parse_timezone(&timezone).ok_or(AuthError::InvalidTimezone { timezone })?;
+        
+        // The session's user has been deleted out from under it
+        if !self.db.set_user_timezone(user_id, tz.name().to_string()).await? {
+            return Err(AuthError::SessionExpired);
+        }
+        
+        Ok(tz)
+    }
+    
+    async fn impersonate(&self, admin_id: UserId, target_id: UserId) -> Result<Session, AuthError> {
+        let admin = self.db.get_user_by_id(admin_id).await?;
+        if !admin.is_some_and(|admin| admin.admin) {
+            return Err(AuthError::InvalidCredentials);
+        }
+        
+        let target = self.db.get_user_by_id(target_id)
+            .await?
+            .ok_or_else(|| AuthError::UserNotFound {
+                email: "unknown".to_string()
+            })?;
+        if target.admin {
+            return Err(AuthError::ImpersonationForbidden { user_id: target_id });
+        }
+        
+        let now = Utc::now();
+        let session = Session {
+            token: Self::generate_secure_token()?,
+            user_id: target_id,
+            created_at: now,
+            expires_at: now + self.impersonation_ttl,
+        };
+        
+        self.db.create_impersonation_session(session.clone(), admin_id)
+            .await?;
+        
+        Ok(session)
+    }
 }
 
 #[cfg(test)]
@@ -198,6 +342,54 @@ mod tests {
         AuthService::new(Arc::new(db))
     }
     
+    #[tokio::test]
+    async fn test_idle_session_rejected_before_expiry() {
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let auth_service = AuthService::new(db.clone())
+            .with_idle_timeout(std::time::Duration::from_secs(15 * 60));
+        
+        let user = auth_service.create_user(
+            "Kiosk User".to_string(),
+            "kiosk@example.com".to_string(),
+            "password123".to_string(),
+        ).await.unwrap();
+        
+        let active = auth_service.create_session(user.id).await.unwrap();
+        let idle = auth_service.create_session(user.id).await.unwrap();
+        assert!(auth_service.validate_session(idle.token.clone()).await.is_ok());
+        
+        // Last used 20 minutes ago, but still 30 days from expiry
+        db.touch_session(idle.token.clone(), Utc::now() - Duration::minutes(20)).await.unwrap();
+        assert!(idle.expires_at > Utc::now());
+        
+        let result = auth_service.validate_session(idle.token.clone()).await;
+        assert!(matches!(result, Err(AuthError::SessionExpired)));
+        
+        // The idle session was cleaned up; the other one is untouched
+        assert!(db.get_session(&idle.token).await.unwrap().is_none());
+        assert!(auth_service.validate_session(active.token).await.is_ok());
+    }
+    
+    #[tokio::test]
+    async fn test_timezone_validated_and_round_trips() {
+        let auth_service = create_test_auth_service().await;
+        let user = auth_service.create_user(
+            "Traveller".to_string(),
+            "traveller@example.com".to_string(),
+            "password123".to_string(),
+        ).await.unwrap();
+        
+        assert_eq!(auth_service.get_timezone(user.id).await.unwrap(), Tz::UTC);
+        
+        let result = auth_service.set_timezone(user.id, "Europe/Atlantis".to_string()).await;
+        assert!(matches!(result, Err(AuthError::InvalidTimezone { ref timezone }) if timezone == "Europe/Atlantis"));
+        assert_eq!(auth_service.get_timezone(user.id).await.unwrap(), Tz::UTC);
+        
+        let tz = auth_service.set_timezone(user.id, "America/Sao_Paulo".to_string()).await.unwrap();
+        assert_eq!(tz.name(), "America/Sao_Paulo");
+        assert_eq!(auth_service.get_timezone(user.id).await.unwrap(), tz);
+    }
+    
     #[tokio::test]
     async fn test_secure_token_generation() {
         // Test Critical Gap #4: Session Token Security
@@ -252,6 +444,29 @@ mod tests {
         assert!(auth_service.validate_session(session.token).await.is_err());
     }
     
+    #[tokio::test]
+    async fn test_delete_all_sessions() {
+        let auth_service = create_test_auth_service().await;
+        
+        let user = auth_service.create_user(
+            "Test User".to_string(),
+            "test@example.com".to_string(),
+            "password123".to_string(),
+        ).await.unwrap();
+        
+        // Two sessions, e.g. laptop and phone
+        let first = auth_service.create_session(user.id).await.unwrap();
+        let second = auth_service.create_session(user.id).await.unwrap();
+        assert!(auth_service.validate_session(first.token.clone()).await.is_ok());
+        
+        let revoked = auth_service.delete_all_sessions(user.id).await.unwrap();
+        assert_eq!(revoked, 2);
+        
+        // Previously valid tokens are now rejected
+        assert!(auth_service.validate_session(first.token).await.is_err());
+        assert!(auth_service.validate_session(second.token).await.is_err());
+    }
+    
     #[tokio::test]
     async fn test_invalid_credentials() {
         let auth_service = create_test_auth_service().await;
@@ -291,7 +506,7 @@ mod tests {
             "short".to_string(),
         ).await;
         
-        assert!(matches!(result, Err(AuthError::WeakPassword)));
+        assert!(matches!(result, Err(AuthError::WeakPassword { .. })));
     }
     
     #[tokio::test]
@@ -328,4 +543,39 @@ mod tests {
         
         assert!(matches!(result, Err(AuthError::EmailExists { .. })));
     }
+    
+    #[tokio::test]
+    async fn test_impersonation_refuses_admin_targets() {
+        let auth_service = create_test_auth_service().await
+            .with_impersonation_ttl(std::time::Duration::from_secs(15 * 60));
+        let test_user = |name: &str, admin: bool| User {
+            admin,
+            ..User::for_tests(name)
+        };
+        let admin = test_user("admin", true);
+        let other_admin = test_user("other-admin", true);
+        let member = test_user("member", false);
+        for user in [&admin, &other_admin, &member] {
+            auth_service.db.create_user(user.clone()).await.unwrap();
+        }
+        
+        let result = auth_service.impersonate(admin.id, other_admin.id).await;
+        assert!(matches!(result, Err(AuthError::ImpersonationForbidden { user_id }) if user_id == other_admin.id));
+        
+        // Only admins can impersonate in the first place
+        let result = auth_service.impersonate(member.id, member.id).await;
+        assert!(matches!(result, Err(AuthError::InvalidCredentials)));
+        
+        // Impersonating a regular member yields a short, flagged session
+        let session = auth_service.impersonate(admin.id, member.id).await.unwrap();
+        assert_eq!(session.user_id, member.id);
+        assert!(session.expires_at <= Utc::now() + Duration::minutes(15));
+        assert_eq!(auth_service.validate_session(session.token.clone()).await.unwrap().id, member.id);
+        let (user, impersonated_by) = auth_service.validate_session_with_impersonator(session.token).await.unwrap();
+        assert_eq!((user.id, impersonated_by), (member.id, Some(admin.id)));
+        
+        let regular = auth_service.create_session(member.id).await.unwrap();
+        let (_, impersonated_by) = auth_service.validate_session_with_impersonator(regular.token).await.unwrap();
+        assert_eq!(impersonated_by, None);
+    }
 }
\ No newline at end of file
@@ -5,12 +5,24 @@ use reqwest::Client;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::timeout;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
+/// Longest webhook response body echoed back by a simulation
+const SIMULATION_BODY_LIMIT: usize = 4096;
+
+/// Match Rails ENDPOINT_TIMEOUT
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(7);
+
+use crate::config::{TextLimits, WelcomeReplyConfig};
 use crate::database::DatabaseWriter;
-use crate::errors::BotError;
+use crate::errors::{BotError, MessageError};
+use crate::events::{DomainEvent, EventBus, EventSubscriber};
+use crate::logging::audit::{AuditAction, AuditEvent};
 use crate::models::*;
-use crate::services::MessageServiceTrait;
+use crate::services::connection::ConnectionManager;
+use crate::services::room::RoomServiceTrait;
+use crate::services::{MessageServiceTrait, WebhookUrlPolicy};
+use crate::validation::normalize_text;
 
 /// Bot service trait for bot management and webhook delivery
 #[async_trait]
@@ -30,6 +42,12 @@ pub trait BotService: Send + Sync {
         webhook_url: Option<String>,
     ) -> Result<Bot, BotError>;
     
+    /// Lets admins pull the bot into a room by mentioning it there
+    async fn set_auto_join_on_mention(&self, bot_id: UserId, enabled: bool) -> Result<Bot, BotError>;
+    
+    /// Lets the bot post messages attributed to other users
+    async fn set_post_on_behalf(&self, bot_id: UserId, enabled: bool) -> Result<Bot, BotError>;
+    
     /// Delete a bot (deactivate)
     async fn delete_bot(&self, bot_id: UserId) -> Result<(), BotError>;
     
@@ -48,12 +66,18 @@ pub trait BotService: Send + Sync {
     /// Deliver webhook notification for a message
     async fn deliver_webhook(&self, bot: &Bot, message: &Message, room: &Room) -> Result<(), BotError>;
     
-    /// Create a message from bot
+    /// Sends a sample payload, as if `requested_by` had mentioned the bot,
+    /// to the bot's webhook and reports what came back. Nothing is posted.
+    async fn simulate_webhook(&self, bot_id: UserId, requested_by: &User) -> Result<WebhookSimulation, BotError>;
+    
+    /// Create a message from bot, or attributed to `on_behalf_of` if the
+    /// bot may post on behalf of users; the bot is recorded either way
     async fn create_bot_message(
         &self,
         bot_id: UserId,
         room_id: RoomId,
         content: String,
+        on_behalf_of: Option<UserId>,
     ) -> Result<Message, BotError>;
 }
 
@@ -63,6 +87,10 @@ pub struct BotServiceImpl {
     database_writer: Arc<dyn DatabaseWriter>,
     http_client: Client,
     message_service: Arc<dyn MessageServiceTrait>,
+    room_service: Arc<dyn RoomServiceTrait>,
+    text_limits: TextLimits,
+    url_policy: WebhookUrlPolicy,
+    max_bots: u32,
 }
 
 impl BotServiceImpl {
@@ -71,19 +99,53 @@ impl BotServiceImpl {
         database_writer: Arc<dyn DatabaseWriter>,
         message_service: Arc<dyn MessageServiceTrait>,
     ) -> Self {
-        let http_client = Client::builder()
-            .timeout(Duration::from_secs(7)) // Match Rails ENDPOINT_TIMEOUT
-            .build()
-            .expect("Failed to create HTTP client");
+        let url_policy = WebhookUrlPolicy::default();
+        let http_client = url_policy.http_client(WEBHOOK_TIMEOUT);
+        let room_service = Arc::new(crate::services::room::RoomService::new(database.clone()));
             
         Self {
             database,
             database_writer,
             http_client,
             message_service,
+            room_service,
+            text_limits: TextLimits::default(),
+            url_policy,
+            max_bots: 0,
         }
     }
     
+    /// Room service used for the bot's own post permission when it posts
+    /// on someone's behalf
+    pub fn with_room_service(mut self, room_service: Arc<dyn RoomServiceTrait>) -> Self {
+        self.room_service = room_service;
+        self
+    }
+    
+    /// Bots the instance may have in total (0 = unlimited)
+    pub fn with_max_bots(mut self, max_bots: u32) -> Self {
+        self.max_bots = max_bots;
+        self
+    }
+    
+    /// Longest bot names accepted, the same limit as for people
+    pub fn with_text_limits(mut self, text_limits: TextLimits) -> Self {
+        self.text_limits = text_limits;
+        self
+    }
+    
+    /// Where webhooks may point; private addresses are refused by default
+    pub fn with_url_policy(mut self, url_policy: WebhookUrlPolicy) -> Self {
+        self.http_client = url_policy.http_client(WEBHOOK_TIMEOUT);
+        self.url_policy = url_policy;
+        self
+    }
+    
+    fn normalize_name(&self, name: &str) -> Result<String, BotError> {
+        normalize_text("Name", name, self.text_limits.user_name)
+            .map_err(|e| BotError::InvalidName { reason: e.to_string() })
+    }
+    
     /// Generate a secure bot token (12 alphanumeric characters like Rails)
     fn generate_bot_token() -> String {
         const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
@@ -121,20 +183,13 @@ impl BotServiceImpl {
         Ok((UserId(user_id), bot_token.to_string()))
     }
     
-    /// Validate webhook URL
-    fn validate_webhook_url(url: &str) -> Result<(), BotError> {
+    /// Validate webhook URL; empty clears the webhook
+    fn validate_webhook_url(&self, url: &str) -> Result<(), BotError> {
         if url.is_empty() {
             return Ok(());
         }
         
-        let parsed = url::Url::parse(url)
-            .map_err(|_| BotError::InvalidWebhookUrl { url: url.to_string() })?;
-            
-        if !matches!(parsed.scheme(), "http" | "https") {
-            return Err(BotError::InvalidWebhookUrl { url: url.to_string() });
-        }
-        
-        Ok(())
+        self.url_policy.check_url(url).map(|_| ())
     }
     
     /// Create webhook payload for message
@@ -182,14 +237,14 @@ impl BotService for BotServiceImpl {
         webhook_url: Option<String>,
     ) -> Result<Bot, BotError> {
         // Validate inputs
-        if name.trim().is_empty() || name.len() > 50 {
-            return Err(BotError::InvalidName { 
-                reason: "Name must be between 1 and 50 characters".to_string() 
-            });
-        }
+        let name = self.normalize_name(&name)?;
         
         if let Some(ref url) = webhook_url {
-            Self::validate_webhook_url(url)?;
+            self.validate_webhook_url(url)?;
+        }
+        
+        if self.max_bots > 0 && self.database.count_bots().await? >= self.max_bots {
+            return Err(BotError::QuotaExceeded { limit: self.max_bots });
         }
         
         // Generate bot token
@@ -211,7 +266,7 @@ impl BotService for BotServiceImpl {
         self.database_writer.create_user(bot_user.clone()).await?;
         
         // Create webhook if URL provided
-        if let Some(webhook_url) = webhook_url.as_ref() {
+        if let Some(webhook_url) = webhook_url.as_ref().filter(|url| !url.is_empty()) {
             self.create_webhook_internal(bot_user.id, webhook_url).await?;
         }
         
@@ -222,6 +277,8 @@ impl BotService for BotServiceImpl {
             name,
             bot_token,
             webhook_url,
+            auto_join_on_mention: false,
+            post_on_behalf: false,
             created_at: bot_user.created_at,
         })
     }
@@ -237,16 +294,10 @@ impl BotService for BotServiceImpl {
             .ok_or(BotError::NotFound { bot_id })?;
         
         // Validate inputs
-        if let Some(ref new_name) = name {
-            if new_name.trim().is_empty() || new_name.len() > 50 {
-                return Err(BotError::InvalidName { 
-                    reason: "Name must be between 1 and 50 characters".to_string() 
-                });
-            }
-        }
+        let name = name.map(|name| self.normalize_name(&name)).transpose()?;
         
         if let Some(ref url) = webhook_url {
-            Self::validate_webhook_url(url)?;
+            self.validate_webhook_url(url)?;
         }
         
         // Update fields
@@ -261,20 +312,43 @@ impl BotService for BotServiceImpl {
             } else {
                 Some(new_webhook_url)
             };
-            // TODO: Update webhook in database
+            self.database_writer.set_bot_webhook_url(bot_id, bot.webhook_url.clone()).await?;
         }
         
         info!("Updated bot: {} ({})", bot.name, bot.id);
         Ok(bot)
     }
     
+    async fn set_auto_join_on_mention(&self, bot_id: UserId, enabled: bool) -> Result<Bot, BotError> {
+        let mut bot = self.get_bot(bot_id).await?
+            .ok_or(BotError::NotFound { bot_id })?;
+        
+        self.database_writer.set_bot_auto_join_on_mention(bot_id, enabled).await?;
+        bot.auto_join_on_mention = enabled;
+        
+        info!("Bot {} auto-join on mention: {}", bot_id, enabled);
+        Ok(bot)
+    }
+    
+    async fn set_post_on_behalf(&self, bot_id: UserId, enabled: bool) -> Result<Bot, BotError> {
+        let mut bot = self.get_bot(bot_id).await?
+            .ok_or(BotError::NotFound { bot_id })?;
+        
+        self.database_writer.set_bot_post_on_behalf(bot_id, enabled).await?;
+        bot.post_on_behalf = enabled;
+        
+        info!("Bot {} may post on behalf of users: {}", bot_id, enabled);
+        Ok(bot)
+    }
+    
     async fn delete_bot(&self, bot_id: UserId) -> Result<(), BotError> {
         // Verify bot exists
         let _bot = self.get_bot(bot_id).await?
             .ok_or(BotError::NotFound { bot_id })?;
         
-        // TODO: Implement bot deactivation (set bot_token to NULL)
-        // For now, we'll just log the action
+        // Without its token the bot can't authenticate and no longer counts
+        // as a bot; its user stays so its messages keep their author
+        self.database_writer.deactivate_bot(bot_id).await?;
         info!("Deactivated bot: {}", bot_id);
         Ok(())
     }
@@ -286,9 +360,13 @@ impl BotService for BotServiceImpl {
             if let Some(bot) = user.to_bot() {
                 // Get webhook URL
                 let webhook_url = self.get_webhook_url_internal(bot_id).await?;
+                let auto_join_on_mention = self.database.get_bot_auto_join_on_mention(bot_id).await?;
+                let post_on_behalf = self.database.get_bot_post_on_behalf(bot_id).await?;
                 
                 Ok(Some(Bot {
                     webhook_url,
+                    auto_join_on_mention,
+                    post_on_behalf,
                     ..bot
                 }))
             } else {
@@ -346,6 +424,12 @@ impl BotService for BotServiceImpl {
             }
         };
         
+        // The stored URL may predate today's rules, or now resolve elsewhere
+        if let Err(e) = self.url_policy.check_delivery(webhook_url).await {
+            warn!("Refusing webhook delivery for bot {} ({}): {}", bot.name, bot.id, e);
+            return Err(e);
+        }
+        
         // Get message creator
         let creator = self.database.get_user_by_id(message.creator_id).await?
             .ok_or(BotError::Database(crate::errors::DatabaseError::DataIntegrity { 
@@ -362,7 +446,7 @@ impl BotService for BotServiceImpl {
             .json(&payload)
             .send();
         
-        match timeout(Duration::from_secs(7), webhook_future).await {
+        match timeout(WEBHOOK_TIMEOUT, webhook_future).await {
             Ok(Ok(response)) => {
                 info!("Webhook delivered to bot {} ({}): {}", bot.name, bot.id, response.status());
                 
@@ -386,16 +470,70 @@ impl BotService for BotServiceImpl {
             }
             Err(_) => {
                 error!("Webhook timeout for bot {} ({})", bot.name, bot.id);
-                Err(BotError::WebhookTimeout { timeout_seconds: 7 })
+                Err(BotError::WebhookTimeout { timeout_seconds: WEBHOOK_TIMEOUT.as_secs() })
             }
         }
     }
     
+    async fn simulate_webhook(&self, bot_id: UserId, requested_by: &User) -> Result<WebhookSimulation, BotError> {
+        let bot = self.get_bot(bot_id).await?
+            .ok_or(BotError::NotFound { bot_id })?;
+        let webhook_url = bot.webhook_url.clone()
+            .ok_or(BotError::NoWebhook { bot_id })?;
+        // The stored URL may predate today's rules, or now resolve elsewhere
+        self.url_policy.check_delivery(&webhook_url).await?;
+        
+        // Never stored: the sample lives only in the payload
+        let room = Room {
+            id: RoomId::new(),
+            name: "Webhook simulation".to_string(),
+            topic: None,
+            room_type: RoomType::Open,
+            created_at: Utc::now(),
+            last_message_at: None,
+        };
+        let message = Message::new(
+            room.id,
+            requested_by.id,
+            format!("@{} this is a test message from Campfire", bot.name),
+            uuid::Uuid::new_v4(),
+        );
+        let payload = self.create_webhook_payload(&message, &room, requested_by, &bot);
+        
+        let started = std::time::Instant::now();
+        let webhook_future = self.http_client
+            .post(&webhook_url)
+            .header("Content-Type", "application/json")
+            .json(&payload)
+            .send();
+        
+        let response = match timeout(WEBHOOK_TIMEOUT, webhook_future).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(e)) => return Err(BotError::WebhookDeliveryFailed { reason: e.to_string() }),
+            Err(_) => return Err(BotError::WebhookTimeout { timeout_seconds: WEBHOOK_TIMEOUT.as_secs() }),
+        };
+        let status = response.status().as_u16();
+        let body = response.text().await
+            .map_err(|e| BotError::HttpRequest(e.to_string()))?;
+        let latency_ms = started.elapsed().as_millis() as u64;
+        
+        info!("Simulated webhook for bot {} ({}): {} in {}ms", bot.name, bot.id, status, latency_ms);
+        
+        Ok(WebhookSimulation {
+            url: webhook_url,
+            payload,
+            status,
+            body: body.chars().take(SIMULATION_BODY_LIMIT).collect(),
+            latency_ms,
+        })
+    }
+    
     async fn create_bot_message(
         &self,
         bot_id: UserId,
         room_id: RoomId,
         content: String,
+        on_behalf_of: Option<UserId>,
     ) -> Result<Message, BotError> {
         // Verify bot exists and is actually a bot
         let bot_user = self.database.get_user_by_id(bot_id).await?
@@ -405,19 +543,68 @@ impl BotService for BotServiceImpl {
             return Err(BotError::NotABot { user_id: bot_id });
         }
         
+        // Messages on a user's behalf are checked against the user's own
+        // access to the room, and the bot must be able to reach it and
+        // post there itself too
+        let author_id = match on_behalf_of {
+            Some(user_id) if user_id != bot_id => {
+                if !self.database.get_bot_post_on_behalf(bot_id).await? {
+                    return Err(BotError::OnBehalfNotGranted { bot_id });
+                }
+                match self.message_service.check_room_access(room_id, bot_id).await {
+                    Ok(true) => {}
+                    Ok(false) | Err(MessageError::Authorization { .. }) => {
+                        return Err(BotError::PostingRestricted { bot_id, room_id });
+                    }
+                    Err(e) => {
+                        return Err(BotError::Database(crate::errors::DatabaseError::DataIntegrity {
+                            reason: e.to_string()
+                        }));
+                    }
+                }
+                let may_post = self.room_service
+                    .check_post_permission(room_id, bot_id)
+                    .await
+                    .map_err(|e| BotError::Database(crate::errors::DatabaseError::DataIntegrity {
+                        reason: e.to_string()
+                    }))?;
+                if !may_post {
+                    return Err(BotError::PostingRestricted { bot_id, room_id });
+                }
+                let user = self.database.get_user_by_id(user_id).await?;
+                if user.is_none_or(|user| user.is_bot()) {
+                    return Err(BotError::InvalidOnBehalf { user_id });
+                }
+                user_id
+            }
+            _ => bot_id,
+        };
+        
         // Create message using message service
         let client_message_id = uuid::Uuid::new_v4();
         
         match self.message_service.create_message_with_deduplication(
             content,
             room_id,
-            bot_id,
+            author_id,
             client_message_id,
         ).await {
+            Ok(message) if author_id != bot_id => {
+                self.database_writer.record_bot_attribution(message.id, bot_id).await?;
+                on_behalf_audit_event(bot_id, &message).log();
+                info!("Bot {} created message in room {} on behalf of {}", bot_id, room_id, author_id);
+                Ok(message)
+            }
             Ok(message) => {
                 info!("Bot {} created message in room {}", bot_id, room_id);
                 Ok(message)
             }
+            Err(MessageError::PostingRestricted { .. }) if author_id == bot_id => {
+                Err(BotError::PostingRestricted { bot_id, room_id })
+            }
+            Err(MessageError::Authorization { .. } | MessageError::PostingRestricted { .. }) if author_id != bot_id => {
+                Err(BotError::InvalidOnBehalf { user_id: author_id })
+            }
             Err(e) => {
                 error!("Failed to create bot message: {}", e);
                 Err(BotError::Database(crate::errors::DatabaseError::DataIntegrity { 
@@ -428,15 +615,177 @@ impl BotService for BotServiceImpl {
     }
 }
 
+/// Audit record of a bot posting as someone else
+fn on_behalf_audit_event(bot_id: UserId, message: &Message) -> AuditEvent {
+    AuditEvent::new(AuditAction::BotPostedOnBehalf, "message")
+        .with_user(bot_id)
+        .with_resource_id(message.id.to_string())
+        .with_detail("on_behalf_of", message.creator_id.to_string())
+        .with_detail("room_id", message.room_id.to_string())
+}
+
 // Internal helper methods
 impl BotServiceImpl {
-    async fn create_webhook_internal(&self, _bot_id: UserId, _webhook_url: &str) -> Result<(), BotError> {
-        // TODO: Implement webhook table creation
+    async fn create_webhook_internal(&self, bot_id: UserId, webhook_url: &str) -> Result<(), BotError> {
+        Ok(self.database_writer.set_bot_webhook_url(bot_id, Some(webhook_url.to_string())).await?)
+    }
+    
+    async fn get_webhook_url_internal(&self, bot_id: UserId) -> Result<Option<String>, BotError> {
+        Ok(self.database.get_bot_webhook_url(bot_id).await?)
+    }
+}
+
+/// Adds bots that allow it to a room when an admin of the room mentions
+/// them there, so the mention reaches a bot that wasn't invited yet
+///
+/// The room's fan-out of the mentioning message has already happened by
+/// the time the bot joins, so the message is sent on to the bot's own
+/// connections as well.
+pub struct BotAutoJoinSubscriber {
+    database: Arc<crate::CampfireDatabase>,
+    room_service: Arc<dyn RoomServiceTrait>,
+    connection_manager: Arc<dyn ConnectionManager>,
+}
+
+impl BotAutoJoinSubscriber {
+    pub fn new(
+        database: Arc<crate::CampfireDatabase>,
+        room_service: Arc<dyn RoomServiceTrait>,
+        connection_manager: Arc<dyn ConnectionManager>,
+    ) -> Self {
+        Self { database, room_service, connection_manager }
+    }
+    
+    async fn join_mentioned_bots(&self, message: &Message) -> Result<(), BotError> {
+        // Direct rooms are between exactly the people in them
+        let Some(room) = self.database.get_room_by_id(message.room_id).await? else {
+            return Ok(());
+        };
+        if matches!(room.room_type, RoomType::Direct) {
+            return Ok(());
+        }
+        
+        let is_site_admin = self.database.get_user_by_id(message.creator_id).await?
+            .is_some_and(|user| user.admin);
+        let is_room_admin = self.database.get_membership(message.room_id, message.creator_id).await?
+            .is_some_and(|membership| matches!(membership.involvement_level, InvolvementLevel::Admin));
+        if !is_site_admin && !is_room_admin {
+            return Ok(());
+        }
+        
+        for mention in &message.mentions {
+            let Some(bot) = self.database.get_user_by_handle(mention).await? else {
+                continue;
+            };
+            if bot.bot_token.is_none()
+                || !self.database.get_bot_auto_join_on_mention(bot.id).await?
+                || self.database.get_membership(message.room_id, bot.id).await?.is_some()
+            {
+                continue;
+            }
+            
+            // The room service applies the room type's rules on who may add
+            match self.room_service
+                .add_member(message.room_id, bot.id, message.creator_id, InvolvementLevel::Member)
+                .await
+            {
+                Ok(()) => {
+                    info!("Bot {} joined room {} on mention by {}", bot.id, message.room_id, message.creator_id);
+                    let frame = WebSocketMessage::NewMessage { message: message.clone() };
+                    if let Err(e) = self.connection_manager.send_to_user(bot.id, frame).await {
+                        warn!("Mention {} not delivered to bot {}: {}", message.id, bot.id, e);
+                    }
+                }
+                Err(e) => warn!("Bot {} not added to room {} on mention: {}", bot.id, message.room_id, e),
+            }
+        }
+        
         Ok(())
     }
+}
+
+#[async_trait]
+impl EventSubscriber for BotAutoJoinSubscriber {
+    async fn handle(&self, event: &DomainEvent) {
+        if let DomainEvent::MessageCreated { message } = event {
+            if message.mentions.is_empty() {
+                return;
+            }
+            if let Err(e) = self.join_mentioned_bots(message).await {
+                warn!("Failed to auto-join mentioned bots for message {}: {}", message.id, e);
+            }
+        }
+    }
+}
+
+/// Mixed into a user's ID to key their welcome reply, so it's stored once
+/// however often their first message is seen
+const WELCOME_REPLY_KEY: u128 = 0x77656c636f6d655f7265706c79000000;
+
+/// Posts onboarding tips as the configured bot when a person's first
+/// message anywhere is in the welcome room
+pub struct WelcomeReplySubscriber {
+    database: Arc<crate::CampfireDatabase>,
+    events: EventBus,
+    config: WelcomeReplyConfig,
+}
+
+impl WelcomeReplySubscriber {
+    /// The reply is emitted on `events` like any other new message
+    pub fn new(database: Arc<crate::CampfireDatabase>, events: EventBus, config: WelcomeReplyConfig) -> Self {
+        Self { database, events, config }
+    }
     
-    async fn get_webhook_url_internal(&self, _bot_id: UserId) -> Result<Option<String>, BotError> {
-        // TODO: Implement webhook URL retrieval from database
-        Ok(None)
+    async fn welcome(&self, message: &Message) -> Result<(), BotError> {
+        let Some(room) = self.database.get_room_by_id(message.room_id).await? else {
+            return Ok(());
+        };
+        if !matches!(room.room_type, RoomType::Open) || !room.name.eq_ignore_ascii_case(&self.config.room_name) {
+            return Ok(());
+        }
+        let Some(author) = self.database.get_user_by_id(message.creator_id).await? else {
+            return Ok(());
+        };
+        if author.is_bot() {
+            return Ok(());
+        }
+        
+        // Only their first message ever, wherever they posted it
+        if self.database.get_messages_by_creator(author.id, None, 2).await?.len() != 1 {
+            return Ok(());
+        }
+        
+        let bot_id = UserId(self.config.bot_id);
+        let bot = self.database.get_user_by_id(bot_id).await?
+            .ok_or(BotError::NotFound { bot_id })?;
+        if !bot.is_bot() {
+            return Err(BotError::NotABot { user_id: bot_id });
+        }
+        
+        let content = self.config.template
+            .replace("{name}", &author.name)
+            .replace("{room}", &room.name);
+        let client_message_id = uuid::Uuid::from_u128(author.id.0.as_u128() ^ WELCOME_REPLY_KEY);
+        let reply = self.database
+            .write_message_with_deduplication(Message::new(room.id, bot_id, content, client_message_id))
+            .await?;
+        if !reply.inserted {
+            return Ok(());
+        }
+        info!("Welcomed {} in room {}", author.id, room.id);
+        
+        self.events.emit(DomainEvent::MessageCreated { message: reply.message }).await;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for WelcomeReplySubscriber {
+    async fn handle(&self, event: &DomainEvent) {
+        if let DomainEvent::MessageCreated { message } = event {
+            if let Err(e) = self.welcome(message).await {
+                warn!("Failed to post welcome reply to message {}: {}", message.id, e);
+            }
+        }
     }
-}
\ No newline at end of file
+}
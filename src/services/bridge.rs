@@ -0,0 +1,262 @@
+//! Room bridges: mirroring a room into an external chat
+//!
+//! Teams moving over gradually can relay a room into Slack or Discord. Each
+//! new message is posted to the bridge's incoming-webhook URL as
+//! `author: text`, with the message's HTML flattened to plain text, retried
+//! with backoff on timeouts and server errors like room webhooks. Messages
+//! from the bridge's inbound bot, which carries the other side's replies
+//! back in, are never relayed, so the chats don't echo each other.
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::database::CampfireDatabase;
+use crate::errors::BotError;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::models::*;
+use crate::services::delivery::{spawn_for_new_message, WebhookDelivery};
+use crate::services::WebhookUrlPolicy;
+
+#[derive(Clone)]
+pub struct RoomBridgeService {
+    db: Arc<CampfireDatabase>,
+    delivery: WebhookDelivery,
+}
+
+impl RoomBridgeService {
+    pub fn new(db: Arc<CampfireDatabase>) -> Self {
+        Self {
+            db,
+            delivery: WebhookDelivery::default(),
+        }
+    }
+
+    /// Where bridges may point; private addresses are refused by default
+    pub fn with_url_policy(mut self, url_policy: WebhookUrlPolicy) -> Self {
+        self.delivery = self.delivery.with_url_policy(url_policy);
+        self
+    }
+
+    /// Attempts per relay and the delay before the first retry, which
+    /// doubles on each further retry
+    pub fn with_retry(mut self, max_attempts: u32, retry_delay: Duration) -> Self {
+        self.delivery = self.delivery.with_retry(max_attempts, retry_delay);
+        self
+    }
+
+    /// Sets up the room's bridge, replacing any it already had
+    pub async fn set_bridge(&self, room_id: RoomId, request: SetRoomBridgeRequest) -> Result<RoomBridge, BotError> {
+        if request.url.is_empty() {
+            return Err(BotError::InvalidWebhookUrl { url: request.url });
+        }
+        self.delivery.url_policy().check_url(&request.url)?;
+
+        if let Some(bot_id) = request.inbound_bot_id {
+            match self.db.get_user_by_id(bot_id).await? {
+                Some(user) if user.is_bot() => {}
+                _ => return Err(BotError::NotABot { user_id: bot_id }),
+            }
+        }
+
+        let created_at = match self.db.get_room_bridge(room_id).await? {
+            Some(existing) => existing.created_at,
+            None => Utc::now(),
+        };
+        let bridge = RoomBridge {
+            room_id,
+            url: request.url,
+            format: request.format,
+            inbound_bot_id: request.inbound_bot_id,
+            enabled: request.enabled.unwrap_or(true),
+            created_at,
+        };
+        self.db.set_room_bridge(bridge.clone()).await?;
+
+        info!("Bridged room {} to {}", room_id, bridge.format.as_str());
+        Ok(bridge)
+    }
+
+    pub async fn get_bridge(&self, room_id: RoomId) -> Result<RoomBridge, BotError> {
+        self.db
+            .get_room_bridge(room_id)
+            .await?
+            .ok_or(BotError::BridgeNotFound { room_id })
+    }
+
+    pub async fn delete_bridge(&self, room_id: RoomId) -> Result<(), BotError> {
+        if !self.db.delete_room_bridge(room_id).await? {
+            return Err(BotError::BridgeNotFound { room_id });
+        }
+        Ok(())
+    }
+
+    /// Posts the message to its room's bridge, after retries. `Ok(false)`
+    /// when nothing was sent: the room isn't bridged, the bridge is off, or
+    /// the message came in over the bridge.
+    pub async fn relay_message(&self, message: &Message) -> Result<bool, BotError> {
+        let bridge = match self.db.get_room_bridge(message.room_id).await? {
+            Some(bridge) if bridge.enabled => bridge,
+            _ => return Ok(false),
+        };
+        if bridge.inbound_bot_id == Some(message.creator_id) {
+            return Ok(false);
+        }
+
+        let author = self.db.get_user_by_id(message.creator_id).await?.ok_or_else(|| {
+            BotError::Database(crate::errors::DatabaseError::DataIntegrity {
+                reason: "Message creator not found".to_string(),
+            })
+        })?;
+        // Content is stored as HTML; the chat app would show the markup
+        let text = crate::rich_text::plain_text(&message.content);
+        let body = serde_json::to_vec(&payload(bridge.format, &author.name, &text))?;
+
+        // Client errors won't get better by retrying, except rate limits
+        self.delivery
+            .post(&bridge.url, &[], &body, |status| {
+                status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            })
+            .await?;
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for RoomBridgeService {
+    async fn handle(&self, event: &DomainEvent) {
+        let service = self.clone();
+        spawn_for_new_message(event, |message| async move {
+            if let Err(e) = service.relay_message(&message).await {
+                warn!("Bridge for room {} failed to relay message {}: {}", message.room_id, message.id, e);
+            }
+        });
+    }
+}
+
+/// Incoming-webhook body for the chat app, with the author's name in bold
+fn payload(format: BridgeFormat, author: &str, text: &str) -> serde_json::Value {
+    match format {
+        BridgeFormat::Slack => json!({ "text": format!("*{}*: {}", author, text) }),
+        BridgeFormat::Discord => json!({ "content": format!("**{}**: {}", author, text) }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, routing::post, Router};
+    use std::sync::Mutex;
+    use uuid::Uuid;
+
+    type Received = Arc<Mutex<Vec<serde_json::Value>>>;
+
+    /// Records every relayed body and answers 200
+    async fn spawn_receiver() -> (String, Received) {
+        let received: Received = Arc::default();
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(|State(received): State<Received>, axum::Json(body): axum::Json<serde_json::Value>| async move {
+                    received.lock().unwrap().push(body);
+                    axum::http::StatusCode::OK
+                }),
+            )
+            .with_state(received.clone());
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+        (format!("http://{}/hook", addr), received)
+    }
+
+    async fn create_user(db: &CampfireDatabase, name: &str, bot_token: Option<String>) -> UserId {
+        let user_id = UserId::new();
+        db.create_user(User {
+            id: user_id,
+            bot_token,
+            ..User::for_tests(name)
+        }).await.unwrap();
+        user_id
+    }
+
+    #[tokio::test]
+    async fn test_bridged_message_is_relayed_but_relayed_back_message_is_not() {
+        let (url, received) = spawn_receiver().await;
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        // The receiver listens on loopback
+        let service = RoomBridgeService::new(db.clone())
+            .with_retry(2, Duration::from_millis(10))
+            .with_url_policy(WebhookUrlPolicy::default().with_private_networks(true));
+
+        let author = create_user(&db, "Alice", None).await;
+        let relay_bot = create_user(&db, "Slack relay", Some("relay-token".to_string())).await;
+        let room_id = RoomId::new();
+        db.create_room(Room {
+            id: room_id,
+            ..Room::for_tests("Migration", RoomType::Open)
+        }).await.unwrap();
+
+        // Only bots can be the inbound side
+        let request = |inbound_bot_id| SetRoomBridgeRequest {
+            url: url.clone(),
+            format: BridgeFormat::Slack,
+            inbound_bot_id: Some(inbound_bot_id),
+            enabled: None,
+        };
+        assert!(matches!(
+            service.set_bridge(room_id, request(author)).await,
+            Err(BotError::NotABot { .. })
+        ));
+        service.set_bridge(room_id, request(relay_bot)).await.unwrap();
+
+        let message = Message::new(room_id, author, "deploy is done".to_string(), Uuid::new_v4());
+        db.create_message_with_deduplication(message.clone()).await.unwrap();
+        assert!(service.relay_message(&message).await.unwrap());
+
+        // What came back from Slack through the relay bot stays here
+        let echoed = Message::new(room_id, relay_bot, "Bob: nice".to_string(), Uuid::new_v4());
+        db.create_message_with_deduplication(echoed.clone()).await.unwrap();
+        assert!(!service.relay_message(&echoed).await.unwrap());
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0], json!({ "text": "*Alice*: deploy is done" }));
+    }
+
+    #[tokio::test]
+    async fn test_relayed_text_has_no_markup_or_entities() {
+        let (url, received) = spawn_receiver().await;
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let service = RoomBridgeService::new(db.clone())
+            .with_url_policy(WebhookUrlPolicy::default().with_private_networks(true));
+
+        let author = create_user(&db, "Alice", None).await;
+        let bob = create_user(&db, "Bob", None).await;
+        let room_id = RoomId::new();
+        db.create_room(Room {
+            id: room_id,
+            ..Room::for_tests("Migration", RoomType::Open)
+        }).await.unwrap();
+        service.set_bridge(room_id, SetRoomBridgeRequest {
+            url,
+            format: BridgeFormat::Discord,
+            inbound_bot_id: None,
+            enabled: None,
+        }).await.unwrap();
+
+        // What the rich text pipeline stores for "@bob deploy & migrate are done"
+        let content = format!(
+            r#"<a href="/users/{}" data-mention-id="{}" class="mention">@bob</a> deploy &amp; <strong>migrate</strong> are done"#,
+            bob.0, bob.0
+        );
+        let message = Message::new(room_id, author, content, Uuid::new_v4());
+        assert!(service.relay_message(&message).await.unwrap());
+
+        let received = received.lock().unwrap();
+        assert_eq!(received[0], json!({ "content": "**Alice**: @bob deploy & migrate are done" }));
+    }
+}
@@ -226,6 +226,7 @@ impl CacheService {
         request.query.hash(&mut hasher);
         request.limit.hash(&mut hasher);
         request.offset.hash(&mut hasher);
+        request.prefix.hash(&mut hasher);
         request.room_id.hash(&mut hasher);
         
         format!("{:x}", hasher.finish())
@@ -588,6 +589,7 @@ mod tests {
             limit: Some(20),
             offset: Some(0),
             room_id: None,
+            prefix: false,
         };
         
         let request2 = SearchRequest {
@@ -595,6 +597,7 @@ mod tests {
             limit: Some(20),
             offset: Some(0),
             room_id: None,
+            prefix: false,
         };
         
         let request3 = SearchRequest {
@@ -602,6 +605,7 @@ mod tests {
             limit: Some(20),
             offset: Some(0),
             room_id: None,
+            prefix: false,
         };
         
         let hash1 = cache.hash_search_query(&request1);
@@ -19,6 +19,7 @@ use crate::services::cache::CacheServiceTrait;
 /// - Failed lookups: 5 minutes (prevents repeated DB queries for invalid tokens)
 #[derive(Clone)]
 pub struct CachedAuthService {
+    db: Arc<CampfireDatabase>,
     auth_service: AuthService,
     cache_service: Arc<dyn CacheServiceTrait>,
 }
@@ -29,11 +30,21 @@ impl CachedAuthService {
         cache_service: Arc<dyn CacheServiceTrait>,
     ) -> Self {
         Self {
-            auth_service: AuthService::new(db),
+            auth_service: AuthService::new(db.clone()),
+            db,
             cache_service,
         }
     }
     
+    /// Expires sessions unused for longer than `idle_timeout`
+    /// 
+    /// Activity has to be seen on every request, so validation skips the
+    /// session cache while an idle timeout is set.
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.auth_service = self.auth_service.with_idle_timeout(idle_timeout);
+        self
+    }
+    
     /// Session cache TTL - 30 minutes for security balance
     const SESSION_CACHE_TTL: Duration = Duration::from_secs(1800);
 }
@@ -74,11 +85,20 @@ impl AuthServiceTrait for CachedAuthService {
     }
     
     async fn validate_session(&self, token: String) -> Result<User, AuthError> {
-        // Try cache first
+        let (user, _) = self.validate_session_with_impersonator(token).await?;
+        Ok(user)
+    }
+    
+    async fn validate_session_with_impersonator(&self, token: String) -> Result<(User, Option<UserId>), AuthError> {
+        if self.auth_service.idle_timeout().is_some() {
+            return self.auth_service.validate_session_with_impersonator(token).await;
+        }
+        
+        // Try cache first; only sessions nobody is impersonating get cached
         match self.cache_service.get_cached_session(&token).await {
             Ok(Some(user)) => {
                 tracing::debug!("Session cache hit for token: {}", &token[..8]);
-                return Ok(user);
+                return Ok((user, None));
             }
             Ok(None) => {
                 tracing::debug!("Session cache miss for token: {}", &token[..8]);
@@ -89,17 +109,19 @@ impl AuthServiceTrait for CachedAuthService {
         }
         
         // Cache miss - validate with database
-        match self.auth_service.validate_session(token.clone()).await {
-            Ok(user) => {
+        match self.auth_service.validate_session_with_impersonator(token.clone()).await {
+            Ok((user, impersonated_by)) => {
                 // Cache the successful validation
-                if let Err(e) = self.cache_service.cache_session(
-                    token.clone(),
-                    user.clone(),
-                    Self::SESSION_CACHE_TTL,
-                ).await {
-                    tracing::warn!("Failed to cache session for token {}: {}", &token[..8], e);
+                if impersonated_by.is_none() {
+                    if let Err(e) = self.cache_service.cache_session(
+                        token.clone(),
+                        user.clone(),
+                        Self::SESSION_CACHE_TTL,
+                    ).await {
+                        tracing::warn!("Failed to cache session for token {}: {}", &token[..8], e);
+                    }
                 }
-                Ok(user)
+                Ok((user, impersonated_by))
             }
             Err(e) => {
                 // Don't cache failures for security reasons
@@ -121,6 +143,22 @@ impl AuthServiceTrait for CachedAuthService {
         result
     }
     
+    async fn delete_all_sessions(&self, user_id: UserId) -> Result<u64, AuthError> {
+        // Collect tokens before deleting so cached copies can be evicted
+        let tokens = self.db.get_user_session_tokens(user_id).await
+            .unwrap_or_default();
+        
+        let result = self.auth_service.delete_all_sessions(user_id).await;
+        
+        for token in tokens {
+            if let Err(e) = self.cache_service.invalidate_session(&token).await {
+                tracing::warn!("Failed to invalidate cached session for token {}: {}", &token[..8], e);
+            }
+        }
+        
+        result
+    }
+    
     async fn create_user(
         &self,
         name: String,
@@ -131,6 +169,18 @@ impl AuthServiceTrait for CachedAuthService {
         // No caching needed for this infrequent operation
         self.auth_service.create_user(name, email, password).await
     }
+    
+    async fn get_timezone(&self, user_id: UserId) -> Result<chrono_tz::Tz, AuthError> {
+        self.auth_service.get_timezone(user_id).await
+    }
+    
+    async fn set_timezone(&self, user_id: UserId, timezone: String) -> Result<chrono_tz::Tz, AuthError> {
+        self.auth_service.set_timezone(user_id, timezone).await
+    }
+    
+    async fn impersonate(&self, admin_id: UserId, target_id: UserId) -> Result<Session, AuthError> {
+        self.auth_service.impersonate(admin_id, target_id).await
+    }
 }
 
 #[cfg(test)]
@@ -226,4 +276,33 @@ mod tests {
         ).await;
         assert!(result2.is_err());
     }
-}
\ No newline at end of file
+    
+    #[tokio::test]
+    async fn test_impersonation_sessions_are_not_cached() {
+        let service = create_test_cached_auth_service().await;
+        let admin = User {
+            admin: true,
+            ..User::for_tests("Admin")
+        };
+        service.db.create_user(admin.clone()).await.unwrap();
+        let member = service.create_user(
+            "Member".to_string(),
+            "member@example.com".to_string(),
+            "password123".to_string(),
+        ).await.unwrap();
+        
+        // A cache hit would lose the admin behind the session
+        let session = service.impersonate(admin.id, member.id).await.unwrap();
+        for _ in 0..2 {
+            let (user, impersonated_by) = service
+                .validate_session_with_impersonator(session.token.clone())
+                .await
+                .unwrap();
+            assert_eq!((user.id, impersonated_by), (member.id, Some(admin.id)));
+        }
+        
+        let own = service.create_session(member.id).await.unwrap();
+        let (_, impersonated_by) = service.validate_session_with_impersonator(own.token).await.unwrap();
+        assert_eq!(impersonated_by, None);
+    }
+}
@@ -5,7 +5,7 @@ use uuid::Uuid;
 
 use crate::database::CampfireDatabase;
 use crate::errors::{MessageError, BroadcastError};
-use crate::models::{Message, MessageId, RoomId, UserId};
+use crate::models::{Mention, Message, MessageId, RoomId, SavedMessage, SeenReceipt, UserId};
 use crate::services::message::{MessageService, MessageServiceTrait};
 use crate::services::room::RoomServiceTrait;
 use crate::services::connection::ConnectionManager;
@@ -162,6 +162,68 @@ impl MessageServiceTrait for CachedMessageService {
         }
     }
     
+    async fn get_first_unread_message_id(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+    ) -> Result<Option<MessageId>, MessageError> {
+        // Per-user, so never served from the shared message cache
+        self.message_service.get_first_unread_message_id(room_id, user_id).await
+    }
+    
+    async fn mark_read(
+        &self,
+        user_id: UserId,
+        message_id: MessageId,
+    ) -> Result<(), MessageError> {
+        self.message_service.mark_read(user_id, message_id).await
+    }
+    
+    async fn get_seen_by(
+        &self,
+        room_id: RoomId,
+        message_id: MessageId,
+        user_id: UserId,
+    ) -> Result<Vec<SeenReceipt>, MessageError> {
+        // Read markers move constantly, so this is never cached
+        self.message_service.get_seen_by(room_id, message_id, user_id).await
+    }
+    
+    async fn get_mentions(
+        &self,
+        user_id: UserId,
+        limit: u32,
+        before: Option<MessageId>,
+    ) -> Result<Vec<Mention>, MessageError> {
+        self.message_service.get_mentions(user_id, limit, before).await
+    }
+    
+    async fn save_message(
+        &self,
+        user_id: UserId,
+        message_id: MessageId,
+    ) -> Result<(), MessageError> {
+        self.message_service.save_message(user_id, message_id).await
+    }
+    
+    async fn unsave_message(
+        &self,
+        user_id: UserId,
+        message_id: MessageId,
+    ) -> Result<(), MessageError> {
+        self.message_service.unsave_message(user_id, message_id).await
+    }
+    
+    async fn get_saved_messages(
+        &self,
+        user_id: UserId,
+        limit: u32,
+        before: Option<MessageId>,
+    ) -> Result<Vec<SavedMessage>, MessageError> {
+        // Private per user, so never in the shared message cache
+        self.message_service.get_saved_messages(user_id, limit, before).await
+    }
+    
     async fn broadcast_message(
         &self,
         message: &Message,
@@ -174,6 +236,10 @@ impl MessageServiceTrait for CachedMessageService {
     fn connection_manager(&self) -> &Arc<dyn ConnectionManager> {
         self.message_service.connection_manager()
     }
+    
+    async fn check_room_access(&self, room_id: RoomId, user_id: UserId) -> Result<bool, MessageError> {
+        self.message_service.check_room_access(room_id, user_id).await
+    }
 }
 
 /// Extension methods for cache management
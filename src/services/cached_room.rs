@@ -4,9 +4,10 @@ use std::time::Duration;
 
 use crate::database::CampfireDatabase;
 use crate::errors::RoomError;
-use crate::models::{Room, RoomId, RoomType, UserId, InvolvementLevel};
+use crate::models::{CannedResponse, DirectConversation, MentionCandidate, Room, RoomId, RoomListEntry, RoomPermissions, RoomType, UserId, InvolvementLevel, PostPermission};
 use crate::services::room::{RoomService, RoomServiceTrait};
 use crate::services::cache::CacheServiceTrait;
+use crate::sounds::SoundPolicy;
 
 /// Cached room service that wraps the base RoomService
 /// 
@@ -108,6 +109,61 @@ impl RoomServiceTrait for CachedRoomService {
         result
     }
     
+    async fn delete_room_permanently(
+        &self,
+        room_id: RoomId,
+        deleted_by: UserId,
+        confirmation: Option<String>,
+    ) -> Result<(), RoomError> {
+        let result = self.room_service.delete_room_permanently(room_id, deleted_by, confirmation).await;
+        
+        if result.is_ok() {
+            if let Err(e) = self.cache_service.invalidate_room_memberships(room_id).await {
+                tracing::warn!("Failed to invalidate room memberships for room {}: {}", room_id, e);
+            }
+            
+            if let Err(e) = self.cache_service.invalidate_room_messages(room_id).await {
+                tracing::warn!("Failed to invalidate cached messages for room {}: {}", room_id, e);
+            }
+            
+            if let Err(e) = self.cache_service.invalidate_search_cache().await {
+                tracing::warn!("Failed to invalidate search cache after deleting room {}: {}", room_id, e);
+            }
+        }
+        
+        result
+    }
+    
+    async fn set_retention(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        retention_days: Option<u32>,
+    ) -> Result<(), RoomError> {
+        self.room_service.set_retention(room_id, changed_by, retention_days).await
+    }
+    
+    async fn remove_member(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+        removed_by: UserId,
+    ) -> Result<(), RoomError> {
+        let result = self.room_service.remove_member(room_id, user_id, removed_by).await;
+        
+        if result.is_ok() {
+            if let Err(e) = self.cache_service.invalidate_membership(room_id, user_id).await {
+                tracing::warn!("Failed to invalidate membership for user {} in room {}: {}", user_id, room_id, e);
+            }
+            
+            if let Err(e) = self.cache_service.invalidate_room_memberships(room_id).await {
+                tracing::warn!("Failed to invalidate room memberships for room {}: {}", room_id, e);
+            }
+        }
+        
+        result
+    }
+    
     async fn check_room_access(
         &self,
         room_id: RoomId,
@@ -159,6 +215,31 @@ impl RoomServiceTrait for CachedRoomService {
         self.room_service.get_user_rooms(user_id).await
     }
     
+    async fn get_room_list(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<RoomListEntry>, RoomError> {
+        // Previews change with every message, so this isn't cached either
+        self.room_service.get_room_list(user_id).await
+    }
+    
+    async fn get_direct_conversations(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<DirectConversation>, RoomError> {
+        // Unread counts move with every message, so this isn't cached
+        self.room_service.get_direct_conversations(user_id).await
+    }
+    
+    async fn get_mentionable(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+        query: &str,
+    ) -> Result<Vec<MentionCandidate>, RoomError> {
+        self.room_service.get_mentionable(room_id, user_id, query).await
+    }
+    
     async fn get_room_by_id(
         &self,
         room_id: RoomId,
@@ -169,6 +250,151 @@ impl RoomServiceTrait for CachedRoomService {
         
         self.room_service.get_room_by_id(room_id).await
     }
+    
+    async fn change_room_type(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        new_type: RoomType,
+    ) -> Result<Room, RoomError> {
+        let room = self.room_service.change_room_type(room_id, changed_by, new_type).await?;
+        
+        // Implicit open-room access is cached per user, so drop it all
+        if let Err(e) = self.cache_service.invalidate_room_memberships(room_id).await {
+            tracing::warn!("Failed to invalidate room memberships for room {}: {}", room_id, e);
+        }
+        
+        Ok(room)
+    }
+    
+    async fn resolve_or_create_room(
+        &self,
+        name: String,
+        user_id: UserId,
+    ) -> Result<(Room, bool), RoomError> {
+        let (room, created) = self.room_service.resolve_or_create_room(name, user_id).await?;
+        
+        if created {
+            if let Err(e) = self.cache_service.cache_membership(
+                room.id,
+                user_id,
+                Some(InvolvementLevel::Admin),
+                Self::MEMBERSHIP_CACHE_TTL,
+            ).await {
+                tracing::warn!("Failed to cache creator membership for room {}: {}", room.id, e);
+            }
+        }
+        
+        Ok((room, created))
+    }
+    
+    async fn get_permissions(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+    ) -> Result<RoomPermissions, RoomError> {
+        self.room_service.get_permissions(room_id, user_id).await
+    }
+    
+    async fn set_post_permission(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        post_permission: PostPermission,
+    ) -> Result<(), RoomError> {
+        self.room_service.set_post_permission(room_id, changed_by, post_permission).await
+    }
+    
+    async fn set_public(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        public: bool,
+    ) -> Result<(), RoomError> {
+        self.room_service.set_public(room_id, changed_by, public).await
+    }
+    
+    async fn set_sound_cooldown(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        cooldown_secs: Option<u64>,
+    ) -> Result<(), RoomError> {
+        self.room_service.set_sound_cooldown(room_id, changed_by, cooldown_secs).await
+    }
+    
+    async fn set_sound_policy(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        policy: SoundPolicy,
+    ) -> Result<(), RoomError> {
+        self.room_service.set_sound_policy(room_id, changed_by, policy).await
+    }
+    
+    async fn set_canned_response(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        shortcut: &str,
+        content: String,
+    ) -> Result<(), RoomError> {
+        self.room_service.set_canned_response(room_id, changed_by, shortcut, content).await
+    }
+    
+    async fn delete_canned_response(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        shortcut: &str,
+    ) -> Result<(), RoomError> {
+        self.room_service.delete_canned_response(room_id, changed_by, shortcut).await
+    }
+    
+    async fn get_canned_responses(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+        prefix: &str,
+    ) -> Result<Vec<CannedResponse>, RoomError> {
+        self.room_service.get_canned_responses(room_id, user_id, prefix).await
+    }
+    
+    async fn set_attachment_types(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        types: Option<Vec<String>>,
+    ) -> Result<(), RoomError> {
+        self.room_service.set_attachment_types(room_id, changed_by, types).await
+    }
+    
+    async fn set_notify_mentions_only(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        mentions_only: bool,
+    ) -> Result<(), RoomError> {
+        self.room_service.set_notify_mentions_only(room_id, changed_by, mentions_only).await
+    }
+    
+    async fn set_post_grant(
+        &self,
+        room_id: RoomId,
+        granted_by: UserId,
+        user_id: UserId,
+        granted: bool,
+    ) -> Result<(), RoomError> {
+        self.room_service.set_post_grant(room_id, granted_by, user_id, granted).await
+    }
+    
+    async fn check_post_permission(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+    ) -> Result<bool, RoomError> {
+        self.room_service.check_post_permission(room_id, user_id).await
+    }
 }
 
 /// Extension methods for cache management
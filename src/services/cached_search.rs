@@ -83,6 +83,7 @@ impl CachedSearchService {
         request.query.hash(&mut hasher);
         request.limit.hash(&mut hasher);
         request.offset.hash(&mut hasher);
+        request.prefix.hash(&mut hasher);
         request.room_id.hash(&mut hasher);
         
         format!("search:{}:{:x}", user_id, hasher.finish())
@@ -170,6 +171,7 @@ impl SearchServiceTrait for CachedSearchService {
             limit: Some(limit),
             offset: Some(offset),
             room_id: Some(room_id),
+            prefix: false,
         };
         
         self.search_messages(user_id, request).await
@@ -201,6 +203,7 @@ impl CachedSearchService {
                 limit: Some(20),
                 offset: Some(0),
                 room_id: None,
+                prefix: false,
             };
             
             // This will populate the cache
@@ -232,6 +235,7 @@ impl CachedSearchService {
                 limit: Some(20),
                 offset: Some(0),
                 room_id: Some(room_id),
+                prefix: false,
             };
             
             if let Err(e) = self.search_messages(user_id, request).await {
@@ -357,6 +361,7 @@ mod tests {
             limit: Some(20),
             offset: Some(0),
             room_id: None,
+            prefix: false,
         };
         
         // First search should hit database and cache the result
@@ -382,6 +387,7 @@ mod tests {
             limit: Some(20),
             offset: Some(0),
             room_id: None,
+            prefix: false,
         };
         
         let key1 = service.create_cache_key(user1, &request);
@@ -423,6 +429,7 @@ mod tests {
             limit: Some(20),
             offset: Some(0),
             room_id: None,
+            prefix: false,
         };
         let short_ttl = service.get_cache_ttl(&short_request);
         
@@ -432,6 +439,7 @@ mod tests {
             limit: Some(100),
             offset: Some(0),
             room_id: Some(RoomId::new()),
+            prefix: false,
         };
         let complex_ttl = service.get_cache_ttl(&complex_request);
         
@@ -441,6 +449,7 @@ mod tests {
             limit: Some(20),
             offset: Some(0),
             room_id: None,
+            prefix: false,
         };
         let simple_ttl = service.get_cache_ttl(&simple_request);
         
@@ -498,6 +507,7 @@ mod tests {
                 limit: Some(20),
                 offset: Some(0),
                 room_id: None,
+                prefix: false,
             };
             
             let _response = service.search_messages(user.id, request).await.unwrap();
@@ -514,6 +524,7 @@ mod tests {
             limit: Some(20),
             offset: Some(0),
             room_id: None,
+            prefix: false,
         };
         
         // Cache a search result
@@ -1,16 +1,125 @@
 use async_trait::async_trait;
-use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, Notify, RwLock};
 use tokio::time::{Duration, Instant};
 
+use crate::config::SendBufferOverflow;
 use crate::errors::{ConnectionError, BroadcastError};
-use crate::models::{ConnectionId, MessageId, RoomId, UserId, WebSocketMessage};
+use crate::models::{ConnectionId, MessageId, RoomId, UserId, WebSocketMessage, WS_LEGACY_PROTOCOL_VERSION};
 use crate::database::CampfireDatabase;
 
 // Type alias for WebSocket sender
 pub type WebSocketSender = mpsc::UnboundedSender<String>;
 
+/// Frames a connection may have queued when no buffer size is configured
+pub const DEFAULT_SEND_BUFFER_SIZE: usize = 256;
+
+/// Users announced typing one by one before a room gets a TypingSummary
+pub const DEFAULT_TYPING_BROADCAST_LIMIT: usize = 5;
+
+/// How long a user may go without activity before showing as offline
+pub const DEFAULT_PRESENCE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Shortest gap between last-seen writes for a user who stays online
+pub const DEFAULT_LAST_SEEN_WRITE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Bounded queue between a connection's channel and its socket writer
+///
+/// The channel is drained into this buffer as fast as frames arrive, so a
+/// client that stops reading fills the buffer rather than server memory.
+/// Once full, the overflow policy decides what gives.
+pub struct SendBuffer {
+    frames: Mutex<VecDeque<String>>,
+    ready: Notify,
+    capacity: usize,
+    overflow: SendBufferOverflow,
+    closed: AtomicBool,
+}
+
+impl SendBuffer {
+    pub fn new(capacity: usize, overflow: SendBufferOverflow) -> Self {
+        Self {
+            frames: Mutex::new(VecDeque::with_capacity(capacity.min(DEFAULT_SEND_BUFFER_SIZE))),
+            ready: Notify::new(),
+            capacity: capacity.max(1),
+            overflow,
+            closed: AtomicBool::new(false),
+        }
+    }
+    
+    /// Queues a frame, returning false once the connection should be dropped
+    pub fn push(&self, frame: String) -> bool {
+        if self.closed.load(Ordering::Acquire) {
+            return false;
+        }
+        
+        {
+            let mut frames = self.frames.lock().unwrap();
+            if frames.len() >= self.capacity {
+                metrics::counter!("websocket_send_buffer_overflows_total", 1);
+                match self.overflow {
+                    SendBufferOverflow::DropOldest => {
+                        frames.pop_front();
+                    }
+                    SendBufferOverflow::Disconnect => {
+                        frames.clear();
+                        drop(frames);
+                        self.close();
+                        return false;
+                    }
+                }
+            }
+            frames.push_back(frame);
+        }
+        
+        self.ready.notify_one();
+        true
+    }
+    
+    /// Stops accepting frames; the writer drains what's left, then sees None
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.ready.notify_one();
+    }
+    
+    /// Waits for the next frame, or None once closed and drained
+    pub async fn pop(&self) -> Option<String> {
+        loop {
+            if let Some(frame) = self.frames.lock().unwrap().pop_front() {
+                return Some(frame);
+            }
+            if self.closed.load(Ordering::Acquire) {
+                return None;
+            }
+            self.ready.notified().await;
+        }
+    }
+    
+    pub fn len(&self) -> usize {
+        self.frames.lock().unwrap().len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// An open WebSocket connection as shown to admins
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConnectionSummary {
+    pub connection_id: ConnectionId,
+    pub user_id: UserId,
+    /// Rooms whose broadcasts reach this connection
+    pub rooms: Vec<RoomId>,
+    pub connected_at: DateTime<Utc>,
+    pub ip: Option<IpAddr>,
+}
+
 #[async_trait]
 pub trait ConnectionManager: Send + Sync {
     /// Adds WebSocket connection for user
@@ -88,11 +197,132 @@ pub trait ConnectionManager: Send + Sync {
         room_id: RoomId,
     ) -> Result<Vec<UserId>, ConnectionError>;
     
+    /// Records a user starting or stopping typing and tells the room with a
+    /// TypingStart or TypingStop frame
+    async fn broadcast_typing(
+        &self,
+        user_id: UserId,
+        room_id: RoomId,
+        is_typing: bool,
+    ) -> Result<(), BroadcastError> {
+        let recorded = if is_typing {
+            self.start_typing(user_id, room_id).await
+        } else {
+            self.stop_typing(user_id, room_id).await
+        };
+        if let Err(e) = recorded {
+            tracing::warn!("Failed to record typing for user {} in room {}: {}", user_id.0, room_id.0, e);
+        }
+        
+        let frame = if is_typing {
+            WebSocketMessage::TypingStart { user_id, room_id }
+        } else {
+            WebSocketMessage::TypingStop { user_id, room_id }
+        };
+        self.broadcast_to_room(room_id, frame).await
+    }
+    
     /// Broadcasts presence update to room members
     async fn broadcast_presence_update(
         &self,
         room_id: RoomId,
     ) -> Result<(), BroadcastError>;
+    
+    /// Closes every connection belonging to a user, returning how many were dropped
+    async fn disconnect_user(
+        &self,
+        user_id: UserId,
+    ) -> Result<usize, ConnectionError>;
+    
+    /// Records the protocol version negotiated for a connection; broadcasts
+    /// skip frames the connection's client is too old to parse
+    async fn set_protocol_version(
+        &self,
+        _connection_id: ConnectionId,
+        _protocol_version: u32,
+    ) -> Result<(), ConnectionError> {
+        Ok(())
+    }
+    
+    /// Records the client address a connection came from
+    async fn set_client_ip(
+        &self,
+        _connection_id: ConnectionId,
+        _ip: IpAddr,
+    ) -> Result<(), ConnectionError> {
+        Ok(())
+    }
+    
+    /// Every open connection, oldest first
+    async fn list_connections(&self) -> Vec<ConnectionSummary> {
+        Vec::new()
+    }
+    
+    /// Closes one connection and updates presence for its user, returning
+    /// who it belonged to
+    async fn force_disconnect(
+        &self,
+        connection_id: ConnectionId,
+    ) -> Result<UserId, ConnectionError> {
+        Err(ConnectionError::NotFound { connection_id })
+    }
+    
+    /// Adds rooms to what a connection receives. The first subscription
+    /// switches the connection from every room its user is in to only the
+    /// rooms it subscribes to; callers check access first.
+    async fn subscribe_rooms(
+        &self,
+        _connection_id: ConnectionId,
+        _room_ids: &[RoomId],
+    ) -> Result<(), ConnectionError> {
+        Ok(())
+    }
+    
+    /// Stops a connection receiving the given rooms' broadcasts
+    async fn unsubscribe_rooms(
+        &self,
+        _connection_id: ConnectionId,
+        _room_ids: &[RoomId],
+    ) -> Result<(), ConnectionError> {
+        Ok(())
+    }
+    
+    /// Starts delivering a room's broadcasts to a user who just became a member
+    async fn join_room(&self, _room_id: RoomId, _user_id: UserId) {}
+    
+    /// Stops delivering a room's broadcasts to a user who was removed from it
+    async fn leave_room(&self, _room_id: RoomId, _user_id: UserId) {}
+    
+    /// Tells a deleted room's connections it's gone and stops routing
+    /// anything to them for it. Returns how many connections were told.
+    async fn close_room(&self, _room_id: RoomId) -> usize {
+        0
+    }
+    
+    /// Sends a frame to every connection of one user, whatever rooms they're
+    /// in. Returns how many connections it was sent to.
+    async fn send_to_user(
+        &self,
+        _user_id: UserId,
+        _message: WebSocketMessage,
+    ) -> Result<usize, BroadcastError> {
+        Ok(0)
+    }
+    
+    /// Sends ServerShutdown to every connection and closes them, waiting up
+    /// to `timeout` for the sockets to finish. Returns how many were closed.
+    async fn drain_connections(
+        &self,
+        _reconnect_after: Duration,
+        _timeout: Duration,
+    ) -> Result<usize, ConnectionError> {
+        Ok(0)
+    }
+    
+    /// Creates the bounded outbound buffer for a new connection's socket writer
+    fn send_buffer(&self) -> SendBuffer {
+        SendBuffer::new(DEFAULT_SEND_BUFFER_SIZE, SendBufferOverflow::DropOldest)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -102,7 +332,21 @@ struct ConnectionInfo {
     sender: WebSocketSender,
     last_seen_message_id: Option<MessageId>,
     connected_at: Instant,
+    connected_since: DateTime<Utc>,
     last_activity: Instant,
+    protocol_version: u32,
+    ip: Option<IpAddr>,
+    /// Rooms the client subscribed this connection to; until it subscribes
+    /// it gets every room its user is in
+    subscriptions: Option<HashSet<RoomId>>,
+}
+
+impl ConnectionInfo {
+    fn receives(&self, room_id: RoomId) -> bool {
+        self.subscriptions
+            .as_ref()
+            .is_none_or(|subscriptions| subscriptions.contains(&room_id))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -142,6 +386,21 @@ pub struct ConnectionManagerImpl {
     
     // Database for missed message queries (Critical Gap #2)
     database: Arc<CampfireDatabase>,
+    
+    // Per-connection outbound buffer depth and what happens when it fills
+    send_buffer_size: usize,
+    send_buffer_overflow: SendBufferOverflow,
+    
+    // Typing users announced individually per room (0 = no limit)
+    typing_broadcast_limit: usize,
+    
+    // Inactivity after which the cleanup task marks a user offline; shared
+    // with that task so it can be configured after it starts
+    presence_timeout_ms: Arc<AtomicU64>,
+    
+    // Last-seen writes are throttled per online user to one per interval
+    last_seen_interval: Duration,
+    last_seen_written: Arc<Mutex<HashMap<UserId, Instant>>>,
 }
 
 impl ConnectionManagerImpl {
@@ -152,6 +411,12 @@ impl ConnectionManagerImpl {
             presence: Arc::new(RwLock::new(HashMap::new())),
             room_presence: Arc::new(RwLock::new(HashMap::new())),
             database,
+            send_buffer_size: DEFAULT_SEND_BUFFER_SIZE,
+            send_buffer_overflow: SendBufferOverflow::DropOldest,
+            typing_broadcast_limit: DEFAULT_TYPING_BROADCAST_LIMIT,
+            presence_timeout_ms: Arc::new(AtomicU64::new(DEFAULT_PRESENCE_TIMEOUT.as_millis() as u64)),
+            last_seen_interval: DEFAULT_LAST_SEEN_WRITE_INTERVAL,
+            last_seen_written: Arc::new(Mutex::new(HashMap::new())),
         };
         
         // Start cleanup task for presence tracking (Critical Gap #5)
@@ -163,6 +428,33 @@ impl ConnectionManagerImpl {
         manager
     }
     
+    /// Bounds each connection's outbound buffer
+    pub fn with_send_buffer(mut self, size: usize, overflow: SendBufferOverflow) -> Self {
+        self.send_buffer_size = size;
+        self.send_buffer_overflow = overflow;
+        self
+    }
+    
+    /// Above `limit` users typing in a room, clients get one TypingSummary
+    /// rather than a frame per user (0 = never summarize)
+    pub fn with_typing_broadcast_limit(mut self, limit: usize) -> Self {
+        self.typing_broadcast_limit = limit;
+        self
+    }
+    
+    /// Marks users offline after `timeout` without activity
+    pub fn with_presence_timeout(self, timeout: Duration) -> Self {
+        self.presence_timeout_ms.store(timeout.as_millis() as u64, Ordering::Relaxed);
+        self
+    }
+    
+    /// Writes an online user's last-seen time at most once per `interval`;
+    /// going offline is always written
+    pub fn with_last_seen_interval(mut self, interval: Duration) -> Self {
+        self.last_seen_interval = interval;
+        self
+    }
+    
     /// Test helper: Add room membership for testing
     pub async fn add_room_membership(&self, room_id: RoomId, user_ids: Vec<UserId>) {
         let mut room_members = self.room_members.write().await;
@@ -176,11 +468,14 @@ impl ConnectionManagerImpl {
     }
     
     /// Starts background task to clean up stale presence information
-    /// Removes users who haven't been active for 60 seconds (Critical Gap #5)
+    /// Removes users who haven't been active for the presence timeout and
+    /// records when they were last seen (Critical Gap #5)
     fn start_presence_cleanup(&self) {
         let presence = Arc::clone(&self.presence);
         let connections = Arc::clone(&self.connections);
         let room_presence = Arc::clone(&self.room_presence);
+        let presence_timeout_ms = Arc::clone(&self.presence_timeout_ms);
+        let database = Arc::clone(&self.database);
         
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(30));
@@ -189,7 +484,7 @@ impl ConnectionManagerImpl {
                 interval.tick().await;
                 
                 let now = Instant::now();
-                let timeout = Duration::from_secs(60);
+                let timeout = Duration::from_millis(presence_timeout_ms.load(Ordering::Relaxed));
                 
                 // Clean up stale presence entries
                 let mut presence_guard = presence.write().await;
@@ -197,12 +492,12 @@ impl ConnectionManagerImpl {
                 
                 for (user_id, info) in presence_guard.iter() {
                     if now.duration_since(info.last_seen) > timeout {
-                        to_remove.push(*user_id);
+                        to_remove.push((*user_id, info.last_seen));
                     }
                 }
                 
-                for user_id in to_remove {
-                    presence_guard.remove(&user_id);
+                for (user_id, _) in &to_remove {
+                    presence_guard.remove(user_id);
                     tracing::debug!("Cleaned up stale presence for user {}", user_id.0);
                 }
                 
@@ -217,6 +512,14 @@ impl ConnectionManagerImpl {
                 drop(presence_guard);
                 drop(room_presence_guard);
                 
+                // Timed-out users were last seen at their last activity
+                for (user_id, last_active) in to_remove {
+                    let idle = chrono::Duration::from_std(now.duration_since(last_active)).unwrap_or_default();
+                    if let Err(e) = database.set_last_seen(user_id, Utc::now() - idle).await {
+                        tracing::warn!("Failed to record last seen for user {}: {}", user_id.0, e);
+                    }
+                }
+                
                 // Also clean up dead connections
                 let connections_guard = connections.read().await;
                 let mut dead_connections = Vec::new();
@@ -307,6 +610,35 @@ impl ConnectionManagerImpl {
         }
     }
     
+    /// Persists the user's last-seen time, at most once per interval while
+    /// they stay online; `went_offline` always writes
+    async fn persist_last_seen(&self, user_id: UserId, went_offline: bool) {
+        let now = Instant::now();
+        {
+            let mut written = self.last_seen_written.lock().unwrap();
+            if went_offline {
+                written.remove(&user_id);
+            } else {
+                if written.get(&user_id).is_some_and(|at| now.duration_since(*at) < self.last_seen_interval) {
+                    return;
+                }
+                written.insert(user_id, now);
+            }
+        }
+        
+        if let Err(e) = self.database.set_last_seen(user_id, Utc::now()).await {
+            tracing::warn!("Failed to record last seen for user {}: {}", user_id.0, e);
+        }
+    }
+    
+    /// Records the last-seen time of a user whose last connection just closed
+    async fn persist_last_seen_if_offline(&self, user_id: UserId) {
+        let online = self.presence.read().await.contains_key(&user_id);
+        if !online {
+            self.persist_last_seen(user_id, true).await;
+        }
+    }
+    
     /// Updates room-specific presence for a user
     async fn update_room_presence(&self, user_id: UserId) {
         let room_members_guard = self.room_members.read().await;
@@ -334,8 +666,19 @@ impl ConnectionManagerImpl {
         }
     }
     
-    /// Gets all connections for users in a room
-    async fn get_room_connections(&self, room_id: RoomId) -> Vec<(ConnectionId, WebSocketSender)> {
+    /// Users typing in a room, earliest first
+    async fn typing_in_order(&self, room_id: RoomId) -> Vec<UserId> {
+        let room_presence_guard = self.room_presence.read().await;
+        let mut typing: Vec<(UserId, Instant)> = room_presence_guard
+            .get(&room_id)
+            .map(|room_info| room_info.typing_users.iter().map(|(user_id, started)| (*user_id, *started)).collect())
+            .unwrap_or_default();
+        typing.sort_by_key(|(_, started)| *started);
+        typing.into_iter().map(|(user_id, _)| user_id).collect()
+    }
+    
+    /// Gets all connections for users in a room, with their protocol versions
+    async fn get_room_connections(&self, room_id: RoomId) -> Vec<(ConnectionId, WebSocketSender, u32)> {
         let connections_guard = self.connections.read().await;
         let room_members_guard = self.room_members.read().await;
         
@@ -345,8 +688,8 @@ impl ConnectionManagerImpl {
         // Find all connections for room members
         let mut room_connections = Vec::new();
         for (connection_id, info) in connections_guard.iter() {
-            if members.contains(&info.user_id) {
-                room_connections.push((*connection_id, info.sender.clone()));
+            if members.contains(&info.user_id) && info.receives(room_id) {
+                room_connections.push((*connection_id, info.sender.clone(), info.protocol_version));
             }
         }
         
@@ -369,7 +712,11 @@ impl ConnectionManager for ConnectionManagerImpl {
             sender,
             last_seen_message_id: None,
             connected_at: now,
+            connected_since: Utc::now(),
             last_activity: now,
+            protocol_version: WS_LEGACY_PROTOCOL_VERSION,
+            ip: None,
+            subscriptions: None,
         };
         
         // Add connection
@@ -383,6 +730,7 @@ impl ConnectionManager for ConnectionManagerImpl {
         
         // Update room-specific presence for all rooms the user is in
         self.update_room_presence(user_id).await;
+        self.persist_last_seen(user_id, false).await;
         
         tracing::info!("Added connection {} for user {}", connection_id.0, user_id.0);
         
@@ -405,6 +753,7 @@ impl ConnectionManager for ConnectionManagerImpl {
         
         // Update room-specific presence for all rooms the user was in
         self.update_room_presence(user_id).await;
+        self.persist_last_seen_if_offline(user_id).await;
         
         tracing::info!("Removed connection {} for user {}", connection_id.0, user_id.0);
         
@@ -422,14 +771,26 @@ impl ConnectionManager for ConnectionManagerImpl {
             return Err(BroadcastError::NoConnections { room_id });
         }
         
-        // Serialize message once
-        let serialized = serde_json::to_string(&message)?;
+        // Serialize message once, tagged with its room so a connection
+        // subscribed to several rooms can tell them apart
+        let mut frame = serde_json::to_value(&message)?;
+        if let Some(fields) = frame.as_object_mut() {
+            fields.entry("room_id").or_insert_with(|| serde_json::json!(room_id));
+        }
+        let serialized = frame.to_string();
+        
+        // Older clients would fail to parse newer frame types
+        let min_protocol_version = message.min_protocol_version();
+        let room_connections: Vec<_> = room_connections
+            .into_iter()
+            .filter(|(_, _, protocol_version)| *protocol_version >= min_protocol_version)
+            .collect();
         
         let mut failed_sends = 0;
         let total_connections = room_connections.len();
         
         // Send to all connections
-        for (connection_id, sender) in room_connections {
+        for (connection_id, sender, _) in room_connections {
             if let Err(_) = sender.send(serialized.clone()) {
                 failed_sends += 1;
                 tracing::warn!("Failed to send message to connection {}", connection_id.0);
@@ -580,12 +941,21 @@ impl ConnectionManager for ConnectionManagerImpl {
         connection_id: ConnectionId,
         message_id: MessageId,
     ) -> Result<(), ConnectionError> {
-        let mut connections_guard = self.connections.write().await;
-        let connection_info = connections_guard.get_mut(&connection_id)
-            .ok_or(ConnectionError::NotFound { connection_id })?;
+        let user_id = {
+            let mut connections_guard = self.connections.write().await;
+            let connection_info = connections_guard.get_mut(&connection_id)
+                .ok_or(ConnectionError::NotFound { connection_id })?;
+            
+            connection_info.last_seen_message_id = Some(message_id);
+            connection_info.last_activity = Instant::now();
+            connection_info.user_id
+        };
         
-        connection_info.last_seen_message_id = Some(message_id);
-        connection_info.last_activity = Instant::now();
+        // Reading counts as activity, keeping the user online
+        if let Some(info) = self.presence.write().await.get_mut(&user_id) {
+            info.last_seen = Instant::now();
+        }
+        self.persist_last_seen(user_id, false).await;
         
         Ok(())
     }
@@ -665,6 +1035,39 @@ impl ConnectionManager for ConnectionManagerImpl {
         }
     }
     
+    async fn broadcast_typing(
+        &self,
+        user_id: UserId,
+        room_id: RoomId,
+        is_typing: bool,
+    ) -> Result<(), BroadcastError> {
+        let limit = self.typing_broadcast_limit;
+        let was_summarized = limit > 0 && self.typing_in_order(room_id).await.len() > limit;
+        
+        if is_typing {
+            self.start_typing(user_id, room_id).await
+        } else {
+            self.stop_typing(user_id, room_id).await
+        }
+        .map_err(|_| BroadcastError::PartialFailure { connection_count: 1 })?;
+        
+        let typing = self.typing_in_order(room_id).await;
+        let frame = if limit > 0 && (typing.len() > limit || was_summarized) {
+            let others = typing.len().saturating_sub(limit);
+            WebSocketMessage::TypingSummary {
+                room_id,
+                user_ids: typing.into_iter().take(limit).collect(),
+                others,
+            }
+        } else if is_typing {
+            WebSocketMessage::TypingStart { user_id, room_id }
+        } else {
+            WebSocketMessage::TypingStop { user_id, room_id }
+        };
+        
+        self.broadcast_to_room(room_id, frame).await
+    }
+    
     async fn broadcast_presence_update(
         &self,
         room_id: RoomId,
@@ -673,15 +1076,258 @@ impl ConnectionManager for ConnectionManagerImpl {
         let online_users = self.get_room_specific_presence(room_id).await
             .map_err(|_e| BroadcastError::PartialFailure { connection_count: 1 })?;
         
+        // Offline members show when they were last around
+        let last_seen = match self.database.get_room_last_seen(room_id).await {
+            Ok(mut last_seen) => {
+                last_seen.retain(|user_id, _| !online_users.contains(user_id));
+                last_seen
+            }
+            Err(e) => {
+                tracing::warn!("Failed to load last seen times for room {}: {}", room_id.0, e);
+                HashMap::new()
+            }
+        };
+        
         // Create presence update message
         let presence_msg = WebSocketMessage::PresenceUpdate {
             room_id,
             online_users,
+            last_seen,
         };
         
         // Broadcast to all room members
         self.broadcast_to_room(room_id, presence_msg).await
     }
+    
+    async fn disconnect_user(
+        &self,
+        user_id: UserId,
+    ) -> Result<usize, ConnectionError> {
+        // Dropping the stored sender closes the connection's outgoing channel,
+        // which ends its WebSocket task
+        let removed = {
+            let mut connections_guard = self.connections.write().await;
+            let before = connections_guard.len();
+            connections_guard.retain(|_, info| info.user_id != user_id);
+            before - connections_guard.len()
+        };
+        
+        self.update_presence(user_id).await;
+        self.update_room_presence(user_id).await;
+        self.persist_last_seen_if_offline(user_id).await;
+        
+        tracing::info!("Disconnected {} connection(s) for user {}", removed, user_id.0);
+        
+        Ok(removed)
+    }
+    
+    async fn set_client_ip(
+        &self,
+        connection_id: ConnectionId,
+        ip: IpAddr,
+    ) -> Result<(), ConnectionError> {
+        let mut connections_guard = self.connections.write().await;
+        let info = connections_guard.get_mut(&connection_id)
+            .ok_or(ConnectionError::NotFound { connection_id })?;
+        info.ip = Some(ip);
+        Ok(())
+    }
+    
+    async fn list_connections(&self) -> Vec<ConnectionSummary> {
+        let connections_guard = self.connections.read().await;
+        let room_members_guard = self.room_members.read().await;
+        
+        let mut summaries: Vec<ConnectionSummary> = connections_guard
+            .iter()
+            .map(|(connection_id, info)| ConnectionSummary {
+                connection_id: *connection_id,
+                user_id: info.user_id,
+                rooms: room_members_guard
+                    .iter()
+                    .filter(|(room_id, members)| members.contains(&info.user_id) && info.receives(**room_id))
+                    .map(|(room_id, _)| *room_id)
+                    .collect(),
+                connected_at: info.connected_since,
+                ip: info.ip,
+            })
+            .collect();
+        summaries.sort_by_key(|summary| summary.connected_at);
+        summaries
+    }
+    
+    async fn subscribe_rooms(
+        &self,
+        connection_id: ConnectionId,
+        room_ids: &[RoomId],
+    ) -> Result<(), ConnectionError> {
+        let mut connections_guard = self.connections.write().await;
+        let info = connections_guard
+            .get_mut(&connection_id)
+            .ok_or(ConnectionError::NotFound { connection_id })?;
+        info.subscriptions
+            .get_or_insert_with(HashSet::new)
+            .extend(room_ids.iter().copied());
+        Ok(())
+    }
+    
+    async fn unsubscribe_rooms(
+        &self,
+        connection_id: ConnectionId,
+        room_ids: &[RoomId],
+    ) -> Result<(), ConnectionError> {
+        let mut connections_guard = self.connections.write().await;
+        let info = connections_guard
+            .get_mut(&connection_id)
+            .ok_or(ConnectionError::NotFound { connection_id })?;
+        // A connection that never subscribed keeps the rest of its user's rooms
+        if info.subscriptions.is_none() {
+            let room_members_guard = self.room_members.read().await;
+            info.subscriptions = Some(
+                room_members_guard
+                    .iter()
+                    .filter(|(_, members)| members.contains(&info.user_id))
+                    .map(|(room_id, _)| *room_id)
+                    .collect(),
+            );
+        }
+        let subscriptions = info.subscriptions.get_or_insert_with(HashSet::new);
+        for room_id in room_ids {
+            subscriptions.remove(room_id);
+        }
+        Ok(())
+    }
+    
+    async fn force_disconnect(
+        &self,
+        connection_id: ConnectionId,
+    ) -> Result<UserId, ConnectionError> {
+        // As with disconnect_user, dropping the sender ends the socket task
+        let user_id = self.connections.write().await
+            .remove(&connection_id)
+            .ok_or(ConnectionError::NotFound { connection_id })?
+            .user_id;
+        
+        self.update_presence(user_id).await;
+        self.update_room_presence(user_id).await;
+        self.persist_last_seen_if_offline(user_id).await;
+        
+        tracing::info!("Force-disconnected connection {} for user {}", connection_id.0, user_id.0);
+        
+        Ok(user_id)
+    }
+    
+    async fn join_room(&self, room_id: RoomId, user_id: UserId) {
+        {
+            let mut room_members_guard = self.room_members.write().await;
+            let members = room_members_guard.entry(room_id).or_default();
+            if !members.contains(&user_id) {
+                members.push(user_id);
+            }
+        }
+        self.update_room_presence(user_id).await;
+    }
+    
+    async fn leave_room(&self, room_id: RoomId, user_id: UserId) {
+        {
+            let mut room_members_guard = self.room_members.write().await;
+            if let Some(members) = room_members_guard.get_mut(&room_id) {
+                members.retain(|member| *member != user_id);
+            }
+        }
+        let mut room_presence_guard = self.room_presence.write().await;
+        if let Some(room_info) = room_presence_guard.get_mut(&room_id) {
+            room_info.online_users.remove(&user_id);
+            room_info.typing_users.remove(&user_id);
+        }
+    }
+    
+    async fn close_room(&self, room_id: RoomId) -> usize {
+        let notified = self.get_room_connections(room_id).await.len();
+        if let Err(e) = self.broadcast_to_room(room_id, WebSocketMessage::RoomDeleted { room_id }).await {
+            tracing::debug!("RoomDeleted for room {} not delivered: {}", room_id, e);
+        }
+        
+        self.room_members.write().await.remove(&room_id);
+        self.room_presence.write().await.remove(&room_id);
+        notified
+    }
+    
+    async fn send_to_user(
+        &self,
+        user_id: UserId,
+        message: WebSocketMessage,
+    ) -> Result<usize, BroadcastError> {
+        let serialized = serde_json::to_string(&message)?;
+        let min_protocol_version = message.min_protocol_version();
+        
+        let connections_guard = self.connections.read().await;
+        let mut sent = 0;
+        let mut failed_sends = 0;
+        for info in connections_guard.values() {
+            if info.user_id != user_id || info.protocol_version < min_protocol_version {
+                continue;
+            }
+            if info.sender.send(serialized.clone()).is_ok() {
+                sent += 1;
+            } else {
+                failed_sends += 1;
+            }
+        }
+        
+        if failed_sends > 0 {
+            return Err(BroadcastError::PartialFailure { connection_count: failed_sends });
+        }
+        
+        Ok(sent)
+    }
+    
+    async fn set_protocol_version(
+        &self,
+        connection_id: ConnectionId,
+        protocol_version: u32,
+    ) -> Result<(), ConnectionError> {
+        let mut connections_guard = self.connections.write().await;
+        let info = connections_guard.get_mut(&connection_id)
+            .ok_or(ConnectionError::NotFound { connection_id })?;
+        info.protocol_version = protocol_version;
+        Ok(())
+    }
+    
+    async fn drain_connections(
+        &self,
+        reconnect_after: Duration,
+        timeout: Duration,
+    ) -> Result<usize, ConnectionError> {
+        let frame = WebSocketMessage::ServerShutdown { reconnect_after: reconnect_after.as_secs() };
+        let serialized = serde_json::to_string(&frame)
+            .map_err(|e| ConnectionError::Protocol(e.to_string()))?;
+        
+        let closed = {
+            let mut connections_guard = self.connections.write().await;
+            for info in connections_guard.values_mut() {
+                if info.protocol_version >= frame.min_protocol_version() {
+                    let _ = info.sender.send(serialized.clone());
+                }
+                // Dropping the live sender lets the socket flush the frame and
+                // close; its handler then removes the connection as usual
+                info.sender = mpsc::unbounded_channel().0;
+            }
+            connections_guard.len()
+        };
+        
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline && !self.connections.read().await.is_empty() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        
+        tracing::info!("Drained {} WebSocket connection(s) for shutdown", closed);
+        
+        Ok(closed)
+    }
+    
+    fn send_buffer(&self) -> SendBuffer {
+        SendBuffer::new(self.send_buffer_size, self.send_buffer_overflow)
+    }
 }
 
 // Mock implementation for testing
@@ -762,6 +1408,11 @@ mock! {
             &self,
             room_id: RoomId,
         ) -> Result<(), BroadcastError>;
+        
+        async fn disconnect_user(
+            &self,
+            user_id: UserId,
+        ) -> Result<usize, ConnectionError>;
     }
 }
 
@@ -796,6 +1447,71 @@ mod tests {
         assert!(manager.remove_connection(connection_id).await.is_err());
     }
     
+    #[tokio::test]
+    async fn test_disconnect_user_closes_all_connections() {
+        let db = crate::database::CampfireDatabase::new(":memory:").await.unwrap();
+        let manager = ConnectionManagerImpl::new(Arc::new(db));
+        let user_id = UserId::new();
+        let other_user = UserId::new();
+        
+        let (sender1, mut receiver1) = mpsc::unbounded_channel();
+        let (sender2, _receiver2) = mpsc::unbounded_channel();
+        let (sender3, _receiver3) = mpsc::unbounded_channel();
+        let conn1 = ConnectionId::new();
+        let conn2 = ConnectionId::new();
+        let other_conn = ConnectionId::new();
+        
+        manager.add_connection(user_id, conn1, sender1).await.unwrap();
+        manager.add_connection(user_id, conn2, sender2).await.unwrap();
+        manager.add_connection(other_user, other_conn, sender3).await.unwrap();
+        
+        let removed = manager.disconnect_user(user_id).await.unwrap();
+        assert_eq!(removed, 2);
+        assert!(!manager.connection_exists(conn1).await);
+        assert!(!manager.connection_exists(conn2).await);
+        assert!(manager.connection_exists(other_conn).await);
+        
+        // The outgoing channel is closed once the manager drops its sender
+        assert!(receiver1.recv().await.is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_force_disconnect_removes_connection_and_presence() {
+        let db = crate::database::CampfireDatabase::new(":memory:").await.unwrap();
+        let manager = ConnectionManagerImpl::new(Arc::new(db));
+        let room_id = RoomId::new();
+        let user_id = UserId::new();
+        let other_user = UserId::new();
+        manager.add_room_membership(room_id, vec![user_id, other_user]).await;
+        
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let (other_sender, _other_receiver) = mpsc::unbounded_channel();
+        let conn = ConnectionId::new();
+        let other_conn = ConnectionId::new();
+        manager.add_connection(user_id, conn, sender).await.unwrap();
+        manager.add_connection(other_user, other_conn, other_sender).await.unwrap();
+        manager.set_client_ip(conn, "203.0.113.7".parse().unwrap()).await.unwrap();
+        
+        let listed = manager.list_connections().await;
+        assert_eq!(listed.len(), 2);
+        let summary = listed.iter().find(|c| c.connection_id == conn).unwrap();
+        assert_eq!(summary.user_id, user_id);
+        assert_eq!(summary.rooms, vec![room_id]);
+        assert_eq!(summary.ip, Some("203.0.113.7".parse().unwrap()));
+        
+        assert_eq!(manager.force_disconnect(conn).await.unwrap(), user_id);
+        assert!(!manager.connection_exists(conn).await);
+        assert!(receiver.recv().await.is_none());
+        assert_eq!(manager.get_room_presence(room_id).await.unwrap(), vec![other_user]);
+        assert_eq!(manager.get_room_specific_presence(room_id).await.unwrap(), vec![other_user]);
+        assert_eq!(manager.list_connections().await.len(), 1);
+        
+        assert!(matches!(
+            manager.force_disconnect(conn).await,
+            Err(ConnectionError::NotFound { .. })
+        ));
+    }
+    
     #[tokio::test]
     async fn test_presence_tracking() {
         // Test Critical Gap #5: Basic Presence Tracking
@@ -872,6 +1588,74 @@ mod tests {
         assert!(received.contains("Test message"));
     }
     
+    #[tokio::test]
+    async fn test_broadcast_skips_frames_legacy_clients_cannot_parse() {
+        let db = crate::database::CampfireDatabase::new(":memory:").await.unwrap();
+        let manager = ConnectionManagerImpl::new(Arc::new(db));
+        let room_id = RoomId::new();
+        let (legacy_id, current_id) = (ConnectionId::new(), ConnectionId::new());
+        let (legacy_user, current_user) = (UserId::new(), UserId::new());
+        
+        let (legacy_tx, mut legacy_rx) = mpsc::unbounded_channel();
+        let (current_tx, mut current_rx) = mpsc::unbounded_channel();
+        manager.add_connection(legacy_user, legacy_id, legacy_tx).await.unwrap();
+        manager.add_connection(current_user, current_id, current_tx).await.unwrap();
+        manager.set_protocol_version(current_id, crate::models::WS_PROTOCOL_VERSION).await.unwrap();
+        manager.add_room_membership(room_id, vec![legacy_user, current_user]).await;
+        
+        manager.broadcast_to_room(room_id, WebSocketMessage::RoomArchived { room_id }).await.unwrap();
+        assert!(current_rx.recv().await.unwrap().contains("RoomArchived"));
+        assert!(legacy_rx.try_recv().is_err());
+        
+        // Frames every version understands still reach everyone
+        let typing = WebSocketMessage::TypingStart { user_id: current_user, room_id };
+        manager.broadcast_to_room(room_id, typing).await.unwrap();
+        assert!(legacy_rx.recv().await.unwrap().contains("TypingStart"));
+        assert!(current_rx.recv().await.unwrap().contains("TypingStart"));
+    }
+    
+    #[tokio::test]
+    async fn test_one_connection_follows_only_its_subscribed_rooms() {
+        let db = crate::database::CampfireDatabase::new(":memory:").await.unwrap();
+        let manager = ConnectionManagerImpl::new(Arc::new(db));
+        let user_id = UserId::new();
+        let connection_id = ConnectionId::new();
+        let (first, second, third) = (RoomId::new(), RoomId::new(), RoomId::new());
+        
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        manager.add_connection(user_id, connection_id, sender).await.unwrap();
+        for room_id in [first, second, third] {
+            manager.add_room_membership(room_id, vec![user_id]).await;
+        }
+        manager.subscribe_rooms(connection_id, &[first, second, third]).await.unwrap();
+        manager.unsubscribe_rooms(connection_id, &[third]).await.unwrap();
+        
+        for room_id in [first, second, third] {
+            let typing = WebSocketMessage::TypingStart { user_id, room_id };
+            let _ = manager.broadcast_to_room(room_id, typing).await;
+        }
+        let updated = WebSocketMessage::RoomUpdated {
+            room: crate::models::Room {
+                id: second,
+                ..crate::models::Room::for_tests("Second", crate::models::RoomType::Open)
+            },
+        };
+        let _ = manager.broadcast_to_room(second, updated).await;
+        
+        // Every frame names its room, including ones whose payload nests it
+        let mut rooms = Vec::new();
+        while let Ok(frame) = receiver.try_recv() {
+            let frame: serde_json::Value = serde_json::from_str(&frame).unwrap();
+            rooms.push(frame["room_id"].as_str().unwrap().to_string());
+        }
+        let expected: Vec<String> = [first, second, second].iter().map(|room| room.0.to_string()).collect();
+        assert_eq!(rooms, expected);
+        
+        let summary = manager.list_connections().await.remove(0);
+        assert_eq!(summary.rooms.len(), 2);
+        assert!(!summary.rooms.contains(&third));
+    }
+    
     #[tokio::test]
     async fn test_last_seen_message_tracking() {
         // Test Critical Gap #2: WebSocket Reconnection State
@@ -1075,6 +1859,51 @@ mod tests {
         assert!(typing_users.is_empty());
     }
     
+    #[tokio::test]
+    async fn test_typing_above_limit_sends_summary() {
+        let db = crate::database::CampfireDatabase::new(":memory:").await.unwrap();
+        let manager = ConnectionManagerImpl::new(Arc::new(db)).with_typing_broadcast_limit(2);
+        let room_id = RoomId::new();
+        let typers: Vec<UserId> = (0..3).map(|_| UserId::new()).collect();
+        let watcher = UserId::new();
+        let mut members = typers.clone();
+        members.push(watcher);
+        manager.add_room_membership(room_id, members).await;
+        
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let conn = ConnectionId::new();
+        manager.add_connection(watcher, conn, sender).await.unwrap();
+        manager.set_protocol_version(conn, crate::models::WS_PROTOCOL_VERSION).await.unwrap();
+        
+        let mut next_frame = move || serde_json::from_str::<serde_json::Value>(&receiver.try_recv().unwrap()).unwrap();
+        
+        // Up to the limit, each typer is announced individually
+        for typer in &typers[..2] {
+            manager.broadcast_typing(*typer, room_id, true).await.unwrap();
+            let frame = next_frame();
+            assert_eq!(frame["type"], "TypingStart");
+            assert_eq!(frame["user_id"], serde_json::json!(typer));
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+        
+        // One more and the room gets a summary instead
+        manager.broadcast_typing(typers[2], room_id, true).await.unwrap();
+        let frame = next_frame();
+        assert_eq!(frame["type"], "TypingSummary");
+        assert_eq!(frame["user_ids"], serde_json::json!(&typers[..2]));
+        assert_eq!(frame["others"], 1);
+        
+        // Dropping back to the limit sends a final summary with everyone left
+        manager.broadcast_typing(typers[0], room_id, false).await.unwrap();
+        let frame = next_frame();
+        assert_eq!(frame["type"], "TypingSummary");
+        assert_eq!(frame["user_ids"], serde_json::json!(&typers[1..]));
+        assert_eq!(frame["others"], 0);
+        
+        manager.broadcast_typing(typers[1], room_id, false).await.unwrap();
+        assert_eq!(next_frame()["type"], "TypingStop");
+    }
+    
     #[tokio::test]
     async fn test_room_specific_presence() {
         // Test room-specific presence tracking
@@ -1177,4 +2006,73 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+    
+    #[tokio::test]
+    async fn test_slow_consumer_drop_oldest_keeps_newest_frames() {
+        let buffer = SendBuffer::new(3, SendBufferOverflow::DropOldest);
+        
+        // Nothing pops, as if the client had stopped reading
+        for i in 0..10 {
+            assert!(buffer.push(format!("frame-{}", i)));
+        }
+        assert_eq!(buffer.len(), 3);
+        
+        buffer.close();
+        let mut delivered = Vec::new();
+        while let Some(frame) = buffer.pop().await {
+            delivered.push(frame);
+        }
+        assert_eq!(delivered, vec!["frame-7", "frame-8", "frame-9"]);
+    }
+    
+    #[tokio::test]
+    async fn test_slow_consumer_disconnect_closes_buffer() {
+        let buffer = SendBuffer::new(3, SendBufferOverflow::Disconnect);
+        
+        for i in 0..3 {
+            assert!(buffer.push(format!("frame-{}", i)));
+        }
+        assert!(!buffer.push("frame-3".to_string()));
+        
+        // Queued frames are discarded and later pushes are refused
+        assert!(!buffer.push("frame-4".to_string()));
+        assert_eq!(buffer.pop().await, None);
+    }
+    
+    #[tokio::test]
+    async fn test_disconnecting_records_last_seen_in_profile() {
+        let db = Arc::new(crate::database::CampfireDatabase::new(":memory:").await.unwrap());
+        // Long enough that only connecting and disconnecting get written
+        let manager = ConnectionManagerImpl::new(db.clone())
+            .with_last_seen_interval(Duration::from_secs(3600));
+        
+        let user = crate::models::User::for_tests("Away User");
+        db.create_user(user.clone()).await.unwrap();
+        assert_eq!(db.get_last_seen(user.id).await.unwrap(), None);
+        
+        let connection_id = ConnectionId::new();
+        let (sender, _receiver) = mpsc::unbounded_channel();
+        manager.add_connection(user.id, connection_id, sender).await.unwrap();
+        let connected_at = db.get_last_seen(user.id).await.unwrap().unwrap();
+        
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        manager.remove_connection(connection_id).await.unwrap();
+        let last_seen = db.get_last_seen(user.id).await.unwrap().unwrap();
+        assert!(last_seen > connected_at);
+        
+        let profile = crate::handlers::users::public_profile(&user, Some(last_seen));
+        assert_eq!(profile["last_seen_at"], serde_json::json!(last_seen));
+    }
+    
+    #[tokio::test]
+    async fn test_manager_hands_out_configured_send_buffer() {
+        let db = crate::database::CampfireDatabase::new(":memory:").await.unwrap();
+        let manager = ConnectionManagerImpl::new(Arc::new(db))
+            .with_send_buffer(2, SendBufferOverflow::Disconnect);
+        
+        let buffer = manager.send_buffer();
+        assert!(buffer.push("a".to_string()));
+        assert!(buffer.push("b".to_string()));
+        assert!(!buffer.push("c".to_string()));
+    }
+}
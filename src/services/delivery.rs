@@ -0,0 +1,119 @@
+//! Outbound JSON deliveries with retries
+//!
+//! Room webhooks and room bridges both post a message to an admin-chosen
+//! URL and retry timeouts and transport errors with a doubling backoff.
+//! They differ only in their headers and which error statuses are worth
+//! another attempt, so that's all callers pass in.
+
+use reqwest::{Client, StatusCode};
+use std::future::Future;
+use std::time::Duration;
+
+use crate::errors::BotError;
+use crate::events::DomainEvent;
+use crate::models::Message;
+use crate::services::WebhookUrlPolicy;
+
+/// Matches the bot webhook timeout
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(7);
+
+#[derive(Clone)]
+pub struct WebhookDelivery {
+    http_client: Client,
+    url_policy: WebhookUrlPolicy,
+    max_attempts: u32,
+    retry_delay: Duration,
+}
+
+impl Default for WebhookDelivery {
+    fn default() -> Self {
+        let url_policy = WebhookUrlPolicy::default();
+
+        Self {
+            http_client: url_policy.http_client(DELIVERY_TIMEOUT),
+            url_policy,
+            max_attempts: 3,
+            retry_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl WebhookDelivery {
+    /// Where deliveries may go; private addresses are refused by default
+    pub fn with_url_policy(mut self, url_policy: WebhookUrlPolicy) -> Self {
+        self.http_client = url_policy.http_client(DELIVERY_TIMEOUT);
+        self.url_policy = url_policy;
+        self
+    }
+
+    /// Attempts per delivery and the delay before the first retry, which
+    /// doubles on each further retry
+    pub fn with_retry(mut self, max_attempts: u32, retry_delay: Duration) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    pub fn url_policy(&self) -> &WebhookUrlPolicy {
+        &self.url_policy
+    }
+
+    /// Posts the JSON body until it's accepted, the endpoint answers with a
+    /// status `is_retryable` turns down, or the attempts run out
+    pub async fn post(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+        is_retryable: impl Fn(StatusCode) -> bool,
+    ) -> Result<(), BotError> {
+        // The stored URL may predate today's rules, or now resolve elsewhere
+        self.url_policy.check_delivery(url).await?;
+
+        let mut delay = self.retry_delay;
+        let mut attempt = 1;
+
+        loop {
+            let mut request = self.http_client
+                .post(url)
+                .header("Content-Type", "application/json");
+            for (name, value) in headers {
+                request = request.header(*name, *value);
+            }
+            let result = request.body(body.to_vec()).send().await;
+
+            let (error, retryable) = match result {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => (
+                    BotError::WebhookDeliveryFailed { reason: format!("endpoint returned {}", response.status()) },
+                    is_retryable(response.status()),
+                ),
+                Err(e) if e.is_timeout() => (
+                    BotError::WebhookTimeout { timeout_seconds: DELIVERY_TIMEOUT.as_secs() },
+                    true,
+                ),
+                Err(e) => (BotError::WebhookDeliveryFailed { reason: e.to_string() }, true),
+            };
+
+            if !retryable || attempt >= self.max_attempts {
+                return Err(error);
+            }
+
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+            attempt += 1;
+        }
+    }
+}
+
+/// Runs `deliver` for a newly created message in the background; retries
+/// can take a while, and message creation shouldn't wait for them
+pub fn spawn_for_new_message<F, Fut>(event: &DomainEvent, deliver: F)
+where
+    F: FnOnce(Message) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    if let DomainEvent::MessageCreated { message } = event {
+        tokio::spawn(deliver(message.clone()));
+    }
+}
@@ -0,0 +1,549 @@
+//! Self-service export of a user's own data
+//!
+//! A user asks for an export and gets a job back straight away; the archive
+//! is written in the background and, once ready, is fetched through a signed
+//! link that expires. The archive is newline-delimited JSON: one `profile`
+//! line, a `membership` line per room, then a `message` line for every
+//! message the user wrote. Messages are read a page at a time and written
+//! as they come, and downloads are streamed from disk, so neither side
+//! holds a long history in memory.
+//!
+//! Only the requesting user's own messages are included, never other
+//! people's messages from the same rooms.
+//!
+//! Timestamps are written in UTC, each next to a `_local` copy rendered in
+//! the timezone the export was requested in (UTC unless one was given).
+//!
+//! Archives hold personal data, so they are deleted once their link
+//! lifetime has passed, including ones left over from before a restart.
+
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use dashmap::DashMap;
+use futures_util::Stream;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use serde::Serialize;
+use serde_json::json;
+use sha2::Sha256;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
+use uuid::Uuid;
+
+use crate::database::CampfireDatabase;
+use crate::errors::ExportError;
+use crate::models::{MessageId, UserId};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Messages read from the database per page while writing an archive
+const EXPORT_PAGE_SIZE: u32 = 500;
+
+/// Bytes read per chunk when streaming an archive to the client
+const DOWNLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportStatus {
+    Pending,
+    Ready,
+    Failed,
+}
+
+/// One export request and how far it has got
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportJob {
+    pub export_id: Uuid,
+    #[serde(skip)]
+    pub user_id: UserId,
+    pub status: ExportStatus,
+    /// IANA zone the archive's local timestamps are rendered in
+    pub timezone: String,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Signed link to the archive, only while it's ready
+    pub download_url: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Builds user data archives and signs the links they're fetched through
+pub struct ExportService {
+    db: Arc<CampfireDatabase>,
+    dir: PathBuf,
+    link_ttl: Duration,
+    // Links are signed with a key that only lives as long as the process;
+    // a restart invalidates them and the user simply asks again
+    signing_key: [u8; 32],
+    jobs: Arc<DashMap<Uuid, ExportJob>>,
+}
+
+impl ExportService {
+    /// Archives are written under `dir`
+    pub fn new(db: Arc<CampfireDatabase>, dir: PathBuf) -> Self {
+        Self {
+            db,
+            dir,
+            link_ttl: Duration::from_secs(900),
+            signing_key: rand::thread_rng().gen(),
+            jobs: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// How long a download link stays valid
+    pub fn with_link_ttl(mut self, ttl: Duration) -> Self {
+        self.link_ttl = ttl;
+        self
+    }
+
+    /// The user's current export in `timezone`: one still being written,
+    /// or a finished one whose link hasn't expired. Otherwise a new export
+    /// is started.
+    pub fn request(&self, user_id: UserId, timezone: Tz) -> ExportJob {
+        let fresh_after = Utc::now() - chrono::Duration::from_std(self.link_ttl).unwrap_or_default();
+        let current = self
+            .jobs
+            .iter()
+            .filter(|job| job.user_id == user_id && job.timezone == timezone.name())
+            .filter(|job| match job.status {
+                ExportStatus::Pending => true,
+                ExportStatus::Ready => job.completed_at.is_some_and(|at| at > fresh_after),
+                ExportStatus::Failed => false,
+            })
+            .max_by_key(|job| job.created_at)
+            .map(|job| job.export_id);
+        if let Some(export_id) = current {
+            if let Some(job) = self.status(user_id, export_id) {
+                return job;
+            }
+        }
+
+        self.discard_user_exports(user_id);
+
+        let job = ExportJob {
+            export_id: Uuid::new_v4(),
+            user_id,
+            status: ExportStatus::Pending,
+            timezone: timezone.name().to_string(),
+            created_at: Utc::now(),
+            completed_at: None,
+            download_url: None,
+            error: None,
+        };
+        self.jobs.insert(job.export_id, job.clone());
+
+        let db = self.db.clone();
+        let jobs = self.jobs.clone();
+        let path = self.archive_path(job.export_id);
+        let export_id = job.export_id;
+        tokio::spawn(async move {
+            let result = write_archive(&db, user_id, timezone, &path).await;
+            if let Some(mut job) = jobs.get_mut(&export_id) {
+                job.completed_at = Some(Utc::now());
+                match result {
+                    Ok(()) => job.status = ExportStatus::Ready,
+                    Err(e) => {
+                        tracing::error!("Export {} for user {} failed: {}", export_id, user_id, e);
+                        job.status = ExportStatus::Failed;
+                        job.error = Some("The export could not be generated".to_string());
+                    }
+                }
+            }
+        });
+
+        tracing::info!("Started data export {} for user {}", job.export_id, user_id);
+        job
+    }
+
+    /// A user's export, with a freshly signed link if it's ready. Other
+    /// users' exports look like they don't exist.
+    pub fn status(&self, user_id: UserId, export_id: Uuid) -> Option<ExportJob> {
+        let mut job = self.jobs.get(&export_id).filter(|job| job.user_id == user_id)?.clone();
+        if job.status == ExportStatus::Ready {
+            let expires = Utc::now().timestamp() + self.link_ttl.as_secs() as i64;
+            job.download_url = Some(format!(
+                "/api/exports/{}/download?expires={}&signature={}",
+                export_id,
+                expires,
+                self.sign(export_id, expires)
+            ));
+        }
+        Some(job)
+    }
+
+    /// Checks a download link and opens the archive it points at, as a
+    /// stream of chunks
+    pub async fn open(
+        &self,
+        export_id: Uuid,
+        expires: i64,
+        signature: &str,
+    ) -> Result<impl Stream<Item = std::io::Result<Bytes>>, ExportError> {
+        let signature = hex::decode(signature).map_err(|_| ExportError::InvalidLink)?;
+        if self.mac(export_id, expires).verify_slice(&signature).is_err() || expires < Utc::now().timestamp() {
+            return Err(ExportError::InvalidLink);
+        }
+
+        match self.jobs.get(&export_id).map(|job| job.status) {
+            None => return Err(ExportError::NotFound { export_id }),
+            Some(ExportStatus::Ready) => {}
+            Some(_) => return Err(ExportError::NotReady { export_id }),
+        }
+
+        let file = tokio::fs::File::open(self.archive_path(export_id)).await?;
+        Ok(futures_util::stream::unfold(Some(file), |file| async move {
+            let mut file = file?;
+            let mut buffer = vec![0; DOWNLOAD_CHUNK_SIZE];
+            match file.read(&mut buffer).await {
+                Ok(0) => None,
+                Ok(read) => {
+                    buffer.truncate(read);
+                    Some((Ok(Bytes::from(buffer)), Some(file)))
+                }
+                // End the stream after reporting the error
+                Err(e) => Some((Err(e), None)),
+            }
+        }))
+    }
+
+    /// Deletes archives (and half-written ones) older than the link TTL,
+    /// forgetting their jobs. Jobs only live in memory, so this is also what
+    /// clears out archives left behind by an earlier process. Returns how
+    /// many files were removed.
+    pub async fn sweep_expired(&self) -> Result<usize, ExportError> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let cutoff = std::time::SystemTime::now()
+            .checked_sub(self.link_ttl)
+            .unwrap_or(std::time::UNIX_EPOCH);
+
+        let mut removed = 0;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let Some(export_id) = name
+                .to_str()
+                .and_then(|name| name.split('.').next())
+                .and_then(|stem| Uuid::parse_str(stem).ok())
+            else {
+                continue;
+            };
+            let pending = self.jobs.get(&export_id).is_some_and(|job| job.status == ExportStatus::Pending);
+            let modified = entry.metadata().await?.modified()?;
+            if pending || modified > cutoff {
+                continue;
+            }
+
+            self.jobs.remove(&export_id);
+            match tokio::fs::remove_file(entry.path()).await {
+                Ok(()) => removed += 1,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(removed)
+    }
+
+    fn archive_path(&self, export_id: Uuid) -> PathBuf {
+        self.dir.join(format!("{}.ndjson", export_id))
+    }
+
+    fn mac(&self, export_id: Uuid, expires: i64) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.signing_key).expect("HMAC accepts keys of any length");
+        mac.update(format!("{}:{}", export_id, expires).as_bytes());
+        mac
+    }
+
+    fn sign(&self, export_id: Uuid, expires: i64) -> String {
+        hex::encode(self.mac(export_id, expires).finalize().into_bytes())
+    }
+
+    /// Forgets a user's earlier exports and removes their archives
+    fn discard_user_exports(&self, user_id: UserId) {
+        let stale: Vec<Uuid> = self
+            .jobs
+            .iter()
+            .filter(|job| job.user_id == user_id && job.status != ExportStatus::Pending)
+            .map(|job| job.export_id)
+            .collect();
+        for export_id in stale {
+            self.jobs.remove(&export_id);
+            let path = self.archive_path(export_id);
+            tokio::spawn(async move {
+                let _ = tokio::fs::remove_file(path).await;
+            });
+        }
+    }
+}
+
+/// `at` in `timezone`, as RFC 3339 with the offset that applied then
+fn local_timestamp(at: DateTime<Utc>, timezone: Tz) -> String {
+    at.with_timezone(&timezone).to_rfc3339()
+}
+
+/// Writes the archive to a temporary file, renaming it into place once
+/// complete so a half-written export is never served
+async fn write_archive(db: &CampfireDatabase, user_id: UserId, timezone: Tz, path: &Path) -> Result<(), ExportError> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let partial = path.with_extension("ndjson.part");
+    let mut out = BufWriter::new(tokio::fs::File::create(&partial).await?);
+
+    async fn line(out: &mut BufWriter<tokio::fs::File>, record: serde_json::Value) -> Result<(), ExportError> {
+        out.write_all(record.to_string().as_bytes()).await?;
+        out.write_all(b"\n").await?;
+        Ok(())
+    }
+
+    let user = db
+        .get_user_by_id(user_id)
+        .await?
+        .ok_or(ExportError::Database(crate::errors::DatabaseError::DataIntegrity {
+            reason: format!("user {} no longer exists", user_id),
+        }))?;
+    line(&mut out, json!({
+        "type": "profile",
+        "id": user.id,
+        "name": user.name,
+        "email": user.email,
+        "bio": user.bio,
+        "admin": user.admin,
+        "timezone": db.get_user_timezone(user_id).await?,
+        "created_at": user.created_at,
+        "created_at_local": local_timestamp(user.created_at, timezone),
+    }))
+    .await?;
+
+    for membership in db.get_user_memberships(user_id).await? {
+        line(&mut out, json!({
+            "type": "membership",
+            "room_id": membership.room_id,
+            "involvement_level": membership.involvement_level,
+            "created_at": membership.created_at,
+            "created_at_local": local_timestamp(membership.created_at, timezone),
+        }))
+        .await?;
+    }
+
+    let mut after: Option<(DateTime<Utc>, MessageId)> = None;
+    loop {
+        let page = db.get_messages_by_creator(user_id, after, EXPORT_PAGE_SIZE).await?;
+        for message in &page {
+            line(&mut out, json!({
+                "type": "message",
+                "id": message.id,
+                "room_id": message.room_id,
+                "content": message.content,
+                "created_at": message.created_at,
+                "created_at_local": local_timestamp(message.created_at, timezone),
+            }))
+            .await?;
+        }
+        match page.last() {
+            Some(last) if page.len() == EXPORT_PAGE_SIZE as usize => after = Some((last.created_at, last.id)),
+            _ => break,
+        }
+    }
+
+    out.flush().await?;
+    drop(out);
+    tokio::fs::rename(&partial, path).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{InvolvementLevel, Membership, Message, Room, RoomType, User};
+    use futures_util::StreamExt;
+
+    #[tokio::test]
+    async fn test_export_contains_own_messages_only() {
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let dir = tempfile::tempdir().unwrap();
+        let exports = ExportService::new(db.clone(), dir.path().to_path_buf());
+
+        let alice = User::for_tests("alice");
+        let bob = User::for_tests("bob");
+        db.create_user(alice.clone()).await.unwrap();
+        db.create_user(bob.clone()).await.unwrap();
+        let room = Room::for_tests("General", RoomType::Open);
+        db.create_room(room.clone()).await.unwrap();
+        for member in [&alice, &bob] {
+            db.create_membership(Membership {
+                room_id: room.id,
+                user_id: member.id,
+                involvement_level: InvolvementLevel::Member,
+                created_at: Utc::now(),
+            })
+            .await
+            .unwrap();
+        }
+        for (author, content) in [(&alice, "alice says hi"), (&bob, "bob's secret"), (&alice, "alice again")] {
+            let message = Message::new(room.id, author.id, content.to_string(), Uuid::new_v4());
+            db.create_message_with_deduplication(message).await.unwrap();
+        }
+
+        let job = exports.request(alice.id, Tz::UTC);
+        assert_eq!(job.status, ExportStatus::Pending);
+        assert!(exports.status(bob.id, job.export_id).is_none());
+
+        let mut ready = None;
+        for _ in 0..100 {
+            let current = exports.status(alice.id, job.export_id).unwrap();
+            if current.status == ExportStatus::Ready {
+                ready = Some(current);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let ready = ready.expect("export should finish");
+        // Asking again returns the same export rather than starting another
+        assert_eq!(exports.request(alice.id, Tz::UTC).export_id, job.export_id);
+
+        let url = url::Url::parse(&format!("http://campfire{}", ready.download_url.unwrap())).unwrap();
+        let query: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+        let expires: i64 = query["expires"].parse().unwrap();
+
+        let mut stream = Box::pin(exports.open(job.export_id, expires, &query["signature"]).await.unwrap());
+        let mut archive = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            archive.extend_from_slice(&chunk.unwrap());
+        }
+        let records: Vec<serde_json::Value> = String::from_utf8(archive)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(records[0]["type"], "profile");
+        assert_eq!(records[0]["email"], alice.email.as_str());
+        assert_eq!(records.iter().filter(|r| r["type"] == "membership").count(), 1);
+        let messages: Vec<&str> = records
+            .iter()
+            .filter(|r| r["type"] == "message")
+            .map(|r| r["content"].as_str().unwrap())
+            .collect();
+        assert_eq!(messages, vec!["alice says hi", "alice again"]);
+
+        // Tampered or expired links are refused
+        assert!(matches!(
+            exports.open(job.export_id, expires + 1, &query["signature"]).await,
+            Err(ExportError::InvalidLink)
+        ));
+        let past = Utc::now().timestamp() - 1;
+        assert!(matches!(
+            exports.open(job.export_id, past, &exports.sign(job.export_id, past)).await,
+            Err(ExportError::InvalidLink)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_export_renders_local_times_in_the_requested_zone() {
+        use chrono::TimeZone;
+
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let dir = tempfile::tempdir().unwrap();
+        let exports = ExportService::new(db.clone(), dir.path().to_path_buf());
+
+        let alice = User::for_tests("alice");
+        db.create_user(alice.clone()).await.unwrap();
+        let room = Room::for_tests("General", RoomType::Open);
+        db.create_room(room.clone()).await.unwrap();
+        let mut message = Message::new(room.id, alice.id, "winter".to_string(), Uuid::new_v4());
+        message.created_at = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+        db.create_message_with_deduplication(message).await.unwrap();
+
+        let new_york: Tz = "America/New_York".parse().unwrap();
+        let job = exports.request(alice.id, new_york);
+        assert_eq!(job.timezone, "America/New_York");
+        // A different zone is a different export
+        assert_ne!(exports.request(alice.id, Tz::UTC).export_id, job.export_id);
+
+        let mut download_url = None;
+        for _ in 0..100 {
+            download_url = exports.status(alice.id, job.export_id).unwrap().download_url;
+            if download_url.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let url = url::Url::parse(&format!("http://campfire{}", download_url.expect("export should finish"))).unwrap();
+        let query: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+        let mut stream = Box::pin(
+            exports.open(job.export_id, query["expires"].parse().unwrap(), &query["signature"]).await.unwrap(),
+        );
+        let mut archive = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            archive.extend_from_slice(&chunk.unwrap());
+        }
+        let message: serde_json::Value = String::from_utf8(archive)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .find(|record| record["type"] == "message")
+            .unwrap();
+
+        // The raw UTC value is kept next to the local one
+        assert_eq!(message["created_at"], "2024-01-15T12:00:00Z");
+        assert_eq!(message["created_at_local"], "2024-01-15T07:00:00-05:00");
+    }
+
+    #[tokio::test]
+    async fn test_message_cursor_survives_deleting_its_message() {
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let alice = User::for_tests("alice");
+        db.create_user(alice.clone()).await.unwrap();
+        let room = Room::for_tests("General", RoomType::Open);
+        db.create_room(room.clone()).await.unwrap();
+        let start = Utc::now() - chrono::Duration::days(30);
+        for i in 0..3 {
+            let mut message = Message::new(room.id, alice.id, format!("message {}", i), Uuid::new_v4());
+            message.created_at = start + chrono::Duration::days(i);
+            db.create_message_with_deduplication(message).await.unwrap();
+        }
+
+        let first = db.get_messages_by_creator(alice.id, None, 1).await.unwrap();
+        assert_eq!(first[0].content, "message 0");
+
+        // A retention purge removes the page's last message mid-export
+        db.purge_messages_before(start + chrono::Duration::hours(1)).await.unwrap();
+        let cursor = Some((first[0].created_at, first[0].id));
+        let rest = db.get_messages_by_creator(alice.id, cursor, 10).await.unwrap();
+        let contents: Vec<&str> = rest.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["message 1", "message 2"]);
+    }
+
+    #[tokio::test]
+    async fn test_sweep_removes_archives_past_the_link_ttl() {
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let dir = tempfile::tempdir().unwrap();
+        // Archives an earlier process wrote and never cleaned up
+        let orphan = dir.path().join(format!("{}.ndjson", Uuid::new_v4()));
+        let partial = dir.path().join(format!("{}.ndjson.part", Uuid::new_v4()));
+        let unrelated = dir.path().join("README");
+        for path in [&orphan, &partial, &unrelated] {
+            tokio::fs::write(path, b"{}\n").await.unwrap();
+        }
+
+        // Within the link lifetime nothing goes
+        let exports = ExportService::new(db.clone(), dir.path().to_path_buf());
+        assert_eq!(exports.sweep_expired().await.unwrap(), 0);
+        assert!(orphan.exists());
+
+        let exports = exports.with_link_ttl(Duration::ZERO);
+        assert_eq!(exports.sweep_expired().await.unwrap(), 2);
+        assert!(!orphan.exists());
+        assert!(!partial.exists());
+        assert!(unrelated.exists());
+
+        // A missing directory just means nothing was exported yet
+        let exports = ExportService::new(db, dir.path().join("missing"));
+        assert_eq!(exports.sweep_expired().await.unwrap(), 0);
+    }
+}
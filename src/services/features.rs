@@ -0,0 +1,144 @@
+//! Feature flag resolution for gradual rollouts
+//!
+//! Global flags come from configuration and are the defaults. Rows in
+//! `feature_overrides` turn a flag on or off for a single room or user, so a
+//! feature can be rolled out (or pulled back) without a redeploy. The most
+//! specific layer wins: user, then room, then global. Flags that only exist
+//! as overrides default to off.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::config::FeatureFlags as GlobalFeatureFlags;
+use crate::database::CampfireDatabase;
+use crate::errors::DatabaseError;
+use crate::models::{FeatureOverride, FeatureScope, RoomId, UserId};
+
+#[derive(Clone)]
+pub struct FeatureFlags {
+    db: Arc<CampfireDatabase>,
+    global: BTreeMap<String, bool>,
+}
+
+impl FeatureFlags {
+    pub fn new(db: Arc<CampfireDatabase>, global: BTreeMap<String, bool>) -> Self {
+        Self { db, global }
+    }
+
+    /// Uses the config field names as flag names, e.g. "search", "sounds"
+    pub fn from_config(db: Arc<CampfireDatabase>, config: &GlobalFeatureFlags) -> Self {
+        let global = serde_json::to_value(config)
+            .ok()
+            .and_then(|value| value.as_object().cloned())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(flag, value)| value.as_bool().map(|enabled| (flag, enabled)))
+            .collect();
+
+        Self::new(db, global)
+    }
+
+    /// Whether `flag` is on for the user, optionally within a room
+    pub async fn is_enabled(
+        &self,
+        flag: &str,
+        user_id: UserId,
+        room_id: Option<RoomId>,
+    ) -> Result<bool, DatabaseError> {
+        let overrides = self.db.get_feature_overrides(user_id, room_id).await?;
+        Ok(self.resolve(&overrides).get(flag).copied().unwrap_or(false))
+    }
+
+    /// Every flag as it applies to the user outside any particular room
+    pub async fn resolve_for_user(&self, user_id: UserId) -> Result<BTreeMap<String, bool>, DatabaseError> {
+        let overrides = self.db.get_feature_overrides(user_id, None).await?;
+        Ok(self.resolve(&overrides))
+    }
+
+    pub async fn set_override(&self, scope: FeatureScope, flag: &str, enabled: bool) -> Result<(), DatabaseError> {
+        self.db.set_feature_override(scope, flag.to_string(), enabled).await
+    }
+
+    pub async fn clear_override(&self, scope: FeatureScope, flag: &str) -> Result<(), DatabaseError> {
+        self.db.clear_feature_override(scope, flag.to_string()).await
+    }
+
+    fn resolve(&self, overrides: &[FeatureOverride]) -> BTreeMap<String, bool> {
+        let mut flags = self.global.clone();
+
+        // Room overrides first so user overrides land on top of them
+        let (user, room): (Vec<_>, Vec<_>) = overrides
+            .iter()
+            .partition(|o| matches!(o.scope, FeatureScope::User(_)));
+        for o in room.into_iter().chain(user) {
+            flags.insert(o.flag.clone(), o.enabled);
+        }
+
+        flags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::User;
+
+    async fn create_test_user(db: &CampfireDatabase) -> UserId {
+        let user_id = UserId::new();
+        db.create_user(User {
+            id: user_id,
+            ..User::for_tests("Test User")
+        }).await.unwrap();
+        user_id
+    }
+
+    #[tokio::test]
+    async fn test_override_precedence() {
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let global = GlobalFeatureFlags {
+            websockets: true,
+            push_notifications: true,
+            bot_api: true,
+            search: true,
+            sounds: false,
+            file_uploads: false,
+            demo_mode: false,
+            auto_create_rooms: true,
+            public_rooms: false,
+            impersonation: false,
+        };
+        let features = FeatureFlags::from_config(db.clone(), &global);
+
+        let user_id = create_test_user(&db).await;
+        let other_id = create_test_user(&db).await;
+        let room_id = RoomId::new();
+
+        // Global only
+        assert!(features.is_enabled("search", user_id, Some(room_id)).await.unwrap());
+        assert!(!features.is_enabled("sounds", user_id, Some(room_id)).await.unwrap());
+        assert!(!features.is_enabled("new_composer", user_id, None).await.unwrap());
+
+        // Room beats global
+        features.set_override(FeatureScope::Room(room_id), "search", false).await.unwrap();
+        features.set_override(FeatureScope::Room(room_id), "sounds", true).await.unwrap();
+        assert!(!features.is_enabled("search", user_id, Some(room_id)).await.unwrap());
+        assert!(features.is_enabled("sounds", user_id, Some(room_id)).await.unwrap());
+        assert!(features.is_enabled("search", user_id, None).await.unwrap());
+
+        // User beats room
+        features.set_override(FeatureScope::User(user_id), "search", true).await.unwrap();
+        assert!(features.is_enabled("search", user_id, Some(room_id)).await.unwrap());
+        assert!(!features.is_enabled("search", other_id, Some(room_id)).await.unwrap());
+
+        // Flags that exist only as overrides roll out to just those users
+        features.set_override(FeatureScope::User(user_id), "new_composer", true).await.unwrap();
+        let resolved = features.resolve_for_user(user_id).await.unwrap();
+        assert_eq!(resolved.get("new_composer"), Some(&true));
+        assert_eq!(resolved.get("sounds"), Some(&false));
+        assert!(!features.resolve_for_user(other_id).await.unwrap().contains_key("new_composer"));
+
+        // Clearing falls back to the next layer
+        features.clear_override(FeatureScope::User(user_id), "search").await.unwrap();
+        assert!(!features.is_enabled("search", user_id, Some(room_id)).await.unwrap());
+    }
+}
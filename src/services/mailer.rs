@@ -0,0 +1,105 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::database::CampfireDatabase;
+use crate::errors::MailerError;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::models::{RoomId, RoomType, UserId};
+
+/// An outgoing email
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Hands emails to whatever delivers them
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> Result<(), MailerError>;
+}
+
+/// Writes emails to the log instead of delivering them; the default until a
+/// transport is configured
+pub struct LogMailer {
+    from: String,
+}
+
+impl LogMailer {
+    pub fn new(from: String) -> Self {
+        Self { from }
+    }
+}
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: Email) -> Result<(), MailerError> {
+        if !email.to.contains('@') {
+            return Err(MailerError::InvalidAddress { address: email.to });
+        }
+        info!(from = %self.from, to = %email.to, subject = %email.subject, "Email: {}", email.body);
+        Ok(())
+    }
+}
+
+/// Emails people who were added to a closed room by someone else, so they
+/// find out even when they aren't signed in. Bots are never emailed, and
+/// users can turn it off with their `room_added_email_enabled` preference.
+pub struct RoomAddedEmailSubscriber {
+    db: Arc<CampfireDatabase>,
+    mailer: Arc<dyn Mailer>,
+    base_url: String,
+}
+
+impl RoomAddedEmailSubscriber {
+    pub fn new(db: Arc<CampfireDatabase>, mailer: Arc<dyn Mailer>, base_url: String) -> Self {
+        Self {
+            db,
+            mailer,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    async fn user_added(&self, room_id: RoomId, user_id: UserId, added_by: UserId) -> Result<(), MailerError> {
+        if user_id == added_by {
+            return Ok(());
+        }
+
+        let Some(room) = self.db.get_room_by_id(room_id).await? else {
+            return Ok(());
+        };
+        if !matches!(room.room_type, RoomType::Closed) {
+            return Ok(());
+        }
+        let Some(user) = self.db.get_user_by_id(user_id).await? else {
+            return Ok(());
+        };
+        if user.is_bot() || !self.db.get_notification_preferences(user_id).await?.room_added_email_enabled {
+            return Ok(());
+        }
+        let added_by_name = self.db.get_user_by_id(added_by).await?
+            .map_or_else(|| "Someone".to_string(), |adder| adder.name);
+
+        self.mailer.send(Email {
+            to: user.email,
+            subject: format!("You've been added to {}", room.name),
+            body: format!(
+                "{} added you to {}.\n\nJoin the conversation: {}/rooms/{}\n",
+                added_by_name, room.name, self.base_url, room.id
+            ),
+        }).await
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for RoomAddedEmailSubscriber {
+    async fn handle(&self, event: &DomainEvent) {
+        if let DomainEvent::UserJoined { room_id, user_id, added_by } = event {
+            if let Err(e) = self.user_added(*room_id, *user_id, *added_by).await {
+                warn!("Room-added email to {} for room {} not sent: {}", user_id, room_id, e);
+            }
+        }
+    }
+}
@@ -1,16 +1,23 @@
 use async_trait::async_trait;
-use chrono::Utc;
+use dashmap::DashMap;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
+use crate::config::PaginationConfig;
 use crate::database::CampfireDatabase;
+use crate::events::{BroadcastSubscriber, DomainEvent, EventBus, PushSubscriber};
 use crate::errors::{MessageError, ValidationError, BroadcastError, RoomError};
-use crate::models::{Message, MessageId, RoomId, UserId, WebSocketMessage};
+use crate::models::{Mention, Message, MessageId, RoomId, SavedMessage, SeenReceipt, UserId, WebSocketMessage};
+use crate::middleware::rate_limiting::RateLimitStatus;
 use crate::services::connection::ConnectionManager;
 use crate::services::room::RoomServiceTrait;
+use crate::services::sliding_window::SlidingWindowLimiter;
+use crate::services::moderation::{ModerationGate, ModerationRequest};
 use crate::services::push::PushNotificationService;
-use crate::rich_text::{RichTextProcessor, RichTextError};
-use crate::sounds::SoundManager;
+use crate::rich_text::{Pipeline, RichTextError, RichTextProcessor, StageContext, DEFAULT_MAX_MENTIONS, ROOM_WIDE_MENTIONS};
+use crate::sounds::SoundCooldown;
 
 #[async_trait]
 pub trait MessageServiceTrait: Send + Sync {
@@ -49,6 +56,73 @@ pub trait MessageServiceTrait: Send + Sync {
         before: Option<MessageId>,
     ) -> Result<Vec<Message>, MessageError>;
     
+    /// Returns the first message the user hasn't read yet, for the client's
+    /// "new messages" divider. Callers must already have checked room access.
+    async fn get_first_unread_message_id(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+    ) -> Result<Option<MessageId>, MessageError>;
+    
+    /// Records that the user has read up to and including `message_id`
+    /// 
+    /// # Error Conditions
+    /// - MessageError::NotFound if the message doesn't exist
+    /// - MessageError::Authorization if user lacks access to its room
+    async fn mark_read(
+        &self,
+        user_id: UserId,
+        message_id: MessageId,
+    ) -> Result<(), MessageError>;
+    
+    /// Members whose read marker has reached `message_id`
+    /// 
+    /// # Error Conditions
+    /// - MessageError::Authorization if user lacks room access
+    /// - MessageError::NotFound if the message isn't in `room_id`
+    /// - MessageError::SeenByUnavailable if the room has too many members
+    async fn get_seen_by(
+        &self,
+        room_id: RoomId,
+        message_id: MessageId,
+        user_id: UserId,
+    ) -> Result<Vec<SeenReceipt>, MessageError>;
+    
+    /// Recent messages mentioning the user across their rooms, newest first
+    async fn get_mentions(
+        &self,
+        user_id: UserId,
+        limit: u32,
+        before: Option<MessageId>,
+    ) -> Result<Vec<Mention>, MessageError>;
+    
+    /// Adds a message to the user's private saved list
+    /// 
+    /// # Error Conditions
+    /// - MessageError::NotFound if the message doesn't exist
+    /// - MessageError::Authorization if user lacks access to its room
+    async fn save_message(
+        &self,
+        user_id: UserId,
+        message_id: MessageId,
+    ) -> Result<(), MessageError>;
+    
+    /// Removes a message from the user's saved list; not saved is not an error
+    async fn unsave_message(
+        &self,
+        user_id: UserId,
+        message_id: MessageId,
+    ) -> Result<(), MessageError>;
+    
+    /// The user's saved messages in rooms they can still access, most
+    /// recently saved first; deleted messages come back as tombstones
+    async fn get_saved_messages(
+        &self,
+        user_id: UserId,
+        limit: u32,
+        before: Option<MessageId>,
+    ) -> Result<Vec<SavedMessage>, MessageError>;
+    
     /// Broadcasts message to room subscribers
     async fn broadcast_message(
         &self,
@@ -58,14 +132,93 @@ pub trait MessageServiceTrait: Send + Sync {
     
     /// Returns reference to the connection manager for WebSocket operations
     fn connection_manager(&self) -> &Arc<dyn ConnectionManager>;
+    
+    /// Whether the user can read and post in the room
+    /// 
+    /// # Error Conditions
+    /// - MessageError::Authorization if the room doesn't exist
+    async fn check_room_access(&self, room_id: RoomId, user_id: UserId) -> Result<bool, MessageError>;
+}
+
+/// Per-user message rate limit applied across all rooms
+/// 
+/// Sliding window: a user may create at most `limit` messages in any
+/// `window`, however they spread them over rooms.
+pub struct MessageRateLimiter {
+    exempt_bots: bool,
+    exempt_admins: bool,
+    headers: bool,
+    window: SlidingWindowLimiter<UserId>,
+}
+
+impl MessageRateLimiter {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            exempt_bots: false,
+            exempt_admins: false,
+            headers: false,
+            window: SlidingWindowLimiter::new(limit, window),
+        }
+    }
+    
+    pub fn with_exemptions(mut self, exempt_bots: bool, exempt_admins: bool) -> Self {
+        self.exempt_bots = exempt_bots;
+        self.exempt_admins = exempt_admins;
+        self
+    }
+    
+    /// Reports `X-RateLimit-*` headers on refusals
+    pub fn with_headers(mut self, headers: bool) -> Self {
+        self.headers = headers;
+        self
+    }
+    
+    /// Records a message for the user, or returns where they stand and how
+    /// long until one is allowed
+    fn try_acquire(&self, user_id: UserId) -> Result<(), (Duration, RateLimitStatus)> {
+        self.window.try_acquire(user_id).map_err(|full| {
+            let status = RateLimitStatus {
+                limit: self.window.limit(),
+                remaining: 0,
+                reset_secs: full.reset_after.as_secs().max(1),
+            };
+            (full.retry_after, status)
+        })
+    }
+    
+    fn window_label(&self) -> String {
+        match self.window.window().as_secs() {
+            60 => "minute".to_string(),
+            secs => format!("{} seconds", secs),
+        }
+    }
 }
 
+/// Seen-by lists are only useful (and cheap) in DMs and small rooms
+pub const DEFAULT_SEEN_BY_MAX_MEMBERS: u32 = 20;
+
 #[derive(Clone)]
 pub struct MessageService {
     db: Arc<CampfireDatabase>,
     connection_manager: Arc<dyn ConnectionManager>,
     room_service: Arc<dyn RoomServiceTrait>,
-    push_service: Option<Arc<dyn PushNotificationService>>,
+    events: EventBus,
+    rate_limiter: Option<Arc<MessageRateLimiter>>,
+    seen_by_max_members: u32,
+    /// 0 = unlimited
+    max_saved_messages: u32,
+    max_mentions: usize,
+    reject_excess_mentions: bool,
+    sound_cooldown: Duration,
+    sound_cooldowns: Arc<SoundCooldown>,
+    write_timeout: Option<Duration>,
+    /// Writes still running after their caller timed out, by room and
+    /// `client_message_id`; flips to true once the write settles
+    pending_writes: Arc<DashMap<(RoomId, Uuid), tokio::sync::watch::Sender<bool>>>,
+    duplicate_window: Option<Duration>,
+    pipeline: Pipeline,
+    moderation: Option<ModerationGate>,
+    max_page_size: u32,
 }
 
 impl MessageService {
@@ -74,11 +227,27 @@ impl MessageService {
         connection_manager: Arc<dyn ConnectionManager>,
         room_service: Arc<dyn RoomServiceTrait>,
     ) -> Self {
+        let events = EventBus::new();
+        events.subscribe(Arc::new(BroadcastSubscriber::new(connection_manager.clone())));
+        
         Self {
             db,
             connection_manager,
             room_service,
-            push_service: None,
+            events,
+            rate_limiter: None,
+            seen_by_max_members: DEFAULT_SEEN_BY_MAX_MEMBERS,
+            max_saved_messages: 0,
+            max_mentions: DEFAULT_MAX_MENTIONS,
+            reject_excess_mentions: false,
+            sound_cooldown: Duration::ZERO,
+            sound_cooldowns: Arc::new(SoundCooldown::new()),
+            write_timeout: None,
+            pending_writes: Arc::new(DashMap::new()),
+            duplicate_window: None,
+            pipeline: Pipeline::default(),
+            moderation: None,
+            max_page_size: PaginationConfig::default().max_limit,
         }
     }
     
@@ -93,14 +262,127 @@ impl MessageService {
         room_service: Arc<dyn RoomServiceTrait>,
         push_service: Arc<dyn PushNotificationService>,
     ) -> Self {
-        Self {
-            db,
-            connection_manager,
-            room_service,
-            push_service: Some(push_service),
+        let service = Self::new(db, connection_manager, room_service);
+        service.events.subscribe(Arc::new(PushSubscriber::new(service.db.clone(), push_service)));
+        service
+    }
+    
+    /// Enables the global per-user message rate limit
+    pub fn with_rate_limiter(mut self, rate_limiter: MessageRateLimiter) -> Self {
+        self.rate_limiter = Some(Arc::new(rate_limiter));
+        self
+    }
+    
+    /// Largest page of messages, mentions or saved messages returned,
+    /// normally the configured `pagination.max_limit`
+    pub fn with_max_page_size(mut self, max_page_size: u32) -> Self {
+        self.max_page_size = max_page_size;
+        self
+    }
+    
+    /// Largest room (by member count) that answers seen-by queries
+    pub fn with_seen_by_max_members(mut self, max_members: u32) -> Self {
+        self.seen_by_max_members = max_members;
+        self
+    }
+    
+    /// Messages each user may have saved (0 = unlimited); saving past it
+    /// answers `SavedLimitReached`
+    pub fn with_saved_message_limit(mut self, max_saved: u32) -> Self {
+        self.max_saved_messages = max_saved;
+        self
+    }
+    
+    /// Lets each room play at most one sound per `cooldown`; extra /play
+    /// commands are dropped. Room admins can override it per room.
+    pub fn with_sound_cooldown(mut self, cooldown: Duration) -> Self {
+        self.sound_cooldown = cooldown;
+        self
+    }
+    
+    /// Gives up on a send that has waited `timeout` for the database writer,
+    /// answering `WriteTimeout`. The write may still land, so clients retry
+    /// with the same client id and deduplication returns the saved message.
+    /// A zero timeout waits indefinitely.
+    pub fn with_write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = (!timeout.is_zero()).then_some(timeout);
+        self
+    }
+    
+    /// Answers a repeat of the user's last message in a room, sent within
+    /// `window`, with that message instead of posting it again. Catches
+    /// double sends that carry a fresh client id. A zero window disables it.
+    pub fn with_duplicate_window(mut self, window: Duration) -> Self {
+        self.duplicate_window = (!window.is_zero()).then_some(window);
+        self
+    }
+    
+    /// Stages message content goes through before it's stored
+    pub fn with_pipeline(mut self, pipeline: Pipeline) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+    
+    /// Has every new message reviewed by an external moderation hook
+    /// before it's stored
+    pub fn with_moderation(mut self, moderation: ModerationGate) -> Self {
+        self.moderation = Some(moderation);
+        self
+    }
+    
+    /// Drops sounds the room's policy doesn't allow
+    async fn apply_sound_policy(&self, room_id: RoomId, mut play_commands: Vec<String>) -> Result<Vec<String>, MessageError> {
+        if play_commands.is_empty() {
+            return Ok(play_commands);
+        }
+        
+        let policy = self.db.get_room_sound_policy(room_id).await?;
+        let before = play_commands.len();
+        play_commands.retain(|sound| policy.permits(sound));
+        let blocked = before - play_commands.len();
+        if blocked > 0 {
+            metrics::counter!("sounds_blocked_total", blocked as u64);
+        }
+        Ok(play_commands)
+    }
+    
+    /// Keeps the first sound if the room isn't cooling down, dropping the rest
+    async fn apply_sound_cooldown(&self, room_id: RoomId, mut play_commands: Vec<String>) -> Result<Vec<String>, MessageError> {
+        if play_commands.is_empty() {
+            return Ok(play_commands);
+        }
+        
+        let cooldown = match self.db.get_room_sound_cooldown(room_id).await? {
+            Some(secs) => Duration::from_secs(secs),
+            None => self.sound_cooldown,
+        };
+        if cooldown.is_zero() {
+            return Ok(play_commands);
+        }
+        
+        if self.sound_cooldowns.try_play(room_id, cooldown) {
+            play_commands.truncate(1);
+            Ok(play_commands)
+        } else {
+            metrics::counter!("sounds_suppressed_total", 1);
+            Ok(Vec::new())
         }
     }
     
+    /// Distinct users a message may mention; extras are ignored, or the
+    /// message is rejected when `reject_excess` is set
+    pub fn with_mention_limit(mut self, max_mentions: usize, reject_excess: bool) -> Self {
+        self.max_mentions = max_mentions;
+        self.reject_excess_mentions = reject_excess;
+        self
+    }
+    
+    /// Bus that MessageCreated is emitted on; subscribe here to react to new
+    /// messages without touching this service
+    pub fn event_bus(&self) -> &EventBus {
+        &self.events
+    }
+    
     /// Returns reference to the connection manager for WebSocket operations
     pub fn connection_manager(&self) -> &Arc<dyn ConnectionManager> {
         &self.connection_manager
@@ -127,41 +409,44 @@ impl MessageService {
             return Err(ValidationError::InvalidContentLength);
         }
         
-        // Extract and clean /play commands first
-        let (cleaned_content, play_commands) = RichTextProcessor::extract_and_clean_play_commands(content);
-        
-        // Use cleaned content for display if play commands were removed
-        let display_content = if cleaned_content.trim().is_empty() && !play_commands.is_empty() {
-            // If only play commands, use a default message
-            format!("🎵 Played: {}", play_commands.join(", "))
-        } else if cleaned_content != content {
-            cleaned_content
-        } else {
-            content.to_string()
-        };
-        
-        // Create user lookup function for @mentions
-        let _db = Arc::clone(&self.db);
-        let user_lookup = move |_username: &str| -> Option<UserId> {
-            // For now, we'll do a simple lookup - in a real implementation,
-            // this would be async and cached
-            // TODO: Implement proper async user lookup with caching
-            None // Placeholder - will be implemented when user lookup is available
+        // Stages run synchronously, so the handles mentioned are resolved
+        // up front; one that can't be looked up is left unlinked
+        let (mentions, _) = RichTextProcessor::limit_mentions(
+            &RichTextProcessor::extract_mentions(content),
+            self.max_mentions,
+        );
+        let mut handles = HashMap::new();
+        for mention in mentions {
+            let handle = mention.to_lowercase();
+            if ROOM_WIDE_MENTIONS.contains(&handle.as_str()) {
+                continue;
+            }
+            if let Ok(Some(user)) = self.db.get_user_by_handle(&handle).await {
+                handles.insert(handle, user.id);
+            }
+        }
+        let user_lookup = |mention: &str| -> Option<UserId> { handles.get(&mention.to_lowercase()).copied() };
+        let context = StageContext {
+            max_mentions: self.max_mentions,
+            user_lookup: &user_lookup,
         };
         
-        // Process rich text content
-        match RichTextProcessor::process_content(&display_content, user_lookup).await {
+        // Run the content through the configured stages once
+        match self.pipeline.run(content, &context) {
+            Ok(processed) if processed.ignored_mentions > 0 && self.reject_excess_mentions => {
+                Err(ValidationError::TooManyMentions { limit: self.max_mentions })
+            }
             Ok(processed) => {
                 // Use the sanitized HTML as the display content
                 let final_display_content = processed.html.clone();
                 
-                let html_content = if processed.has_rich_features || processed.html != display_content {
+                let html_content = if processed.has_rich_features {
                     Some(processed.html)
                 } else {
                     None
                 };
                 
-                Ok((final_display_content, html_content, processed.mentions, play_commands))
+                Ok((final_display_content, html_content, processed.mentions, processed.play_commands))
             }
             Err(RichTextError::SanitizationRemoved) => {
                 Err(ValidationError::HtmlSanitization {
@@ -176,21 +461,63 @@ impl MessageService {
         }
     }
     
-    /// Checks if user has access to the room using RoomService
-    async fn check_room_access(&self, room_id: RoomId, user_id: UserId) -> Result<bool, MessageError> {
-        match self.room_service.check_room_access(room_id, user_id).await {
-            Ok(Some(_involvement_level)) => Ok(true),
-            Ok(None) => Ok(false),
-            Err(RoomError::NotFound { .. }) => {
-                Err(MessageError::Authorization { user_id, room_id })
-            }
-            Err(e) => {
-                // Convert RoomError to MessageError
-                Err(MessageError::Database(
-                    sqlx::Error::Configuration(format!("Room access check failed: {}", e).into())
-                ))
+    /// Swaps `/canned <shortcut>` for the room's saved reply, which then
+    /// goes through the content pipeline like anything typed
+    async fn expand_canned_response(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+        content: String,
+    ) -> Result<String, MessageError> {
+        let Some(rest) = content.trim().strip_prefix("/canned") else {
+            return Ok(content);
+        };
+        // `/cannedfoo` is just text
+        if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+            return Ok(content);
+        }
+        
+        // Outsiders can't probe a room's shortcuts
+        if !self.check_room_access(room_id, user_id).await? {
+            return Err(MessageError::Authorization { user_id, room_id });
+        }
+        
+        let shortcut = rest.trim().to_lowercase();
+        self.db
+            .get_canned_response(room_id, &shortcut)
+            .await?
+            .ok_or(MessageError::UnknownCannedResponse { shortcut })
+    }
+    
+    /// Enforces the global per-user message rate, if configured
+    async fn check_rate_limit(&self, user_id: UserId) -> Result<(), MessageError> {
+        let Some(limiter) = &self.rate_limiter else {
+            return Ok(());
+        };
+        
+        let Err((retry_after, status)) = limiter.try_acquire(user_id) else {
+            return Ok(());
+        };
+        
+        // Only look the user up once they're actually over the limit
+        if limiter.exempt_bots || limiter.exempt_admins {
+            if let Some(user) = self.db.get_user_by_id(user_id).await? {
+                let is_bot = user.bot_token.is_some();
+                if (limiter.exempt_bots && is_bot) || (limiter.exempt_admins && user.admin) {
+                    return Ok(());
+                }
             }
         }
+        
+        metrics::counter!("messages_rate_limited_total", 1);
+        tracing::warn!("User {} exceeded the global message rate", user_id);
+        
+        Err(MessageError::RateLimit {
+            limit: limiter.window.limit(),
+            window: limiter.window_label(),
+            retry_after_secs: retry_after.as_secs().max(1),
+            status: limiter.headers.then_some(status),
+        })
     }
 }
 
@@ -203,6 +530,29 @@ impl MessageServiceTrait for MessageService {
         user_id: UserId,
         client_message_id: Uuid,
     ) -> Result<Message, MessageError> {
+        // A retry of a send whose write is still running waits for it,
+        // rather than racing it through the checks below
+        let pending = self.pending_writes
+            .get(&(room_id, client_message_id))
+            .map(|done| done.subscribe());
+        if let (Some(mut pending), Some(timeout)) = (pending, self.write_timeout) {
+            tokio::time::timeout(timeout, pending.wait_for(|done| *done))
+                .await
+                .map_err(|_| MessageError::WriteTimeout { client_message_id })?
+                .ok();
+        }
+        
+        // A send that already landed, retried after a timeout or replayed
+        // after a reconnect, gets its message back before anything that
+        // counts against the user or has side effects
+        if let Some(existing) = self.db.get_message_by_client_id(client_message_id, room_id).await? {
+            if existing.creator_id == user_id {
+                return Ok(existing);
+            }
+        }
+        
+        let content = self.expand_canned_response(room_id, user_id, content).await?;
+        
         // Step 1: Validate and process content with rich text features
         let (display_content, html_content, mentions, play_commands) = self
             .validate_and_process_content(&content)
@@ -216,6 +566,54 @@ impl MessageServiceTrait for MessageService {
             return Err(MessageError::Authorization { user_id, room_id });
         }
         
+        // Announcement rooms only take posts from admins and granted users
+        let may_post = self.room_service
+            .check_post_permission(room_id, user_id)
+            .await
+            .map_err(|e| MessageError::Database(
+                sqlx::Error::Configuration(format!("Post permission check failed: {}", e).into())
+            ))?;
+        if !may_post {
+            return Err(MessageError::PostingRestricted { user_id, room_id });
+        }
+        
+        // An identical send moments after the last one is a double click,
+        // not a new message; hand back the one already posted
+        if let Some(window) = self.duplicate_window {
+            let since = chrono::Utc::now() - chrono::Duration::from_std(window).unwrap_or_default();
+            if let Some(existing) = self.db
+                .get_recent_identical_message(room_id, user_id, &display_content, since)
+                .await?
+            {
+                metrics::counter!("duplicate_messages_suppressed_total", 1);
+                return Ok(existing);
+            }
+        }
+        
+        // Global per-user rate, counted across every room
+        self.check_rate_limit(user_id).await?;
+        
+        // External moderation sees what the user wrote; redacted text goes
+        // through the content pipeline again
+        let (display_content, html_content, mentions, play_commands) = match &self.moderation {
+            Some(moderation) => {
+                let request = ModerationRequest { room_id, user_id, content: &content };
+                match moderation.check(request).await? {
+                    Some(redacted) => self
+                        .validate_and_process_content(&redacted)
+                        .await
+                        .map_err(|e| MessageError::InvalidContent { reason: e.to_string() })?,
+                    None => (display_content, html_content, mentions, play_commands),
+                }
+            }
+            None => (display_content, html_content, mentions, play_commands),
+        };
+        
+        // Only sounds the room allows, at most one per cooldown; the message
+        // still posts
+        let play_commands = self.apply_sound_policy(room_id, play_commands).await?;
+        let play_commands = self.apply_sound_cooldown(room_id, play_commands).await?;
+        
         // Step 3: Create message object with rich text features
         let message = Message::with_rich_content(
             room_id,
@@ -224,71 +622,49 @@ impl MessageServiceTrait for MessageService {
             client_message_id,
             html_content,
             mentions,
-            play_commands.clone(),
+            play_commands,
         );
         
-        // Step 4: Persist with deduplication (Critical Gap #1)
-        let persisted_message = self.db
-            .create_message_with_deduplication(message)
-            .await?;
-        
-        // Step 5: Broadcast message to room subscribers
-        if let Err(broadcast_error) = self.broadcast_message(&persisted_message, room_id).await {
-            // Log the error but don't fail the message creation
-            tracing::warn!("Failed to broadcast message {}: {}", persisted_message.id.0, broadcast_error);
-        }
-        
-        // Step 6: Send push notifications if service is available
-        if let Some(push_service) = &self.push_service {
-            // Get room information for notification context
-            if let Ok(Some(room)) = self.room_service.get_room_by_id(room_id).await {
-                // Get sender name for notification
-                if let Ok(Some(sender)) = self.db.get_user_by_id(user_id).await {
-                    // Send message notification
-                    if let Err(e) = push_service.send_message_notification(&persisted_message, &room, &sender.name).await {
-                        tracing::warn!("Failed to send push notification for message {}: {}", persisted_message.id.0, e);
-                    }
-                    
-                    // Send mention notifications for each mentioned user
-                    for mention in &persisted_message.mentions {
-                        if let Ok(Some(mentioned_user)) = self.db.get_user_by_email(mention).await {
-                            if let Err(e) = push_service.send_mention_notification(&persisted_message, &room, &sender.name, mentioned_user.id).await {
-                                tracing::warn!("Failed to send mention notification to {}: {}", mention, e);
-                            }
-                        }
-                    }
-                }
+        // Step 4: Persist with deduplication (Critical Gap #1), then
+        // broadcast; push notifications and sounds are subscribers too. Only
+        // a new row is announced, and a write that outlives the timeout
+        // still announces itself when it lands.
+        let db = self.db.clone();
+        let events = self.events.clone();
+        let write = async move {
+            let write = db.write_message_with_deduplication(message).await?;
+            if write.inserted {
+                events
+                    .emit(DomainEvent::MessageCreated { message: write.message.clone() })
+                    .await;
             }
-        }
-        
-        // Step 7: Broadcast sound playback commands if any
-        for sound_name in &play_commands {
-            if SoundManager::sound_exists(sound_name) {
-                let sound_message = WebSocketMessage::SoundPlayback {
-                    sound_name: sound_name.clone(),
-                    triggered_by: user_id,
-                    room_id,
-                    timestamp: Utc::now(),
-                };
-                
-                if let Err(e) = self.connection_manager.broadcast_to_room(room_id, sound_message).await {
-                    tracing::warn!("Failed to broadcast sound playback {}: {}", sound_name, e);
-                }
-                
-                // Send push notification for sound playback if service is available
-                if let Some(push_service) = &self.push_service {
-                    if let Ok(Some(room)) = self.room_service.get_room_by_id(room_id).await {
-                        if let Ok(Some(sender)) = self.db.get_user_by_id(user_id).await {
-                            if let Err(e) = push_service.send_sound_notification(sound_name, &room, &sender.name).await {
-                                tracing::warn!("Failed to send sound notification for {}: {}", sound_name, e);
-                            }
-                        }
+            Ok::<_, MessageError>(write.message)
+        };
+        match self.write_timeout {
+            Some(timeout) => {
+                let key = (room_id, client_message_id);
+                let (done, _) = tokio::sync::watch::channel(false);
+                self.pending_writes.insert(key, done);
+                let pending_writes = self.pending_writes.clone();
+                let write = tokio::spawn(async move {
+                    let result = write.await;
+                    if let Some((_, done)) = pending_writes.remove(&key) {
+                        done.send_replace(true);
                     }
-                }
+                    result
+                });
+                tokio::time::timeout(timeout, write)
+                    .await
+                    .map_err(|_| {
+                        metrics::counter!("message_write_timeouts_total", 1);
+                        MessageError::WriteTimeout { client_message_id }
+                    })?
+                    .map_err(|e| MessageError::Database(
+                        sqlx::Error::Configuration(format!("Message write failed: {}", e).into())
+                    ))?
             }
+            None => write.await,
         }
-        
-        Ok(persisted_message)
     }
     
     async fn get_room_messages(
@@ -304,7 +680,7 @@ impl MessageServiceTrait for MessageService {
         }
         
         // Limit the number of messages to prevent abuse
-        let safe_limit = std::cmp::min(limit, 100);
+        let safe_limit = std::cmp::min(limit, self.max_page_size);
         
         let messages = self.db
             .get_room_messages(room_id, safe_limit, before)
@@ -313,6 +689,95 @@ impl MessageServiceTrait for MessageService {
         Ok(messages)
     }
     
+    async fn get_first_unread_message_id(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+    ) -> Result<Option<MessageId>, MessageError> {
+        Ok(self.db.get_first_unread_message_id(room_id, user_id).await?)
+    }
+    
+    async fn mark_read(
+        &self,
+        user_id: UserId,
+        message_id: MessageId,
+    ) -> Result<(), MessageError> {
+        let room_id = self.db.get_message_room_id(message_id).await?
+            .ok_or(MessageError::NotFound { message_id })?;
+        if !self.check_room_access(room_id, user_id).await? {
+            return Err(MessageError::Authorization { user_id, room_id });
+        }
+        
+        Ok(self.db.update_read_marker(user_id, message_id).await?)
+    }
+    
+    async fn get_seen_by(
+        &self,
+        room_id: RoomId,
+        message_id: MessageId,
+        user_id: UserId,
+    ) -> Result<Vec<SeenReceipt>, MessageError> {
+        if !self.check_room_access(room_id, user_id).await? {
+            return Err(MessageError::Authorization { user_id, room_id });
+        }
+        
+        if self.db.get_message_room_id(message_id).await? != Some(room_id) {
+            return Err(MessageError::NotFound { message_id });
+        }
+        
+        if self.db.count_room_members(room_id).await? > self.seen_by_max_members {
+            return Err(MessageError::SeenByUnavailable { limit: self.seen_by_max_members });
+        }
+        
+        Ok(self.db.get_seen_by(message_id).await?)
+    }
+    
+    async fn get_mentions(
+        &self,
+        user_id: UserId,
+        limit: u32,
+        before: Option<MessageId>,
+    ) -> Result<Vec<Mention>, MessageError> {
+        let safe_limit = std::cmp::min(limit, self.max_page_size);
+        Ok(self.db.get_mentions(user_id, safe_limit, before).await?)
+    }
+    
+    async fn save_message(
+        &self,
+        user_id: UserId,
+        message_id: MessageId,
+    ) -> Result<(), MessageError> {
+        let room_id = self.db.get_message_room_id(message_id).await?
+            .ok_or(MessageError::NotFound { message_id })?;
+        if !self.check_room_access(room_id, user_id).await? {
+            return Err(MessageError::Authorization { user_id, room_id });
+        }
+        
+        if !self.db.save_message(user_id, message_id, room_id, self.max_saved_messages).await? {
+            return Err(MessageError::SavedLimitReached { limit: self.max_saved_messages });
+        }
+        Ok(())
+    }
+    
+    async fn unsave_message(
+        &self,
+        user_id: UserId,
+        message_id: MessageId,
+    ) -> Result<(), MessageError> {
+        self.db.unsave_message(user_id, message_id).await?;
+        Ok(())
+    }
+    
+    async fn get_saved_messages(
+        &self,
+        user_id: UserId,
+        limit: u32,
+        before: Option<MessageId>,
+    ) -> Result<Vec<SavedMessage>, MessageError> {
+        let safe_limit = std::cmp::min(limit, self.max_page_size);
+        Ok(self.db.get_saved_messages(user_id, safe_limit, before).await?)
+    }
+    
     async fn broadcast_message(
         &self,
         message: &Message,
@@ -330,13 +795,34 @@ impl MessageServiceTrait for MessageService {
     fn connection_manager(&self) -> &Arc<dyn ConnectionManager> {
         &self.connection_manager
     }
+    
+    /// Checks if user has access to the room using RoomService
+    async fn check_room_access(&self, room_id: RoomId, user_id: UserId) -> Result<bool, MessageError> {
+        match self.room_service.check_room_access(room_id, user_id).await {
+            Ok(Some(_involvement_level)) => Ok(true),
+            Ok(None) => Ok(false),
+            Err(RoomError::NotFound { .. }) => {
+                Err(MessageError::Authorization { user_id, room_id })
+            }
+            Err(e) => {
+                // Convert RoomError to MessageError
+                Err(MessageError::Database(
+                    sqlx::Error::Configuration(format!("Room access check failed: {}", e).into())
+                ))
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::PostPermission;
     use crate::database::CampfireDatabase;
     use crate::services::connection::ConnectionManagerImpl;
+    use crate::config::ModerationFailurePolicy;
+    use crate::services::moderation::{ModerationHook, ModerationVerdict};
+    use crate::sounds::SoundPolicy;
     use sqlx::Row;
     
     async fn create_test_message_service() -> MessageService {
@@ -531,22 +1017,903 @@ mod tests {
     }
     
     #[tokio::test]
-    async fn test_message_creation_with_broadcast() {
+    async fn test_excess_mentions_are_ignored_or_rejected() {
+        let content = "@alice @bob @carol @room @dave";
+        
+        let service = create_test_message_service().await.with_mention_limit(2, false);
+        let (_, _, mentions, _) = service.validate_and_process_content(content).await.unwrap();
+        assert_eq!(mentions, vec!["alice", "bob", "room"]);
+        
+        let service = create_test_message_service().await.with_mention_limit(2, true);
+        let result = service.validate_and_process_content(content).await;
+        assert!(matches!(result, Err(ValidationError::TooManyMentions { limit: 2 })));
+        
+        // @room doesn't count toward the cap
+        assert!(service.validate_and_process_content("@alice @room @bob @here").await.is_ok());
+    }
+    
+    #[tokio::test]
+    async fn test_mentions_link_users_by_handle() {
         let service = create_test_message_service().await;
+        let (person_id, _) = create_test_user_and_room(&service.db).await;
+        let bot_id = UserId::new();
+        service.db.create_user(crate::models::User {
+            id: bot_id,
+            email: "bot-secret@bots.example.com".to_string(),
+            password_hash: String::new(),
+            bot_token: Some("secret".to_string()),
+            ..crate::models::User::for_tests("Deploy Bot")
+        }).await.unwrap();
         
-        let room_id = RoomId::new();
+        let content = format!("@{} @DEPLOY-BOT", person_id.0.to_string().to_uppercase());
+        let (_, html, _, _) = service.validate_and_process_content(&content).await.unwrap();
+        let html = html.unwrap();
+        assert!(html.contains(&format!(r#"data-mention-id="{}""#, person_id)));
+        assert!(html.contains(&format!(r#"data-mention-id="{}""#, bot_id)));
+        
+        // A bot's email carries its token, so that isn't a handle
+        let (_, html, mentions, _) = service.validate_and_process_content("@bot-secret hi").await.unwrap();
+        assert_eq!(mentions, vec!["bot-secret"]);
+        assert!(!html.unwrap_or_default().contains("data-mention-id"));
+    }
+    
+    /// Creates a user, an open room and the user's membership
+    async fn create_test_user_and_room(db: &CampfireDatabase) -> (UserId, RoomId) {
         let user_id = UserId::new();
+        let room_id = RoomId::new();
         
-        // Create the user first
-        let user = crate::models::User {
+        db.create_user(crate::models::User {
             id: user_id,
-            name: "Test User".to_string(),
-            email: "test2@example.com".to_string(),
-            password_hash: "hash".to_string(),
-            bio: None,
-            admin: false,
-            bot_token: None,
-            created_at: chrono::Utc::now(),
+            email: format!("{}@example.com", user_id.0),
+            ..crate::models::User::for_tests("Test User")
+        }).await.unwrap();
+        
+        db.create_room(crate::models::Room {
+            id: room_id,
+            ..crate::models::Room::for_tests(&format!("Test Room {}", room_id.0), crate::models::RoomType::Open)
+        }).await.unwrap();
+        
+        db.create_membership(crate::models::Membership {
+            room_id,
+            user_id,
+            involvement_level: crate::models::InvolvementLevel::Member,
+            created_at: chrono::Utc::now(),
+        }).await.unwrap();
+        
+        (user_id, room_id)
+    }
+    
+    async fn count_client_id(db: &CampfireDatabase, client_message_id: Uuid) -> i64 {
+        sqlx::query("SELECT COUNT(*) as count FROM messages WHERE client_message_id = ?")
+            .bind(client_message_id.to_string())
+            .fetch_one(db.pool())
+            .await
+            .unwrap()
+            .get("count")
+    }
+    
+    #[tokio::test]
+    async fn test_deduplication_survives_volume_and_retention_purge() {
+        let service = create_test_message_service().await;
+        let (user_id, room_id) = create_test_user_and_room(&service.db).await;
+        
+        let client_ids: Vec<Uuid> = (0..200).map(|_| Uuid::new_v4()).collect();
+        let mut originals = Vec::new();
+        for (i, client_id) in client_ids.iter().enumerate() {
+            let message = service
+                .create_message_with_deduplication(format!("message {}", i), room_id, user_id, *client_id)
+                .await
+                .unwrap();
+            originals.push(message);
+        }
+        
+        // Retry of the very first message is still deduplicated
+        let retry = service
+            .create_message_with_deduplication("retry".to_string(), room_id, user_id, client_ids[0])
+            .await
+            .unwrap();
+        assert_eq!(retry.id, originals[0].id);
+        
+        // Age the first half past the retention window and purge it
+        let old = chrono::Utc::now() - chrono::Duration::days(60);
+        for message in &originals[..100] {
+            sqlx::query("UPDATE messages SET created_at = ? WHERE id = ?")
+                .bind(old)
+                .bind(message.id.0.to_string())
+                .execute(service.db.pool())
+                .await
+                .unwrap();
+        }
+        let purged = service.db
+            .purge_messages_before(chrono::Utc::now() - chrono::Duration::days(30))
+            .await
+            .unwrap();
+        assert_eq!(purged, 100);
+        
+        // Retained messages keep their dedup guarantee and no duplicate reappears
+        let retry = service
+            .create_message_with_deduplication("retry".to_string(), room_id, user_id, client_ids[150])
+            .await
+            .unwrap();
+        assert_eq!(retry.id, originals[150].id);
+        assert_eq!(retry.content, "message 150");
+        assert_eq!(count_client_id(&service.db, client_ids[150]).await, 1);
+    }
+    
+    #[tokio::test]
+    async fn test_concurrent_identical_submits_deduplicate() {
+        let service = create_test_message_service().await;
+        let (user_id, room_id) = create_test_user_and_room(&service.db).await;
+        let client_message_id = Uuid::new_v4();
+        
+        let (first, second) = tokio::join!(
+            service.create_message_with_deduplication("Hello".to_string(), room_id, user_id, client_message_id),
+            service.create_message_with_deduplication("Hello".to_string(), room_id, user_id, client_message_id),
+        );
+        
+        assert_eq!(first.unwrap().id, second.unwrap().id);
+        assert_eq!(count_client_id(&service.db, client_message_id).await, 1);
+    }
+    
+    #[tokio::test]
+    async fn test_duplicate_window_catches_double_sends() {
+        let service = create_test_message_service().await
+            .with_duplicate_window(Duration::from_secs(5));
+        let (user_id, room_id) = create_test_user_and_room(&service.db).await;
+        
+        // Same text under a fresh client id is answered with the first message
+        let first = service
+            .create_message_with_deduplication("Hello".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        let double = service
+            .create_message_with_deduplication("Hello".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        assert_eq!(double.id, first.id);
+        assert_eq!(count_client_id(&service.db, first.client_message_id).await, 1);
+        
+        // Different text, or the same text from someone else, still posts
+        let (other_user_id, _) = create_test_user_and_room(&service.db).await;
+        service.db.create_membership(crate::models::Membership {
+            room_id,
+            user_id: other_user_id,
+            involvement_level: crate::models::InvolvementLevel::Member,
+            created_at: chrono::Utc::now(),
+        }).await.unwrap();
+        let echoed = service
+            .create_message_with_deduplication("Hello".to_string(), room_id, other_user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        assert_ne!(echoed.id, first.id);
+        let reply = service
+            .create_message_with_deduplication("Hello again".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        assert_ne!(reply.id, first.id);
+    }
+    
+    #[tokio::test]
+    async fn test_duplicate_window_allows_repeats_outside_it() {
+        let service = create_test_message_service().await
+            .with_duplicate_window(Duration::from_secs(5));
+        let (user_id, room_id) = create_test_user_and_room(&service.db).await;
+        
+        let first = service
+            .create_message_with_deduplication("+1".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        sqlx::query("UPDATE messages SET created_at = ? WHERE id = ?")
+            .bind(chrono::Utc::now() - chrono::Duration::seconds(10))
+            .bind(first.id.0.to_string())
+            .execute(service.db.pool())
+            .await
+            .unwrap();
+        
+        let repeat = service
+            .create_message_with_deduplication("+1".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        assert_ne!(repeat.id, first.id);
+        
+        // Without a window, immediate repeats are left alone
+        let service = create_test_message_service().await;
+        let (user_id, room_id) = create_test_user_and_room(&service.db).await;
+        let first = service
+            .create_message_with_deduplication("+1".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        let repeat = service
+            .create_message_with_deduplication("+1".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        assert_ne!(repeat.id, first.id);
+    }
+    
+    #[tokio::test]
+    async fn test_saved_messages_respect_room_access() {
+        let service = create_test_message_service().await;
+        let (user_id, room_id) = create_test_user_and_room(&service.db).await;
+        let (outsider_id, other_room_id) = create_test_user_and_room(&service.db).await;
+        for room_id in [room_id, other_room_id] {
+            service.db.update_room_type(room_id, crate::models::RoomType::Closed).await.unwrap();
+        }
+        
+        let first = service
+            .create_message_with_deduplication("Remember this".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        let second = service
+            .create_message_with_deduplication("And this".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        let elsewhere = service
+            .create_message_with_deduplication("Not for you".to_string(), other_room_id, outsider_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        
+        service.save_message(user_id, first.id).await.unwrap();
+        service.save_message(user_id, second.id).await.unwrap();
+        service.save_message(user_id, second.id).await.unwrap();
+        
+        // Only messages in rooms the user can see may be saved
+        match service.save_message(user_id, elsewhere.id).await {
+            Err(MessageError::Authorization { .. }) => {}
+            other => panic!("expected Authorization, got {:?}", other),
+        }
+        
+        let saved = service.get_saved_messages(user_id, 50, None).await.unwrap();
+        let ids: Vec<MessageId> = saved.iter().map(|s| s.message_id).collect();
+        assert_eq!(ids, vec![second.id, first.id]);
+        assert_eq!(saved[0].message.as_ref().unwrap().content, "And this");
+        
+        // Nobody else sees the user's saved list
+        assert!(service.get_saved_messages(outsider_id, 50, None).await.unwrap().is_empty());
+        
+        // A deleted message stays as a tombstone
+        sqlx::query("DELETE FROM messages WHERE id = ?")
+            .bind(first.id.0.to_string())
+            .execute(service.db.pool())
+            .await
+            .unwrap();
+        let saved = service.get_saved_messages(user_id, 50, None).await.unwrap();
+        assert_eq!(saved.len(), 2);
+        assert!(saved[1].deleted && saved[1].message.is_none());
+        
+        service.unsave_message(user_id, second.id).await.unwrap();
+        let saved = service.get_saved_messages(user_id, 50, None).await.unwrap();
+        assert_eq!(saved.len(), 1);
+        
+        // Leaving the room hides what was saved there
+        service.db.delete_membership(room_id, user_id).await.unwrap();
+        assert!(service.get_saved_messages(user_id, 50, None).await.unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_saving_past_the_limit_is_rejected() {
+        let service = create_test_message_service().await.with_saved_message_limit(2);
+        let (user_id, room_id) = create_test_user_and_room(&service.db).await;
+        
+        let mut messages = Vec::new();
+        for content in ["one", "two", "three"] {
+            let message = service
+                .create_message_with_deduplication(content.to_string(), room_id, user_id, Uuid::new_v4())
+                .await
+                .unwrap();
+            messages.push(message.id);
+        }
+        
+        service.save_message(user_id, messages[0]).await.unwrap();
+        service.save_message(user_id, messages[1]).await.unwrap();
+        match service.save_message(user_id, messages[2]).await {
+            Err(MessageError::SavedLimitReached { limit: 2 }) => {}
+            other => panic!("expected SavedLimitReached, got {:?}", other),
+        }
+        // Re-saving one already saved is still fine at the limit
+        service.save_message(user_id, messages[1]).await.unwrap();
+        assert_eq!(service.get_saved_messages(user_id, 50, None).await.unwrap().len(), 2);
+        
+        // Unsaving frees a slot
+        service.unsave_message(user_id, messages[0]).await.unwrap();
+        service.save_message(user_id, messages[2]).await.unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_sound_cooldown_limits_playback_broadcasts() {
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let connection_manager = Arc::new(ConnectionManagerImpl::new(db.clone()));
+        let room_service = Arc::new(crate::services::room::RoomService::new(db.clone()));
+        let service = MessageService::new(db.clone(), connection_manager.clone(), room_service)
+            .with_sound_cooldown(Duration::from_secs(60));
+        let (user_id, room_id) = create_test_user_and_room(&db).await;
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        connection_manager.add_connection(user_id, crate::models::ConnectionId::new(), tx).await.unwrap();
+        connection_manager.add_room_membership(room_id, vec![user_id]).await;
+
+        for _ in 0..5 {
+            service.create_message_with_deduplication("/play tada".to_string(), room_id, user_id, Uuid::new_v4())
+                .await
+                .unwrap();
+        }
+
+        let mut sound_broadcasts = 0;
+        while let Ok(frame) = rx.try_recv() {
+            if frame.contains("\"SoundPlayback\"") {
+                sound_broadcasts += 1;
+            }
+        }
+        assert_eq!(sound_broadcasts, 1);
+
+        // An admin override of zero turns the cooldown off for that room
+        db.set_room_sound_cooldown(room_id, Some(0)).await.unwrap();
+        for _ in 0..3 {
+            service.create_message_with_deduplication("/play tada".to_string(), room_id, user_id, Uuid::new_v4())
+                .await
+                .unwrap();
+        }
+        let overridden = std::iter::from_fn(|| rx.try_recv().ok())
+            .filter(|frame| frame.contains("\"SoundPlayback\""))
+            .count();
+        assert_eq!(overridden, 3);
+    }
+
+    #[tokio::test]
+    async fn test_sound_policy_drops_disallowed_sounds() {
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let connection_manager = Arc::new(ConnectionManagerImpl::new(db.clone()));
+        let room_service = Arc::new(crate::services::room::RoomService::new(db.clone()));
+        let service = MessageService::new(db.clone(), connection_manager.clone(), room_service);
+        let (user_id, room_id) = create_test_user_and_room(&db).await;
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        connection_manager.add_connection(user_id, crate::models::ConnectionId::new(), tx).await.unwrap();
+        connection_manager.add_room_membership(room_id, vec![user_id]).await;
+
+        // Sounds are off: the message posts without them
+        db.set_room_sound_policy(room_id, SoundPolicy::Disabled).await.unwrap();
+        let message = service
+            .create_message_with_deduplication("/play tada".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        assert!(message.sound_commands.is_empty());
+        assert!(!std::iter::from_fn(|| rx.try_recv().ok()).any(|frame| frame.contains("\"SoundPlayback\"")));
+
+        // Only allowlisted sounds play
+        db.set_room_sound_policy(room_id, SoundPolicy::Allowlist { sounds: vec!["rimshot".to_string()] })
+            .await
+            .unwrap();
+        let blocked = service
+            .create_message_with_deduplication("/play tada".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        assert!(blocked.sound_commands.is_empty());
+        let allowed = service
+            .create_message_with_deduplication("/play rimshot".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        assert_eq!(allowed.sound_commands, vec!["rimshot".to_string()]);
+        let played: Vec<String> = std::iter::from_fn(|| rx.try_recv().ok())
+            .filter(|frame| frame.contains("\"SoundPlayback\""))
+            .collect();
+        assert_eq!(played.len(), 1);
+        assert!(played[0].contains("rimshot"));
+    }
+
+    #[tokio::test]
+    async fn test_sound_policy_allowlist_must_name_real_sounds() {
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let room_service = crate::services::room::RoomService::new(db.clone());
+        let (_, room_id) = create_test_user_and_room(&db).await;
+        let admin_id = UserId::new();
+        db.create_user(crate::models::User {
+            id: admin_id,
+            admin: true,
+            ..crate::models::User::for_tests("Admin")
+        }).await.unwrap();
+
+        let policy = SoundPolicy::Allowlist { sounds: vec!["tada".to_string(), "kazoo-solo".to_string()] };
+        match room_service.set_sound_policy(room_id, admin_id, policy).await {
+            Err(RoomError::UnknownSound { name }) => assert_eq!(name, "kazoo-solo"),
+            other => panic!("expected UnknownSound, got {:?}", other),
+        }
+    }
+
+    /// Answers every review with `verdict` after `delay`
+    struct StubModerationHook {
+        verdict: ModerationVerdict,
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl ModerationHook for StubModerationHook {
+        async fn review(&self, _request: &ModerationRequest<'_>) -> Result<ModerationVerdict, crate::errors::ModerationError> {
+            tokio::time::sleep(self.delay).await;
+            Ok(self.verdict.clone())
+        }
+    }
+
+    async fn moderated_service(
+        verdict: ModerationVerdict,
+        delay: Duration,
+        failure_policy: ModerationFailurePolicy,
+    ) -> (MessageService, UserId, RoomId) {
+        let hook = Arc::new(StubModerationHook { verdict, delay });
+        let service = create_test_message_service().await
+            .with_moderation(ModerationGate::new(hook, Duration::from_millis(50), failure_policy));
+        let (user_id, room_id) = create_test_user_and_room(&service.db).await;
+        (service, user_id, room_id)
+    }
+
+    #[tokio::test]
+    async fn test_moderation_hook_allows_redacts_and_blocks() {
+        let (service, user_id, room_id) =
+            moderated_service(ModerationVerdict::Allow, Duration::ZERO, ModerationFailurePolicy::FailClosed).await;
+        let message = service
+            .create_message_with_deduplication("Hello there".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        assert_eq!(message.content, "Hello there");
+
+        let redact = ModerationVerdict::Redact { content: "My card is [redacted]".to_string() };
+        let (service, user_id, room_id) =
+            moderated_service(redact, Duration::ZERO, ModerationFailurePolicy::FailClosed).await;
+        let message = service
+            .create_message_with_deduplication("My card is 4111 1111 1111 1111".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        assert_eq!(message.content, "My card is [redacted]");
+
+        let block = ModerationVerdict::Block { reason: Some("Contains a card number".to_string()) };
+        let (service, user_id, room_id) =
+            moderated_service(block, Duration::ZERO, ModerationFailurePolicy::FailOpen).await;
+        match service
+            .create_message_with_deduplication("4111 1111 1111 1111".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+        {
+            Err(MessageError::Blocked { reason }) => assert_eq!(reason.as_deref(), Some("Contains a card number")),
+            other => panic!("expected Blocked, got {:?}", other),
+        }
+        assert!(service.db.get_room_messages(room_id, 10, None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_moderation_timeout_follows_failure_policy() {
+        let block = ModerationVerdict::Block { reason: None };
+
+        // Fail-open: a hook that's too slow doesn't hold the message back
+        let (service, user_id, room_id) =
+            moderated_service(block.clone(), Duration::from_secs(5), ModerationFailurePolicy::FailOpen).await;
+        let message = service
+            .create_message_with_deduplication("Slow hook".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        assert_eq!(message.content, "Slow hook");
+
+        // Fail-closed: the same timeout rejects it
+        let (service, user_id, room_id) =
+            moderated_service(block, Duration::from_secs(5), ModerationFailurePolicy::FailClosed).await;
+        assert!(matches!(
+            service
+                .create_message_with_deduplication("Slow hook".to_string(), room_id, user_id, Uuid::new_v4())
+                .await,
+            Err(MessageError::ModerationUnavailable)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_slow_writer_times_out_and_retry_dedupes() {
+        #[derive(Default)]
+        struct CountingSubscriber(std::sync::atomic::AtomicUsize);
+        
+        #[async_trait]
+        impl crate::events::EventSubscriber for CountingSubscriber {
+            async fn handle(&self, _event: &DomainEvent) {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+        
+        // A budget of one send: the retries must not count against it
+        let service = create_test_message_service().await
+            .with_write_timeout(Duration::from_millis(100))
+            .with_rate_limiter(MessageRateLimiter::new(1, Duration::from_secs(60)));
+        let created = Arc::new(CountingSubscriber::default());
+        service.event_bus().subscribe(created.clone());
+        let (user_id, room_id) = create_test_user_and_room(&service.db).await;
+        let client_message_id = Uuid::new_v4();
+
+        // Another connection's open write transaction stalls the writer
+        let mut blocker = service.db.pool().begin().await.unwrap();
+        sqlx::query("DELETE FROM sessions WHERE 0")
+            .execute(&mut blocker)
+            .await
+            .unwrap();
+
+        let result = service
+            .create_message_with_deduplication("Hello".to_string(), room_id, user_id, client_message_id)
+            .await;
+        match result {
+            Err(MessageError::WriteTimeout { client_message_id: echoed }) => assert_eq!(echoed, client_message_id),
+            other => panic!("expected WriteTimeout, got {:?}", other),
+        }
+
+        // Once the writer catches up, the retry returns the same message
+        blocker.rollback().await.unwrap();
+        let first = service
+            .create_message_with_deduplication("Hello".to_string(), room_id, user_id, client_message_id)
+            .await
+            .unwrap();
+        let retried = service
+            .create_message_with_deduplication("Hello".to_string(), room_id, user_id, client_message_id)
+            .await
+            .unwrap();
+        assert_eq!(first.id, retried.id);
+        // The timed-out write announced itself when it landed, once
+        assert_eq!(created.0.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        let count: i64 = sqlx::query("SELECT COUNT(*) AS count FROM messages WHERE client_message_id = ?")
+            .bind(client_message_id.to_string())
+            .fetch_one(service.db.pool())
+            .await
+            .unwrap()
+            .get("count");
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_replayed_send_skips_rate_limit_and_announces_nothing() {
+        #[derive(Default)]
+        struct CountingSubscriber(std::sync::atomic::AtomicUsize);
+        
+        #[async_trait]
+        impl crate::events::EventSubscriber for CountingSubscriber {
+            async fn handle(&self, _event: &DomainEvent) {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+        
+        let service = create_test_message_service().await
+            .with_rate_limiter(MessageRateLimiter::new(1, Duration::from_secs(60)));
+        let created = Arc::new(CountingSubscriber::default());
+        service.event_bus().subscribe(created.clone());
+        let (user_id, room_id) = create_test_user_and_room(&service.db).await;
+        let client_message_id = Uuid::new_v4();
+        
+        let first = service
+            .create_message_with_deduplication("Hello".to_string(), room_id, user_id, client_message_id)
+            .await
+            .unwrap();
+        // The budget is spent, but a replay of the same send still succeeds
+        for _ in 0..2 {
+            let replayed = service
+                .create_message_with_deduplication("Hello".to_string(), room_id, user_id, client_message_id)
+                .await
+                .unwrap();
+            assert_eq!(replayed.id, first.id);
+        }
+        assert_eq!(created.0.load(std::sync::atomic::Ordering::SeqCst), 1);
+        
+        assert!(matches!(
+            service
+                .create_message_with_deduplication("New".to_string(), room_id, user_id, Uuid::new_v4())
+                .await,
+            Err(MessageError::RateLimit { .. })
+        ));
+    }
+    
+    #[tokio::test]
+    async fn test_global_rate_limit_spans_rooms() {
+        let service = create_test_message_service().await
+            .with_rate_limiter(MessageRateLimiter::new(3, Duration::from_secs(60)).with_headers(true));
+        let (user_id, first_room) = create_test_user_and_room(&service.db).await;
+        let (other_user_id, other_room) = create_test_user_and_room(&service.db).await;
+        
+        // Spread the user's posts over three rooms
+        let mut rooms = vec![first_room];
+        for _ in 0..2 {
+            let (_, room_id) = create_test_user_and_room(&service.db).await;
+            service.db.create_membership(crate::models::Membership {
+                room_id,
+                user_id,
+                involvement_level: crate::models::InvolvementLevel::Member,
+                created_at: chrono::Utc::now(),
+            }).await.unwrap();
+            rooms.push(room_id);
+        }
+        for room_id in &rooms {
+            service.create_message_with_deduplication("Hi".to_string(), *room_id, user_id, Uuid::new_v4())
+                .await
+                .unwrap();
+        }
+        
+        let result = service
+            .create_message_with_deduplication("One more".to_string(), first_room, user_id, Uuid::new_v4())
+            .await;
+        match result {
+            Err(MessageError::RateLimit { limit, retry_after_secs, status, .. }) => {
+                assert_eq!(limit, 3);
+                assert!(retry_after_secs > 0 && retry_after_secs <= 60);
+                let status = status.expect("headers are enabled");
+                assert_eq!((status.limit, status.remaining), (3, 0));
+                assert!(status.reset_secs >= retry_after_secs);
+            }
+            other => panic!("expected rate limit, got {:?}", other.map(|m| m.id)),
+        }
+        
+        // Other users have their own budget
+        service.create_message_with_deduplication("Hello".to_string(), other_room, other_user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_global_rate_limit_exempts_bots() {
+        let service = create_test_message_service().await
+            .with_rate_limiter(MessageRateLimiter::new(1, Duration::from_secs(60)).with_exemptions(true, false));
+        let (user_id, room_id) = create_test_user_and_room(&service.db).await;
+        sqlx::query("UPDATE users SET bot_token = 'token' WHERE id = ?")
+            .bind(user_id.0.to_string())
+            .execute(service.db.pool())
+            .await
+            .unwrap();
+        
+        for _ in 0..3 {
+            service.create_message_with_deduplication("Beep".to_string(), room_id, user_id, Uuid::new_v4())
+                .await
+                .unwrap();
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_first_unread_message_follows_read_marker() {
+        let service = create_test_message_service().await;
+        let (reader_id, room_id) = create_test_user_and_room(&service.db).await;
+        let (author_id, _) = create_test_user_and_room(&service.db).await;
+        service.db.create_membership(crate::models::Membership {
+            room_id,
+            user_id: author_id,
+            involvement_level: crate::models::InvolvementLevel::Member,
+            created_at: chrono::Utc::now(),
+        }).await.unwrap();
+        
+        let start = chrono::Utc::now() - chrono::Duration::minutes(10);
+        let mut ids = Vec::new();
+        for i in 0..4 {
+            let mut message = Message::new(room_id, author_id, format!("Message {}", i), Uuid::new_v4());
+            message.created_at = start + chrono::Duration::seconds(i);
+            ids.push(service.db.create_message_with_deduplication(message).await.unwrap().id);
+        }
+        
+        // No marker yet means no divider
+        assert_eq!(service.get_first_unread_message_id(room_id, reader_id).await.unwrap(), None);
+        
+        service.mark_read(reader_id, ids[1]).await.unwrap();
+        assert_eq!(service.get_first_unread_message_id(room_id, reader_id).await.unwrap(), Some(ids[2]));
+        
+        // A stale update doesn't rewind the marker
+        service.mark_read(reader_id, ids[0]).await.unwrap();
+        assert_eq!(service.get_first_unread_message_id(room_id, reader_id).await.unwrap(), Some(ids[2]));
+        
+        service.mark_read(reader_id, ids[3]).await.unwrap();
+        assert_eq!(service.get_first_unread_message_id(room_id, reader_id).await.unwrap(), None);
+        
+        // The reader's own messages never count as unread
+        let own = Message::new(room_id, reader_id, "Mine".to_string(), Uuid::new_v4());
+        service.db.create_message_with_deduplication(own).await.unwrap();
+        assert_eq!(service.get_first_unread_message_id(room_id, reader_id).await.unwrap(), None);
+        
+        // Messages posted in the same instant are ordered by sequence
+        let now = chrono::Utc::now();
+        let mut tied = Vec::new();
+        for i in 0..3 {
+            let mut message = Message::new(room_id, author_id, format!("Tied {}", i), Uuid::new_v4());
+            message.created_at = now;
+            tied.push(service.db.create_message_with_deduplication(message).await.unwrap().id);
+        }
+        service.mark_read(reader_id, tied[1]).await.unwrap();
+        service.mark_read(reader_id, tied[0]).await.unwrap();
+        let marker: String = sqlx::query_scalar(
+            "SELECT last_read_message_id FROM read_markers WHERE user_id = ? AND room_id = ?"
+        )
+        .bind(reader_id.0.to_string())
+        .bind(room_id.0.to_string())
+        .fetch_one(service.db.pool())
+        .await
+        .unwrap();
+        assert_eq!(marker, tied[1].0.to_string());
+        assert_eq!(service.get_first_unread_message_id(room_id, reader_id).await.unwrap(), Some(tied[2]));
+        
+        // Outsiders can't move markers in closed rooms they aren't in
+        service.db.update_room_type(room_id, crate::models::RoomType::Closed).await.unwrap();
+        let (outsider_id, _) = create_test_user_and_room(&service.db).await;
+        assert!(matches!(
+            service.mark_read(outsider_id, ids[3]).await,
+            Err(MessageError::Authorization { .. })
+        ));
+        assert!(matches!(
+            service.mark_read(reader_id, MessageId::new()).await,
+            Err(MessageError::NotFound { .. })
+        ));
+    }
+    
+    #[tokio::test]
+    async fn test_admins_only_room_blocks_members() {
+        let service = create_test_message_service().await;
+        let (member_id, room_id) = create_test_user_and_room(&service.db).await;
+        let (admin_id, _) = create_test_user_and_room(&service.db).await;
+        let (bot_id, _) = create_test_user_and_room(&service.db).await;
+        sqlx::query("UPDATE users SET bot_token = 'bot-token' WHERE id = ?")
+            .bind(bot_id.0.to_string())
+            .execute(service.db.pool())
+            .await
+            .unwrap();
+        for (user_id, involvement_level) in [
+            (admin_id, crate::models::InvolvementLevel::Admin),
+            (bot_id, crate::models::InvolvementLevel::Admin),
+        ] {
+            service.db.create_membership(crate::models::Membership {
+                room_id,
+                user_id,
+                involvement_level,
+                created_at: chrono::Utc::now(),
+            }).await.unwrap();
+        }
+        
+        // Only admins may restrict posting
+        assert!(matches!(
+            service.room_service.set_post_permission(room_id, member_id, PostPermission::AdminsOnly).await,
+            Err(RoomError::NotAuthorized { .. })
+        ));
+        service.room_service
+            .set_post_permission(room_id, admin_id, PostPermission::AdminsOnly)
+            .await
+            .unwrap();
+        
+        let post = |user_id| service.create_message_with_deduplication(
+            "Announcement".to_string(),
+            room_id,
+            user_id,
+            Uuid::new_v4(),
+        );
+        assert!(matches!(post(member_id).await, Err(MessageError::PostingRestricted { .. })));
+        assert!(post(admin_id).await.is_ok());
+        
+        // A bot needs an explicit grant even as a room admin
+        assert!(matches!(post(bot_id).await, Err(MessageError::PostingRestricted { .. })));
+        service.room_service.set_post_grant(room_id, admin_id, bot_id, true).await.unwrap();
+        assert!(post(bot_id).await.is_ok());
+        
+        let permissions = service.room_service.get_permissions(room_id, member_id).await.unwrap();
+        assert_eq!(permissions.post_permission, PostPermission::AdminsOnly);
+        assert!(permissions.can_read && !permissions.can_post);
+        
+        service.room_service
+            .set_post_permission(room_id, admin_id, PostPermission::Everyone)
+            .await
+            .unwrap();
+        assert!(post(member_id).await.is_ok());
+    }
+    
+    #[tokio::test]
+    async fn test_seen_by_follows_read_markers() {
+        let service = create_test_message_service().await;
+        let (author_id, room_id) = create_test_user_and_room(&service.db).await;
+        let (reader_id, _) = create_test_user_and_room(&service.db).await;
+        service.db.create_membership(crate::models::Membership {
+            room_id,
+            user_id: reader_id,
+            involvement_level: crate::models::InvolvementLevel::Member,
+            created_at: chrono::Utc::now(),
+        }).await.unwrap();
+        
+        let start = chrono::Utc::now() - chrono::Duration::minutes(10);
+        let mut ids = Vec::new();
+        for i in 0..3 {
+            let mut message = Message::new(room_id, author_id, format!("Message {}", i), Uuid::new_v4());
+            message.created_at = start + chrono::Duration::seconds(i);
+            ids.push(service.db.create_message_with_deduplication(message).await.unwrap().id);
+        }
+        
+        assert!(service.get_seen_by(room_id, ids[1], author_id).await.unwrap().is_empty());
+        
+        // Reading a later message covers everything before it
+        service.mark_read(reader_id, ids[2]).await.unwrap();
+        let seen_by = service.get_seen_by(room_id, ids[1], author_id).await.unwrap();
+        assert_eq!(seen_by.len(), 1);
+        assert_eq!(seen_by[0].user_id, reader_id);
+        
+        // The author's own marker isn't listed
+        service.mark_read(author_id, ids[2]).await.unwrap();
+        assert_eq!(service.get_seen_by(room_id, ids[2], reader_id).await.unwrap().len(), 1);
+        
+        // Message must belong to the room asked about
+        let (_, other_room) = create_test_user_and_room(&service.db).await;
+        assert!(matches!(
+            service.get_seen_by(other_room, ids[0], author_id).await,
+            Err(MessageError::NotFound { .. })
+        ));
+        
+        // Reading the first of two messages sent in the same instant
+        // doesn't mark the second as seen
+        let now = chrono::Utc::now();
+        let mut tied = Vec::new();
+        for i in 0..2 {
+            let mut message = Message::new(room_id, author_id, format!("Tied {}", i), Uuid::new_v4());
+            message.created_at = now;
+            tied.push(service.db.create_message_with_deduplication(message).await.unwrap().id);
+        }
+        service.mark_read(reader_id, tied[0]).await.unwrap();
+        assert_eq!(service.get_seen_by(room_id, tied[0], author_id).await.unwrap().len(), 1);
+        assert!(service.get_seen_by(room_id, tied[1], author_id).await.unwrap().is_empty());
+        
+        // Rooms over the member limit don't answer
+        let service = service.with_seen_by_max_members(1);
+        assert!(matches!(
+            service.get_seen_by(room_id, ids[0], author_id).await,
+            Err(MessageError::SeenByUnavailable { limit: 1 })
+        ));
+    }
+    
+    #[tokio::test]
+    async fn test_mentions_only_include_requester_in_member_rooms() {
+        let service = create_test_message_service().await;
+        let (author_id, room_id) = create_test_user_and_room(&service.db).await;
+        let (reader_id, _) = create_test_user_and_room(&service.db).await;
+        let (_, other_room) = create_test_user_and_room(&service.db).await;
+        service.db.create_membership(crate::models::Membership {
+            room_id,
+            user_id: reader_id,
+            involvement_level: crate::models::InvolvementLevel::Member,
+            created_at: chrono::Utc::now(),
+        }).await.unwrap();
+        service.db.create_membership(crate::models::Membership {
+            room_id: other_room,
+            user_id: author_id,
+            involvement_level: crate::models::InvolvementLevel::Member,
+            created_at: chrono::Utc::now(),
+        }).await.unwrap();
+        
+        // A person's handle is their email's local part, matched case-insensitively
+        let handle = reader_id.0.to_string().to_uppercase();
+        let start = chrono::Utc::now() - chrono::Duration::minutes(10);
+        let post = |room_id: RoomId, mentions: Vec<String>, offset: i64| {
+            let mut message = Message::new(room_id, author_id, "hey".to_string(), Uuid::new_v4());
+            message.mentions = mentions;
+            message.created_at = start + chrono::Duration::seconds(offset);
+            message
+        };
+        
+        let first = service.db.create_message_with_deduplication(post(room_id, vec![handle.clone()], 0)).await.unwrap();
+        service.db.create_message_with_deduplication(post(room_id, vec!["someone".to_string()], 1)).await.unwrap();
+        service.db.create_message_with_deduplication(post(room_id, vec![], 2)).await.unwrap();
+        let second = service.db.create_message_with_deduplication(post(room_id, vec!["x".to_string(), handle.clone()], 3)).await.unwrap();
+        // Not a member of this room
+        service.db.create_message_with_deduplication(post(other_room, vec![handle.clone()], 4)).await.unwrap();
+        
+        let mentions = service.get_mentions(reader_id, 50, None).await.unwrap();
+        let ids: Vec<MessageId> = mentions.iter().map(|m| m.message.id).collect();
+        assert_eq!(ids, vec![second.id, first.id]);
+        assert_eq!(mentions[0].room_name, format!("Test Room {}", room_id.0));
+        
+        // Paginates backwards
+        let page = service.get_mentions(reader_id, 50, Some(second.id)).await.unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].message.id, first.id);
+        
+        assert!(service.get_mentions(author_id, 50, None).await.unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_message_creation_with_broadcast() {
+        let service = create_test_message_service().await;
+        
+        let room_id = RoomId::new();
+        let user_id = UserId::new();
+        
+        // Create the user first
+        let user = crate::models::User {
+            id: user_id,
+            ..crate::models::User::for_tests("Test User")
         };
         
         service.db.create_user(user).await.unwrap();
@@ -586,6 +1953,39 @@ mod tests {
         assert_eq!(message.creator_id, user_id);
     }
     
+    #[tokio::test]
+    async fn test_canned_shortcut_expands_to_stored_content() {
+        let service = create_test_message_service().await;
+        let (user_id, room_id) = create_test_user_and_room(&service.db).await;
+        
+        service.db.set_canned_response(
+            room_id,
+            "greeting".to_string(),
+            "Thanks for reaching out! /play tada".to_string(),
+        ).await.unwrap();
+        
+        let message = service
+            .create_message_with_deduplication("/canned Greeting".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        // Expanded text goes through the rich-text stages like typed text,
+        // so the `/play` command is lifted out
+        assert_eq!(message.content, "Thanks for reaching out!");
+        assert!(message.html_content.is_some());
+        
+        let result = service
+            .create_message_with_deduplication("/canned missing".to_string(), room_id, user_id, Uuid::new_v4())
+            .await;
+        assert!(matches!(result, Err(MessageError::UnknownCannedResponse { ref shortcut }) if shortcut == "missing"));
+        
+        // Only the exact command is expanded
+        let message = service
+            .create_message_with_deduplication("/cannedgreeting".to_string(), room_id, user_id, Uuid::new_v4())
+            .await
+            .unwrap();
+        assert_eq!(message.content, "/cannedgreeting");
+    }
+    
     #[tokio::test]
     async fn test_get_room_messages() {
         let service = create_test_message_service().await;
@@ -686,4 +2086,25 @@ mod tests {
         // Should be limited (though empty in this test)
         assert!(messages.len() <= 100);
     }
+    
+    #[tokio::test]
+    async fn test_configured_page_size_above_default_cap() {
+        let service = create_test_message_service().await.with_max_page_size(200);
+        let (user_id, room_id) = create_test_user_and_room(&service.db).await;
+        
+        let start = chrono::Utc::now() - chrono::Duration::hours(1);
+        for i in 0..150 {
+            let mut message = Message::new(room_id, user_id, format!("Message {}", i), Uuid::new_v4());
+            message.created_at = start + chrono::Duration::seconds(i);
+            service.db.create_message_with_deduplication(message).await.unwrap();
+        }
+        
+        let messages = service.get_room_messages(room_id, user_id, 150, None).await.unwrap();
+        assert_eq!(messages.len(), 150);
+        
+        // The cap still applies above it
+        let service = service.with_max_page_size(120);
+        let messages = service.get_room_messages(room_id, user_id, 1000, None).await.unwrap();
+        assert_eq!(messages.len(), 120);
+    }
 }
\ No newline at end of file
@@ -14,9 +14,20 @@ pub mod cached_room;
 pub mod cached_message;
 pub mod cached_search;
 pub mod cache_manager;
+pub mod features;
+pub mod webhooks;
+pub mod bridge;
+pub mod webhook_policy;
+pub mod delivery;
+pub mod export;
+pub mod mailer;
+pub mod scheduler;
+pub mod tokens;
+pub mod moderation;
+pub mod sliding_window;
 
 pub use auth::AuthService;
-pub use message::{MessageService, MessageServiceTrait};
+pub use message::{MessageService, MessageServiceTrait, MessageRateLimiter};
 pub use room::RoomService;
 pub use connection::ConnectionManager;
 pub use search::{SearchService, SearchServiceTrait};
@@ -30,4 +41,12 @@ pub use cached_auth::CachedAuthService;
 pub use cached_room::CachedRoomService;
 pub use cached_message::CachedMessageService;
 pub use cached_search::CachedSearchService;
-pub use cache_manager::{CacheManager, CacheManagerFactory, CacheHealthStatus, CacheHealth};
\ No newline at end of file
+pub use webhooks::RoomWebhookService;
+pub use bridge::RoomBridgeService;
+pub use webhook_policy::WebhookUrlPolicy;
+pub use delivery::WebhookDelivery;
+pub use export::ExportService;
+pub use scheduler::Scheduler;
+pub use tokens::TokenService;
+pub use moderation::{ModerationGate, ModerationHook, ModerationVerdict};
+pub use cache_manager::{CacheManager, CacheManagerFactory, CacheHealthStatus, CacheHealth};
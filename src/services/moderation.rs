@@ -0,0 +1,148 @@
+//! Pre-store hooks that let an external moderation or DLP service review
+//! new messages
+//!
+//! A hook sees the text as the user wrote it and answers allow, redact (post
+//! this text instead) or block. [`ModerationGate`] bounds each review with a
+//! timeout; when the hook fails or times out it follows the configured
+//! failure policy, posting the message unchanged (fail-open) or rejecting it
+//! (fail-closed).
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::config::{ModerationConfig, ModerationFailurePolicy};
+use crate::errors::{MessageError, ModerationError};
+use crate::models::{RoomId, UserId};
+
+/// What a hook is asked to review
+#[derive(Debug, Clone, Serialize)]
+pub struct ModerationRequest<'a> {
+    pub room_id: RoomId,
+    pub user_id: UserId,
+    pub content: &'a str,
+}
+
+/// A hook's decision on a message
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "verdict", rename_all = "snake_case")]
+pub enum ModerationVerdict {
+    Allow,
+    /// Post `content` in place of what the user wrote
+    Redact { content: String },
+    /// Reject the message; `reason` is shown to the sender
+    Block {
+        #[serde(default)]
+        reason: Option<String>,
+    },
+}
+
+#[async_trait]
+pub trait ModerationHook: Send + Sync {
+    async fn review(&self, request: &ModerationRequest<'_>) -> Result<ModerationVerdict, ModerationError>;
+}
+
+/// Posts each message as JSON to a moderation service, which answers with
+/// a verdict, e.g. `{"verdict": "block", "reason": "Contains a card number"}`
+pub struct WebhookModerationHook {
+    http_client: Client,
+    url: String,
+}
+
+impl WebhookModerationHook {
+    pub fn new(url: String) -> Self {
+        Self {
+            http_client: Client::new(),
+            url,
+        }
+    }
+}
+
+#[async_trait]
+impl ModerationHook for WebhookModerationHook {
+    async fn review(&self, request: &ModerationRequest<'_>) -> Result<ModerationVerdict, ModerationError> {
+        let response = self.http_client
+            .post(&self.url)
+            .json(request)
+            .send()
+            .await
+            .map_err(|e| ModerationError::Request(e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ModerationError::Status { status: status.as_u16() });
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| ModerationError::InvalidVerdict(e.to_string()))
+    }
+}
+
+/// A hook plus the timeout and failure policy it runs under
+#[derive(Clone)]
+pub struct ModerationGate {
+    hook: Arc<dyn ModerationHook>,
+    timeout: Duration,
+    failure_policy: ModerationFailurePolicy,
+}
+
+impl ModerationGate {
+    pub fn new(hook: Arc<dyn ModerationHook>, timeout: Duration, failure_policy: ModerationFailurePolicy) -> Self {
+        Self {
+            hook,
+            timeout,
+            failure_policy,
+        }
+    }
+
+    pub fn from_config(config: &ModerationConfig) -> Self {
+        Self::new(
+            Arc::new(WebhookModerationHook::new(config.webhook_url.clone())),
+            Duration::from_millis(config.timeout_ms),
+            config.failure_policy,
+        )
+    }
+
+    /// Reviews a message about to be posted, returning replacement content
+    /// when the hook redacts it
+    pub async fn check(&self, request: ModerationRequest<'_>) -> Result<Option<String>, MessageError> {
+        let verdict = match tokio::time::timeout(self.timeout, self.hook.review(&request)).await {
+            Ok(Ok(verdict)) => verdict,
+            Ok(Err(e)) => return self.on_failure(&request, &e.to_string()),
+            Err(_) => return self.on_failure(&request, "timed out"),
+        };
+
+        match verdict {
+            ModerationVerdict::Allow => Ok(None),
+            ModerationVerdict::Redact { content } => {
+                metrics::counter!("messages_redacted_by_moderation_total", 1);
+                Ok(Some(content))
+            }
+            ModerationVerdict::Block { reason } => {
+                metrics::counter!("messages_blocked_by_moderation_total", 1);
+                Err(MessageError::Blocked { reason })
+            }
+        }
+    }
+
+    fn on_failure(&self, request: &ModerationRequest<'_>, error: &str) -> Result<Option<String>, MessageError> {
+        metrics::counter!("moderation_hook_failures_total", 1);
+        warn!(
+            error_code = "MODERATION_UNAVAILABLE",
+            room_id = %request.room_id,
+            user_id = %request.user_id,
+            fail_open = self.failure_policy == ModerationFailurePolicy::FailOpen,
+            "Moderation hook failed: {}",
+            error
+        );
+        match self.failure_policy {
+            ModerationFailurePolicy::FailOpen => Ok(None),
+            ModerationFailurePolicy::FailClosed => Err(MessageError::ModerationUnavailable),
+        }
+    }
+}
@@ -7,7 +7,7 @@ use dashmap::DashMap;
 use tracing::{debug, info, warn, error};
 
 use crate::errors::{ConnectionError, BroadcastError};
-use crate::models::{ConnectionId, MessageId, RoomId, UserId, WebSocketMessage};
+use crate::models::{ConnectionId, MessageId, RoomId, UserId, WebSocketMessage, WS_LEGACY_PROTOCOL_VERSION};
 use crate::services::ConnectionManager;
 use crate::metrics::get_performance_monitor;
 
@@ -50,6 +50,7 @@ struct ConnectionInfo {
     connected_at: Instant,
     last_activity: Instant,
     room_subscriptions: Vec<RoomId>,
+    protocol_version: u32,
 }
 
 #[derive(Debug, Clone)]
@@ -330,7 +331,7 @@ impl OptimizedConnectionManager {
     }
     
     /// Get connections for users in a room
-    fn get_room_connections(&self, room_id: RoomId) -> Vec<(ConnectionId, WebSocketSender)> {
+    fn get_room_connections(&self, room_id: RoomId, min_protocol_version: u32) -> Vec<(ConnectionId, WebSocketSender)> {
         let mut room_connections = Vec::new();
         
         if let Some(users) = self.room_users.get(&room_id) {
@@ -338,7 +339,9 @@ impl OptimizedConnectionManager {
                 if let Some(connection_ids) = self.user_connections.get(&user_id) {
                     for &connection_id in connection_ids.iter() {
                         if let Some(info) = self.connections.get(&connection_id) {
-                            room_connections.push((connection_id, info.sender.clone()));
+                            if info.protocol_version >= min_protocol_version {
+                                room_connections.push((connection_id, info.sender.clone()));
+                            }
                         }
                     }
                 }
@@ -366,6 +369,16 @@ impl OptimizedConnectionManager {
             WebSocketMessage::TypingIndicator { .. } => 5u8,
             WebSocketMessage::PresenceUpdate { .. } => 6u8,
             WebSocketMessage::SoundPlayback { .. } => 7u8,
+            WebSocketMessage::RoomUpdated { .. } => 8u8,
+            WebSocketMessage::RoomArchived { .. } => 9u8,
+            WebSocketMessage::PostPermissionChanged { .. } => 10u8,
+            WebSocketMessage::Capabilities { .. } => 11u8,
+            WebSocketMessage::ServerShutdown { .. } => 12u8,
+            WebSocketMessage::RoomMembershipChanged { .. } => 13u8,
+            WebSocketMessage::RoomDeleted { .. } => 14u8,
+            WebSocketMessage::TypingSummary { .. } => 15u8,
+            WebSocketMessage::Impersonation { .. } => 16u8,
+            WebSocketMessage::Welcome { .. } => 17u8,
         };
         
         let cache_key = format!("{}:{}", 
@@ -374,6 +387,14 @@ impl OptimizedConnectionManager {
                 WebSocketMessage::NewMessage { message } => message.id.0.to_string(),
                 WebSocketMessage::PresenceUpdate { room_id, .. } => room_id.0.to_string(),
                 WebSocketMessage::TypingIndicator { room_id, .. } => room_id.0.to_string(),
+                WebSocketMessage::RoomUpdated { room } => format!("{}:{:?}", room.id.0, room.room_type),
+                WebSocketMessage::RoomArchived { room_id } => room_id.0.to_string(),
+                WebSocketMessage::TypingSummary { room_id, user_ids, others } => {
+                    format!("{}:{:?}:{}", room_id.0, user_ids, others)
+                }
+                WebSocketMessage::PostPermissionChanged { room_id, post_permission } => {
+                    format!("{}:{}", room_id.0, post_permission.as_str())
+                }
                 _ => "generic".to_string(),
             }
         );
@@ -383,7 +404,7 @@ impl OptimizedConnectionManager {
         let serialized = String::from_utf8_lossy(&serialized_data);
         
         // Get room connections
-        let room_connections = self.get_room_connections(room_id);
+        let room_connections = self.get_room_connections(room_id, message.min_protocol_version());
         
         if room_connections.is_empty() {
             return Err(BroadcastError::NoConnections { room_id });
@@ -474,6 +495,7 @@ impl ConnectionManager for OptimizedConnectionManager {
             connected_at: now,
             last_activity: now,
             room_subscriptions: Vec::new(),
+            protocol_version: WS_LEGACY_PROTOCOL_VERSION,
         };
         
         self.connections.insert(connection_id, connection_info);
@@ -666,10 +688,70 @@ impl ConnectionManager for OptimizedConnectionManager {
         let presence_msg = WebSocketMessage::PresenceUpdate {
             room_id,
             online_users,
+            last_seen: HashMap::new(),
         };
         
         self.broadcast_to_room(room_id, presence_msg).await
     }
+    
+    async fn disconnect_user(
+        &self,
+        user_id: UserId,
+    ) -> Result<usize, ConnectionError> {
+        let connection_ids = self.user_connections
+            .get(&user_id)
+            .map(|ids| ids.value().clone())
+            .unwrap_or_default();
+        
+        let mut removed = 0;
+        for connection_id in connection_ids {
+            if self.remove_connection(connection_id).await.is_ok() {
+                removed += 1;
+            }
+        }
+        
+        Ok(removed)
+    }
+    
+    async fn set_protocol_version(
+        &self,
+        connection_id: ConnectionId,
+        protocol_version: u32,
+    ) -> Result<(), ConnectionError> {
+        let mut info = self.connections.get_mut(&connection_id)
+            .ok_or(ConnectionError::NotFound { connection_id })?;
+        info.protocol_version = protocol_version;
+        Ok(())
+    }
+    
+    async fn drain_connections(
+        &self,
+        reconnect_after: Duration,
+        timeout: Duration,
+    ) -> Result<usize, ConnectionError> {
+        let frame = WebSocketMessage::ServerShutdown { reconnect_after: reconnect_after.as_secs() };
+        let serialized = serde_json::to_string(&frame)
+            .map_err(|e| ConnectionError::Protocol(e.to_string()))?;
+        
+        let mut closed = 0;
+        for mut info in self.connections.iter_mut() {
+            if info.protocol_version >= frame.min_protocol_version() {
+                let _ = info.sender.send(serialized.clone());
+            }
+            // The handler removes the connection once its socket has closed
+            info.sender = mpsc::unbounded_channel().0;
+            closed += 1;
+        }
+        
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline && !self.connections.is_empty() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        
+        info!("Drained {} WebSocket connection(s) for shutdown", closed);
+        
+        Ok(closed)
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -736,6 +818,7 @@ mod tests {
         let message = WebSocketMessage::PresenceUpdate {
             room_id,
             online_users: vec![UserId::new()],
+            last_seen: HashMap::new(),
         };
         
         // First broadcast should miss cache
@@ -1,10 +1,18 @@
+use crate::config::NotificationSounds;
 use crate::database::{CampfireDatabase, DatabaseWriter};
 use crate::errors::PushNotificationError;
 use crate::models::*;
 use crate::validation::CreatePushSubscriptionRequest;
+use crate::rich_text::DEFAULT_MAX_MENTIONS;
 use async_trait::async_trait;
 use chrono::Utc;
-use std::sync::Arc;
+use metrics::{counter, gauge};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
 use web_push::{
     WebPushClient, WebPushMessageBuilder, VapidSignatureBuilder, SubscriptionInfo,
 };
@@ -63,6 +71,11 @@ pub trait PushNotificationService: Send + Sync {
         room: &Room,
         triggered_by_name: &str,
     ) -> Result<(), PushNotificationError>;
+    
+    /// Push requests currently being sent
+    fn in_flight(&self) -> usize {
+        0
+    }
 }
 
 /// VAPID configuration for Web Push
@@ -84,12 +97,137 @@ impl Default for VapidConfig {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+struct EndpointBackoff {
+    failures: u32,
+    retry_at: Instant,
+}
+
+/// Bounds how many push requests are in flight and backs off failing endpoints
+///
+/// A fan-out to thousands of subscriptions would otherwise open a connection
+/// per subscription at once. Sends beyond the limit wait for a permit. An
+/// endpoint that fails is skipped until its backoff expires; the backoff
+/// doubles with each consecutive failure, up to the configured maximum, and
+/// resets on the first success.
+pub struct PushDispatcher {
+    permits: Semaphore,
+    in_flight: AtomicUsize,
+    backoff_base: Duration,
+    backoff_max: Duration,
+    backoff: Mutex<HashMap<String, EndpointBackoff>>,
+}
+
+impl PushDispatcher {
+    pub fn new(max_concurrent: usize, backoff_base: Duration, backoff_max: Duration) -> Self {
+        Self {
+            permits: Semaphore::new(max_concurrent),
+            in_flight: AtomicUsize::new(0),
+            backoff_base,
+            backoff_max,
+            backoff: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_config(config: &crate::config::PushConfig) -> Self {
+        Self::new(
+            config.max_concurrent_sends,
+            Duration::from_secs(config.backoff_base_secs),
+            Duration::from_secs(config.backoff_max_secs),
+        )
+    }
+
+    /// Push requests currently being sent
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Whether sends to `endpoint` are currently being skipped
+    pub fn is_backing_off(&self, endpoint: &str) -> bool {
+        self.backoff
+            .lock()
+            .unwrap()
+            .get(endpoint)
+            .is_some_and(|backoff| backoff.retry_at > Instant::now())
+    }
+
+    /// Sends every job through `send`, at most `max_concurrent` at a time,
+    /// returning how many succeeded
+    pub async fn dispatch<T, F, Fut>(&self, jobs: Vec<(PushSubscription, T)>, send: F) -> usize
+    where
+        F: Fn(PushSubscription, T) -> Fut,
+        Fut: Future<Output = Result<(), PushNotificationError>>,
+    {
+        let sends = jobs.into_iter().map(|(subscription, job)| {
+            let send = &send;
+            async move {
+                if self.is_backing_off(&subscription.endpoint) {
+                    counter!("push_notifications_backoff_skipped_total", 1);
+                    return false;
+                }
+
+                let _permit = match self.permits.acquire().await {
+                    Ok(permit) => permit,
+                    Err(_) => return false,
+                };
+                let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                gauge!("push_notifications_in_flight", in_flight as f64);
+
+                let endpoint = subscription.endpoint.clone();
+                let subscription_id = subscription.id;
+                let result = send(subscription, job).await;
+
+                let in_flight = self.in_flight.fetch_sub(1, Ordering::SeqCst) - 1;
+                gauge!("push_notifications_in_flight", in_flight as f64);
+                crate::metrics::record_push_notification(result.is_ok());
+
+                match result {
+                    Ok(()) => {
+                        self.backoff.lock().unwrap().remove(&endpoint);
+                        true
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to send push notification to subscription {}: {:?}", subscription_id, e);
+                        self.record_failure(endpoint);
+                        false
+                    }
+                }
+            }
+        });
+
+        futures_util::future::join_all(sends)
+            .await
+            .into_iter()
+            .filter(|delivered| *delivered)
+            .count()
+    }
+
+    fn record_failure(&self, endpoint: String) {
+        let mut backoff = self.backoff.lock().unwrap();
+        let failures = backoff.get(&endpoint).map_or(0, |b| b.failures) + 1;
+        let delay = self
+            .backoff_base
+            .saturating_mul(2u32.saturating_pow(failures - 1))
+            .min(self.backoff_max);
+        backoff.insert(endpoint, EndpointBackoff { failures, retry_at: Instant::now() + delay });
+    }
+}
+
+impl Default for PushDispatcher {
+    fn default() -> Self {
+        Self::new(32, Duration::from_secs(30), Duration::from_secs(3600))
+    }
+}
+
 /// Push notification service implementation
 pub struct PushNotificationServiceImpl {
     database: CampfireDatabase,
     writer: Arc<dyn DatabaseWriter>,
     vapid_config: VapidConfig,
     client: WebPushClient,
+    dispatcher: PushDispatcher,
+    max_mentions: usize,
+    sounds: NotificationSounds,
 }
 
 impl PushNotificationServiceImpl {
@@ -105,9 +243,39 @@ impl PushNotificationServiceImpl {
             writer,
             vapid_config,
             client,
+            dispatcher: PushDispatcher::default(),
+            max_mentions: DEFAULT_MAX_MENTIONS,
+            sounds: NotificationSounds::default(),
         }
     }
     
+    /// Sound hint attached to each notification type
+    pub fn with_sounds(mut self, sounds: NotificationSounds) -> Self {
+        self.sounds = sounds;
+        self
+    }
+    
+    /// Mentioned users notified per message; further mentions are ignored
+    pub fn with_max_mentions(mut self, max_mentions: usize) -> Self {
+        self.max_mentions = max_mentions;
+        self
+    }
+    
+    /// Replaces the default concurrency limit and backoff
+    pub fn with_dispatcher(mut self, dispatcher: PushDispatcher) -> Self {
+        self.dispatcher = dispatcher;
+        self
+    }
+    
+    /// Sends each payload to its subscription through the dispatcher
+    async fn deliver(&self, jobs: Vec<(PushSubscription, PushNotificationPayload)>) {
+        self.dispatcher
+            .dispatch(jobs, |subscription, payload| async move {
+                self.send_push_notification(&subscription, &payload).await
+            })
+            .await;
+    }
+    
     /// Send a push notification to a specific subscription
     async fn send_push_notification(
         &self,
@@ -173,13 +341,15 @@ impl PushNotificationServiceImpl {
         }
     }
     
-    /// Create notification payload based on type and context
-    fn create_notification_payload(
+    /// Create notification payload based on type and context, with the
+    /// type's sound hint unless the recipient turned sounds off
+    pub fn create_notification_payload(
         &self,
         notification_type: NotificationType,
         message: &Message,
         room: &Room,
         sender_name: &str,
+        sounds_enabled: bool,
     ) -> PushNotificationPayload {
         let (title, body) = match notification_type {
             NotificationType::DirectMessage => (
@@ -200,6 +370,7 @@ impl PushNotificationServiceImpl {
             ),
         };
         
+        let sound = self.sounds.for_type(&notification_type).filter(|_| sounds_enabled);
         let data = serde_json::json!({
             "messageId": message.id,
             "roomId": message.room_id,
@@ -210,6 +381,7 @@ impl PushNotificationServiceImpl {
                 NotificationType::NewMessage => "new_message",
                 NotificationType::SoundPlayback => "sound_playback",
             },
+            "sound": sound,
             "timestamp": message.created_at,
         });
         
@@ -230,6 +402,10 @@ impl PushNotificationServiceImpl {
 
 #[async_trait]
 impl PushNotificationService for PushNotificationServiceImpl {
+    fn in_flight(&self) -> usize {
+        self.dispatcher.in_flight()
+    }
+    
     async fn create_subscription(
         &self,
         user_id: UserId,
@@ -279,6 +455,9 @@ impl PushNotificationService for PushNotificationServiceImpl {
         if let Some(sounds_enabled) = request.sounds_enabled {
             preferences.sounds_enabled = sounds_enabled;
         }
+        if let Some(room_added_email_enabled) = request.room_added_email_enabled {
+            preferences.room_added_email_enabled = room_added_email_enabled;
+        }
         
         preferences.updated_at = Utc::now();
         
@@ -301,7 +480,8 @@ impl PushNotificationService for PushNotificationServiceImpl {
         sender_name: &str,
     ) -> Result<(), PushNotificationError> {
         // Get users who should receive notifications
-        let recipients = self.database.get_notification_recipients(message, room).await?;
+        let recipients = self.database.get_notification_recipients(message, room, self.max_mentions).await?;
+        let mut jobs = Vec::new();
         
         for (user_id, preferences) in recipients {
             // Skip if user has disabled relevant notifications
@@ -330,21 +510,14 @@ impl PushNotificationService for PushNotificationServiceImpl {
                 message,
                 room,
                 sender_name,
+                preferences.sounds_enabled,
             );
             
-            // Send to all subscriptions for this user
-            for subscription in subscriptions {
-                if let Err(e) = self.send_push_notification(&subscription, &payload).await {
-                    tracing::warn!(
-                        "Failed to send push notification to subscription {}: {:?}",
-                        subscription.id,
-                        e
-                    );
-                    // Continue with other subscriptions
-                }
-            }
+            jobs.extend(subscriptions.into_iter().map(|subscription| (subscription, payload.clone())));
         }
         
+        self.deliver(jobs).await;
+        
         Ok(())
     }
     
@@ -375,18 +548,11 @@ impl PushNotificationService for PushNotificationServiceImpl {
             message,
             room,
             sender_name,
+            preferences.sounds_enabled,
         );
         
-        // Send to all subscriptions for this user
-        for subscription in subscriptions {
-            if let Err(e) = self.send_push_notification(&subscription, &payload).await {
-                tracing::warn!(
-                    "Failed to send mention notification to subscription {}: {:?}",
-                    subscription.id,
-                    e
-                );
-            }
-        }
+        let jobs = subscriptions.into_iter().map(|subscription| (subscription, payload.clone())).collect();
+        self.deliver(jobs).await;
         
         Ok(())
     }
@@ -410,6 +576,7 @@ impl PushNotificationService for PushNotificationServiceImpl {
         .bind(room.id.0.to_string())
         .fetch_all(self.database.pool())
         .await?;
+        let mut jobs = Vec::new();
         
         for row in rows {
             let user_id_str: &str = row.get("user_id");
@@ -433,23 +600,17 @@ impl PushNotificationService for PushNotificationServiceImpl {
                     "roomId": room.id,
                     "type": "sound_playback",
                     "soundName": sound_name,
+                    "sound": self.sounds.for_type(&NotificationType::SoundPlayback),
                     "triggeredBy": triggered_by_name,
                     "timestamp": Utc::now(),
                 }),
             };
             
-            // Send to all subscriptions for this user
-            for subscription in subscriptions {
-                if let Err(e) = self.send_push_notification(&subscription, &payload).await {
-                    tracing::warn!(
-                        "Failed to send sound notification to subscription {}: {:?}",
-                        subscription.id,
-                        e
-                    );
-                }
-            }
+            jobs.extend(subscriptions.into_iter().map(|subscription| (subscription, payload.clone())));
         }
         
+        self.deliver(jobs).await;
+        
         Ok(())
     }
 }
\ No newline at end of file
@@ -1,16 +1,28 @@
 use async_trait::async_trait;
 use chrono::Utc;
+use sha2::{Digest, Sha256};
 use std::sync::Arc;
+use std::time::Duration;
 
+use crate::config::TextLimits;
 use crate::database::CampfireDatabase;
 use crate::errors::RoomError;
-use crate::models::{Room, RoomId, RoomType, UserId, InvolvementLevel, Membership};
+use crate::events::{BroadcastSubscriber, DomainEvent, EventBus};
+use crate::models::{
+    CannedResponse, DirectConversation, DIRECT_ROOM_MAX_MEMBERS, MentionCandidate, Room, RoomId, RoomListEntry, RoomPermissions, RoomType, UserId, InvolvementLevel, Membership,
+    PostPermission, WebSocketMessage,
+};
+use crate::services::connection::ConnectionManager;
+use crate::services::sliding_window::SlidingWindowLimiter;
+use crate::sounds::{SoundManager, SoundPolicy};
+use crate::storage::content_types;
+use crate::validation::{normalize_optional_text, normalize_text};
 
 /// Room Service trait defining the contract for room management operations
 /// 
 /// # Preconditions
 /// - All user IDs must reference existing users in the database
-/// - Room names must be 1-100 characters long
+/// - Room names must be 1 to `TextLimits::room_name` graphemes long
 /// - Authorization checks must be performed before operations
 /// 
 /// # Postconditions  
@@ -24,6 +36,7 @@ use crate::models::{Room, RoomId, RoomType, UserId, InvolvementLevel, Membership
 /// - RoomError::NotAuthorized if user lacks permissions
 /// - RoomError::AlreadyMember if user is already a member
 /// - RoomError::InvalidName if room name is invalid
+/// - RoomError::InvalidTypeChange if a room can't take the requested type
 /// - RoomError::Database on persistence failure
 #[async_trait]
 pub trait RoomServiceTrait: Send + Sync {
@@ -45,6 +58,35 @@ pub trait RoomServiceTrait: Send + Sync {
         involvement_level: InvolvementLevel,
     ) -> Result<(), RoomError>;
     
+    /// Removes a user from a room; only room and site admins may. Removing
+    /// someone who isn't a member succeeds without doing anything.
+    async fn remove_member(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+        removed_by: UserId,
+    ) -> Result<(), RoomError>;
+    
+    /// Deletes a room and everything in it for good. Site admins only; a
+    /// call without the right `confirmation` fails with
+    /// `ConfirmationRequired`, carrying the token to repeat it with.
+    async fn delete_room_permanently(
+        &self,
+        room_id: RoomId,
+        deleted_by: UserId,
+        confirmation: Option<String>,
+    ) -> Result<(), RoomError>;
+    
+    /// Overrides the global message retention for one room (site admins
+    /// only). None inherits the global setting; 0 is a legal hold and the
+    /// room's messages are never purged.
+    async fn set_retention(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        retention_days: Option<u32>,
+    ) -> Result<(), RoomError>;
+    
     /// Checks if user has access to room and returns involvement level
     async fn check_room_access(
         &self,
@@ -58,21 +100,342 @@ pub trait RoomServiceTrait: Send + Sync {
         user_id: UserId,
     ) -> Result<Vec<Room>, RoomError>;
     
+    /// Gets the user's rooms as `get_user_rooms` does, each with a preview
+    /// of its last message
+    async fn get_room_list(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<RoomListEntry>, RoomError>;
+    
+    /// Lists the user's direct rooms with the other participant and unread
+    /// count, most recently active first
+    async fn get_direct_conversations(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<DirectConversation>, RoomError>;
+    
+    /// Autocomplete candidates for `@query` in a room: members whose name or
+    /// username starts with the query, plus `@room` / `@here` when room-wide
+    /// mentions are enabled. The caller must have access to the room.
+    async fn get_mentionable(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+        query: &str,
+    ) -> Result<Vec<MentionCandidate>, RoomError>;
+    
     /// Gets a room by ID
     async fn get_room_by_id(
         &self,
         room_id: RoomId,
     ) -> Result<Option<Room>, RoomError>;
+    
+    /// Converts a room between open and closed, broadcasting RoomUpdated
+    /// 
+    /// Only room admins (or site admins) may convert. Explicit memberships are
+    /// kept as-is, so closing an open room keeps its members; people who only
+    /// had implicit access to the open room lose it. Direct rooms can't be
+    /// converted, and nothing can be converted into one.
+    async fn change_room_type(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        new_type: RoomType,
+    ) -> Result<Room, RoomError>;
+    
+    /// Returns the open room called `name` (case-insensitive, leading `#`
+    /// ignored), creating it with the user as admin if there isn't one
+    /// 
+    /// Idempotent: repeated or concurrent calls with the same name resolve to
+    /// the same room. The bool is true when this call created it.
+    async fn resolve_or_create_room(
+        &self,
+        name: String,
+        user_id: UserId,
+    ) -> Result<(Room, bool), RoomError>;
+    
+    /// Computes what the user may do in the room from membership and room type
+    /// 
+    /// Mirrors the checks the mutating operations perform, so a `true` here
+    /// means the corresponding request will be authorized.
+    async fn get_permissions(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+    ) -> Result<RoomPermissions, RoomError>;
+    
+    /// Sets who may post in the room, broadcasting PostPermissionChanged
+    /// 
+    /// Only room admins (or site admins) may change it.
+    async fn set_post_permission(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        post_permission: PostPermission,
+    ) -> Result<(), RoomError>;
+    
+    /// Makes an open room readable without signing in, or takes it back
+    /// 
+    /// Only room admins (or site admins) may change it, and only open rooms
+    /// can be public. Closing a room clears the flag.
+    async fn set_public(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        public: bool,
+    ) -> Result<(), RoomError>;
+    
+    /// Sets the room's own sound cooldown in seconds, or with None goes back
+    /// to the configured default. Only room admins (or site admins) may.
+    async fn set_sound_cooldown(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        cooldown_secs: Option<u64>,
+    ) -> Result<(), RoomError>;
+    
+    /// Sets which sounds `/play` may trigger in the room. Allowlisted names
+    /// must be real sounds. Only room admins (or site admins) may.
+    async fn set_sound_policy(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        policy: SoundPolicy,
+    ) -> Result<(), RoomError>;
+    
+    /// Creates or replaces the room's canned response for `shortcut`. Only
+    /// room admins (or site admins) may.
+    async fn set_canned_response(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        shortcut: &str,
+        content: String,
+    ) -> Result<(), RoomError>;
+    
+    /// Removes one of the room's canned responses. Admins only.
+    async fn delete_canned_response(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        shortcut: &str,
+    ) -> Result<(), RoomError>;
+    
+    /// The room's canned responses whose shortcut starts with `prefix`, for
+    /// autocomplete. The caller must have access to the room.
+    async fn get_canned_responses(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+        prefix: &str,
+    ) -> Result<Vec<CannedResponse>, RoomError>;
+    
+    /// Sets the content types attachments in the room may have, or with
+    /// None goes back to the configured allowlist. Only room admins (or
+    /// site admins) may.
+    async fn set_attachment_types(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        types: Option<Vec<String>>,
+    ) -> Result<(), RoomError>;
+    
+    /// Limits push notifications in the room to messages that mention
+    /// someone (or `@room`), overriding members' all-messages preference.
+    /// Meant for large rooms. Only room admins (or site admins) may.
+    async fn set_notify_mentions_only(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        mentions_only: bool,
+    ) -> Result<(), RoomError>;
+    
+    /// Grants or revokes a user's right to post in an admins-only room.
+    /// Bots can only post in such rooms with a grant. Admins only.
+    async fn set_post_grant(
+        &self,
+        room_id: RoomId,
+        granted_by: UserId,
+        user_id: UserId,
+        granted: bool,
+    ) -> Result<(), RoomError>;
+    
+    /// Whether the room's post permission lets the user post. Room access is
+    /// checked separately; this is cheap for rooms anyone may post in.
+    async fn check_post_permission(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+    ) -> Result<bool, RoomError>;
+}
+
+/// Characters of the last message shown in the room list
+pub const DEFAULT_ROOM_PREVIEW_LENGTH: usize = 80;
+
+/// Mention autocomplete candidates returned when not configured
+pub const DEFAULT_MENTION_AUTOCOMPLETE_LIMIT: u32 = 10;
+
+/// Longest shortcut a canned response may have
+pub const MAX_CANNED_SHORTCUT_LENGTH: usize = 32;
+
+/// Canned responses post as messages, so they share the message length cap
+pub const MAX_CANNED_RESPONSE_LENGTH: usize = 10000;
+
+/// Lowercases a canned response shortcut and checks it's made of letters,
+/// digits, `-` and `_`
+pub fn normalize_canned_shortcut(shortcut: &str) -> Result<String, RoomError> {
+    let shortcut = shortcut.trim().to_lowercase();
+    let valid = !shortcut.is_empty()
+        && shortcut.len() <= MAX_CANNED_SHORTCUT_LENGTH
+        && shortcut.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(RoomError::InvalidCannedResponse {
+            reason: format!(
+                "shortcut must be 1-{} letters, digits, '-' or '_'",
+                MAX_CANNED_SHORTCUT_LENGTH
+            ),
+        });
+    }
+    Ok(shortcut)
+}
+
+/// Synthetic mentions that address the whole room
+const ROOM_WIDE_MENTIONS: &[(&str, &str)] = &[
+    ("room", "Everyone in this room"),
+    ("here", "Everyone online in this room"),
+];
+
+/// Per-user limit on how fast open rooms are joined
+///
+/// Complements any cap on total memberships by bounding the rate: a script
+/// can't join someone to thousands of open rooms at once. Sliding window of
+/// one minute.
+pub struct JoinRateLimiter {
+    window: SlidingWindowLimiter<UserId>,
+}
+
+impl JoinRateLimiter {
+    pub fn new(joins_per_minute: u32) -> Self {
+        Self {
+            window: SlidingWindowLimiter::new(joins_per_minute, Duration::from_secs(60)),
+        }
+    }
+    
+    /// Records a join for the user, or returns how long until one is allowed
+    fn try_acquire(&self, user_id: UserId) -> Result<(), Duration> {
+        self.window.try_acquire(user_id).map_err(|full| full.retry_after)
+    }
 }
 
 #[derive(Clone)]
 pub struct RoomService {
     db: Arc<CampfireDatabase>,
+    connection_manager: Option<Arc<dyn ConnectionManager>>,
+    events: EventBus,
+    preview_length: usize,
+    mention_limit: u32,
+    room_mentions: bool,
+    text_limits: TextLimits,
+    join_limiter: Option<Arc<JoinRateLimiter>>,
+    /// 0 = unlimited
+    max_canned_responses: u32,
 }
 
 impl RoomService {
     pub fn new(db: Arc<CampfireDatabase>) -> Self {
-        Self { db }
+        Self {
+            db,
+            connection_manager: None,
+            events: EventBus::new(),
+            preview_length: DEFAULT_ROOM_PREVIEW_LENGTH,
+            mention_limit: DEFAULT_MENTION_AUTOCOMPLETE_LIMIT,
+            room_mentions: true,
+            text_limits: TextLimits::default(),
+            join_limiter: None,
+            max_canned_responses: 0,
+        }
+    }
+    
+    pub fn with_connection_manager(
+        db: Arc<CampfireDatabase>,
+        connection_manager: Arc<dyn ConnectionManager>,
+    ) -> Self {
+        let events = EventBus::new();
+        events.subscribe(Arc::new(BroadcastSubscriber::new(connection_manager.clone())));
+        
+        Self {
+            db,
+            connection_manager: Some(connection_manager),
+            events,
+            preview_length: DEFAULT_ROOM_PREVIEW_LENGTH,
+            mention_limit: DEFAULT_MENTION_AUTOCOMPLETE_LIMIT,
+            room_mentions: true,
+            text_limits: TextLimits::default(),
+            join_limiter: None,
+            max_canned_responses: 0,
+        }
+    }
+    
+    /// Characters of the last message shown in the room list (0 = no previews)
+    pub fn with_preview_length(mut self, preview_length: usize) -> Self {
+        self.preview_length = preview_length;
+        self
+    }
+    
+    /// How many `@` autocomplete candidates to return, and whether `@room` /
+    /// `@here` are offered
+    pub fn with_mention_autocomplete(mut self, limit: u32, room_mentions: bool) -> Self {
+        self.mention_limit = limit;
+        self.room_mentions = room_mentions;
+        self
+    }
+    
+    /// Longest room names and topics accepted
+    pub fn with_text_limits(mut self, text_limits: TextLimits) -> Self {
+        self.text_limits = text_limits;
+        self
+    }
+    
+    /// Canned responses each room may have (0 = unlimited)
+    pub fn with_canned_response_limit(mut self, max_canned_responses: u32) -> Self {
+        self.max_canned_responses = max_canned_responses;
+        self
+    }
+    
+    /// Open rooms a user may join per minute (0 = unlimited)
+    pub fn with_join_rate_limit(mut self, joins_per_minute: u32) -> Self {
+        self.join_limiter = (joins_per_minute > 0).then(|| Arc::new(JoinRateLimiter::new(joins_per_minute)));
+        self
+    }
+    
+    /// Enforces the open-room join rate for `user_id`, unless a site admin
+    /// is doing the adding
+    async fn check_join_rate(&self, user_id: UserId, added_by: UserId) -> Result<(), RoomError> {
+        let Some(limiter) = &self.join_limiter else {
+            return Ok(());
+        };
+        
+        let Err(retry_after) = limiter.try_acquire(user_id) else {
+            return Ok(());
+        };
+        
+        // Only look the adder up once the user is actually over the limit
+        if self.db.get_user_by_id(added_by).await?.is_some_and(|user| user.admin) {
+            return Ok(());
+        }
+        
+        metrics::counter!("room_joins_rate_limited_total", 1);
+        tracing::warn!("User {} exceeded the open room join rate", user_id);
+        
+        Err(RoomError::JoinRateLimit {
+            limit: limiter.window.limit(),
+            retry_after_secs: retry_after.as_secs().max(1),
+        })
+    }
+    
+    /// Bus that UserJoined and RoomArchived are emitted on
+    pub fn event_bus(&self) -> &EventBus {
+        &self.events
     }
     
     /// Get reference to the database for testing purposes
@@ -80,21 +443,50 @@ impl RoomService {
         &self.db
     }
     
-    /// Validates room name according to business rules
-    fn validate_room_name(name: &str) -> Result<(), RoomError> {
-        let trimmed = name.trim();
-        if trimmed.is_empty() {
-            return Err(RoomError::InvalidName {
-                reason: "Room name cannot be empty".to_string(),
-            });
+    /// Archives rooms with no messages for `idle_for`, emitting RoomArchived
+    /// for each. Direct rooms are never archived.
+    pub async fn archive_inactive_rooms(&self, idle_for: chrono::Duration) -> Result<Vec<RoomId>, RoomError> {
+        let archived = self.db.archive_inactive_rooms(Utc::now() - idle_for).await?;
+        
+        for room_id in &archived {
+            self.events.emit(DomainEvent::RoomArchived { room_id: *room_id }).await;
         }
-        if trimmed.len() > 100 {
-            return Err(RoomError::InvalidName {
-                reason: format!("Room name too long: {} chars (max: 100)", trimmed.len()),
-            });
+        
+        Ok(archived)
+    }
+    
+    /// Fails with NotAuthorized unless the user is a room admin or site admin
+    async fn require_admin(&self, room_id: RoomId, user_id: UserId) -> Result<(), RoomError> {
+        if self.db.get_room_by_id(room_id).await?.is_none() {
+            return Err(RoomError::NotFound { room_id });
+        }
+        
+        let is_room_admin = matches!(
+            self.db.get_membership(room_id, user_id).await?,
+            Some(Membership { involvement_level: InvolvementLevel::Admin, .. })
+        );
+        let is_site_admin = self.db.get_user_by_id(user_id).await?
+            .map(|user| user.admin)
+            .unwrap_or(false);
+        if !is_room_admin && !is_site_admin {
+            return Err(RoomError::NotAuthorized { user_id, room_id });
         }
+        
         Ok(())
     }
+    
+    /// Token an admin must echo back to delete a room. Derived from the room,
+    /// so it's stable between the two calls and useless for any other room.
+    fn deletion_token(room: &Room) -> String {
+        let digest = Sha256::digest(format!("delete-room:{}:{}", room.id, room.created_at.to_rfc3339()));
+        hex::encode(&digest[..8])
+    }
+    
+    /// Validates a room name and returns it with whitespace normalized
+    fn normalize_room_name(&self, name: &str) -> Result<String, RoomError> {
+        normalize_text("Room name", name, self.text_limits.room_name)
+            .map_err(|e| RoomError::InvalidName { reason: e.to_string() })
+    }
 }
 
 #[async_trait]
@@ -106,17 +498,9 @@ impl RoomServiceTrait for RoomService {
         room_type: RoomType,
         creator_id: UserId,
     ) -> Result<Room, RoomError> {
-        // Validate room name
-        Self::validate_room_name(&name)?;
-        
-        // Validate topic length if provided
-        if let Some(ref topic) = topic {
-            if topic.len() > 500 {
-                return Err(RoomError::InvalidName {
-                    reason: format!("Topic too long: {} chars (max: 500)", topic.len()),
-                });
-            }
-        }
+        let name = self.normalize_room_name(&name)?;
+        let topic = normalize_optional_text("Topic", topic.as_deref(), self.text_limits.room_topic)
+            .map_err(|e| RoomError::InvalidName { reason: e.to_string() })?;
         
         // Check if creator exists
         if !self.db.user_exists(creator_id).await? {
@@ -125,11 +509,20 @@ impl RoomServiceTrait for RoomService {
             ));
         }
         
+        // Open rooms are addressed by name, so their names are unique
+        if matches!(room_type, RoomType::Open)
+            && self.db.get_open_room_by_name(&name).await?.is_some()
+        {
+            return Err(RoomError::InvalidName {
+                reason: format!("An open room named '{}' already exists", name),
+            });
+        }
+        
         let now = Utc::now();
         let room = Room {
             id: RoomId::new(),
-            name: name.trim().to_string(),
-            topic: topic.map(|t| t.trim().to_string()).filter(|t| !t.is_empty()),
+            name,
+            topic,
             room_type,
             created_at: now,
             last_message_at: None,
@@ -159,10 +552,8 @@ impl RoomServiceTrait for RoomService {
         involvement_level: InvolvementLevel,
     ) -> Result<(), RoomError> {
         // Check if room exists
-        let room = self.db.get_room_by_id(room_id).await?;
-        if room.is_none() {
-            return Err(RoomError::NotFound { room_id });
-        }
+        let room = self.db.get_room_by_id(room_id).await?
+            .ok_or(RoomError::NotFound { room_id })?;
         
         // Check if user to be added exists
         if !self.db.user_exists(user_id).await? {
@@ -184,6 +575,20 @@ impl RoomServiceTrait for RoomService {
             });
         }
         
+        if matches!(room.room_type, RoomType::Open) {
+            self.check_join_rate(user_id, added_by).await?;
+        }
+        
+        // Direct rooms stay between the two people who started them
+        if matches!(room.room_type, RoomType::Direct)
+            && self.db.count_room_members(room_id).await? >= DIRECT_ROOM_MAX_MEMBERS
+        {
+            return Err(RoomError::DirectRoomFull {
+                room_id,
+                max_members: DIRECT_ROOM_MAX_MEMBERS,
+            });
+        }
+        
         // Create membership
         let membership = Membership {
             room_id,
@@ -194,9 +599,73 @@ impl RoomServiceTrait for RoomService {
         
         self.db.create_membership(membership).await?;
         
+        self.events.emit(DomainEvent::UserJoined { room_id, user_id, added_by }).await;
+        
+        Ok(())
+    }
+    
+    async fn remove_member(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+        removed_by: UserId,
+    ) -> Result<(), RoomError> {
+        self.require_admin(room_id, removed_by).await?;
+        
+        if self.db.delete_membership(room_id, user_id).await? {
+            self.events.emit(DomainEvent::UserRemoved { room_id, user_id }).await;
+        }
+        
+        Ok(())
+    }
+    
+    async fn delete_room_permanently(
+        &self,
+        room_id: RoomId,
+        deleted_by: UserId,
+        confirmation: Option<String>,
+    ) -> Result<(), RoomError> {
+        let room = self.db.get_room_by_id(room_id).await?
+            .ok_or(RoomError::NotFound { room_id })?;
+        
+        let is_site_admin = self.db.get_user_by_id(deleted_by).await?
+            .map(|user| user.admin)
+            .unwrap_or(false);
+        if !is_site_admin {
+            return Err(RoomError::NotAuthorized { user_id: deleted_by, room_id });
+        }
+        
+        let token = Self::deletion_token(&room);
+        if confirmation.as_deref() != Some(token.as_str()) {
+            return Err(RoomError::ConfirmationRequired { room_id, token });
+        }
+        
+        if let Some(blob_keys) = self.db.delete_room_permanently(room_id).await? {
+            self.events.emit(DomainEvent::RoomDeleted { room_id, blob_keys }).await;
+        }
+        
         Ok(())
     }
     
+    async fn set_retention(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        retention_days: Option<u32>,
+    ) -> Result<(), RoomError> {
+        if self.db.get_room_by_id(room_id).await?.is_none() {
+            return Err(RoomError::NotFound { room_id });
+        }
+        
+        let is_site_admin = self.db.get_user_by_id(changed_by).await?
+            .is_some_and(|user| user.admin);
+        if !is_site_admin {
+            return Err(RoomError::NotAuthorized { user_id: changed_by, room_id });
+        }
+        
+        Ok(self.db.set_room_retention(room_id, retention_days).await?)
+    }
+    
     async fn check_room_access(
         &self,
         room_id: RoomId,
@@ -244,10 +713,388 @@ impl RoomServiceTrait for RoomService {
         Ok(rooms)
     }
     
+    async fn get_room_list(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<RoomListEntry>, RoomError> {
+        let rooms = self.get_user_rooms(user_id).await?;
+        
+        let mut previews = if self.preview_length > 0 {
+            self.db.get_last_message_previews(user_id, self.preview_length).await?
+        } else {
+            Default::default()
+        };
+        
+        Ok(rooms
+            .into_iter()
+            .map(|room| RoomListEntry {
+                last_message_preview: previews.remove(&room.id),
+                room,
+            })
+            .collect())
+    }
+    
+    async fn get_direct_conversations(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<DirectConversation>, RoomError> {
+        Ok(self.db.get_direct_conversations(user_id).await?)
+    }
+    
+    async fn get_mentionable(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+        query: &str,
+    ) -> Result<Vec<MentionCandidate>, RoomError> {
+        let room = self.db.get_room_by_id(room_id).await?
+            .ok_or(RoomError::NotFound { room_id })?;
+        if self.check_room_access(room_id, user_id).await?.is_none() {
+            return Err(RoomError::NotAuthorized { user_id, room_id });
+        }
+        
+        let prefix = query.trim().trim_start_matches('@').to_lowercase();
+        let mut candidates = Vec::new();
+        
+        if self.room_mentions && !matches!(room.room_type, RoomType::Direct) {
+            candidates.extend(
+                ROOM_WIDE_MENTIONS
+                    .iter()
+                    .filter(|(handle, _)| handle.starts_with(&prefix))
+                    .map(|(handle, name)| MentionCandidate {
+                        handle: handle.to_string(),
+                        name: name.to_string(),
+                        user_id: None,
+                    }),
+            );
+        }
+        
+        let remaining = self.mention_limit.saturating_sub(candidates.len() as u32);
+        if remaining > 0 {
+            candidates.extend(self.db.find_room_members_by_prefix(room_id, &prefix, remaining).await?);
+        }
+        candidates.truncate(self.mention_limit as usize);
+        
+        Ok(candidates)
+    }
+    
     async fn get_room_by_id(
         &self,
         room_id: RoomId,
     ) -> Result<Option<Room>, RoomError> {
         Ok(self.db.get_room_by_id(room_id).await?)
     }
+    
+    async fn change_room_type(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        new_type: RoomType,
+    ) -> Result<Room, RoomError> {
+        let mut room = self.db.get_room_by_id(room_id).await?
+            .ok_or(RoomError::NotFound { room_id })?;
+        
+        if matches!(room.room_type, RoomType::Direct) {
+            return Err(RoomError::InvalidTypeChange {
+                room_id,
+                reason: "direct rooms cannot be converted".to_string(),
+            });
+        }
+        if matches!(new_type, RoomType::Direct) {
+            return Err(RoomError::InvalidTypeChange {
+                room_id,
+                reason: "rooms cannot be converted into direct rooms".to_string(),
+            });
+        }
+        
+        // Room admins and site admins may convert
+        let is_room_admin = matches!(
+            self.db.get_membership(room_id, changed_by).await?,
+            Some(Membership { involvement_level: InvolvementLevel::Admin, .. })
+        );
+        let is_site_admin = self.db.get_user_by_id(changed_by).await?
+            .map(|user| user.admin)
+            .unwrap_or(false);
+        if !is_room_admin && !is_site_admin {
+            return Err(RoomError::NotAuthorized { user_id: changed_by, room_id });
+        }
+        
+        if room.room_type == new_type {
+            return Ok(room);
+        }
+        
+        if matches!(new_type, RoomType::Open)
+            && self.db.get_open_room_by_name(&room.name).await?.is_some()
+        {
+            return Err(RoomError::InvalidTypeChange {
+                room_id,
+                reason: format!("an open room named '{}' already exists", room.name),
+            });
+        }
+        
+        self.db.update_room_type(room_id, new_type.clone()).await?;
+        if matches!(new_type, RoomType::Closed) {
+            self.db.set_room_public(room_id, false).await?;
+        }
+        room.room_type = new_type;
+        
+        // Members are told over the room channel; with nobody connected
+        // there's no one to tell
+        if let Some(connection_manager) = &self.connection_manager {
+            let update = WebSocketMessage::RoomUpdated { room: room.clone() };
+            if let Err(e) = connection_manager.broadcast_to_room(room_id, update).await {
+                tracing::debug!("RoomUpdated for room {} not delivered: {}", room_id, e);
+            }
+        }
+        
+        Ok(room)
+    }
+    
+    async fn resolve_or_create_room(
+        &self,
+        name: String,
+        user_id: UserId,
+    ) -> Result<(Room, bool), RoomError> {
+        let name = self.normalize_room_name(name.trim().trim_start_matches('#'))?;
+        
+        if let Some(room) = self.db.get_open_room_by_name(&name).await? {
+            return Ok((room, false));
+        }
+        
+        if !self.db.user_exists(user_id).await? {
+            return Err(RoomError::Database(
+                sqlx::Error::RowNotFound
+            ));
+        }
+        
+        let room = Room {
+            id: RoomId::new(),
+            name,
+            topic: None,
+            room_type: RoomType::Open,
+            created_at: Utc::now(),
+            last_message_at: None,
+        };
+        
+        // The writer re-checks the name, so two racing calls get one room
+        Ok(self.db.resolve_or_create_open_room(room, user_id).await?)
+    }
+    
+    async fn get_permissions(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+    ) -> Result<RoomPermissions, RoomError> {
+        let room = self.db.get_room_by_id(room_id).await?
+            .ok_or(RoomError::NotFound { room_id })?;
+        
+        let involvement_level = self.db.get_membership(room_id, user_id).await?
+            .map(|membership| membership.involvement_level);
+        let user = self.db.get_user_by_id(user_id).await?;
+        let is_site_admin = user.as_ref().map(|user| user.admin).unwrap_or(false);
+        let is_bot = user.as_ref().map(|user| user.is_bot()).unwrap_or(false);
+        let is_room_admin = matches!(involvement_level, Some(InvolvementLevel::Admin));
+        let is_admin = is_room_admin || is_site_admin;
+        
+        let is_open = matches!(room.room_type, RoomType::Open);
+        let can_read = is_open || involvement_level.is_some();
+        
+        // Bots never post on the strength of an admin role alone
+        let post_permission = self.db.get_room_post_permission(room_id).await?;
+        let post_allowed = match post_permission {
+            PostPermission::Everyone => true,
+            PostPermission::AdminsOnly => {
+                (is_admin && !is_bot) || self.db.has_room_post_grant(room_id, user_id).await?
+            }
+        };
+        
+        Ok(RoomPermissions {
+            room_id,
+            involvement_level,
+            is_admin,
+            can_read,
+            post_permission,
+            can_post: can_read && post_allowed,
+            // Same rule as add_member: anyone in an open room, admins elsewhere
+            can_invite: is_open || is_room_admin,
+            can_change_type: is_admin && !matches!(room.room_type, RoomType::Direct),
+        })
+    }
+    
+    async fn set_post_permission(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        post_permission: PostPermission,
+    ) -> Result<(), RoomError> {
+        self.require_admin(room_id, changed_by).await?;
+        
+        self.db.update_room_post_permission(room_id, post_permission).await?;
+        
+        if let Some(connection_manager) = &self.connection_manager {
+            let update = WebSocketMessage::PostPermissionChanged { room_id, post_permission };
+            if let Err(e) = connection_manager.broadcast_to_room(room_id, update).await {
+                tracing::debug!("PostPermissionChanged for room {} not delivered: {}", room_id, e);
+            }
+        }
+        
+        Ok(())
+    }
+    
+    async fn set_public(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        public: bool,
+    ) -> Result<(), RoomError> {
+        self.require_admin(room_id, changed_by).await?;
+        
+        if public {
+            let room = self.db.get_room_by_id(room_id).await?
+                .ok_or(RoomError::NotFound { room_id })?;
+            if !matches!(room.room_type, RoomType::Open) {
+                return Err(RoomError::NotOpen { room_id });
+            }
+        }
+        
+        Ok(self.db.set_room_public(room_id, public).await?)
+    }
+    
+    async fn set_sound_cooldown(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        cooldown_secs: Option<u64>,
+    ) -> Result<(), RoomError> {
+        self.require_admin(room_id, changed_by).await?;
+        
+        Ok(self.db.set_room_sound_cooldown(room_id, cooldown_secs).await?)
+    }
+    
+    async fn set_sound_policy(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        policy: SoundPolicy,
+    ) -> Result<(), RoomError> {
+        self.require_admin(room_id, changed_by).await?;
+        
+        if let SoundPolicy::Allowlist { sounds } = &policy {
+            if let Some(name) = sounds.iter().find(|name| !SoundManager::sound_exists(name)) {
+                return Err(RoomError::UnknownSound { name: name.clone() });
+            }
+        }
+        
+        Ok(self.db.set_room_sound_policy(room_id, policy).await?)
+    }
+    
+    async fn set_canned_response(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        shortcut: &str,
+        content: String,
+    ) -> Result<(), RoomError> {
+        self.require_admin(room_id, changed_by).await?;
+        
+        let shortcut = normalize_canned_shortcut(shortcut)?;
+        if content.trim().is_empty() || content.chars().count() > MAX_CANNED_RESPONSE_LENGTH {
+            return Err(RoomError::InvalidCannedResponse {
+                reason: format!("content must be 1-{} characters", MAX_CANNED_RESPONSE_LENGTH),
+            });
+        }
+        
+        // Replacing an existing shortcut doesn't count against the limit
+        if self.max_canned_responses > 0
+            && self.db.get_canned_response(room_id, &shortcut).await?.is_none()
+            && self.db.count_canned_responses(room_id).await? >= self.max_canned_responses
+        {
+            return Err(RoomError::CannedResponseLimitReached { limit: self.max_canned_responses });
+        }
+        
+        Ok(self.db.set_canned_response(room_id, shortcut, content).await?)
+    }
+    
+    async fn delete_canned_response(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        shortcut: &str,
+    ) -> Result<(), RoomError> {
+        self.require_admin(room_id, changed_by).await?;
+        
+        let shortcut = normalize_canned_shortcut(shortcut)?;
+        if !self.db.delete_canned_response(room_id, shortcut.clone()).await? {
+            return Err(RoomError::CannedResponseNotFound { shortcut });
+        }
+        Ok(())
+    }
+    
+    async fn get_canned_responses(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+        prefix: &str,
+    ) -> Result<Vec<CannedResponse>, RoomError> {
+        if self.db.get_room_by_id(room_id).await?.is_none() {
+            return Err(RoomError::NotFound { room_id });
+        }
+        if self.check_room_access(room_id, user_id).await?.is_none() {
+            return Err(RoomError::NotAuthorized { user_id, room_id });
+        }
+        
+        let prefix = prefix.trim().to_lowercase();
+        let limit = if self.max_canned_responses > 0 { self.max_canned_responses } else { u32::MAX };
+        Ok(self.db.get_canned_responses(room_id, &prefix, limit).await?)
+    }
+    
+    async fn set_attachment_types(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        types: Option<Vec<String>>,
+    ) -> Result<(), RoomError> {
+        self.require_admin(room_id, changed_by).await?;
+        
+        let types = types
+            .map(|types| content_types::normalize(&types))
+            .transpose()
+            .map_err(|value| RoomError::InvalidAttachmentType { value })?;
+        
+        Ok(self.db.set_room_attachment_types(room_id, types).await?)
+    }
+    
+    async fn set_notify_mentions_only(
+        &self,
+        room_id: RoomId,
+        changed_by: UserId,
+        mentions_only: bool,
+    ) -> Result<(), RoomError> {
+        self.require_admin(room_id, changed_by).await?;
+        
+        Ok(self.db.set_room_notify_mentions_only(room_id, mentions_only).await?)
+    }
+    
+    async fn set_post_grant(
+        &self,
+        room_id: RoomId,
+        granted_by: UserId,
+        user_id: UserId,
+        granted: bool,
+    ) -> Result<(), RoomError> {
+        self.require_admin(room_id, granted_by).await?;
+        
+        Ok(self.db.set_room_post_grant(room_id, user_id, granted).await?)
+    }
+    
+    async fn check_post_permission(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+    ) -> Result<bool, RoomError> {
+        match self.db.get_room_post_permission(room_id).await? {
+            PostPermission::Everyone => Ok(true),
+            PostPermission::AdminsOnly => Ok(self.get_permissions(room_id, user_id).await?.can_post),
+        }
+    }
 }
\ No newline at end of file
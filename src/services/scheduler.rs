@@ -0,0 +1,166 @@
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use metrics::{counter, gauge};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, warn};
+
+type Job = Arc<dyn Fn() -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+/// What an admin sees about a recurring job
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub name: String,
+    pub interval_secs: u64,
+    pub runs: u64,
+    pub failures: u64,
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<u64>,
+    pub next_run: Option<DateTime<Utc>>,
+    /// Error from the most recent run; cleared when a run succeeds
+    pub last_error: Option<String>,
+}
+
+/// Runs named background jobs on fixed intervals
+///
+/// Each job gets its own task and runs once at registration, then every
+/// `interval`; a run that overruns delays the next one rather than
+/// overlapping it. Every run is recorded for the admin jobs endpoint and
+/// counted in `scheduler_job_runs_total` / `scheduler_job_failures_total`.
+#[derive(Clone, Default)]
+pub struct Scheduler {
+    jobs: Arc<Mutex<BTreeMap<String, JobStatus>>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `job` to run every `interval`; registering a name twice is
+    /// ignored
+    pub fn every<F, Fut, E>(&self, name: &str, interval: Duration, job: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display,
+    {
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            if jobs.contains_key(name) {
+                warn!("Scheduled job '{}' is already registered", name);
+                return;
+            }
+            jobs.insert(name.to_string(), JobStatus {
+                name: name.to_string(),
+                interval_secs: interval.as_secs(),
+                runs: 0,
+                failures: 0,
+                last_run: None,
+                last_duration_ms: None,
+                next_run: Some(Utc::now()),
+                last_error: None,
+            });
+        }
+
+        let job: Job = Arc::new(move || {
+            let run = job();
+            Box::pin(async move { run.await.map_err(|e| e.to_string()) })
+        });
+        let jobs = self.jobs.clone();
+        let name = name.to_string();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                run_once(&jobs, &name, interval, &job).await;
+            }
+        });
+    }
+
+    /// Every registered job, by name
+    pub fn statuses(&self) -> Vec<JobStatus> {
+        self.jobs.lock().unwrap().values().cloned().collect()
+    }
+}
+
+async fn run_once(jobs: &Mutex<BTreeMap<String, JobStatus>>, name: &str, interval: Duration, job: &Job) {
+    let started_at = Utc::now();
+    let started = Instant::now();
+    let result = job().await;
+    let elapsed = started.elapsed();
+
+    counter!("scheduler_job_runs_total", 1, "job" => name.to_string());
+    gauge!("scheduler_job_duration_seconds", elapsed.as_secs_f64(), "job" => name.to_string());
+    if let Err(e) = &result {
+        counter!("scheduler_job_failures_total", 1, "job" => name.to_string());
+        warn!("Scheduled job '{}' failed: {}", name, e);
+    } else {
+        debug!("Scheduled job '{}' finished in {:?}", name, elapsed);
+    }
+
+    let mut jobs = jobs.lock().unwrap();
+    if let Some(status) = jobs.get_mut(name) {
+        status.runs += 1;
+        status.last_run = Some(started_at);
+        status.last_duration_ms = Some(elapsed.as_millis() as u64);
+        status.next_run = chrono::Duration::from_std(interval).ok().map(|interval| started_at + interval);
+        match result {
+            Ok(()) => status.last_error = None,
+            Err(e) => {
+                status.failures += 1;
+                status.last_error = Some(e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_job_runs_on_its_interval_and_records_outcome() {
+        let scheduler = Scheduler::new();
+        let calls = Arc::new(AtomicU32::new(0));
+        let job_calls = calls.clone();
+        scheduler.every("flaky", Duration::from_secs(60), move || {
+            let call = job_calls.fetch_add(1, Ordering::SeqCst) + 1;
+            async move {
+                if call == 2 { Err("disk full") } else { Ok(()) }
+            }
+        });
+
+        let status = |scheduler: &Scheduler| scheduler.statuses().into_iter().next().unwrap();
+
+        // Runs as soon as it's registered
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let first = status(&scheduler);
+        assert_eq!((first.name.as_str(), first.runs, first.failures), ("flaky", 1, 0));
+        assert!(first.last_error.is_none());
+        assert_eq!(first.next_run.unwrap() - first.last_run.unwrap(), chrono::Duration::seconds(60));
+
+        // Not again until the interval has passed
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        let failed = status(&scheduler);
+        assert_eq!((failed.runs, failed.failures), (2, 1));
+        assert_eq!(failed.last_error.as_deref(), Some("disk full"));
+
+        // A later success clears the error but keeps the failure count
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        let recovered = status(&scheduler);
+        assert_eq!((recovered.runs, recovered.failures), (3, 1));
+        assert!(recovered.last_error.is_none());
+    }
+}
@@ -1,6 +1,7 @@
 use async_trait::async_trait;
 use std::sync::Arc;
 use crate::database::CampfireDatabase;
+use crate::config::PaginationConfig;
 use crate::models::{Message, UserId, RoomId, MessageId};
 use crate::errors::{DatabaseError, RoomError};
 use crate::services::room::{RoomServiceTrait};
@@ -20,6 +21,9 @@ pub enum SearchError {
     #[error("Search query too long: maximum 100 characters")]
     QueryTooLong,
     
+    #[error("Prefix too short: each word needs at least {min} characters")]
+    PrefixTooShort { min: usize },
+    
     #[error("Database operation failed: {0}")]
     Database(#[from] DatabaseError),
     
@@ -42,8 +46,18 @@ pub struct SearchRequest {
     pub limit: Option<u32>,
     pub offset: Option<u32>,
     pub room_id: Option<RoomId>,
+    /// Search-as-you-type: match words starting with each term. Room-scoped
+    /// only, and capped at `PREFIX_RESULT_LIMIT` results
+    #[serde(default)]
+    pub prefix: bool,
 }
 
+/// Shortest term allowed in prefix mode; "a*" would match nearly every message
+pub const MIN_PREFIX_LENGTH: usize = 3;
+
+/// Result cap for prefix mode, which runs on every keystroke
+pub const PREFIX_RESULT_LIMIT: u32 = 10;
+
 /// Search response with pagination
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResponse {
@@ -98,6 +112,7 @@ pub trait SearchServiceTrait: Send + Sync {
 pub struct SearchService {
     db: Arc<CampfireDatabase>,
     room_service: Arc<dyn RoomServiceTrait>,
+    max_page_size: u32,
 }
 
 impl SearchService {
@@ -105,7 +120,18 @@ impl SearchService {
         db: Arc<CampfireDatabase>,
         room_service: Arc<dyn RoomServiceTrait>,
     ) -> Self {
-        Self { db, room_service }
+        Self {
+            db,
+            room_service,
+            max_page_size: PaginationConfig::default().max_limit,
+        }
+    }
+    
+    /// Most results one page returns, normally the configured
+    /// `pagination.max_limit`
+    pub fn with_max_page_size(mut self, max_page_size: u32) -> Self {
+        self.max_page_size = max_page_size;
+        self
     }
     
     /// Get reference to the database for testing purposes
@@ -140,6 +166,31 @@ impl SearchService {
         Ok(escaped)
     }
     
+    /// Build an FTS5 prefix query, e.g. `auth tok` -> `"auth"* "tok"*`
+    fn prefix_match_query(&self, validated_query: &str) -> Result<String, SearchError> {
+        let terms: Vec<&str> = validated_query
+            .split_whitespace()
+            .map(|term| term.trim_matches('"'))
+            .filter(|term| !term.is_empty())
+            .collect();
+        
+        if terms.is_empty() {
+            return Err(SearchError::InvalidQuery {
+                reason: "Query cannot be empty".to_string(),
+            });
+        }
+        
+        if terms.iter().any(|term| term.chars().count() < MIN_PREFIX_LENGTH) {
+            return Err(SearchError::PrefixTooShort { min: MIN_PREFIX_LENGTH });
+        }
+        
+        Ok(terms
+            .iter()
+            .map(|term| format!("\"{}\"*", term))
+            .collect::<Vec<_>>()
+            .join(" "))
+    }
+    
     /// Generate snippet with highlighted matches
     fn generate_snippet(&self, content: &str, query: &str) -> String {
         let query_lower = query.to_lowercase();
@@ -189,7 +240,19 @@ impl SearchServiceTrait for SearchService {
         let validated_query = self.validate_query(&request.query)?;
         
         // Get pagination parameters
-        let limit = request.limit.unwrap_or(20).min(100); // Max 100 results per page
+        let mut limit = request.limit.unwrap_or(20).min(self.max_page_size);
+        
+        let match_query = if request.prefix {
+            if request.room_id.is_none() {
+                return Err(SearchError::InvalidQuery {
+                    reason: "Prefix search requires a room_id".to_string(),
+                });
+            }
+            limit = limit.min(PREFIX_RESULT_LIMIT);
+            self.prefix_match_query(&validated_query)?
+        } else {
+            validated_query.clone()
+        };
         let offset = request.offset.unwrap_or(0);
         
         // Get user's accessible rooms for authorization
@@ -213,7 +276,10 @@ impl SearchServiceTrait for SearchService {
             .collect::<Vec<_>>()
             .join(",");
         
-        // Search with FTS5 and join with messages table for full data
+        // Search with FTS5 and join with messages table for full data. The
+        // triggers keep the index in step, but if one lags, an index row for a
+        // deleted message has nothing to join and one holding pre-edit text no
+        // longer matches the message, so neither can surface
         let search_query = if let Some(room_id) = request.room_id {
             // Search within specific room (if user has access)
             if !accessible_room_ids.contains(&room_id) {
@@ -233,7 +299,7 @@ impl SearchServiceTrait for SearchService {
                        rank
                 FROM messages_fts fts
                 INNER JOIN messages m ON fts.message_id = m.id
-                WHERE messages_fts MATCH ? AND m.room_id = ?
+                WHERE messages_fts MATCH ? AND fts.content = m.content AND m.room_id = ?
                 ORDER BY rank, m.created_at DESC
                 LIMIT ? OFFSET ?
                 "#
@@ -246,7 +312,7 @@ impl SearchServiceTrait for SearchService {
                        rank
                 FROM messages_fts fts
                 INNER JOIN messages m ON fts.message_id = m.id
-                WHERE messages_fts MATCH ? AND m.room_id IN ({})
+                WHERE messages_fts MATCH ? AND fts.content = m.content AND m.room_id IN ({})
                 ORDER BY rank, m.created_at DESC
                 LIMIT ? OFFSET ?
                 "#,
@@ -256,7 +322,7 @@ impl SearchServiceTrait for SearchService {
         
         // Execute search query
         let mut query_builder = sqlx::query(&search_query)
-            .bind(&validated_query);
+            .bind(&match_query);
         
         if let Some(room_id) = request.room_id {
             query_builder = query_builder.bind(room_id.0.to_string());
@@ -271,7 +337,7 @@ impl SearchServiceTrait for SearchService {
             .bind(offset as i64);
         
         let rows = query_builder
-            .fetch_all(self.db.pool())
+            .fetch_all(self.db.read_pool_for(user_id))
             .await
             .map_err(|e| DatabaseError::Connection(e))?;
         
@@ -317,7 +383,7 @@ impl SearchServiceTrait for SearchService {
                 SELECT COUNT(*) as total
                 FROM messages_fts fts
                 INNER JOIN messages m ON fts.message_id = m.id
-                WHERE messages_fts MATCH ? AND m.room_id = ?
+                WHERE messages_fts MATCH ? AND fts.content = m.content AND m.room_id = ?
                 "#
             )
         } else {
@@ -326,14 +392,14 @@ impl SearchServiceTrait for SearchService {
                 SELECT COUNT(*) as total
                 FROM messages_fts fts
                 INNER JOIN messages m ON fts.message_id = m.id
-                WHERE messages_fts MATCH ? AND m.room_id IN ({})
+                WHERE messages_fts MATCH ? AND fts.content = m.content AND m.room_id IN ({})
                 "#,
                 room_placeholders
             )
         };
         
         let mut count_query_builder = sqlx::query(&count_query)
-            .bind(&validated_query);
+            .bind(&match_query);
         
         if let Some(room_id) = request.room_id {
             count_query_builder = count_query_builder.bind(room_id.0.to_string());
@@ -344,7 +410,7 @@ impl SearchServiceTrait for SearchService {
         }
         
         let count_row = count_query_builder
-            .fetch_one(self.db.pool())
+            .fetch_one(self.db.read_pool_for(user_id))
             .await
             .map_err(|e| DatabaseError::Connection(e))?;
         
@@ -374,6 +440,7 @@ impl SearchServiceTrait for SearchService {
             limit: Some(limit),
             offset: Some(offset),
             room_id: Some(room_id),
+            prefix: false,
         };
         
         self.search_messages(user_id, request).await
@@ -386,7 +453,8 @@ impl From<SearchError> for axum::http::StatusCode {
         match err {
             SearchError::InvalidQuery { .. }
             | SearchError::QueryTooShort
-            | SearchError::QueryTooLong => axum::http::StatusCode::BAD_REQUEST,
+            | SearchError::QueryTooLong
+            | SearchError::PrefixTooShort { .. } => axum::http::StatusCode::BAD_REQUEST,
             SearchError::Database(_) => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
             SearchError::RoomAccess(_) => axum::http::StatusCode::FORBIDDEN,
         }
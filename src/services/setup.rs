@@ -4,13 +4,18 @@ use chrono::{Duration, Utc};
 use rand::Rng;
 use sqlx::Row;
 use std::env;
+use std::net::IpAddr;
+use tokio::sync::Mutex;
 
+use crate::config::{AdminSeedConfig, PasswordPolicy, TextLimits};
 use crate::database::CampfireDatabase;
 use crate::errors::{SetupError, DatabaseError};
 use crate::models::{
     User, UserId, Session, DeploymentConfig, SystemHealth,
     CreateAdminRequest, SetupStatusResponse, AdminCreationResponse,
 };
+use crate::services::sliding_window::SlidingWindowLimiter;
+use crate::validation::normalize_text;
 
 /// First-Run Setup Service - Basecamp-style admin setup (Requirement 11)
 /// 
@@ -47,6 +52,20 @@ pub trait SetupService: Send + Sync {
         request: CreateAdminRequest,
     ) -> Result<AdminCreationResponse, SetupError>;
     
+    /// Records an admin-creation attempt from `client`
+    /// 
+    /// # Postconditions
+    /// - Returns TooManyAttempts when the client has made too many recently
+    async fn record_attempt(&self, client: IpAddr) -> Result<(), SetupError>;
+    
+    /// Provisions the initial admin from configuration, without /setup
+    /// 
+    /// # Postconditions
+    /// - Returns the new admin when the database had no users
+    /// - Returns None (and changes nothing) when any user already exists
+    /// - Setup is complete afterwards, since users now exist
+    async fn seed_admin(&self, seed: &AdminSeedConfig) -> Result<Option<User>, SetupError>;
+    
     /// Gets environment-based configuration
     async fn get_deployment_config(&self) -> Result<DeploymentConfig, SetupError>;
     
@@ -57,14 +76,64 @@ pub trait SetupService: Send + Sync {
     async fn get_setup_status(&self) -> Result<SetupStatusResponse, SetupError>;
 }
 
+/// Per-client limit on admin-creation attempts
+///
+/// Setup is open to anyone until the first admin exists, so repeated
+/// attempts from one address are throttled. Sliding window of one minute.
+pub struct SetupAttemptLimiter {
+    window: SlidingWindowLimiter<IpAddr>,
+}
+
+impl SetupAttemptLimiter {
+    pub fn new(attempts_per_minute: u32) -> Self {
+        Self {
+            window: SlidingWindowLimiter::new(attempts_per_minute, std::time::Duration::from_secs(60)),
+        }
+    }
+    
+    /// Records an attempt from the client, or returns how long until one is allowed
+    fn try_acquire(&self, client: IpAddr) -> Result<(), std::time::Duration> {
+        self.window.try_acquire(client).map_err(|full| full.retry_after)
+    }
+}
+
 /// Implementation of SetupService following Rails-style patterns
 pub struct SetupServiceImpl {
     database: CampfireDatabase,
+    password_policy: PasswordPolicy,
+    text_limits: TextLimits,
+    attempt_limiter: Option<SetupAttemptLimiter>,
+    /// Held while an admin is created, so concurrent attempts queue up and
+    /// all but the first find setup already complete
+    creation_lock: Mutex<()>,
 }
 
 impl SetupServiceImpl {
     pub fn new(database: CampfireDatabase) -> Self {
-        Self { database }
+        Self {
+            database,
+            password_policy: PasswordPolicy::default(),
+            text_limits: TextLimits::default(),
+            attempt_limiter: None,
+            creation_lock: Mutex::new(()),
+        }
+    }
+    
+    /// Admin-creation attempts a client may make per minute (0 = unlimited)
+    pub fn with_attempt_limit(mut self, attempts_per_minute: u32) -> Self {
+        self.attempt_limiter = (attempts_per_minute > 0).then(|| SetupAttemptLimiter::new(attempts_per_minute));
+        self
+    }
+    
+    pub fn with_password_policy(mut self, password_policy: PasswordPolicy) -> Self {
+        self.password_policy = password_policy;
+        self
+    }
+    
+    /// Longest admin name accepted
+    pub fn with_text_limits(mut self, text_limits: TextLimits) -> Self {
+        self.text_limits = text_limits;
+        self
     }
     
     /// Validates email format using simple regex
@@ -100,48 +169,35 @@ impl SetupServiceImpl {
         Ok(())
     }
     
-    /// Validates password strength
+    /// Validates password strength against the configured policy
+    /// 
+    /// The admin password always needs a letter and a number, whatever the
+    /// policy says.
     fn validate_password(&self, password: &str) -> Result<(), SetupError> {
-        if password.len() < 8 {
-            return Err(SetupError::WeakPassword { 
-                reason: "Password must be at least 8 characters long".to_string() 
-            });
-        }
+        let policy = PasswordPolicy {
+            require_digit: true,
+            ..self.password_policy.clone()
+        };
+        let mut reasons: Vec<String> = match crate::validation::validate_password(password, &policy) {
+            Ok(()) => Vec::new(),
+            Err(e) => e.details.into_values().flatten().collect(),
+        };
         
-        if password.len() > 128 {
-            return Err(SetupError::WeakPassword { 
-                reason: "Password must be less than 128 characters".to_string() 
-            });
+        if !password.chars().any(|c| c.is_alphabetic()) {
+            reasons.push("Password must contain at least one letter".to_string());
         }
         
-        // Check for at least one letter and one number
-        let has_letter = password.chars().any(|c| c.is_alphabetic());
-        let has_number = password.chars().any(|c| c.is_numeric());
-        
-        if !has_letter || !has_number {
-            return Err(SetupError::WeakPassword { 
-                reason: "Password must contain at least one letter and one number".to_string() 
-            });
+        if !reasons.is_empty() {
+            return Err(SetupError::WeakPassword { reasons });
         }
         
         Ok(())
     }
     
-    /// Validates user name
-    fn validate_name(&self, name: &str) -> Result<(), SetupError> {
-        if name.trim().is_empty() {
-            return Err(SetupError::AdminCreationFailed(
-                "Name cannot be empty".to_string()
-            ));
-        }
-        
-        if name.len() > 100 {
-            return Err(SetupError::AdminCreationFailed(
-                "Name must be less than 100 characters".to_string()
-            ));
-        }
-        
-        Ok(())
+    /// Validates a user name and returns it with whitespace normalized
+    fn normalize_name(&self, name: &str) -> Result<String, SetupError> {
+        normalize_text("Name", name, self.text_limits.user_name)
+            .map_err(|e| SetupError::AdminCreationFailed(e.to_string()))
     }
     
     /// Generates secure session token
@@ -173,24 +229,7 @@ impl SetupServiceImpl {
     
     /// Creates user directly in database (bypasses writer for setup)
     async fn create_user_direct(&self, user: &User) -> Result<(), DatabaseError> {
-        sqlx::query(
-            r#"
-            INSERT INTO users (id, name, email, password_hash, bio, admin, bot_token, created_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
-            "#
-        )
-        .bind(user.id.0.to_string())
-        .bind(&user.name)
-        .bind(&user.email)
-        .bind(&user.password_hash)
-        .bind(&user.bio)
-        .bind(user.admin)
-        .bind(&user.bot_token)
-        .bind(user.created_at)
-        .execute(self.database.pool())
-        .await?;
-        
-        Ok(())
+        crate::database::Database::insert_user(self.database.pool(), user).await
     }
     
     /// Creates session directly in database (bypasses writer for setup)
@@ -230,6 +269,8 @@ impl SetupService for SetupServiceImpl {
         &self,
         request: CreateAdminRequest,
     ) -> Result<AdminCreationResponse, SetupError> {
+        let _creating = self.creation_lock.lock().await;
+        
         // Verify this is still a first-run scenario
         if !self.is_first_run().await? {
             return Err(SetupError::NotFirstRun);
@@ -238,7 +279,7 @@ impl SetupService for SetupServiceImpl {
         // Validate input
         self.validate_email(&request.email)?;
         self.validate_password(&request.password)?;
-        self.validate_name(&request.name)?;
+        let name = self.normalize_name(&request.name)?;
         
         // Hash password
         let password_hash = hash(&request.password, DEFAULT_COST)?;
@@ -246,7 +287,7 @@ impl SetupService for SetupServiceImpl {
         // Create admin user
         let user = User {
             id: UserId::new(),
-            name: request.name.trim().to_string(),
+            name,
             email: request.email.trim().to_lowercase(),
             password_hash,
             bio: Some("System Administrator".to_string()),
@@ -282,6 +323,47 @@ impl SetupService for SetupServiceImpl {
         })
     }
     
+    async fn record_attempt(&self, client: IpAddr) -> Result<(), SetupError> {
+        let Some(limiter) = &self.attempt_limiter else {
+            return Ok(());
+        };
+        
+        limiter.try_acquire(client).map_err(|retry_after| {
+            tracing::warn!("Client {} exceeded the setup attempt rate", client);
+            SetupError::TooManyAttempts {
+                retry_after_secs: retry_after.as_secs().max(1),
+            }
+        })
+    }
+    
+    async fn seed_admin(&self, seed: &AdminSeedConfig) -> Result<Option<User>, SetupError> {
+        let _creating = self.creation_lock.lock().await;
+        
+        if self.has_existing_users().await? {
+            return Ok(None);
+        }
+        
+        self.validate_email(&seed.email)?;
+        self.validate_password(&seed.password)?;
+        let name = self.normalize_name(&seed.name)?;
+        
+        let user = User {
+            id: UserId::new(),
+            name,
+            email: seed.email.trim().to_lowercase(),
+            password_hash: hash(&seed.password, DEFAULT_COST)?,
+            bio: Some("System Administrator".to_string()),
+            admin: true,
+            bot_token: None,
+            created_at: Utc::now(),
+        };
+        
+        self.create_user_direct(&user).await
+            .map_err(|e| SetupError::AdminCreationFailed(e.to_string()))?;
+        
+        Ok(Some(user))
+    }
+    
     async fn get_deployment_config(&self) -> Result<DeploymentConfig, SetupError> {
         Ok(DeploymentConfig {
             database_url: env::var("CAMPFIRE_DATABASE_URL")
@@ -369,6 +451,7 @@ impl SetupService for SetupServiceImpl {
 mod tests {
     use super::*;
     use crate::database::Database;
+    use std::sync::Arc;
 
     
     async fn create_test_setup_service() -> SetupServiceImpl {
@@ -460,6 +543,80 @@ mod tests {
         assert!(matches!(result, Err(SetupError::NotFirstRun)));
     }
     
+    #[tokio::test]
+    async fn test_concurrent_admin_creation_makes_one_admin() {
+        let service = Arc::new(create_test_setup_service().await);
+        
+        let attempts = (0..3).map(|i| {
+            let service = service.clone();
+            tokio::spawn(async move {
+                service.create_admin_account(CreateAdminRequest {
+                    email: format!("admin{}@example.com", i),
+                    password: "securepass123".to_string(),
+                    name: format!("Admin {}", i),
+                }).await
+            })
+        }).collect::<Vec<_>>();
+        
+        let mut created = 0;
+        for attempt in attempts {
+            match attempt.await.unwrap() {
+                Ok(_) => created += 1,
+                Err(e) => assert!(matches!(e, SetupError::NotFirstRun)),
+            }
+        }
+        assert_eq!(created, 1);
+        
+        // Once setup is complete every later attempt is refused with 409
+        let result = service.create_admin_account(CreateAdminRequest {
+            email: "late@example.com".to_string(),
+            password: "securepass123".to_string(),
+            name: "Late Admin".to_string(),
+        }).await;
+        let err = result.unwrap_err();
+        assert!(matches!(err, SetupError::NotFirstRun));
+        assert_eq!(axum::http::StatusCode::from(err), axum::http::StatusCode::CONFLICT);
+    }
+    
+    #[tokio::test]
+    async fn test_setup_attempts_are_throttled_per_client() {
+        let service = create_test_setup_service().await.with_attempt_limit(2);
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+        
+        assert!(service.record_attempt(client).await.is_ok());
+        assert!(service.record_attempt(client).await.is_ok());
+        assert!(matches!(
+            service.record_attempt(client).await,
+            Err(SetupError::TooManyAttempts { retry_after_secs }) if retry_after_secs >= 1
+        ));
+        
+        // Other clients have their own allowance
+        assert!(service.record_attempt("203.0.113.8".parse().unwrap()).await.is_ok());
+    }
+    
+    #[tokio::test]
+    async fn test_seed_admin() {
+        let service = create_test_setup_service().await;
+        let seed = AdminSeedConfig {
+            email: "Ops@Example.com".to_string(),
+            password: "bootstrap123".to_string(),
+            name: "Administrator".to_string(),
+        };
+        
+        let admin = service.seed_admin(&seed).await.unwrap().unwrap();
+        assert_eq!(admin.email, "ops@example.com");
+        assert!(admin.admin);
+        assert!(bcrypt::verify("bootstrap123", &admin.password_hash).unwrap());
+        
+        // Setup is done: no longer first run and the admin is detected
+        let status = service.get_setup_status().await.unwrap();
+        assert!(!status.is_first_run);
+        assert!(status.admin_exists);
+        
+        // A second boot with the same env leaves the existing users alone
+        assert!(service.seed_admin(&seed).await.unwrap().is_none());
+    }
+    
     #[tokio::test]
     async fn test_system_health_check() {
         let service = create_test_setup_service().await;
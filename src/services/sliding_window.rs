@@ -0,0 +1,86 @@
+//! Sliding-window counting for the per-user and per-client limiters
+//!
+//! Each key may be let through at most `limit` times in any `window`. The
+//! message, join and setup limiters wrap this with their own keys, windows
+//! and exemptions.
+
+use dashmap::DashMap;
+use std::collections::VecDeque;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// Why a key was turned away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowFull {
+    /// Until the oldest counted use leaves the window and one is allowed
+    pub retry_after: Duration,
+    /// Until the newest counted use leaves the window and the key is clear
+    pub reset_after: Duration,
+}
+
+pub struct SlidingWindowLimiter<K> {
+    limit: u32,
+    window: Duration,
+    recent: DashMap<K, VecDeque<Instant>>,
+}
+
+impl<K: Hash + Eq> SlidingWindowLimiter<K> {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            recent: DashMap::new(),
+        }
+    }
+
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Counts a use for the key, or says how long until one is allowed
+    pub fn try_acquire(&self, key: K) -> Result<(), WindowFull> {
+        let now = Instant::now();
+        let mut recent = self.recent.entry(key).or_default();
+
+        while recent.front().is_some_and(|used| now.duration_since(*used) >= self.window) {
+            recent.pop_front();
+        }
+
+        if recent.len() >= self.limit as usize {
+            let oldest = recent.front().copied().unwrap_or(now);
+            let newest = recent.back().copied().unwrap_or(now);
+            return Err(WindowFull {
+                retry_after: self.window.saturating_sub(now.duration_since(oldest)),
+                reset_after: self.window.saturating_sub(now.duration_since(newest)),
+            });
+        }
+
+        recent.push_back(now);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_are_counted_separately_until_the_window_passes() {
+        let limiter = SlidingWindowLimiter::new(2, Duration::from_millis(50));
+        assert!(limiter.try_acquire("a").is_ok());
+        assert!(limiter.try_acquire("a").is_ok());
+        let full = limiter.try_acquire("a").unwrap_err();
+        assert!(full.retry_after <= full.reset_after);
+        assert!(full.reset_after <= Duration::from_millis(50));
+
+        // Another key has its own window
+        assert!(limiter.try_acquire("b").is_ok());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(limiter.try_acquire("a").is_ok());
+    }
+}
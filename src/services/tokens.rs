@@ -0,0 +1,103 @@
+//! Single-use tokens behind password reset, email verification and room
+//! invite links
+//!
+//! Every kind shares one table. A token is valid until it is redeemed or its
+//! kind's configured lifetime runs out, whichever comes first; the cleanup
+//! job then deletes it, so the table only holds tokens that could still be
+//! used.
+
+use chrono::Utc;
+use rand::{thread_rng, Rng};
+use std::sync::Arc;
+
+use crate::config::TokenLifetimes;
+use crate::database::CampfireDatabase;
+use crate::errors::DatabaseError;
+use crate::models::{Token, TokenKind};
+
+#[derive(Clone)]
+pub struct TokenService {
+    db: Arc<CampfireDatabase>,
+    lifetimes: TokenLifetimes,
+}
+
+impl TokenService {
+    pub fn new(db: Arc<CampfireDatabase>, lifetimes: TokenLifetimes) -> Self {
+        Self { db, lifetimes }
+    }
+
+    /// Issues a token of `kind` for `subject`, valid for the kind's lifetime
+    pub async fn issue(&self, kind: TokenKind, subject: &str) -> Result<Token, DatabaseError> {
+        let mut token_bytes = [0u8; 32];
+        thread_rng().fill(&mut token_bytes);
+
+        let created_at = Utc::now();
+        let token = Token {
+            token: base64::encode_config(token_bytes, base64::URL_SAFE_NO_PAD),
+            kind,
+            subject: subject.to_string(),
+            created_at,
+            expires_at: created_at + self.lifetimes.lifetime(kind),
+            used_at: None,
+        };
+        self.db.create_token(token.clone()).await?;
+        Ok(token)
+    }
+
+    /// Redeems a token, returning its subject; unknown, expired, already
+    /// used and wrong-kind tokens all give `None`
+    pub async fn redeem(&self, kind: TokenKind, token: &str) -> Result<Option<String>, DatabaseError> {
+        Ok(self.db.consume_token(token, kind).await?.map(|token| token.subject))
+    }
+
+    /// Deletes expired and used tokens, returning how many were removed
+    pub async fn purge_expired(&self) -> Result<u64, DatabaseError> {
+        self.db.purge_expired_tokens().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn count_tokens(db: &CampfireDatabase) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM tokens")
+            .fetch_one(db.pool())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_expired_tokens_are_rejected_and_purged() {
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let service = TokenService::new(db.clone(), TokenLifetimes::default());
+
+        let valid = service.issue(TokenKind::PasswordReset, "user-1").await.unwrap();
+        let used = service.issue(TokenKind::RoomInvite, "room-1").await.unwrap();
+        let expired = Token {
+            token: "expired".to_string(),
+            kind: TokenKind::EmailVerification,
+            subject: "user-2".to_string(),
+            created_at: Utc::now() - chrono::Duration::hours(49),
+            expires_at: Utc::now() - chrono::Duration::hours(1),
+            used_at: None,
+        };
+        db.create_token(expired.clone()).await.unwrap();
+
+        assert_eq!(service.redeem(TokenKind::EmailVerification, "expired").await.unwrap(), None);
+        // A token only works for its own kind, and only once
+        assert_eq!(service.redeem(TokenKind::PasswordReset, &used.token).await.unwrap(), None);
+        assert_eq!(
+            service.redeem(TokenKind::RoomInvite, &used.token).await.unwrap().as_deref(),
+            Some("room-1")
+        );
+        assert_eq!(service.redeem(TokenKind::RoomInvite, &used.token).await.unwrap(), None);
+
+        assert_eq!(service.purge_expired().await.unwrap(), 2);
+        assert_eq!(count_tokens(&db).await, 1);
+        assert_eq!(
+            service.redeem(TokenKind::PasswordReset, &valid.token).await.unwrap().as_deref(),
+            Some("user-1")
+        );
+    }
+}
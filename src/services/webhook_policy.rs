@@ -0,0 +1,391 @@
+//! Where outbound webhooks may be sent
+//!
+//! Bot and room webhook URLs are chosen by admins, so a mistake or a
+//! compromised account could aim them at services that are only reachable
+//! from inside the network (SSRF). URLs are checked when they're saved and
+//! again before every delivery, when the host is also resolved so a public
+//! name pointing at a private address is caught too. Delivery clients come
+//! from `WebhookUrlPolicy::http_client`, whose resolver checks the addresses
+//! it actually connects to, so a name that changes between the check and
+//! the request (DNS rebinding) can't slip through.
+//!
+//! Private, loopback and link-local addresses are refused unless explicitly
+//! allowed. Host rules are CIDRs (`10.1.0.0/16`), bare IPs, or domains, which
+//! match the domain itself and every subdomain. The denylist always wins; a
+//! non-empty allowlist admits only the hosts it lists.
+
+use hyper::client::connect::dns::Name;
+use ipnet::IpNet;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::Client;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use url::{Host, Url};
+
+use crate::config::SecurityConfig;
+use crate::errors::BotError;
+use crate::middleware::client_ip::parse_proxy_net;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum HostRule {
+    Network(IpNet),
+    Domain(String),
+}
+
+impl HostRule {
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().trim_end_matches('.').to_ascii_lowercase();
+        if let Ok(network) = parse_proxy_net(&value) {
+            return Some(HostRule::Network(network));
+        }
+
+        let domain = value.trim_start_matches("*.").trim_start_matches('.');
+        let valid = !domain.is_empty()
+            && domain
+                .split('.')
+                .all(|label| !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'));
+        valid.then(|| HostRule::Domain(domain.to_string()))
+    }
+
+    fn matches_domain(&self, host: &str) -> bool {
+        match self {
+            HostRule::Domain(domain) => {
+                host == domain || host.strip_suffix(domain.as_str()).is_some_and(|rest| rest.ends_with('.'))
+            }
+            HostRule::Network(_) => false,
+        }
+    }
+
+    fn matches_ip(&self, ip: IpAddr) -> bool {
+        match self {
+            HostRule::Network(network) => network.contains(&ip),
+            HostRule::Domain(_) => false,
+        }
+    }
+}
+
+/// Whether a host rule is well formed; used by `Config::validate`
+pub fn is_valid_host_rule(value: &str) -> bool {
+    HostRule::parse(value).is_some()
+}
+
+/// Loopback, private, link-local (including cloud metadata at
+/// 169.254.169.254), carrier-grade NAT, benchmarking, multicast, reserved
+/// and other non-public addresses. IPv6 forms that embed an IPv4 address
+/// (mapped, compatible, NAT64 and 6to4) are judged by that address.
+pub fn is_private_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_multicast()
+                || a == 0
+                || a >= 240 // reserved, including broadcast
+                || (a == 100 && (64..128).contains(&b))
+                || (a == 198 && (b & 0xfe) == 18) // benchmarking, 198.18.0.0/15
+        }
+        IpAddr::V6(v6) => {
+            if v6.is_loopback() || v6.is_unspecified() {
+                return true;
+            }
+            let segments = v6.segments();
+            let embedded = |high: u16, low: u16| {
+                let [a, b] = high.to_be_bytes();
+                let [c, d] = low.to_be_bytes();
+                Ipv4Addr::new(a, b, c, d)
+            };
+            // Mapped ::ffff:a.b.c.d and compatible ::a.b.c.d
+            if let Some(v4) = v6.to_ipv4() {
+                return is_private_address(IpAddr::V4(v4));
+            }
+            // NAT64 64:ff9b::/96
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                return is_private_address(IpAddr::V4(embedded(segments[6], segments[7])));
+            }
+            // 6to4 2002::/16
+            if segments[0] == 0x2002 {
+                return is_private_address(IpAddr::V4(embedded(segments[1], segments[2])));
+            }
+            let first = segments[0];
+            (first & 0xfe00) == 0xfc00 // unique local
+                || (first & 0xffc0) == 0xfe80 // link-local
+                || (first & 0xff00) == 0xff00 // multicast
+        }
+    }
+}
+
+/// Resolves names for webhook clients, refusing any address the policy
+/// would, so the addresses connected to are the ones that were checked
+struct PolicyResolver {
+    policy: WebhookUrlPolicy,
+}
+
+impl Resolve for PolicyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let policy = self.policy.clone();
+        Box::pin(async move {
+            let host = name.as_str().trim_end_matches('.').to_ascii_lowercase();
+            let host_allowed = policy.allowed.iter().any(|rule| rule.matches_domain(&host));
+            // The connector fills in the port from the URL
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            for address in &addresses {
+                policy
+                    .check_address(address.ip(), host_allowed)
+                    .map_err(|reason| format!("{} resolves to {}: {}", host, address.ip(), reason))?;
+            }
+            let addresses: Addrs = Box::new(addresses.into_iter());
+            Ok(addresses)
+        })
+    }
+}
+
+/// Allow/deny rules for outbound webhook URLs
+#[derive(Debug, Clone, Default)]
+pub struct WebhookUrlPolicy {
+    require_https: bool,
+    allow_private_networks: bool,
+    allowed: Vec<HostRule>,
+    denied: Vec<HostRule>,
+}
+
+impl WebhookUrlPolicy {
+    /// Invalid rules are rejected by `Config::validate` before we get here
+    pub fn from_config(config: &SecurityConfig) -> Self {
+        Self::default()
+            .with_https_required(config.webhook_require_https)
+            .with_private_networks(config.webhook_allow_private_networks)
+            .with_allowed_hosts(&config.webhook_allowed_hosts)
+            .with_denied_hosts(&config.webhook_denied_hosts)
+    }
+
+    pub fn with_https_required(mut self, require_https: bool) -> Self {
+        self.require_https = require_https;
+        self
+    }
+
+    /// Lets webhooks reach private and loopback addresses, e.g. for bots
+    /// running next to the server
+    pub fn with_private_networks(mut self, allow: bool) -> Self {
+        self.allow_private_networks = allow;
+        self
+    }
+
+    pub fn with_allowed_hosts(mut self, hosts: &[String]) -> Self {
+        self.allowed = hosts.iter().filter_map(|host| HostRule::parse(host)).collect();
+        self
+    }
+
+    pub fn with_denied_hosts(mut self, hosts: &[String]) -> Self {
+        self.denied = hosts.iter().filter_map(|host| HostRule::parse(host)).collect();
+        self
+    }
+
+    /// An HTTP client for deliveries under this policy. It follows no
+    /// redirects, which could lead past the policy, and only connects to
+    /// addresses the policy allows.
+    pub fn http_client(&self, timeout: Duration) -> Client {
+        Client::builder()
+            .timeout(timeout)
+            .redirect(reqwest::redirect::Policy::none())
+            .dns_resolver(Arc::new(PolicyResolver { policy: self.clone() }))
+            .build()
+            .expect("Failed to create HTTP client")
+    }
+
+    /// Checks a URL as written: scheme, host lists and literal addresses
+    pub fn check_url(&self, url: &str) -> Result<Url, BotError> {
+        let parsed = Url::parse(url).map_err(|_| BotError::InvalidWebhookUrl { url: url.to_string() })?;
+        let blocked = |reason: &str| BotError::WebhookUrlBlocked {
+            url: url.to_string(),
+            reason: reason.to_string(),
+        };
+
+        match parsed.scheme() {
+            "https" => {}
+            "http" if self.require_https => return Err(blocked("webhooks must use https")),
+            "http" => {}
+            _ => return Err(BotError::InvalidWebhookUrl { url: url.to_string() }),
+        }
+
+        let ip = match parsed.host() {
+            None => return Err(BotError::InvalidWebhookUrl { url: url.to_string() }),
+            Some(Host::Ipv4(ip)) => IpAddr::V4(ip),
+            Some(Host::Ipv6(ip)) => IpAddr::V6(ip),
+            Some(Host::Domain(domain)) => {
+                let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+                if self.denied.iter().any(|rule| rule.matches_domain(&domain)) {
+                    return Err(blocked("host is on the denylist"));
+                }
+                let explicitly_allowed = self.allowed.iter().any(|rule| rule.matches_domain(&domain));
+                if !self.allowed.is_empty() && !explicitly_allowed {
+                    return Err(blocked("host is not on the allowlist"));
+                }
+                let local_name = domain == "localhost" || domain.ends_with(".localhost");
+                if local_name && !self.allow_private_networks && !explicitly_allowed {
+                    return Err(blocked("private and loopback addresses are not allowed"));
+                }
+                return Ok(parsed);
+            }
+        };
+
+        self.check_address(ip, false).map_err(blocked)?;
+        Ok(parsed)
+    }
+
+    /// `check_url`, then resolves the host and checks every address it
+    /// points at. Run before each delivery, since DNS can change after the
+    /// URL was saved; the client's resolver checks again when connecting.
+    pub async fn check_delivery(&self, url: &str) -> Result<(), BotError> {
+        let parsed = self.check_url(url)?;
+        let domain = match parsed.host() {
+            Some(Host::Domain(domain)) => domain.trim_end_matches('.').to_ascii_lowercase(),
+            _ => return Ok(()),
+        };
+        let domain_allowed = self.allowed.iter().any(|rule| rule.matches_domain(&domain));
+        let port = parsed.port_or_known_default().unwrap_or(443);
+
+        let addresses = tokio::net::lookup_host((domain.as_str(), port))
+            .await
+            .map_err(|e| BotError::WebhookDeliveryFailed { reason: format!("could not resolve {}: {}", domain, e) })?;
+        for address in addresses {
+            self.check_address(address.ip(), domain_allowed).map_err(|reason| BotError::WebhookUrlBlocked {
+                url: url.to_string(),
+                reason: format!("{} resolves to {}: {}", domain, address.ip(), reason),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// `host_allowed` is set when the name the address came from is itself
+    /// on the allowlist, which vouches for wherever it resolves
+    fn check_address(&self, ip: IpAddr, host_allowed: bool) -> Result<(), &'static str> {
+        if self.denied.iter().any(|rule| rule.matches_ip(ip)) {
+            return Err("address is on the denylist");
+        }
+        let explicitly_allowed = host_allowed || self.allowed.iter().any(|rule| rule.matches_ip(ip));
+        if !self.allowed.is_empty() && !explicitly_allowed {
+            return Err("address is not on the allowlist");
+        }
+        if is_private_address(ip) && !self.allow_private_networks && !explicitly_allowed {
+            return Err("private and loopback addresses are not allowed");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hosts(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_internal_urls_rejected_by_default() {
+        let policy = WebhookUrlPolicy::default();
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://localhost/hook",
+            "http://10.0.0.5/hook",
+            "http://192.168.1.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+        ] {
+            assert!(
+                matches!(policy.check_url(url), Err(BotError::WebhookUrlBlocked { .. })),
+                "{} should be blocked",
+                url
+            );
+        }
+        assert!(matches!(policy.check_url("ftp://example.com"), Err(BotError::InvalidWebhookUrl { .. })));
+        assert!(matches!(
+            policy.check_delivery("http://127.0.0.1:9/hook").await,
+            Err(BotError::WebhookUrlBlocked { .. })
+        ));
+
+        assert!(policy.check_url("https://hooks.example.com/bot").is_ok());
+        let local = WebhookUrlPolicy::default().with_private_networks(true);
+        assert!(local.check_url("http://127.0.0.1:8080/hook").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_allowed_external_https_url_passes() {
+        let policy = WebhookUrlPolicy::default()
+            .with_https_required(true)
+            .with_allowed_hosts(&hosts(&["hooks.example.com", "203.0.113.0/24", "10.1.2.3"]))
+            .with_denied_hosts(&hosts(&["legacy.hooks.example.com"]));
+
+        assert!(policy.check_url("https://hooks.example.com/bot").is_ok());
+        assert!(policy.check_url("https://eu.hooks.example.com/bot").is_ok());
+        assert!(policy.check_url("https://203.0.113.9/bot").is_ok());
+        // Listing a private address is how an internal bot is let through
+        assert!(policy.check_url("https://10.1.2.3/bot").is_ok());
+        assert!(policy.check_delivery("https://203.0.113.9/bot").await.is_ok());
+
+        for url in [
+            "http://hooks.example.com/bot",
+            "https://legacy.hooks.example.com/bot",
+            "https://example.com/bot",
+            "https://evilhooks.example.com.attacker.net/bot",
+            "https://10.1.2.4/bot",
+        ] {
+            assert!(
+                matches!(policy.check_url(url), Err(BotError::WebhookUrlBlocked { .. })),
+                "{} should be blocked",
+                url
+            );
+        }
+
+        assert!(is_valid_host_rule("*.example.com"));
+        assert!(!is_valid_host_rule("https://example.com/"));
+    }
+
+    #[test]
+    fn test_embedded_and_reserved_addresses_are_private() {
+        for ip in [
+            "::127.0.0.1",
+            "::10.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+            "2002:7f00:1::",
+            "ff02::1",
+            "198.18.0.1",
+            "198.19.255.255",
+            "224.0.0.1",
+            "240.0.0.1",
+            "255.255.255.255",
+        ] {
+            assert!(is_private_address(ip.parse().unwrap()), "{} should be private", ip);
+        }
+        for ip in ["93.184.216.34", "64:ff9b::5db8:d822", "2002:5db8:d822::", "2606:4700::1111", "198.20.0.1"] {
+            assert!(!is_private_address(ip.parse().unwrap()), "{} should be public", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_refuses_names_resolving_to_private_addresses() {
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let _ = socket.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await;
+            }
+        });
+        let url = format!("http://localhost:{}/hook", port);
+
+        // The name passed the URL check, but the connection is still refused
+        let client = WebhookUrlPolicy::default().http_client(Duration::from_secs(2));
+        assert!(client.post(&url).send().await.is_err());
+
+        let client = WebhookUrlPolicy::default().with_private_networks(true).http_client(Duration::from_secs(2));
+        assert!(client.post(&url).send().await.unwrap().status().is_success());
+    }
+}
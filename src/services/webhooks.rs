@@ -0,0 +1,317 @@
+//! Per-room outbound webhooks
+//!
+//! A room can notify any number of external endpoints about new messages.
+//! Each webhook has its own secret and enabled flag; deliveries to a room's
+//! webhooks run in parallel and are retried independently, so one slow or
+//! failing endpoint doesn't hold up or suppress the others. Every request
+//! carries `X-Campfire-Signature: sha256=<hex HMAC of the body>` keyed with
+//! that webhook's secret.
+
+use async_trait::async_trait;
+use chrono::Utc;
+use futures_util::future::join_all;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::database::CampfireDatabase;
+use crate::errors::BotError;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::models::*;
+use crate::services::delivery::{spawn_for_new_message, WebhookDelivery};
+use crate::services::WebhookUrlPolicy;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const SIGNATURE_HEADER: &str = "X-Campfire-Signature";
+
+#[derive(Clone)]
+pub struct RoomWebhookService {
+    db: Arc<CampfireDatabase>,
+    delivery: WebhookDelivery,
+}
+
+impl RoomWebhookService {
+    pub fn new(db: Arc<CampfireDatabase>) -> Self {
+        Self {
+            db,
+            delivery: WebhookDelivery::default(),
+        }
+    }
+
+    /// Where webhooks may point; private addresses are refused by default
+    pub fn with_url_policy(mut self, url_policy: WebhookUrlPolicy) -> Self {
+        self.delivery = self.delivery.with_url_policy(url_policy);
+        self
+    }
+
+    /// Attempts per delivery and the delay before the first retry, which
+    /// doubles on each further retry
+    pub fn with_retry(mut self, max_attempts: u32, retry_delay: Duration) -> Self {
+        self.delivery = self.delivery.with_retry(max_attempts, retry_delay);
+        self
+    }
+
+    /// Adds an enabled webhook with a fresh secret; the returned value is the
+    /// only place the secret is exposed
+    pub async fn create_webhook(&self, room_id: RoomId, url: String) -> Result<RoomWebhook, BotError> {
+        if url.is_empty() {
+            return Err(BotError::InvalidWebhookUrl { url });
+        }
+        self.delivery.url_policy().check_url(&url)?;
+
+        let webhook = RoomWebhook {
+            id: RoomWebhookId::new(),
+            room_id,
+            url,
+            secret: generate_secret(),
+            enabled: true,
+            created_at: Utc::now(),
+        };
+        self.db.create_room_webhook(webhook.clone()).await?;
+
+        info!("Added webhook {} to room {}", webhook.id, room_id);
+        Ok(webhook)
+    }
+
+    pub async fn list_webhooks(&self, room_id: RoomId) -> Result<Vec<RoomWebhook>, BotError> {
+        Ok(self.db.get_room_webhooks(room_id).await?)
+    }
+
+    pub async fn set_enabled(
+        &self,
+        room_id: RoomId,
+        webhook_id: RoomWebhookId,
+        enabled: bool,
+    ) -> Result<(), BotError> {
+        self.require_in_room(room_id, webhook_id).await?;
+        self.db.set_room_webhook_enabled(webhook_id, enabled).await?;
+        Ok(())
+    }
+
+    pub async fn delete_webhook(&self, room_id: RoomId, webhook_id: RoomWebhookId) -> Result<(), BotError> {
+        self.require_in_room(room_id, webhook_id).await?;
+        self.db.delete_room_webhook(webhook_id).await?;
+        Ok(())
+    }
+
+    async fn require_in_room(&self, room_id: RoomId, webhook_id: RoomWebhookId) -> Result<(), BotError> {
+        let webhooks = self.db.get_room_webhooks(room_id).await?;
+        if !webhooks.iter().any(|webhook| webhook.id == webhook_id) {
+            return Err(BotError::WebhookNotFound { webhook_id });
+        }
+        Ok(())
+    }
+
+    /// Delivers the message to every enabled webhook of its room in parallel,
+    /// returning each webhook's outcome after retries
+    pub async fn deliver_message(&self, message: &Message) -> Vec<(RoomWebhookId, Result<(), BotError>)> {
+        let webhooks: Vec<RoomWebhook> = match self.db.get_room_webhooks(message.room_id).await {
+            Ok(webhooks) => webhooks.into_iter().filter(|webhook| webhook.enabled).collect(),
+            Err(e) => {
+                warn!("Failed to load webhooks for room {}: {}", message.room_id, e);
+                return Vec::new();
+            }
+        };
+        if webhooks.is_empty() {
+            return Vec::new();
+        }
+
+        let body = match self.payload(message).await {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Failed to build webhook payload for message {}: {}", message.id, e);
+                return Vec::new();
+            }
+        };
+
+        let deliveries = webhooks.iter().map(|webhook| async {
+            let result = self.deliver_with_retry(webhook, &body).await;
+            if let Err(e) = &result {
+                warn!("Webhook {} for room {} failed: {}", webhook.id, webhook.room_id, e);
+            }
+            (webhook.id, result)
+        });
+        join_all(deliveries).await
+    }
+
+    async fn payload(&self, message: &Message) -> Result<Vec<u8>, BotError> {
+        let room = self.db.get_room_by_id(message.room_id).await?.ok_or_else(|| {
+            BotError::Database(crate::errors::DatabaseError::DataIntegrity {
+                reason: "Message room not found".to_string(),
+            })
+        })?;
+        let creator = self.db.get_user_by_id(message.creator_id).await?.ok_or_else(|| {
+            BotError::Database(crate::errors::DatabaseError::DataIntegrity {
+                reason: "Message creator not found".to_string(),
+            })
+        })?;
+
+        let payload = WebhookPayload {
+            user: WebhookUser { id: creator.id, name: creator.name },
+            room: WebhookRoom {
+                id: room.id,
+                name: room.name,
+                path: format!("/rooms/{}", room.id.0),
+            },
+            message: WebhookMessage {
+                id: message.id,
+                body: WebhookMessageBody {
+                    html: message.display_content().to_string(),
+                    plain: message.content.clone(),
+                },
+                path: format!("/rooms/{}/messages/{}", room.id.0, message.id.0),
+            },
+        };
+        Ok(serde_json::to_vec(&payload)?)
+    }
+
+    async fn deliver_with_retry(&self, webhook: &RoomWebhook, body: &[u8]) -> Result<(), BotError> {
+        let signature = sign(&webhook.secret, body);
+        // Client errors won't get better by retrying
+        self.delivery
+            .post(&webhook.url, &[(SIGNATURE_HEADER, &signature)], body, |status| status.is_server_error())
+            .await
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for RoomWebhookService {
+    async fn handle(&self, event: &DomainEvent) {
+        let service = self.clone();
+        spawn_for_new_message(event, |message| async move {
+            service.deliver_message(&message).await;
+        });
+    }
+}
+
+fn generate_secret() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    hex::encode(bytes)
+}
+
+/// `sha256=<hex>` HMAC of the body, for receivers to verify
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, http::HeaderMap, routing::post, Router};
+    use std::sync::Mutex;
+    use uuid::Uuid;
+
+    type Received = Arc<Mutex<Vec<(String, String, Vec<u8>)>>>;
+
+    /// Records every request and answers 200, except `/flaky` which fails once
+    async fn spawn_receiver() -> (String, Received) {
+        let received: Received = Arc::default();
+        let app = Router::new()
+            .route(
+                "/:endpoint",
+                post(
+                    |State(received): State<Received>,
+                     axum::extract::Path(endpoint): axum::extract::Path<String>,
+                     headers: HeaderMap,
+                     body: axum::body::Bytes| async move {
+                        let signature = headers[SIGNATURE_HEADER].to_str().unwrap().to_string();
+                        let mut received = received.lock().unwrap();
+                        let attempts = received.iter().filter(|(e, _, _)| *e == endpoint).count();
+                        received.push((endpoint.clone(), signature, body.to_vec()));
+                        if endpoint == "flaky" && attempts == 0 {
+                            axum::http::StatusCode::SERVICE_UNAVAILABLE
+                        } else {
+                            axum::http::StatusCode::OK
+                        }
+                    },
+                ),
+            )
+            .with_state(received.clone());
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+        (format!("http://{}", addr), received)
+    }
+
+    async fn create_room_with_author(db: &CampfireDatabase) -> (RoomId, UserId) {
+        let user_id = UserId::new();
+        db.create_user(User {
+            id: user_id,
+            ..User::for_tests("Author")
+        }).await.unwrap();
+
+        let room_id = RoomId::new();
+        db.create_room(Room {
+            id: room_id,
+            ..Room::for_tests(&format!("Deploys {}", room_id.0), RoomType::Open)
+        }).await.unwrap();
+
+        (room_id, user_id)
+    }
+
+    #[tokio::test]
+    async fn test_message_fans_out_to_each_webhook() {
+        let (base_url, received) = spawn_receiver().await;
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        // The receiver listens on loopback
+        let service = RoomWebhookService::new(db.clone())
+            .with_retry(3, Duration::from_millis(10))
+            .with_url_policy(WebhookUrlPolicy::default().with_private_networks(true));
+        let (room_id, author_id) = create_room_with_author(&db).await;
+
+        let first = service.create_webhook(room_id, format!("{}/first", base_url)).await.unwrap();
+        let flaky = service.create_webhook(room_id, format!("{}/flaky", base_url)).await.unwrap();
+        let disabled = service.create_webhook(room_id, format!("{}/disabled", base_url)).await.unwrap();
+        service.set_enabled(room_id, disabled.id, false).await.unwrap();
+        assert_ne!(first.secret, flaky.secret);
+
+        let message = Message::new(room_id, author_id, "shipped".to_string(), Uuid::new_v4());
+        db.create_message_with_deduplication(message.clone()).await.unwrap();
+        let results = service.deliver_message(&message).await;
+
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+
+        let received = received.lock().unwrap();
+        let endpoints: Vec<&str> = received.iter().map(|(e, _, _)| e.as_str()).collect();
+        assert_eq!(endpoints.iter().filter(|e| **e == "first").count(), 1);
+        // The failing endpoint was retried without a second delivery elsewhere
+        assert_eq!(endpoints.iter().filter(|e| **e == "flaky").count(), 2);
+        assert!(!endpoints.contains(&"disabled"));
+
+        // Each delivery is signed with its own webhook's secret
+        for (endpoint, signature, body) in received.iter() {
+            let secret = if endpoint == "first" { &first.secret } else { &flaky.secret };
+            assert_eq!(signature, &sign(secret, body));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_webhooks_are_scoped_to_their_room() {
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let service = RoomWebhookService::new(db.clone());
+        let (room_id, _) = create_room_with_author(&db).await;
+        let (other_room, _) = create_room_with_author(&db).await;
+
+        assert!(matches!(
+            service.create_webhook(room_id, "ftp://example.com".to_string()).await,
+            Err(BotError::InvalidWebhookUrl { .. })
+        ));
+
+        let webhook = service.create_webhook(room_id, "https://example.com/hook".to_string()).await.unwrap();
+        assert!(matches!(
+            service.delete_webhook(other_room, webhook.id).await,
+            Err(BotError::WebhookNotFound { .. })
+        ));
+
+        service.delete_webhook(room_id, webhook.id).await.unwrap();
+        assert!(service.list_webhooks(room_id).await.unwrap().is_empty());
+    }
+}
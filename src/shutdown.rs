@@ -7,6 +7,9 @@ use tokio::time::timeout;
 use tracing::{error, info, warn};
 use futures_util::stream::StreamExt;
 
+use crate::database::CampfireDatabase;
+use crate::services::ConnectionManager;
+
 /// Shutdown coordinator that manages graceful shutdown of all components
 pub struct ShutdownCoordinator {
     /// Broadcast sender for shutdown signals
@@ -242,6 +245,37 @@ impl Resource for DatabaseResource {
     }
 }
 
+/// Drains the database writer so writes accepted before shutdown reach disk,
+/// then closes the pools
+pub struct DatabaseDrainResource {
+    name: String,
+    db: CampfireDatabase,
+    timeout: Duration,
+}
+
+impl DatabaseDrainResource {
+    pub fn new(name: String, db: CampfireDatabase, timeout: Duration) -> Self {
+        Self { name, db, timeout }
+    }
+}
+
+#[async_trait::async_trait]
+impl Resource for DatabaseDrainResource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    async fn cleanup(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let report = self.db.shutdown(self.timeout).await?;
+        if report.dropped > 0 {
+            error!("Dropped {} queued writes still pending after {:?} for: {}",
+                   report.dropped, self.timeout, self.name);
+        }
+        info!("Applied {} queued writes and closed database: {}", report.applied, self.name);
+        Ok(())
+    }
+}
+
 /// WebSocket connection resource
 pub struct WebSocketResource {
     name: String,
@@ -268,6 +302,40 @@ impl Resource for WebSocketResource {
     }
 }
 
+/// Drains live WebSocket connections, telling clients when to reconnect
+pub struct WebSocketDrainResource {
+    name: String,
+    connection_manager: Arc<dyn ConnectionManager>,
+    reconnect_after: Duration,
+    timeout: Duration,
+}
+
+impl WebSocketDrainResource {
+    pub fn new(
+        name: String,
+        connection_manager: Arc<dyn ConnectionManager>,
+        reconnect_after: Duration,
+        timeout: Duration,
+    ) -> Self {
+        Self { name, connection_manager, reconnect_after, timeout }
+    }
+}
+
+#[async_trait::async_trait]
+impl Resource for WebSocketDrainResource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    async fn cleanup(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let closed = self.connection_manager
+            .drain_connections(self.reconnect_after, self.timeout)
+            .await?;
+        info!("Closed {} WebSocket connections for: {}", closed, self.name);
+        Ok(())
+    }
+}
+
 /// Background task resource
 #[allow(dead_code)]
 pub struct BackgroundTaskResource {
@@ -455,6 +523,112 @@ mod tests {
         manager.cleanup_all().await;
     }
     
+    #[tokio::test]
+    async fn test_shutdown_sends_server_shutdown_before_closing() {
+        let db = Arc::new(crate::database::CampfireDatabase::new(":memory:").await.unwrap());
+        let connection_manager = Arc::new(crate::ConnectionManagerImpl::new(db));
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+        let connection_id = crate::models::ConnectionId::new();
+        connection_manager
+            .add_connection(crate::models::UserId::new(), connection_id, tx)
+            .await
+            .unwrap();
+        connection_manager
+            .set_protocol_version(connection_id, crate::models::WS_PROTOCOL_VERSION)
+            .await
+            .unwrap();
+        
+        let mut manager = ResourceManager::new();
+        manager.add_resource(WebSocketDrainResource::new(
+            "websocket_connections".to_string(),
+            connection_manager.clone(),
+            Duration::from_secs(7),
+            Duration::from_secs(5),
+        ));
+        let manager = Arc::new(manager);
+        
+        let mut coordinator = ShutdownCoordinator::new();
+        coordinator.add_task("resource_cleanup".to_string(), Duration::from_secs(5), move || {
+            let manager = manager.clone();
+            tokio::spawn(async move { manager.cleanup_all().await })
+        });
+        
+        // Stands in for the socket task: forwards until the channel closes,
+        // then deregisters the connection
+        let socket = tokio::spawn(async move {
+            let mut frames = Vec::new();
+            while let Some(frame) = rx.recv().await {
+                frames.push(frame);
+            }
+            connection_manager.remove_connection(connection_id).await.unwrap();
+            frames
+        });
+        
+        coordinator.shutdown(ShutdownSignal::Application).await;
+        
+        let frames = socket.await.unwrap();
+        assert_eq!(frames.len(), 1);
+        let frame: serde_json::Value = serde_json::from_str(&frames[0]).unwrap();
+        assert_eq!(frame["type"], "ServerShutdown");
+        assert_eq!(frame["reconnect_after"], 7);
+    }
+    
+    #[tokio::test]
+    async fn test_database_drain_persists_writes_queued_before_shutdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!("sqlite://{}?mode=rwc", dir.path().join("campfire.db").display());
+        let db = CampfireDatabase::new(&url).await.unwrap();
+        let user_id = crate::models::UserId::new();
+        let room_id = crate::models::RoomId::new();
+        db.create_user(crate::models::User {
+            id: user_id,
+            ..crate::models::User::for_tests("Sender")
+        }).await.unwrap();
+        db.create_room(crate::models::Room {
+            id: room_id,
+            ..crate::models::Room::for_tests("Deploys", crate::models::RoomType::Open)
+        }).await.unwrap();
+        
+        let message = |content: String| {
+            crate::models::Message::new(room_id, user_id, content, uuid::Uuid::new_v4())
+        };
+        
+        // Queue a burst of writes and start shutting down before they finish
+        let sends: Vec<_> = (0..50)
+            .map(|n| {
+                let db = db.clone();
+                let message = message(format!("in flight {}", n));
+                tokio::spawn(async move { db.create_message_with_deduplication(message).await })
+            })
+            .collect();
+        tokio::task::yield_now().await;
+        
+        let mut manager = ResourceManager::new();
+        manager.add_resource(DatabaseDrainResource::new(
+            "campfire_db".to_string(),
+            db.clone(),
+            Duration::from_secs(5),
+        ));
+        manager.cleanup_all().await;
+        
+        for send in sends {
+            assert!(send.await.unwrap().is_ok());
+        }
+        
+        // Writes arriving after the drain started are refused
+        assert!(matches!(
+            db.create_message_with_deduplication(message("too late".to_string())).await,
+            Err(crate::errors::DatabaseError::WriterChannelClosed)
+        ));
+        
+        let reopened = CampfireDatabase::new(&url).await.unwrap();
+        let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages")
+            .fetch_one(reopened.pool())
+            .await
+            .unwrap();
+        assert_eq!(stored, 50);
+    }
+    
     #[tokio::test]
     async fn test_startup_validator() {
         let mut validator = StartupValidator::new();
@@ -1,6 +1,10 @@
+use dashmap::{mapref::entry::Entry, DashMap};
 use rust_embed::RustEmbed;
 use std::collections::HashMap;
 use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::models::RoomId;
 
 /// Embedded sound assets using rust-embed
 /// 
@@ -205,10 +209,77 @@ impl SoundPlayback {
     }
 }
 
+/// Which sounds a room lets `/play` trigger; room admins choose it
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum SoundPolicy {
+    /// Every sound plays
+    #[default]
+    Any,
+    /// No sounds play
+    Disabled,
+    /// Only the listed sounds play
+    Allowlist { sounds: Vec<String> },
+}
+
+impl SoundPolicy {
+    pub fn permits(&self, sound_name: &str) -> bool {
+        match self {
+            SoundPolicy::Any => true,
+            SoundPolicy::Disabled => false,
+            SoundPolicy::Allowlist { sounds } => sounds.iter().any(|sound| sound == sound_name),
+        }
+    }
+}
+
+/// Lets each room play at most one sound per cooldown window
+#[derive(Default)]
+pub struct SoundCooldown {
+    last_played: DashMap<RoomId, Instant>,
+}
+
+impl SoundCooldown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Claims the room's next playback, false while the room is cooling down.
+    /// A zero cooldown never blocks.
+    pub fn try_play(&self, room_id: RoomId, cooldown: Duration) -> bool {
+        let now = Instant::now();
+        match self.last_played.entry(room_id) {
+            Entry::Occupied(mut last_played) => {
+                if cooldown.is_zero() || now.duration_since(*last_played.get()) >= cooldown {
+                    last_played.insert(now);
+                    true
+                } else {
+                    false
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(now);
+                true
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     
+    #[test]
+    fn test_sound_cooldown_per_room() {
+        let cooldown = SoundCooldown::new();
+        let room = RoomId::new();
+        let other_room = RoomId::new();
+        
+        assert!(cooldown.try_play(room, Duration::from_secs(60)));
+        assert!(!cooldown.try_play(room, Duration::from_secs(60)));
+        assert!(cooldown.try_play(other_room, Duration::from_secs(60)));
+        assert!(cooldown.try_play(room, Duration::ZERO));
+    }
+    
     #[test]
     fn test_sound_assets_embedded() {
         // Test that we can access embedded sound files
@@ -0,0 +1,71 @@
+//! Content-type allowlists for attachments
+//!
+//! An allowlist holds MIME types (`application/pdf`) and wildcards over a
+//! top-level type (`image/*`). An empty allowlist allows every type. Rooms
+//! may set their own list, which replaces the configured one; a room without
+//! one inherits it.
+
+/// Checks and normalizes allowlist entries, returning the first bad entry
+pub fn normalize(types: &[String]) -> Result<Vec<String>, String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(types.len());
+    for entry in types {
+        let entry = entry.trim().to_lowercase();
+        if !is_valid_pattern(&entry) {
+            return Err(entry);
+        }
+        if !normalized.contains(&entry) {
+            normalized.push(entry);
+        }
+    }
+    Ok(normalized)
+}
+
+fn is_valid_pattern(pattern: &str) -> bool {
+    let Some((top, sub)) = pattern.split_once('/') else {
+        return false;
+    };
+    let is_token = |part: &str| {
+        !part.is_empty()
+            && part.chars().all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+    };
+    is_token(top) && (sub == "*" || is_token(sub))
+}
+
+/// Whether `content_type` (parameters such as `; charset=` are ignored)
+/// matches the allowlist
+pub fn is_allowed(allowed: &[String], content_type: &str) -> bool {
+    if allowed.is_empty() {
+        return true;
+    }
+
+    let mime = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+    let Some((top, _)) = mime.split_once('/') else {
+        return false;
+    };
+    allowed.iter().any(|pattern| match pattern.strip_suffix("/*") {
+        Some(allowed_top) => allowed_top == top,
+        None => *pattern == mime,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowlist_matching() {
+        let allowed = vec!["image/*".to_string(), "application/pdf".to_string()];
+        assert!(is_allowed(&allowed, "image/png"));
+        assert!(is_allowed(&allowed, "Application/PDF; name=brief.pdf"));
+        assert!(!is_allowed(&allowed, "application/zip"));
+        assert!(!is_allowed(&allowed, "imagepng"));
+        assert!(is_allowed(&[], "application/zip"));
+
+        assert_eq!(
+            normalize(&[" IMAGE/* ".to_string(), "image/*".to_string()]),
+            Ok(vec!["image/*".to_string()])
+        );
+        assert_eq!(normalize(&["*/*".to_string()]), Err("*/*".to_string()));
+        assert_eq!(normalize(&["pdf".to_string()]), Err("pdf".to_string()));
+    }
+}
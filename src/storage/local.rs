@@ -0,0 +1,131 @@
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::{validate_key, BlobStore};
+use crate::errors::StorageError;
+
+/// Stores blobs as files under a root directory
+#[derive(Debug, Clone)]
+pub struct LocalBlobStore {
+    root: PathBuf,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn path_for(&self, key: &str) -> Result<PathBuf, StorageError> {
+        validate_key(key)?;
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+    async fn put(&self, key: &str, data: Vec<u8>, _content_type: &str) -> Result<(), StorageError> {
+        let path = self.path_for(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, data).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        let path = self.path_for(key)?;
+        match tokio::fs::read(path).await {
+            Ok(data) => Ok(data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(StorageError::NotFound { key: key.to_string() })
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let path = self.path_for(key)?;
+        match tokio::fs::remove_file(path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list(&self) -> Result<Option<Vec<(String, u64)>>, StorageError> {
+        let mut blobs = Vec::new();
+        let mut pending = vec![self.root.clone()];
+
+        while let Some(dir) = pending.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let metadata = entry.metadata().await?;
+                if metadata.is_dir() {
+                    pending.push(entry.path());
+                    continue;
+                }
+                let Ok(relative) = entry.path().strip_prefix(&self.root).map(|path| path.to_path_buf()) else {
+                    continue;
+                };
+                let key = relative
+                    .components()
+                    .map(|part| part.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                // Anything that isn't a valid key wasn't stored through us
+                if validate_key(&key).is_ok() {
+                    blobs.push((key, metadata.len()));
+                }
+            }
+        }
+
+        Ok(Some(blobs))
+    }
+
+    fn presigned_upload_url(&self, key: &str, _expires_in: Duration) -> Result<Option<String>, StorageError> {
+        validate_key(key)?;
+        Ok(None)
+    }
+
+    async fn presigned_download_url(&self, key: &str, _expires_in: Duration) -> Result<Option<String>, StorageError> {
+        validate_key(key)?;
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LocalBlobStore::new(dir.path().to_path_buf());
+
+        store.put("attachments/1/hello.txt", b"hello".to_vec(), "text/plain").await.unwrap();
+        assert_eq!(store.get("attachments/1/hello.txt").await.unwrap(), b"hello");
+
+        store.delete("attachments/1/hello.txt").await.unwrap();
+        assert!(matches!(
+            store.get("attachments/1/hello.txt").await,
+            Err(StorageError::NotFound { .. })
+        ));
+
+        // Deleting twice is fine
+        store.delete("attachments/1/hello.txt").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_local_rejects_traversal() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LocalBlobStore::new(dir.path().to_path_buf());
+
+        let result = store.put("../escape.txt", b"x".to_vec(), "text/plain").await;
+        assert!(matches!(result, Err(StorageError::InvalidKey { .. })));
+    }
+}
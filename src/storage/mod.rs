@@ -0,0 +1,141 @@
+//! Blob storage for uploaded files
+//!
+//! Files are addressed by an opaque key (e.g. `attachments/<uuid>/photo.png`).
+//! The local backend stores them on disk and suits single-node deployments;
+//! the S3 backend targets any S3-compatible service so several nodes can
+//! share storage, and hands out presigned URLs so large transfers bypass
+//! the app server.
+
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::config::{StorageBackend, StorageConfig};
+use crate::errors::StorageError;
+
+pub mod content_types;
+pub mod local;
+pub mod quota;
+pub mod s3;
+pub mod scan;
+pub mod thumbnails;
+
+pub use local::LocalBlobStore;
+pub use quota::{QuotaBlobStore, StoredBlob};
+pub use s3::S3BlobStore;
+pub use scan::{AttachmentScanner, ClamAvScanner, NoopScanner, ScanVerdict};
+pub use thumbnails::ThumbnailLimits;
+
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Stores `data` under `key`, replacing any existing blob
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<(), StorageError>;
+
+    /// Reads the blob stored under `key`
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StorageError>;
+
+    /// Removes the blob; deleting a missing key is not an error
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+
+    /// Every stored key with its size, or None if the backend can't list
+    async fn list(&self) -> Result<Option<Vec<(String, u64)>>, StorageError> {
+        Ok(None)
+    }
+
+    /// URL a client can PUT the blob to directly, if the backend supports it
+    fn presigned_upload_url(&self, key: &str, expires_in: Duration) -> Result<Option<String>, StorageError>;
+
+    /// URL a client can GET the blob from directly, if the backend supports it
+    /// and the blob may be read
+    async fn presigned_download_url(&self, key: &str, expires_in: Duration) -> Result<Option<String>, StorageError>;
+}
+
+/// Rejects keys that could escape the storage root or confuse URL signing
+pub fn validate_key(key: &str) -> Result<(), StorageError> {
+    let invalid = key.is_empty()
+        || key.len() > 1024
+        || key.starts_with('/')
+        || key.contains('\\')
+        || key.contains('\0')
+        || key.split('/').any(|segment| segment.is_empty() || segment == "." || segment == "..");
+
+    if invalid {
+        return Err(StorageError::InvalidKey { key: key.to_string() });
+    }
+    Ok(())
+}
+
+/// Builds the configured blob store, falling back to local storage when the
+/// S3 backend is selected but not fully configured
+pub fn create_blob_store(config: &StorageConfig) -> Arc<dyn BlobStore> {
+    if matches!(config.backend, StorageBackend::S3) {
+        match &config.s3 {
+            Some(s3_config) => {
+                info!("Using S3 blob storage (bucket: {})", s3_config.bucket);
+                return Arc::new(S3BlobStore::new(s3_config.clone()));
+            }
+            None => {
+                warn!("S3 storage selected but bucket/credentials are missing; using local storage");
+            }
+        }
+    }
+
+    info!("Using local blob storage at {}", config.local_path.display());
+    Arc::new(LocalBlobStore::new(config.local_path.clone()))
+}
+
+/// Builds the configured virus scanner; uploads aren't scanned without one
+pub fn create_scanner(config: &StorageConfig) -> Arc<dyn AttachmentScanner> {
+    match &config.clamav_address {
+        Some(address) => {
+            info!("Scanning uploads with clamd at {}", address);
+            Arc::new(ClamAvScanner::new(address.clone()))
+        }
+        None => Arc::new(NoopScanner),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::S3Config;
+
+    #[test]
+    fn test_validate_key() {
+        assert!(validate_key("attachments/abc/photo.png").is_ok());
+        assert!(validate_key("").is_err());
+        assert!(validate_key("/etc/passwd").is_err());
+        assert!(validate_key("attachments/../../etc/passwd").is_err());
+        assert!(validate_key("attachments//double").is_err());
+        assert!(validate_key("windows\\path").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unconfigured_s3_falls_back_to_local() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = StorageConfig {
+            backend: StorageBackend::S3,
+            local_path: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        // Local storage has no presigned URLs
+        let store = create_blob_store(&config);
+        assert!(store.presigned_download_url("a/b", Duration::from_secs(60)).await.unwrap().is_none());
+
+        let config = StorageConfig {
+            s3: Some(S3Config {
+                endpoint: "http://127.0.0.1:9000".to_string(),
+                bucket: "campfire".to_string(),
+                region: "us-east-1".to_string(),
+                access_key_id: "key".to_string(),
+                secret_access_key: "secret".to_string(),
+                path_style: true,
+            }),
+            ..config
+        };
+        let store = create_blob_store(&config);
+        assert!(store.presigned_download_url("a/b", Duration::from_secs(60)).await.unwrap().is_some());
+    }
+}
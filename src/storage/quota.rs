@@ -0,0 +1,652 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use metrics::{counter, gauge};
+use rand::Rng;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tracing::{error, info, warn};
+
+use super::content_types;
+use super::scan::{AttachmentScanner, NoopScanner, ScanVerdict};
+use super::thumbnails::ThumbnailLimits;
+use super::BlobStore;
+use crate::config::StorageConfig;
+use crate::database::CampfireDatabase;
+use crate::errors::StorageError;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::models::{BlobOwner, BlobUsage, RoomId, UserId};
+use crate::validation::AttachmentLimits;
+
+/// Where uploaded attachments are kept
+const ATTACHMENT_PREFIX: &str = "attachments/";
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Default)]
+struct Usage {
+    total: u64,
+    per_user: HashMap<UserId, u64>,
+}
+
+impl Usage {
+    fn add(&mut self, user_id: Option<UserId>, bytes: u64) {
+        self.total += bytes;
+        if let Some(user_id) = user_id {
+            *self.per_user.entry(user_id).or_default() += bytes;
+        }
+    }
+
+    fn release(&mut self, user_id: Option<UserId>, bytes: u64) {
+        self.total = self.total.saturating_sub(bytes);
+        if let Some(used) = user_id.and_then(|user_id| self.per_user.get_mut(&user_id)) {
+            *used = used.saturating_sub(bytes);
+        }
+    }
+}
+
+fn release_usage(usage: &Mutex<Usage>, user_id: Option<UserId>, bytes: u64) {
+    let mut usage = usage.lock().unwrap();
+    usage.release(user_id, bytes);
+    gauge!("storage_used_bytes", usage.total as f64);
+}
+
+/// Enforces the storage quotas and upload concurrency in front of a backend
+///
+/// Every blob written through here is recorded in the `blobs` table with its
+/// size and owner, and the running totals are seeded from that table by
+/// [`load_usage`](Self::load_usage) at startup. Over the per-user quota is 413,
+/// over the total quota is 507. Presigned uploads go straight to the backend
+/// and aren't counted.
+///
+/// Uploads are virus-scanned before they're stored. A flagged upload is
+/// rejected (422) and never kept. If the scan outlasts the scan timeout the
+/// upload is stored quarantined, reads of it (and requests for a presigned
+/// download URL) fail with 423, and the scan
+/// finishes in the background: clean releases it, flagged deletes it.
+/// Presigned uploads skip the scanner just as they skip the quotas.
+///
+/// Attachments posted to a room must match the room's content-type
+/// allowlist, or the configured one when the room has none (415). The type
+/// each blob was stored with is recorded so it can be served back with it.
+pub struct QuotaBlobStore {
+    inner: Arc<dyn BlobStore>,
+    db: Arc<CampfireDatabase>,
+    /// 0 = unlimited
+    quota_bytes: u64,
+    /// 0 = unlimited
+    user_quota_bytes: u64,
+    usage: Arc<Mutex<Usage>>,
+    uploads: Semaphore,
+    scanner: Arc<dyn AttachmentScanner>,
+    scan_timeout: Duration,
+    /// Empty = any type
+    allowed_types: Vec<String>,
+    /// Empty = none
+    previewable_types: Vec<String>,
+    attachment_origin: Option<String>,
+    /// How long a link to the attachment origin stays valid
+    origin_link_ttl: Duration,
+    // The origin doesn't get the session cookie, so it's sent signed links
+    // instead, keyed from the configured secret so they survive restarts and
+    // verify on every node
+    signing_key: [u8; 32],
+    attachment_limits: AttachmentLimits,
+    thumbnail_limits: ThumbnailLimits,
+}
+
+/// A stored blob with the content type it was uploaded with
+#[derive(Debug, Clone)]
+pub struct StoredBlob {
+    pub data: Vec<u8>,
+    pub content_type: String,
+}
+
+impl QuotaBlobStore {
+    pub fn new(inner: Arc<dyn BlobStore>, db: Arc<CampfireDatabase>, config: &StorageConfig) -> Self {
+        Self {
+            inner,
+            db,
+            quota_bytes: config.quota_bytes,
+            user_quota_bytes: config.user_quota_bytes,
+            usage: Arc::new(Mutex::new(Usage::default())),
+            uploads: Semaphore::new(config.max_concurrent_uploads),
+            scanner: Arc::new(NoopScanner),
+            scan_timeout: Duration::from_millis(config.scan_timeout_ms),
+            allowed_types: config.allowed_attachment_types.clone(),
+            previewable_types: config.previewable_attachment_types.clone(),
+            attachment_origin: config.attachment_origin.clone(),
+            origin_link_ttl: Duration::from_secs(config.presign_expiry_secs),
+            signing_key: signing_key(config),
+            attachment_limits: AttachmentLimits {
+                max_count: config.max_attachments_per_message,
+                max_total_bytes: config.max_attachment_bytes_per_message,
+            },
+            thumbnail_limits: ThumbnailLimits::from_config(config),
+        }
+    }
+
+    /// Scans every upload with `scanner` before it can be read
+    pub fn with_scanner(mut self, scanner: Arc<dyn AttachmentScanner>) -> Self {
+        self.scanner = scanner;
+        self
+    }
+
+    /// Counts what is already stored; call once before accepting uploads
+    ///
+    /// Attachments the backend holds without a record (written before
+    /// uploads were recorded) are recorded first, unowned, so they count
+    /// toward the total quota. Other files, such as export archives, are
+    /// left alone. Backends that can't list are counted from records only.
+    pub async fn load_usage(&self) -> Result<u64, StorageError> {
+        match self.inner.list().await? {
+            Some(objects) => self.record_unaccounted(objects).await?,
+            None => info!("Storage backend can't list its blobs; counting recorded uploads only"),
+        }
+
+        let stored = self.db.get_blob_usage().await.map_err(backend_error)?;
+
+        let mut usage = Usage::default();
+        for BlobUsage { user_id, size_bytes } in stored {
+            usage.add(user_id, size_bytes);
+        }
+        let total = usage.total;
+        *self.usage.lock().unwrap() = usage;
+
+        gauge!("storage_used_bytes", total as f64);
+        Ok(total)
+    }
+
+    async fn record_unaccounted(&self, objects: Vec<(String, u64)>) -> Result<(), StorageError> {
+        let recorded = self.db.get_recorded_blob_keys().await.map_err(backend_error)?;
+
+        let mut backfilled = 0;
+        let unaccounted = objects
+            .into_iter()
+            .filter(|(key, _)| key.starts_with(ATTACHMENT_PREFIX) && !recorded.contains(key));
+        for (key, size) in unaccounted {
+            self.db
+                .record_blob(key, None, None, size, "application/octet-stream".to_string(), false)
+                .await
+                .map_err(backend_error)?;
+            backfilled += 1;
+        }
+        if backfilled > 0 {
+            info!("Recorded {} stored blobs that predate usage tracking", backfilled);
+        }
+        Ok(())
+    }
+
+    pub fn used_bytes(&self) -> u64 {
+        self.usage.lock().unwrap().total
+    }
+
+    /// Whether browsers may display blobs of `content_type` inline
+    pub fn is_previewable(&self, content_type: &str) -> bool {
+        !self.previewable_types.is_empty() && content_types::is_allowed(&self.previewable_types, content_type)
+    }
+
+    /// Caps on what a single message may attach
+    pub fn attachment_limits(&self) -> AttachmentLimits {
+        self.attachment_limits
+    }
+
+    /// Bounds on the thumbnails made for image uploads
+    pub fn thumbnail_limits(&self) -> ThumbnailLimits {
+        self.thumbnail_limits
+    }
+
+    /// Size of a blob `user_id` uploaded, or None if they have no such blob
+    pub async fn owned_blob_size(&self, user_id: UserId, key: &str) -> Result<Option<u64>, StorageError> {
+        self.db.get_blob_size(key, user_id).await.map_err(backend_error)
+    }
+
+    /// Separate origin attachments are served from, if configured
+    pub fn attachment_origin(&self) -> Option<&str> {
+        self.attachment_origin.as_deref()
+    }
+
+    /// Uploader and room of a stored blob (None = no such blob)
+    pub async fn blob_owner(&self, key: &str) -> Result<Option<BlobOwner>, StorageError> {
+        self.db.get_blob_owner(key).await.map_err(backend_error)
+    }
+
+    /// Short-lived link to the blob on the attachment origin, if there is one
+    pub fn signed_origin_url(&self, key: &str) -> Option<String> {
+        let origin = self.attachment_origin.as_deref()?;
+        let expires = Utc::now().timestamp() + self.origin_link_ttl.as_secs() as i64;
+        let signature = hex::encode(self.origin_mac(key, expires).finalize().into_bytes());
+        Some(format!(
+            "{}/api/attachments/{}?expires={}&signature={}",
+            origin.trim_end_matches('/'),
+            key,
+            expires,
+            signature
+        ))
+    }
+
+    /// Whether a link from [`signed_origin_url`](Self::signed_origin_url)
+    /// is genuine and unexpired
+    pub fn verify_origin_link(&self, key: &str, expires: i64, signature: &str) -> bool {
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+        expires >= Utc::now().timestamp() && self.origin_mac(key, expires).verify_slice(&signature).is_ok()
+    }
+
+    fn origin_mac(&self, key: &str, expires: i64) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.signing_key).expect("HMAC accepts keys of any length");
+        mac.update(format!("{}:{}", key, expires).as_bytes());
+        mac
+    }
+
+    /// Reads a blob along with its recorded content type; blobs stored
+    /// without one are `application/octet-stream`
+    pub async fn get_with_type(&self, key: &str) -> Result<StoredBlob, StorageError> {
+        let data = self.get(key).await?;
+        let content_type = self
+            .db
+            .get_blob_content_type(key)
+            .await
+            .map_err(backend_error)?
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+        Ok(StoredBlob { data, content_type })
+    }
+
+    /// Stores an upload and charges it to `user_id`'s quota
+    pub async fn put_for_user(
+        &self,
+        user_id: UserId,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<(), StorageError> {
+        self.put_owned(Some(user_id), None, key, data, content_type).await
+    }
+
+    /// Stores an attachment posted to `room_id`, if its content type is
+    /// allowed there
+    pub async fn put_attachment(
+        &self,
+        user_id: UserId,
+        room_id: RoomId,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<(), StorageError> {
+        let room_types = self.db.get_room_attachment_types(room_id).await.map_err(backend_error)?;
+        let allowed = room_types.as_deref().unwrap_or(&self.allowed_types);
+        if !content_types::is_allowed(allowed, content_type) {
+            counter!("storage_uploads_disallowed_type_total", 1);
+            return Err(StorageError::DisallowedType {
+                content_type: content_type.to_string(),
+            });
+        }
+
+        self.put_owned(Some(user_id), Some(room_id), key, data, content_type).await
+    }
+
+    async fn put_owned(
+        &self,
+        user_id: Option<UserId>,
+        room_id: Option<RoomId>,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<(), StorageError> {
+        let _permit = self.uploads.acquire().await.map_err(|_| StorageError::Backend {
+            reason: "upload limiter closed".to_string(),
+        })?;
+
+        // Reserve before writing so concurrent uploads can't overshoot together
+        let size = data.len() as u64;
+        self.reserve(user_id, size)?;
+
+        let data = Arc::new(data);
+        let mut scan = {
+            let scanner = self.scanner.clone();
+            let key = key.to_string();
+            let data = data.clone();
+            tokio::spawn(async move { scanner.scan(&key, &data).await })
+        };
+
+        let quarantined = match tokio::time::timeout(self.scan_timeout, &mut scan).await {
+            Ok(verdict) => {
+                if let Err(e) = flatten_verdict(key, verdict) {
+                    self.release(user_id, size);
+                    return Err(e);
+                }
+                false
+            }
+            Err(_) => true,
+        };
+
+        let data = Arc::try_unwrap(data).unwrap_or_else(|data| data.as_ref().clone());
+        if let Err(e) = self.inner.put(key, data, content_type).await {
+            self.release(user_id, size);
+            return Err(e);
+        }
+
+        match self.db.record_blob(key.to_string(), user_id, room_id, size, content_type.to_string(), quarantined).await {
+            Ok(replaced) => {
+                if let Some(replaced) = replaced {
+                    self.release(replaced.user_id, replaced.size_bytes);
+                }
+            }
+            Err(e) => {
+                // Unaccounted blobs would escape the quota, so don't keep it
+                let _ = self.inner.delete(key).await;
+                self.release(user_id, size);
+                return Err(backend_error(e));
+            }
+        }
+
+        if quarantined {
+            warn!("Virus scan of {} is taking longer than {:?}; quarantined until it finishes", key, self.scan_timeout);
+            self.finish_scan_in_background(key.to_string(), scan);
+        }
+        Ok(())
+    }
+
+    /// Releases a quarantined blob once its scan passes, or deletes it
+    fn finish_scan_in_background(
+        &self,
+        key: String,
+        scan: tokio::task::JoinHandle<Result<ScanVerdict, StorageError>>,
+    ) {
+        let inner = self.inner.clone();
+        let db = self.db.clone();
+        let usage = self.usage.clone();
+
+        tokio::spawn(async move {
+            match flatten_verdict(&key, scan.await) {
+                Ok(()) => {
+                    if let Err(e) = db.set_blob_quarantined(key.clone(), false).await {
+                        error!("Failed to release scanned blob {}: {}", key, e);
+                    }
+                }
+                Err(e) => {
+                    warn!("Deleting quarantined blob {}: {}", key, e);
+                    if let Err(e) = inner.delete(&key).await {
+                        error!("Failed to delete quarantined blob {}: {}", key, e);
+                    }
+                    match db.delete_blob_record(key.clone()).await {
+                        Ok(Some(deleted)) => release_usage(&usage, deleted.user_id, deleted.size_bytes),
+                        Ok(None) => {}
+                        Err(e) => error!("Failed to drop record of quarantined blob {}: {}", key, e),
+                    }
+                }
+            }
+        });
+    }
+
+    fn reserve(&self, user_id: Option<UserId>, size: u64) -> Result<(), StorageError> {
+        let mut usage = self.usage.lock().unwrap();
+
+        if let Some(user_id) = user_id {
+            let used = usage.per_user.get(&user_id).copied().unwrap_or(0);
+            if self.user_quota_bytes > 0 && used + size > self.user_quota_bytes {
+                return Err(StorageError::QuotaExceeded { limit: self.user_quota_bytes });
+            }
+        }
+        if self.quota_bytes > 0 && usage.total + size > self.quota_bytes {
+            return Err(StorageError::StorageFull);
+        }
+
+        usage.add(user_id, size);
+        gauge!("storage_used_bytes", usage.total as f64);
+        Ok(())
+    }
+
+    fn release(&self, user_id: Option<UserId>, size: u64) {
+        release_usage(&self.usage, user_id, size);
+    }
+}
+
+fn backend_error(e: crate::errors::DatabaseError) -> StorageError {
+    StorageError::Backend { reason: e.to_string() }
+}
+
+/// Anything but a clean verdict rejects the upload; a scan that couldn't run
+/// counts as flagged
+fn flatten_verdict(
+    key: &str,
+    verdict: Result<Result<ScanVerdict, StorageError>, tokio::task::JoinError>,
+) -> Result<(), StorageError> {
+    let verdict = verdict.map_err(|e| StorageError::Backend { reason: format!("virus scan failed: {}", e) })?;
+    match verdict {
+        Ok(ScanVerdict::Clean) => Ok(()),
+        Ok(ScanVerdict::Infected { signature }) => {
+            counter!("storage_scan_flagged_total", 1);
+            Err(StorageError::Infected { key: key.to_string(), signature })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[async_trait]
+impl BlobStore for QuotaBlobStore {
+    /// Unowned blobs only count toward the total quota
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<(), StorageError> {
+        self.put_owned(None, None, key, data, content_type).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        if self.db.is_blob_quarantined(key).await.map_err(backend_error)? {
+            return Err(StorageError::Quarantined { key: key.to_string() });
+        }
+        self.inner.get(key).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.inner.delete(key).await?;
+
+        if let Some(deleted) = self.db.delete_blob_record(key.to_string()).await.map_err(backend_error)? {
+            self.release(deleted.user_id, deleted.size_bytes);
+        }
+        Ok(())
+    }
+
+    fn presigned_upload_url(&self, key: &str, expires_in: Duration) -> Result<Option<String>, StorageError> {
+        self.inner.presigned_upload_url(key, expires_in)
+    }
+
+    /// Quarantined blobs get no URL, just as reads of them fail
+    async fn presigned_download_url(&self, key: &str, expires_in: Duration) -> Result<Option<String>, StorageError> {
+        if self.db.is_blob_quarantined(key).await.map_err(backend_error)? {
+            return Err(StorageError::Quarantined { key: key.to_string() });
+        }
+        self.inner.presigned_download_url(key, expires_in).await
+    }
+}
+
+/// Deletes the uploads of permanently deleted rooms, releasing their quota
+#[async_trait]
+impl EventSubscriber for QuotaBlobStore {
+    async fn handle(&self, event: &DomainEvent) {
+        if let DomainEvent::RoomDeleted { room_id, blob_keys } = event {
+            for key in blob_keys {
+                if let Err(e) = self.delete(key).await {
+                    warn!("Failed to delete blob {} of deleted room {}: {}", key, room_id, e);
+                }
+            }
+            if !blob_keys.is_empty() {
+                info!("Deleted {} blob(s) of deleted room {}", blob_keys.len(), room_id);
+            }
+        }
+    }
+}
+
+/// Key for origin links, derived from the configured secret. Without an
+/// attachment origin no links are handed out, so a throwaway key will do.
+fn signing_key(config: &StorageConfig) -> [u8; 32] {
+    match &config.attachment_signing_secret {
+        Some(secret) => {
+            let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+            mac.update(b"campfire attachment origin links");
+            mac.finalize().into_bytes().into()
+        }
+        None => rand::thread_rng().gen(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::User;
+    use crate::storage::LocalBlobStore;
+
+    fn config(user_quota_bytes: u64, quota_bytes: u64) -> StorageConfig {
+        StorageConfig {
+            quota_bytes,
+            user_quota_bytes,
+            ..Default::default()
+        }
+    }
+
+    async fn create_test_user(db: &CampfireDatabase) -> UserId {
+        let user_id = UserId::new();
+        db.create_user(User {
+            id: user_id,
+            ..User::for_tests("Uploader")
+        }).await.unwrap();
+        user_id
+    }
+
+    #[tokio::test]
+    async fn test_upload_over_user_quota_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let inner = Arc::new(LocalBlobStore::new(dir.path().to_path_buf()));
+        let store = QuotaBlobStore::new(inner, db.clone(), &config(10, 0));
+        let user_id = create_test_user(&db).await;
+        let other_id = create_test_user(&db).await;
+
+        store.put_for_user(user_id, "attachments/1/a.txt", vec![0; 6], "text/plain").await.unwrap();
+        store.put_for_user(user_id, "attachments/2/b.txt", vec![0; 4], "text/plain").await.unwrap();
+
+        let result = store.put_for_user(user_id, "attachments/3/c.txt", vec![0; 1], "text/plain").await;
+        assert!(matches!(result, Err(StorageError::QuotaExceeded { limit: 10 })));
+        assert!(matches!(store.get("attachments/3/c.txt").await, Err(StorageError::NotFound { .. })));
+
+        // Quotas are per user, and deleting frees space
+        store.put_for_user(other_id, "attachments/4/d.txt", vec![0; 10], "text/plain").await.unwrap();
+        store.delete("attachments/1/a.txt").await.unwrap();
+        store.put_for_user(user_id, "attachments/3/c.txt", vec![0; 6], "text/plain").await.unwrap();
+
+        // A restart recounts what's stored
+        let reloaded = QuotaBlobStore::new(
+            Arc::new(LocalBlobStore::new(dir.path().to_path_buf())),
+            db,
+            &config(10, 0),
+        );
+        assert_eq!(reloaded.load_usage().await.unwrap(), 20);
+        let result = reloaded.put_for_user(user_id, "attachments/5/e.txt", vec![0; 1], "text/plain").await;
+        assert!(matches!(result, Err(StorageError::QuotaExceeded { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_load_usage_backfills_unrecorded_blobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let inner = Arc::new(LocalBlobStore::new(dir.path().to_path_buf()));
+        // Written straight to disk, as uploads were before they were recorded
+        inner.put("attachments/old/report.pdf", vec![0; 12], "application/pdf").await.unwrap();
+        inner.put("exports/archive.zip", vec![0; 40], "application/zip").await.unwrap();
+
+        let store = QuotaBlobStore::new(inner.clone(), db.clone(), &config(0, 16));
+        assert_eq!(store.load_usage().await.unwrap(), 12);
+        let result = store.put("attachments/new/more.pdf", vec![0; 5], "application/pdf").await;
+        assert!(matches!(result, Err(StorageError::StorageFull)));
+
+        // Already recorded, so a restart doesn't count it twice
+        let reloaded = QuotaBlobStore::new(inner, db, &config(0, 16));
+        assert_eq!(reloaded.load_usage().await.unwrap(), 12);
+    }
+
+    #[tokio::test]
+    async fn test_total_quota_reports_storage_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let inner = Arc::new(LocalBlobStore::new(dir.path().to_path_buf()));
+        let store = QuotaBlobStore::new(inner, db.clone(), &config(0, 16));
+        let user_id = create_test_user(&db).await;
+
+        store.put_for_user(user_id, "attachments/1/a.txt", vec![0; 8], "text/plain").await.unwrap();
+        let result = store.put("attachments/2/b.txt", vec![0; 9], "text/plain").await;
+        assert!(matches!(result, Err(StorageError::StorageFull)));
+
+        // Replacing a blob only charges the difference
+        store.put_for_user(user_id, "attachments/1/a.txt", vec![0; 5], "text/plain").await.unwrap();
+        assert_eq!(store.used_bytes(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_room_attachment_types_override_the_global_allowlist() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let inner = Arc::new(LocalBlobStore::new(dir.path().to_path_buf()));
+        let mut config = config(0, 0);
+        config.allowed_attachment_types = vec!["image/*".to_string(), "application/pdf".to_string()];
+        let store = QuotaBlobStore::new(inner, db.clone(), &config);
+        let user_id = create_test_user(&db).await;
+
+        let mut room_ids = Vec::new();
+        for name in ["Legal", "Design"] {
+            let room = crate::models::Room::for_tests(name, crate::models::RoomType::Open);
+            db.create_room(room.clone()).await.unwrap();
+            room_ids.push(room.id);
+        }
+        let (legal, design) = (room_ids[0], room_ids[1]);
+        db.set_room_attachment_types(legal, Some(vec!["application/pdf".to_string()])).await.unwrap();
+
+        let result = store.put_attachment(user_id, legal, "attachments/1/logo.png", vec![0; 4], "image/png").await;
+        assert!(matches!(result, Err(StorageError::DisallowedType { .. })));
+        assert!(matches!(store.get("attachments/1/logo.png").await, Err(StorageError::NotFound { .. })));
+        store.put_attachment(user_id, legal, "attachments/2/brief.pdf", vec![0; 4], "application/pdf").await.unwrap();
+
+        // A room without its own list inherits the global one
+        store.put_attachment(user_id, design, "attachments/3/logo.png", vec![0; 4], "image/png").await.unwrap();
+        let result = store.put_attachment(user_id, design, "attachments/4/site.zip", vec![0; 4], "application/zip").await;
+        assert!(matches!(result, Err(StorageError::DisallowedType { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_origin_links_are_signed_per_key_and_expire() {
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let inner = Arc::new(LocalBlobStore::new("unused".into()));
+        let mut config = config(0, 0);
+        config.attachment_origin = Some("https://files.example.com".to_string());
+        config.attachment_signing_secret = Some("0123456789abcdef0123456789abcdef".to_string());
+        let store = QuotaBlobStore::new(inner.clone(), db.clone(), &config);
+
+        let url = store.signed_origin_url("attachments/1/a.png").unwrap();
+        let query = url
+            .strip_prefix("https://files.example.com/api/attachments/attachments/1/a.png?")
+            .unwrap();
+        let params: HashMap<_, _> = query.split('&').filter_map(|pair| pair.split_once('=')).collect();
+        let expires: i64 = params["expires"].parse().unwrap();
+        let signature = params["signature"];
+
+        assert!(store.verify_origin_link("attachments/1/a.png", expires, signature));
+        // Not for another blob, a later expiry, or a garbled signature
+        assert!(!store.verify_origin_link("attachments/2/b.png", expires, signature));
+        assert!(!store.verify_origin_link("attachments/1/a.png", expires + 60, signature));
+        assert!(!store.verify_origin_link("attachments/1/a.png", expires, "not-hex"));
+
+        // Another node (or this one after a restart) with the same secret
+        // accepts the link; one with a different secret doesn't
+        let restarted = QuotaBlobStore::new(inner.clone(), db.clone(), &config);
+        assert!(restarted.verify_origin_link("attachments/1/a.png", expires, signature));
+        config.attachment_signing_secret = Some("fedcba9876543210fedcba9876543210".to_string());
+        let other = QuotaBlobStore::new(inner, db, &config);
+        assert!(!other.verify_origin_link("attachments/1/a.png", expires, signature));
+
+        let past = Utc::now().timestamp() - 1;
+        let stale = hex::encode(store.origin_mac("attachments/1/a.png", past).finalize().into_bytes());
+        assert!(!store.verify_origin_link("attachments/1/a.png", past, &stale));
+    }
+}
@@ -0,0 +1,221 @@
+//! Virus scanning for uploads
+//!
+//! Every upload written through [`QuotaBlobStore`](super::QuotaBlobStore) is
+//! handed to an [`AttachmentScanner`] before it can be read back. The default
+//! scanner accepts everything; [`ClamAvScanner`] asks a clamd daemon over TCP.
+
+use async_trait::async_trait;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::errors::StorageError;
+
+/// Largest chunk sent to clamd in one INSTREAM frame
+const CLAMAV_CHUNK_SIZE: usize = 64 * 1024;
+
+/// How long a whole clamd exchange may take unless configured otherwise
+const CLAMAV_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanVerdict {
+    Clean,
+    /// Flagged by the scanner; `signature` names what it found
+    Infected { signature: String },
+}
+
+#[async_trait]
+pub trait AttachmentScanner: Send + Sync {
+    /// Inspects an upload's bytes. An error means the scan couldn't be done,
+    /// which is treated like a flagged file.
+    async fn scan(&self, key: &str, data: &[u8]) -> Result<ScanVerdict, StorageError>;
+}
+
+/// Accepts every upload; used when no scanner is configured
+pub struct NoopScanner;
+
+#[async_trait]
+impl AttachmentScanner for NoopScanner {
+    async fn scan(&self, _key: &str, _data: &[u8]) -> Result<ScanVerdict, StorageError> {
+        Ok(ScanVerdict::Clean)
+    }
+}
+
+/// Scans uploads with clamd's INSTREAM command
+pub struct ClamAvScanner {
+    /// `host:port` of the clamd TCP socket
+    address: String,
+    timeout: Duration,
+}
+
+impl ClamAvScanner {
+    pub fn new(address: String) -> Self {
+        Self { address, timeout: CLAMAV_TIMEOUT }
+    }
+
+    /// Gives up on a scan (connect, send and verdict) after `timeout`; a
+    /// scan that times out counts as flagged
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn instream(&self, data: &[u8]) -> Result<ScanVerdict, StorageError> {
+        let mut stream = TcpStream::connect(&self.address).await.map_err(scanner_error)?;
+
+        stream.write_all(b"zINSTREAM\0").await.map_err(scanner_error)?;
+        for chunk in data.chunks(CLAMAV_CHUNK_SIZE) {
+            stream.write_all(&(chunk.len() as u32).to_be_bytes()).await.map_err(scanner_error)?;
+            stream.write_all(chunk).await.map_err(scanner_error)?;
+        }
+        stream.write_all(&0u32.to_be_bytes()).await.map_err(scanner_error)?;
+
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply).await.map_err(scanner_error)?;
+        parse_clamd_reply(&String::from_utf8_lossy(&reply))
+    }
+}
+
+#[async_trait]
+impl AttachmentScanner for ClamAvScanner {
+    async fn scan(&self, _key: &str, data: &[u8]) -> Result<ScanVerdict, StorageError> {
+        tokio::time::timeout(self.timeout, self.instream(data))
+            .await
+            .map_err(|_| StorageError::Backend { reason: format!("virus scan timed out after {:?}", self.timeout) })?
+    }
+}
+
+/// Reads clamd's `stream: OK` / `stream: <signature> FOUND` reply
+fn parse_clamd_reply(reply: &str) -> Result<ScanVerdict, StorageError> {
+    let reply = reply.trim_end_matches(['\0', '\n']);
+    let result = reply.strip_prefix("stream: ").unwrap_or(reply);
+
+    if result == "OK" {
+        Ok(ScanVerdict::Clean)
+    } else if let Some(signature) = result.strip_suffix(" FOUND") {
+        Ok(ScanVerdict::Infected { signature: signature.to_string() })
+    } else {
+        Err(StorageError::Backend { reason: format!("clamd: {}", result) })
+    }
+}
+
+fn scanner_error(e: std::io::Error) -> StorageError {
+    StorageError::Backend { reason: format!("virus scanner unavailable: {}", e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::StorageConfig;
+    use crate::database::CampfireDatabase;
+    use crate::models::{User, UserId};
+    use crate::storage::{BlobStore, LocalBlobStore, QuotaBlobStore};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Flags any upload containing the given marker, after an optional delay
+    struct StubScanner {
+        marker: &'static [u8],
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl AttachmentScanner for StubScanner {
+        async fn scan(&self, _key: &str, data: &[u8]) -> Result<ScanVerdict, StorageError> {
+            tokio::time::sleep(self.delay).await;
+            if data.windows(self.marker.len()).any(|window| window == self.marker) {
+                Ok(ScanVerdict::Infected { signature: "Stub-Test-Signature".to_string() })
+            } else {
+                Ok(ScanVerdict::Clean)
+            }
+        }
+    }
+
+    async fn scanned_store(dir: &tempfile::TempDir, delay: Duration, timeout: Duration) -> (QuotaBlobStore, UserId) {
+        let db = Arc::new(CampfireDatabase::new(":memory:").await.unwrap());
+        let user_id = UserId::new();
+        db.create_user(User {
+            id: user_id,
+            ..User::for_tests("Uploader")
+        }).await.unwrap();
+
+        let config = StorageConfig {
+            local_path: dir.path().to_path_buf(),
+            scan_timeout_ms: timeout.as_millis() as u64,
+            ..Default::default()
+        };
+        let inner = Arc::new(LocalBlobStore::new(dir.path().to_path_buf()));
+        let scanner = Arc::new(StubScanner { marker: b"EICAR", delay });
+        let store = QuotaBlobStore::new(inner, db, &config).with_scanner(scanner);
+        (store, user_id)
+    }
+
+    #[test]
+    fn test_parse_clamd_reply() {
+        assert_eq!(parse_clamd_reply("stream: OK\0").unwrap(), ScanVerdict::Clean);
+        assert_eq!(
+            parse_clamd_reply("stream: Eicar-Test-Signature FOUND\0").unwrap(),
+            ScanVerdict::Infected { signature: "Eicar-Test-Signature".to_string() }
+        );
+        assert!(parse_clamd_reply("INSTREAM size limit exceeded. ERROR\0").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unresponsive_clamd_times_out() {
+        // Accepts the connection, then never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (_socket, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(30)).await;
+        });
+
+        let scanner = ClamAvScanner::new(address).with_timeout(Duration::from_millis(50));
+        let result = tokio::time::timeout(Duration::from_secs(5), scanner.scan("a/b", b"data")).await.unwrap();
+        assert!(matches!(result, Err(StorageError::Backend { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_clean_upload_passes_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, user_id) = scanned_store(&dir, Duration::ZERO, Duration::from_secs(1)).await;
+
+        store.put_for_user(user_id, "attachments/1/notes.txt", b"hello".to_vec(), "text/plain").await.unwrap();
+        assert_eq!(store.get("attachments/1/notes.txt").await.unwrap(), b"hello");
+        assert_eq!(store.used_bytes(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_flagged_upload_is_rejected_and_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, user_id) = scanned_store(&dir, Duration::ZERO, Duration::from_secs(1)).await;
+
+        let result = store.put_for_user(user_id, "attachments/1/bad.exe", b"xxEICARxx".to_vec(), "application/octet-stream").await;
+        assert!(matches!(result, Err(StorageError::Infected { ref signature, .. }) if signature == "Stub-Test-Signature"));
+        assert!(matches!(store.get("attachments/1/bad.exe").await, Err(StorageError::NotFound { .. })));
+        assert_eq!(store.used_bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_slow_scan_quarantines_until_verdict() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, user_id) = scanned_store(&dir, Duration::from_millis(200), Duration::from_millis(20)).await;
+
+        // The response doesn't wait for the scan, but the file isn't readable yet
+        store.put_for_user(user_id, "attachments/1/bad.exe", b"EICAR".to_vec(), "application/octet-stream").await.unwrap();
+        assert!(matches!(store.get("attachments/1/bad.exe").await, Err(StorageError::Quarantined { .. })));
+
+        store.put_for_user(user_id, "attachments/2/ok.txt", b"fine".to_vec(), "text/plain").await.unwrap();
+        assert!(matches!(store.get("attachments/2/ok.txt").await, Err(StorageError::Quarantined { .. })));
+        // Nor can a direct download link be had for it
+        assert!(matches!(
+            store.presigned_download_url("attachments/2/ok.txt", Duration::from_secs(60)).await,
+            Err(StorageError::Quarantined { .. })
+        ));
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert!(matches!(store.get("attachments/1/bad.exe").await, Err(StorageError::NotFound { .. })));
+        assert_eq!(store.get("attachments/2/ok.txt").await.unwrap(), b"fine");
+        assert_eq!(store.used_bytes(), 4);
+    }
+}
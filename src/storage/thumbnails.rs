@@ -0,0 +1,133 @@
+//! Downscaled previews of image uploads
+//!
+//! Decoding is bounded before any pixels are allocated: sources over the
+//! configured width/height are rejected from their header, and the decoder
+//! may only allocate so much, so a small file claiming a huge canvas
+//! (a decompression bomb) is refused rather than expanded.
+
+use image::{DynamicImage, ImageFormat, ImageReader, Limits};
+use std::io::Cursor;
+
+use crate::config::StorageConfig;
+
+/// Size and decoding bounds for thumbnails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThumbnailLimits {
+    /// Longest side of a thumbnail; 0 = no thumbnails
+    pub max_dimension: u32,
+    pub max_source_dimension: u32,
+    pub max_decode_bytes: u64,
+}
+
+impl ThumbnailLimits {
+    pub fn from_config(config: &StorageConfig) -> Self {
+        Self {
+            max_dimension: config.thumbnail_max_dimension,
+            max_source_dimension: config.thumbnail_max_source_dimension,
+            max_decode_bytes: config.thumbnail_max_decode_bytes,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.max_dimension > 0
+    }
+}
+
+/// Format a content type is thumbnailed as, or None for types that aren't
+fn thumbnail_format(content_type: &str) -> Option<ImageFormat> {
+    let essence = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    match essence.as_str() {
+        "image/png" => Some(ImageFormat::Png),
+        "image/jpeg" | "image/jpg" => Some(ImageFormat::Jpeg),
+        "image/gif" => Some(ImageFormat::Gif),
+        _ => None,
+    }
+}
+
+/// Whether uploads of `content_type` get a thumbnail
+pub fn is_thumbnailable(content_type: &str) -> bool {
+    thumbnail_format(content_type).is_some()
+}
+
+/// Key the thumbnail of the blob at `key` is stored under, next to it
+pub fn thumbnail_key(key: &str) -> String {
+    match key.rsplit_once('/') {
+        Some((dir, name)) => format!("{}/thumb_{}", dir, name),
+        None => format!("thumb_{}", key),
+    }
+}
+
+/// Decodes `data` within `limits` and returns it shrunk to fit
+/// `max_dimension` on both sides, encoded in the upload's own format
+///
+/// Returns None for types that aren't thumbnailed, images over the limits
+/// and anything that fails to decode. Images already small enough are
+/// re-encoded as they are. This is CPU-bound; run it off the async runtime.
+pub fn make_thumbnail(data: &[u8], content_type: &str, limits: &ThumbnailLimits) -> Option<Vec<u8>> {
+    let format = thumbnail_format(content_type)?;
+    if !limits.enabled() {
+        return None;
+    }
+
+    let mut decode_limits = Limits::default();
+    decode_limits.max_image_width = Some(limits.max_source_dimension);
+    decode_limits.max_image_height = Some(limits.max_source_dimension);
+    decode_limits.max_alloc = Some(limits.max_decode_bytes);
+
+    let mut reader = ImageReader::with_format(Cursor::new(data), format);
+    reader.limits(decode_limits);
+    let image = reader.decode().ok()?;
+
+    let thumbnail = if image.width() > limits.max_dimension || image.height() > limits.max_dimension {
+        image.thumbnail(limits.max_dimension, limits.max_dimension)
+    } else {
+        image
+    };
+    // JPEG has no alpha channel
+    let thumbnail = match format {
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(thumbnail.to_rgb8()),
+        _ => thumbnail,
+    };
+
+    let mut encoded = Cursor::new(Vec::new());
+    thumbnail.write_to(&mut encoded, format).ok()?;
+    Some(encoded.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut encoded = Cursor::new(Vec::new());
+        DynamicImage::ImageRgba8(RgbaImage::new(width, height))
+            .write_to(&mut encoded, ImageFormat::Png)
+            .unwrap();
+        encoded.into_inner()
+    }
+
+    fn limits() -> ThumbnailLimits {
+        ThumbnailLimits { max_dimension: 64, max_source_dimension: 1024, max_decode_bytes: 16 * 1024 * 1024 }
+    }
+
+    #[test]
+    fn test_thumbnails_are_bounded() {
+        let thumbnail = make_thumbnail(&png(800, 200), "image/png", &limits()).unwrap();
+        let decoded = image::load_from_memory(&thumbnail).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (64, 16));
+
+        // Too large to decode, by dimensions or by memory
+        assert!(make_thumbnail(&png(2000, 10), "image/png", &limits()).is_none());
+        let tight = ThumbnailLimits { max_decode_bytes: 1024, ..limits() };
+        assert!(make_thumbnail(&png(800, 200), "image/png", &tight).is_none());
+
+        // Other types, undecodable data and disabled thumbnails get none
+        assert!(make_thumbnail(&png(800, 200), "application/pdf", &limits()).is_none());
+        assert!(make_thumbnail(b"not a png", "image/png", &limits()).is_none());
+        let disabled = ThumbnailLimits { max_dimension: 0, ..limits() };
+        assert!(make_thumbnail(&png(800, 200), "image/png", &disabled).is_none());
+
+        assert_eq!(thumbnail_key("attachments/abc/photo.png"), "attachments/abc/thumb_photo.png");
+    }
+}
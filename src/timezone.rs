@@ -0,0 +1,63 @@
+//! User timezones
+//!
+//! Timestamps are stored and sent in UTC. Each user may pick an IANA zone
+//! (e.g. `Europe/Paris`); responses carry its current offset so clients
+//! and anything scheduling per user (quiet hours, digests) agree on it.
+
+use chrono::{DateTime, Offset, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+
+/// Zone for users who never picked one
+pub const DEFAULT_TIMEZONE: Tz = Tz::UTC;
+
+/// Parses an IANA zone identifier such as `America/New_York`
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+    name.trim().parse().ok()
+}
+
+/// A stored zone name, falling back to UTC if it's missing or no longer valid
+pub fn resolve_timezone(name: Option<&str>) -> Tz {
+    name.and_then(parse_timezone).unwrap_or(DEFAULT_TIMEZONE)
+}
+
+/// The zone's offset from UTC at `at`, formatted as `+HH:MM`
+pub fn utc_offset(timezone: Tz, at: DateTime<Utc>) -> String {
+    at.with_timezone(&timezone).offset().fix().to_string()
+}
+
+/// The timezone annotation added to responses that carry timestamps
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LocalTime {
+    pub timezone: String,
+    pub utc_offset: String,
+}
+
+impl LocalTime {
+    pub fn now(timezone: Tz) -> Self {
+        Self {
+            timezone: timezone.name().to_string(),
+            utc_offset: utc_offset(timezone, Utc::now()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_offsets_follow_daylight_saving() {
+        let paris = parse_timezone("Europe/Paris").unwrap();
+        let winter = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+        let summer = Utc.with_ymd_and_hms(2024, 7, 15, 12, 0, 0).unwrap();
+        assert_eq!(utc_offset(paris, winter), "+01:00");
+        assert_eq!(utc_offset(paris, summer), "+02:00");
+        assert_eq!(utc_offset(DEFAULT_TIMEZONE, summer), "+00:00");
+
+        assert!(parse_timezone("Mars/Olympus_Mons").is_none());
+        assert_eq!(resolve_timezone(Some("nonsense")), DEFAULT_TIMEZONE);
+        assert_eq!(resolve_timezone(None), DEFAULT_TIMEZONE);
+    }
+}
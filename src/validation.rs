@@ -7,6 +7,10 @@ use axum::{
 };
 use std::collections::HashMap;
 use ammonia::Builder;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::config::{PaginationConfig, PasswordPolicy};
+use crate::models::UserId;
 
 /// Custom validation error response
 #[derive(Debug, Serialize)]
@@ -50,12 +54,14 @@ pub struct LoginRequest {
 }
 
 /// Create room request validation
+///
+/// Name and topic lengths are configurable and counted in graphemes, so the
+/// room service checks them with `normalize_text`.
 #[derive(Debug, Deserialize, Validate)]
 pub struct CreateRoomRequest {
-    #[validate(length(min = 1, max = 100, message = "Room name must be 1-100 characters"))]
+    #[validate(length(min = 1, message = "Room name is required"))]
     pub name: String,
     
-    #[validate(length(max = 500, message = "Topic must be less than 500 characters"))]
     pub topic: Option<String>,
     
     #[validate(custom = "validate_room_type")]
@@ -76,6 +82,10 @@ pub struct CreateMessageRequest {
     pub content: String,
     
     pub client_message_id: uuid::Uuid,
+    
+    /// Keys of the sender's uploaded blobs to attach
+    #[serde(default)]
+    pub attachments: Vec<String>,
 }
 
 /// Add room member request validation
@@ -87,6 +97,35 @@ pub struct AddRoomMemberRequest {
     pub involvement_level: String,
 }
 
+/// Resolve-or-create room request validation
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResolveRoomRequest {
+    /// Channel name as typed, with or without the leading `#`
+    #[validate(length(min = 1, message = "Channel name is required"))]
+    pub name: String,
+}
+
+/// Change room type request validation
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangeRoomTypeRequest {
+    #[validate(custom = "validate_room_type")]
+    pub room_type: String,
+}
+
+/// Post permission request validation
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdatePostPermissionRequest {
+    #[validate(custom = "validate_post_permission")]
+    pub post_permission: String,
+}
+
+fn validate_post_permission(post_permission: &str) -> Result<(), ValidationError> {
+    match post_permission {
+        "everyone" | "admins_only" => Ok(()),
+        _ => Err(ValidationError::new("invalid_post_permission")),
+    }
+}
+
 fn validate_involvement_level(level: &str) -> Result<(), ValidationError> {
     match level {
         "member" | "admin" => Ok(()),
@@ -100,10 +139,13 @@ pub struct SearchRequest {
     #[validate(length(min = 1, max = 100, message = "Search query must be 1-100 characters"))]
     pub q: String,
     
-    #[validate(range(min = 1, max = 100, message = "Limit must be between 1 and 100"))]
-    pub limit: Option<u32>,
+    /// Resolved with `resolve_limit`
+    pub limit: Option<i64>,
     
     pub room_id: Option<uuid::Uuid>,
+    
+    #[serde(default)]
+    pub prefix: bool,
 }
 
 /// Push subscription request validation
@@ -127,7 +169,8 @@ pub struct PushSubscriptionKeys {
 /// Bot creation request validation
 #[derive(Debug, Deserialize, Validate)]
 pub struct CreateBotRequest {
-    #[validate(length(min = 1, max = 50, message = "Bot name must be 1-50 characters"))]
+    /// Length is checked by the bot service, in graphemes
+    #[validate(length(min = 1, message = "Bot name is required"))]
     pub name: String,
     
     #[validate(length(max = 200, message = "Description must be less than 200 characters"))]
@@ -142,6 +185,201 @@ pub struct CreateBotRequest {
 pub struct CreateBotMessageRequest {
     #[validate(length(min = 1, max = 10000, message = "Message content must be 1-10000 characters"))]
     pub content: String,
+    
+    /// User the message is attributed to, for bots granted that capability
+    #[serde(default)]
+    pub on_behalf_of: Option<UserId>,
+}
+
+/// Shortest minimum length `security.password_policy` may be configured with
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Longest password accepted regardless of policy
+pub const MAX_PASSWORD_LENGTH: usize = 128;
+
+/// Passwords from breach corpora that attackers try first; compared case-insensitively
+const COMMON_PASSWORDS: &[&str] = &[
+    "password", "password1", "password!", "passw0rd", "p@ssw0rd", "12345678",
+    "123456789", "1234567890", "11111111", "00000000", "87654321", "qwerty123",
+    "qwertyuiop", "1q2w3e4r", "1qaz2wsx", "abc12345", "abcd1234", "iloveyou",
+    "letmein1", "welcome1", "welcome123", "sunshine", "princess", "football",
+    "baseball", "superman", "trustno1", "admin123", "administrator", "changeme",
+    "campfire", "campfire1",
+];
+
+/// Checks a new password against the configured policy
+///
+/// Every rule that fails is reported, so the client can show them all at once
+/// under the `password` field. Demo accounts are seeded directly and never
+/// pass through here.
+pub fn validate_password(password: &str, policy: &PasswordPolicy) -> Result<(), ValidationErrorResponse> {
+    let mut errors = Vec::new();
+    let length = password.chars().count();
+
+    if length < policy.min_length {
+        errors.push(format!("Password must be at least {} characters long", policy.min_length));
+    }
+    if length > MAX_PASSWORD_LENGTH {
+        errors.push(format!("Password must be at most {} characters long", MAX_PASSWORD_LENGTH));
+    }
+    if policy.require_mixed_case
+        && !(password.chars().any(char::is_lowercase) && password.chars().any(char::is_uppercase))
+    {
+        errors.push("Password must contain both uppercase and lowercase letters".to_string());
+    }
+    if policy.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+        errors.push("Password must contain at least one number".to_string());
+    }
+    if policy.require_symbol && password.chars().all(char::is_alphanumeric) {
+        errors.push("Password must contain at least one symbol".to_string());
+    }
+    if policy.block_common && COMMON_PASSWORDS.contains(&password.to_lowercase().as_str()) {
+        errors.push("Password is too common".to_string());
+    }
+
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(ValidationErrorResponse {
+        error: "Validation failed".to_string(),
+        details: HashMap::from([("password".to_string(), errors)]),
+    })
+}
+
+/// `?limit=` for listings that take nothing else
+#[derive(Debug, Default, Deserialize)]
+pub struct LimitQuery {
+    pub limit: Option<i64>,
+}
+
+/// Turns a client-supplied `limit` into a page size
+///
+/// Missing means the configured default and anything above the cap is
+/// clamped to it, so clients can always ask for "as many as allowed".
+/// Negative limits are rejected rather than guessed at.
+pub fn resolve_limit(requested: Option<i64>, pagination: &PaginationConfig) -> Result<u32, ValidationErrorResponse> {
+    match requested {
+        None => Ok(pagination.default_limit.min(pagination.max_limit)),
+        Some(limit) if limit < 0 => Err(ValidationErrorResponse {
+            error: "Validation failed".to_string(),
+            details: HashMap::from([("limit".to_string(), vec!["Limit must not be negative".to_string()])]),
+        }),
+        Some(limit) => Ok(limit.clamp(1, pagination.max_limit as i64) as u32),
+    }
+}
+
+/// Caps on the attachments one message may reference (0 = unlimited)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AttachmentLimits {
+    pub max_count: usize,
+    pub max_total_bytes: u64,
+}
+
+fn attachment_error(message: String) -> ValidationErrorResponse {
+    ValidationErrorResponse {
+        error: "Validation failed".to_string(),
+        details: HashMap::from([("attachments".to_string(), vec![message])]),
+    }
+}
+
+/// Checked before looking anything up, so an oversized list costs nothing
+pub fn validate_attachment_count(count: usize, limits: &AttachmentLimits) -> Result<(), ValidationErrorResponse> {
+    if limits.max_count > 0 && count > limits.max_count {
+        return Err(attachment_error(format!(
+            "A message can have at most {} attachments",
+            limits.max_count
+        )));
+    }
+    Ok(())
+}
+
+pub fn validate_attachment_bytes(total_bytes: u64, limits: &AttachmentLimits) -> Result<(), ValidationErrorResponse> {
+    if limits.max_total_bytes > 0 && total_bytes > limits.max_total_bytes {
+        return Err(attachment_error(format!(
+            "A message's attachments can total at most {} bytes",
+            limits.max_total_bytes
+        )));
+    }
+    Ok(())
+}
+
+/// Rejects attachment keys that aren't one of the sender's uploads
+pub fn unknown_attachment(key: &str) -> ValidationErrorResponse {
+    attachment_error(format!("Unknown attachment: {}", key))
+}
+
+/// Rejects uploads made to a different room than the message is posted in
+pub fn attachment_from_other_room(key: &str) -> ValidationErrorResponse {
+    attachment_error(format!("Attachment was uploaded to another room: {}", key))
+}
+
+/// Refuses batch lookups asking for more ids than the configured cap
+pub fn validate_batch_size(field: &str, count: usize, pagination: &PaginationConfig) -> Result<(), ValidationErrorResponse> {
+    if count > pagination.max_batch_ids {
+        return Err(ValidationErrorResponse {
+            error: "Validation failed".to_string(),
+            details: HashMap::from([(
+                field.to_string(),
+                vec![format!("At most {} ids can be looked up at once", pagination.max_batch_ids)],
+            )]),
+        });
+    }
+    Ok(())
+}
+
+/// Why [`normalize_text`] refused a name or topic
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TextError {
+    #[error("{field} cannot be empty")]
+    Empty { field: &'static str },
+    
+    #[error("{field} too long: {length} chars (max: {max})")]
+    TooLong { field: &'static str, length: usize, max: usize },
+    
+    #[error("{field} contains control characters")]
+    ControlCharacters { field: &'static str },
+}
+
+/// Length as a reader would count it: one per grapheme cluster, so a flag
+/// or a family emoji is a single character
+pub fn grapheme_len(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+/// Checks a single-line name or topic and returns it cleaned up
+///
+/// Runs of whitespace, newlines and tabs included, become one space and the
+/// ends are trimmed. Other control characters are refused rather than
+/// stripped, since they mean the client sent something malformed. The
+/// length limit applies to the normalized text, in grapheme clusters.
+pub fn normalize_text(field: &'static str, text: &str, max: usize) -> Result<String, TextError> {
+    if text.chars().any(|c| c.is_control() && !c.is_whitespace()) {
+        return Err(TextError::ControlCharacters { field });
+    }
+    
+    let normalized = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if normalized.is_empty() {
+        return Err(TextError::Empty { field });
+    }
+    
+    let length = grapheme_len(&normalized);
+    if length > max {
+        return Err(TextError::TooLong { field, length, max });
+    }
+    
+    Ok(normalized)
+}
+
+/// [`normalize_text`] for optional fields, where blank means absent
+pub fn normalize_optional_text(
+    field: &'static str,
+    text: Option<&str>,
+    max: usize,
+) -> Result<Option<String>, TextError> {
+    match text.map(|text| normalize_text(field, text, max)) {
+        None | Some(Err(TextError::Empty { .. })) => Ok(None),
+        Some(result) => result.map(Some),
+    }
 }
 
 /// Content sanitization utilities
@@ -375,22 +613,49 @@ mod tests {
         let valid_request = CreateMessageRequest {
             content: "Hello, world!".to_string(),
             client_message_id: uuid::Uuid::new_v4(),
+            attachments: vec![],
         };
         assert!(valid_request.validate().is_ok());
 
         let empty_content = CreateMessageRequest {
             content: "".to_string(),
             client_message_id: uuid::Uuid::new_v4(),
+            attachments: vec![],
         };
         assert!(empty_content.validate().is_err());
 
         let too_long_content = CreateMessageRequest {
             content: "a".repeat(10001),
             client_message_id: uuid::Uuid::new_v4(),
+            attachments: vec![],
         };
         assert!(too_long_content.validate().is_err());
     }
 
+    #[test]
+    fn test_attachment_count_limit_boundary() {
+        let limits = AttachmentLimits { max_count: 3, max_total_bytes: 0 };
+        assert!(validate_attachment_count(3, &limits).is_ok());
+        
+        let error = validate_attachment_count(4, &limits).unwrap_err();
+        assert_eq!(error.details["attachments"], vec!["A message can have at most 3 attachments"]);
+        
+        let unlimited = AttachmentLimits::default();
+        assert!(validate_attachment_count(1000, &unlimited).is_ok());
+    }
+
+    #[test]
+    fn test_attachment_total_size_limit_boundary() {
+        let limits = AttachmentLimits { max_count: 0, max_total_bytes: 1024 };
+        assert!(validate_attachment_bytes(1024, &limits).is_ok());
+        
+        let error = validate_attachment_bytes(1025, &limits).unwrap_err();
+        assert_eq!(error.details["attachments"], vec!["A message's attachments can total at most 1024 bytes"]);
+        
+        let unlimited = AttachmentLimits::default();
+        assert!(validate_attachment_bytes(u64::MAX, &unlimited).is_ok());
+    }
+
     #[test]
     fn test_content_sanitization() {
         use sanitization::*;
@@ -406,4 +671,131 @@ mod tests {
         let room_name = sanitize_room_name("  <script>Room</script>  ");
         assert_eq!(room_name, "Room");
     }
-}
\ No newline at end of file
+
+    fn strict_policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 12,
+            require_mixed_case: true,
+            require_digit: true,
+            require_symbol: true,
+            block_common: true,
+        }
+    }
+
+    fn password_errors(password: &str, policy: &PasswordPolicy) -> Vec<String> {
+        match validate_password(password, policy) {
+            Ok(()) => vec![],
+            Err(e) => e.details["password"].clone(),
+        }
+    }
+
+    #[test]
+    fn test_password_policy_min_length() {
+        let errors = password_errors("Sh0rt!pw", &strict_policy());
+        assert_eq!(errors, vec!["Password must be at least 12 characters long"]);
+
+        assert!(password_errors(&"Aa1!".repeat(33), &strict_policy())
+            .contains(&"Password must be at most 128 characters long".to_string()));
+    }
+
+    #[test]
+    fn test_password_policy_mixed_case() {
+        let errors = password_errors("lowercase-only-42", &strict_policy());
+        assert_eq!(errors, vec!["Password must contain both uppercase and lowercase letters"]);
+    }
+
+    #[test]
+    fn test_password_policy_digit() {
+        let errors = password_errors("No-Digits-Here", &strict_policy());
+        assert_eq!(errors, vec!["Password must contain at least one number"]);
+    }
+
+    #[test]
+    fn test_password_policy_symbol() {
+        let errors = password_errors("NoSymbolsHere42", &strict_policy());
+        assert_eq!(errors, vec!["Password must contain at least one symbol"]);
+    }
+
+    #[test]
+    fn test_password_policy_blocks_common_passwords() {
+        let policy = PasswordPolicy::default();
+        assert_eq!(password_errors("Password", &policy), vec!["Password is too common"]);
+        assert_eq!(password_errors("12345678", &policy), vec!["Password is too common"]);
+
+        let policy = PasswordPolicy { block_common: false, ..policy };
+        assert!(validate_password("12345678", &policy).is_ok());
+    }
+
+    #[test]
+    fn test_password_policy_reports_every_failed_rule() {
+        let errors = password_errors("password", &strict_policy());
+        assert_eq!(errors.len(), 5);
+    }
+
+    #[test]
+    fn test_strong_password_passes_strict_policy() {
+        assert!(validate_password("Correct-Horse-7-Battery", &strict_policy()).is_ok());
+        assert!(validate_password("Correct-Horse-7-Battery", &PasswordPolicy::default()).is_ok());
+    }
+
+    #[test]
+    fn test_resolve_limit_clamps_to_configured_cap() {
+        let pagination = PaginationConfig { default_limit: 25, max_limit: 200, ..Default::default() };
+        assert_eq!(resolve_limit(None, &pagination).unwrap(), 25);
+        assert_eq!(resolve_limit(Some(100_000), &pagination).unwrap(), 200);
+        assert_eq!(resolve_limit(Some(0), &pagination).unwrap(), 1);
+        assert_eq!(resolve_limit(Some(75), &pagination).unwrap(), 75);
+    }
+
+    #[test]
+    fn test_resolve_limit_rejects_negative_limits() {
+        let error = resolve_limit(Some(-1), &PaginationConfig::default()).unwrap_err();
+        assert!(error.details.contains_key("limit"));
+    }
+
+    #[test]
+    fn test_normalize_text_counts_graphemes() {
+        // Seven code points and 25 bytes, but one character to a reader
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{200D}\u{1F466}";
+        assert_eq!(grapheme_len(family), 1);
+
+        let at_limit = family.repeat(100);
+        assert_eq!(normalize_text("Room name", &at_limit, 100).unwrap(), at_limit);
+
+        let over_limit = format!("{}e\u{301}", family.repeat(99) + "\u{1F1FA}\u{1F1F8}");
+        assert_eq!(
+            normalize_text("Room name", &over_limit, 100),
+            Err(TextError::TooLong { field: "Room name", length: 101, max: 100 })
+        );
+        assert_eq!(
+            normalize_text("Room name", &over_limit, 100).unwrap_err().to_string(),
+            "Room name too long: 101 chars (max: 100)"
+        );
+    }
+
+    #[test]
+    fn test_normalize_text_rejects_control_characters() {
+        assert_eq!(
+            normalize_text("Name", "Mallory\u{7}", 50),
+            Err(TextError::ControlCharacters { field: "Name" })
+        );
+        assert_eq!(
+            normalize_text("Name", "\u{1b}[31mRed", 50),
+            Err(TextError::ControlCharacters { field: "Name" })
+        );
+        assert_eq!(
+            normalize_text("Name", "nul\0byte", 50),
+            Err(TextError::ControlCharacters { field: "Name" })
+        );
+    }
+
+    #[test]
+    fn test_normalize_text_collapses_whitespace() {
+        assert_eq!(normalize_text("Topic", "  Release\n\tplanning   chat ", 50).unwrap(), "Release planning chat");
+        assert_eq!(normalize_text("Name", " \n ", 50), Err(TextError::Empty { field: "Name" }));
+
+        assert_eq!(normalize_optional_text("Topic", Some("  "), 50), Ok(None));
+        assert_eq!(normalize_optional_text("Topic", None, 50), Ok(None));
+        assert_eq!(normalize_optional_text("Topic", Some(" Ship it "), 50), Ok(Some("Ship it".to_string())));
+    }
+}
@@ -2,6 +2,7 @@ use campfire_on_rust::{
     BotServiceImpl, BotService, CampfireDatabase, MessageService, 
     models::*,
     errors::BotError,
+    services::WebhookUrlPolicy,
 };
 use std::sync::Arc;
 
@@ -67,6 +68,36 @@ async fn test_create_bot_invalid_webhook_url() {
     assert!(matches!(result, Err(BotError::InvalidWebhookUrl { .. })));
 }
 
+#[tokio::test]
+async fn test_webhook_url_policy_applies_to_bots() {
+    let bot_service = create_test_bot_service().await;
+    
+    // Internal addresses are refused on create and update
+    for url in ["http://127.0.0.1:8080/hook", "http://169.254.169.254/latest/meta-data"] {
+        let result = bot_service.create_bot("Sneaky Bot".to_string(), Some(url.to_string())).await;
+        assert!(matches!(result, Err(BotError::WebhookUrlBlocked { .. })), "{} should be blocked", url);
+    }
+    let bot = bot_service.create_bot("Deploy Bot".to_string(), None).await.unwrap();
+    let result = bot_service.update_bot(bot.id, None, Some("http://10.0.0.1/hook".to_string())).await;
+    assert!(matches!(result, Err(BotError::WebhookUrlBlocked { .. })));
+    
+    // An allowlisted external https endpoint is fine
+    let bot_service = create_test_bot_service().await.with_url_policy(
+        WebhookUrlPolicy::default()
+            .with_https_required(true)
+            .with_allowed_hosts(&["hooks.example.com".to_string()]),
+    );
+    let bot = bot_service
+        .create_bot("Deploy Bot".to_string(), Some("https://hooks.example.com/deploy".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(bot.webhook_url.as_deref(), Some("https://hooks.example.com/deploy"));
+    let result = bot_service
+        .create_bot("Other Bot".to_string(), Some("https://example.org/deploy".to_string()))
+        .await;
+    assert!(matches!(result, Err(BotError::WebhookUrlBlocked { .. })));
+}
+
 #[tokio::test]
 async fn test_authenticate_bot() {
     let bot_service = create_test_bot_service().await;
@@ -100,4 +131,377 @@ async fn test_authenticate_bot_invalid_key() {
     let fake_key = format!("{}-faketoken123", fake_uuid);
     let result = bot_service.authenticate_bot(&fake_key).await;
     assert!(matches!(result, Err(BotError::InvalidToken)));
-}
\ No newline at end of file
+}
+type Received = Arc<std::sync::Mutex<Vec<serde_json::Value>>>;
+
+/// Records each JSON body it's sent and answers 202 "pong"
+async fn spawn_mock_webhook() -> (String, Received) {
+    use axum::{extract::State, routing::post, Json, Router};
+    
+    let received: Received = Arc::default();
+    let app = Router::new()
+        .route(
+            "/hook",
+            post(|State(received): State<Received>, Json(body): Json<serde_json::Value>| async move {
+                received.lock().unwrap().push(body);
+                (axum::http::StatusCode::ACCEPTED, "pong")
+            }),
+        )
+        .with_state(received.clone());
+    
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+    (format!("http://{}/hook", addr), received)
+}
+
+fn admin_user() -> User {
+    User {
+        id: UserId::new(),
+        name: "Admin".to_string(),
+        email: "admin@example.com".to_string(),
+        password_hash: String::new(),
+        bio: None,
+        admin: true,
+        bot_token: None,
+        created_at: chrono::Utc::now(),
+    }
+}
+
+#[tokio::test]
+async fn test_simulate_webhook_reports_response_diagnostics() {
+    // The mock endpoint listens on loopback
+    let bot_service = create_test_bot_service().await
+        .with_url_policy(WebhookUrlPolicy::default().with_private_networks(true));
+    let (url, received) = spawn_mock_webhook().await;
+    let bot = bot_service.create_bot("Deploy Bot".to_string(), Some(url.clone())).await.unwrap();
+    let admin = admin_user();
+    
+    let simulation = bot_service.simulate_webhook(bot.id, &admin).await.unwrap();
+    
+    assert_eq!(simulation.url, url);
+    assert_eq!(simulation.status, 202);
+    assert_eq!(simulation.body, "pong");
+    assert!(simulation.latency_ms < 7000);
+    
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    let payload = &received[0];
+    assert_eq!(payload["user"]["id"], admin.id.0.to_string());
+    assert_eq!(payload["user"]["name"], "Admin");
+    assert!(payload["room"]["path"].as_str().unwrap().ends_with(&format!("/bot/{}/messages", bot.bot_key())));
+    assert!(payload["message"]["body"]["plain"].as_str().unwrap().starts_with("@Deploy Bot"));
+    assert!(payload["message"]["body"]["html"].is_string());
+    assert!(payload["message"]["path"].is_string());
+}
+
+#[tokio::test]
+async fn test_simulate_webhook_requires_a_webhook() {
+    let bot_service = create_test_bot_service().await;
+    let bot = bot_service.create_bot("Quiet Bot".to_string(), None).await.unwrap();
+    
+    let result = bot_service.simulate_webhook(bot.id, &admin_user()).await;
+    assert!(matches!(result, Err(BotError::NoWebhook { .. })));
+}
+
+#[tokio::test]
+async fn test_mention_adds_auto_join_bots_only() {
+    use campfire_on_rust::services::bot::BotAutoJoinSubscriber;
+    use campfire_on_rust::{ConnectionManager, MessageServiceTrait};
+    
+    let db = CampfireDatabase::new(":memory:").await.unwrap();
+    let db_arc = Arc::new(db.clone());
+    let connection_manager = Arc::new(campfire_on_rust::ConnectionManagerImpl::new(db_arc.clone()));
+    let room_service = Arc::new(campfire_on_rust::RoomService::new(db_arc.clone()));
+    let message_service = Arc::new(MessageService::new(db_arc.clone(), connection_manager.clone(), room_service.clone()));
+    message_service.event_bus().subscribe(Arc::new(BotAutoJoinSubscriber::new(
+        db_arc.clone(),
+        room_service,
+        connection_manager.clone(),
+    )));
+    let bot_service = BotServiceImpl::new(db_arc.clone(), db.writer(), message_service.clone());
+    
+    let admin = admin_user();
+    db.create_user(admin.clone()).await.unwrap();
+    let room = Room {
+        id: RoomId::new(),
+        name: "Ops".to_string(),
+        topic: None,
+        room_type: RoomType::Closed,
+        created_at: chrono::Utc::now(),
+        last_message_at: None,
+    };
+    db.create_room(room.clone()).await.unwrap();
+    db.create_membership(Membership {
+        room_id: room.id,
+        user_id: admin.id,
+        involvement_level: InvolvementLevel::Admin,
+        created_at: chrono::Utc::now(),
+    }).await.unwrap();
+    
+    let eager = bot_service.create_bot("Eager Bot".to_string(), None).await.unwrap();
+    let eager = bot_service.set_auto_join_on_mention(eager.id, true).await.unwrap();
+    assert!(eager.auto_join_on_mention);
+    let shy = bot_service.create_bot("Shy Bot".to_string(), None).await.unwrap();
+    assert!(!bot_service.get_bot(shy.id).await.unwrap().unwrap().auto_join_on_mention);
+    
+    // The eager bot is connected, but not to a room it isn't in yet
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    connection_manager.add_connection(eager.id, ConnectionId::new(), tx).await.unwrap();
+    
+    // Bots are mentioned by their handle, never their token
+    let content = "@eager-bot and @shy-bot please deploy".to_string();
+    let message = message_service
+        .create_message_with_deduplication(content, room.id, admin.id, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    
+    assert!(db.get_membership(room.id, eager.id).await.unwrap().is_some());
+    assert!(db.get_membership(room.id, shy.id).await.unwrap().is_none());
+    
+    // The message that brought it in still reaches it
+    let mut delivered = false;
+    while let Ok(frame) = rx.try_recv() {
+        delivered |= frame.contains("NewMessage") && frame.contains(&message.id.0.to_string());
+    }
+    assert!(delivered);
+}
+
+#[tokio::test]
+async fn test_create_bot_past_cap_is_rejected() {
+    let bot_service = create_test_bot_service().await.with_max_bots(2);
+    
+    for i in 0..2 {
+        bot_service.create_bot(format!("Bot {}", i), None).await.unwrap();
+    }
+    
+    let result = bot_service.create_bot("One Too Many".to_string(), None).await;
+    assert!(matches!(result, Err(BotError::QuotaExceeded { limit: 2 })));
+}
+
+#[tokio::test]
+async fn test_deleted_bot_frees_its_slot_and_stops_authenticating() {
+    let bot_service = create_test_bot_service().await.with_max_bots(2);
+    
+    let retired = bot_service.create_bot("Retired".to_string(), None).await.unwrap();
+    bot_service.create_bot("Busy".to_string(), None).await.unwrap();
+    
+    bot_service.delete_bot(retired.id).await.unwrap();
+    assert!(matches!(bot_service.get_bot(retired.id).await, Err(BotError::NotABot { .. })));
+    assert!(bot_service.authenticate_bot(&retired.bot_key()).await.is_err());
+    
+    bot_service.create_bot("Replacement".to_string(), None).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_welcome_reply_only_answers_first_message() {
+    use campfire_on_rust::config::WelcomeReplyConfig;
+    use campfire_on_rust::services::bot::WelcomeReplySubscriber;
+    use campfire_on_rust::MessageServiceTrait;
+    
+    let db = CampfireDatabase::new(":memory:").await.unwrap();
+    let db_arc = Arc::new(db.clone());
+    let connection_manager = Arc::new(campfire_on_rust::ConnectionManagerImpl::new(db_arc.clone()));
+    let room_service = Arc::new(campfire_on_rust::RoomService::new(db_arc.clone()));
+    let message_service = Arc::new(MessageService::new(db_arc.clone(), connection_manager, room_service));
+    let bot_service = BotServiceImpl::new(db_arc.clone(), db.writer(), message_service.clone());
+    let greeter = bot_service.create_bot("Greeter".to_string(), None).await.unwrap();
+    message_service.event_bus().subscribe(Arc::new(WelcomeReplySubscriber::new(
+        db_arc.clone(),
+        message_service.event_bus().clone(),
+        WelcomeReplyConfig {
+            room_name: "General".to_string(),
+            bot_id: greeter.id.0,
+            template: "Welcome to {room}, {name}!".to_string(),
+        },
+    )));
+    
+    let newcomer = User { name: "Newcomer".to_string(), email: "new@example.com".to_string(), admin: false, ..admin_user() };
+    db.create_user(newcomer.clone()).await.unwrap();
+    let general = Room {
+        id: RoomId::new(),
+        name: "General".to_string(),
+        topic: None,
+        room_type: RoomType::Open,
+        created_at: chrono::Utc::now(),
+        last_message_at: None,
+    };
+    db.create_room(general.clone()).await.unwrap();
+    
+    let replies_from_greeter = || async {
+        db.get_room_messages(general.id, 50, None).await.unwrap()
+            .into_iter()
+            .filter(|m| m.creator_id == greeter.id)
+            .collect::<Vec<_>>()
+    };
+    
+    message_service
+        .create_message_with_deduplication("hi all".to_string(), general.id, newcomer.id, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    let replies = replies_from_greeter().await;
+    assert_eq!(replies.len(), 1);
+    assert_eq!(replies[0].content, "Welcome to General, Newcomer!");
+    
+    message_service
+        .create_message_with_deduplication("anyone around?".to_string(), general.id, newcomer.id, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(replies_from_greeter().await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_bot_messages_on_behalf_of_users_keep_the_real_bot() {
+    let db = CampfireDatabase::new(":memory:").await.unwrap();
+    let db_arc = Arc::new(db.clone());
+    let connection_manager = Arc::new(campfire_on_rust::ConnectionManagerImpl::new(db_arc.clone()));
+    let room_service = Arc::new(campfire_on_rust::RoomService::new(db_arc.clone()));
+    let message_service = Arc::new(MessageService::new(db_arc.clone(), connection_manager, room_service));
+    let bot_service = BotServiceImpl::new(db_arc.clone(), db.writer(), message_service);
+    
+    let person = admin_user();
+    db.create_user(person.clone()).await.unwrap();
+    let room = Room {
+        id: RoomId::new(),
+        name: "Reviews".to_string(),
+        topic: None,
+        room_type: RoomType::Open,
+        created_at: chrono::Utc::now(),
+        last_message_at: None,
+    };
+    db.create_room(room.clone()).await.unwrap();
+    let github = bot_service.create_bot("GitHub".to_string(), None).await.unwrap();
+    let other_bot = bot_service.create_bot("Other".to_string(), None).await.unwrap();
+    
+    // By default a message is the bot's own
+    let own = bot_service
+        .create_bot_message(github.id, room.id, "CI passed".to_string(), None)
+        .await
+        .unwrap();
+    assert_eq!(own.creator_id, github.id);
+    assert_eq!(db.get_message_bot_author(own.id).await.unwrap(), None);
+    
+    // Posting as someone else needs the grant
+    let result = bot_service
+        .create_bot_message(github.id, room.id, "LGTM".to_string(), Some(person.id))
+        .await;
+    assert!(matches!(result, Err(BotError::OnBehalfNotGranted { .. })));
+    
+    let github = bot_service.set_post_on_behalf(github.id, true).await.unwrap();
+    assert!(github.post_on_behalf);
+    assert!(bot_service.get_bot(github.id).await.unwrap().unwrap().post_on_behalf);
+    
+    let relayed = bot_service
+        .create_bot_message(github.id, room.id, "LGTM".to_string(), Some(person.id))
+        .await
+        .unwrap();
+    assert_eq!(relayed.creator_id, person.id);
+    assert_eq!(db.get_message_bot_author(relayed.id).await.unwrap(), Some(github.id));
+    
+    // Only people can be posted as
+    let result = bot_service
+        .create_bot_message(github.id, room.id, "beep".to_string(), Some(other_bot.id))
+        .await;
+    assert!(matches!(result, Err(BotError::InvalidOnBehalf { .. })));
+}
+
+#[tokio::test]
+async fn test_bots_cannot_post_on_behalf_into_rooms_they_are_not_in() {
+    let db = CampfireDatabase::new(":memory:").await.unwrap();
+    let db_arc = Arc::new(db.clone());
+    let connection_manager = Arc::new(campfire_on_rust::ConnectionManagerImpl::new(db_arc.clone()));
+    let room_service = Arc::new(campfire_on_rust::RoomService::new(db_arc.clone()));
+    let message_service = Arc::new(MessageService::new(db_arc.clone(), connection_manager, room_service));
+    let bot_service = BotServiceImpl::new(db_arc.clone(), db.writer(), message_service);
+    
+    let person = admin_user();
+    db.create_user(person.clone()).await.unwrap();
+    let private = Room {
+        id: RoomId::new(),
+        name: "Private".to_string(),
+        topic: None,
+        room_type: RoomType::Closed,
+        created_at: chrono::Utc::now(),
+        last_message_at: None,
+    };
+    db.create_room(private.clone()).await.unwrap();
+    db.create_membership(Membership {
+        room_id: private.id,
+        user_id: person.id,
+        involvement_level: InvolvementLevel::Member,
+        created_at: chrono::Utc::now(),
+    }).await.unwrap();
+    let github = bot_service.create_bot("GitHub".to_string(), None).await.unwrap();
+    bot_service.set_post_on_behalf(github.id, true).await.unwrap();
+    
+    // The user can post there, but the bot isn't a member
+    let result = bot_service
+        .create_bot_message(github.id, private.id, "LGTM".to_string(), Some(person.id))
+        .await;
+    assert!(matches!(result, Err(BotError::PostingRestricted { .. })));
+    
+    db.create_membership(Membership {
+        room_id: private.id,
+        user_id: github.id,
+        involvement_level: InvolvementLevel::Member,
+        created_at: chrono::Utc::now(),
+    }).await.unwrap();
+    let relayed = bot_service
+        .create_bot_message(github.id, private.id, "LGTM".to_string(), Some(person.id))
+        .await
+        .unwrap();
+    assert_eq!(relayed.creator_id, person.id);
+}
+
+#[tokio::test]
+async fn test_bots_cannot_post_on_behalf_of_admins_without_a_post_grant() {
+    use campfire_on_rust::RoomServiceTrait;
+    
+    let db = CampfireDatabase::new(":memory:").await.unwrap();
+    let db_arc = Arc::new(db.clone());
+    let connection_manager = Arc::new(campfire_on_rust::ConnectionManagerImpl::new(db_arc.clone()));
+    let room_service = Arc::new(campfire_on_rust::RoomService::new(db_arc.clone()));
+    let message_service = Arc::new(MessageService::new(db_arc.clone(), connection_manager, room_service.clone()));
+    let bot_service = BotServiceImpl::new(db_arc.clone(), db.writer(), message_service)
+        .with_room_service(room_service.clone());
+    
+    let admin = admin_user();
+    db.create_user(admin.clone()).await.unwrap();
+    let announcements = Room {
+        id: RoomId::new(),
+        name: "Announcements".to_string(),
+        topic: None,
+        room_type: RoomType::Open,
+        created_at: chrono::Utc::now(),
+        last_message_at: None,
+    };
+    db.create_room(announcements.clone()).await.unwrap();
+    room_service
+        .set_post_permission(announcements.id, admin.id, PostPermission::AdminsOnly)
+        .await
+        .unwrap();
+    let github = bot_service.create_bot("GitHub".to_string(), None).await.unwrap();
+    bot_service.set_post_on_behalf(github.id, true).await.unwrap();
+    db.create_membership(Membership {
+        room_id: announcements.id,
+        user_id: github.id,
+        involvement_level: InvolvementLevel::Member,
+        created_at: chrono::Utc::now(),
+    }).await.unwrap();
+    
+    // The admin may post there, but the bot itself has no post grant
+    let result = bot_service
+        .create_bot_message(github.id, announcements.id, "Release shipped".to_string(), Some(admin.id))
+        .await;
+    assert!(matches!(result, Err(BotError::PostingRestricted { .. })));
+    
+    room_service
+        .set_post_grant(announcements.id, admin.id, github.id, true)
+        .await
+        .unwrap();
+    let relayed = bot_service
+        .create_bot_message(github.id, announcements.id, "Release shipped".to_string(), Some(admin.id))
+        .await
+        .unwrap();
+    assert_eq!(relayed.creator_id, admin.id);
+}
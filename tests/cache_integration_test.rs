@@ -210,6 +210,7 @@ async fn test_search_caching_integration() {
         limit: Some(20),
         offset: Some(0),
         room_id: None,
+        prefix: false,
     };
     
     // First search should cache the result
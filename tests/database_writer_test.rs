@@ -110,9 +110,11 @@ async fn test_message_deduplication_with_writer_pattern() {
     let result2 = writer.create_message_with_deduplication(message2).await.unwrap();
     
     // Should return the original message (deduplication)
-    assert_eq!(result1.id, result2.id);
-    assert_eq!(result1.content, result2.content);
-    assert_eq!(result1.content, "Test message"); // Original content preserved
+    assert!(result1.inserted);
+    assert!(!result2.inserted);
+    assert_eq!(result1.message.id, result2.message.id);
+    assert_eq!(result1.message.content, result2.message.content);
+    assert_eq!(result1.message.content, "Test message"); // Original content preserved
 }
 
 #[tokio::test]
@@ -291,4 +293,432 @@ async fn test_session_operations_through_writer() {
     // Verify session is deleted
     let deleted_session = db.get_session(&session.token).await.unwrap();
     assert!(deleted_session.is_none());
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_reindex_repairs_drifted_search_index() {
+    let db = CampfireDatabase::new(":memory:").await.unwrap();
+    let user = User {
+        id: UserId::new(),
+        name: "Test User".to_string(),
+        email: "test@example.com".to_string(),
+        password_hash: "hashed_password".to_string(),
+        bio: None,
+        admin: false,
+        bot_token: None,
+        created_at: Utc::now(),
+    };
+    db.create_user(user.clone()).await.unwrap();
+    let room = Room {
+        id: RoomId::new(),
+        name: "Test Room".to_string(),
+        topic: None,
+        room_type: RoomType::Open,
+        created_at: Utc::now(),
+        last_message_at: None,
+    };
+    db.create_room(room.clone()).await.unwrap();
+    for content in ["alpha launch", "beta launch", "gamma launch"] {
+        let message = Message::new(room.id, user.id, content.to_string(), uuid::Uuid::new_v4());
+        db.create_message_with_deduplication(message).await.unwrap();
+    }
+    assert!(db.check_search_index().await.unwrap().is_consistent());
+    
+    // Simulate a manual migration that bypassed the triggers
+    sqlx::query("DELETE FROM messages_fts WHERE content = 'alpha launch'")
+        .execute(db.pool())
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO messages_fts (message_id, content) VALUES ('gone', 'stale launch')")
+        .execute(db.pool())
+        .await
+        .unwrap();
+    
+    // The counts still match; only the full comparison sees the drift
+    let counted = db.check_search_index().await.unwrap();
+    assert_eq!((counted.messages, counted.indexed), (3, 3));
+    let drifted = db.find_search_index_drift().await.unwrap();
+    assert!(!drifted.is_consistent());
+    assert_eq!((drifted.missing, drifted.orphaned), (1, 1));
+    
+    sqlx::query("DELETE FROM messages_fts WHERE message_id = 'gone'")
+        .execute(db.pool())
+        .await
+        .unwrap();
+    assert!(!db.check_search_index().await.unwrap().is_consistent());
+    
+    assert_eq!(db.rebuild_search_index().await.unwrap(), 3);
+    let repaired = db.check_search_index().await.unwrap();
+    assert!(repaired.is_consistent());
+    assert_eq!(repaired.indexed, 3);
+    assert!(db.find_search_index_drift().await.unwrap().is_consistent());
+    
+    let hits: Vec<(String,)> = sqlx::query_as("SELECT content FROM messages_fts WHERE messages_fts MATCH 'launch' ORDER BY content")
+        .fetch_all(db.pool())
+        .await
+        .unwrap();
+    let hits: Vec<String> = hits.into_iter().map(|(content,)| content).collect();
+    assert_eq!(hits, vec!["alpha launch", "beta launch", "gamma launch"]);
+}
+
+#[tokio::test]
+async fn test_vacuum_reclaims_space_and_keeps_data() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("vacuum.db");
+    let db = CampfireDatabase::new(&format!("sqlite://{}?mode=rwc", path.display())).await.unwrap();
+    let user = User {
+        id: UserId::new(),
+        name: "Test User".to_string(),
+        email: "test@example.com".to_string(),
+        password_hash: "hashed_password".to_string(),
+        bio: None,
+        admin: false,
+        bot_token: None,
+        created_at: Utc::now(),
+    };
+    db.create_user(user.clone()).await.unwrap();
+    let keep = Room {
+        id: RoomId::new(),
+        name: "Keep".to_string(),
+        topic: None,
+        room_type: RoomType::Open,
+        created_at: Utc::now(),
+        last_message_at: None,
+    };
+    let purge = Room { id: RoomId::new(), name: "Purge".to_string(), ..keep.clone() };
+    db.create_room(keep.clone()).await.unwrap();
+    db.create_room(purge.clone()).await.unwrap();
+    let kept = Message::new(keep.id, user.id, "still here".to_string(), uuid::Uuid::new_v4());
+    db.create_message_with_deduplication(kept.clone()).await.unwrap();
+    for i in 0..300 {
+        let message = Message::new(purge.id, user.id, format!("{} {}", i, "filler ".repeat(100)), uuid::Uuid::new_v4());
+        db.create_message_with_deduplication(message).await.unwrap();
+    }
+    assert!(db.delete_room_permanently(purge.id).await.unwrap().is_some());
+    
+    let report = db.vacuum().await.unwrap();
+    assert!(report.reclaimed_bytes() > 0, "{:?}", report);
+    
+    let messages = db.get_room_messages(keep.id, 10, None).await.unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].id, kept.id);
+    let integrity: (String,) = sqlx::query_as("PRAGMA integrity_check").fetch_one(db.pool()).await.unwrap();
+    assert_eq!(integrity.0, "ok");
+    
+    // Writes carry on through the writer afterwards
+    let after = Message::new(keep.id, user.id, "after vacuum".to_string(), uuid::Uuid::new_v4());
+    db.create_message_with_deduplication(after).await.unwrap();
+    assert_eq!(db.get_room_messages(keep.id, 10, None).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_messages_with_same_timestamp_get_ordered_sequence_numbers() {
+    let db = CampfireDatabase::new(":memory:").await.unwrap();
+    let user = User {
+        id: UserId::new(),
+        name: "Test User".to_string(),
+        email: "test@example.com".to_string(),
+        password_hash: "hashed_password".to_string(),
+        bio: None,
+        admin: false,
+        bot_token: None,
+        created_at: Utc::now(),
+    };
+    db.create_user(user.clone()).await.unwrap();
+    let room = Room {
+        id: RoomId::new(),
+        name: "Test Room".to_string(),
+        topic: None,
+        room_type: RoomType::Open,
+        created_at: Utc::now(),
+        last_message_at: None,
+    };
+    db.create_room(room.clone()).await.unwrap();
+    
+    let created_at = Utc::now();
+    let mut first = Message::new(room.id, user.id, "first".to_string(), uuid::Uuid::new_v4());
+    let mut second = Message::new(room.id, user.id, "second".to_string(), uuid::Uuid::new_v4());
+    first.created_at = created_at;
+    second.created_at = created_at;
+    db.create_message_with_deduplication(first.clone()).await.unwrap();
+    db.create_message_with_deduplication(second.clone()).await.unwrap();
+    
+    let seqs: Vec<(String, i64)> = sqlx::query_as("SELECT id, seq FROM messages WHERE room_id = ? ORDER BY seq")
+        .bind(room.id.0.to_string())
+        .fetch_all(db.pool())
+        .await
+        .unwrap();
+    assert_eq!(seqs, vec![(first.id.0.to_string(), 1), (second.id.0.to_string(), 2)]);
+    
+    // Newest first, and paging past the newer one still finds the older
+    let page = db.get_room_messages(room.id, 1, None).await.unwrap();
+    assert_eq!(page[0].id, second.id);
+    let page = db.get_room_messages(room.id, 1, Some(second.id)).await.unwrap();
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].id, first.id);
+    
+    // A deleted message's number isn't handed out again
+    sqlx::query("DELETE FROM messages WHERE id = ?")
+        .bind(second.id.0.to_string())
+        .execute(db.pool())
+        .await
+        .unwrap();
+    let third = Message::new(room.id, user.id, "third".to_string(), uuid::Uuid::new_v4());
+    db.create_message_with_deduplication(third.clone()).await.unwrap();
+    let seq: i64 = sqlx::query_scalar("SELECT seq FROM messages WHERE id = ?")
+        .bind(third.id.0.to_string())
+        .fetch_one(db.pool())
+        .await
+        .unwrap();
+    assert_eq!(seq, 3);
+}
+
+#[tokio::test]
+async fn test_stored_html_never_contains_scripts_or_event_handlers() {
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+    
+    const FRAGMENTS: &[&str] = &[
+        "<script>alert(1)</script>", "<SCRIPT SRC=//evil.example/x.js></SCRIPT>",
+        "<img src=x onerror=alert(1)>", "<a href=\"javascript:alert(1)\">", "<a HREF=' JaVaScRiPt:alert(1)'>",
+        "<span class=\"mention\" onmouseover=\"steal()\">", "<svg onload=alert(1)>", "<iframe src=\"data:text/html,x\">",
+        "<b onclick=alert(1)>", "<div style=\"background:url(javascript:alert(1))\">", "<object data=x>",
+        "<a href=\"/users/1\" data-mention-id=\"1\" class=\"mention\">", "</a>", "</span>", "<br>", "<code>",
+        "<scr<script>ipt>", "\"", "'", ">", "<", " onfocus=", "=", "hello", "@alice", "/play tada",
+    ];
+    
+    let db = CampfireDatabase::new(":memory:").await.unwrap();
+    let user = User {
+        id: UserId::new(),
+        name: "Test User".to_string(),
+        email: "test@example.com".to_string(),
+        password_hash: "hashed_password".to_string(),
+        bio: None,
+        admin: false,
+        bot_token: None,
+        created_at: Utc::now(),
+    };
+    db.create_user(user.clone()).await.unwrap();
+    let room = Room {
+        id: RoomId::new(),
+        name: "Test Room".to_string(),
+        topic: None,
+        room_type: RoomType::Open,
+        created_at: Utc::now(),
+        last_message_at: None,
+    };
+    db.create_room(room.clone()).await.unwrap();
+    
+    // Writers that skip the rich text pipeline hand HTML straight to the database
+    let mut rng = StdRng::seed_from_u64(1225);
+    for _ in 0..200 {
+        let parts = rng.gen_range(1..12);
+        let html: String = (0..parts).map(|_| *FRAGMENTS.choose(&mut rng).unwrap()).collect();
+        let message = Message::with_rich_content(
+            room.id,
+            user.id,
+            "hostile".to_string(),
+            uuid::Uuid::new_v4(),
+            Some(html.clone()),
+            vec![],
+            vec![],
+        );
+        
+        let returned = db.create_message_with_deduplication(message).await.unwrap();
+        let stored: Option<String> = sqlx::query_scalar("SELECT html_content FROM messages WHERE id = ?")
+            .bind(returned.id.0.to_string())
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(stored, returned.html_content);
+        
+        // Anything left outside a tag is escaped text; check every real tag
+        let stored = stored.unwrap().to_lowercase();
+        let tags = regex::Regex::new(r"<[^>]*>").unwrap();
+        let handler = regex::Regex::new(r"\son[a-z]+\s*=").unwrap();
+        for tag in tags.find_iter(&stored).map(|tag| tag.as_str()) {
+            assert!(!handler.is_match(tag), "{:?} kept a handler: {}", html, tag);
+            assert!(!tag.contains("javascript:") && !tag.contains("style="), "{:?} kept {}", html, tag);
+            assert!(
+                !["<script", "<iframe", "<svg", "<object"].iter().any(|name| tag.starts_with(name)),
+                "{:?} kept {}", html, tag
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_html_stored_before_sanitizing_is_cleaned_on_startup() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let url = format!("sqlite://{}?mode=rwc", dir.path().join("legacy.db").display());
+    let db = CampfireDatabase::new(&url).await.unwrap();
+    let user = User {
+        id: UserId::new(),
+        name: "Test User".to_string(),
+        email: "test@example.com".to_string(),
+        password_hash: "hashed_password".to_string(),
+        bio: None,
+        admin: false,
+        bot_token: None,
+        created_at: Utc::now(),
+    };
+    db.create_user(user.clone()).await.unwrap();
+    let room = Room {
+        id: RoomId::new(),
+        name: "Test Room".to_string(),
+        topic: None,
+        room_type: RoomType::Open,
+        created_at: Utc::now(),
+        last_message_at: None,
+    };
+    db.create_room(room.clone()).await.unwrap();
+    let message = db
+        .create_message_with_deduplication(Message::new(room.id, user.id, "old".to_string(), uuid::Uuid::new_v4()))
+        .await
+        .unwrap();
+    
+    // A row written before the chokepoint existed, in a database that
+    // hasn't run the migration yet
+    sqlx::query("UPDATE messages SET html_content = ? WHERE id = ?")
+        .bind("<b onclick=\"steal()\">hi</b><script>alert(1)</script>")
+        .bind(message.id.0.to_string())
+        .execute(db.pool())
+        .await
+        .unwrap();
+    sqlx::query("DELETE FROM data_migrations").execute(db.pool()).await.unwrap();
+    
+    let reopened = CampfireDatabase::new(&url).await.unwrap();
+    let stored: Option<String> = sqlx::query_scalar("SELECT html_content FROM messages WHERE id = ?")
+        .bind(message.id.0.to_string())
+        .fetch_one(reopened.pool())
+        .await
+        .unwrap();
+    assert_eq!(stored.as_deref(), Some("<b>hi</b>"));
+    
+    // Recorded, so later startups skip it
+    let applied: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM data_migrations WHERE name = 'resanitize_html_content'")
+        .fetch_one(reopened.pool())
+        .await
+        .unwrap();
+    assert_eq!(applied, 1);
+}
+
+#[tokio::test]
+async fn test_heavy_reads_use_the_replica_and_writes_the_primary() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let url = |name: &str| format!("sqlite://{}?mode=rwc", dir.path().join(name).display());
+    let user = User {
+        id: UserId::new(),
+        name: "Test User".to_string(),
+        email: "test@example.com".to_string(),
+        password_hash: "hashed_password".to_string(),
+        bio: None,
+        admin: false,
+        bot_token: None,
+        created_at: Utc::now(),
+    };
+    let room = Room {
+        id: RoomId::new(),
+        name: "Test Room".to_string(),
+        topic: None,
+        room_type: RoomType::Open,
+        created_at: Utc::now(),
+        last_message_at: None,
+    };
+    
+    // A replica that has a message the primary doesn't, so reads show where they went
+    let replica = CampfireDatabase::new(&url("replica.db")).await.unwrap();
+    replica.create_user(user.clone()).await.unwrap();
+    replica.create_room(room.clone()).await.unwrap();
+    let replicated = Message::new(room.id, user.id, "from the replica".to_string(), uuid::Uuid::new_v4());
+    replica.create_message_with_deduplication(replicated).await.unwrap();
+    
+    let primary = CampfireDatabase::new(&url("primary.db")).await.unwrap();
+    primary.create_user(user.clone()).await.unwrap();
+    primary.create_room(room.clone()).await.unwrap();
+    let db = primary
+        .with_read_replica(&url("replica.db"), 2, Duration::from_secs(60))
+        .await
+        .unwrap();
+    
+    let history = db.get_room_messages(room.id, 10, None).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].content, "from the replica");
+    
+    let posted = Message::new(room.id, user.id, "from the primary".to_string(), uuid::Uuid::new_v4());
+    db.create_message_with_deduplication(posted.clone()).await.unwrap();
+    let count = |pool| async move {
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM messages WHERE id = ?")
+            .bind(posted.id.0.to_string())
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    };
+    assert_eq!(count(db.pool()).await, 1);
+    assert_eq!(count(replica.pool()).await, 0);
+    
+    // Having just written, the room and its author read from the primary
+    let history = db.get_room_messages(room.id, 10, None).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].content, "from the primary");
+    let newest = |pool| async move {
+        sqlx::query_scalar::<_, String>("SELECT content FROM messages ORDER BY created_at DESC LIMIT 1")
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    };
+    assert_eq!(newest(db.read_pool_for(user.id)).await, "from the primary");
+    assert_eq!(newest(db.read_pool_for(UserId::new())).await, "from the replica");
+    
+    // The replica refuses writes outright
+    let write = sqlx::query("DELETE FROM messages").execute(db.read_pool_for(UserId::new())).await;
+    assert!(write.is_err());
+}
+
+#[tokio::test]
+async fn test_unread_counts_follow_sequence_when_timestamps_tie() {
+    let db = CampfireDatabase::new(":memory:").await.unwrap();
+    let mut users = Vec::new();
+    for name in ["Reader", "Writer"] {
+        let user = User {
+            id: UserId::new(),
+            name: name.to_string(),
+            email: format!("{}@example.com", name.to_lowercase()),
+            password_hash: "hashed_password".to_string(),
+            bio: None,
+            admin: false,
+            bot_token: None,
+            created_at: Utc::now(),
+        };
+        db.create_user(user.clone()).await.unwrap();
+        users.push(user);
+    }
+    let (reader, writer) = (&users[0], &users[1]);
+    let room = Room {
+        id: RoomId::new(),
+        name: "Test Room".to_string(),
+        topic: None,
+        room_type: RoomType::Open,
+        created_at: Utc::now(),
+        last_message_at: None,
+    };
+    db.create_room(room.clone()).await.unwrap();
+    for user in &users {
+        db.create_membership(Membership {
+            room_id: room.id,
+            user_id: user.id,
+            involvement_level: InvolvementLevel::Member,
+            created_at: Utc::now(),
+        }).await.unwrap();
+    }
+    
+    let created_at = Utc::now();
+    let mut ids = Vec::new();
+    for n in 0..3 {
+        let mut message = Message::new(room.id, writer.id, format!("tied {}", n), uuid::Uuid::new_v4());
+        message.created_at = created_at;
+        ids.push(db.create_message_with_deduplication(message).await.unwrap().id);
+    }
+    db.update_read_marker(reader.id, ids[0]).await.unwrap();
+    
+    let counts = db.get_room_unread_counts(reader.id, 10).await.unwrap();
+    assert_eq!(counts, vec![(room.id, 2)]);
+}
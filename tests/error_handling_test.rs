@@ -79,7 +79,9 @@ async fn test_message_error_handling() {
     // Test rate limit error
     let error = MessageError::RateLimit { 
         limit: 10, 
-        window: "minute".to_string() 
+        window: "minute".to_string(),
+        retry_after_secs: 30,
+        status: None,
     };
     let user_friendly = handle_message_error(error, Some("create_message"));
     
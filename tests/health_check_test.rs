@@ -59,4 +59,37 @@ async fn test_startup_validator() {
     
     let result = validator.validate_all().await;
     assert!(result.is_ok());
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_health_detail_reports_saturation_fields() {
+    use campfire_on_rust::{
+        models::{ConnectionId, UserId}, CampfireDatabase, ConnectionManager, ConnectionManagerImpl,
+        PushNotificationServiceImpl, VapidConfig,
+    };
+    use std::sync::Arc;
+    
+    let db = CampfireDatabase::new(":memory:").await.unwrap();
+    let connections = ConnectionManagerImpl::new(Arc::new(db.clone()));
+    let push = PushNotificationServiceImpl::new(db.clone(), db.writer(), VapidConfig::default());
+    
+    let user_id = UserId::new();
+    let mut receivers = Vec::new();
+    for _ in 0..2 {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        connections.add_connection(user_id, ConnectionId::new(), sender).await.unwrap();
+        receivers.push(receiver);
+    }
+    
+    let detail = health::HealthDetail::collect(&db, &connections, &push).await;
+    assert_eq!(detail.websocket_connections, 2);
+    assert_eq!(detail.writer_queue_depth, 0);
+    assert_eq!(detail.push_in_flight, 0);
+    assert!(detail.db_pool.max > 0);
+    assert!(detail.db_pool.open <= detail.db_pool.max);
+    assert!((0.0..=1.0).contains(&detail.db_pool.utilization));
+    
+    let json = serde_json::to_value(&detail).unwrap();
+    for field in ["writer_queue_depth", "websocket_connections", "db_pool", "push_in_flight"] {
+        assert!(json.get(field).is_some(), "missing {}", field);
+    }
+}
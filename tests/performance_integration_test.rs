@@ -301,6 +301,7 @@ async fn test_optimized_connection_manager_performance() {
     let message = WebSocketMessage::PresenceUpdate {
         room_id,
         online_users: vec![user_id],
+        last_seen: Default::default(),
     };
     
     // First broadcast should populate cache
@@ -1,7 +1,9 @@
 use campfire_on_rust::{
-    CampfireDatabase, PushNotificationServiceImpl, VapidConfig, PushNotificationService,
-    models::*, validation::{CreatePushSubscriptionRequest, PushSubscriptionKeys},
+    CampfireDatabase, PushDispatcher, PushNotificationServiceImpl, VapidConfig, PushNotificationService,
+    config::NotificationSounds, errors::PushNotificationError, models::*, validation::{CreatePushSubscriptionRequest, PushSubscriptionKeys},
 };
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 
 #[tokio::test]
 async fn test_push_notification_service_creation() {
@@ -26,6 +28,7 @@ async fn test_push_notification_service_creation() {
         direct_messages_enabled: true,
         all_messages_enabled: false,
         sounds_enabled: true,
+        room_added_email_enabled: true,
         updated_at: chrono::Utc::now(),
     };
     
@@ -37,6 +40,7 @@ async fn test_push_notification_service_creation() {
             direct_messages_enabled: Some(true),
             all_messages_enabled: Some(false),
             sounds_enabled: Some(true),
+            room_added_email_enabled: None,
         },
     ).await;
     
@@ -111,4 +115,196 @@ async fn test_notification_preferences_defaults() {
     assert!(preferences.direct_messages_enabled);
     assert!(!preferences.all_messages_enabled);
     assert!(preferences.sounds_enabled);
-}
\ No newline at end of file
+}
+
+fn test_subscription(endpoint: String) -> PushSubscription {
+    PushSubscription {
+        id: PushSubscriptionId::new(),
+        user_id: UserId::new(),
+        endpoint,
+        p256dh_key: "p256dh".to_string(),
+        auth_key: "auth".to_string(),
+        created_at: chrono::Utc::now(),
+        last_used_at: None,
+    }
+}
+
+#[tokio::test]
+async fn test_push_fan_out_never_exceeds_concurrency_limit() {
+    let dispatcher = PushDispatcher::new(8, Duration::from_secs(30), Duration::from_secs(60));
+    let jobs: Vec<_> = (0..500)
+        .map(|i| (test_subscription(format!("https://push.example.com/{}", i)), ()))
+        .collect();
+    
+    let in_flight = AtomicUsize::new(0);
+    let peak = AtomicUsize::new(0);
+    let delivered = dispatcher
+        .dispatch(jobs, |_, ()| async {
+            let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(2)).await;
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        })
+        .await;
+    
+    assert_eq!(delivered, 500);
+    assert!(peak.load(Ordering::SeqCst) <= 8, "peak concurrency was {}", peak.load(Ordering::SeqCst));
+    assert_eq!(peak.load(Ordering::SeqCst), 8);
+}
+
+#[tokio::test]
+async fn test_failing_endpoint_backs_off() {
+    let dispatcher = PushDispatcher::new(4, Duration::from_secs(30), Duration::from_secs(60));
+    let endpoint = "https://push.example.com/gone".to_string();
+    let attempts = AtomicUsize::new(0);
+    let send = |_, ()| async {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        Err(PushNotificationError::SendFailed("410 Gone".to_string()))
+    };
+    
+    dispatcher.dispatch(vec![(test_subscription(endpoint.clone()), ())], send).await;
+    assert!(dispatcher.is_backing_off(&endpoint));
+    
+    // Skipped while backing off, without touching the endpoint
+    let delivered = dispatcher.dispatch(vec![(test_subscription(endpoint), ())], send).await;
+    assert_eq!(delivered, 0);
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_mentions_beyond_cap_are_not_notified() {
+    let db = CampfireDatabase::new(":memory:").await.unwrap();
+    
+    let mut usernames = Vec::new();
+    for i in 0..5 {
+        let user = User {
+            id: UserId::new(),
+            name: format!("User {}", i),
+            email: format!("user{}@example.com", i),
+            password_hash: "hash".to_string(),
+            bio: None,
+            admin: false,
+            bot_token: None,
+            created_at: chrono::Utc::now(),
+        };
+        db.create_user(user).await.unwrap();
+        usernames.push(format!("user{}", i));
+    }
+    
+    let room = Room {
+        id: RoomId::new(),
+        name: "Busy".to_string(),
+        topic: None,
+        room_type: RoomType::Open,
+        created_at: chrono::Utc::now(),
+        last_message_at: None,
+    };
+    let mut message = Message::new(room.id, UserId::new(), "spam".to_string(), uuid::Uuid::new_v4());
+    message.mentions = usernames.clone();
+    message.mentions.push("USER0".to_string());
+    
+    let recipients = db.get_notification_recipients(&message, &room, 3).await.unwrap();
+    assert_eq!(recipients.len(), 3);
+    
+    // Under the cap, everyone mentioned is notified once
+    let recipients = db.get_notification_recipients(&message, &room, 10).await.unwrap();
+    assert_eq!(recipients.len(), 5);
+}
+
+#[tokio::test]
+async fn test_mentions_only_room_ignores_all_messages_preference() {
+    let db = CampfireDatabase::new(":memory:").await.unwrap();
+    
+    let room = Room {
+        id: RoomId::new(),
+        name: "Town Hall".to_string(),
+        topic: None,
+        room_type: RoomType::Open,
+        created_at: chrono::Utc::now(),
+        last_message_at: None,
+    };
+    db.create_room(room.clone()).await.unwrap();
+    
+    let mut members = Vec::new();
+    for name in ["sender", "listener", "target"] {
+        let user = User {
+            id: UserId::new(),
+            name: name.to_string(),
+            email: format!("{}@example.com", name),
+            password_hash: "hash".to_string(),
+            bio: None,
+            admin: false,
+            bot_token: None,
+            created_at: chrono::Utc::now(),
+        };
+        db.create_user(user.clone()).await.unwrap();
+        db.create_membership(Membership {
+            room_id: room.id,
+            user_id: user.id,
+            involvement_level: InvolvementLevel::Member,
+            created_at: chrono::Utc::now(),
+        })
+        .await
+        .unwrap();
+        db.update_notification_preferences(NotificationPreferences {
+            user_id: user.id,
+            mentions_enabled: true,
+            direct_messages_enabled: true,
+            all_messages_enabled: true,
+            sounds_enabled: true,
+            room_added_email_enabled: true,
+            updated_at: chrono::Utc::now(),
+        })
+        .await
+        .unwrap();
+        members.push(user.id);
+    }
+    let (sender, target) = (members[0], members[2]);
+    
+    let plain = Message::new(room.id, sender, "lunch?".to_string(), uuid::Uuid::new_v4());
+    assert_eq!(db.get_notification_recipients(&plain, &room, 10).await.unwrap().len(), 2);
+    
+    db.set_room_notify_mentions_only(room.id, true).await.unwrap();
+    assert!(db.get_room_notify_mentions_only(room.id).await.unwrap());
+    assert!(db.get_notification_recipients(&plain, &room, 10).await.unwrap().is_empty());
+    
+    let mut mention = Message::new(room.id, sender, "@target lunch?".to_string(), uuid::Uuid::new_v4());
+    mention.mentions = vec!["target".to_string()];
+    let recipients = db.get_notification_recipients(&mention, &room, 10).await.unwrap();
+    assert_eq!(recipients.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![target]);
+    
+    let mut everyone = Message::new(room.id, sender, "@room lunch!".to_string(), uuid::Uuid::new_v4());
+    everyone.mentions = vec!["room".to_string()];
+    assert_eq!(db.get_notification_recipients(&everyone, &room, 10).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_mention_payload_carries_configured_sound_hint() {
+    let db = CampfireDatabase::new(":memory:").await.unwrap();
+    let push_service = PushNotificationServiceImpl::new(db.clone(), db.writer(), VapidConfig::default())
+        .with_sounds(NotificationSounds {
+            mention: Some("chime".to_string()),
+            ..NotificationSounds::default()
+        });
+    
+    let room = Room {
+        id: RoomId::new(),
+        name: "General".to_string(),
+        topic: None,
+        room_type: RoomType::Open,
+        created_at: chrono::Utc::now(),
+        last_message_at: None,
+    };
+    let message = Message::new(room.id, UserId::new(), "@target look".to_string(), uuid::Uuid::new_v4());
+    
+    let payload = push_service.create_notification_payload(NotificationType::Mention, &message, &room, "Alice", true);
+    assert_eq!(payload.data["sound"], "chime");
+    
+    let payload = push_service.create_notification_payload(NotificationType::DirectMessage, &message, &room, "Alice", true);
+    assert_eq!(payload.data["sound"], "direct_message");
+    
+    // Users who turned sounds off get no hint
+    let payload = push_service.create_notification_payload(NotificationType::Mention, &message, &room, "Alice", false);
+    assert!(payload.data["sound"].is_null());
+}
@@ -1,9 +1,11 @@
 use campfire_on_rust::{CampfireDatabase, RoomService, RoomServiceTrait};
 use campfire_on_rust::models::{
-    User, UserId, RoomId, RoomType, InvolvementLevel
+    Membership, Message, Room, User, UserId, RoomId, RoomType, InvolvementLevel
 };
 use campfire_on_rust::validation::CreateRoomRequest;
 use campfire_on_rust::errors::RoomError;
+use campfire_on_rust::config::StorageConfig;
+use campfire_on_rust::storage::{LocalBlobStore, QuotaBlobStore};
 use chrono::Utc;
 use std::sync::Arc;
 use uuid::Uuid;
@@ -191,6 +193,80 @@ async fn test_add_member_already_member() {
     assert!(matches!(result, Err(RoomError::AlreadyMember { .. })));
 }
 
+#[tokio::test]
+async fn test_add_member_direct_room_rejects_third_member() {
+    let db = create_test_db().await;
+    let room_service = RoomService::new(Arc::new(db.clone()));
+    
+    let creator_id = create_test_user(&db, "creator@test.com", "Creator").await;
+    let other_id = create_test_user(&db, "other@test.com", "Other").await;
+    let third_id = create_test_user(&db, "third@test.com", "Third").await;
+    
+    let direct = room_service.create_room(
+        "Direct".to_string(),
+        None,
+        RoomType::Direct,
+        creator_id,
+    ).await.unwrap();
+    room_service.add_member(
+        direct.id,
+        other_id,
+        creator_id,
+        InvolvementLevel::Member,
+    ).await.unwrap();
+    
+    let result = room_service.add_member(
+        direct.id,
+        third_id,
+        creator_id,
+        InvolvementLevel::Member,
+    ).await;
+    
+    assert!(matches!(result, Err(RoomError::DirectRoomFull { max_members: 2, .. })));
+    assert!(room_service.check_room_access(direct.id, third_id).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_rapid_open_room_joins_are_throttled() {
+    let db = create_test_db().await;
+    let room_service = RoomService::new(Arc::new(db.clone())).with_join_rate_limit(2);
+    
+    let creator_id = create_test_user(&db, "creator@test.com", "Creator").await;
+    let joiner_id = create_test_user(&db, "joiner@test.com", "Joiner").await;
+    
+    let mut rooms = Vec::new();
+    for i in 0..3 {
+        let room = room_service.create_room(
+            format!("Open {}", i),
+            None,
+            RoomType::Open,
+            creator_id,
+        ).await.unwrap();
+        rooms.push(room.id);
+    }
+    
+    for room_id in &rooms[..2] {
+        room_service.add_member(*room_id, joiner_id, joiner_id, InvolvementLevel::Member).await.unwrap();
+    }
+    let result = room_service.add_member(rooms[2], joiner_id, joiner_id, InvolvementLevel::Member).await;
+    assert!(matches!(result, Err(RoomError::JoinRateLimit { limit: 2, retry_after_secs }) if retry_after_secs > 0));
+    assert!(db.get_membership(rooms[2], joiner_id).await.unwrap().is_none());
+    
+    // Site admins adding people aren't throttled
+    let admin = User {
+        id: UserId::new(),
+        name: "Admin".to_string(),
+        email: "admin@test.com".to_string(),
+        password_hash: "test_hash".to_string(),
+        bio: None,
+        admin: true,
+        bot_token: None,
+        created_at: Utc::now(),
+    };
+    db.create_user(admin.clone()).await.unwrap();
+    room_service.add_member(rooms[2], joiner_id, admin.id, InvolvementLevel::Member).await.unwrap();
+}
+
 #[tokio::test]
 async fn test_add_member_room_not_found() {
     let db = create_test_db().await;
@@ -274,6 +350,232 @@ async fn test_check_room_access_closed_room() {
     assert!(access.is_none());
 }
 
+#[tokio::test]
+async fn test_change_room_type_open_to_closed_preserves_members() {
+    let db = create_test_db().await;
+    let room_service = RoomService::new(Arc::new(db.clone()));
+    
+    let creator_id = create_test_user(&db, "creator@test.com", "Creator").await;
+    let member_id = create_test_user(&db, "member@test.com", "Member").await;
+    let other_user_id = create_test_user(&db, "other@test.com", "Other").await;
+    
+    let room = room_service.create_room(
+        "Open Room".to_string(),
+        None,
+        RoomType::Open,
+        creator_id,
+    ).await.unwrap();
+    room_service.add_member(
+        room.id,
+        member_id,
+        creator_id,
+        InvolvementLevel::Member,
+    ).await.unwrap();
+    
+    let room = room_service.change_room_type(room.id, creator_id, RoomType::Closed).await.unwrap();
+    assert!(matches!(room.room_type, RoomType::Closed));
+    
+    let stored = room_service.get_room_by_id(room.id).await.unwrap().unwrap();
+    assert!(matches!(stored.room_type, RoomType::Closed));
+    
+    // Explicit members keep access, implicit open-room access is gone
+    let access = room_service.check_room_access(room.id, member_id).await.unwrap();
+    assert!(matches!(access, Some(InvolvementLevel::Member)));
+    let access = room_service.check_room_access(room.id, other_user_id).await.unwrap();
+    assert!(access.is_none());
+    
+    // And back again
+    let room = room_service.change_room_type(room.id, creator_id, RoomType::Open).await.unwrap();
+    assert!(matches!(room.room_type, RoomType::Open));
+    let access = room_service.check_room_access(room.id, other_user_id).await.unwrap();
+    assert!(matches!(access, Some(InvolvementLevel::Member)));
+}
+
+#[tokio::test]
+async fn test_change_room_type_requires_room_admin() {
+    let db = create_test_db().await;
+    let room_service = RoomService::new(Arc::new(db.clone()));
+    
+    let creator_id = create_test_user(&db, "creator@test.com", "Creator").await;
+    let member_id = create_test_user(&db, "member@test.com", "Member").await;
+    
+    let room = room_service.create_room(
+        "Closed Room".to_string(),
+        None,
+        RoomType::Closed,
+        creator_id,
+    ).await.unwrap();
+    room_service.add_member(
+        room.id,
+        member_id,
+        creator_id,
+        InvolvementLevel::Member,
+    ).await.unwrap();
+    
+    let result = room_service.change_room_type(room.id, member_id, RoomType::Open).await;
+    assert!(matches!(result, Err(RoomError::NotAuthorized { .. })));
+}
+
+#[tokio::test]
+async fn test_change_room_type_rejects_direct_rooms() {
+    let db = create_test_db().await;
+    let room_service = RoomService::new(Arc::new(db.clone()));
+    
+    let creator_id = create_test_user(&db, "creator@test.com", "Creator").await;
+    
+    let direct = room_service.create_room(
+        "Direct".to_string(),
+        None,
+        RoomType::Direct,
+        creator_id,
+    ).await.unwrap();
+    let result = room_service.change_room_type(direct.id, creator_id, RoomType::Open).await;
+    assert!(matches!(result, Err(RoomError::InvalidTypeChange { .. })));
+    
+    let open = room_service.create_room(
+        "Open".to_string(),
+        None,
+        RoomType::Open,
+        creator_id,
+    ).await.unwrap();
+    let result = room_service.change_room_type(open.id, creator_id, RoomType::Direct).await;
+    assert!(matches!(result, Err(RoomError::InvalidTypeChange { .. })));
+}
+
+#[tokio::test]
+async fn test_only_open_rooms_can_be_public() {
+    let db = create_test_db().await;
+    let room_service = RoomService::new(Arc::new(db.clone()));
+    
+    let creator_id = create_test_user(&db, "creator@test.com", "Creator").await;
+    
+    let closed = room_service.create_room(
+        "Closed".to_string(),
+        None,
+        RoomType::Closed,
+        creator_id,
+    ).await.unwrap();
+    let result = room_service.set_public(closed.id, creator_id, true).await;
+    assert!(matches!(result, Err(RoomError::NotOpen { .. })));
+    
+    let open = room_service.create_room(
+        "Open".to_string(),
+        None,
+        RoomType::Open,
+        creator_id,
+    ).await.unwrap();
+    room_service.set_public(open.id, creator_id, true).await.unwrap();
+    assert!(db.is_room_public(open.id).await.unwrap());
+    
+    // Closing the room withdraws public access, and reopening doesn't restore it
+    room_service.change_room_type(open.id, creator_id, RoomType::Closed).await.unwrap();
+    room_service.change_room_type(open.id, creator_id, RoomType::Open).await.unwrap();
+    assert!(!db.is_room_public(open.id).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_resolve_or_create_returns_existing_open_room() {
+    let db = create_test_db().await;
+    let room_service = RoomService::new(Arc::new(db.clone()));
+    
+    let creator_id = create_test_user(&db, "creator@test.com", "Creator").await;
+    let other_id = create_test_user(&db, "other@test.com", "Other").await;
+    
+    let general = room_service.create_room(
+        "general".to_string(),
+        None,
+        RoomType::Open,
+        creator_id,
+    ).await.unwrap();
+    
+    let (room, created) = room_service
+        .resolve_or_create_room("#General".to_string(), other_id)
+        .await
+        .unwrap();
+    assert!(!created);
+    assert_eq!(room.id, general.id);
+    assert_eq!(room_service.check_room_access(room.id, other_id).await.unwrap(), Some(InvolvementLevel::Member));
+    
+    // A closed room with the same name doesn't count, and an open one can't
+    // be created next to the existing one
+    let result = room_service.create_room(
+        "GENERAL".to_string(),
+        None,
+        RoomType::Open,
+        other_id,
+    ).await;
+    assert!(matches!(result, Err(RoomError::InvalidName { .. })));
+}
+
+#[tokio::test]
+async fn test_resolve_or_create_creates_once() {
+    let db = create_test_db().await;
+    let room_service = RoomService::new(Arc::new(db.clone()));
+    
+    let user_id = create_test_user(&db, "user@test.com", "User").await;
+    let other_id = create_test_user(&db, "other@test.com", "Other").await;
+    
+    let (room, created) = room_service
+        .resolve_or_create_room("#newchannel".to_string(), user_id)
+        .await
+        .unwrap();
+    assert!(created);
+    assert_eq!(room.name, "newchannel");
+    assert_eq!(room.room_type, RoomType::Open);
+    assert_eq!(room_service.check_room_access(room.id, user_id).await.unwrap(), Some(InvolvementLevel::Admin));
+    
+    let (again, created_again) = room_service
+        .resolve_or_create_room("newchannel".to_string(), other_id)
+        .await
+        .unwrap();
+    assert!(!created_again);
+    assert_eq!(again.id, room.id);
+    
+    // Only the first call created a room (and an admin membership)
+    let count = |rooms: Vec<Room>| rooms.into_iter().filter(|r| r.name == "newchannel").count();
+    assert_eq!(count(room_service.get_user_rooms(user_id).await.unwrap()), 1);
+    assert_eq!(count(room_service.get_user_rooms(other_id).await.unwrap()), 0);
+    
+    let result = room_service.resolve_or_create_room("#  ".to_string(), user_id).await;
+    assert!(matches!(result, Err(RoomError::InvalidName { .. })));
+}
+
+#[tokio::test]
+async fn test_room_permissions_admin_vs_member() {
+    let db = create_test_db().await;
+    let room_service = RoomService::new(Arc::new(db.clone()));
+    
+    let admin_id = create_test_user(&db, "admin@test.com", "Admin").await;
+    let member_id = create_test_user(&db, "member@test.com", "Member").await;
+    let outsider_id = create_test_user(&db, "outsider@test.com", "Outsider").await;
+    
+    let room = room_service.create_room(
+        "Closed Room".to_string(),
+        None,
+        RoomType::Closed,
+        admin_id,
+    ).await.unwrap();
+    room_service.add_member(
+        room.id,
+        member_id,
+        admin_id,
+        InvolvementLevel::Member,
+    ).await.unwrap();
+    
+    let admin = room_service.get_permissions(room.id, admin_id).await.unwrap();
+    assert_eq!(admin.involvement_level, Some(InvolvementLevel::Admin));
+    assert!(admin.is_admin && admin.can_read && admin.can_post);
+    assert!(admin.can_invite && admin.can_change_type);
+    
+    let member = room_service.get_permissions(room.id, member_id).await.unwrap();
+    assert_eq!(member.involvement_level, Some(InvolvementLevel::Member));
+    assert!(member.can_read && member.can_post);
+    assert!(!member.is_admin && !member.can_invite && !member.can_change_type);
+    
+    let outsider = room_service.get_permissions(room.id, outsider_id).await.unwrap();
+    assert!(!outsider.can_read && !outsider.can_post && !outsider.can_invite);
+}
+
 #[tokio::test]
 async fn test_check_room_access_room_not_found() {
     let db = create_test_db().await;
@@ -362,11 +664,13 @@ async fn test_room_name_validation_properties() {
         (too_long_name.as_str(), false),      // Too long
     ];
     
+    // Closed rooms, since "Valid Room" is created twice and open room names
+    // must be unique
     for (name, should_succeed) in test_cases {
         let result = room_service.create_room(
             name.to_string(),
             None,
-            RoomType::Open,
+            RoomType::Closed,
             creator_id,
         ).await;
         
@@ -449,4 +753,512 @@ async fn test_complete_room_workflow() {
     
     let new_member_access = room_service.check_room_access(room.id, new_member_id).await.unwrap();
     assert!(matches!(new_member_access, Some(InvolvementLevel::Member)));
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_inactive_rooms_are_auto_archived() {
+    let db = create_test_db().await;
+    let room_service = RoomService::new(Arc::new(db.clone()));
+    let user_id = create_test_user(&db, "member@test.com", "Member").await;
+    
+    let now = Utc::now();
+    let create = |name: &str, room_type: RoomType, last_message_days_ago: Option<i64>| {
+        let room = Room {
+            id: RoomId::new(),
+            name: name.to_string(),
+            topic: None,
+            room_type,
+            created_at: now - chrono::Duration::days(365),
+            last_message_at: last_message_days_ago.map(|days| now - chrono::Duration::days(days)),
+        };
+        let db = db.clone();
+        async move {
+            db.create_room(room.clone()).await.unwrap();
+            db.create_membership(Membership {
+                room_id: room.id,
+                user_id,
+                involvement_level: InvolvementLevel::Member,
+                created_at: now,
+            }).await.unwrap();
+            room.id
+        }
+    };
+    let stale = create("Stale", RoomType::Open, Some(120)).await;
+    let never_used = create("Never Used", RoomType::Closed, None).await;
+    let active = create("Active", RoomType::Open, Some(1)).await;
+    let old_dm = create("Old DM", RoomType::Direct, Some(120)).await;
+    
+    let mut archived = room_service.archive_inactive_rooms(chrono::Duration::days(90)).await.unwrap();
+    archived.sort_by_key(|id| id.0);
+    let mut expected = vec![stale, never_used];
+    expected.sort_by_key(|id| id.0);
+    assert_eq!(archived, expected);
+    
+    let archived = db.get_archived_room_ids(user_id).await.unwrap();
+    assert!(!archived.contains(&active));
+    assert!(!archived.contains(&old_dm));
+    
+    // Already-archived rooms aren't reported again
+    assert!(room_service.archive_inactive_rooms(chrono::Duration::days(90)).await.unwrap().is_empty());
+    
+    // A new message brings the room back
+    let message = Message::new(stale, user_id, "Anyone here?".to_string(), Uuid::new_v4());
+    db.create_message_with_deduplication(message).await.unwrap();
+    assert_eq!(db.get_archived_room_ids(user_id).await.unwrap(), vec![never_used]);
+}
+
+#[tokio::test]
+async fn test_room_list_preview_follows_last_message() {
+    let db = create_test_db().await;
+    let room_service = RoomService::new(Arc::new(db.clone())).with_preview_length(20);
+    let alice = create_test_user(&db, "alice@test.com", "Alice").await;
+    let bob = create_test_user(&db, "bob@test.com", "Bob").await;
+    
+    let room = room_service.create_room("Preview".to_string(), None, RoomType::Open, alice).await.unwrap();
+    let preview = || async {
+        let list = room_service.get_room_list(alice).await.unwrap();
+        list.into_iter().find(|entry| entry.room.id == room.id).unwrap().last_message_preview
+    };
+    assert!(preview().await.is_none());
+    
+    // Posting
+    let first = Message::new(room.id, alice, "Hello   everyone".to_string(), Uuid::new_v4());
+    db.create_message_with_deduplication(first).await.unwrap();
+    let last = Message::new(room.id, bob, "Deploy is done, all green now".to_string(), Uuid::new_v4());
+    let last = db.create_message_with_deduplication(last).await.unwrap();
+    
+    let current = preview().await.unwrap();
+    assert_eq!(current.sender_name, "Bob");
+    assert_eq!(current.text, "Deploy is done, all…");
+    
+    // Editing
+    sqlx::query("UPDATE messages SET content = ? WHERE id = ?")
+        .bind("Deploy rolled back")
+        .bind(last.id.0.to_string())
+        .execute(db.pool())
+        .await
+        .unwrap();
+    assert_eq!(preview().await.unwrap().text, "Deploy rolled back");
+    
+    // Deleting falls back to the message before it
+    sqlx::query("DELETE FROM messages WHERE id = ?")
+        .bind(last.id.0.to_string())
+        .execute(db.pool())
+        .await
+        .unwrap();
+    let current = preview().await.unwrap();
+    assert_eq!(current.sender_name, "Alice");
+    assert_eq!(current.text, "Hello everyone");
+}
+
+#[tokio::test]
+async fn test_direct_conversations_lists_only_direct_rooms() {
+    let db = create_test_db().await;
+    let room_service = RoomService::new(Arc::new(db.clone()));
+    
+    let alice = create_test_user(&db, "alice@test.com", "Alice").await;
+    let bob = create_test_user(&db, "bob@test.com", "Bob").await;
+    let carol = create_test_user(&db, "carol@test.com", "Carol").await;
+    
+    room_service.create_room("Open".to_string(), None, RoomType::Open, alice).await.unwrap();
+    let with_bob = room_service.create_room("Alice & Bob".to_string(), None, RoomType::Direct, alice).await.unwrap();
+    room_service.add_member(with_bob.id, bob, alice, InvolvementLevel::Member).await.unwrap();
+    let with_carol = room_service.create_room("Alice & Carol".to_string(), None, RoomType::Direct, alice).await.unwrap();
+    room_service.add_member(with_carol.id, carol, alice, InvolvementLevel::Member).await.unwrap();
+    
+    // Sent in the same instant, so only the sequence tells them apart
+    let sent_at = Utc::now();
+    let mut sent = Vec::new();
+    for content in ["Hi Alice", "Are you there?"] {
+        let mut message = Message::new(with_bob.id, bob, content.to_string(), Uuid::new_v4());
+        message.created_at = sent_at;
+        sent.push(db.create_message_with_deduplication(message).await.unwrap().id);
+    }
+    
+    let conversations = room_service.get_direct_conversations(alice).await.unwrap();
+    assert_eq!(conversations.len(), 2);
+    assert!(conversations.iter().all(|c| c.room.room_type == RoomType::Direct));
+    
+    // Bob's messages make his conversation the most recent
+    assert_eq!(conversations[0].room.id, with_bob.id);
+    let participant = conversations[0].participant.as_ref().unwrap();
+    assert_eq!(participant.id, bob);
+    assert_eq!(participant.name, "Bob");
+    assert_eq!(conversations[0].unread_count, 2);
+    
+    assert_eq!(conversations[1].room.id, with_carol.id);
+    assert_eq!(conversations[1].participant.as_ref().unwrap().id, carol);
+    assert_eq!(conversations[1].unread_count, 0);
+    
+    // Bob sees Alice, and his own messages aren't unread
+    let bobs = room_service.get_direct_conversations(bob).await.unwrap();
+    assert_eq!(bobs.len(), 1);
+    assert_eq!(bobs[0].participant.as_ref().unwrap().id, alice);
+    assert_eq!(bobs[0].unread_count, 0);
+    
+    db.update_read_marker(alice, sent[0]).await.unwrap();
+    let conversations = room_service.get_direct_conversations(alice).await.unwrap();
+    assert_eq!(conversations[0].unread_count, 1);
+}
+
+#[tokio::test]
+async fn test_mentionable_filters_members_by_prefix() {
+    let db = create_test_db().await;
+    let room_service = RoomService::new(Arc::new(db.clone()));
+    
+    let creator = create_test_user(&db, "creator@test.com", "Creator").await;
+    let alice = create_test_user(&db, "alice@test.com", "Alice Smith").await;
+    let albert = create_test_user(&db, "bert@test.com", "Albert").await;
+    let outsider = create_test_user(&db, "alfred@test.com", "Alfred").await;
+    let bob = create_test_user(&db, "bob@test.com", "Bob").await;
+    
+    let room = room_service.create_room("Team".to_string(), None, RoomType::Closed, creator).await.unwrap();
+    for member in [alice, albert, bob] {
+        room_service.add_member(room.id, member, creator, InvolvementLevel::Member).await.unwrap();
+    }
+    
+    // Alfred matches the prefix but isn't in the room
+    let candidates = room_service.get_mentionable(room.id, creator, "@AL").await.unwrap();
+    let ids: Vec<_> = candidates.iter().map(|c| c.user_id).collect();
+    assert_eq!(ids, vec![Some(albert), Some(alice)]);
+    assert_eq!(candidates[1].handle, "alice");
+    
+    // Usernames match too, and room-wide mentions are offered
+    let candidates = room_service.get_mentionable(room.id, creator, "b").await.unwrap();
+    let handles: Vec<_> = candidates.iter().map(|c| c.handle.as_str()).collect();
+    assert_eq!(handles, vec!["bert", "bob"]);
+    
+    let candidates = room_service.get_mentionable(room.id, creator, "h").await.unwrap();
+    assert_eq!(candidates.len(), 1);
+    assert_eq!(candidates[0].handle, "here");
+    assert_eq!(candidates[0].user_id, None);
+    
+    // Only people who can see the room may ask
+    let result = room_service.get_mentionable(room.id, outsider, "a").await;
+    assert!(matches!(result, Err(RoomError::NotAuthorized { .. })));
+}
+
+#[tokio::test]
+async fn test_mentionable_never_reveals_bot_tokens_or_emails() {
+    let db = create_test_db().await;
+    let room_service = RoomService::new(Arc::new(db.clone()));
+    
+    let creator = create_test_user(&db, "creator@test.com", "Creator").await;
+    let bot = User {
+        id: UserId::new(),
+        name: "Deploy Bot".to_string(),
+        email: "bot-s3cr3tt0k3n@campfire.local".to_string(),
+        password_hash: String::new(),
+        bio: None,
+        admin: false,
+        bot_token: Some("s3cr3tt0k3n".to_string()),
+        created_at: Utc::now(),
+    };
+    db.create_user(bot.clone()).await.unwrap();
+    let room = room_service.create_room("Ops".to_string(), None, RoomType::Closed, creator).await.unwrap();
+    room_service.add_member(room.id, bot.id, creator, InvolvementLevel::Member).await.unwrap();
+    
+    // The bot goes by its name, not anything from its email
+    let candidates = room_service.get_mentionable(room.id, creator, "").await.unwrap();
+    let bot_candidate = candidates.iter().find(|c| c.user_id == Some(bot.id)).unwrap();
+    assert_eq!(bot_candidate.handle, "deploy-bot");
+    let response = serde_json::to_string(&candidates).unwrap();
+    assert!(!response.contains("s3cr3tt0k3n"));
+    assert!(!response.contains("@test.com") && !response.contains("campfire.local"));
+    
+    // Emails can't be probed by prefix either
+    assert!(room_service.get_mentionable(room.id, creator, "bot-s3").await.unwrap().is_empty());
+    assert!(room_service.get_mentionable(room.id, creator, "creator@").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_membership_changes_notify_the_affected_user() {
+    use campfire_on_rust::{ConnectionManager, ConnectionManagerImpl};
+    use campfire_on_rust::models::{ConnectionId, WebSocketMessage, WS_PROTOCOL_VERSION};
+    
+    let db = Arc::new(create_test_db().await);
+    let connection_manager = Arc::new(ConnectionManagerImpl::new(db.clone()));
+    let room_service = RoomService::with_connection_manager(db.clone(), connection_manager.clone());
+    
+    let admin = create_test_user(&db, "admin@test.com", "Admin").await;
+    let user = create_test_user(&db, "user@test.com", "User").await;
+    let room = room_service.create_room("Private".to_string(), None, RoomType::Closed, admin).await.unwrap();
+    
+    let connection_id = ConnectionId::new();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    connection_manager.add_connection(user, connection_id, tx).await.unwrap();
+    connection_manager.set_protocol_version(connection_id, WS_PROTOCOL_VERSION).await.unwrap();
+    
+    room_service.add_member(room.id, user, admin, InvolvementLevel::Member).await.unwrap();
+    let frame: serde_json::Value = serde_json::from_str(&rx.try_recv().unwrap()).unwrap();
+    assert_eq!(frame["type"], "RoomMembershipChanged");
+    assert_eq!(frame["room_id"], room.id.0.to_string());
+    assert_eq!(frame["added"], true);
+    
+    // Now a member, they receive the room's broadcasts
+    connection_manager
+        .broadcast_to_room(room.id, WebSocketMessage::RoomArchived { room_id: room.id })
+        .await
+        .unwrap();
+    assert!(rx.try_recv().unwrap().contains("RoomArchived"));
+    
+    // Only admins may remove members
+    let result = room_service.remove_member(room.id, admin, user).await;
+    assert!(matches!(result, Err(RoomError::NotAuthorized { .. })));
+    
+    room_service.remove_member(room.id, user, admin).await.unwrap();
+    let frame: serde_json::Value = serde_json::from_str(&rx.try_recv().unwrap()).unwrap();
+    assert_eq!(frame["type"], "RoomMembershipChanged");
+    assert_eq!(frame["added"], false);
+    assert!(room_service.check_room_access(room.id, user).await.unwrap().is_none());
+    
+    // ...and stop receiving the room's events
+    let _ = connection_manager
+        .broadcast_to_room(room.id, WebSocketMessage::RoomArchived { room_id: room.id })
+        .await;
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_room_name_limits_count_graphemes() {
+    let db = create_test_db().await;
+    let room_service = RoomService::new(Arc::new(db.clone()));
+    let creator_id = create_test_user(&db, "creator@test.com", "Creator").await;
+    
+    // 100 flags are 800 bytes but 100 characters on screen
+    let flags = "\u{1F1F3}\u{1F1F4}".repeat(100);
+    let room = room_service.create_room(flags.clone(), None, RoomType::Closed, creator_id).await.unwrap();
+    assert_eq!(room.name, flags);
+    
+    let result = room_service.create_room(format!("{}!", flags), None, RoomType::Closed, creator_id).await;
+    assert!(matches!(result, Err(RoomError::InvalidName { ref reason }) if reason.contains("101 chars")));
+    
+    let result = room_service
+        .create_room("Launch\u{8}".to_string(), None, RoomType::Closed, creator_id)
+        .await;
+    assert!(matches!(result, Err(RoomError::InvalidName { ref reason }) if reason.contains("control characters")));
+    
+    let room = room_service
+        .create_room("  Launch \n plans ".to_string(), Some(" \t ".to_string()), RoomType::Closed, creator_id)
+        .await
+        .unwrap();
+    assert_eq!(room.name, "Launch plans");
+    assert_eq!(room.topic, None);
+}
+
+#[tokio::test]
+async fn test_admin_delete_room_removes_everything() {
+    use campfire_on_rust::{ConnectionManager, ConnectionManagerImpl};
+    use campfire_on_rust::models::{ConnectionId, WebSocketMessage, WS_PROTOCOL_VERSION};
+    
+    let db = Arc::new(create_test_db().await);
+    let connection_manager = Arc::new(ConnectionManagerImpl::new(db.clone()));
+    let room_service = RoomService::with_connection_manager(db.clone(), connection_manager.clone());
+    
+    let site_admin = User {
+        id: UserId::new(),
+        name: "Site Admin".to_string(),
+        email: "site-admin@test.com".to_string(),
+        password_hash: "test_hash".to_string(),
+        bio: None,
+        admin: true,
+        bot_token: None,
+        created_at: Utc::now(),
+    };
+    db.create_user(site_admin.clone()).await.unwrap();
+    let owner = create_test_user(&db, "owner@test.com", "Owner").await;
+    let member = create_test_user(&db, "member@test.com", "Member").await;
+    let room = room_service.create_room("Doomed".to_string(), None, RoomType::Closed, owner).await.unwrap();
+    
+    let connection_id = ConnectionId::new();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    connection_manager.add_connection(member, connection_id, tx).await.unwrap();
+    connection_manager.set_protocol_version(connection_id, WS_PROTOCOL_VERSION).await.unwrap();
+    room_service.add_member(room.id, member, owner, InvolvementLevel::Member).await.unwrap();
+    while rx.try_recv().is_ok() {}
+    
+    // Uploads posted in the room go with it, and their quota is released
+    let storage_dir = tempfile::TempDir::new().unwrap();
+    let blob_store = Arc::new(QuotaBlobStore::new(
+        Arc::new(LocalBlobStore::new(storage_dir.path().to_path_buf())),
+        db.clone(),
+        &StorageConfig {
+            local_path: storage_dir.path().to_path_buf(),
+            ..Default::default()
+        },
+    ));
+    room_service.event_bus().subscribe(blob_store.clone());
+    let key = "attachments/farewell.png";
+    blob_store.put_attachment(member, room.id, key, vec![0; 64], "image/png").await.unwrap();
+    assert_eq!(blob_store.used_bytes(), 64);
+    
+    let message = db
+        .create_message_with_deduplication(Message::new(room.id, member, "goodbye".to_string(), Uuid::new_v4()))
+        .await
+        .unwrap();
+    db.link_message_attachments(message.id, vec![key.to_string()]).await.unwrap();
+    db.update_read_marker(member, message.id).await.unwrap();
+    assert!(db.save_message(member, message.id, room.id, 0).await.unwrap());
+    
+    // Room admins are not enough; this is reserved for site admins
+    let result = room_service.delete_room_permanently(room.id, owner, None).await;
+    assert!(matches!(result, Err(RoomError::NotAuthorized { .. })));
+    
+    // The first call only hands back the confirmation token
+    let token = match room_service.delete_room_permanently(room.id, site_admin.id, None).await {
+        Err(RoomError::ConfirmationRequired { token, .. }) => token,
+        other => panic!("expected ConfirmationRequired, got {:?}", other),
+    };
+    let result = room_service
+        .delete_room_permanently(room.id, site_admin.id, Some("wrong".to_string()))
+        .await;
+    assert!(matches!(result, Err(RoomError::ConfirmationRequired { .. })));
+    assert!(db.get_room_by_id(room.id).await.unwrap().is_some());
+    
+    room_service.delete_room_permanently(room.id, site_admin.id, Some(token)).await.unwrap();
+    
+    for (table, column) in [
+        ("rooms", "id"),
+        ("messages", "room_id"),
+        ("room_memberships", "room_id"),
+        ("saved_messages", "room_id"),
+        ("blobs", "room_id"),
+    ] {
+        let (count,): (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) FROM {} WHERE {} = ?", table, column))
+            .bind(room.id.0.to_string())
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(count, 0, "{} still has rows for the room", table);
+    }
+    let (markers,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM read_markers WHERE user_id = ?")
+        .bind(member.0.to_string())
+        .fetch_one(db.pool())
+        .await
+        .unwrap();
+    assert_eq!(markers, 0);
+    let (indexed,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM messages_fts WHERE messages_fts MATCH 'goodbye'")
+        .fetch_one(db.pool())
+        .await
+        .unwrap();
+    assert_eq!(indexed, 0);
+    assert!(db.get_saved_messages(member, 10, None).await.unwrap().is_empty());
+    assert!(!storage_dir.path().join(key).exists());
+    assert_eq!(blob_store.used_bytes(), 0);
+    
+    // Subscribers are told and detached from the room
+    let frame: serde_json::Value = serde_json::from_str(&rx.try_recv().unwrap()).unwrap();
+    assert_eq!(frame["type"], "RoomDeleted");
+    assert_eq!(frame["room_id"], room.id.0.to_string());
+    let _ = connection_manager
+        .broadcast_to_room(room.id, WebSocketMessage::RoomArchived { room_id: room.id })
+        .await;
+    assert!(rx.try_recv().is_err());
+    
+    let result = room_service.delete_room_permanently(room.id, site_admin.id, None).await;
+    assert!(matches!(result, Err(RoomError::NotFound { .. })));
+}
+
+#[derive(Default)]
+struct RecordingMailer {
+    sent: std::sync::Mutex<Vec<campfire_on_rust::services::mailer::Email>>,
+}
+
+#[async_trait::async_trait]
+impl campfire_on_rust::services::mailer::Mailer for RecordingMailer {
+    async fn send(
+        &self,
+        email: campfire_on_rust::services::mailer::Email,
+    ) -> Result<(), campfire_on_rust::errors::MailerError> {
+        self.sent.lock().unwrap().push(email);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_adding_to_closed_room_emails_users_who_want_it() {
+    use campfire_on_rust::models::NotificationPreferences;
+    use campfire_on_rust::services::mailer::RoomAddedEmailSubscriber;
+    
+    let db = Arc::new(create_test_db().await);
+    let room_service = RoomService::new(db.clone());
+    let mailer = Arc::new(RecordingMailer::default());
+    room_service.event_bus().subscribe(Arc::new(RoomAddedEmailSubscriber::new(
+        db.clone(),
+        mailer.clone(),
+        "https://chat.example.com/".to_string(),
+    )));
+    
+    let admin_id = create_test_user(&db, "admin@test.com", "Admin").await;
+    let wants_email = create_test_user(&db, "wants@test.com", "Wants").await;
+    let opted_out = create_test_user(&db, "opted-out@test.com", "Opted Out").await;
+    db.update_notification_preferences(NotificationPreferences {
+        user_id: opted_out,
+        room_added_email_enabled: false,
+        ..Default::default()
+    }).await.unwrap();
+    
+    let room = room_service.create_room("Private".to_string(), None, RoomType::Closed, admin_id).await.unwrap();
+    room_service.add_member(room.id, wants_email, admin_id, InvolvementLevel::Member).await.unwrap();
+    room_service.add_member(room.id, opted_out, admin_id, InvolvementLevel::Member).await.unwrap();
+    
+    let sent = mailer.sent.lock().unwrap();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].to, "wants@test.com");
+    assert!(sent[0].body.contains(&format!("https://chat.example.com/rooms/{}", room.id)));
+}
+
+#[tokio::test]
+async fn test_room_retention_override_purges_on_its_own_schedule() {
+    let db = Arc::new(create_test_db().await);
+    let room_service = RoomService::new(db.clone());
+    
+    let site_admin = User {
+        id: UserId::new(),
+        name: "Site Admin".to_string(),
+        email: "site-admin@test.com".to_string(),
+        password_hash: "test_hash".to_string(),
+        bio: None,
+        admin: true,
+        bot_token: None,
+        created_at: Utc::now(),
+    };
+    db.create_user(site_admin.clone()).await.unwrap();
+    let owner = create_test_user(&db, "owner@test.com", "Owner").await;
+    
+    let inherits = room_service.create_room("Inherits".to_string(), None, RoomType::Open, owner).await.unwrap();
+    let short = room_service.create_room("Short".to_string(), None, RoomType::Open, owner).await.unwrap();
+    let held = room_service.create_room("Held".to_string(), None, RoomType::Open, owner).await.unwrap();
+    
+    // Only site admins set retention, not room admins
+    let result = room_service.set_retention(short.id, owner, Some(7)).await;
+    assert!(matches!(result, Err(RoomError::NotAuthorized { .. })));
+    room_service.set_retention(short.id, site_admin.id, Some(7)).await.unwrap();
+    room_service.set_retention(held.id, site_admin.id, Some(0)).await.unwrap();
+    assert_eq!(db.get_room_retention(short.id).await.unwrap(), Some(7));
+    assert_eq!(db.get_room_retention(inherits.id).await.unwrap(), None);
+    
+    let now = Utc::now();
+    for room_id in [inherits.id, short.id, held.id] {
+        for age_days in [10, 40] {
+            let mut message = Message::new(room_id, owner, format!("{} days old", age_days), Uuid::new_v4());
+            message.created_at = now - chrono::Duration::days(age_days);
+            db.create_message_with_deduplication(message).await.unwrap();
+        }
+    }
+    let remaining = |room_id: RoomId| {
+        let db = db.clone();
+        async move { db.get_room_messages(room_id, 10, None).await.unwrap().len() }
+    };
+    
+    // Global retention off: only the room with its own window is purged
+    assert_eq!(db.purge_expired_messages(0, now).await.unwrap(), 2);
+    assert_eq!(remaining(inherits.id).await, 2);
+    assert_eq!(remaining(short.id).await, 0);
+    assert_eq!(remaining(held.id).await, 2);
+    
+    // With a 30 day default, inheriting rooms follow it and held rooms keep everything
+    assert_eq!(db.purge_expired_messages(30, now).await.unwrap(), 1);
+    assert_eq!(remaining(inherits.id).await, 1);
+    assert_eq!(remaining(held.id).await, 2);
+}
@@ -66,7 +66,7 @@ async fn create_test_message(db: &CampfireDatabase, room_id: RoomId, user_id: Us
         sound_commands: Vec::new(),
     };
     
-    db.writer().create_message_with_deduplication(message.clone()).await.unwrap()
+    db.writer().create_message_with_deduplication(message.clone()).await.unwrap().message
 }
 
 #[tokio::test]
@@ -95,6 +95,7 @@ async fn test_search_messages_success() {
         limit: Some(10),
         offset: Some(0),
         room_id: None,
+        prefix: false,
     };
     
     let response = search_service.search_messages(user.id, request).await.unwrap();
@@ -131,6 +132,7 @@ async fn test_search_messages_authorization() {
         limit: Some(10),
         offset: Some(0),
         room_id: None,
+        prefix: false,
     };
     
     let response1 = search_service.search_messages(user1.id, request.clone()).await.unwrap();
@@ -168,6 +170,7 @@ async fn test_search_messages_room_specific() {
         limit: Some(10),
         offset: Some(0),
         room_id: Some(room1.id),
+        prefix: false,
     };
     
     let response = search_service.search_messages(user.id, request).await.unwrap();
@@ -203,6 +206,7 @@ async fn test_search_messages_pagination() {
         limit: Some(2),
         offset: Some(0),
         room_id: None,
+        prefix: false,
     };
     
     let response = search_service.search_messages(user.id, request).await.unwrap();
@@ -219,6 +223,7 @@ async fn test_search_messages_pagination() {
         limit: Some(2),
         offset: Some(2),
         room_id: None,
+        prefix: false,
     };
     
     let response2 = search_service.search_messages(user.id, request2).await.unwrap();
@@ -230,6 +235,32 @@ async fn test_search_messages_pagination() {
     assert!(response2.has_more);
 }
 
+#[tokio::test]
+async fn test_search_page_size_follows_configured_cap() {
+    let db = setup_test_db().await;
+    let room_service = Arc::new(RoomService::new(db.clone()));
+    let search_service = SearchService::new(db.clone(), room_service).with_max_page_size(200);
+    
+    let user = create_test_user(&db, "Test User", "test@example.com").await;
+    let room = create_test_room(&db, "Test Room", RoomType::Open).await;
+    create_test_membership(&db, room.id, user.id, InvolvementLevel::Member).await;
+    for i in 1..=150 {
+        create_test_message(&db, room.id, user.id, &format!("Deploy note {}", i)).await;
+    }
+    
+    let request = SearchRequest {
+        query: "deploy".to_string(),
+        limit: Some(150),
+        offset: Some(0),
+        room_id: None,
+        prefix: false,
+    };
+    let response = search_service.search_messages(user.id, request).await.unwrap();
+    
+    assert_eq!(response.results.len(), 150);
+    assert_eq!(response.limit, 150);
+}
+
 #[tokio::test]
 async fn test_search_messages_query_validation() {
     let db = setup_test_db().await;
@@ -244,6 +275,7 @@ async fn test_search_messages_query_validation() {
         limit: Some(10),
         offset: Some(0),
         room_id: None,
+        prefix: false,
     };
     
     let result = search_service.search_messages(user.id, request).await;
@@ -255,6 +287,7 @@ async fn test_search_messages_query_validation() {
         limit: Some(10),
         offset: Some(0),
         room_id: None,
+        prefix: false,
     };
     
     let result = search_service.search_messages(user.id, request).await;
@@ -266,6 +299,7 @@ async fn test_search_messages_query_validation() {
         limit: Some(10),
         offset: Some(0),
         room_id: None,
+        prefix: false,
     };
     
     let result = search_service.search_messages(user.id, request).await;
@@ -298,14 +332,16 @@ async fn test_search_messages_ranking() {
         limit: Some(10),
         offset: Some(0),
         room_id: None,
+        prefix: false,
     };
     
     let response = search_service.search_messages(user.id, request).await.unwrap();
     
     assert_eq!(response.results.len(), 2);
     
-    // Results should be ranked by relevance (more "rust" mentions = higher rank)
-    assert!(response.results[0].rank >= response.results[1].rank);
+    // Results should be ranked by relevance (more "rust" mentions first);
+    // FTS5 ranks are negative and the best match has the lowest
+    assert!(response.results[0].rank <= response.results[1].rank);
     assert!(response.results[0].message.content.contains("rust rust rust"));
 }
 
@@ -334,6 +370,7 @@ async fn test_search_messages_snippet_generation() {
         limit: Some(10),
         offset: Some(0),
         room_id: None,
+        prefix: false,
     };
     
     let response = search_service.search_messages(user.id, request).await.unwrap();
@@ -402,6 +439,7 @@ async fn test_search_no_results() {
         limit: Some(10),
         offset: Some(0),
         room_id: None,
+        prefix: false,
     };
     
     let response = search_service.search_messages(user.id, request).await.unwrap();
@@ -409,4 +447,130 @@ async fn test_search_no_results() {
     assert_eq!(response.results.len(), 0);
     assert_eq!(response.total_count, 0);
     assert!(!response.has_more);
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_prefix_search_matches_word_start() {
+    let db = setup_test_db().await;
+    let room_service = Arc::new(RoomService::new(db.clone()));
+    let search_service = SearchService::new(db.clone(), room_service);
+    
+    let user = create_test_user(&db, "Test User", "test@example.com").await;
+    let room = create_test_room(&db, "Test Room", RoomType::Open).await;
+    create_test_membership(&db, room.id, user.id, InvolvementLevel::Member).await;
+    
+    create_test_message(&db, room.id, user.id, "The authentication flow is broken").await;
+    create_test_message(&db, room.id, user.id, "Lunch at noon").await;
+    
+    let request = SearchRequest {
+        query: "auth".to_string(),
+        limit: Some(50),
+        offset: Some(0),
+        room_id: Some(room.id),
+        prefix: true,
+    };
+    
+    // Without prefix mode "auth" is a whole word and matches nothing
+    let exact = search_service
+        .search_messages(user.id, SearchRequest { prefix: false, ..request.clone() })
+        .await
+        .unwrap();
+    assert_eq!(exact.results.len(), 0);
+    
+    let response = search_service.search_messages(user.id, request.clone()).await.unwrap();
+    assert_eq!(response.results.len(), 1);
+    assert!(response.results[0].message.content.contains("authentication"));
+    assert_eq!(response.limit, 10);
+    
+    // A trailing "*" typed by the user is harmless
+    let starred = SearchRequest { query: "auth*".to_string(), ..request.clone() };
+    assert_eq!(search_service.search_messages(user.id, starred).await.unwrap().results.len(), 1);
+    
+    // Too-short prefixes would match nearly everything
+    let short = SearchRequest { query: "au".to_string(), ..request.clone() };
+    assert!(matches!(
+        search_service.search_messages(user.id, short).await,
+        Err(SearchError::PrefixTooShort { .. })
+    ));
+    
+    // Prefix mode is room-scoped only
+    let unscoped = SearchRequest { room_id: None, ..request };
+    assert!(matches!(
+        search_service.search_messages(user.id, unscoped).await,
+        Err(SearchError::InvalidQuery { .. })
+    ));
+}
+
+fn room_search(room_id: RoomId, query: &str) -> SearchRequest {
+    SearchRequest {
+        query: query.to_string(),
+        limit: Some(10),
+        offset: Some(0),
+        room_id: Some(room_id),
+        prefix: false,
+    }
+}
+
+#[tokio::test]
+async fn test_deleted_message_disappears_from_search() {
+    let db = setup_test_db().await;
+    let room_service = Arc::new(RoomService::new(db.clone()));
+    let search_service = SearchService::new(db.clone(), room_service);
+    
+    let user = create_test_user(&db, "Test User", "test@example.com").await;
+    let room = create_test_room(&db, "Test Room", RoomType::Open).await;
+    create_test_membership(&db, room.id, user.id, InvolvementLevel::Member).await;
+    
+    let doomed = create_test_message(&db, room.id, user.id, "quarterly numbers leaked").await;
+    create_test_message(&db, room.id, user.id, "quarterly planning").await;
+    
+    // Simulate a lagging trigger: the index row outlives the message
+    sqlx::query("DROP TRIGGER messages_fts_delete").execute(db.pool()).await.unwrap();
+    sqlx::query("DELETE FROM messages WHERE id = ?")
+        .bind(doomed.id.0.to_string())
+        .execute(db.pool())
+        .await
+        .unwrap();
+    
+    let response = search_service.search_messages(user.id, room_search(room.id, "leaked")).await.unwrap();
+    assert!(response.results.is_empty());
+    assert_eq!(response.total_count, 0);
+    
+    let response = search_service.search_messages(user.id, room_search(room.id, "quarterly")).await.unwrap();
+    assert_eq!(response.results.len(), 1);
+    assert_eq!(response.results[0].message.content, "quarterly planning");
+}
+
+#[tokio::test]
+async fn test_edited_message_matches_current_content() {
+    let db = setup_test_db().await;
+    let room_service = Arc::new(RoomService::new(db.clone()));
+    let search_service = SearchService::new(db.clone(), room_service);
+    
+    let user = create_test_user(&db, "Test User", "test@example.com").await;
+    let room = create_test_room(&db, "Test Room", RoomType::Open).await;
+    create_test_membership(&db, room.id, user.id, InvolvementLevel::Member).await;
+    
+    let message = create_test_message(&db, room.id, user.id, "meeting on tuesday").await;
+    sqlx::query("UPDATE messages SET content = ? WHERE id = ?")
+        .bind("meeting on wednesday")
+        .bind(message.id.0.to_string())
+        .execute(db.pool())
+        .await
+        .unwrap();
+    
+    let response = search_service.search_messages(user.id, room_search(room.id, "wednesday")).await.unwrap();
+    assert_eq!(response.results.len(), 1);
+    assert_eq!(response.results[0].message.content, "meeting on wednesday");
+    assert!(search_service.search_messages(user.id, room_search(room.id, "tuesday")).await.unwrap().results.is_empty());
+    
+    // A stale index row left behind by a lagging trigger doesn't match either
+    sqlx::query("INSERT INTO messages_fts (message_id, content) VALUES (?, ?)")
+        .bind(message.id.0.to_string())
+        .bind("meeting on tuesday")
+        .execute(db.pool())
+        .await
+        .unwrap();
+    let response = search_service.search_messages(user.id, room_search(room.id, "tuesday")).await.unwrap();
+    assert!(response.results.is_empty());
+    assert_eq!(response.total_count, 0);
+}
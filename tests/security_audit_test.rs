@@ -254,8 +254,10 @@ async fn test_rate_limiting_security() {
     let config = RateLimitConfig {
         general_rpm: 5,
         auth_rpm: 2,
+        search_rpm: 10,
         bot_rpm: 10,
         burst_size: 2,
+        headers: true,
     };
     
     let middleware = RateLimitingMiddleware::new(config);
@@ -21,8 +21,10 @@ async fn test_rate_limiting_general_api() {
     let config = RateLimitConfig {
         general_rpm: 2, // Very low for testing
         auth_rpm: 1,
+        search_rpm: 10,
         bot_rpm: 3,
         burst_size: 1,
+        headers: true,
     };
     
     let middleware = RateLimitingMiddleware::new(config);
@@ -50,8 +52,10 @@ async fn test_rate_limiting_auth_endpoints() {
     let config = RateLimitConfig {
         general_rpm: 10,
         auth_rpm: 1, // Very strict for testing
+        search_rpm: 10,
         bot_rpm: 10,
         burst_size: 1,
+        headers: true,
     };
     
     let middleware = RateLimitingMiddleware::new(config);
@@ -76,8 +80,10 @@ async fn test_bot_api_rate_limiting() {
     let config = RateLimitConfig {
         general_rpm: 10,
         auth_rpm: 5,
+        search_rpm: 10,
         bot_rpm: 2, // Low for testing
         burst_size: 1,
+        headers: true,
     };
     
     let middleware = RateLimitingMiddleware::new(config);
@@ -82,6 +82,7 @@ async fn test_basic_search_functionality() {
         limit: Some(10),
         offset: Some(0),
         room_id: None,
+        prefix: false,
     };
     
     let response = search_service.search_messages(user.id, request).await.unwrap();
@@ -121,6 +122,7 @@ async fn test_search_validation() {
         limit: Some(10),
         offset: Some(0),
         room_id: None,
+        prefix: false,
     };
     
     let result = search_service.search_messages(user.id, request).await;
@@ -132,6 +134,7 @@ async fn test_search_validation() {
         limit: Some(10),
         offset: Some(0),
         room_id: None,
+        prefix: false,
     };
     
     let result = search_service.search_messages(user.id, request).await;
@@ -69,6 +69,7 @@ async fn create_full_test_app() -> (Router, Arc<CampfireDatabase>) {
         setup_service,
         demo_service,
         analytics_store,
+        ..AppState::for_tests(db.clone())
     };
 
     let app = Router::new()